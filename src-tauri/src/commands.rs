@@ -8,9 +8,10 @@ use tauri::{Emitter, Manager, State};
 use tokio::io::{AsyncBufReadExt, AsyncReadExt};
 
 use crate::model::error::ModelError;
-use crate::model::inspect::{self, InspectData};
-use crate::model::state::AppState;
+use crate::model::inspect::{self, InspectData, InspectTensor, KvCacheBreakdown, ParamBreakdown};
+use crate::model::state::{AppState, ChatMessage, ChatSession, GenerationSettings, LlamaServer};
 use crate::model::{ModelFormat, ModelInfo};
+use crate::training::config::DatasetFormat;
 
 fn detect_format(path: &std::path::Path) -> Result<ModelFormat, ModelError> {
     match path
@@ -21,6 +22,7 @@ fn detect_format(path: &std::path::Path) -> Result<ModelFormat, ModelError> {
     {
         Some("safetensors") => Ok(ModelFormat::SafeTensors),
         Some("gguf") => Ok(ModelFormat::Gguf),
+        Some("bin") | Some("pt") | Some("pth") => Ok(ModelFormat::PyTorch),
         Some(ext) => Err(ModelError::UnsupportedFormat(ext.to_string())),
         None => Err(ModelError::UnsupportedFormat("no extension".to_string())),
     }
@@ -43,6 +45,7 @@ pub fn load_model(path: String, state: State<'_, AppState>) -> Result<ModelInfo,
     let info = match format {
         ModelFormat::SafeTensors => crate::model::safetensors::parse(&path)?,
         ModelFormat::Gguf => crate::model::gguf::parse(&path)?,
+        ModelFormat::PyTorch => crate::model::pytorch::parse(&path)?,
     };
 
     let mut loaded = state.loaded_model.lock().unwrap();
@@ -76,6 +79,92 @@ pub fn load_model_dir(path: String, state: State<'_, AppState>) -> Result<ModelI
     Ok(info)
 }
 
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LoadTimeEstimate {
+    pub estimated_seconds: f64,
+    pub file_size: u64,
+    pub file_size_display: String,
+    pub header_parse_ms: f64,
+    pub read_throughput_mb_s: f64,
+}
+
+/// Disk throughput assumed when the read sample is too small to time reliably.
+const FALLBACK_READ_MB_S: f64 = 500.0;
+/// How much of the largest file to read when benchmarking sequential throughput.
+const READ_SAMPLE_BYTES: usize = 32 * 1024 * 1024;
+
+/// Estimate how long a full load of a model file or shard directory will take:
+/// time the header parse, then extrapolate data-read time from a short
+/// sequential-read benchmark of the largest file.
+#[tauri::command]
+pub async fn estimate_load_time(path: String) -> Result<LoadTimeEstimate, ModelError> {
+    let path = PathBuf::from(&path);
+    if !path.exists() {
+        return Err(ModelError::FileNotFound(path.to_string_lossy().to_string()));
+    }
+
+    tauri::async_runtime::spawn_blocking(move || {
+        let parse_start = std::time::Instant::now();
+        let files: Vec<PathBuf> = if path.is_dir() {
+            crate::model::safetensors::parse_dir(&path)?;
+            std::fs::read_dir(&path)?
+                .flatten()
+                .map(|e| e.path())
+                .filter(|p| p.extension().and_then(|e| e.to_str()) == Some("safetensors"))
+                .collect()
+        } else {
+            match detect_format(&path)? {
+                ModelFormat::SafeTensors => crate::model::safetensors::parse(&path)?,
+                ModelFormat::Gguf => crate::model::gguf::parse(&path)?,
+                ModelFormat::PyTorch => crate::model::pytorch::parse(&path)?,
+            };
+            vec![path.clone()]
+        };
+        let header_parse_ms = parse_start.elapsed().as_secs_f64() * 1000.0;
+
+        let sizes: Vec<(u64, &PathBuf)> = files
+            .iter()
+            .map(|f| (std::fs::metadata(f).map(|m| m.len()).unwrap_or(0), f))
+            .collect();
+        let file_size: u64 = sizes.iter().map(|(s, _)| s).sum();
+
+        let mut read_throughput_mb_s = FALLBACK_READ_MB_S;
+        if let Some((_, largest)) = sizes.iter().max_by_key(|(s, _)| *s) {
+            let mut file = std::fs::File::open(largest)?;
+            let mut buffer = vec![0u8; 1024 * 1024];
+            let mut read_total = 0usize;
+            let read_start = std::time::Instant::now();
+            while read_total < READ_SAMPLE_BYTES {
+                let n = file.read(&mut buffer)?;
+                if n == 0 {
+                    break;
+                }
+                read_total += n;
+            }
+            let secs = read_start.elapsed().as_secs_f64();
+            if read_total >= 4 * 1024 * 1024 && secs > 0.0 {
+                read_throughput_mb_s = read_total as f64 / (1024.0 * 1024.0) / secs;
+            }
+        }
+
+        let read_secs = file_size as f64 / (1024.0 * 1024.0) / read_throughput_mb_s;
+        let estimated_seconds = header_parse_ms / 1000.0 + read_secs;
+
+        Ok::<_, ModelError>(LoadTimeEstimate {
+            estimated_seconds: (estimated_seconds * 10.0).round() / 10.0,
+            file_size,
+            file_size_display: crate::model::format_file_size(file_size),
+            header_parse_ms: (header_parse_ms * 10.0).round() / 10.0,
+            read_throughput_mb_s: read_throughput_mb_s.round(),
+        })
+    })
+    .await
+    .map_err(|e| ModelError::ParseError {
+        format: "inspect".into(),
+        reason: format!("Task failed: {}", e),
+    })?
+}
+
 #[tauri::command]
 pub fn get_loaded_model(state: State<'_, AppState>) -> Option<ModelInfo> {
     state.loaded_model.lock().unwrap().clone()
@@ -98,19 +187,151 @@ pub fn inspect_model(state: State<'_, AppState>) -> Result<InspectData, ModelErr
     Ok(inspect::analyze(&info.all_tensors, &info.metadata))
 }
 
+#[tauri::command]
+pub fn inspect_param_breakdown(state: State<'_, AppState>) -> Result<ParamBreakdown, ModelError> {
+    let loaded = state.loaded_model.lock().unwrap();
+    let info = loaded.as_ref().ok_or_else(|| ModelError::ParseError {
+        format: "inspect".into(),
+        reason: "No model loaded".into(),
+    })?;
+
+    let data = inspect::analyze(&info.all_tensors, &info.metadata);
+    Ok(inspect::param_breakdown(&data))
+}
+
+/// Per-layer KV-cache size for a given context, from the detected attention layout.
+#[tauri::command]
+pub fn kv_cache_breakdown(
+    context_length: u64,
+    state: State<'_, AppState>,
+) -> Result<KvCacheBreakdown, ModelError> {
+    let loaded = state.loaded_model.lock().unwrap();
+    let info = loaded.as_ref().ok_or_else(|| ModelError::ParseError {
+        format: "inspect".into(),
+        reason: "No model loaded".into(),
+    })?;
+
+    let data = inspect::analyze(&info.all_tensors, &info.metadata);
+    inspect::kv_cache_breakdown(&data, context_length).ok_or_else(|| ModelError::ParseError {
+        format: "inspect".into(),
+        reason: "Could not determine KV heads / head dimension for this model".into(),
+    })
+}
+
+/// List tensors of a single dtype, e.g. which tensors stayed F16 in a Q4_K_M file.
+#[tauri::command]
+pub fn inspect_tensors_by_dtype(
+    dtype: String,
+    state: State<'_, AppState>,
+) -> Result<Vec<InspectTensor>, ModelError> {
+    let loaded = state.loaded_model.lock().unwrap();
+    let info = loaded.as_ref().ok_or_else(|| ModelError::ParseError {
+        format: "inspect".into(),
+        reason: "No model loaded".into(),
+    })?;
+
+    let data = inspect::analyze(&info.all_tensors, &info.metadata);
+    Ok(inspect::tensors_by_dtype(&data, &dtype))
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TensorNamePage {
+    pub names: Vec<String>,
+    /// Number of tensors matching the filter (before pagination).
+    pub total: usize,
+    pub offset: usize,
+    pub limit: usize,
+}
+
+/// Case-insensitive match: `*`/`?` wildcards make it a glob, otherwise a substring test.
+fn tensor_name_matches(name: &str, filter: &str) -> bool {
+    let name = name.to_lowercase();
+    let filter = filter.to_lowercase();
+    if !filter.contains('*') && !filter.contains('?') {
+        return name.contains(&filter);
+    }
+
+    let n: Vec<char> = name.chars().collect();
+    let p: Vec<char> = filter.chars().collect();
+    let (mut ni, mut pi) = (0usize, 0usize);
+    let mut star: Option<(usize, usize)> = None;
+    while ni < n.len() {
+        if pi < p.len() && (p[pi] == '?' || p[pi] == n[ni]) {
+            ni += 1;
+            pi += 1;
+        } else if pi < p.len() && p[pi] == '*' {
+            star = Some((pi, ni));
+            pi += 1;
+        } else if let Some((sp, sn)) = star {
+            pi = sp + 1;
+            ni = sn + 1;
+            star = Some((sp, sn + 1));
+        } else {
+            return false;
+        }
+    }
+    p[pi..].iter().all(|&c| c == '*')
+}
+
+/// Page through the loaded model's tensor names, optionally filtered.
+#[tauri::command]
+pub fn list_tensor_names(
+    offset: usize,
+    limit: usize,
+    filter: Option<String>,
+    state: State<'_, AppState>,
+) -> Result<TensorNamePage, ModelError> {
+    let loaded = state.loaded_model.lock().unwrap();
+    let info = loaded.as_ref().ok_or_else(|| ModelError::ParseError {
+        format: "inspect".into(),
+        reason: "No model loaded".into(),
+    })?;
+
+    let filter = filter.as_deref().map(str::trim).filter(|f| !f.is_empty());
+    let matching: Vec<&str> = info
+        .all_tensors
+        .iter()
+        .map(|t| t.name.as_str())
+        .filter(|name| filter.map_or(true, |f| tensor_name_matches(name, f)))
+        .collect();
+
+    let names = matching
+        .iter()
+        .skip(offset)
+        .take(limit)
+        .map(|n| n.to_string())
+        .collect();
+
+    Ok(TensorNamePage {
+        names,
+        total: matching.len(),
+        offset,
+        limit,
+    })
+}
+
 #[tauri::command]
 pub fn inspect_capabilities(
+    app: tauri::AppHandle,
     state: State<'_, AppState>,
 ) -> Result<crate::merge::capabilities::CapabilityReport, ModelError> {
+    let rules = user_capability_rules(&app)?;
     let loaded = state.loaded_model.lock().unwrap();
     let info = loaded.as_ref().ok_or_else(|| ModelError::ParseError {
         format: "inspect".into(),
         reason: "No model loaded".into(),
     })?;
 
-    // Build a temporary ParentModel from the loaded ModelInfo
+    let parent = loaded_as_parent(info);
+    let gguf_metadata = crate::merge::capabilities::load_gguf_metadata(&parent);
+    Ok(crate::merge::capabilities::detect_capabilities(&parent, gguf_metadata.as_ref(), &rules))
+}
+
+/// Build a temporary ParentModel from the loaded ModelInfo so merge-side
+/// helpers (capability detection, tensor loading) can be reused.
+fn loaded_as_parent(info: &ModelInfo) -> crate::merge::registry::ParentModel {
     let compat = crate::merge::registry::CompatInfo::from_model_info(info);
-    let parent = crate::merge::registry::ParentModel {
+    crate::merge::registry::ParentModel {
         id: "inspect".into(),
         slot: 0,
         name: info.file_name.clone(),
@@ -125,23 +346,192 @@ pub fn inspect_capabilities(
         quantization: info.quantization.clone(),
         compat,
         color: "#f59e0b".into(),
-        is_dir: info.shard_count.map_or(false, |s| s > 0),
+        is_dir: std::path::Path::new(&info.file_path).is_dir(),
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExportedTensor {
+    pub output_path: String,
+    pub shape: Vec<usize>,
+    pub file_size: u64,
+    pub file_size_display: String,
+}
+
+/// Extract one tensor from the loaded model (dequantized to F32) and save it as
+/// a single-tensor `.safetensors` file or a NumPy `.npy` file.
+#[tauri::command]
+pub async fn export_tensor(
+    tensor_name: String,
+    output_path: String,
+    format: String,
+    state: State<'_, AppState>,
+) -> Result<ExportedTensor, ModelError> {
+    let parent = {
+        let loaded = state.loaded_model.lock().unwrap();
+        let info = loaded.as_ref().ok_or_else(|| ModelError::ParseError {
+            format: "inspect".into(),
+            reason: "No model loaded".into(),
+        })?;
+        if !info.all_tensors.iter().any(|t| t.name == tensor_name) {
+            return Err(ModelError::TensorNotFound {
+                tensor_name,
+                parent_id: info.file_name.clone(),
+            });
+        }
+        loaded_as_parent(info)
     };
 
-    Ok(crate::merge::capabilities::detect_capabilities(&parent))
+    let format = format.to_lowercase();
+    if format != "safetensors" && format != "npy" {
+        return Err(ModelError::ParseError {
+            format: "inspect".into(),
+            reason: format!("Invalid export format: {}. Use: safetensors, npy", format),
+        });
+    }
+
+    let out = PathBuf::from(&output_path);
+    if let Some(dir) = out.parent() {
+        if !dir.as_os_str().is_empty() && !dir.exists() {
+            return Err(ModelError::FileNotFound(dir.to_string_lossy().to_string()));
+        }
+    }
+
+    tauri::async_runtime::spawn_blocking(move || {
+        let tensor = crate::merge::tensor_io::load_tensor(&parent, &tensor_name)?;
+        let shape = tensor.shape().dims().to_vec();
+
+        if format == "npy" {
+            crate::merge::output::write_npy(&output_path, &tensor)?;
+        } else {
+            crate::merge::output::write_safetensors(
+                &output_path,
+                &[(tensor_name, tensor)],
+                crate::merge::config::OutputDtype::F32,
+            )?;
+        }
+
+        let file_size = std::fs::metadata(&output_path).map(|m| m.len()).unwrap_or(0);
+        Ok(ExportedTensor {
+            output_path,
+            shape,
+            file_size,
+            file_size_display: crate::model::format_file_size(file_size),
+        })
+    })
+    .await
+    .map_err(|e| ModelError::ParseError {
+        format: "inspect".into(),
+        reason: format!("Tensor export failed: {}", e),
+    })?
 }
 
 // ── Fingerprint ────────────────────────────────────────
 
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FileFingerprint {
+    pub file_name: String,
+    pub sha256: String,
+    pub size_bytes: u64,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ModelFingerprint {
+    /// Hash of the file itself, or for multi-file models a digest over the
+    /// sorted `name:sha256` lines of every entry in `files`.
     pub sha256: String,
     pub file_size_bytes: u64,
     pub tensor_count_verified: bool,
+    pub files: Vec<FileFingerprint>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct FingerprintProgress {
+    pub file_name: String,
+    pub bytes_hashed: u64,
+    pub total_bytes: u64,
+    pub percent: f64,
+}
+
+/// Emit `fingerprint:progress` roughly every 64 MB.
+const FINGERPRINT_PROGRESS_BYTES: u64 = 64 * 1024 * 1024;
+
+/// Files that make up a model: every shard plus config/tokenizer for a
+/// SafeTensors folder, every shard of a split GGUF, else the file itself.
+fn fingerprint_files(path: &std::path::Path) -> Result<Vec<PathBuf>, ModelError> {
+    let mut files: Vec<PathBuf> = if path.is_dir() {
+        std::fs::read_dir(path)?
+            .flatten()
+            .map(|e| e.path())
+            .filter(|p| {
+                let name = p.file_name().and_then(|n| n.to_str()).unwrap_or("");
+                name.to_lowercase().ends_with(".safetensors")
+                    || name == "config.json"
+                    || name == "tokenizer.json"
+            })
+            .collect()
+    } else {
+        crate::model::gguf::split_shard_paths(path)?
+    };
+    files.sort_by_key(|p| p.file_name().map(|n| n.to_os_string()));
+    Ok(files)
+}
+
+/// Hex SHA256 and size of a file, hashed in 1 MB chunks.
+fn sha256_file(path: &std::path::Path) -> Result<(String, u64), std::io::Error> {
+    let mut file = std::fs::File::open(path)?;
+    let file_size = file.metadata()?.len();
+
+    let mut hasher = Sha256::new();
+    let mut buffer = vec![0u8; 1024 * 1024];
+    loop {
+        let n = file.read(&mut buffer)?;
+        if n == 0 {
+            break;
+        }
+        hasher.update(&buffer[..n]);
+    }
+    Ok((format!("{:x}", hasher.finalize()), file_size))
+}
+
+/// Like `sha256_file`, but checks `cancel` between chunks and reports progress.
+/// `hashed` carries the running byte count across a model's files.
+fn sha256_file_cancellable(
+    path: &std::path::Path,
+    cancel: &std::sync::atomic::AtomicBool,
+    hashed: &mut u64,
+    on_progress: &mut dyn FnMut(&str, u64),
+) -> Result<(String, u64), ModelError> {
+    let file_name = path.file_name().unwrap_or_default().to_string_lossy().to_string();
+    let mut file = std::fs::File::open(path)?;
+    let file_size = file.metadata()?.len();
+
+    let mut hasher = Sha256::new();
+    let mut buffer = vec![0u8; 1024 * 1024];
+    let mut since_report = 0u64;
+    loop {
+        if cancel.load(std::sync::atomic::Ordering::Relaxed) {
+            return Err(ModelError::Cancelled("Fingerprint".into()));
+        }
+        let n = file.read(&mut buffer)?;
+        if n == 0 {
+            break;
+        }
+        hasher.update(&buffer[..n]);
+        *hashed += n as u64;
+        since_report += n as u64;
+        if since_report >= FINGERPRINT_PROGRESS_BYTES {
+            since_report = 0;
+            on_progress(&file_name, *hashed);
+        }
+    }
+    on_progress(&file_name, *hashed);
+    Ok((format!("{:x}", hasher.finalize()), file_size))
 }
 
 #[tauri::command]
 pub async fn compute_fingerprint(
+    app: tauri::AppHandle,
     state: State<'_, AppState>,
 ) -> Result<ModelFingerprint, ModelError> {
     let file_path = {
@@ -153,45 +543,94 @@ pub async fn compute_fingerprint(
         info.file_path.clone()
     };
 
-    let result = tauri::async_runtime::spawn_blocking(move || {
-        let mut file = std::fs::File::open(&file_path)?;
-        let file_size = file.metadata()?.len();
+    let cancel = state.fingerprint_cancel.clone();
+    cancel.store(false, std::sync::atomic::Ordering::Relaxed);
 
-        let mut hasher = Sha256::new();
-        let mut buffer = vec![0u8; 1024 * 1024];
-        loop {
-            let n = file.read(&mut buffer)?;
-            if n == 0 {
-                break;
-            }
-            hasher.update(&buffer[..n]);
-        }
-        let hash = format!("{:x}", hasher.finalize());
+    let files = tauri::async_runtime::spawn_blocking(move || -> Result<Vec<FileFingerprint>, ModelError> {
+        let paths = fingerprint_files(std::path::Path::new(&file_path))?;
+        let total_bytes: u64 = paths
+            .iter()
+            .map(|p| std::fs::metadata(p).map(|m| m.len()).unwrap_or(0))
+            .sum();
+        let mut hashed = 0u64;
+        let mut on_progress = |file_name: &str, bytes_hashed: u64| {
+            let _ = app.emit(
+                "fingerprint:progress",
+                FingerprintProgress {
+                    file_name: file_name.to_string(),
+                    bytes_hashed,
+                    total_bytes,
+                    percent: if total_bytes > 0 { bytes_hashed as f64 / total_bytes as f64 * 100.0 } else { 100.0 },
+                },
+            );
+        };
 
-        Ok::<_, std::io::Error>((hash, file_size))
+        paths
+            .iter()
+            .map(|p| {
+                let (sha256, size_bytes) = sha256_file_cancellable(p, &cancel, &mut hashed, &mut on_progress)?;
+                Ok(FileFingerprint {
+                    file_name: p.file_name().unwrap_or_default().to_string_lossy().to_string(),
+                    sha256,
+                    size_bytes,
+                })
+            })
+            .collect()
     })
     .await
     .map_err(|e| ModelError::ParseError {
         format: "fingerprint".into(),
         reason: format!("Task failed: {}", e),
-    })?
-    .map_err(ModelError::IoError)?;
+    })??;
+
+    let sha256 = match files.as_slice() {
+        [single] => single.sha256.clone(),
+        _ => {
+            let mut hasher = Sha256::new();
+            for f in &files {
+                hasher.update(format!("{}:{}\n", f.file_name, f.sha256).as_bytes());
+            }
+            format!("{:x}", hasher.finalize())
+        }
+    };
 
     Ok(ModelFingerprint {
-        sha256: result.0,
-        file_size_bytes: result.1,
+        sha256,
+        file_size_bytes: files.iter().map(|f| f.size_bytes).sum(),
         tensor_count_verified: true,
+        files,
     })
 }
 
+#[tauri::command]
+pub fn fingerprint_cancel(state: State<'_, AppState>) {
+    state.fingerprint_cancel.store(true, std::sync::atomic::Ordering::Relaxed);
+}
+
 // ── GPU Detection ──────────────────────────────────────
 
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GpuDevice {
+    pub index: u32,
+    pub name: String,
+    pub vram_mb: u64,
+    pub vram: String,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct GpuInfo {
     pub has_nvidia: bool,
+    /// Primary (first) device; every device is listed in `gpus`.
     pub nvidia_name: Option<String>,
     pub nvidia_vram: Option<String>,
+    pub gpus: Vec<GpuDevice>,
     pub cuda_version: Option<String>,
+    /// Set when NVIDIA hardware appears present but the driver is unusable.
+    pub driver_issue: Option<String>,
+    pub has_rocm: bool,
+    pub rocm_name: Option<String>,
+    pub rocm_vram: Option<String>,
+    pub rocm_version: Option<String>,
     pub has_vulkan: bool,
     pub has_metal: bool,
     pub recommended_variant: String,
@@ -199,19 +638,42 @@ pub struct GpuInfo {
     pub arch: String,
 }
 
-fn probe_nvidia() -> (bool, Option<String>, Option<String>, Option<String>) {
+/// Parse `nvidia-smi --query-gpu=index,name,memory.total --format=csv,noheader,nounits`,
+/// one device per row.
+fn parse_nvidia_smi_devices(stdout: &str) -> Vec<GpuDevice> {
+    stdout
+        .lines()
+        .filter_map(|line| {
+            let parts: Vec<&str> = line.split(',').map(|s| s.trim()).collect();
+            if parts.len() < 3 {
+                return None;
+            }
+            let index = parts[0].parse().ok()?;
+            // Names never contain commas in practice, but keep everything between index and VRAM
+            let name = parts[1..parts.len() - 1].join(", ");
+            let vram_mb = parts[parts.len() - 1].parse().unwrap_or(0);
+            Some(GpuDevice {
+                index,
+                name,
+                vram_mb,
+                vram: format!("{} MB", vram_mb),
+            })
+        })
+        .collect()
+}
+
+type NvidiaProbe = (bool, Vec<GpuDevice>, Option<String>, Option<String>);
+
+fn probe_nvidia() -> NvidiaProbe {
     let output = std::process::Command::new("nvidia-smi")
-        .arg("--query-gpu=name,memory.total")
+        .arg("--query-gpu=index,name,memory.total")
         .arg("--format=csv,noheader,nounits")
         .output();
 
     match output {
         Ok(o) if o.status.success() => {
             let stdout = String::from_utf8_lossy(&o.stdout);
-            let line = stdout.lines().next().unwrap_or("").trim().to_string();
-            let parts: Vec<&str> = line.split(", ").collect();
-            let name = parts.first().map(|s| s.trim().to_string());
-            let vram = parts.get(1).map(|s| format!("{} MB", s.trim()));
+            let devices = parse_nvidia_smi_devices(&stdout);
 
             // Parse CUDA version from nvidia-smi header output
             let cuda_ver = std::process::Command::new("nvidia-smi")
@@ -228,10 +690,112 @@ fn probe_nvidia() -> (bool, Option<String>, Option<String>, Option<String>) {
                         })
                 });
 
-            (true, name, vram, cuda_ver)
+            (true, devices, cuda_ver, None)
+        }
+        Ok(o) => {
+            // nvidia-smi is installed but cannot talk to the driver
+            let stderr = String::from_utf8_lossy(&o.stderr);
+            let stdout = String::from_utf8_lossy(&o.stdout);
+            let msg = stderr
+                .lines()
+                .chain(stdout.lines())
+                .map(|l| l.trim())
+                .find(|l| !l.is_empty())
+                .unwrap_or("nvidia-smi exited with an error")
+                .to_string();
+            (false, vec![], None, Some(format!("NVIDIA driver error: {}", msg)))
+        }
+        Err(_) => {
+            let issue = if has_pci_display_device("0x10de") {
+                Some("NVIDIA GPU found but nvidia-smi is missing. Install the NVIDIA driver.".to_string())
+            } else {
+                None
+            };
+            (false, vec![], None, issue)
+        }
+    }
+}
+
+/// Check the PCI bus for a display controller (class 0x03xxxx) from `vendor`
+/// (0x10de for NVIDIA, 0x1002 for AMD).
+#[cfg(target_os = "linux")]
+fn has_pci_display_device(vendor_id: &str) -> bool {
+    let Ok(entries) = std::fs::read_dir("/sys/bus/pci/devices") else {
+        return false;
+    };
+    entries.flatten().any(|entry| {
+        let path = entry.path();
+        let vendor = std::fs::read_to_string(path.join("vendor")).unwrap_or_default();
+        let class = std::fs::read_to_string(path.join("class")).unwrap_or_default();
+        vendor.trim().eq_ignore_ascii_case(vendor_id) && class.trim().starts_with("0x03")
+    })
+}
+
+#[cfg(not(target_os = "linux"))]
+fn has_pci_display_device(_vendor_id: &str) -> bool {
+    false
+}
+
+/// Probe for an AMD GPU usable through ROCm/HIP: (found, name, vram, rocm version).
+fn probe_rocm() -> (bool, Option<String>, Option<String>, Option<String>) {
+    let mut name = None;
+    let mut vram = None;
+
+    // rocm-smi --json: {"card0": {"Card series": "...", "VRAM Total Memory (B)": "..."}}
+    if let Ok(o) = std::process::Command::new("rocm-smi")
+        .args(["--showproductname", "--showmeminfo", "vram", "--json"])
+        .output()
+    {
+        if o.status.success() {
+            let json: Option<serde_json::Value> = serde_json::from_slice(&o.stdout).ok();
+            if let Some(card) = json.as_ref().and_then(|j| j.as_object()).and_then(|m| {
+                m.iter().find(|(k, _)| k.starts_with("card")).map(|(_, v)| v)
+            }) {
+                name = ["Card Series", "Card series", "Card SKU", "Card model"]
+                    .iter()
+                    .find_map(|k| card.get(*k).and_then(|v| v.as_str()))
+                    .map(|s| s.trim().to_string())
+                    .filter(|s| !s.is_empty());
+                vram = card
+                    .get("VRAM Total Memory (B)")
+                    .and_then(|v| v.as_str())
+                    .and_then(|s| s.trim().parse::<u64>().ok())
+                    .map(|b| format!("{} MB", b / (1024 * 1024)));
+            }
+        }
+    }
+
+    // rocminfo lists every agent; GPU agents have a gfx* Name followed by a Marketing Name.
+    if name.is_none() {
+        if let Ok(o) = std::process::Command::new("rocminfo").output() {
+            if o.status.success() {
+                let stdout = String::from_utf8_lossy(&o.stdout);
+                let mut last_name = String::new();
+                for line in stdout.lines().map(|l| l.trim()) {
+                    if let Some(v) = line.strip_prefix("Name:") {
+                        last_name = v.trim().to_string();
+                    } else if let Some(v) = line.strip_prefix("Marketing Name:") {
+                        if last_name.starts_with("gfx") {
+                            name = Some(v.trim().to_string());
+                            break;
+                        }
+                    }
+                }
+            }
         }
-        _ => (false, None, None, None),
     }
+
+    let rocm_root = std::path::Path::new("/opt/rocm");
+    let version = std::fs::read_to_string(rocm_root.join(".info/version"))
+        .ok()
+        .map(|s| s.trim().to_string())
+        .filter(|s| !s.is_empty());
+
+    let found = name.is_some() || (rocm_root.exists() && has_pci_display_device("0x1002"));
+    if !found {
+        return (false, None, None, None);
+    }
+    (true, name, vram, version)
 }
 
 fn probe_vulkan() -> bool {
@@ -262,12 +826,17 @@ pub fn detect_gpu() -> GpuInfo {
     let os = std::env::consts::OS.to_string();
     let arch = std::env::consts::ARCH.to_string();
 
-    let (has_nvidia, nvidia_name, nvidia_vram, cuda_version) = probe_nvidia();
+    let (has_nvidia, gpus, cuda_version, driver_issue) = probe_nvidia();
+    let nvidia_name = gpus.first().map(|d| d.name.clone());
+    let nvidia_vram = gpus.first().map(|d| d.vram.clone());
+    let (has_rocm, rocm_name, rocm_vram, rocm_version) = probe_rocm();
     let has_vulkan = probe_vulkan();
     let has_metal = cfg!(target_os = "macos");
 
     let recommended_variant = if has_nvidia {
         "cuda".to_string()
+    } else if has_rocm {
+        "rocm".to_string()
     } else if has_metal {
         "cpu".to_string() // macOS builds include Metal by default
     } else if has_vulkan {
@@ -280,7 +849,13 @@ pub fn detect_gpu() -> GpuInfo {
         has_nvidia,
         nvidia_name,
         nvidia_vram,
+        gpus,
         cuda_version,
+        driver_issue,
+        has_rocm,
+        rocm_name,
+        rocm_vram,
+        rocm_version,
         has_vulkan,
         has_metal,
         recommended_variant,
@@ -304,6 +879,9 @@ struct ToolsManifest {
     version: String,
     variant: String,
     asset_name: String,
+    /// Hex SHA256 of the downloaded archive (absent in older manifests).
+    #[serde(default)]
+    sha256: Option<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -312,14 +890,14 @@ pub struct DownloadResult {
     pub version: String,
     pub variant: String,
     pub path: String,
+    /// Hex SHA256 of the downloaded archive.
+    pub sha256: String,
+    /// Version string the extracted binary reported for `--version`.
+    pub binary_version: String,
 }
 
 fn get_tools_dir(app: &tauri::AppHandle) -> Result<PathBuf, ModelError> {
-    let data_dir = app.path().app_data_dir().map_err(|e| ModelError::ParseError {
-        format: "tools".into(),
-        reason: format!("Cannot resolve app data dir: {}", e),
-    })?;
-    Ok(data_dir.join("tools").join("llama-cpp"))
+    Ok(get_tools_root(app, "tools")?.join("llama-cpp"))
 }
 
 fn quantize_binary_name() -> &'static str {
@@ -356,6 +934,25 @@ fn resolve_quantize_binary(app: &tauri::AppHandle) -> PathBuf {
     PathBuf::from(name) // fallback to PATH
 }
 
+fn imatrix_binary_name() -> &'static str {
+    if cfg!(target_os = "windows") {
+        "llama-imatrix.exe"
+    } else {
+        "llama-imatrix"
+    }
+}
+
+/// Resolve the llama-imatrix binary: bundled first, then PATH fallback.
+fn resolve_imatrix_binary(app: &tauri::AppHandle) -> PathBuf {
+    let name = imatrix_binary_name();
+    if let Ok(tools_dir) = get_tools_dir(app) {
+        if let Some(bundled) = find_binary_recursive(&tools_dir, name) {
+            return bundled;
+        }
+    }
+    PathBuf::from(name) // fallback to PATH
+}
+
 #[tauri::command]
 pub fn get_tools_status(app: tauri::AppHandle) -> Result<ToolsStatus, ModelError> {
     let tools_dir = get_tools_dir(&app)?;
@@ -435,6 +1032,18 @@ fn match_asset<'a>(assets: &'a [GitHubAsset], variant: &str) -> Option<&'a GitHu
             return Some(a);
         }
     }
+    if variant == "rocm" {
+        if let Some(a) = matching.iter().find(|a| {
+            let n = a.name.to_lowercase();
+            n.contains("hip") || n.contains("rocm")
+        }) {
+            return Some(a);
+        }
+        // HIP builds are Windows-only too; Vulkan runs on AMD everywhere else
+        if let Some(a) = matching.iter().find(|a| a.name.to_lowercase().contains("vulkan")) {
+            return Some(a);
+        }
+    }
     if variant == "vulkan" {
         if let Some(a) = matching.iter().find(|a| a.name.to_lowercase().contains("vulkan")) {
             return Some(a);
@@ -452,6 +1061,34 @@ fn match_asset<'a>(assets: &'a [GitHubAsset], variant: &str) -> Option<&'a GitHu
         .copied()
 }
 
+/// Run `binary --version` and return the "N (commit)" part of llama.cpp's
+/// `version:` line. Fails when the binary can't start or reports no version,
+/// which is how truncated or corrupt extractions show up.
+fn probe_llama_version(binary: &std::path::Path) -> Result<String, String> {
+    let output = std::process::Command::new(binary)
+        .arg("--version")
+        .output()
+        .map_err(|e| format!("{} failed to start: {}", binary.display(), e))?;
+    let text = format!(
+        "{}\n{}",
+        String::from_utf8_lossy(&output.stdout),
+        String::from_utf8_lossy(&output.stderr)
+    );
+    let version = text
+        .lines()
+        .find_map(|l| l.trim().strip_prefix("version:"))
+        .map(|v| v.trim().to_string())
+        .filter(|v| !v.is_empty());
+    match version {
+        Some(v) if output.status.success() => Ok(v),
+        _ => Err(format!(
+            "{} --version did not report a version ({})",
+            binary.display(),
+            output.status
+        )),
+    }
+}
+
 #[tauri::command]
 pub async fn download_llama_cpp(
     variant: String,
@@ -468,9 +1105,8 @@ pub async fn download_llama_cpp(
             reason: format!("HTTP client error: {}", e),
         })?;
 
-    let release: GitHubRelease = client
-        .get("https://api.github.com/repos/ggml-org/llama.cpp/releases/latest")
-        .send()
+    let release_url = "https://api.github.com/repos/ggml-org/llama.cpp/releases/latest";
+    let release: GitHubRelease = send_with_retry(client.get(release_url), HTTP_MAX_RETRIES)
         .await
         .map_err(|e| ModelError::ParseError {
             format: "download".into(),
@@ -499,9 +1135,7 @@ pub async fn download_llama_cpp(
     let version = release.tag_name.clone();
 
     // 3. Download the zip
-    let zip_bytes = client
-        .get(&download_url)
-        .send()
+    let zip_bytes = send_with_retry(client.get(&download_url), HTTP_MAX_RETRIES)
         .await
         .map_err(|e| ModelError::ParseError {
             format: "download".into(),
@@ -514,13 +1148,16 @@ pub async fn download_llama_cpp(
             reason: format!("Download failed: {}", e),
         })?;
 
-    // 4. Extract on a blocking thread
+    let sha256 = format!("{:x}", Sha256::digest(&zip_bytes));
+
+    // 4. Extract and probe on a blocking thread
     let td = tools_dir.clone();
     let ver = version.clone();
     let var = variant.clone();
     let aname = asset_name.clone();
+    let digest = sha256.clone();
 
-    tauri::async_runtime::spawn_blocking(move || -> Result<(), String> {
+    let installed = tauri::async_runtime::spawn_blocking(move || -> Result<String, String> {
         // Clean previous installation
         if td.exists() {
             std::fs::remove_dir_all(&td).map_err(|e| format!("Cleanup failed: {}", e))?;
@@ -629,6 +1266,7 @@ pub async fn download_llama_cpp(
                 "llama-cli",
                 "llama-server",
                 "llama-perplexity",
+                "llama-imatrix",
                 "llama-bench",
             ];
             for exe in &executables {
@@ -638,32 +1276,47 @@ pub async fn download_llama_cpp(
             }
         }
 
+        // A binary that starts and reports its version rules out a partial
+        // or corrupt extraction
+        let probe_target = [llama_cli_binary_name(), llama_server_binary_name(), quantize_binary_name()]
+            .iter()
+            .find_map(|name| find_binary_recursive(&td, name))
+            .ok_or_else(|| "No llama.cpp binaries found in downloaded archive".to_string())?;
+        let binary_version = probe_llama_version(&probe_target)
+            .map_err(|e| format!("Downloaded build failed verification: {}", e))?;
+
         // Save manifest
         let manifest = ToolsManifest {
             version: ver,
             variant: var,
             asset_name: aname,
+            sha256: Some(digest),
         };
         let json =
             serde_json::to_string_pretty(&manifest).map_err(|e| format!("JSON error: {}", e))?;
         std::fs::write(td.join("manifest.json"), json)
             .map_err(|e| format!("Write manifest failed: {}", e))?;
 
-        Ok(())
+        Ok(binary_version)
     })
     .await
     .map_err(|e| ModelError::ParseError {
         format: "download".into(),
         reason: format!("Task join error: {}", e),
-    })?
-    .map_err(|e| ModelError::ParseError {
-        format: "download".into(),
-        reason: e,
     })?;
 
-    // 5. Verify the binary exists
-    let name = quantize_binary_name();
-    let binary_path =
+    // Don't leave a half-extracted or broken install behind
+    let binary_version = installed.map_err(|e| {
+        let _ = std::fs::remove_dir_all(&tools_dir);
+        ModelError::ParseError {
+            format: "download".into(),
+            reason: e,
+        }
+    })?;
+
+    // 5. Verify the binary exists
+    let name = quantize_binary_name();
+    let binary_path =
         find_binary_recursive(&tools_dir, name).ok_or_else(|| ModelError::ParseError {
             format: "download".into(),
             reason: "llama-quantize not found in downloaded archive".into(),
@@ -674,6 +1327,8 @@ pub async fn download_llama_cpp(
         version,
         variant,
         path: binary_path.to_string_lossy().to_string(),
+        sha256,
+        binary_version,
     })
 }
 
@@ -702,6 +1357,14 @@ pub struct HfFileInfo {
     pub format: Option<String>,
 }
 
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HfModelSearchHit {
+    pub id: String,
+    pub downloads: u64,
+    pub likes: u64,
+    pub pipeline_tag: Option<String>,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct LocalModelEntry {
     pub id: String,
@@ -723,14 +1386,53 @@ pub struct DownloadProgress {
     pub status: String,
     pub files_done: Option<u32>,
     pub files_total: Option<u32>,
+    /// Transfer rate averaged over the last few seconds; `None` outside active transfer.
+    #[serde(default)]
+    pub bytes_per_second: Option<f64>,
+    /// Seconds until `bytes_total` at the current rate; `None` when the total or rate is unknown.
+    #[serde(default)]
+    pub eta_seconds: Option<f64>,
+}
+
+/// Download rate as a moving average over recent progress samples, so bursty
+/// connections don't make the figure jump between emits.
+struct TransferRate {
+    samples: std::collections::VecDeque<(std::time::Instant, u64)>,
+}
+
+impl TransferRate {
+    const WINDOW: std::time::Duration = std::time::Duration::from_secs(5);
+
+    fn new() -> Self {
+        Self { samples: std::collections::VecDeque::from([(std::time::Instant::now(), 0)]) }
+    }
+
+    /// Record the running total of bytes received over the network.
+    fn record(&mut self, transferred: u64) {
+        let now = std::time::Instant::now();
+        self.samples.push_back((now, transferred));
+        while self.samples.len() > 2 && now.duration_since(self.samples[0].0) > Self::WINDOW {
+            self.samples.pop_front();
+        }
+    }
+
+    fn bytes_per_second(&self) -> Option<f64> {
+        let (start, first) = self.samples.front()?;
+        let (end, last) = self.samples.back()?;
+        let secs = end.duration_since(*start).as_secs_f64();
+        (secs > 0.0).then(|| last.saturating_sub(*first) as f64 / secs)
+    }
+
+    fn eta_seconds(&self, remaining: u64) -> Option<f64> {
+        self.bytes_per_second()
+            .filter(|rate| *rate > 0.0)
+            .map(|rate| remaining as f64 / rate)
+    }
 }
 
 fn get_models_dir(app: &tauri::AppHandle) -> Result<PathBuf, ModelError> {
-    let data_dir = app.path().app_data_dir().map_err(|e| ModelError::ParseError {
-        format: "hub".into(),
-        reason: format!("Cannot resolve app data dir: {}", e),
-    })?;
-    Ok(data_dir.join("models"))
+    let configured = load_settings(app.clone()).models_dir;
+    resolve_data_dir(app, configured, "models", "hub")
 }
 
 fn read_manifest(models_dir: &std::path::Path) -> Vec<LocalModelEntry> {
@@ -751,6 +1453,102 @@ fn write_manifest(models_dir: &std::path::Path, entries: &[LocalModelEntry]) ->
     Ok(())
 }
 
+/// Repo listings are reused for this long before the hub is asked again.
+const REPO_CACHE_TTL_SECS: i64 = 3600;
+const REPO_CACHE_MAX_ENTRIES: usize = 100;
+
+#[derive(Serialize, Deserialize)]
+struct CachedRepoInfo {
+    fetched_at: i64,
+    info: HfRepoInfo,
+}
+
+/// Cache key; the endpoint is included so a mirror's listings stay separate,
+/// and a token fingerprint so a gated repo listed with one token isn't served
+/// to a session with another (or none).
+fn repo_cache_key(endpoint: &str, repo_id: &str, token: Option<&str>) -> String {
+    let fingerprint = match token {
+        Some(token) => format!("{:x}", Sha256::digest(token.as_bytes()))[..16].to_string(),
+        None => "anon".to_string(),
+    };
+    format!("{}@{}/{}", fingerprint, endpoint, repo_id)
+}
+
+fn read_repo_cache(models_dir: &std::path::Path) -> std::collections::HashMap<String, CachedRepoInfo> {
+    std::fs::read_to_string(models_dir.join("repo_cache.json"))
+        .ok()
+        .and_then(|s| serde_json::from_str(&s).ok())
+        .unwrap_or_default()
+}
+
+/// Drop expired entries, keep the newest `REPO_CACHE_MAX_ENTRIES`, and write
+/// the cache. Failures are ignored; the cache is only an optimisation.
+fn write_repo_cache(
+    models_dir: &std::path::Path,
+    mut cache: std::collections::HashMap<String, CachedRepoInfo>,
+) {
+    let now = chrono::Utc::now().timestamp();
+    cache.retain(|_, e| now - e.fetched_at < REPO_CACHE_TTL_SECS);
+    if cache.len() > REPO_CACHE_MAX_ENTRIES {
+        let mut by_age: Vec<(String, i64)> = cache.iter().map(|(k, e)| (k.clone(), e.fetched_at)).collect();
+        by_age.sort_by_key(|(_, fetched_at)| std::cmp::Reverse(*fetched_at));
+        for (key, _) in by_age.into_iter().skip(REPO_CACHE_MAX_ENTRIES) {
+            cache.remove(&key);
+        }
+    }
+
+    if std::fs::create_dir_all(models_dir).is_err() {
+        return;
+    }
+    if let Ok(json) = serde_json::to_string(&cache) {
+        let tmp = models_dir.join("repo_cache.json.tmp");
+        if std::fs::write(&tmp, json).is_ok() {
+            let _ = std::fs::rename(&tmp, models_dir.join("repo_cache.json"));
+        }
+    }
+}
+
+/// Repo listing from the on-disk cache when fresh, otherwise from the hub.
+async fn fetch_repo_info_cached(
+    app: &tauri::AppHandle,
+    repo_id: &str,
+    force_refresh: bool,
+) -> Result<HfRepoInfo, ModelError> {
+    let endpoint = hf_endpoint(app);
+    let models_dir = get_models_dir(app)?;
+    let token = hf_token(app);
+    let key = repo_cache_key(&endpoint, repo_id, token.as_deref());
+
+    if !force_refresh {
+        if let Some(entry) = read_repo_cache(&models_dir).remove(&key) {
+            if chrono::Utc::now().timestamp() - entry.fetched_at < REPO_CACHE_TTL_SECS {
+                return Ok(entry.info);
+            }
+        }
+    }
+
+    let info = fetch_repo_info(&endpoint, repo_id, token.as_deref()).await?;
+    let mut cache = read_repo_cache(&models_dir);
+    cache.insert(key, CachedRepoInfo {
+        fetched_at: chrono::Utc::now().timestamp(),
+        info: info.clone(),
+    });
+    write_repo_cache(&models_dir, cache);
+    Ok(info)
+}
+
+/// Forget a repo's cached listings under every token, e.g. after downloading from it.
+fn invalidate_repo_cache(app: &tauri::AppHandle, repo_id: &str) {
+    let Ok(models_dir) = get_models_dir(app) else { return };
+    let mut cache = read_repo_cache(&models_dir);
+    let suffix = format!("@{}/{}", hf_endpoint(app), repo_id);
+    let before = cache.len();
+    cache.retain(|key, _| !key.ends_with(&suffix));
+    if cache.len() != before {
+        write_repo_cache(&models_dir, cache);
+    }
+}
+
 fn detect_file_format(filename: &str) -> Option<String> {
     let lower = filename.to_lowercase();
     if lower.ends_with(".gguf") {
@@ -766,6 +1564,15 @@ fn detect_file_format(filename: &str) -> Option<String> {
 struct HfApiSibling {
     rfilename: String,
     size: Option<u64>,
+    #[serde(default)]
+    lfs: Option<HfLfsInfo>,
+}
+
+/// Git-LFS pointer info; `sha256` (`oid` in the tree API) is the file's content hash.
+#[derive(Deserialize)]
+struct HfLfsInfo {
+    #[serde(alias = "oid")]
+    sha256: Option<String>,
 }
 
 #[derive(Deserialize)]
@@ -776,21 +1583,119 @@ struct HfApiResponse {
     siblings: Option<Vec<HfApiSibling>>,
 }
 
-async fn fetch_repo_info(repo_id: &str) -> Result<HfRepoInfo, ModelError> {
-    let client = reqwest::Client::builder()
-        .user_agent("ForgeAI")
-        .build()
-        .map_err(|e| ModelError::ParseError {
-            format: "hub".into(),
-            reason: format!("HTTP client error: {}", e),
-        })?;
+#[derive(Deserialize)]
+struct HfApiSearchEntry {
+    #[serde(rename = "modelId")]
+    model_id: Option<String>,
+    id: Option<String>,
+    #[serde(default)]
+    downloads: u64,
+    #[serde(default)]
+    likes: u64,
+    pipeline_tag: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct HfTreeLfs {
+    size: Option<u64>,
+}
+
+#[derive(Deserialize)]
+struct HfTreeEntry {
+    #[serde(rename = "type")]
+    entry_type: String,
+    path: String,
+    size: Option<u64>,
+    lfs: Option<HfTreeLfs>,
+}
+
+/// Expected SHA256 of an LFS-stored file, from the model API's blob listing.
+/// Small files kept in plain git have no SHA256 and aren't verified.
+async fn fetch_lfs_sha256(
+    client: &reqwest::Client,
+    endpoint: &str,
+    repo_id: &str,
+    filename: &str,
+) -> Option<String> {
+    let url = format!("{}/api/models/{}?blobs=true", endpoint, repo_id);
+    let resp = send_with_retry(client.get(&url), HTTP_MAX_RETRIES).await.ok()?;
+    if !resp.status().is_success() {
+        return None;
+    }
+    let api_resp: HfApiResponse = resp.json().await.ok()?;
+    api_resp
+        .siblings?
+        .into_iter()
+        .find(|s| s.rfilename == filename)?
+        .lfs?
+        .sha256
+        .map(|h| h.to_lowercase())
+}
+
+/// Extract the `rel="next"` URL from a paginated API `Link` header.
+fn next_page_url(headers: &reqwest::header::HeaderMap) -> Option<String> {
+    let link = headers.get(reqwest::header::LINK)?.to_str().ok()?;
+    link.split(',').find_map(|part| {
+        let mut segments = part.split(';');
+        let url = segments.next()?.trim();
+        let is_next = segments.any(|seg| seg.trim() == "rel=\"next\"");
+        if is_next {
+            Some(url.trim_start_matches('<').trim_end_matches('>').to_string())
+        } else {
+            None
+        }
+    })
+}
+
+/// Fetch accurate per-file sizes from the tree API, following pagination.
+/// LFS files report their real blob size rather than the pointer size.
+/// Returns None if any page fails so the caller can fall back to sibling data.
+async fn fetch_repo_tree_sizes(
+    client: &reqwest::Client,
+    endpoint: &str,
+    repo_id: &str,
+) -> Option<std::collections::HashMap<String, u64>> {
+    let mut sizes = std::collections::HashMap::new();
+    let mut url = Some(format!(
+        "{}/api/models/{}/tree/main?recursive=true",
+        endpoint, repo_id
+    ));
+
+    while let Some(page_url) = url.take() {
+        let resp = send_with_retry(client.get(&page_url), HTTP_MAX_RETRIES).await.ok()?;
+        if !resp.status().is_success() {
+            return None;
+        }
+        url = next_page_url(resp.headers());
+        let entries: Vec<HfTreeEntry> = resp.json().await.ok()?;
+        for entry in entries {
+            if entry.entry_type != "file" {
+                continue;
+            }
+            let size = entry.lfs.and_then(|l| l.size).or(entry.size);
+            if let Some(size) = size {
+                sizes.insert(entry.path, size);
+            }
+        }
+    }
+
+    Some(sizes)
+}
+
+async fn fetch_repo_info(
+    endpoint: &str,
+    repo_id: &str,
+    token: Option<&str>,
+) -> Result<HfRepoInfo, ModelError> {
+    let client = build_hf_client(token)?;
 
-    let url = format!("https://huggingface.co/api/models/{}", repo_id);
-    let resp = client.get(&url).send().await.map_err(|e| ModelError::ParseError {
+    let url = format!("{}/api/models/{}", endpoint, repo_id);
+    let resp = send_with_retry(client.get(&url), HTTP_MAX_RETRIES).await.map_err(|e| ModelError::ParseError {
         format: "hub".into(),
         reason: format!("Failed to fetch repo: {}", e),
     })?;
 
+    check_hf_auth(resp.status(), repo_id, token)?;
     if !resp.status().is_success() {
         return Err(ModelError::ParseError {
             format: "hub".into(),
@@ -806,14 +1711,26 @@ async fn fetch_repo_info(repo_id: &str) -> Result<HfRepoInfo, ModelError> {
     let repo_name = api_resp.model_id.or(api_resp.id).unwrap_or_else(|| repo_id.to_string());
     let siblings = api_resp.siblings.unwrap_or_default();
 
+    // The model API often omits sizes (especially for LFS files on large repos).
+    // Ask the tree API for accurate sizes; keep sibling data if that fails.
+    let tree_sizes = if siblings.iter().any(|s| s.size.is_none()) {
+        fetch_repo_tree_sizes(&client, endpoint, repo_id).await
+    } else {
+        None
+    };
+
     let mut files: Vec<HfFileInfo> = siblings
         .into_iter()
         .map(|s| {
             let format = detect_file_format(&s.rfilename);
-            let size_display = s.size.map(|sz| crate::model::format_file_size(sz)).unwrap_or_else(|| "---".into());
+            let size = tree_sizes
+                .as_ref()
+                .and_then(|t| t.get(&s.rfilename).copied())
+                .or(s.size);
+            let size_display = size.map(|sz| crate::model::format_file_size(sz)).unwrap_or_else(|| "---".into());
             HfFileInfo {
                 rfilename: s.rfilename,
-                size: s.size,
+                size,
                 size_display,
                 format,
             }
@@ -835,8 +1752,24 @@ async fn fetch_repo_info(repo_id: &str) -> Result<HfRepoInfo, ModelError> {
 }
 
 fn build_http_client() -> Result<reqwest::Client, ModelError> {
+    build_hf_client(None)
+}
+
+/// HTTP client for huggingface.co that sends `Authorization: Bearer <token>`
+/// on every request when a token is configured. The token only ever travels
+/// in this header (marked sensitive), never in URLs or error messages.
+fn build_hf_client(token: Option<&str>) -> Result<reqwest::Client, ModelError> {
+    let mut headers = reqwest::header::HeaderMap::new();
+    if let Some(token) = token {
+        let mut value = reqwest::header::HeaderValue::from_str(&format!("Bearer {}", token))
+            .map_err(|_| ModelError::HubAuth("HuggingFace token contains invalid characters".into()))?;
+        value.set_sensitive(true);
+        headers.insert(reqwest::header::AUTHORIZATION, value);
+    }
+
     reqwest::Client::builder()
         .user_agent("ForgeAI")
+        .default_headers(headers)
         .build()
         .map_err(|e| ModelError::ParseError {
             format: "hub".into(),
@@ -844,14 +1777,243 @@ fn build_http_client() -> Result<reqwest::Client, ModelError> {
         })
 }
 
+/// Retries for metadata fetches and the initial request of a download.
+const HTTP_MAX_RETRIES: u32 = 3;
+
+/// Longest wait honoured from a `Retry-After` header.
+const HTTP_MAX_RETRY_AFTER_SECS: u64 = 60;
+
+fn retry_delay(attempt: u32, resp: Option<&reqwest::Response>) -> std::time::Duration {
+    let retry_after = resp
+        .filter(|r| r.status() == reqwest::StatusCode::TOO_MANY_REQUESTS)
+        .and_then(|r| r.headers().get(reqwest::header::RETRY_AFTER))
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.trim().parse::<u64>().ok());
+    match retry_after {
+        Some(secs) => std::time::Duration::from_secs(secs.min(HTTP_MAX_RETRY_AFTER_SECS)),
+        None => std::time::Duration::from_millis(500 * 2u64.pow(attempt)),
+    }
+}
+
+/// Send `req`, retrying connection errors, timeouts, 429 and 5xx responses
+/// up to `max_retries` times with exponential backoff (500 ms, 1 s, 2 s, ...).
+/// A 429's `Retry-After` seconds replace the backoff. The last response or
+/// error is returned as-is so callers keep their own status handling.
+async fn send_with_retry(
+    req: reqwest::RequestBuilder,
+    max_retries: u32,
+) -> Result<reqwest::Response, reqwest::Error> {
+    let mut attempt = 0;
+    loop {
+        // Streaming bodies can't be cloned; those get a single attempt
+        let Some(this_try) = req.try_clone() else {
+            return req.send().await;
+        };
+        let delay = match this_try.send().await {
+            Ok(resp) => {
+                let status = resp.status();
+                let retryable = status == reqwest::StatusCode::TOO_MANY_REQUESTS || status.is_server_error();
+                if !retryable || attempt >= max_retries {
+                    return Ok(resp);
+                }
+                retry_delay(attempt, Some(&resp))
+            }
+            Err(e) => {
+                if !(e.is_connect() || e.is_timeout()) || attempt >= max_retries {
+                    return Err(e);
+                }
+                retry_delay(attempt, None)
+            }
+        };
+        tokio::time::sleep(delay).await;
+        attempt += 1;
+    }
+}
+
+const HF_DEFAULT_ENDPOINT: &str = "https://huggingface.co";
+
+/// Reject anything but a plain http(s) base URL; API paths are appended to it.
+fn validate_hf_endpoint(endpoint: &str) -> Result<(), ModelError> {
+    let invalid = |reason: &str| ModelError::ParseError {
+        format: "settings".into(),
+        reason: format!("HuggingFace endpoint '{}' {}", endpoint, reason),
+    };
+    let url = reqwest::Url::parse(endpoint.trim()).map_err(|_| invalid("is not a valid URL"))?;
+    if url.scheme() != "http" && url.scheme() != "https" {
+        return Err(invalid("must start with http:// or https://"));
+    }
+    if url.host_str().is_none() {
+        return Err(invalid("has no host"));
+    }
+    if url.query().is_some() || url.fragment().is_some() {
+        return Err(invalid("must not contain a query or fragment"));
+    }
+    Ok(())
+}
+
+/// HuggingFace base URL from the saved settings, without a trailing slash.
+fn hf_endpoint(app: &tauri::AppHandle) -> String {
+    load_settings(app.clone())
+        .hf_endpoint
+        .map(|e| e.trim().trim_end_matches('/').to_string())
+        .filter(|e| !e.is_empty() && validate_hf_endpoint(e).is_ok())
+        .unwrap_or_else(|| HF_DEFAULT_ENDPOINT.to_string())
+}
+
+/// HuggingFace token from the saved settings, if one is set.
+fn hf_token(app: &tauri::AppHandle) -> Option<String> {
+    load_settings(app.clone())
+        .hf_token
+        .map(|t| t.trim().to_string())
+        .filter(|t| !t.is_empty())
+}
+
+/// Turn a 401/403 from HuggingFace into an error that says what to fix.
+fn check_hf_auth(status: reqwest::StatusCode, what: &str, token: Option<&str>) -> Result<(), ModelError> {
+    match (status.as_u16(), token) {
+        (401 | 403, None) => Err(ModelError::HubAuth(format!(
+            "{} is gated or private. Add a HuggingFace access token in Settings.",
+            what
+        ))),
+        (401, Some(_)) => Err(ModelError::HubAuth(
+            "the HuggingFace token was rejected. Check that it is valid in Settings.".into(),
+        )),
+        (403, Some(_)) => Err(ModelError::HubAuth(format!(
+            "your token lacks access to {}. Accept the model's terms on huggingface.co or use a token with read access.",
+            what
+        ))),
+        _ => Ok(()),
+    }
+}
+
+/// A download opened by [`open_resumable_download`].
+struct ResumableDownload {
+    resp: reqwest::Response,
+    file: std::fs::File,
+    /// Bytes already in the `.partial` file that the server is continuing from.
+    resumed_from: u64,
+    /// Full size of the file, or 0 when the server doesn't report it.
+    total_size: u64,
+}
+
+/// Total size from a `Content-Range: bytes start-end/total` header, checking
+/// that the range starts where the partial file ends.
+fn content_range_total(resp: &reqwest::Response, expected_start: u64) -> Option<u64> {
+    let value = resp.headers().get(reqwest::header::CONTENT_RANGE)?.to_str().ok()?;
+    let (range, total) = value.strip_prefix("bytes ")?.split_once('/')?;
+    let start: u64 = range.split_once('-')?.0.parse().ok()?;
+    if start != expected_start {
+        return None;
+    }
+    total.parse().ok()
+}
+
+/// Request `url`, resuming from the end of `partial_path` with a range request
+/// when it already holds data. A 206 with a matching `Content-Range` appends to
+/// the partial file; anything else (a 200, or a range the server won't honor)
+/// truncates it and starts from zero.
+async fn open_resumable_download(
+    client: &reqwest::Client,
+    url: &str,
+    partial_path: &std::path::Path,
+    token: Option<&str>,
+) -> Result<ResumableDownload, ModelError> {
+    let existing = std::fs::metadata(partial_path).map(|m| m.len()).unwrap_or(0);
+
+    let mut req = client.get(url);
+    if existing > 0 {
+        req = req.header(reqwest::header::RANGE, format!("bytes={}-", existing));
+    }
+    let resp = send_with_retry(req, HTTP_MAX_RETRIES).await.map_err(|e| ModelError::ParseError {
+        format: "hub".into(),
+        reason: format!("Download request failed: {}", e),
+    })?;
+
+    if existing > 0 && resp.status() == reqwest::StatusCode::PARTIAL_CONTENT {
+        if let Some(total_size) = content_range_total(&resp, existing) {
+            let file = std::fs::OpenOptions::new()
+                .append(true)
+                .open(partial_path)
+                .map_err(ModelError::IoError)?;
+            return Ok(ResumableDownload { resp, file, resumed_from: existing, total_size });
+        }
+    }
+
+    // A 200 already carries the whole file. Otherwise the partial can't be
+    // continued (range not satisfiable, or a mismatched range): fetch it all again
+    let resp = if existing > 0 && resp.status() != reqwest::StatusCode::OK {
+        send_with_retry(client.get(url), HTTP_MAX_RETRIES).await.map_err(|e| ModelError::ParseError {
+            format: "hub".into(),
+            reason: format!("Download request failed: {}", e),
+        })?
+    } else {
+        resp
+    };
+
+    let name = url.rsplit('/').next().unwrap_or(url);
+    check_hf_auth(resp.status(), name, token)?;
+    if !resp.status().is_success() {
+        return Err(ModelError::ParseError {
+            format: "hub".into(),
+            reason: format!("Download failed (HTTP {})", resp.status()),
+        });
+    }
+
+    let total_size = resp.content_length().unwrap_or(0);
+    let file = std::fs::File::create(partial_path).map_err(ModelError::IoError)?;
+    Ok(ResumableDownload { resp, file, resumed_from: 0, total_size })
+}
+
+// ── Disk Space Preflight ──────────────────────────────
+
+/// Free bytes on the volume holding `path`, resolved through its nearest
+/// existing ancestor. `None` when no mounted disk matches.
+fn available_space(path: &std::path::Path) -> Option<u64> {
+    let existing = path.ancestors().find(|p| p.exists())?;
+    let resolved = existing.canonicalize().ok()?;
+
+    let disks = sysinfo::Disks::new_with_refreshed_list();
+    disks
+        .list()
+        .iter()
+        .filter(|d| resolved.starts_with(d.mount_point()))
+        .max_by_key(|d| d.mount_point().as_os_str().len())
+        .map(|d| d.available_space())
+}
+
+/// Error unless `available` bytes cover `required`.
+fn check_free_space(required: u64, available: u64, format: &str) -> Result<(), ModelError> {
+    if available >= required {
+        return Ok(());
+    }
+    Err(ModelError::ParseError {
+        format: format.into(),
+        reason: format!(
+            "Need {} free, only {} available",
+            crate::model::format_file_size(required),
+            crate::model::format_file_size(available)
+        ),
+    })
+}
+
+/// Fail fast when the volume holding `path` can't fit `required` bytes.
+/// Passes when free space can't be determined.
+pub(crate) fn ensure_disk_space(path: &std::path::Path, required: u64, format: &str) -> Result<(), ModelError> {
+    match available_space(path) {
+        Some(available) => check_free_space(required, available, format),
+        None => Ok(()),
+    }
+}
+
 fn calculate_dir_size(dir: &std::path::Path) -> u64 {
     let mut total = 0;
     if let Ok(entries) = std::fs::read_dir(dir) {
         for entry in entries.flatten() {
             let path = entry.path();
-            if path.is_dir() {
+            // Symlinked directories aren't followed, so link cycles can't recurse
+            if entry.file_type().is_ok_and(|t| t.is_dir()) {
                 total += calculate_dir_size(&path);
-            } else if let Ok(meta) = path.metadata() {
+            } else if let Some(meta) = path.metadata().ok().filter(|m| m.is_file()) {
                 total += meta.len();
             }
         }
@@ -859,9 +2021,100 @@ fn calculate_dir_size(dir: &std::path::Path) -> u64 {
     total
 }
 
+/// File listing of a model repo. Listings are cached for an hour;
+/// `force_refresh` always asks the hub.
+#[tauri::command]
+pub async fn hf_fetch_repo(
+    repo_id: String,
+    force_refresh: Option<bool>,
+    app: tauri::AppHandle,
+) -> Result<HfRepoInfo, ModelError> {
+    fetch_repo_info_cached(&app, &repo_id, force_refresh.unwrap_or(false)).await
+}
+
+const HF_SEARCH_DEFAULT_LIMIT: u32 = 20;
+const HF_SEARCH_MAX_LIMIT: u32 = 100;
+
+/// Search the hub for models, most downloaded first. `format` restricts hits to
+/// repos tagged `gguf` or `safetensors`.
 #[tauri::command]
-pub async fn hf_fetch_repo(repo_id: String) -> Result<HfRepoInfo, ModelError> {
-    fetch_repo_info(&repo_id).await
+pub async fn hf_search_models(
+    query: String,
+    format: Option<String>,
+    limit: Option<u32>,
+    app: tauri::AppHandle,
+) -> Result<Vec<HfModelSearchHit>, ModelError> {
+    let query = query.trim();
+    if query.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let filter = match format.as_deref().map(str::to_lowercase).as_deref() {
+        None | Some("") | Some("all") => None,
+        Some("gguf") => Some("gguf"),
+        Some("safetensors") => Some("safetensors"),
+        Some(other) => {
+            return Err(ModelError::UnsupportedFormat(format!(
+                "'{}' (search supports gguf or safetensors)",
+                other
+            )))
+        }
+    };
+    let limit = limit
+        .unwrap_or(HF_SEARCH_DEFAULT_LIMIT)
+        .clamp(1, HF_SEARCH_MAX_LIMIT)
+        .to_string();
+
+    let client = build_hf_client(hf_token(&app).as_deref())?;
+    let url = format!("{}/api/models", hf_endpoint(&app));
+    let mut params = vec![
+        ("search", query),
+        ("sort", "downloads"),
+        ("direction", "-1"),
+        ("limit", limit.as_str()),
+    ];
+    if let Some(filter) = filter {
+        params.push(("filter", filter));
+    }
+
+    let resp = send_with_retry(client.get(&url).query(&params), HTTP_MAX_RETRIES)
+        .await
+        .map_err(|e| ModelError::ParseError {
+            format: "hub".into(),
+            reason: format!("Search failed: {}", e),
+        })?;
+    if !resp.status().is_success() {
+        return Err(ModelError::ParseError {
+            format: "hub".into(),
+            reason: format!("Search failed (HTTP {})", resp.status()),
+        });
+    }
+
+    let body = resp.text().await.map_err(|e| ModelError::ParseError {
+        format: "hub".into(),
+        reason: format!("Search failed: {}", e),
+    })?;
+    parse_search_hits(&body)
+}
+
+/// Hits from an `/api/models` response body; entries without an id are dropped.
+fn parse_search_hits(body: &str) -> Result<Vec<HfModelSearchHit>, ModelError> {
+    let entries: Vec<HfApiSearchEntry> = serde_json::from_str(body).map_err(|e| ModelError::ParseError {
+        format: "hub".into(),
+        reason: format!("Failed to parse search results: {}", e),
+    })?;
+
+    Ok(entries
+        .into_iter()
+        .filter_map(|e| {
+            Some(HfModelSearchHit {
+                id: e.id.or(e.model_id)?,
+                downloads: e.downloads,
+                likes: e.likes,
+                pipeline_tag: e.pipeline_tag,
+            })
+        })
+        .collect())
 }
 
 #[tauri::command]
@@ -878,26 +2131,14 @@ pub async fn hf_download_file(
     let cancel = state.download_cancel.clone();
     cancel.store(false, std::sync::atomic::Ordering::Relaxed);
 
+    let endpoint = hf_endpoint(&app);
     let download_url = format!(
-        "https://huggingface.co/{}/resolve/main/{}",
-        repo_id, filename
+        "{}/{}/resolve/main/{}",
+        endpoint, repo_id, filename
     );
 
-    let client = build_http_client()?;
-
-    let resp = client.get(&download_url).send().await.map_err(|e| ModelError::ParseError {
-        format: "hub".into(),
-        reason: format!("Download request failed: {}", e),
-    })?;
-
-    if !resp.status().is_success() {
-        return Err(ModelError::ParseError {
-            format: "hub".into(),
-            reason: format!("Download failed (HTTP {})", resp.status()),
-        });
-    }
-
-    let total_size = resp.content_length().unwrap_or(0);
+    let token = hf_token(&app);
+    let client = build_hf_client(token.as_deref())?;
 
     // Sanitize filename: take only the last path component
     let safe_name = std::path::Path::new(&filename)
@@ -908,10 +2149,13 @@ pub async fn hf_download_file(
     let file_path = models_dir.join(&safe_name);
     let partial_path = models_dir.join(format!("{}.partial", safe_name));
 
-    let mut file = std::fs::File::create(&partial_path).map_err(ModelError::IoError)?;
+    // Continue a previously interrupted download if its .partial is still around
+    let ResumableDownload { resp, mut file, resumed_from, total_size } =
+        open_resumable_download(&client, &download_url, &partial_path, token.as_deref()).await?;
     let mut stream = resp.bytes_stream();
-    let mut downloaded: u64 = 0;
+    let mut downloaded: u64 = resumed_from;
     let mut last_emit = std::time::Instant::now();
+    let mut rate = TransferRate::new();
 
     while let Some(chunk) = stream.next().await {
         if cancel.load(std::sync::atomic::Ordering::Relaxed) {
@@ -925,6 +2169,8 @@ pub async fn hf_download_file(
                 status: "cancelled".into(),
                 files_done: None,
                 files_total: None,
+                bytes_per_second: None,
+                eta_seconds: None,
             });
             return Err(ModelError::ParseError {
                 format: "hub".into(),
@@ -950,6 +2196,7 @@ pub async fn hf_download_file(
             } else {
                 0.0
             };
+            rate.record(downloaded - resumed_from);
             let _ = app.emit("hub:download-progress", DownloadProgress {
                 file_name: safe_name.clone(),
                 bytes_downloaded: downloaded,
@@ -958,6 +2205,8 @@ pub async fn hf_download_file(
                 status: "downloading".into(),
                 files_done: None,
                 files_total: None,
+                bytes_per_second: rate.bytes_per_second(),
+                eta_seconds: (total_size > 0).then(|| rate.eta_seconds(total_size.saturating_sub(downloaded))).flatten(),
             });
             last_emit = now;
         }
@@ -968,6 +2217,41 @@ pub async fn hf_download_file(
     // Rename partial to final
     std::fs::rename(&partial_path, &file_path).map_err(ModelError::IoError)?;
 
+    // Verify against the LFS SHA256 before the manifest records the file
+    if let Some(expected) = fetch_lfs_sha256(&client, &endpoint, &repo_id, &filename).await {
+        let _ = app.emit("hub:download-progress", DownloadProgress {
+            file_name: safe_name.clone(),
+            bytes_downloaded: downloaded,
+            bytes_total: total_size,
+            percent: 100.0,
+            status: "verifying".into(),
+            files_done: None,
+            files_total: None,
+            bytes_per_second: None,
+            eta_seconds: None,
+        });
+
+        let hash_path = file_path.clone();
+        let (actual, _) = tauri::async_runtime::spawn_blocking(move || sha256_file(&hash_path))
+            .await
+            .map_err(|e| ModelError::ParseError {
+                format: "hub".into(),
+                reason: format!("Task failed: {}", e),
+            })?
+            .map_err(ModelError::IoError)?;
+
+        if actual != expected {
+            let _ = std::fs::remove_file(&file_path);
+            return Err(ModelError::ParseError {
+                format: "hub".into(),
+                reason: format!(
+                    "Checksum mismatch for {}: expected sha256 {}, got {}. The corrupted file was deleted.",
+                    safe_name, expected, actual
+                ),
+            });
+        }
+    }
+
     let file_size = std::fs::metadata(&file_path).map(|m| m.len()).unwrap_or(0);
     let format = detect_file_format(&safe_name).unwrap_or_else(|| "unknown".into());
     let id = format!(
@@ -991,6 +2275,7 @@ pub async fn hf_download_file(
     let mut manifest = read_manifest(&models_dir);
     manifest.push(entry.clone());
     write_manifest(&models_dir, &manifest)?;
+    invalidate_repo_cache(&app, entry.source_repo.as_deref().unwrap_or_default());
 
     // Emit completion
     let _ = app.emit("hub:download-progress", DownloadProgress {
@@ -1001,6 +2286,8 @@ pub async fn hf_download_file(
         status: "complete".into(),
         files_done: None,
         files_total: None,
+        bytes_per_second: None,
+        eta_seconds: None,
     });
 
     Ok(entry)
@@ -1022,13 +2309,22 @@ pub async fn hf_download_repo(
     cancel.store(false, std::sync::atomic::Ordering::Relaxed);
 
     // Fetch repo file listing
-    let repo_info = fetch_repo_info(&repo_id).await?;
+    let token = hf_token(&app);
+    let endpoint = hf_endpoint(&app);
+    let repo_info = fetch_repo_info_cached(&app, &repo_id, false).await?;
 
     let total_size: u64 = repo_info.files.iter().filter_map(|f| f.size).sum();
     let total_files = repo_info.files.len() as u32;
+
+    // Bytes already in the repo folder (finished files or partials) are not needed again
+    let on_disk = calculate_dir_size(&repo_dir);
+    ensure_disk_space(&repo_dir, total_size.saturating_sub(on_disk), "hub")?;
     let mut overall_downloaded: u64 = 0;
+    // Bytes received in this run only; skipped and resumed bytes would inflate the rate
+    let mut transferred: u64 = 0;
+    let mut rate = TransferRate::new();
 
-    let client = build_http_client()?;
+    let client = build_hf_client(token.as_deref())?;
 
     for (idx, file_info) in repo_info.files.iter().enumerate() {
         // Check cancel before each file
@@ -1041,6 +2337,8 @@ pub async fn hf_download_repo(
                 status: "cancelled".into(),
                 files_done: Some(idx as u32),
                 files_total: Some(total_files),
+                bytes_per_second: None,
+                eta_seconds: None,
             });
             return Err(ModelError::ParseError {
                 format: "hub".into(),
@@ -1049,8 +2347,8 @@ pub async fn hf_download_repo(
         }
 
         let download_url = format!(
-            "https://huggingface.co/{}/resolve/main/{}",
-            repo_id, file_info.rfilename
+            "{}/{}/resolve/main/{}",
+            endpoint, repo_id, file_info.rfilename
         );
 
         // Preserve directory structure within the repo folder
@@ -1059,24 +2357,34 @@ pub async fn hf_download_repo(
             std::fs::create_dir_all(parent).map_err(ModelError::IoError)?;
         }
 
-        let resp = client.get(&download_url).send().await.map_err(|e| ModelError::ParseError {
-            format: "hub".into(),
-            reason: format!("Failed to download {}: {}", file_info.rfilename, e),
-        })?;
-
-        if !resp.status().is_success() {
-            // Skip files that can't be fetched (e.g., LFS pointers without auth)
-            continue;
+        // Files finished by an earlier, interrupted run are kept as-is
+        if let (Some(size), Ok(meta)) = (file_info.size, std::fs::metadata(&out_path)) {
+            if meta.len() == size {
+                overall_downloaded += size;
+                continue;
+            }
         }
 
-        let mut outfile = std::fs::File::create(&out_path).map_err(ModelError::IoError)?;
-        let mut stream = resp.bytes_stream();
-        let mut last_emit = std::time::Instant::now();
+        let partial_path = out_path.with_file_name(format!(
+            "{}.partial",
+            out_path.file_name().and_then(|n| n.to_str()).unwrap_or("download")
+        ));
+
+        let ResumableDownload { resp, file: mut outfile, resumed_from, .. } =
+            match open_resumable_download(&client, &download_url, &partial_path, token.as_deref()).await {
+                Ok(download) => download,
+                Err(e @ ModelError::HubAuth(_)) => return Err(e),
+                // Skip files that can't be fetched (e.g., LFS pointers without auth)
+                Err(_) => continue,
+            };
+        overall_downloaded += resumed_from;
+        let mut stream = resp.bytes_stream();
+        let mut last_emit = std::time::Instant::now();
 
         while let Some(chunk) = stream.next().await {
             if cancel.load(std::sync::atomic::Ordering::Relaxed) {
                 drop(outfile);
-                let _ = std::fs::remove_file(&out_path);
+                let _ = std::fs::remove_file(&partial_path);
                 let _ = app.emit("hub:download-progress", DownloadProgress {
                     file_name: repo_dir_name.clone(),
                     bytes_downloaded: overall_downloaded,
@@ -1085,6 +2393,8 @@ pub async fn hf_download_repo(
                     status: "cancelled".into(),
                     files_done: Some(idx as u32),
                     files_total: Some(total_files),
+                    bytes_per_second: None,
+                    eta_seconds: None,
                 });
                 return Err(ModelError::ParseError {
                     format: "hub".into(),
@@ -1102,9 +2412,11 @@ pub async fn hf_download_repo(
                 outfile.write_all(&bytes).map_err(ModelError::IoError)?;
             }
             overall_downloaded += bytes.len() as u64;
+            transferred += bytes.len() as u64;
 
             let now = std::time::Instant::now();
             if now.duration_since(last_emit).as_millis() >= 500 {
+                rate.record(transferred);
                 let percent = if total_size > 0 {
                     (overall_downloaded as f64 / total_size as f64) * 100.0
                 } else {
@@ -1118,11 +2430,18 @@ pub async fn hf_download_repo(
                     status: "downloading".into(),
                     files_done: Some(idx as u32),
                     files_total: Some(total_files),
+                    bytes_per_second: rate.bytes_per_second(),
+                    eta_seconds: (total_size > 0)
+                        .then(|| rate.eta_seconds(total_size.saturating_sub(overall_downloaded)))
+                        .flatten(),
                 });
                 last_emit = now;
             }
         }
 
+        drop(outfile);
+        std::fs::rename(&partial_path, &out_path).map_err(ModelError::IoError)?;
+
         // Per-file completion emit
         let percent = if total_size > 0 {
             (overall_downloaded as f64 / total_size as f64) * 100.0
@@ -1137,6 +2456,10 @@ pub async fn hf_download_repo(
             status: "downloading".into(),
             files_done: Some((idx + 1) as u32),
             files_total: Some(total_files),
+            bytes_per_second: rate.bytes_per_second(),
+            eta_seconds: (total_size > 0)
+                .then(|| rate.eta_seconds(total_size.saturating_sub(overall_downloaded)))
+                .flatten(),
         });
     }
 
@@ -1158,6 +2481,7 @@ pub async fn hf_download_repo(
     let mut manifest = read_manifest(&models_dir);
     manifest.push(entry.clone());
     write_manifest(&models_dir, &manifest)?;
+    invalidate_repo_cache(&app, entry.source_repo.as_deref().unwrap_or_default());
 
     // Emit completion
     let _ = app.emit("hub:download-progress", DownloadProgress {
@@ -1168,6 +2492,8 @@ pub async fn hf_download_repo(
         status: "complete".into(),
         files_done: Some(total_files),
         files_total: Some(total_files),
+        bytes_per_second: None,
+        eta_seconds: None,
     });
 
     Ok(entry)
@@ -1209,6 +2535,123 @@ pub async fn hub_delete_model(model_id: String, app: tauri::AppHandle) -> Result
     Ok(())
 }
 
+/// Outcome of `hub_rebuild_manifest`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ManifestRebuild {
+    pub entries: Vec<LocalModelEntry>,
+    /// Entries from the old manifest that parsed and still point at a file.
+    pub kept: usize,
+    /// Models found on disk with no manifest entry.
+    pub recovered: usize,
+    /// Old entries that were unreadable or whose files are gone.
+    pub dropped: usize,
+}
+
+/// How deep `dir_contains_model` looks below the directory it is given.
+const MODEL_SCAN_MAX_DEPTH: usize = 4;
+
+/// True when `dir` holds a `.gguf` or `.safetensors` file within
+/// `MODEL_SCAN_MAX_DEPTH` levels. Symlinked directories are not entered, so a
+/// link cycle can't recurse forever; symlinked files still count.
+fn dir_contains_model(dir: &std::path::Path) -> bool {
+    dir_contains_model_within(dir, MODEL_SCAN_MAX_DEPTH)
+}
+
+fn dir_contains_model_within(dir: &std::path::Path, depth: usize) -> bool {
+    std::fs::read_dir(dir).map_or(false, |entries| {
+        entries.flatten().any(|e| match e.file_type() {
+            Ok(t) if t.is_dir() => depth > 0 && dir_contains_model_within(&e.path(), depth - 1),
+            Ok(_) => detect_file_format(&e.file_name().to_string_lossy()).is_some(),
+            Err(_) => false,
+        })
+    })
+}
+
+/// Library entry for a model found on disk, dated by its modification time.
+fn recover_manifest_entry(path: &std::path::Path) -> Option<LocalModelEntry> {
+    let file_name = path.file_name()?.to_string_lossy().to_string();
+    let meta = std::fs::metadata(path).ok()?;
+
+    let (file_size, format, source_repo) = if meta.is_dir() {
+        if !dir_contains_model(path) {
+            return None;
+        }
+        // Hub repo downloads are stored as `owner--name`
+        match file_name.split_once("--") {
+            Some((owner, name)) => (calculate_dir_size(path), "repo".to_string(), Some(format!("{}/{}", owner, name))),
+            None => (calculate_dir_size(path), "safetensors".to_string(), None),
+        }
+    } else {
+        (meta.len(), detect_file_format(&file_name)?, None)
+    };
+
+    let modified: chrono::DateTime<chrono::Utc> = meta
+        .modified()
+        .map(Into::into)
+        .unwrap_or_else(|_| chrono::Utc::now());
+
+    Some(LocalModelEntry {
+        id: format!("{}-{}", file_name.replace('.', "-"), modified.timestamp()),
+        file_name,
+        file_path: path.to_string_lossy().to_string(),
+        file_size,
+        file_size_display: crate::model::format_file_size(file_size),
+        format,
+        source_repo,
+        downloaded_at: modified.to_rfc3339(),
+    })
+}
+
+/// Rebuild `manifest.json` from the models directory. Readable entries whose
+/// files still exist are kept as-is; models on disk without an entry are added.
+/// An unparseable manifest is kept alongside as `manifest.json.corrupt`.
+#[tauri::command]
+pub async fn hub_rebuild_manifest(app: tauri::AppHandle) -> Result<ManifestRebuild, ModelError> {
+    let models_dir = get_models_dir(&app)?;
+    let manifest_path = models_dir.join("manifest.json");
+
+    let raw: Vec<serde_json::Value> = match std::fs::read_to_string(&manifest_path) {
+        Ok(text) => match serde_json::from_str(&text) {
+            Ok(values) => values,
+            Err(_) => {
+                std::fs::write(models_dir.join("manifest.json.corrupt"), &text).map_err(ModelError::IoError)?;
+                vec![]
+            }
+        },
+        Err(_) => vec![],
+    };
+    let total = raw.len();
+
+    let mut entries: Vec<LocalModelEntry> = raw
+        .into_iter()
+        .filter_map(|v| serde_json::from_value::<LocalModelEntry>(v).ok())
+        .filter(|e| std::path::Path::new(&e.file_path).exists())
+        .collect();
+    let kept = entries.len();
+
+    let known: std::collections::HashSet<PathBuf> =
+        entries.iter().map(|e| PathBuf::from(&e.file_path)).collect();
+    let mut found: Vec<LocalModelEntry> = std::fs::read_dir(&models_dir)
+        .map_err(ModelError::IoError)?
+        .flatten()
+        .map(|e| e.path())
+        .filter(|p| !known.contains(p))
+        .filter_map(|p| recover_manifest_entry(&p))
+        .collect();
+    found.sort_by(|a, b| a.downloaded_at.cmp(&b.downloaded_at));
+    let recovered = found.len();
+    entries.extend(found);
+
+    write_manifest(&models_dir, &entries)?;
+
+    Ok(ManifestRebuild {
+        entries,
+        kept,
+        recovered,
+        dropped: total - kept,
+    })
+}
+
 #[tauri::command]
 pub async fn hub_cancel_download(state: State<'_, AppState>) -> Result<(), ModelError> {
     state.download_cancel.store(true, std::sync::atomic::Ordering::Relaxed);
@@ -1379,11 +2822,7 @@ pub struct ConvertResult {
 }
 
 fn get_convert_dir(app: &tauri::AppHandle) -> Result<PathBuf, ModelError> {
-    let data_dir = app.path().app_data_dir().map_err(|e| ModelError::ParseError {
-        format: "convert".into(),
-        reason: format!("Cannot resolve app data dir: {}", e),
-    })?;
-    Ok(data_dir.join("tools").join("convert"))
+    Ok(get_tools_root(app, "convert")?.join("convert"))
 }
 
 pub(crate) fn find_python() -> Option<(String, String)> {
@@ -1423,6 +2862,50 @@ fn get_script_path(convert_dir: &std::path::Path) -> PathBuf {
     convert_dir.join("convert_hf_to_gguf.py")
 }
 
+/// llama.cpp release tag used for both the gguf package and the convert script
+/// so they stay in sync. Pinned so a change upstream can't break conversion;
+/// bump it deliberately.
+const CONVERT_SCRIPT_REF: &str = "b6000";
+/// A previously downloaded script younger than this is reused instead of re-fetched.
+const CONVERT_SCRIPT_MAX_AGE_SECS: u64 = 7 * 24 * 60 * 60;
+
+/// Written next to the convert script to record where and when it came from.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct ConvertScriptStamp {
+    git_ref: String,
+    sha256: String,
+    downloaded_at: u64,
+}
+
+fn get_script_stamp_path(convert_dir: &std::path::Path) -> PathBuf {
+    convert_dir.join("convert_hf_to_gguf.stamp.json")
+}
+
+fn unix_now() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+/// True when the installed script is unmodified, from the pinned ref, and recent.
+fn convert_script_is_current(convert_dir: &std::path::Path) -> bool {
+    let Ok(script) = std::fs::read(get_script_path(convert_dir)) else {
+        return false;
+    };
+    let Some(stamp) = std::fs::read_to_string(get_script_stamp_path(convert_dir))
+        .ok()
+        .and_then(|s| serde_json::from_str::<ConvertScriptStamp>(&s).ok())
+    else {
+        return false;
+    };
+
+    let hash = format!("{:x}", Sha256::digest(&script));
+    stamp.git_ref == CONVERT_SCRIPT_REF
+        && stamp.sha256 == hash
+        && unix_now().saturating_sub(stamp.downloaded_at) < CONVERT_SCRIPT_MAX_AGE_SECS
+}
+
 fn check_packages(venv_python: &std::path::Path) -> (bool, Vec<String>) {
     let required = ["gguf", "numpy", "sentencepiece", "transformers", "safetensors"];
     let mut missing = Vec::new();
@@ -1535,10 +3018,17 @@ pub async fn convert_check_deps(app: tauri::AppHandle) -> Result<ConvertDepsStat
 }
 
 #[tauri::command]
-pub async fn convert_setup(app: tauri::AppHandle) -> Result<(), ModelError> {
+pub async fn convert_setup(
+    app: tauri::AppHandle,
+    force_update_script: Option<bool>,
+) -> Result<(), ModelError> {
     let convert_dir = get_convert_dir(&app)?;
     std::fs::create_dir_all(&convert_dir).map_err(ModelError::IoError)?;
 
+    // Keep a recent, pinned script (and the gguf package installed alongside it)
+    // unless a refresh is requested, so re-running setup doesn't drift versions.
+    let refresh_script = force_update_script.unwrap_or(false) || !convert_script_is_current(&convert_dir);
+
     let (python_cmd, _) = find_python().ok_or_else(|| ModelError::ParseError {
         format: "convert".into(),
         reason: "Python 3 not found. Please install Python 3.10+.".into(),
@@ -1641,45 +3131,51 @@ pub async fn convert_setup(app: tauri::AppHandle) -> Result<(), ModelError> {
     }
 
     // 3. Install gguf from llama.cpp source (must match the convert script version)
-    let _ = app.emit(
-        "convert:setup-progress",
-        ConvertProgress {
-            stage: "setup".into(),
-            message: "Removing old gguf package...".into(),
-            percent: 45.0,
-        },
-    );
-
-    // Uninstall any existing gguf from PyPI first
-    let _ = tokio::process::Command::new(&venv_python)
-        .args(["-m", "pip", "uninstall", "gguf", "-y", "--quiet", "--disable-pip-version-check"])
-        .status()
-        .await;
+    let gguf_installed = !check_packages(&venv_python).1.iter().any(|p| p == "gguf");
+    if refresh_script || !gguf_installed {
+        let _ = app.emit(
+            "convert:setup-progress",
+            ConvertProgress {
+                stage: "setup".into(),
+                message: "Removing old gguf package...".into(),
+                percent: 45.0,
+            },
+        );
 
-    let _ = app.emit(
-        "convert:setup-progress",
-        ConvertProgress {
-            stage: "setup".into(),
-            message: "Installing gguf package from llama.cpp source...".into(),
-            percent: 50.0,
-        },
-    );
+        // Uninstall any existing gguf from PyPI first
+        let _ = tokio::process::Command::new(&venv_python)
+            .args(["-m", "pip", "uninstall", "gguf", "-y", "--quiet", "--disable-pip-version-check"])
+            .status()
+            .await;
+
+        let _ = app.emit(
+            "convert:setup-progress",
+            ConvertProgress {
+                stage: "setup".into(),
+                message: "Installing gguf package from llama.cpp source...".into(),
+                percent: 50.0,
+            },
+        );
 
-    let gguf_url = "https://github.com/ggml-org/llama.cpp/archive/refs/heads/master.tar.gz#subdirectory=gguf-py";
-    let status = tokio::process::Command::new(&venv_python)
-        .args(["-m", "pip", "install", gguf_url, "--no-cache-dir", "--quiet", "--disable-pip-version-check"])
-        .status()
-        .await
-        .map_err(|e| ModelError::ParseError {
-            format: "convert".into(),
-            reason: format!("Failed to install gguf from source: {}", e),
-        })?;
+        let gguf_url = format!(
+            "https://github.com/ggml-org/llama.cpp/archive/refs/tags/{}.tar.gz#subdirectory=gguf-py",
+            CONVERT_SCRIPT_REF
+        );
+        let status = tokio::process::Command::new(&venv_python)
+            .args(["-m", "pip", "install", gguf_url.as_str(), "--no-cache-dir", "--quiet", "--disable-pip-version-check"])
+            .status()
+            .await
+            .map_err(|e| ModelError::ParseError {
+                format: "convert".into(),
+                reason: format!("Failed to install gguf from source: {}", e),
+            })?;
 
-    if !status.success() {
-        return Err(ModelError::ParseError {
-            format: "convert".into(),
-            reason: "Failed to install gguf package from llama.cpp source.".into(),
-        });
+        if !status.success() {
+            return Err(ModelError::ParseError {
+                format: "convert".into(),
+                reason: "Failed to install gguf package from llama.cpp source.".into(),
+            });
+        }
     }
 
     // 4. Install other packages
@@ -1713,43 +3209,63 @@ pub async fn convert_setup(app: tauri::AppHandle) -> Result<(), ModelError> {
     }
 
     // 5. Download convert script from llama.cpp
-    let _ = app.emit(
-        "convert:setup-progress",
-        ConvertProgress {
-            stage: "setup".into(),
-            message: "Downloading conversion script...".into(),
-            percent: 90.0,
-        },
-    );
+    if refresh_script {
+        let _ = app.emit(
+            "convert:setup-progress",
+            ConvertProgress {
+                stage: "setup".into(),
+                message: "Downloading conversion script...".into(),
+                percent: 90.0,
+            },
+        );
 
-    let script_url =
-        "https://raw.githubusercontent.com/ggml-org/llama.cpp/master/convert_hf_to_gguf.py";
-    let client = build_http_client()?;
-    let resp = client
-        .get(script_url)
-        .send()
-        .await
-        .map_err(|e| ModelError::ParseError {
-            format: "convert".into(),
-            reason: format!("Failed to download convert script: {}", e),
-        })?;
+        let script_url = format!(
+            "https://raw.githubusercontent.com/ggml-org/llama.cpp/{}/convert_hf_to_gguf.py",
+            CONVERT_SCRIPT_REF
+        );
+        let client = build_http_client()?;
+        let resp = send_with_retry(client.get(&script_url), HTTP_MAX_RETRIES)
+            .await
+            .map_err(|e| ModelError::ParseError {
+                format: "convert".into(),
+                reason: format!("Failed to download convert script: {}", e),
+            })?;
 
-    if !resp.status().is_success() {
-        return Err(ModelError::ParseError {
+        if !resp.status().is_success() {
+            return Err(ModelError::ParseError {
+                format: "convert".into(),
+                reason: format!(
+                    "Failed to download convert script (HTTP {})",
+                    resp.status()
+                ),
+            });
+        }
+
+        let script_bytes = resp.bytes().await.map_err(|e| ModelError::ParseError {
             format: "convert".into(),
-            reason: format!(
-                "Failed to download convert script (HTTP {})",
-                resp.status()
-            ),
-        });
-    }
+            reason: format!("Download error: {}", e),
+        })?;
 
-    let script_bytes = resp.bytes().await.map_err(|e| ModelError::ParseError {
-        format: "convert".into(),
-        reason: format!("Download error: {}", e),
-    })?;
+        std::fs::write(get_script_path(&convert_dir), &script_bytes).map_err(ModelError::IoError)?;
 
-    std::fs::write(get_script_path(&convert_dir), &script_bytes).map_err(ModelError::IoError)?;
+        let stamp = ConvertScriptStamp {
+            git_ref: CONVERT_SCRIPT_REF.to_string(),
+            sha256: format!("{:x}", Sha256::digest(&script_bytes)),
+            downloaded_at: unix_now(),
+        };
+        if let Ok(json) = serde_json::to_string_pretty(&stamp) {
+            let _ = std::fs::write(get_script_stamp_path(&convert_dir), json);
+        }
+    } else {
+        let _ = app.emit(
+            "convert:setup-progress",
+            ConvertProgress {
+                stage: "setup".into(),
+                message: "Conversion script is up to date, skipping download".into(),
+                percent: 90.0,
+            },
+        );
+    }
 
     let _ = app.emit(
         "convert:setup-progress",
@@ -1889,6 +3405,27 @@ pub async fn convert_run(
         .to_string();
     let outfile = models_dir.join(format!("{}-{}.gguf", repo_dir_name, outtype));
 
+    // Estimate the GGUF size from the source weights, assuming 16-bit checkpoints
+    let weights_size: u64 = std::fs::read_dir(&repo_path)
+        .map(|entries| {
+            entries
+                .flatten()
+                .filter(|e| {
+                    let name = e.file_name().to_string_lossy().to_string();
+                    name.ends_with(".safetensors") || name.ends_with(".bin")
+                })
+                .filter_map(|e| e.metadata().ok().map(|m| m.len()))
+                .sum()
+        })
+        .unwrap_or(0);
+    let scale = match outtype.as_str() {
+        "f32" => 2.0,
+        "q8_0" => 8.5 / 16.0,
+        _ => 1.0,
+    };
+    std::fs::create_dir_all(&models_dir).map_err(ModelError::IoError)?;
+    ensure_disk_space(&models_dir, (weights_size as f64 * scale) as u64, "convert")?;
+
     // Reset cancel flag
     let cancel = state.convert_cancel.clone();
     cancel.store(false, std::sync::atomic::Ordering::Relaxed);
@@ -2080,534 +3617,2704 @@ pub async fn convert_cancel(state: State<'_, AppState>) -> Result<(), ModelError
     Ok(())
 }
 
-// ── Model Testing / Inference ──────────────────────────
+// ── GGUF → SafeTensors ────────────────────────────────
 
-fn llama_cli_binary_name() -> &'static str {
-    if cfg!(target_os = "windows") {
-        "llama-cli.exe"
-    } else {
-        "llama-cli"
-    }
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GgufExportResult {
+    pub output_dir: String,
+    pub files: Vec<String>,
+    pub tensor_count: usize,
+    pub output_size: u64,
+    pub output_size_display: String,
+    pub dtype: String,
+    pub tokenizer_written: bool,
+    /// GGUF tensors with no HuggingFace equivalent, left out of the export.
+    pub skipped_tensors: Vec<String>,
+    /// Set when the source was quantized: dequantized weights keep the quantization error.
+    pub precision_note: Option<String>,
 }
 
-fn resolve_llama_cli(app: &tauri::AppHandle) -> PathBuf {
-    let name = llama_cli_binary_name();
-    if let Ok(tools_dir) = get_tools_dir(app) {
-        if let Some(bundled) = find_binary_recursive(&tools_dir, name) {
-            return bundled;
+/// GGUF architectures whose tensor names map onto the HF Llama layout.
+const GGUF_EXPORT_ARCHS: &[(&str, &str, &str)] = &[
+    ("llama", "LlamaForCausalLM", "llama"),
+    ("mistral", "MistralForCausalLM", "mistral"),
+    ("qwen2", "Qwen2ForCausalLM", "qwen2"),
+    ("qwen3", "Qwen3ForCausalLM", "qwen3"),
+];
+
+/// Rebuild an HF `config.json` from GGUF metadata.
+fn gguf_export_config(
+    meta: &std::collections::HashMap<String, serde_json::Value>,
+    arch: &str,
+    vocab_size: usize,
+    tied_embeddings: bool,
+    dtype: crate::merge::config::OutputDtype,
+) -> serde_json::Value {
+    use crate::merge::config::OutputDtype;
+    let (_, class, model_type) = GGUF_EXPORT_ARCHS
+        .iter()
+        .find(|(a, _, _)| *a == arch)
+        .copied()
+        .unwrap_or(("llama", "LlamaForCausalLM", "llama"));
+    let get = |key: &str| meta.get(&format!("{}.{}", arch, key)).cloned();
+
+    let mut config = serde_json::json!({
+        "architectures": [class],
+        "model_type": model_type,
+        "vocab_size": vocab_size,
+        "tie_word_embeddings": tied_embeddings,
+        "torch_dtype": match dtype {
+            OutputDtype::F32 => "float32",
+            OutputDtype::F16 => "float16",
+            OutputDtype::Bf16 => "bfloat16",
+        },
+        "hidden_act": "silu",
+    });
+    let fields = [
+        ("hidden_size", get("embedding_length")),
+        ("intermediate_size", get("feed_forward_length")),
+        ("num_hidden_layers", get("block_count")),
+        ("num_attention_heads", get("attention.head_count")),
+        ("num_key_value_heads", get("attention.head_count_kv").or_else(|| get("attention.head_count"))),
+        ("head_dim", get("attention.key_length")),
+        ("max_position_embeddings", get("context_length")),
+        ("rms_norm_eps", get("attention.layer_norm_rms_epsilon")),
+        ("rope_theta", get("rope.freq_base")),
+        ("bos_token_id", meta.get("tokenizer.ggml.bos_token_id").cloned()),
+        ("eos_token_id", meta.get("tokenizer.ggml.eos_token_id").cloned()),
+    ];
+    for (key, value) in fields {
+        if let Some(value) = value {
+            config[key] = value;
         }
     }
-    PathBuf::from(name)
+    config
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct TestResult {
-    pub text: String,
-    pub tokens_generated: u32,
-    pub time_ms: u64,
-    pub device: String,
+/// BPE merges for a SentencePiece vocab, rebuilt the way transformers'
+/// `SentencePieceExtractor` does: every split of a piece into two known
+/// pieces is a merge, ranked by the whole piece's score (ties keep id order).
+fn sentencepiece_merges(tokens: &[String], scores: &[f64]) -> Vec<String> {
+    let ids: std::collections::HashMap<&str, usize> =
+        tokens.iter().enumerate().map(|(id, t)| (t.as_str(), id)).collect();
+    let mut merges: Vec<(f64, usize, usize, usize)> = Vec::new();
+    for (id, piece) in tokens.iter().enumerate() {
+        let score = scores.get(id).copied().unwrap_or(0.0);
+        let mut local: Vec<(usize, usize)> = piece
+            .char_indices()
+            .skip(1)
+            .filter_map(|(at, _)| Some((*ids.get(&piece[..at])?, *ids.get(&piece[at..])?)))
+            .collect();
+        local.sort();
+        merges.extend(local.into_iter().map(|(l, r)| (score, id, l, r)));
+    }
+    merges.sort_by(|a, b| b.0.total_cmp(&a.0).then(a.1.cmp(&b.1)));
+    merges
+        .into_iter()
+        .map(|(_, _, l, r)| format!("{} {}", tokens[l], tokens[r]))
+        .collect()
 }
 
-#[tauri::command]
-pub async fn test_generate(
-    model_path: String,
-    prompt: String,
-    max_tokens: u32,
-    temperature: f64,
-    top_p: Option<f64>,
-    top_k: Option<u32>,
-    repeat_penalty: Option<f64>,
-    gpu_layers: Option<i32>,
-    system_prompt: Option<String>,
-    context_size: Option<u32>,
-    app: tauri::AppHandle,
-    state: State<'_, AppState>,
-) -> Result<TestResult, ModelError> {
-    let path = PathBuf::from(&model_path);
-    let cancel = state.test_cancel.clone();
-    cancel.store(false, std::sync::atomic::Ordering::Relaxed);
+/// Rebuild `tokenizer.json` and `tokenizer_config.json` from the embedded
+/// tokenizer: byte-level BPE for `gpt2` vocabularies, byte-fallback BPE for
+/// SentencePiece (`llama`) ones. Returns false for other tokenizer models.
+fn gguf_export_tokenizer(
+    meta: &std::collections::HashMap<String, serde_json::Value>,
+    out_dir: &std::path::Path,
+) -> Result<bool, ModelError> {
+    use serde_json::{json, Value};
+
+    let strings = |key: &str| -> Vec<String> {
+        meta.get(key)
+            .and_then(|v| v.as_array())
+            .map(|a| a.iter().map(|v| v.as_str().unwrap_or_default().to_string()).collect())
+            .unwrap_or_default()
+    };
+    let tokens = strings("tokenizer.ggml.tokens");
+    if tokens.is_empty() {
+        return Ok(false);
+    }
+    let token_types: Vec<i64> = meta
+        .get("tokenizer.ggml.token_type")
+        .and_then(|v| v.as_array())
+        .map(|a| a.iter().map(|v| v.as_i64().unwrap_or(1)).collect())
+        .unwrap_or_default();
+    let token_id = |key: &str| meta.get(key).and_then(|v| v.as_u64()).map(|v| v as usize);
+    let token_text = |id: Option<usize>| id.and_then(|i| tokens.get(i)).cloned();
+
+    // Control (3) and user-defined (4) tokens are matched before the model runs
+    let added_tokens: Vec<Value> = token_types
+        .iter()
+        .enumerate()
+        .filter(|(_, t)| **t == 3 || **t == 4)
+        .filter_map(|(id, t)| {
+            tokens.get(id).map(|content| {
+                json!({
+                    "id": id,
+                    "content": content,
+                    "single_word": false,
+                    "lstrip": false,
+                    "rstrip": false,
+                    "normalized": false,
+                    "special": *t == 3,
+                })
+            })
+        })
+        .collect();
 
-    // Detect format: directory → safetensors, .gguf file → gguf, .safetensors file → use parent dir
-    let (format, inference_path) = if path.is_dir() {
-        if !path.join("config.json").exists() {
-            return Err(ModelError::ParseError {
-                format: "test".into(),
-                reason: "Directory must contain config.json for SafeTensors inference.".into(),
-            });
+    let tokenizer = match meta.get("tokenizer.ggml.model").and_then(|v| v.as_str()) {
+        Some("gpt2") => {
+            let vocab: serde_json::Map<String, Value> = tokens
+                .iter()
+                .enumerate()
+                .map(|(id, t)| (t.clone(), json!(id)))
+                .collect();
+            json!({
+                "version": "1.0",
+                "added_tokens": added_tokens,
+                "normalizer": null,
+                "pre_tokenizer": { "type": "ByteLevel", "add_prefix_space": false, "trim_offsets": true, "use_regex": true },
+                "post_processor": { "type": "ByteLevel", "add_prefix_space": true, "trim_offsets": false, "use_regex": true },
+                "decoder": { "type": "ByteLevel", "add_prefix_space": true, "trim_offsets": true, "use_regex": true },
+                "model": {
+                    "type": "BPE",
+                    "dropout": null,
+                    "unk_token": null,
+                    "continuing_subword_prefix": null,
+                    "end_of_word_suffix": null,
+                    "fuse_unk": false,
+                    "byte_fallback": false,
+                    "vocab": vocab,
+                    "merges": strings("tokenizer.ggml.merges"),
+                },
+            })
         }
-        ("safetensors", path)
-    } else if path.extension().map_or(false, |e| e == "gguf") {
-        if !path.exists() {
-            return Err(ModelError::ParseError {
-                format: "test".into(),
-                reason: "GGUF file not found.".into(),
-            });
+        Some("llama") => {
+            let scores: Vec<f64> = meta
+                .get("tokenizer.ggml.scores")
+                .and_then(|v| v.as_array())
+                .map(|a| a.iter().map(|v| v.as_f64().unwrap_or(0.0)).collect())
+                .unwrap_or_default();
+            let vocab: serde_json::Map<String, Value> = tokens
+                .iter()
+                .enumerate()
+                .map(|(id, t)| (t.clone(), json!(id)))
+                .collect();
+            let mut merges = strings("tokenizer.ggml.merges");
+            if merges.is_empty() {
+                merges = sentencepiece_merges(&tokens, &scores);
+            }
+            json!({
+                "version": "1.0",
+                "added_tokens": added_tokens,
+                "normalizer": { "type": "Sequence", "normalizers": [
+                    { "type": "Prepend", "prepend": "\u{2581}" },
+                    { "type": "Replace", "pattern": { "String": " " }, "content": "\u{2581}" },
+                ]},
+                "pre_tokenizer": null,
+                "post_processor": null,
+                "decoder": { "type": "Sequence", "decoders": [
+                    { "type": "Replace", "pattern": { "String": "\u{2581}" }, "content": " " },
+                    { "type": "ByteFallback" },
+                    { "type": "Fuse" },
+                    { "type": "Strip", "content": " ", "start": 1, "stop": 0 },
+                ]},
+                "model": {
+                    "type": "BPE",
+                    "dropout": null,
+                    "unk_token": token_text(token_id("tokenizer.ggml.unknown_token_id")),
+                    "continuing_subword_prefix": null,
+                    "end_of_word_suffix": null,
+                    "fuse_unk": true,
+                    "byte_fallback": true,
+                    "vocab": vocab,
+                    "merges": merges,
+                },
+            })
         }
-        ("gguf", path)
-    } else if path.extension().map_or(false, |e| e == "safetensors") {
-        let parent = path.parent().ok_or_else(|| ModelError::ParseError {
-            format: "test".into(),
-            reason: "Cannot determine model directory.".into(),
-        })?.to_path_buf();
-        if !parent.join("config.json").exists() {
-            return Err(ModelError::ParseError {
-                format: "test".into(),
-                reason: "SafeTensors inference requires a model directory with config.json. Use the folder path instead.".into(),
-            });
+        _ => return Ok(false),
+    };
+
+    let mut tokenizer_config = json!({
+        "tokenizer_class": "PreTrainedTokenizerFast",
+        "clean_up_tokenization_spaces": false,
+    });
+    let specials = [
+        ("bos_token", token_text(token_id("tokenizer.ggml.bos_token_id"))),
+        ("eos_token", token_text(token_id("tokenizer.ggml.eos_token_id"))),
+        ("unk_token", token_text(token_id("tokenizer.ggml.unknown_token_id"))),
+        ("pad_token", token_text(token_id("tokenizer.ggml.padding_token_id"))),
+    ];
+    for (key, value) in specials {
+        if let Some(value) = value {
+            tokenizer_config[key] = json!(value);
         }
-        ("safetensors", parent)
-    } else {
-        return Err(ModelError::ParseError {
-            format: "test".into(),
-            reason: "Unsupported format. Use a .gguf file or SafeTensors directory.".into(),
-        });
+    }
+    if let Some(add_bos) = meta.get("tokenizer.ggml.add_bos_token") {
+        tokenizer_config["add_bos_token"] = add_bos.clone();
+    }
+    if let Some(template) = meta.get("tokenizer.chat_template") {
+        tokenizer_config["chat_template"] = template.clone();
+    }
+
+    let write_json = |name: &str, value: &Value| -> Result<(), ModelError> {
+        let json = serde_json::to_string_pretty(value).map_err(|e| ModelError::ParseError {
+            format: "convert".into(),
+            reason: format!("Failed to serialize {}: {}", name, e),
+        })?;
+        std::fs::write(out_dir.join(name), json).map_err(ModelError::IoError)
     };
+    write_json("tokenizer.json", &tokenizer)?;
+    write_json("tokenizer_config.json", &tokenizer_config)?;
+    Ok(true)
+}
 
-    let start = std::time::Instant::now();
+/// Convert a GGUF back to an HF-style SafeTensors directory (sharded at 5 GB)
+/// with a reconstructed `config.json` and tokenizer, so it can be fine-tuned.
+/// Quantized tensors are dequantized; their quantization error is kept.
+#[tauri::command]
+pub async fn convert_gguf_to_safetensors(
+    gguf_path: String,
+    output_dir: String,
+    dtype: Option<crate::merge::config::OutputDtype>,
+    app: tauri::AppHandle,
+    state: State<'_, AppState>,
+) -> Result<GgufExportResult, ModelError> {
+    use crate::merge::config::OutputDtype;
 
-    let (full_output, device) = if format == "gguf" {
-        // ── GGUF: use llama-cli ──
-        let binary = resolve_llama_cli(&app);
-        let temp_str = format!("{:.2}", temperature);
-        let n_str = max_tokens.to_string();
+    let fail = |reason: String| ModelError::ParseError { format: "convert".into(), reason };
+    let path = PathBuf::from(&gguf_path);
+    if !path.is_file() {
+        return Err(ModelError::FileNotFound(gguf_path));
+    }
 
-        let gpu = detect_gpu();
-        let has_gpu = gpu.has_nvidia || gpu.has_vulkan || gpu.has_metal;
-        let ngl_val = match gpu_layers {
-            Some(n) if n >= 0 => n.to_string(),
-            _ => if has_gpu { "99".to_string() } else { "0".to_string() },
-        };
-        let gguf_device = if ngl_val == "0" {
-            "CPU".to_string()
-        } else if has_gpu {
-            if gpu.has_nvidia { "CUDA".to_string() }
-            else if gpu.has_metal { "METAL".to_string() }
-            else { "VULKAN".to_string() }
-        } else {
-            "CPU".to_string()
-        };
+    let info = crate::model::gguf::parse(&path)?;
+    let meta = crate::model::gguf::read_metadata(&path)?;
+    let arch = meta
+        .get("general.architecture")
+        .and_then(|v| v.as_str())
+        .unwrap_or_default()
+        .to_string();
+    if !GGUF_EXPORT_ARCHS.iter().any(|(a, _, _)| *a == arch) {
+        return Err(fail(format!(
+            "Converting '{}' GGUFs back to SafeTensors is not supported yet (supported: {})",
+            arch,
+            GGUF_EXPORT_ARCHS.iter().map(|(a, _, _)| *a).collect::<Vec<_>>().join(", ")
+        )));
+    }
 
-        // Build the full prompt with optional system prompt
-        let full_prompt = if let Some(ref sys) = system_prompt {
-            if !sys.trim().is_empty() {
-                format!("[INST] <<SYS>>\n{}\n<</SYS>>\n\n{} [/INST]", sys.trim(), prompt)
-            } else {
-                prompt.clone()
-            }
-        } else {
-            prompt.clone()
-        };
+    let quantized = info
+        .all_tensors
+        .iter()
+        .any(|t| !matches!(t.dtype.to_uppercase().as_str(), "F32" | "F16" | "BF16"));
+    let dtype = dtype.unwrap_or_else(|| match OutputDtype::infer(info.all_tensors.iter().map(|t| t.dtype.as_str())) {
+        OutputDtype::F32 if quantized => OutputDtype::F16,
+        inferred => inferred,
+    });
 
-        let mut args = vec![
-            "-m".to_string(), inference_path.to_string_lossy().to_string(),
-            "-p".to_string(), full_prompt,
-            "-n".to_string(), n_str.clone(),
-            "--temp".to_string(), temp_str.clone(),
-            "-ngl".to_string(), ngl_val,
-            "--no-display-prompt".to_string(),
-            "--log-disable".to_string(),
-            "--simple-io".to_string(),
-        ];
+    let out_dir = PathBuf::from(&output_dir);
+    std::fs::create_dir_all(&out_dir).map_err(ModelError::IoError)?;
+    let estimated = info.parameter_count * dtype.size_in_bytes();
+    ensure_disk_space(&out_dir, estimated, "convert")?;
 
-        if let Some(tp) = top_p {
-            args.push("--top-p".to_string());
-            args.push(format!("{:.2}", tp));
+    let cancel = state.convert_cancel.clone();
+    cancel.store(false, std::sync::atomic::Ordering::Relaxed);
+
+    tauri::async_runtime::spawn_blocking(move || -> Result<GgufExportResult, ModelError> {
+        use crate::merge::{names, output::StreamingSafeTensorsWriter, precompute, tensor_io};
+
+        // to_canonical needs the parent's head counts for the Q/K row permutation
+        let parent = crate::merge::registry::ParentRegistry::default()
+            .add(info.clone(), 0, false)
+            .map_err(fail)?;
+
+        // HF name and shape for every tensor that has one, in GGUF order
+        let mut skipped_tensors = Vec::new();
+        let mut plan: Vec<(String, String, Vec<usize>)> = Vec::new();
+        for t in &info.all_tensors {
+            let hf_name = names::canonical_name(&t.name);
+            if hf_name == t.name {
+                skipped_tensors.push(t.name.clone());
+                continue;
+            }
+            let shape: Vec<usize> = t.shape.iter().rev().map(|&d| d as usize).collect();
+            plan.push((t.name.clone(), hf_name, shape));
         }
-        if let Some(tk) = top_k {
-            args.push("--top-k".to_string());
-            args.push(tk.to_string());
+        let tied_embeddings = !plan.iter().any(|(_, hf, _)| hf == "lm_head.weight");
+        let vocab_size = plan
+            .iter()
+            .find(|(_, hf, _)| hf == "model.embed_tokens.weight")
+            .map(|(_, _, shape)| shape[0])
+            .ok_or_else(|| fail("GGUF has no token embedding tensor".into()))?;
+
+        let manifest = precompute::manifest_from_shapes(
+            plan.iter().map(|(_, hf, shape)| (hf.as_str(), shape.as_slice())),
+        );
+        let source = tensor_io::GgufTensorSource::open(&path)?;
+        let mut writer = StreamingSafeTensorsWriter::new(&output_dir, &manifest, dtype, Some(5 * 1024 * 1024 * 1024))?;
+        let files = writer.output_files();
+
+        for (i, (gguf_name, hf_name, _)) in plan.iter().enumerate() {
+            if cancel.load(std::sync::atomic::Ordering::Relaxed) {
+                drop(writer);
+                for file in &files {
+                    let _ = std::fs::remove_file(file);
+                }
+                return Err(fail("Conversion cancelled".into()));
+            }
+            let _ = app.emit(
+                "convert:progress",
+                ConvertProgress {
+                    stage: "converting".into(),
+                    message: format!("{} → {}", gguf_name, hf_name),
+                    percent: i as f64 / plan.len() as f64 * 100.0,
+                },
+            );
+            let tensor = source.load(gguf_name)?;
+            let tensor = names::to_canonical(&parent, gguf_name, tensor)?;
+            writer.write_tensor(&tensor)?;
         }
-        if let Some(rp) = repeat_penalty {
-            args.push("--repeat-penalty".to_string());
-            args.push(format!("{:.2}", rp));
+        let output_size = writer.bytes_written();
+        writer.finish()?;
+
+        let _ = app.emit(
+            "convert:progress",
+            ConvertProgress {
+                stage: "writing".into(),
+                message: "Writing config and tokenizer...".into(),
+                percent: 100.0,
+            },
+        );
+        let config = gguf_export_config(&meta, &arch, vocab_size, tied_embeddings, dtype);
+        let config_json = serde_json::to_string_pretty(&config)
+            .map_err(|e| fail(format!("Failed to serialize config.json: {}", e)))?;
+        std::fs::write(out_dir.join("config.json"), config_json).map_err(ModelError::IoError)?;
+        let tokenizer_written = gguf_export_tokenizer(&meta, &out_dir)?;
+
+        let precision_note = quantized.then(|| {
+            format!(
+                "Source was quantized ({}); weights were dequantized to {} but keep the quantization error, so they will not match the original full-precision model.",
+                info.quantization.clone().unwrap_or_else(|| "mixed".into()),
+                dtype.safetensors_name()
+            )
+        });
+
+        Ok(GgufExportResult {
+            output_dir,
+            files,
+            tensor_count: plan.len(),
+            output_size,
+            output_size_display: crate::model::format_file_size(output_size),
+            dtype: dtype.safetensors_name().to_string(),
+            tokenizer_written,
+            skipped_tensors,
+            precision_note,
+        })
+    })
+    .await
+    .map_err(|e| ModelError::ParseError {
+        format: "convert".into(),
+        reason: format!("Task failed: {}", e),
+    })?
+}
+
+// ── Vocab Consistency ─────────────────────────────────
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct VocabCheckResult {
+    pub consistent: bool,
+    pub embedding_rows: Option<u64>,
+    pub lm_head_rows: Option<u64>,
+    pub tokenizer_vocab: Option<u64>,
+    pub message: Option<String>,
+}
+
+/// Count tokens in a HuggingFace tokenizer.json (base vocab plus added tokens).
+fn tokenizer_json_vocab_size(dir: &std::path::Path) -> Option<u64> {
+    let content = std::fs::read_to_string(dir.join("tokenizer.json")).ok()?;
+    let json: serde_json::Value = serde_json::from_str(&content).ok()?;
+
+    let mut max_id: Option<u64> = None;
+    let mut bump = |id: u64| max_id = Some(max_id.map_or(id, |m| m.max(id)));
+
+    match json.pointer("/model/vocab") {
+        Some(serde_json::Value::Object(vocab)) => {
+            vocab.values().filter_map(|v| v.as_u64()).for_each(&mut bump);
         }
-        if let Some(ctx) = context_size {
-            args.push("-c".to_string());
-            args.push(ctx.to_string());
+        // Unigram tokenizers store vocab as a list of [piece, score]
+        Some(serde_json::Value::Array(vocab)) if !vocab.is_empty() => {
+            bump(vocab.len() as u64 - 1);
         }
+        _ => {}
+    }
+    if let Some(added) = json.get("added_tokens").and_then(|a| a.as_array()) {
+        added.iter().filter_map(|t| t.get("id").and_then(|i| i.as_u64())).for_each(&mut bump);
+    }
 
-        let mut child = tokio::process::Command::new(&binary)
-            .args(&args)
-            .stdout(std::process::Stdio::piped())
-            .stderr(std::process::Stdio::piped())
-            .spawn()
-            .map_err(|e| ModelError::ParseError {
-                format: "test".into(),
-                reason: if e.kind() == std::io::ErrorKind::NotFound {
-                    "llama-cli not found. Install llama.cpp tools via Settings > Tools.".into()
-                } else {
-                    format!("Failed to start inference: {}", e)
-                },
-            })?;
+    max_id.map(|m| m + 1)
+}
 
-        if let Some(pid) = child.id() {
-            *state.test_pid.lock().unwrap() = Some(pid);
+fn check_vocab(path: &std::path::Path) -> Result<VocabCheckResult, ModelError> {
+    let (info, tokenizer_dir) = if path.is_dir() {
+        (crate::model::safetensors::parse_dir(path)?, Some(path.to_path_buf()))
+    } else {
+        let info = match detect_format(path)? {
+            ModelFormat::SafeTensors => crate::model::safetensors::parse(path)?,
+            ModelFormat::Gguf => crate::model::gguf::parse(path)?,
+            ModelFormat::PyTorch => crate::model::pytorch::parse(path)?,
+        };
+        let dir = match info.format {
+            ModelFormat::SafeTensors | ModelFormat::PyTorch => path.parent().map(|p| p.to_path_buf()),
+            ModelFormat::Gguf => None,
+        };
+        (info, dir)
+    };
+
+    // SafeTensors shapes are [vocab, hidden]; GGUF stores dims reversed ([hidden, vocab])
+    let vocab_dim = |shape: &[u64]| -> Option<u64> {
+        match info.format {
+            ModelFormat::SafeTensors | ModelFormat::PyTorch => shape.first().copied(),
+            ModelFormat::Gguf => shape.last().copied(),
         }
+    };
 
-        let stdout = child.stdout.take();
-        let app_out = app.clone();
-        let cancel_out = cancel.clone();
+    let embedding_rows = info
+        .all_tensors
+        .iter()
+        .find(|t| inspect::classify_tensor(&t.name) == "embedding" && t.shape.len() == 2)
+        .and_then(|t| vocab_dim(&t.shape));
+    let lm_head_rows = info
+        .all_tensors
+        .iter()
+        .find(|t| {
+            let lower = t.name.to_lowercase();
+            (lower.contains("lm_head") || lower == "output.weight") && t.shape.len() == 2
+        })
+        .and_then(|t| vocab_dim(&t.shape));
+
+    let tokenizer_vocab = match info.format {
+        ModelFormat::Gguf => info
+            .metadata
+            .get("tokenizer.ggml.tokens_count")
+            .and_then(|v| v.parse::<u64>().ok()),
+        ModelFormat::SafeTensors | ModelFormat::PyTorch => {
+            tokenizer_dir.as_deref().and_then(tokenizer_json_vocab_size)
+        }
+    };
 
-        let output_handle = tokio::spawn(async move {
-            let mut output = String::new();
-            if let Some(stdout) = stdout {
-                let mut reader = tokio::io::BufReader::new(stdout);
-                let mut buf = [0u8; 256];
-                loop {
-                    if cancel_out.load(std::sync::atomic::Ordering::Relaxed) {
-                        break;
-                    }
-                    match reader.read(&mut buf).await {
-                        Ok(0) => break,
-                        Ok(n) => {
-                            let chunk = String::from_utf8_lossy(&buf[..n]).to_string();
-                            output.push_str(&chunk);
-                            let _ = app_out.emit("test:token", &chunk);
-                        }
-                        Err(_) => break,
-                    }
+    let mut problems = Vec::new();
+    if let (Some(emb), Some(head)) = (embedding_rows, lm_head_rows) {
+        if emb != head {
+            problems.push(format!(
+                "Embedding has {} rows but output head has {} rows",
+                emb, head
+            ));
+        }
+    }
+    if let Some(vocab) = tokenizer_vocab {
+        for (label, rows) in [("Embedding", embedding_rows), ("Output head", lm_head_rows)] {
+            if let Some(rows) = rows {
+                // Padded embeddings (rows > vocab) are common and harmless
+                if vocab > rows {
+                    problems.push(format!(
+                        "Tokenizer has {} tokens but {} has only {} rows",
+                        vocab, label.to_lowercase(), rows
+                    ));
                 }
             }
-            output
-        });
+        }
+    }
 
-        let stderr = child.stderr.take();
-        let err_handle = tokio::spawn(async move {
-            let mut last_err = String::new();
-            if let Some(stderr) = stderr {
-                let reader = tokio::io::BufReader::new(stderr);
-                let mut lines = reader.lines();
-                while let Ok(Some(line)) = lines.next_line().await {
-                    if !line.trim().is_empty() {
-                        last_err = line;
-                    }
-                }
+    Ok(VocabCheckResult {
+        consistent: problems.is_empty(),
+        embedding_rows,
+        lm_head_rows,
+        tokenizer_vocab,
+        message: if problems.is_empty() { None } else { Some(problems.join("; ")) },
+    })
+}
+
+#[tauri::command]
+pub async fn check_vocab_consistency(path: String) -> Result<VocabCheckResult, ModelError> {
+    let path = PathBuf::from(&path);
+    if !path.exists() {
+        return Err(ModelError::FileNotFound(path.to_string_lossy().to_string()));
+    }
+    tauri::async_runtime::spawn_blocking(move || check_vocab(&path))
+        .await
+        .map_err(|e| ModelError::ParseError {
+            format: "test".into(),
+            reason: format!("Vocab check failed: {}", e),
+        })?
+}
+
+// ── Model Testing / Inference ──────────────────────────
+
+fn llama_cli_binary_name() -> &'static str {
+    if cfg!(target_os = "windows") {
+        "llama-cli.exe"
+    } else {
+        "llama-cli"
+    }
+}
+
+fn resolve_llama_cli(app: &tauri::AppHandle) -> PathBuf {
+    let name = llama_cli_binary_name();
+    if let Ok(tools_dir) = get_tools_dir(app) {
+        if let Some(bundled) = find_binary_recursive(&tools_dir, name) {
+            return bundled;
+        }
+    }
+    PathBuf::from(name)
+}
+
+fn llama_server_binary_name() -> &'static str {
+    if cfg!(target_os = "windows") {
+        "llama-server.exe"
+    } else {
+        "llama-server"
+    }
+}
+
+/// Resolve llama-server: bundled first, then PATH. `None` means GGUF
+/// inference falls back to spawning llama-cli per request.
+fn resolve_llama_server(app: &tauri::AppHandle) -> Option<PathBuf> {
+    let name = llama_server_binary_name();
+    if let Ok(tools_dir) = get_tools_dir(app) {
+        if let Some(bundled) = find_binary_recursive(&tools_dir, name) {
+            return Some(bundled);
+        }
+    }
+    std::env::var_os("PATH").and_then(|paths| {
+        std::env::split_paths(&paths)
+            .map(|dir| dir.join(name))
+            .find(|p| p.is_file())
+    })
+}
+
+/// `-ngl` value and device label for GGUF inference. `None` or a negative
+/// count offloads everything when a GPU is present.
+fn gguf_offload(gpu_layers: Option<i32>) -> (String, String) {
+    offload_for(&detect_gpu(), gpu_layers)
+}
+
+fn offload_for(gpu: &GpuInfo, gpu_layers: Option<i32>) -> (String, String) {
+    let has_gpu = gpu.has_nvidia || gpu.has_rocm || gpu.has_vulkan || gpu.has_metal;
+    let ngl_val = match gpu_layers {
+        Some(n) if n >= 0 => n.to_string(),
+        _ => if has_gpu { "99".to_string() } else { "0".to_string() },
+    };
+    let device = if ngl_val == "0" {
+        "CPU".to_string()
+    } else if has_gpu {
+        if gpu.has_nvidia { "CUDA".to_string() }
+        else if gpu.has_rocm { "ROCM".to_string() }
+        else if gpu.has_metal { "METAL".to_string() }
+        else { "VULKAN".to_string() }
+    } else {
+        "CPU".to_string()
+    };
+    (ngl_val, device)
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ServerStatus {
+    pub running: bool,
+    pub port: Option<u16>,
+    pub model_path: Option<String>,
+}
+
+fn server_status(state: &AppState) -> ServerStatus {
+    let mut guard = state.llama_server.lock().unwrap();
+    // Forget a server that exited on its own
+    if guard.as_mut().is_some_and(|s| !matches!(s.child.try_wait(), Ok(None))) {
+        *guard = None;
+    }
+    ServerStatus {
+        running: guard.is_some(),
+        port: guard.as_ref().map(|s| s.port),
+        model_path: guard.as_ref().map(|s| s.model_path.clone()),
+    }
+}
+
+fn stop_llama_server(state: &AppState) {
+    if let Some(mut server) = state.llama_server.lock().unwrap().take() {
+        let _ = server.child.start_kill();
+    }
+}
+
+/// Start llama-server for `model_path` unless one is already serving it with
+/// the same settings, then wait until `/health` reports the model loaded.
+async fn ensure_llama_server(
+    state: &AppState,
+    binary: &std::path::Path,
+    model_path: &str,
+    gpu_layers: &str,
+    context_size: Option<u32>,
+    cancel: &std::sync::atomic::AtomicBool,
+) -> Result<u16, ModelError> {
+    {
+        let mut guard = state.llama_server.lock().unwrap();
+        if let Some(server) = guard.as_mut() {
+            let alive = matches!(server.child.try_wait(), Ok(None));
+            if alive
+                && server.model_path == model_path
+                && server.gpu_layers == gpu_layers
+                && server.context_size == context_size
+            {
+                return Ok(server.port);
             }
-            last_err
-        });
+            let _ = server.child.start_kill();
+            *guard = None;
+        }
+    }
 
-        let status = loop {
-            tokio::select! {
-                result = child.wait() => {
-                    break result.map_err(|e| ModelError::ParseError {
-                        format: "test".into(),
-                        reason: format!("Process error: {}", e),
-                    })?;
-                }
-                _ = tokio::time::sleep(std::time::Duration::from_millis(500)) => {
-                    if cancel.load(std::sync::atomic::Ordering::Relaxed) {
-                        child.kill().await.ok();
-                        *state.test_pid.lock().unwrap() = None;
-                        return Err(ModelError::ParseError {
-                            format: "test".into(),
-                            reason: "Generation cancelled".into(),
-                        });
-                    }
+    let port = std::net::TcpListener::bind("127.0.0.1:0")
+        .and_then(|l| l.local_addr())
+        .map(|a| a.port())
+        .map_err(ModelError::IoError)?;
+
+    let mut args = vec![
+        "-m".to_string(), model_path.to_string(),
+        "--host".to_string(), "127.0.0.1".to_string(),
+        "--port".to_string(), port.to_string(),
+        "-ngl".to_string(), gpu_layers.to_string(),
+    ];
+    if let Some(ctx) = context_size {
+        args.push("-c".to_string());
+        args.push(ctx.to_string());
+    }
+
+    let mut child = tokio::process::Command::new(binary)
+        .args(&args)
+        .stdout(std::process::Stdio::null())
+        .stderr(std::process::Stdio::piped())
+        .kill_on_drop(true)
+        .spawn()
+        .map_err(|e| ModelError::ParseError {
+            format: "test".into(),
+            reason: format!("Failed to start llama-server: {}", e),
+        })?;
+
+    // Keep draining the log so the server never blocks on a full pipe
+    let last_log = std::sync::Arc::new(std::sync::Mutex::new(String::new()));
+    if let Some(stderr) = child.stderr.take() {
+        let last_log = last_log.clone();
+        tokio::spawn(async move {
+            let mut lines = tokio::io::BufReader::new(stderr).lines();
+            while let Ok(Some(line)) = lines.next_line().await {
+                if !line.trim().is_empty() {
+                    *last_log.lock().unwrap() = line;
                 }
             }
-        };
-
-        let output = output_handle.await.unwrap_or_default();
-        let last_error = err_handle.await.unwrap_or_default();
-        *state.test_pid.lock().unwrap() = None;
+        });
+    }
 
-        if !status.success() && output.is_empty() {
+    let client = reqwest::Client::new();
+    let health_url = format!("http://127.0.0.1:{}/health", port);
+    let deadline = std::time::Instant::now() + std::time::Duration::from_secs(300);
+    loop {
+        if cancel.load(std::sync::atomic::Ordering::Relaxed) {
+            let _ = child.start_kill();
             return Err(ModelError::ParseError {
                 format: "test".into(),
-                reason: if last_error.is_empty() {
-                    format!("Inference failed (exit code {:?})", status.code())
+                reason: "Generation cancelled".into(),
+            });
+        }
+        if let Ok(Some(status)) = child.try_wait() {
+            let log = last_log.lock().unwrap().clone();
+            return Err(ModelError::ParseError {
+                format: "test".into(),
+                reason: if log.is_empty() {
+                    format!("llama-server exited while loading (exit code {:?})", status.code())
                 } else {
-                    format!("Inference failed: {}", last_error)
+                    format!("llama-server failed: {}", log)
                 },
             });
         }
-
-        (output, gguf_device)
-    } else {
-        // ── SafeTensors: use Python transformers ──
-        // Prefer training venv (has bitsandbytes, accelerate, peft for quantized models),
-        // fall back to convert venv
-        let training_dir = crate::training::venv::get_training_dir(&app)?;
-        let training_python = crate::training::venv::get_venv_python(&training_dir);
-
-        let convert_dir = get_convert_dir(&app)?;
-        let convert_python = get_venv_python(&convert_dir);
-
-        let venv_python = if training_python.exists() {
-            training_python
-        } else if convert_python.exists() {
-            convert_python
-        } else {
+        if std::time::Instant::now() > deadline {
+            let _ = child.start_kill();
             return Err(ModelError::ParseError {
                 format: "test".into(),
-                reason: "Python environment not set up. Install dependencies via the TRAINING or CONVERT page first.".into(),
+                reason: "llama-server did not become ready within 5 minutes".into(),
             });
-        };
+        }
+        // 503 while the model is still loading, 200 once ready
+        if let Ok(resp) = client.get(&health_url).send().await {
+            if resp.status().is_success() {
+                break;
+            }
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(500)).await;
+    }
 
-        let script = r#"
-import sys, json, os, torch, warnings, gc
-warnings.filterwarnings("ignore")
-os.environ["PYTORCH_CUDA_ALLOC_CONF"] = "expandable_segments:True"
+    *state.llama_server.lock().unwrap() = Some(LlamaServer {
+        child,
+        port,
+        model_path: model_path.to_string(),
+        gpu_layers: gpu_layers.to_string(),
+        context_size,
+    });
+    Ok(port)
+}
 
-from transformers import AutoModelForCausalLM, AutoTokenizer, TextIteratorStreamer, BitsAndBytesConfig
-from threading import Thread
+/// Payload of `test:token`. `session_id` is set for chat turns so listeners
+/// can tell sessions apart.
+#[derive(Debug, Clone, Serialize)]
+pub struct TokenEvent {
+    pub session_id: Option<String>,
+    pub text: String,
+}
 
-path, prompt = sys.argv[1], sys.argv[2]
-max_tok, temp = int(sys.argv[3]), float(sys.argv[4])
-opts = json.loads(sys.argv[5]) if len(sys.argv) > 5 else {}
+fn emit_token(app: &tauri::AppHandle, session_id: Option<&str>, text: &str) {
+    let _ = app.emit("test:token", TokenEvent {
+        session_id: session_id.map(String::from),
+        text: text.to_string(),
+    });
+}
 
-force_cpu = opts.get("gpu_layers", -1) == 0
-has_cuda = torch.cuda.is_available() and not force_cpu
+/// Llama-2 style `[INST]` prompt for the GGUF backends, re-rendered from the
+/// whole history on every turn. A lone user message with no system prompt is
+/// passed through untouched.
+fn render_inst_prompt(messages: &[ChatMessage]) -> String {
+    let mut system = messages
+        .iter()
+        .find(|m| m.role == "system" && !m.content.trim().is_empty())
+        .map(|m| m.content.trim().to_string());
+    let turns: Vec<&ChatMessage> = messages.iter().filter(|m| m.role != "system").collect();
 
-# Check if model has quantization config (4-bit/8-bit fine-tuned)
-import json as _json
-config_path = os.path.join(path, "config.json")
-is_quantized = False
-if os.path.exists(config_path):
+    if system.is_none() && turns.len() == 1 {
+        return turns[0].content.clone();
+    }
+
+    let mut prompt = String::new();
+    for msg in turns {
+        if msg.role == "assistant" {
+            prompt.push_str(&format!(" {} </s>", msg.content.trim()));
+            continue;
+        }
+        if !prompt.is_empty() {
+            prompt.push_str("<s>");
+        }
+        match system.take() {
+            Some(sys) => prompt.push_str(&format!("[INST] <<SYS>>\n{}\n<</SYS>>\n\n{} [/INST]", sys, msg.content)),
+            None => prompt.push_str(&format!("[INST] {} [/INST]", msg.content)),
+        }
+    }
+    prompt
+}
+
+/// Prompt format a model's chat template belongs to, recognised by its
+/// special tokens. There is no Jinja engine here, so the common families are
+/// rendered by hand.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum ChatFormat {
+    ChatMl,
+    Llama3,
+    Gemma,
+    Phi3,
+    Inst,
+}
+
+fn detect_chat_format(template: &str) -> ChatFormat {
+    if template.contains("<|im_start|>") {
+        ChatFormat::ChatMl
+    } else if template.contains("<|start_header_id|>") {
+        ChatFormat::Llama3
+    } else if template.contains("<start_of_turn>") {
+        ChatFormat::Gemma
+    } else if template.contains("<|user|>") && template.contains("<|end|>") {
+        ChatFormat::Phi3
+    } else {
+        ChatFormat::Inst
+    }
+}
+
+/// Render the history in the format of the model's own chat template,
+/// ending with the assistant turn open. Without a template this falls back
+/// to `render_inst_prompt`.
+fn render_chat_prompt(messages: &[ChatMessage], chat_template: Option<&str>) -> String {
+    let format = match chat_template {
+        Some(template) => detect_chat_format(template),
+        None => return render_inst_prompt(messages),
+    };
+    let turns = messages.iter().filter(|m| m.role != "system" || !m.content.trim().is_empty());
+
+    let mut prompt = String::new();
+    match format {
+        ChatFormat::ChatMl => {
+            for msg in turns {
+                prompt.push_str(&format!("<|im_start|>{}\n{}<|im_end|>\n", msg.role, msg.content));
+            }
+            prompt.push_str("<|im_start|>assistant\n");
+        }
+        ChatFormat::Llama3 => {
+            for msg in turns {
+                prompt.push_str(&format!(
+                    "<|start_header_id|>{}<|end_header_id|>\n\n{}<|eot_id|>",
+                    msg.role,
+                    msg.content.trim()
+                ));
+            }
+            prompt.push_str("<|start_header_id|>assistant<|end_header_id|>\n\n");
+        }
+        ChatFormat::Gemma => {
+            // Gemma has no system role; the system prompt leads the first user turn
+            let mut system = None;
+            for msg in turns {
+                match msg.role.as_str() {
+                    "system" => system = Some(msg.content.trim().to_string()),
+                    "assistant" => prompt.push_str(&format!("<start_of_turn>model\n{}<end_of_turn>\n", msg.content.trim())),
+                    _ => {
+                        let content = match system.take() {
+                            Some(sys) => format!("{}\n\n{}", sys, msg.content.trim()),
+                            None => msg.content.trim().to_string(),
+                        };
+                        prompt.push_str(&format!("<start_of_turn>user\n{}<end_of_turn>\n", content));
+                    }
+                }
+            }
+            prompt.push_str("<start_of_turn>model\n");
+        }
+        ChatFormat::Phi3 => {
+            for msg in turns {
+                prompt.push_str(&format!("<|{}|>\n{}<|end|>\n", msg.role, msg.content));
+            }
+            prompt.push_str("<|assistant|>\n");
+        }
+        ChatFormat::Inst => return render_inst_prompt(messages),
+    }
+    prompt
+}
+
+/// POST a streaming `/completion` to the resident server, forwarding each
+/// piece of text as `test:token`. Returns the text and the server's count of
+/// generated tokens.
+async fn server_completion(
+    app: &tauri::AppHandle,
+    port: u16,
+    body: serde_json::Value,
+    stop: &[String],
+    session_id: Option<&str>,
+    cancel: &std::sync::atomic::AtomicBool,
+) -> Result<(String, Option<u32>), ModelError> {
+    let resp = reqwest::Client::new()
+        .post(format!("http://127.0.0.1:{}/completion", port))
+        .json(&body)
+        .send()
+        .await
+        .map_err(|e| ModelError::ParseError {
+            format: "test".into(),
+            reason: format!("llama-server request failed: {}", e),
+        })?;
+    if !resp.status().is_success() {
+        let status = resp.status();
+        let text = resp.text().await.unwrap_or_default();
+        return Err(ModelError::ParseError {
+            format: "test".into(),
+            reason: format!("llama-server returned {}: {}", status, text.trim()),
+        });
+    }
+
+    let mut stream = resp.bytes_stream();
+    let mut pending: Vec<u8> = Vec::new();
+    let mut filter = StopFilter::new(stop);
+    let mut tokens_predicted = None;
+
+    'events: loop {
+        let chunk = tokio::select! {
+            chunk = stream.next() => chunk,
+            _ = tokio::time::sleep(std::time::Duration::from_millis(200)) => {
+                if cancel.load(std::sync::atomic::Ordering::Relaxed) {
+                    // Dropping the response closes the connection, which stops generation
+                    return Err(ModelError::ParseError {
+                        format: "test".into(),
+                        reason: "Generation cancelled".into(),
+                    });
+                }
+                continue;
+            }
+        };
+        let Some(chunk) = chunk else { break };
+        let chunk = chunk.map_err(|e| ModelError::ParseError {
+            format: "test".into(),
+            reason: format!("llama-server stream error: {}", e),
+        })?;
+        pending.extend_from_slice(&chunk);
+
+        // SSE: one `data: {json}` line per event
+        while let Some(pos) = pending.iter().position(|&b| b == b'\n') {
+            let line: Vec<u8> = pending.drain(..=pos).collect();
+            let line = String::from_utf8_lossy(&line);
+            let Some(data) = line.trim().strip_prefix("data:") else { continue };
+            let Ok(event) = serde_json::from_str::<serde_json::Value>(data.trim()) else { continue };
+
+            if let Some(text) = event["content"].as_str().filter(|t| !t.is_empty()) {
+                let visible = filter.push(text);
+                if !visible.is_empty() {
+                    emit_token(app, session_id, &visible);
+                }
+            }
+            if filter.hit || event["stop"].as_bool() == Some(true) {
+                tokens_predicted = event["tokens_predicted"].as_u64().map(|n| n as u32);
+                break 'events;
+            }
+        }
+    }
+
+    let tail = filter.finish();
+    if !tail.is_empty() {
+        emit_token(app, session_id, &tail);
+    }
+    Ok((filter.text, tokens_predicted))
+}
+
+/// Cuts streamed text at the first stop sequence. Text that could still turn
+/// into a stop sequence is held back until the next chunk settles it.
+struct StopFilter {
+    stops: Vec<String>,
+    text: String,
+    emitted: usize,
+    hit: bool,
+}
+
+impl StopFilter {
+    /// Empty stop strings are ignored.
+    fn new(stops: &[String]) -> Self {
+        Self {
+            stops: stops.iter().filter(|s| !s.is_empty()).cloned().collect(),
+            text: String::new(),
+            emitted: 0,
+            hit: false,
+        }
+    }
+
+    /// Add a chunk and return the text that is now safe to show.
+    fn push(&mut self, chunk: &str) -> String {
+        if self.hit {
+            return String::new();
+        }
+        self.text.push_str(chunk);
+
+        let first_hit = self.stops.iter().filter_map(|s| self.text.find(s.as_str())).min();
+        if let Some(pos) = first_hit {
+            self.text.truncate(pos);
+            self.hit = true;
+            return self.release(self.text.len());
+        }
+
+        // Longest tail of the text that is a proper prefix of some stop string
+        let held = self
+            .stops
+            .iter()
+            .filter_map(|stop| {
+                (1..stop.len().min(self.text.len() + 1))
+                    .rev()
+                    .find(|&k| stop.is_char_boundary(k) && self.text.ends_with(&stop[..k]))
+            })
+            .max()
+            .unwrap_or(0);
+        self.release(self.text.len() - held)
+    }
+
+    /// Release whatever was held back once the stream has ended.
+    fn finish(&mut self) -> String {
+        self.release(self.text.len())
+    }
+
+    fn release(&mut self, upto: usize) -> String {
+        let upto = upto.max(self.emitted);
+        let out = self.text[self.emitted..upto].to_string();
+        self.emitted = upto;
+        out
+    }
+}
+
+/// Generated token count from a llama.cpp perf line such as
+/// `llama_perf_context_print:        eval time = 812.3 ms /   127 runs   (...)`.
+/// The prompt eval line is skipped.
+fn parse_llama_eval_tokens(line: &str) -> Option<u32> {
+    let timing = line.split_once("eval time")?.0;
+    if timing.trim_end().ends_with("prompt") {
+        return None;
+    }
+    let after_slash = line.split_once('/')?.1;
+    let runs = after_slash.split_whitespace().collect::<Vec<_>>();
+    match runs.as_slice() {
+        [count, unit, ..] if unit.starts_with("run") || unit.starts_with("token") => count.parse().ok(),
+        _ => None,
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TestResult {
+    pub text: String,
+    pub tokens_generated: u32,
+    pub tokens_per_second: f64,
+    pub time_ms: u64,
+    pub device: String,
+    /// Whether the output was constrained by a GBNF grammar
+    #[serde(default)]
+    pub grammar_applied: bool,
+}
+
+/// Reword a llama.cpp error caused by an invalid grammar, if it is one.
+fn grammar_error(message: &str) -> Option<String> {
+    let lower = message.to_lowercase();
+    (lower.contains("grammar") && (lower.contains("pars") || lower.contains("invalid")))
+        .then(|| format!("llama.cpp rejected the grammar: {}", message.trim()))
+}
+
+#[tauri::command]
+pub async fn test_generate(
+    model_path: String,
+    prompt: String,
+    max_tokens: u32,
+    temperature: f64,
+    top_p: Option<f64>,
+    top_k: Option<u32>,
+    repeat_penalty: Option<f64>,
+    gpu_layers: Option<i32>,
+    system_prompt: Option<String>,
+    context_size: Option<u32>,
+    stop: Option<Vec<String>>,
+    grammar: Option<String>,
+    app: tauri::AppHandle,
+    state: State<'_, AppState>,
+) -> Result<TestResult, ModelError> {
+    if grammar.as_deref().is_some_and(|g| g.trim().is_empty()) {
+        return Err(ModelError::ParseError {
+            format: "test".into(),
+            reason: "Grammar is empty. Provide a GBNF grammar or clear the field.".into(),
+        });
+    }
+
+    let mut messages = Vec::new();
+    if let Some(sys) = system_prompt.filter(|s| !s.trim().is_empty()) {
+        messages.push(ChatMessage { role: "system".into(), content: sys });
+    }
+    messages.push(ChatMessage { role: "user".into(), content: prompt });
+
+    let settings = GenerationSettings {
+        max_tokens,
+        temperature,
+        top_p,
+        top_k,
+        repeat_penalty,
+        gpu_layers,
+        context_size,
+        stop,
+        grammar,
+    };
+    run_generation(model_path, messages, settings, None, app, &state).await
+}
+
+/// Generate a reply to `messages` with the backend matching the model format,
+/// streaming text through `test:token`.
+async fn run_generation(
+    model_path: String,
+    messages: Vec<ChatMessage>,
+    settings: GenerationSettings,
+    session_id: Option<String>,
+    app: tauri::AppHandle,
+    state: &AppState,
+) -> Result<TestResult, ModelError> {
+    let GenerationSettings {
+        max_tokens,
+        temperature,
+        top_p,
+        top_k,
+        repeat_penalty,
+        gpu_layers,
+        context_size,
+        stop,
+        grammar,
+    } = settings;
+    let grammar = grammar.filter(|g| !g.trim().is_empty());
+
+    // Empty stop strings are ignored
+    let stop: Vec<String> = stop.unwrap_or_default().into_iter().filter(|s| !s.is_empty()).collect();
+    let stop_hit = std::sync::Arc::new(std::sync::atomic::AtomicBool::new(false));
+    let path = PathBuf::from(&model_path);
+    let path_for_check = path.clone();
+    let cancel = state.test_cancel.clone();
+    cancel.store(false, std::sync::atomic::Ordering::Relaxed);
+
+    // Detect format: directory → safetensors, .gguf file → gguf, .safetensors file → use parent dir
+    let (format, inference_path) = if path.is_dir() {
+        if !path.join("config.json").exists() {
+            return Err(ModelError::ParseError {
+                format: "test".into(),
+                reason: "Directory must contain config.json for SafeTensors inference.".into(),
+            });
+        }
+        ("safetensors", path)
+    } else if path.extension().map_or(false, |e| e == "gguf") {
+        if !path.exists() {
+            return Err(ModelError::ParseError {
+                format: "test".into(),
+                reason: "GGUF file not found.".into(),
+            });
+        }
+        ("gguf", path)
+    } else if path.extension().map_or(false, |e| e == "safetensors") {
+        let parent = path.parent().ok_or_else(|| ModelError::ParseError {
+            format: "test".into(),
+            reason: "Cannot determine model directory.".into(),
+        })?.to_path_buf();
+        if !parent.join("config.json").exists() {
+            return Err(ModelError::ParseError {
+                format: "test".into(),
+                reason: "SafeTensors inference requires a model directory with config.json. Use the folder path instead.".into(),
+            });
+        }
+        ("safetensors", parent)
+    } else {
+        return Err(ModelError::ParseError {
+            format: "test".into(),
+            reason: "Unsupported format. Use a .gguf file or SafeTensors directory.".into(),
+        });
+    };
+
+    if grammar.is_some() && format != "gguf" {
+        return Err(ModelError::ParseError {
+            format: "test".into(),
+            reason: "Grammar-constrained generation is only supported for GGUF models (llama.cpp).".into(),
+        });
+    }
+
+    // Warn (without blocking) when the tokenizer and embedding sizes disagree
+    if let Ok(check) = check_vocab(&path_for_check) {
+        if let Some(msg) = check.message {
+            let _ = app.emit("test:warning", format!("Vocab mismatch: {}", msg));
+        }
+    }
+
+    let start = std::time::Instant::now();
+
+    // Real generated-token count from the backend, when it reports one
+    let (full_output, device, counted_tokens) = if format == "gguf" {
+        let (ngl_val, gguf_device) = gguf_offload(gpu_layers);
+
+        let chat_template = crate::model::gguf::read_chat_template(&inference_path);
+        let full_prompt = render_chat_prompt(&messages, chat_template.as_deref());
+
+        if let Some(server_binary) = resolve_llama_server(&app) {
+            // ── GGUF: resident llama-server, model stays loaded between requests ──
+            let model_key = inference_path.to_string_lossy().to_string();
+            let port = ensure_llama_server(
+                state, &server_binary, &model_key, &ngl_val, context_size, &cancel,
+            ).await?;
+
+            let mut body = serde_json::json!({
+                "prompt": full_prompt,
+                "n_predict": max_tokens,
+                "temperature": temperature,
+                "stream": true,
+                "cache_prompt": true,
+            });
+            if let Some(tp) = top_p { body["top_p"] = serde_json::json!(tp); }
+            if let Some(tk) = top_k { body["top_k"] = serde_json::json!(tk); }
+            if let Some(rp) = repeat_penalty { body["repeat_penalty"] = serde_json::json!(rp); }
+            if !stop.is_empty() { body["stop"] = serde_json::json!(stop); }
+            if let Some(g) = &grammar { body["grammar"] = serde_json::json!(g); }
+
+            let (output, tokens) =
+                server_completion(&app, port, body, &stop, session_id.as_deref(), &cancel)
+                    .await
+                    .map_err(|e| match (&grammar, e) {
+                        (Some(_), ModelError::ParseError { format, reason }) => ModelError::ParseError {
+                            format,
+                            reason: grammar_error(&reason).unwrap_or(reason),
+                        },
+                        (_, e) => e,
+                    })?;
+            (output, gguf_device, tokens)
+        } else {
+            // ── GGUF: llama-cli fallback, one process per request ──
+            let binary = resolve_llama_cli(&app);
+            let temp_str = format!("{:.2}", temperature);
+            let n_str = max_tokens.to_string();
+
+            let mut args = vec![
+                "-m".to_string(), inference_path.to_string_lossy().to_string(),
+                "-p".to_string(), full_prompt,
+                "-n".to_string(), n_str.clone(),
+                "--temp".to_string(), temp_str.clone(),
+                "-ngl".to_string(), ngl_val,
+                "--no-display-prompt".to_string(),
+                "--simple-io".to_string(),
+            ];
+            // Logging stays on: the llama_perf summary on stderr carries the token count
+
+            if let Some(tp) = top_p {
+                args.push("--top-p".to_string());
+                args.push(format!("{:.2}", tp));
+            }
+            if let Some(tk) = top_k {
+                args.push("--top-k".to_string());
+                args.push(tk.to_string());
+            }
+            if let Some(rp) = repeat_penalty {
+                args.push("--repeat-penalty".to_string());
+                args.push(format!("{:.2}", rp));
+            }
+            if let Some(ctx) = context_size {
+                args.push("-c".to_string());
+                args.push(ctx.to_string());
+            }
+            for seq in &stop {
+                args.push("-r".to_string());
+                args.push(seq.clone());
+            }
+
+            // llama-cli reads grammars from a file; removed once the process exits
+            let grammar_path = match &grammar {
+                Some(g) => {
+                    let p = std::env::temp_dir().join(format!("forgeai-grammar-{}.gbnf", uuid::Uuid::new_v4()));
+                    std::fs::write(&p, g).map_err(ModelError::IoError)?;
+                    args.push("--grammar-file".to_string());
+                    args.push(p.to_string_lossy().to_string());
+                    Some(p)
+                }
+                None => None,
+            };
+
+            let mut child = tokio::process::Command::new(&binary)
+                .args(&args)
+                .stdout(std::process::Stdio::piped())
+                .stderr(std::process::Stdio::piped())
+                .spawn()
+                .map_err(|e| ModelError::ParseError {
+                    format: "test".into(),
+                    reason: if e.kind() == std::io::ErrorKind::NotFound {
+                        "llama-cli not found. Install llama.cpp tools via Settings > Tools.".into()
+                    } else {
+                        format!("Failed to start inference: {}", e)
+                    },
+                })?;
+
+            if let Some(pid) = child.id() {
+                *state.test_pid.lock().unwrap() = Some(pid);
+            }
+
+            let stdout = child.stdout.take();
+            let app_out = app.clone();
+            let cancel_out = cancel.clone();
+            let stop_out = stop_hit.clone();
+            let session_out = session_id.clone();
+            let mut filter = StopFilter::new(&stop);
+
+            let output_handle = tokio::spawn(async move {
+                if let Some(stdout) = stdout {
+                    let mut reader = tokio::io::BufReader::new(stdout);
+                    let mut buf = [0u8; 256];
+                    loop {
+                        if cancel_out.load(std::sync::atomic::Ordering::Relaxed) {
+                            break;
+                        }
+                        match reader.read(&mut buf).await {
+                            Ok(0) => break,
+                            Ok(n) => {
+                                let chunk = String::from_utf8_lossy(&buf[..n]).to_string();
+                                let visible = filter.push(&chunk);
+                                if !visible.is_empty() {
+                                    emit_token(&app_out, session_out.as_deref(), &visible);
+                                }
+                                if filter.hit {
+                                    stop_out.store(true, std::sync::atomic::Ordering::Relaxed);
+                                    break;
+                                }
+                            }
+                            Err(_) => break,
+                        }
+                    }
+                }
+                let tail = filter.finish();
+                if !tail.is_empty() {
+                    emit_token(&app_out, session_out.as_deref(), &tail);
+                }
+                filter.text
+            });
+
+            let stderr = child.stderr.take();
+            let err_handle = tokio::spawn(async move {
+                let mut last_err = String::new();
+                let mut grammar_err = None;
+                let mut eval_tokens = None;
+                if let Some(stderr) = stderr {
+                    let reader = tokio::io::BufReader::new(stderr);
+                    let mut lines = reader.lines();
+                    while let Ok(Some(line)) = lines.next_line().await {
+                        if let Some(n) = parse_llama_eval_tokens(&line) {
+                            eval_tokens = Some(n);
+                        } else if !line.trim().is_empty() {
+                            // The first grammar complaint names the bad rule; later lines are fallout
+                            if grammar_err.is_none() {
+                                grammar_err = grammar_error(&line);
+                            }
+                            last_err = line;
+                        }
+                    }
+                }
+                (grammar_err.unwrap_or(last_err), eval_tokens)
+            });
+
+            let status = loop {
+                tokio::select! {
+                    result = child.wait() => {
+                        break result.map_err(|e| ModelError::ParseError {
+                            format: "test".into(),
+                            reason: format!("Process error: {}", e),
+                        })?;
+                    }
+                    _ = tokio::time::sleep(std::time::Duration::from_millis(500)) => {
+                        if cancel.load(std::sync::atomic::Ordering::Relaxed) {
+                            child.kill().await.ok();
+                            *state.test_pid.lock().unwrap() = None;
+                            if let Some(p) = &grammar_path {
+                                let _ = std::fs::remove_file(p);
+                            }
+                            return Err(ModelError::ParseError {
+                                format: "test".into(),
+                                reason: "Generation cancelled".into(),
+                            });
+                        }
+                        if stop_hit.load(std::sync::atomic::Ordering::Relaxed) {
+                            // Stop sequence reached; the rest of the generation is unwanted
+                            child.kill().await.ok();
+                        }
+                    }
+                }
+            };
+
+            let output = output_handle.await.unwrap_or_default();
+            let (last_error, eval_tokens) = err_handle.await.unwrap_or_default();
+            *state.test_pid.lock().unwrap() = None;
+            if let Some(p) = &grammar_path {
+                let _ = std::fs::remove_file(p);
+            }
+
+            let stopped = stop_hit.load(std::sync::atomic::Ordering::Relaxed);
+            if !status.success() && output.is_empty() && !stopped {
+                return Err(ModelError::ParseError {
+                    format: "test".into(),
+                    reason: if last_error.is_empty() {
+                        format!("Inference failed (exit code {:?})", status.code())
+                    } else {
+                        format!("Inference failed: {}", last_error)
+                    },
+                });
+            }
+
+            (output, gguf_device, eval_tokens)
+        }
+    } else {
+        // ── SafeTensors: use Python transformers ──
+        // Prefer training venv (has bitsandbytes, accelerate, peft for quantized models),
+        // fall back to convert venv
+        let training_dir = crate::training::venv::get_training_dir(&app)?;
+        let training_python = crate::training::venv::get_venv_python(&training_dir);
+
+        let convert_dir = get_convert_dir(&app)?;
+        let convert_python = get_venv_python(&convert_dir);
+
+        let venv_python = if training_python.exists() {
+            training_python
+        } else if convert_python.exists() {
+            convert_python
+        } else {
+            return Err(ModelError::ParseError {
+                format: "test".into(),
+                reason: "Python environment not set up. Install dependencies via the TRAINING or CONVERT page first.".into(),
+            });
+        };
+
+        let script = r#"
+import sys, json, os, warnings, gc
+warnings.filterwarnings("ignore")
+os.environ["PYTORCH_CUDA_ALLOC_CONF"] = "expandable_segments:True"
+# Ops MPS lacks run on the CPU instead of raising; must be set before importing torch
+os.environ.setdefault("PYTORCH_ENABLE_MPS_FALLBACK", "1")
+import torch
+
+from transformers import AutoModelForCausalLM, AutoTokenizer, TextIteratorStreamer, BitsAndBytesConfig
+from threading import Thread
+
+path, prompt = sys.argv[1], sys.argv[2]
+max_tok, temp = int(sys.argv[3]), float(sys.argv[4])
+opts = json.loads(sys.argv[5]) if len(sys.argv) > 5 else {}
+
+force_cpu = opts.get("gpu_layers", -1) == 0
+has_cuda = torch.cuda.is_available() and not force_cpu
+has_mps = (
+    not has_cuda and not force_cpu
+    and getattr(torch.backends, "mps", None) is not None
+    and torch.backends.mps.is_available()
+)
+
+# Check if model has quantization config (4-bit/8-bit fine-tuned)
+import json as _json
+config_path = os.path.join(path, "config.json")
+is_quantized = False
+if os.path.exists(config_path):
     with open(config_path) as f:
         cfg = _json.load(f)
     is_quantized = "quantization_config" in cfg
 
 tok = AutoTokenizer.from_pretrained(path, trust_remote_code=True)
-if tok.pad_token is None:
-    tok.pad_token = tok.eos_token
+if tok.pad_token is None:
+    tok.pad_token = tok.eos_token
+
+device = "cpu"
+model = None
+
+if has_cuda:
+    torch.cuda.empty_cache()
+    gc.collect()
+    try:
+        if is_quantized:
+            # Quantized models: use device_map="auto" (requires accelerate)
+            model = AutoModelForCausalLM.from_pretrained(
+                path, device_map="auto", trust_remote_code=True, low_cpu_mem_usage=True,
+                ignore_mismatched_sizes=True,
+            )
+            device = "cuda"
+        else:
+            model = AutoModelForCausalLM.from_pretrained(
+                path, torch_dtype=torch.float16, low_cpu_mem_usage=True, trust_remote_code=True,
+                ignore_mismatched_sizes=True,
+            ).to("cuda")
+            device = "cuda"
+    except Exception:
+        gc.collect()
+        torch.cuda.empty_cache()
+        device = "cpu"
+        model = None
+
+# bitsandbytes quantization has no MPS kernels, so quantized models stay on CPU
+if model is None and has_mps and not is_quantized:
+    try:
+        model = AutoModelForCausalLM.from_pretrained(
+            path, torch_dtype=torch.float16, low_cpu_mem_usage=True, trust_remote_code=True,
+            ignore_mismatched_sizes=True,
+        ).to("mps")
+        device = "mps"
+    except Exception:
+        model = None
+        gc.collect()
+        torch.mps.empty_cache()
+        device = "cpu"
+
+if model is None:
+    if is_quantized:
+        try:
+            model = AutoModelForCausalLM.from_pretrained(
+                path, device_map="cpu", trust_remote_code=True, low_cpu_mem_usage=True,
+                ignore_mismatched_sizes=True,
+                quantization_config=BitsAndBytesConfig(load_in_8bit=False, load_in_4bit=False) if not has_cuda else None,
+            )
+        except Exception:
+            # Last resort: force no quantization
+            model = AutoModelForCausalLM.from_pretrained(
+                path, torch_dtype=torch.float32, low_cpu_mem_usage=True, trust_remote_code=True,
+                ignore_mismatched_sizes=True,
+            )
+    else:
+        model = AutoModelForCausalLM.from_pretrained(
+            path, torch_dtype=torch.float32, low_cpu_mem_usage=True, trust_remote_code=True,
+            ignore_mismatched_sizes=True,
+        )
+
+sys.stderr.write(f"[device:{device}]\n")
+sys.stderr.flush()
+model.eval()
+
+# Build prompt from the full message history — try chat template first, fall back to manual
+messages = opts.get("messages") or [{"role": "user", "content": prompt}]
+try:
+    full = tok.apply_chat_template(messages, tokenize=False, add_generation_prompt=True)
+except Exception:
+    sys_prompt = next((m["content"] for m in messages if m["role"] == "system"), "")
+    turns = [m for m in messages if m["role"] != "system"]
+    if len(turns) > 1:
+        full = "".join(f"{m['role'].capitalize()}: {m['content']}\n" for m in messages) + "Assistant:"
+    elif sys_prompt:
+        full = f"[INST] <<SYS>>\n{sys_prompt}\n<</SYS>>\n\n{prompt} [/INST]"
+    else:
+        full = prompt
+
+ids = tok(full, return_tensors="pt")
+if device == "cuda" and not is_quantized:
+    ids = ids.to("cuda")
+elif device == "cuda" and is_quantized:
+    ids = ids.to(model.device)
+elif device == "mps":
+    ids = ids.to("mps")
+
+class CountingStreamer(TextIteratorStreamer):
+    # Counts generated token ids as they arrive, excluding the prompt
+    n_tokens = 0
+
+    def put(self, value):
+        if not (self.skip_prompt and self.next_tokens_are_prompt):
+            self.n_tokens += value.numel()
+        super().put(value)
+
+streamer = CountingStreamer(tok, skip_prompt=True, skip_special_tokens=True)
+gen_kwargs = dict(**ids, max_new_tokens=max_tok, temperature=max(temp, 0.01), do_sample=temp > 0, streamer=streamer)
+
+if "top_p" in opts and opts["top_p"] is not None:
+    gen_kwargs["top_p"] = opts["top_p"]
+if "top_k" in opts and opts["top_k"] is not None:
+    gen_kwargs["top_k"] = opts["top_k"]
+if "repeat_penalty" in opts and opts["repeat_penalty"] is not None:
+    gen_kwargs["repetition_penalty"] = opts["repeat_penalty"]
+
+stops = [s for s in (opts.get("stop") or []) if s]
+if stops:
+    from transformers import StoppingCriteria, StoppingCriteriaList
+    prompt_len = ids["input_ids"].shape[1]
+
+    class StopOnStrings(StoppingCriteria):
+        def __call__(self, input_ids, scores, **kwargs):
+            text = tok.decode(input_ids[0][prompt_len:], skip_special_tokens=True)
+            return any(s in text for s in stops)
+
+    gen_kwargs["stopping_criteria"] = StoppingCriteriaList([StopOnStrings()])
+
+thread = Thread(target=model.generate, kwargs=gen_kwargs)
+thread.start()
+for text in streamer:
+    sys.stdout.write(text)
+    sys.stdout.flush()
+thread.join()
+sys.stderr.write(f"[tokens:{streamer.n_tokens}]\n")
+sys.stderr.flush()
+"#;
+
+        let temp_str = format!("{:.2}", temperature);
+        let n_str = max_tokens.to_string();
+
+        // Build extra options JSON for Python
+        let mut py_opts = serde_json::json!({});
+        if let Some(tp) = top_p { py_opts["top_p"] = serde_json::json!(tp); }
+        if let Some(tk) = top_k { py_opts["top_k"] = serde_json::json!(tk); }
+        if let Some(rp) = repeat_penalty { py_opts["repeat_penalty"] = serde_json::json!(rp); }
+        if let Some(gl) = gpu_layers { py_opts["gpu_layers"] = serde_json::json!(gl); }
+        py_opts["messages"] = serde_json::json!(messages);
+        if !stop.is_empty() { py_opts["stop"] = serde_json::json!(stop); }
+        let py_opts_str = py_opts.to_string();
+
+        let prompt = messages.last().map(|m| m.content.clone()).unwrap_or_default();
+        let mut child = tokio::process::Command::new(&venv_python)
+            .args(["-c", script, &inference_path.to_string_lossy(), &prompt, &n_str, &temp_str, &py_opts_str])
+            .stdout(std::process::Stdio::piped())
+            .stderr(std::process::Stdio::piped())
+            .spawn()
+            .map_err(|e| ModelError::ParseError {
+                format: "test".into(),
+                reason: format!("Failed to start Python inference: {}", e),
+            })?;
+
+        if let Some(pid) = child.id() {
+            *state.test_pid.lock().unwrap() = Some(pid);
+        }
+
+        let stdout = child.stdout.take();
+        let app_out = app.clone();
+        let cancel_out = cancel.clone();
+        let stop_out = stop_hit.clone();
+        let session_out = session_id.clone();
+        let mut filter = StopFilter::new(&stop);
+
+        let output_handle = tokio::spawn(async move {
+            if let Some(stdout) = stdout {
+                let mut reader = tokio::io::BufReader::new(stdout);
+                let mut buf = [0u8; 256];
+                loop {
+                    if cancel_out.load(std::sync::atomic::Ordering::Relaxed) {
+                        break;
+                    }
+                    match reader.read(&mut buf).await {
+                        Ok(0) => break,
+                        Ok(n) => {
+                            let chunk = String::from_utf8_lossy(&buf[..n]).to_string();
+                            let visible = filter.push(&chunk);
+                            if !visible.is_empty() {
+                                emit_token(&app_out, session_out.as_deref(), &visible);
+                            }
+                            if filter.hit {
+                                stop_out.store(true, std::sync::atomic::Ordering::Relaxed);
+                                break;
+                            }
+                        }
+                        Err(_) => break,
+                    }
+                }
+            }
+            let tail = filter.finish();
+            if !tail.is_empty() {
+                emit_token(&app_out, session_out.as_deref(), &tail);
+            }
+            filter.text
+        });
+
+        let stderr = child.stderr.take();
+        let err_handle = tokio::spawn(async move {
+            let mut stderr_lines: Vec<String> = Vec::new();
+            let mut detected_device = String::new();
+            let mut generated_tokens = None;
+            if let Some(stderr) = stderr {
+                let reader = tokio::io::BufReader::new(stderr);
+                let mut lines = reader.lines();
+                while let Ok(Some(line)) = lines.next_line().await {
+                    if line.starts_with("[device:") && line.ends_with(']') {
+                        detected_device = line[8..line.len()-1].to_uppercase();
+                    } else if line.starts_with("[tokens:") && line.ends_with(']') {
+                        generated_tokens = line[8..line.len()-1].parse::<u32>().ok();
+                    } else if !line.trim().is_empty() {
+                        stderr_lines.push(line);
+                    }
+                }
+            }
+            // Extract meaningful error: find last traceback or last meaningful lines
+            let error_msg = {
+                let mut tb_start: Option<usize> = None;
+                for (i, l) in stderr_lines.iter().enumerate() {
+                    if l.starts_with("Traceback (most recent call last)") {
+                        tb_start = Some(i);
+                    }
+                }
+                if let Some(start) = tb_start {
+                    stderr_lines[start..].iter().take(30).cloned().collect::<Vec<_>>().join("\n")
+                } else {
+                    stderr_lines.iter().rev()
+                        .filter(|l| !l.starts_with("WARNING") && !l.contains("FutureWarning") && !l.contains("UserWarning"))
+                        .take(5)
+                        .cloned()
+                        .collect::<Vec<_>>()
+                        .into_iter().rev().collect::<Vec<_>>().join("\n")
+                }
+            };
+            (error_msg, detected_device, generated_tokens)
+        });
+
+        let status = loop {
+            tokio::select! {
+                result = child.wait() => {
+                    break result.map_err(|e| ModelError::ParseError {
+                        format: "test".into(),
+                        reason: format!("Process error: {}", e),
+                    })?;
+                }
+                _ = tokio::time::sleep(std::time::Duration::from_millis(500)) => {
+                    if cancel.load(std::sync::atomic::Ordering::Relaxed) {
+                        child.kill().await.ok();
+                        *state.test_pid.lock().unwrap() = None;
+                        return Err(ModelError::ParseError {
+                            format: "test".into(),
+                            reason: "Generation cancelled".into(),
+                        });
+                    }
+                    if stop_hit.load(std::sync::atomic::Ordering::Relaxed) {
+                        // Stop sequence reached; the rest of the generation is unwanted
+                        child.kill().await.ok();
+                    }
+                }
+            }
+        };
+
+        let output = output_handle.await.unwrap_or_default();
+        let (last_error, py_device, py_tokens) = err_handle.await.unwrap_or_default();
+        *state.test_pid.lock().unwrap() = None;
+
+        let stopped = stop_hit.load(std::sync::atomic::Ordering::Relaxed);
+        if !status.success() && output.is_empty() && !stopped {
+            return Err(ModelError::ParseError {
+                format: "test".into(),
+                reason: if last_error.is_empty() {
+                    format!("Inference failed (exit code {:?})", status.code())
+                } else {
+                    format!("Inference failed: {}", last_error)
+                },
+            });
+        }
+
+        let st_device = if py_device.is_empty() { "CPU".to_string() } else { py_device };
+        (output, st_device, py_tokens)
+    };
+
+    let elapsed = start.elapsed().as_millis() as u64;
+    let token_count = counted_tokens.unwrap_or_else(|| full_output.split_whitespace().count() as u32);
+    let tokens_per_second = if elapsed > 0 {
+        token_count as f64 * 1000.0 / elapsed as f64
+    } else {
+        0.0
+    };
+
+    let _ = app.emit("test:done", &full_output);
+
+    Ok(TestResult {
+        text: full_output,
+        tokens_generated: token_count,
+        tokens_per_second,
+        time_ms: elapsed,
+        device,
+        grammar_applied: grammar.is_some(),
+    })
+}
+
+// ── Chat Sessions ──────────────────────────────────────
+
+/// Open a chat with `model_path` and return its session id.
+#[tauri::command]
+pub fn chat_start(
+    model_path: String,
+    system_prompt: Option<String>,
+    settings: GenerationSettings,
+    state: State<'_, AppState>,
+) -> String {
+    let session_id = uuid::Uuid::new_v4().to_string();
+    state.chat_sessions.lock().unwrap().insert(session_id.clone(), ChatSession {
+        model_path,
+        system_prompt: system_prompt.filter(|s| !s.trim().is_empty()),
+        settings,
+        messages: Vec::new(),
+    });
+    session_id
+}
+
+fn chat_not_found(session_id: &str) -> ModelError {
+    ModelError::ParseError {
+        format: "chat".into(),
+        reason: format!("Chat session not found: {}", session_id),
+    }
+}
+
+/// Send a user message, generate over the whole history and record the reply.
+/// A failed or cancelled turn leaves the history unchanged.
+#[tauri::command]
+pub async fn chat_send(
+    session_id: String,
+    message: String,
+    app: tauri::AppHandle,
+    state: State<'_, AppState>,
+) -> Result<TestResult, ModelError> {
+    let session = state
+        .chat_sessions
+        .lock()
+        .unwrap()
+        .get(&session_id)
+        .cloned()
+        .ok_or_else(|| chat_not_found(&session_id))?;
+
+    let user = ChatMessage { role: "user".into(), content: message };
+    let mut messages = Vec::new();
+    if let Some(sys) = session.system_prompt.clone() {
+        messages.push(ChatMessage { role: "system".into(), content: sys });
+    }
+    messages.extend(session.messages.iter().cloned());
+    messages.push(user.clone());
+
+    let result = run_generation(
+        session.model_path,
+        messages,
+        session.settings,
+        Some(session_id.clone()),
+        app,
+        &state,
+    )
+    .await?;
+
+    if let Some(session) = state.chat_sessions.lock().unwrap().get_mut(&session_id) {
+        session.messages.push(user);
+        session.messages.push(ChatMessage { role: "assistant".into(), content: result.text.clone() });
+    }
+    Ok(result)
+}
+
+/// Clear a session's history, keeping its model and settings.
+#[tauri::command]
+pub fn chat_reset(session_id: String, state: State<'_, AppState>) -> Result<(), ModelError> {
+    let mut sessions = state.chat_sessions.lock().unwrap();
+    let session = sessions.get_mut(&session_id).ok_or_else(|| chat_not_found(&session_id))?;
+    session.messages.clear();
+    Ok(())
+}
+
+#[tauri::command]
+pub fn chat_end(session_id: String, state: State<'_, AppState>) {
+    state.chat_sessions.lock().unwrap().remove(&session_id);
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SmokeTestResult {
+    pub passed: bool,
+    pub output: String,
+    pub tokens_generated: u32,
+    pub time_ms: u64,
+    pub device: String,
+    pub error: Option<String>,
+}
+
+/// Run a tiny greedy generation and report whether the model produced any
+/// non-whitespace output without crashing. Meant as a post-pipeline health check.
+#[tauri::command]
+pub async fn smoke_test(
+    model_path: String,
+    app: tauri::AppHandle,
+    state: State<'_, AppState>,
+) -> Result<SmokeTestResult, ModelError> {
+    let result = test_generate(
+        model_path,
+        "The capital of France is".into(),
+        8,
+        0.0,
+        None,
+        None,
+        None,
+        None,
+        None,
+        Some(512),
+        None,
+        None,
+        app,
+        state,
+    )
+    .await;
+
+    Ok(match result {
+        Ok(r) => {
+            let passed = !r.text.trim().is_empty();
+            SmokeTestResult {
+                passed,
+                output: r.text,
+                tokens_generated: r.tokens_generated,
+                time_ms: r.time_ms,
+                device: r.device,
+                error: if passed { None } else { Some("Model produced no output".into()) },
+            }
+        }
+        Err(e) => SmokeTestResult {
+            passed: false,
+            output: String::new(),
+            tokens_generated: 0,
+            time_ms: 0,
+            device: String::new(),
+            error: Some(e.to_string()),
+        },
+    })
+}
+
+/// Load a GGUF into a resident llama-server ahead of the first generation.
+#[tauri::command]
+pub async fn test_server_start(
+    model_path: String,
+    gpu_layers: Option<i32>,
+    context_size: Option<u32>,
+    app: tauri::AppHandle,
+    state: State<'_, AppState>,
+) -> Result<ServerStatus, ModelError> {
+    let binary = resolve_llama_server(&app).ok_or_else(|| ModelError::ParseError {
+        format: "test".into(),
+        reason: "llama-server not found. Install llama.cpp tools via Settings > Tools.".into(),
+    })?;
+    let cancel = state.test_cancel.clone();
+    cancel.store(false, std::sync::atomic::Ordering::Relaxed);
+
+    let (ngl_val, _) = gguf_offload(gpu_layers);
+    ensure_llama_server(&state, &binary, &model_path, &ngl_val, context_size, &cancel).await?;
+    Ok(server_status(&state))
+}
+
+#[tauri::command]
+pub fn test_server_stop(state: State<'_, AppState>) -> ServerStatus {
+    stop_llama_server(&state);
+    server_status(&state)
+}
+
+#[tauri::command]
+pub fn test_server_status(state: State<'_, AppState>) -> ServerStatus {
+    server_status(&state)
+}
+
+#[tauri::command]
+pub async fn test_cancel(state: State<'_, AppState>) -> Result<(), ModelError> {
+    state.test_cancel.store(true, std::sync::atomic::Ordering::Relaxed);
+
+    if let Some(pid) = *state.test_pid.lock().unwrap() {
+        #[cfg(unix)]
+        {
+            let _ = std::process::Command::new("kill")
+                .arg(pid.to_string())
+                .output();
+        }
+        #[cfg(windows)]
+        {
+            let _ = std::process::Command::new("taskkill")
+                .args(["/PID", &pid.to_string(), "/T", "/F"])
+                .output();
+        }
+    }
+
+    Ok(())
+}
+
+// ── Perplexity ─────────────────────────────────────────
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PerplexityResult {
+    pub perplexity: f64,
+    pub tokens: u64,
+    pub chunks: u32,
+    pub elapsed_ms: u64,
+    /// "llama.cpp" or "transformers"
+    pub backend: String,
+}
+
+/// Running estimate after `chunk` chunks, emitted as `perplexity:progress`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PerplexityProgress {
+    pub chunk: u32,
+    pub total_chunks: Option<u32>,
+    pub perplexity: f64,
+}
+
+/// Sliding-window perplexity over non-overlapping `ctx`-token chunks, printing
+/// one JSON line per chunk and a final summary.
+const PERPLEXITY_SCRIPT: &str = r#"
+import sys, json, math, torch, warnings
+warnings.filterwarnings("ignore")
+from transformers import AutoModelForCausalLM, AutoTokenizer
+
+path, text_path, ctx = sys.argv[1], sys.argv[2], int(sys.argv[3])
+opts = json.loads(sys.argv[4]) if len(sys.argv) > 4 else {}
+device = "cuda" if torch.cuda.is_available() and opts.get("gpu_layers", -1) != 0 else "cpu"
+
+tok = AutoTokenizer.from_pretrained(path, trust_remote_code=True)
+model = AutoModelForCausalLM.from_pretrained(
+    path, trust_remote_code=True, low_cpu_mem_usage=True,
+    torch_dtype=torch.float16 if device == "cuda" else torch.float32,
+).to(device)
+model.eval()
 
-device = "cpu"
-model = None
+with open(text_path, encoding="utf-8", errors="replace") as f:
+    ids = tok(f.read(), return_tensors="pt").input_ids[0]
+
+chunks = len(ids) // ctx
+if opts.get("max_chunks"):
+    chunks = min(chunks, int(opts["max_chunks"]))
+if chunks == 0:
+    raise SystemExit(f"Text has {len(ids)} tokens, fewer than one {ctx}-token chunk")
+
+nll, count = 0.0, 0
+for i in range(chunks):
+    window = ids[i * ctx:(i + 1) * ctx].unsqueeze(0).to(device)
+    with torch.no_grad():
+        loss = model(window, labels=window).loss.item()
+    nll += loss * (ctx - 1)
+    count += ctx - 1
+    print(json.dumps({"chunk": i + 1, "total": chunks, "ppl": math.exp(nll / count)}), flush=True)
+
+print(json.dumps({"final": math.exp(nll / count), "tokens": chunks * ctx, "chunks": chunks}), flush=True)
+"#;
 
-if has_cuda:
-    torch.cuda.empty_cache()
-    gc.collect()
-    try:
-        if is_quantized:
-            # Quantized models: use device_map="auto" (requires accelerate)
-            model = AutoModelForCausalLM.from_pretrained(
-                path, device_map="auto", trust_remote_code=True, low_cpu_mem_usage=True,
-                ignore_mismatched_sizes=True,
-            )
-            device = "cuda"
-        else:
-            model = AutoModelForCausalLM.from_pretrained(
-                path, torch_dtype=torch.float16, low_cpu_mem_usage=True, trust_remote_code=True,
-                ignore_mismatched_sizes=True,
-            ).to("cuda")
-            device = "cuda"
-    except Exception:
-        gc.collect()
-        torch.cuda.empty_cache()
-        device = "cpu"
-        model = None
+/// `[N]value` entries from llama-perplexity's running output. Complete entries
+/// are drained from `buf`; a trailing partial entry is kept for the next read.
+fn drain_ppl_updates(buf: &mut String) -> Vec<(u32, f64)> {
+    let cut = buf.rfind([',', '\n']).map(|i| i + 1).unwrap_or(0);
+    let complete: String = buf.drain(..cut).collect();
+    complete
+        .split([',', '\n'])
+        .filter_map(|piece| {
+            let piece = piece.trim();
+            let rest = piece.strip_prefix('[')?;
+            let (chunk, value) = rest.split_once(']')?;
+            Some((chunk.parse().ok()?, value.trim().parse().ok()?))
+        })
+        .collect()
+}
 
-if model is None:
-    if is_quantized:
-        try:
-            model = AutoModelForCausalLM.from_pretrained(
-                path, device_map="cpu", trust_remote_code=True, low_cpu_mem_usage=True,
-                ignore_mismatched_sizes=True,
-                quantization_config=BitsAndBytesConfig(load_in_8bit=False, load_in_4bit=False) if not has_cuda else None,
-            )
-        except Exception:
-            # Last resort: force no quantization
-            model = AutoModelForCausalLM.from_pretrained(
-                path, torch_dtype=torch.float32, low_cpu_mem_usage=True, trust_remote_code=True,
-                ignore_mismatched_sizes=True,
-            )
-    else:
-        model = AutoModelForCausalLM.from_pretrained(
-            path, torch_dtype=torch.float32, low_cpu_mem_usage=True, trust_remote_code=True,
-            ignore_mismatched_sizes=True,
-        )
+/// Perplexity of a model on a text file: llama-perplexity for GGUF, a
+/// transformers loop for SafeTensors directories. Lower is better, so running
+/// it before and after a quantize or merge quantifies the degradation.
+#[tauri::command]
+pub async fn compute_perplexity(
+    model_path: String,
+    text_path: String,
+    context_size: Option<u32>,
+    max_chunks: Option<u32>,
+    gpu_layers: Option<i32>,
+    app: tauri::AppHandle,
+    state: State<'_, AppState>,
+) -> Result<PerplexityResult, ModelError> {
+    let fail = |reason: String| ModelError::ParseError { format: "perplexity".into(), reason };
+    let path = PathBuf::from(&model_path);
+    if !path.exists() {
+        return Err(ModelError::FileNotFound(model_path));
+    }
+    if !std::path::Path::new(&text_path).is_file() {
+        return Err(ModelError::FileNotFound(text_path));
+    }
+    let ctx = context_size.unwrap_or(512).max(16);
+    let is_gguf = path.extension().map_or(false, |e| e == "gguf");
+    let model_dir = if path.is_dir() { path.clone() } else { path.parent().map(PathBuf::from).unwrap_or_default() };
+
+    let cancel = state.test_cancel.clone();
+    cancel.store(false, std::sync::atomic::Ordering::Relaxed);
+    let started = std::time::Instant::now();
+
+    let mut child = if is_gguf {
+        let binary = {
+            let name = if cfg!(target_os = "windows") { "llama-perplexity.exe" } else { "llama-perplexity" };
+            get_tools_dir(&app)
+                .ok()
+                .and_then(|dir| find_binary_recursive(&dir, name))
+                .unwrap_or_else(|| PathBuf::from(name))
+        };
+        let (ngl_val, _) = gguf_offload(gpu_layers);
+        let mut cmd = tokio::process::Command::new(&binary);
+        cmd.args(["-m", &model_path, "-f", &text_path, "-c", &ctx.to_string(), "-ngl", &ngl_val]);
+        if let Some(n) = max_chunks {
+            cmd.args(["--chunks", &n.to_string()]);
+        }
+        cmd
+    } else {
+        if !model_dir.join("config.json").exists() {
+            return Err(fail("SafeTensors perplexity needs a model directory with config.json".into()));
+        }
+        let training_python = crate::training::venv::get_venv_python(&crate::training::venv::get_training_dir(&app)?);
+        let convert_python = get_venv_python(&get_convert_dir(&app)?);
+        let venv_python = if training_python.exists() {
+            training_python
+        } else if convert_python.exists() {
+            convert_python
+        } else {
+            return Err(fail("Python environment not set up. Install dependencies via the TRAINING or CONVERT page first.".into()));
+        };
+        let mut opts = serde_json::json!({});
+        if let Some(n) = max_chunks { opts["max_chunks"] = serde_json::json!(n); }
+        if let Some(gl) = gpu_layers { opts["gpu_layers"] = serde_json::json!(gl); }
+        let mut cmd = tokio::process::Command::new(&venv_python);
+        cmd.args([
+            "-c",
+            PERPLEXITY_SCRIPT,
+            &model_dir.to_string_lossy(),
+            &text_path,
+            &ctx.to_string(),
+            &opts.to_string(),
+        ]);
+        cmd
+    }
+    .stdout(std::process::Stdio::piped())
+    .stderr(std::process::Stdio::piped())
+    .spawn()
+    .map_err(|e| fail(format!("Failed to start perplexity run: {}", e)))?;
+
+    if let Some(pid) = child.id() {
+        *state.test_pid.lock().unwrap() = Some(pid);
+    }
+
+    // Both streams feed one channel: llama.cpp splits its progress across them
+    let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel::<String>();
+    let mut readers = Vec::new();
+    let streams: [Option<Box<dyn tokio::io::AsyncRead + Unpin + Send>>; 2] = [
+        child.stdout.take().map(|s| Box::new(s) as _),
+        child.stderr.take().map(|s| Box::new(s) as _),
+    ];
+    for mut stream in streams.into_iter().flatten() {
+        let tx = tx.clone();
+        readers.push(tokio::spawn(async move {
+            let mut buf = [0u8; 4096];
+            while let Ok(n) = stream.read(&mut buf).await {
+                if n == 0 || tx.send(String::from_utf8_lossy(&buf[..n]).to_string()).is_err() {
+                    break;
+                }
+            }
+        }));
+    }
+    drop(tx);
+
+    let mut pending = String::new();
+    let mut log_tail: Vec<String> = Vec::new();
+    let mut total_chunks: Option<u32> = None;
+    let mut last: Option<(u32, f64)> = None;
+    let mut final_ppl: Option<f64> = None;
+    let mut tokens: Option<u64> = None;
+
+    while let Some(text) = rx.recv().await {
+        if cancel.load(std::sync::atomic::Ordering::Relaxed) {
+            let _ = child.kill().await;
+            break;
+        }
+        for line in text.lines() {
+            let line = line.trim();
+            if line.is_empty() {
+                continue;
+            }
+            if let Ok(json) = serde_json::from_str::<serde_json::Value>(line) {
+                // transformers script output
+                if let Some(ppl) = json.get("final").and_then(|v| v.as_f64()) {
+                    final_ppl = Some(ppl);
+                    tokens = json.get("tokens").and_then(|v| v.as_u64());
+                    total_chunks = json.get("chunks").and_then(|v| v.as_u64()).map(|c| c as u32);
+                } else if let (Some(chunk), Some(ppl)) = (
+                    json.get("chunk").and_then(|v| v.as_u64()),
+                    json.get("ppl").and_then(|v| v.as_f64()),
+                ) {
+                    total_chunks = json.get("total").and_then(|v| v.as_u64()).map(|t| t as u32);
+                    last = Some((chunk as u32, ppl));
+                    let _ = app.emit("perplexity:progress", PerplexityProgress {
+                        chunk: chunk as u32,
+                        total_chunks,
+                        perplexity: ppl,
+                    });
+                }
+                continue;
+            }
+            // "calculating perplexity over 655 chunks, n_ctx=512, ..."
+            if let Some(rest) = line.split("perplexity over ").nth(1) {
+                total_chunks = rest.split_whitespace().next().and_then(|n| n.parse().ok());
+            }
+            // "Final estimate: PPL = 5.1234 +/- 0.0321"
+            if let Some(rest) = line.split("PPL = ").nth(1) {
+                final_ppl = rest.split_whitespace().next().and_then(|v| v.parse().ok());
+            }
+            if !line.starts_with('[') {
+                log_tail.push(line.to_string());
+                if log_tail.len() > 20 {
+                    log_tail.remove(0);
+                }
+            }
+        }
+
+        if is_gguf {
+            pending.push_str(&text);
+            for (chunk, ppl) in drain_ppl_updates(&mut pending) {
+                last = Some((chunk, ppl));
+                let _ = app.emit("perplexity:progress", PerplexityProgress { chunk, total_chunks, perplexity: ppl });
+            }
+        }
+    }
+    for reader in readers {
+        let _ = reader.await;
+    }
+
+    let status = child.wait().await;
+    *state.test_pid.lock().unwrap() = None;
+    if cancel.load(std::sync::atomic::Ordering::Relaxed) {
+        return Err(fail("Perplexity run cancelled".into()));
+    }
+
+    let chunks = total_chunks.or(last.map(|(c, _)| c)).unwrap_or(0);
+    match final_ppl.or(last.map(|(_, p)| p)) {
+        Some(perplexity) if status.as_ref().is_ok_and(|s| s.success()) => Ok(PerplexityResult {
+            perplexity,
+            tokens: tokens.unwrap_or(chunks as u64 * ctx as u64),
+            chunks,
+            elapsed_ms: started.elapsed().as_millis() as u64,
+            backend: if is_gguf { "llama.cpp".into() } else { "transformers".into() },
+        }),
+        _ => Err(fail(format!(
+            "Perplexity run failed: {}",
+            log_tail.iter().rev().take(5).rev().cloned().collect::<Vec<_>>().join("\n")
+        ))),
+    }
+}
+
+// ── Quantize ───────────────────────────────────────────
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct QuantizeResult {
+    pub success: bool,
+    pub output_path: String,
+    pub output_size: u64,
+    pub output_size_display: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct QuantizeProgress {
+    /// "converting" (SafeTensors to F16 GGUF) or "quantizing"
+    pub stage: String,
+    pub current: u32,
+    pub total: u32,
+    pub percent: f64,
+    pub tensor: String,
+}
+
+/// Parse a per-tensor line such as
+/// `[  12/ 291]   blk.0.attn_k.weight - [ 4096,  1024, 1, 1], type = f16, ...`.
+fn parse_quantize_progress(line: &str) -> Option<QuantizeProgress> {
+    let rest = line.trim_start().strip_prefix('[')?;
+    let (counts, rest) = rest.split_once(']')?;
+    let (current, total) = counts.split_once('/')?;
+    let current: u32 = current.trim().parse().ok()?;
+    let total: u32 = total.trim().parse().ok()?;
+    let tensor = rest.split_whitespace().next().unwrap_or_default().to_string();
+    Some(QuantizeProgress {
+        stage: "quantizing".into(),
+        current,
+        total,
+        percent: if total > 0 { current as f64 / total as f64 * 100.0 } else { 0.0 },
+        tensor,
+    })
+}
+
+/// Target types `quantize_model` accepts.
+const QUANTIZE_TYPES: &[&str] = &[
+    "IQ1_S", "IQ1_M", "IQ2_XXS", "IQ2_XS", "IQ2_S", "IQ2_M", "IQ3_XXS", "IQ3_XS", "IQ3_S", "IQ3_M",
+    "IQ4_XS", "IQ4_NL", "Q2_K", "Q2_K_S", "Q3_K_S", "Q3_K_M", "Q3_K_L", "Q4_0", "Q4_K_S", "Q4_K_M",
+    "Q4_K_L", "Q5_0", "Q5_K_S", "Q5_K_M", "Q6_K", "Q8_0", "F16",
+];
+
+/// The `bits_per_weight` type used to estimate a target's output size. K-quant
+/// mixes (_S/_M/_L) use their base type; IQ mixes their nearest base type.
+fn quant_size_basis(target_type: &str) -> &str {
+    match target_type {
+        "IQ2_M" => "IQ2_S",
+        "IQ3_XS" => "IQ3_XXS",
+        "IQ3_M" => "IQ3_S",
+        t if t.starts_with("IQ") => t,
+        t => ["_S", "_M", "_L"]
+            .iter()
+            .find_map(|suffix| t.strip_suffix(*suffix))
+            .unwrap_or(t),
+    }
+}
+
+/// Types llama-quantize refuses to produce without an importance matrix.
+/// Other low-bit types (Q2_K, Q3_K_*, Q4_K_*) run without one but lose
+/// noticeably more quality.
+const IMATRIX_REQUIRED_TYPES: &[&str] = &["IQ1_S", "IQ1_M", "IQ2_XXS", "IQ2_XS", "IQ2_S", "Q2_K_S"];
+
+/// Tensor names covered by an imatrix file, in either the GGUF layout
+/// (`<tensor>.in_sum2` entries) or the legacy `.dat` layout (count, then
+/// length-prefixed names each followed by ncall, nval and nval floats).
+fn imatrix_tensor_names(path: &std::path::Path) -> Result<Vec<String>, ModelError> {
+    let invalid = |reason: &str| ModelError::ParseError {
+        format: "imatrix".into(),
+        reason: format!("{}: {}", path.display(), reason),
+    };
+    let file = std::fs::File::open(path).map_err(ModelError::IoError)?;
+    let data = unsafe { memmap2::Mmap::map(&file) }.map_err(ModelError::IoError)?;
+
+    if data.starts_with(b"GGUF") {
+        let layout = crate::merge::tensor_io::read_gguf_layout(&data)?;
+        return Ok(layout
+            .tensors
+            .iter()
+            .filter_map(|t| t.name.strip_suffix(".in_sum2").map(String::from))
+            .collect());
+    }
+
+    let read_i32 = |pos: usize| -> Option<i32> {
+        data.get(pos..pos + 4).map(|b| i32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+    };
+    let count = read_i32(0).filter(|n| *n > 0).ok_or_else(|| invalid("not an imatrix file"))?;
+    let mut pos = 4;
+    let mut names = Vec::with_capacity(count as usize);
+    for _ in 0..count {
+        let len = read_i32(pos).filter(|n| *n > 0).ok_or_else(|| invalid("truncated entry"))? as usize;
+        let name = data.get(pos + 4..pos + 4 + len).ok_or_else(|| invalid("truncated entry"))?;
+        names.push(String::from_utf8_lossy(name).to_string());
+        pos += 4 + len + 4; // name, ncall
+        let nval = read_i32(pos).filter(|n| *n >= 0).ok_or_else(|| invalid("truncated entry"))? as usize;
+        pos += 4 + nval * 4;
+        if pos > data.len() {
+            return Err(invalid("truncated entry"));
+        }
+    }
+    Ok(names)
+}
+
+/// Reject an imatrix that was computed for a different model: most of its
+/// entries must name tensors that exist in `model_path`.
+fn validate_imatrix(imatrix_path: &std::path::Path, model_path: &std::path::Path) -> Result<(), ModelError> {
+    if !imatrix_path.is_file() {
+        return Err(ModelError::FileNotFound(imatrix_path.to_string_lossy().to_string()));
+    }
+    let entries = imatrix_tensor_names(imatrix_path)?;
+
+    let file = std::fs::File::open(model_path).map_err(ModelError::IoError)?;
+    let data = unsafe { memmap2::Mmap::map(&file) }.map_err(ModelError::IoError)?;
+    let layout = crate::merge::tensor_io::read_gguf_layout(&data)?;
+    let model_tensors: std::collections::HashSet<&str> =
+        layout.tensors.iter().map(|t| t.name.as_str()).collect();
+
+    let matched = entries.iter().filter(|n| model_tensors.contains(n.as_str())).count();
+    if entries.is_empty() || matched * 2 < entries.len() {
+        return Err(ModelError::ParseError {
+            format: "imatrix".into(),
+            reason: format!(
+                "Importance matrix does not match this model ({} of {} entries name tensors in it)",
+                matched,
+                entries.len()
+            ),
+        });
+    }
+    Ok(())
+}
+
+/// Rejects types llama-quantize doesn't produce, and imatrix-only types when
+/// no importance matrix was supplied.
+fn validate_quantize_target(target_type: &str, has_imatrix: bool) -> Result<(), ModelError> {
+    if !QUANTIZE_TYPES.contains(&target_type) {
+        return Err(ModelError::ParseError {
+            format: "quantize".into(),
+            reason: format!("Unknown quantization type: {}", target_type),
+        });
+    }
+
+    if !has_imatrix && IMATRIX_REQUIRED_TYPES.contains(&target_type) {
+        return Err(ModelError::ParseError {
+            format: "quantize".into(),
+            reason: format!(
+                "{} requires an importance matrix. Generate one from a calibration text, or pick a K-quant such as Q3_K_S instead.",
+                target_type
+            ),
+        });
+    }
+
+    Ok(())
+}
+
+/// Quantize the loaded model with llama-quantize. A SafeTensors model is first
+/// converted to an intermediate F16 GGUF next to `output_path`, which is
+/// removed afterwards. `imatrix_path` points at an importance matrix from
+/// `generate_imatrix`; it is required for the types in `IMATRIX_REQUIRED_TYPES`
+/// and improves every low-bit K-quant.
+#[tauri::command]
+pub async fn quantize_model(
+    target_type: String,
+    output_path: String,
+    imatrix_path: Option<String>,
+    state: State<'_, AppState>,
+    app: tauri::AppHandle,
+) -> Result<QuantizeResult, ModelError> {
+    // Extract file path and format
+    let (model_path, format, parameter_count) = {
+        let loaded = state.loaded_model.lock().unwrap();
+        let info = loaded.as_ref().ok_or_else(|| ModelError::ParseError {
+            format: "quantize".into(),
+            reason: "No model loaded".into(),
+        })?;
+        (info.file_path.clone(), info.format.clone(), info.parameter_count)
+    };
 
-sys.stderr.write(f"[device:{device}]\n")
-sys.stderr.flush()
-model.eval()
+    let imatrix_path = imatrix_path.filter(|p| !p.trim().is_empty());
+    validate_quantize_target(&target_type, imatrix_path.is_some())?;
 
-# Build prompt — try chat template first, fall back to manual
-sys_prompt = opts.get("system_prompt", "")
-try:
-    messages = []
-    if sys_prompt:
-        messages.append({"role": "system", "content": sys_prompt})
-    messages.append({"role": "user", "content": prompt})
-    full = tok.apply_chat_template(messages, tokenize=False, add_generation_prompt=True)
-except Exception:
-    if sys_prompt:
-        full = f"[INST] <<SYS>>\n{sys_prompt}\n<</SYS>>\n\n{prompt} [/INST]"
-    else:
-        full = prompt
+    let estimated = parameter_count as f64 * crate::model::inspect::bits_per_weight(quant_size_basis(&target_type)) / 8.0 * 1.05;
 
-ids = tok(full, return_tensors="pt")
-if device == "cuda" and not is_quantized:
-    ids = ids.to("cuda")
-elif device == "cuda" and is_quantized:
-    ids = ids.to(model.device)
+    let cancel = state.quantize_cancel.clone();
+    cancel.store(false, std::sync::atomic::Ordering::Relaxed);
 
-streamer = TextIteratorStreamer(tok, skip_prompt=True, skip_special_tokens=True)
-gen_kwargs = dict(**ids, max_new_tokens=max_tok, temperature=max(temp, 0.01), do_sample=temp > 0, streamer=streamer)
+    let intermediate = match format {
+        ModelFormat::Gguf => {
+            ensure_disk_space(std::path::Path::new(&output_path), estimated as u64, "quantize")?;
+            None
+        }
+        ModelFormat::SafeTensors | ModelFormat::PyTorch => {
+            // The F16 intermediate and the quantized output coexist until cleanup
+            let intermediate_size = parameter_count * 2;
+            ensure_disk_space(
+                std::path::Path::new(&output_path),
+                intermediate_size + estimated as u64,
+                "quantize",
+            )?;
+            let path = PathBuf::from(format!("{}.f16-intermediate.gguf", output_path));
+            if let Err(e) = convert_for_quantize(&app, &state, &model_path, &path).await {
+                let _ = std::fs::remove_file(&path);
+                return Err(e);
+            }
+            Some(path)
+        }
+    };
+    let input_path = intermediate
+        .as_ref()
+        .map(|p| p.to_string_lossy().to_string())
+        .unwrap_or(model_path);
+
+    let result = match &imatrix_path {
+        Some(imatrix) => validate_imatrix(std::path::Path::new(imatrix), std::path::Path::new(&input_path)),
+        None => Ok(()),
+    };
+    let result = match result {
+        Ok(()) => run_llama_quantize(&app, &state, &input_path, &output_path, &target_type, imatrix_path.as_deref()).await,
+        Err(e) => Err(e),
+    };
+    if let Some(path) = &intermediate {
+        let _ = std::fs::remove_file(path);
+    }
+    result?;
 
-if "top_p" in opts and opts["top_p"] is not None:
-    gen_kwargs["top_p"] = opts["top_p"]
-if "top_k" in opts and opts["top_k"] is not None:
-    gen_kwargs["top_k"] = opts["top_k"]
-if "repeat_penalty" in opts and opts["repeat_penalty"] is not None:
-    gen_kwargs["repetition_penalty"] = opts["repeat_penalty"]
+    let output_meta = std::fs::metadata(&output_path).map_err(ModelError::IoError)?;
 
-thread = Thread(target=model.generate, kwargs=gen_kwargs)
-thread.start()
-for text in streamer:
-    sys.stdout.write(text)
-    sys.stdout.flush()
-thread.join()
-"#;
+    Ok(QuantizeResult {
+        success: true,
+        output_path,
+        output_size: output_meta.len(),
+        output_size_display: crate::model::format_file_size(output_meta.len()),
+    })
+}
 
-        let temp_str = format!("{:.2}", temperature);
-        let n_str = max_tokens.to_string();
+/// Convert a SafeTensors model to an F16 GGUF at `outfile` with the convert
+/// pipeline, reporting as the "converting" stage of `quantize:progress`.
+async fn convert_for_quantize(
+    app: &tauri::AppHandle,
+    state: &AppState,
+    model_path: &str,
+    outfile: &std::path::Path,
+) -> Result<(), ModelError> {
+    let convert_dir = get_convert_dir(app)?;
+    let venv_python = get_venv_python(&convert_dir);
+    let script_path = get_script_path(&convert_dir);
+    if !venv_python.exists() || !script_path.exists() {
+        return Err(ModelError::ParseError {
+            format: "quantize".into(),
+            reason: "SafeTensors models are converted to GGUF before quantizing, but convert dependencies are not set up. Run setup on the CONVERT page first.".into(),
+        });
+    }
 
-        // Build extra options JSON for Python
-        let mut py_opts = serde_json::json!({});
-        if let Some(tp) = top_p { py_opts["top_p"] = serde_json::json!(tp); }
-        if let Some(tk) = top_k { py_opts["top_k"] = serde_json::json!(tk); }
-        if let Some(rp) = repeat_penalty { py_opts["repeat_penalty"] = serde_json::json!(rp); }
-        if let Some(gl) = gpu_layers { py_opts["gpu_layers"] = serde_json::json!(gl); }
-        if let Some(ref sp) = system_prompt { py_opts["system_prompt"] = serde_json::json!(sp); }
-        let py_opts_str = py_opts.to_string();
+    let model = std::path::Path::new(model_path);
+    let repo_dir = if model.is_dir() { model } else { model.parent().unwrap_or(model) };
 
-        let mut child = tokio::process::Command::new(&venv_python)
-            .args(["-c", script, &inference_path.to_string_lossy(), &prompt, &n_str, &temp_str, &py_opts_str])
-            .stdout(std::process::Stdio::piped())
-            .stderr(std::process::Stdio::piped())
-            .spawn()
-            .map_err(|e| ModelError::ParseError {
-                format: "test".into(),
-                reason: format!("Failed to start Python inference: {}", e),
-            })?;
+    let _ = app.emit(
+        "quantize:progress",
+        QuantizeProgress {
+            stage: "converting".into(),
+            current: 0,
+            total: 0,
+            percent: -1.0,
+            tensor: "Converting to F16 GGUF...".into(),
+        },
+    );
 
-        if let Some(pid) = child.id() {
-            *state.test_pid.lock().unwrap() = Some(pid);
-        }
+    let mut child = tokio::process::Command::new(&venv_python)
+        .arg(&script_path)
+        .arg(repo_dir)
+        .arg("--outtype")
+        .arg("f16")
+        .arg("--outfile")
+        .arg(outfile)
+        .stdout(std::process::Stdio::piped())
+        .stderr(std::process::Stdio::piped())
+        .spawn()
+        .map_err(|e| ModelError::ParseError {
+            format: "quantize".into(),
+            reason: format!("Failed to start conversion: {}", e),
+        })?;
 
-        let stdout = child.stdout.take();
-        let app_out = app.clone();
-        let cancel_out = cancel.clone();
+    if let Some(pid) = child.id() {
+        *state.quantize_pid.lock().unwrap() = Some(pid);
+    }
 
-        let output_handle = tokio::spawn(async move {
-            let mut output = String::new();
-            if let Some(stdout) = stdout {
-                let mut reader = tokio::io::BufReader::new(stdout);
-                let mut buf = [0u8; 256];
-                loop {
-                    if cancel_out.load(std::sync::atomic::Ordering::Relaxed) {
-                        break;
-                    }
-                    match reader.read(&mut buf).await {
-                        Ok(0) => break,
-                        Ok(n) => {
-                            let chunk = String::from_utf8_lossy(&buf[..n]).to_string();
-                            output.push_str(&chunk);
-                            let _ = app_out.emit("test:token", &chunk);
-                        }
-                        Err(_) => break,
-                    }
+    let readers: Vec<_> = [
+        child.stdout.take().map(|s| Box::new(s) as Box<dyn tokio::io::AsyncRead + Unpin + Send>),
+        child.stderr.take().map(|s| Box::new(s) as Box<dyn tokio::io::AsyncRead + Unpin + Send>),
+    ]
+    .into_iter()
+    .flatten()
+    .map(|stream| {
+        let app = app.clone();
+        tokio::spawn(async move {
+            let mut last_error = String::new();
+            let mut lines = tokio::io::BufReader::new(stream).lines();
+            while let Ok(Some(line)) = lines.next_line().await {
+                if line.trim().is_empty() {
+                    continue;
+                }
+                let progress = parse_convert_progress(&line);
+                if progress.stage == "error" {
+                    last_error = line.clone();
                 }
+                let _ = app.emit(
+                    "quantize:progress",
+                    QuantizeProgress {
+                        stage: "converting".into(),
+                        current: 0,
+                        total: 0,
+                        percent: progress.percent,
+                        tensor: progress.message,
+                    },
+                );
             }
-            output
-        });
+            last_error
+        })
+    })
+    .collect();
 
-        let stderr = child.stderr.take();
-        let err_handle = tokio::spawn(async move {
-            let mut stderr_lines: Vec<String> = Vec::new();
-            let mut detected_device = String::new();
-            if let Some(stderr) = stderr {
-                let reader = tokio::io::BufReader::new(stderr);
-                let mut lines = reader.lines();
-                while let Ok(Some(line)) = lines.next_line().await {
-                    if line.starts_with("[device:") && line.ends_with(']') {
-                        detected_device = line[8..line.len()-1].to_uppercase();
-                    } else if !line.trim().is_empty() {
-                        stderr_lines.push(line);
-                    }
-                }
+    let cancel = state.quantize_cancel.clone();
+    let status = loop {
+        tokio::select! {
+            result = child.wait() => {
+                break result.map_err(ModelError::IoError)?;
             }
-            // Extract meaningful error: find last traceback or last meaningful lines
-            let error_msg = {
-                let mut tb_start: Option<usize> = None;
-                for (i, l) in stderr_lines.iter().enumerate() {
-                    if l.starts_with("Traceback (most recent call last)") {
-                        tb_start = Some(i);
-                    }
-                }
-                if let Some(start) = tb_start {
-                    stderr_lines[start..].iter().take(30).cloned().collect::<Vec<_>>().join("\n")
-                } else {
-                    stderr_lines.iter().rev()
-                        .filter(|l| !l.starts_with("WARNING") && !l.contains("FutureWarning") && !l.contains("UserWarning"))
-                        .take(5)
-                        .cloned()
-                        .collect::<Vec<_>>()
-                        .into_iter().rev().collect::<Vec<_>>().join("\n")
+            _ = tokio::time::sleep(std::time::Duration::from_millis(500)) => {
+                if cancel.load(std::sync::atomic::Ordering::Relaxed) {
+                    child.kill().await.ok();
+                    *state.quantize_pid.lock().unwrap() = None;
+                    return Err(ModelError::ParseError {
+                        format: "quantize".into(),
+                        reason: "Quantization cancelled".into(),
+                    });
                 }
-            };
-            (error_msg, detected_device)
+            }
+        }
+    };
+
+    let mut last_error = String::new();
+    for reader in readers {
+        let err = reader.await.unwrap_or_default();
+        if !err.is_empty() {
+            last_error = err;
+        }
+    }
+    *state.quantize_pid.lock().unwrap() = None;
+
+    if !status.success() {
+        return Err(ModelError::ParseError {
+            format: "quantize".into(),
+            reason: if last_error.is_empty() {
+                format!("Conversion to GGUF failed with exit code: {:?}", status.code())
+            } else {
+                format!("Conversion to GGUF failed: {}", last_error)
+            },
         });
+    }
+    Ok(())
+}
 
-        let status = loop {
-            tokio::select! {
-                result = child.wait() => {
-                    break result.map_err(|e| ModelError::ParseError {
-                        format: "test".into(),
-                        reason: format!("Process error: {}", e),
-                    })?;
-                }
-                _ = tokio::time::sleep(std::time::Duration::from_millis(500)) => {
-                    if cancel.load(std::sync::atomic::Ordering::Relaxed) {
-                        child.kill().await.ok();
-                        *state.test_pid.lock().unwrap() = None;
-                        return Err(ModelError::ParseError {
-                            format: "test".into(),
-                            reason: "Generation cancelled".into(),
-                        });
-                    }
+/// Run llama-quantize on a GGUF, reporting as the "quantizing" stage of
+/// `quantize:progress`. A cancelled run removes the partial output.
+async fn run_llama_quantize(
+    app: &tauri::AppHandle,
+    state: &AppState,
+    input_path: &str,
+    output_path: &str,
+    target_type: &str,
+    imatrix_path: Option<&str>,
+) -> Result<(), ModelError> {
+    // Resolve binary: bundled takes priority, then PATH
+    let binary = resolve_quantize_binary(app);
+
+    let _ = app.emit(
+        "quantize:progress",
+        QuantizeProgress {
+            stage: "quantizing".into(),
+            current: 0,
+            total: 0,
+            percent: 0.0,
+            tensor: String::new(),
+        },
+    );
+
+    let mut cmd = tokio::process::Command::new(&binary);
+    if let Some(imatrix) = imatrix_path {
+        cmd.arg("--imatrix").arg(imatrix);
+    }
+    // Q4_K_L is Q4_K_M with the embedding and output tensors kept at Q8_0
+    let llama_type = if target_type == "Q4_K_L" {
+        cmd.args(["--token-embedding-type", "q8_0", "--output-tensor-type", "q8_0"]);
+        "Q4_K_M"
+    } else {
+        target_type
+    };
+    let mut child = cmd
+        .arg(input_path)
+        .arg(output_path)
+        .arg(llama_type)
+        .stdout(std::process::Stdio::piped())
+        .stderr(std::process::Stdio::piped())
+        .spawn()
+        .map_err(|e| {
+            if e.kind() == std::io::ErrorKind::NotFound {
+                ModelError::ParseError {
+                    format: "quantize".into(),
+                    reason: "llama-quantize not found. Install it via Settings > Tools or add it to your PATH.".into(),
                 }
+            } else {
+                ModelError::IoError(e)
             }
-        };
+        })?;
 
-        let output = output_handle.await.unwrap_or_default();
-        let (last_error, py_device) = err_handle.await.unwrap_or_default();
-        *state.test_pid.lock().unwrap() = None;
+    if let Some(pid) = child.id() {
+        *state.quantize_pid.lock().unwrap() = Some(pid);
+    }
 
-        if !status.success() && output.is_empty() {
-            return Err(ModelError::ParseError {
-                format: "test".into(),
-                reason: if last_error.is_empty() {
-                    format!("Inference failed (exit code {:?})", status.code())
+    // llama-quantize logs to both streams; keep the tail of each for errors
+    let readers: Vec<_> = [
+        child.stdout.take().map(|s| Box::new(s) as Box<dyn tokio::io::AsyncRead + Unpin + Send>),
+        child.stderr.take().map(|s| Box::new(s) as Box<dyn tokio::io::AsyncRead + Unpin + Send>),
+    ]
+    .into_iter()
+    .flatten()
+    .map(|stream| {
+        let app = app.clone();
+        tokio::spawn(async move {
+            let mut tail: Vec<String> = Vec::new();
+            let mut lines = tokio::io::BufReader::new(stream).lines();
+            while let Ok(Some(line)) = lines.next_line().await {
+                if line.trim().is_empty() {
+                    continue;
+                }
+                if let Some(progress) = parse_quantize_progress(&line) {
+                    let _ = app.emit("quantize:progress", progress);
                 } else {
-                    format!("Inference failed: {}", last_error)
-                },
-            });
-        }
+                    tail.push(line);
+                    if tail.len() > 5 {
+                        tail.remove(0);
+                    }
+                }
+            }
+            tail
+        })
+    })
+    .collect();
 
-        let st_device = if py_device.is_empty() { "CPU".to_string() } else { py_device };
-        (output, st_device)
+    let cancel = state.quantize_cancel.clone();
+    let status = loop {
+        tokio::select! {
+            result = child.wait() => {
+                break result.map_err(ModelError::IoError)?;
+            }
+            _ = tokio::time::sleep(std::time::Duration::from_millis(500)) => {
+                if cancel.load(std::sync::atomic::Ordering::Relaxed) {
+                    child.kill().await.ok();
+                    *state.quantize_pid.lock().unwrap() = None;
+                    let _ = std::fs::remove_file(output_path);
+                    return Err(ModelError::ParseError {
+                        format: "quantize".into(),
+                        reason: "Quantization cancelled".into(),
+                    });
+                }
+            }
+        }
     };
 
-    let elapsed = start.elapsed().as_millis() as u64;
-    let token_count = full_output.split_whitespace().count() as u32;
-
-    let _ = app.emit("test:done", &full_output);
+    let mut last_lines = Vec::new();
+    for reader in readers {
+        last_lines.extend(reader.await.unwrap_or_default());
+    }
+    *state.quantize_pid.lock().unwrap() = None;
 
-    Ok(TestResult {
-        text: full_output,
-        tokens_generated: token_count,
-        time_ms: elapsed,
-        device,
-    })
+    if !status.success() {
+        return Err(ModelError::ParseError {
+            format: "quantize".into(),
+            reason: format!("llama-quantize failed: {}", last_lines.join("\n")),
+        });
+    }
+    Ok(())
 }
 
 #[tauri::command]
-pub async fn test_cancel(state: State<'_, AppState>) -> Result<(), ModelError> {
-    state.test_cancel.store(true, std::sync::atomic::Ordering::Relaxed);
+pub async fn quantize_cancel(state: State<'_, AppState>) -> Result<(), ModelError> {
+    state
+        .quantize_cancel
+        .store(true, std::sync::atomic::Ordering::Relaxed);
 
-    if let Some(pid) = *state.test_pid.lock().unwrap() {
+    if let Some(pid) = *state.quantize_pid.lock().unwrap() {
         #[cfg(unix)]
         {
             let _ = std::process::Command::new("kill")
@@ -2625,117 +6332,142 @@ pub async fn test_cancel(state: State<'_, AppState>) -> Result<(), ModelError> {
     Ok(())
 }
 
-// ── Quantize ───────────────────────────────────────────
+// ── Importance Matrix ─────────────────────────────────
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct QuantizeResult {
-    pub success: bool,
+pub struct ImatrixResult {
     pub output_path: String,
     pub output_size: u64,
     pub output_size_display: String,
+    pub chunks: u32,
+    pub elapsed_ms: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ImatrixProgress {
+    pub chunk: u32,
+    pub total_chunks: Option<u32>,
+    pub perplexity: f64,
 }
 
+/// Run `llama-imatrix` over a calibration text to produce the importance
+/// matrix that `quantize_model` accepts as `imatrix_path`. Defaults to
+/// `<model>.imatrix` next to the model. Cancelled via `quantize_cancel`.
 #[tauri::command]
-pub async fn quantize_model(
-    target_type: String,
-    output_path: String,
-    state: State<'_, AppState>,
+pub async fn generate_imatrix(
+    model_path: String,
+    text_path: String,
+    output_path: Option<String>,
+    context_size: Option<u32>,
+    max_chunks: Option<u32>,
+    gpu_layers: Option<i32>,
     app: tauri::AppHandle,
-) -> Result<QuantizeResult, ModelError> {
-    // Extract file path and validate format
-    let input_path = {
-        let loaded = state.loaded_model.lock().unwrap();
-        let info = loaded.as_ref().ok_or_else(|| ModelError::ParseError {
-            format: "quantize".into(),
-            reason: "No model loaded".into(),
-        })?;
+    state: State<'_, AppState>,
+) -> Result<ImatrixResult, ModelError> {
+    let fail = |reason: String| ModelError::ParseError { format: "imatrix".into(), reason };
+    let model = PathBuf::from(&model_path);
+    if !model.is_file() || model.extension().map_or(true, |e| e != "gguf") {
+        return Err(fail("An importance matrix can only be computed for a GGUF model".into()));
+    }
+    if !std::path::Path::new(&text_path).is_file() {
+        return Err(ModelError::FileNotFound(text_path));
+    }
+    let output_path = output_path.unwrap_or_else(|| model.with_extension("imatrix").to_string_lossy().to_string());
 
-        match info.format {
-            ModelFormat::Gguf => {}
-            _ => {
-                return Err(ModelError::ParseError {
-                    format: "quantize".into(),
-                    reason: "Only GGUF models can be quantized with llama-quantize".into(),
-                })
+    let cancel = state.quantize_cancel.clone();
+    cancel.store(false, std::sync::atomic::Ordering::Relaxed);
+    let started = std::time::Instant::now();
+
+    let (ngl_val, _) = gguf_offload(gpu_layers);
+    let mut cmd = tokio::process::Command::new(resolve_imatrix_binary(&app));
+    cmd.args(["-m", &model_path, "-f", &text_path, "-o", &output_path, "-ngl", &ngl_val]);
+    cmd.args(["-c", &context_size.unwrap_or(512).to_string()]);
+    if let Some(n) = max_chunks {
+        cmd.args(["--chunks", &n.to_string()]);
+    }
+    let mut child = cmd
+        .stdout(std::process::Stdio::piped())
+        .stderr(std::process::Stdio::piped())
+        .spawn()
+        .map_err(|e| {
+            if e.kind() == std::io::ErrorKind::NotFound {
+                fail("llama-imatrix not found. Install it via Settings > Tools or add it to your PATH.".into())
+            } else {
+                ModelError::IoError(e)
             }
-        }
+        })?;
 
-        info.file_path.clone()
-    };
+    if let Some(pid) = child.id() {
+        *state.quantize_pid.lock().unwrap() = Some(pid);
+    }
 
-    // Validate target quantization type
-    let valid_types = [
-        "Q2_K", "Q3_K_S", "Q3_K_M", "Q3_K_L", "Q4_0", "Q4_K_S", "Q4_K_M", "Q5_0", "Q5_K_S",
-        "Q5_K_M", "Q6_K", "Q8_0", "F16",
+    // Progress arrives as unterminated `[N]ppl,` runs, so read raw chunks
+    let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel::<String>();
+    let streams: [Option<Box<dyn tokio::io::AsyncRead + Unpin + Send>>; 2] = [
+        child.stdout.take().map(|s| Box::new(s) as _),
+        child.stderr.take().map(|s| Box::new(s) as _),
     ];
-    if !valid_types.contains(&target_type.as_str()) {
-        return Err(ModelError::ParseError {
-            format: "quantize".into(),
-            reason: format!("Unknown quantization type: {}", target_type),
+    for mut stream in streams.into_iter().flatten() {
+        let tx = tx.clone();
+        tokio::spawn(async move {
+            let mut buf = [0u8; 4096];
+            while let Ok(n) = stream.read(&mut buf).await {
+                if n == 0 || tx.send(String::from_utf8_lossy(&buf[..n]).to_string()).is_err() {
+                    break;
+                }
+            }
         });
     }
+    drop(tx);
 
-    // Resolve binary: bundled takes priority, then PATH
-    let binary = resolve_quantize_binary(&app);
-    let target = target_type.clone();
-    let output = output_path.clone();
-
-    tauri::async_runtime::spawn_blocking(move || {
-        let child = std::process::Command::new(&binary)
-            .arg(&input_path)
-            .arg(&output)
-            .arg(&target)
-            .output();
-
-        match child {
-            Ok(proc_output) => {
-                if proc_output.status.success() {
-                    Ok(())
-                } else {
-                    // Combine stdout + stderr, take only the last meaningful lines
-                    let stdout = String::from_utf8_lossy(&proc_output.stdout);
-                    let stderr = String::from_utf8_lossy(&proc_output.stderr);
-                    let combined = format!("{}{}", stdout, stderr);
-                    let last_lines: String = combined
-                        .lines()
-                        .rev()
-                        .take(5)
-                        .collect::<Vec<_>>()
-                        .into_iter()
-                        .rev()
-                        .collect::<Vec<_>>()
-                        .join("\n");
-                    Err(ModelError::ParseError {
-                        format: "quantize".into(),
-                        reason: format!("llama-quantize failed: {}", last_lines),
-                    })
-                }
+    let mut pending = String::new();
+    let mut log_tail: Vec<String> = Vec::new();
+    let mut total_chunks: Option<u32> = None;
+    let mut chunks = 0;
+    while let Some(text) = rx.recv().await {
+        if cancel.load(std::sync::atomic::Ordering::Relaxed) {
+            let _ = child.kill().await;
+            break;
+        }
+        for line in text.lines().map(str::trim).filter(|l| !l.is_empty()) {
+            // "compute_imatrix: computing over 100 chunks with batch_size 512"
+            if let Some(rest) = line.split("computing over ").nth(1) {
+                total_chunks = rest.split_whitespace().next().and_then(|n| n.parse().ok());
             }
-            Err(e) => {
-                if e.kind() == std::io::ErrorKind::NotFound {
-                    Err(ModelError::ParseError {
-                        format: "quantize".into(),
-                        reason: "llama-quantize not found. Install it via Settings > Tools or add it to your PATH.".into(),
-                    })
-                } else {
-                    Err(ModelError::IoError(e))
+            if !line.starts_with('[') {
+                log_tail.push(line.to_string());
+                if log_tail.len() > 20 {
+                    log_tail.remove(0);
                 }
             }
         }
-    })
-    .await
-    .map_err(|e| ModelError::ParseError {
-        format: "quantize".into(),
-        reason: format!("Task failed: {}", e),
-    })??;
+        pending.push_str(&text);
+        for (chunk, ppl) in drain_ppl_updates(&mut pending) {
+            chunks = chunk;
+            let _ = app.emit("imatrix:progress", ImatrixProgress { chunk, total_chunks, perplexity: ppl });
+        }
+    }
 
-    let output_meta = std::fs::metadata(&output_path).map_err(ModelError::IoError)?;
+    let status = child.wait().await;
+    *state.quantize_pid.lock().unwrap() = None;
+    if cancel.load(std::sync::atomic::Ordering::Relaxed) {
+        let _ = std::fs::remove_file(&output_path);
+        return Err(fail("Importance matrix generation cancelled".into()));
+    }
+    if !status.as_ref().is_ok_and(|s| s.success()) {
+        return Err(fail(format!(
+            "llama-imatrix failed: {}",
+            log_tail.iter().rev().take(5).rev().cloned().collect::<Vec<_>>().join("\n")
+        )));
+    }
 
-    Ok(QuantizeResult {
-        success: true,
-        output_path,
+    let output_meta = std::fs::metadata(&output_path).map_err(ModelError::IoError)?;
+    Ok(ImatrixResult {
         output_size: output_meta.len(),
         output_size_display: crate::model::format_file_size(output_meta.len()),
+        output_path,
+        chunks: total_chunks.unwrap_or(chunks),
+        elapsed_ms: started.elapsed().as_millis() as u64,
     })
 }
 
@@ -2779,11 +6511,197 @@ pub fn get_system_info() -> SystemInfo {
     }
 }
 
+// ── GPU Monitor ───────────────────────────────────────
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GpuDeviceStats {
+    pub index: u32,
+    pub utilization: Option<f64>,
+    pub memory_used_mb: u64,
+    pub memory_total_mb: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GpuStats {
+    pub backend: String,
+    pub devices: Vec<GpuDeviceStats>,
+}
+
+/// Seconds the monitor keeps polling with no operation running before it stops itself.
+const GPU_MONITOR_IDLE_GRACE: u32 = 5;
+
+async fn sample_nvidia_stats() -> Option<Vec<GpuDeviceStats>> {
+    let output = tokio::process::Command::new("nvidia-smi")
+        .arg("--query-gpu=index,utilization.gpu,memory.used,memory.total")
+        .arg("--format=csv,noheader,nounits")
+        .output()
+        .await
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let devices: Vec<GpuDeviceStats> = stdout
+        .lines()
+        .filter_map(|line| {
+            let parts: Vec<&str> = line.split(',').map(|s| s.trim()).collect();
+            if parts.len() < 4 {
+                return None;
+            }
+            Some(GpuDeviceStats {
+                index: parts[0].parse().ok()?,
+                utilization: parts[1].parse().ok(),
+                memory_used_mb: parts[2].parse().unwrap_or(0),
+                memory_total_mb: parts[3].parse().unwrap_or(0),
+            })
+        })
+        .collect();
+    (!devices.is_empty()).then_some(devices)
+}
+
+async fn sample_rocm_stats() -> Option<Vec<GpuDeviceStats>> {
+    let output = tokio::process::Command::new("rocm-smi")
+        .args(["--showuse", "--showmeminfo", "vram", "--json"])
+        .output()
+        .await
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    let json: serde_json::Value = serde_json::from_slice(&output.stdout).ok()?;
+    let bytes_to_mb = |v: Option<&serde_json::Value>| {
+        v.and_then(|v| v.as_str())
+            .and_then(|s| s.trim().parse::<u64>().ok())
+            .map(|b| b / (1024 * 1024))
+            .unwrap_or(0)
+    };
+    let mut devices: Vec<GpuDeviceStats> = json
+        .as_object()?
+        .iter()
+        .filter_map(|(key, card)| {
+            let index = key.strip_prefix("card")?.parse().ok()?;
+            Some(GpuDeviceStats {
+                index,
+                utilization: card
+                    .get("GPU use (%)")
+                    .and_then(|v| v.as_str())
+                    .and_then(|s| s.trim().parse().ok()),
+                memory_used_mb: bytes_to_mb(card.get("VRAM Total Used Memory (B)")),
+                memory_total_mb: bytes_to_mb(card.get("VRAM Total Memory (B)")),
+            })
+        })
+        .collect();
+    devices.sort_by_key(|d| d.index);
+    (!devices.is_empty()).then_some(devices)
+}
+
+async fn sample_gpu_stats() -> Option<GpuStats> {
+    if let Some(devices) = sample_nvidia_stats().await {
+        return Some(GpuStats { backend: "cuda".into(), devices });
+    }
+    if let Some(devices) = sample_rocm_stats().await {
+        return Some(GpuStats { backend: "rocm".into(), devices });
+    }
+    None
+}
+
+/// True while any long-running job (convert, quantize, test, server, merge, training) is active.
+fn operation_running(state: &AppState) -> bool {
+    state.convert_pid.lock().unwrap().is_some()
+        || state.quantize_pid.lock().unwrap().is_some()
+        || state.test_pid.lock().unwrap().is_some()
+        || state.training_pid.lock().unwrap().is_some()
+        || state.llama_server.lock().unwrap().is_some()
+        || state.merge_active.load(std::sync::atomic::Ordering::Relaxed)
+}
+
+/// Poll GPU utilization and memory every second, emitting `system:gpu-stats`.
+/// Returns false (and does nothing) when no NVIDIA or ROCm GPU can be queried.
+#[tauri::command]
+pub async fn gpu_monitor_start(app: tauri::AppHandle, state: State<'_, AppState>) -> Result<bool, ModelError> {
+    if sample_gpu_stats().await.is_none() {
+        return Ok(false);
+    }
+    if state.gpu_monitor_active.swap(true, std::sync::atomic::Ordering::Relaxed) {
+        return Ok(true); // already running
+    }
+
+    let active = state.gpu_monitor_active.clone();
+    tokio::spawn(async move {
+        let mut idle_ticks = 0;
+        while active.load(std::sync::atomic::Ordering::Relaxed) {
+            if let Some(stats) = sample_gpu_stats().await {
+                let _ = app.emit("system:gpu-stats", &stats);
+            }
+
+            if operation_running(&app.state::<AppState>()) {
+                idle_ticks = 0;
+            } else {
+                idle_ticks += 1;
+                if idle_ticks >= GPU_MONITOR_IDLE_GRACE {
+                    break;
+                }
+            }
+
+            tokio::time::sleep(std::time::Duration::from_secs(1)).await;
+        }
+        active.store(false, std::sync::atomic::Ordering::Relaxed);
+    });
+
+    Ok(true)
+}
+
+#[tauri::command]
+pub fn gpu_monitor_stop(state: State<'_, AppState>) {
+    state.gpu_monitor_active.store(false, std::sync::atomic::Ordering::Relaxed);
+}
+
 // ── App Settings Persistence ──────────────────────────
 
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
 pub struct AppSettings {
     pub memory_limit_mb: Option<u64>,
+    /// HuggingFace access token for gated and private repos.
+    #[serde(default)]
+    pub hf_token: Option<String>,
+    /// Absolute path overrides; unset means the folder under the app data dir.
+    #[serde(default)]
+    pub models_dir: Option<String>,
+    #[serde(default)]
+    pub tools_dir: Option<String>,
+    #[serde(default)]
+    pub datasets_dir: Option<String>,
+    /// HuggingFace base URL (e.g. a mirror); unset means `HF_DEFAULT_ENDPOINT`.
+    #[serde(default)]
+    pub hf_endpoint: Option<String>,
+}
+
+/// The configured directory when it is an absolute path, otherwise
+/// `default` under the app data dir.
+fn resolve_data_dir(
+    app: &tauri::AppHandle,
+    configured: Option<String>,
+    default: &str,
+    format: &str,
+) -> Result<PathBuf, ModelError> {
+    let configured = configured
+        .map(|d| PathBuf::from(d.trim()))
+        .filter(|d| d.is_absolute());
+    if let Some(dir) = configured {
+        return Ok(dir);
+    }
+
+    let data_dir = app.path().app_data_dir().map_err(|e| ModelError::ParseError {
+        format: format.into(),
+        reason: format!("Cannot resolve app data dir: {}", e),
+    })?;
+    Ok(data_dir.join(default))
+}
+
+/// Root holding llama.cpp, the convert env and the training env.
+pub(crate) fn get_tools_root(app: &tauri::AppHandle, format: &str) -> Result<PathBuf, ModelError> {
+    let configured = load_settings(app.clone()).tools_dir;
+    resolve_data_dir(app, configured, "tools", format)
 }
 
 #[tauri::command]
@@ -2796,8 +6714,22 @@ pub fn load_settings(app: tauri::AppHandle) -> AppSettings {
         .unwrap_or_default()
 }
 
+/// User capability rules from `capability_rules.json` in app data.
+pub(crate) fn user_capability_rules(
+    app: &tauri::AppHandle,
+) -> Result<Vec<crate::merge::capabilities::CapabilityRule>, ModelError> {
+    let dir = app.path().app_data_dir().map_err(|e| ModelError::ParseError {
+        format: "capability_rules".into(),
+        reason: format!("Cannot resolve app data dir: {}", e),
+    })?;
+    crate::merge::capabilities::load_user_rules(&dir.join("capability_rules.json"))
+}
+
 #[tauri::command]
 pub fn save_settings(app: tauri::AppHandle, settings: AppSettings) -> Result<(), ModelError> {
+    if let Some(endpoint) = settings.hf_endpoint.as_deref().filter(|e| !e.trim().is_empty()) {
+        validate_hf_endpoint(endpoint)?;
+    }
     let dir = app.path().app_data_dir().expect("No app data dir");
     std::fs::create_dir_all(&dir).map_err(ModelError::IoError)?;
     let path = dir.join("settings.json");
@@ -2850,23 +6782,22 @@ fn detect_dataset_format(filename: &str) -> Option<String> {
 }
 
 fn get_datasets_dir(app: &tauri::AppHandle) -> Result<PathBuf, ModelError> {
-    let data_dir = app.path().app_data_dir().map_err(|e| ModelError::ParseError {
-        format: "datastudio".into(),
-        reason: format!("Cannot resolve app data dir: {}", e),
-    })?;
-    Ok(data_dir.join("datasets"))
+    let configured = load_settings(app.clone()).datasets_dir;
+    resolve_data_dir(app, configured, "datasets", "datastudio")
 }
 
 #[tauri::command]
-pub async fn hf_fetch_dataset_repo(repo_id: String) -> Result<HfDatasetRepoInfo, ModelError> {
-    let client = build_http_client()?;
+pub async fn hf_fetch_dataset_repo(repo_id: String, app: tauri::AppHandle) -> Result<HfDatasetRepoInfo, ModelError> {
+    let token = hf_token(&app);
+    let client = build_hf_client(token.as_deref())?;
 
-    let url = format!("https://huggingface.co/api/datasets/{}", repo_id);
-    let resp = client.get(&url).send().await.map_err(|e| ModelError::ParseError {
+    let url = format!("{}/api/datasets/{}", hf_endpoint(&app), repo_id);
+    let resp = send_with_retry(client.get(&url), HTTP_MAX_RETRIES).await.map_err(|e| ModelError::ParseError {
         format: "datastudio".into(),
         reason: format!("Failed to fetch dataset repo: {}", e),
     })?;
 
+    check_hf_auth(resp.status(), &repo_id, token.as_deref())?;
     if !resp.status().is_success() {
         return Err(ModelError::ParseError {
             format: "datastudio".into(),
@@ -2920,6 +6851,169 @@ pub async fn hf_fetch_dataset_repo(repo_id: String) -> Result<HfDatasetRepoInfo,
     })
 }
 
+/// Row-preview API for datasets on the canonical hub.
+const HF_DATASETS_SERVER: &str = "https://datasets-server.huggingface.co";
+const DATASET_PREVIEW_ROWS: usize = 10;
+/// How much of a jsonl/csv file is fetched when the datasets-server has no preview.
+const DATASET_PREVIEW_PREFIX_BYTES: usize = 256 * 1024;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HfDatasetPreview {
+    pub id: String,
+    /// "datasets-server", or the repo file the rows were read from.
+    pub source: String,
+    pub split: Option<String>,
+    pub columns: Vec<String>,
+    pub rows: Vec<serde_json::Value>,
+}
+
+/// First rows of the train split (or the first split) from the datasets-server.
+/// None when the dataset isn't indexed there.
+async fn fetch_datasets_server_rows(client: &reqwest::Client, repo_id: &str) -> Option<HfDatasetPreview> {
+    #[derive(Deserialize)]
+    struct DsSplit {
+        config: String,
+        split: String,
+    }
+    #[derive(Deserialize)]
+    struct DsSplits {
+        splits: Vec<DsSplit>,
+    }
+    #[derive(Deserialize)]
+    struct DsFeature {
+        name: String,
+    }
+    #[derive(Deserialize)]
+    struct DsRow {
+        row: serde_json::Value,
+    }
+    #[derive(Deserialize)]
+    struct DsRows {
+        features: Vec<DsFeature>,
+        rows: Vec<DsRow>,
+    }
+
+    let url = format!("{}/splits", HF_DATASETS_SERVER);
+    let resp = send_with_retry(client.get(&url).query(&[("dataset", repo_id)]), HTTP_MAX_RETRIES)
+        .await
+        .ok()?;
+    if !resp.status().is_success() {
+        return None;
+    }
+    let splits: DsSplits = resp.json().await.ok()?;
+    let split = splits
+        .splits
+        .iter()
+        .find(|s| s.split == "train")
+        .or(splits.splits.first())?;
+
+    let url = format!("{}/rows", HF_DATASETS_SERVER);
+    let length = DATASET_PREVIEW_ROWS.to_string();
+    let params = [
+        ("dataset", repo_id),
+        ("config", split.config.as_str()),
+        ("split", split.split.as_str()),
+        ("offset", "0"),
+        ("length", length.as_str()),
+    ];
+    let resp = send_with_retry(client.get(&url).query(&params), HTTP_MAX_RETRIES)
+        .await
+        .ok()?;
+    if !resp.status().is_success() {
+        return None;
+    }
+    let rows: DsRows = resp.json().await.ok()?;
+
+    Some(HfDatasetPreview {
+        id: repo_id.to_string(),
+        source: "datasets-server".into(),
+        split: Some(split.split.clone()),
+        columns: rows.features.into_iter().map(|f| f.name).collect(),
+        rows: rows.rows.into_iter().map(|r| r.row).collect(),
+    })
+}
+
+/// Columns and a few example rows of a hub dataset, so users can check the
+/// schema before downloading. Falls back to reading the start of the smallest
+/// jsonl/csv file when the datasets-server has no preview.
+#[tauri::command]
+pub async fn hf_preview_dataset(repo_id: String, app: tauri::AppHandle) -> Result<HfDatasetPreview, ModelError> {
+    let token = hf_token(&app);
+    let client = build_hf_client(token.as_deref())?;
+    let endpoint = hf_endpoint(&app);
+
+    // The datasets-server only indexes the canonical hub
+    if endpoint == HF_DEFAULT_ENDPOINT {
+        if let Some(preview) = fetch_datasets_server_rows(&client, &repo_id).await {
+            return Ok(preview);
+        }
+    }
+
+    let repo = hf_fetch_dataset_repo(repo_id.clone(), app.clone()).await?;
+    let file = repo
+        .files
+        .iter()
+        .filter(|f| matches!(f.format.as_deref(), Some("jsonl") | Some("csv")))
+        .min_by_key(|f| f.size.unwrap_or(u64::MAX))
+        .ok_or_else(|| ModelError::ParseError {
+            format: "datastudio".into(),
+            reason: "No preview available: the dataset has no row preview and no jsonl or csv file".into(),
+        })?;
+    let format = if file.format.as_deref() == Some("csv") {
+        DatasetFormat::Csv
+    } else {
+        DatasetFormat::Jsonl
+    };
+
+    let url = format!("{}/datasets/{}/resolve/main/{}", endpoint, repo_id, file.rfilename);
+    let range = format!("bytes=0-{}", DATASET_PREVIEW_PREFIX_BYTES - 1);
+    let resp = send_with_retry(client.get(&url).header(reqwest::header::RANGE, range), HTTP_MAX_RETRIES)
+        .await
+        .map_err(|e| ModelError::ParseError {
+            format: "datastudio".into(),
+            reason: format!("Preview request failed: {}", e),
+        })?;
+
+    check_hf_auth(resp.status(), &repo_id, token.as_deref())?;
+    if !resp.status().is_success() {
+        return Err(ModelError::ParseError {
+            format: "datastudio".into(),
+            reason: format!("Preview download failed (HTTP {})", resp.status()),
+        });
+    }
+
+    // Servers that ignore Range send the whole file; stop reading once the prefix is full
+    let mut prefix = Vec::new();
+    let mut truncated = false;
+    let mut stream = resp.bytes_stream();
+    while let Some(chunk) = stream.next().await {
+        let chunk = chunk.map_err(|e| ModelError::ParseError {
+            format: "datastudio".into(),
+            reason: format!("Preview download error: {}", e),
+        })?;
+        prefix.extend_from_slice(&chunk);
+        if prefix.len() >= DATASET_PREVIEW_PREFIX_BYTES {
+            truncated = true;
+            break;
+        }
+    }
+    // A file read to its end has no partial last line, even without a trailing newline
+    if !truncated {
+        prefix.push(b'\n');
+    }
+
+    let (columns, rows) =
+        crate::training::datasets::preview_from_prefix(&prefix, &format, DATASET_PREVIEW_ROWS)?;
+
+    Ok(HfDatasetPreview {
+        id: repo.id,
+        source: file.rfilename.clone(),
+        split: None,
+        columns,
+        rows,
+    })
+}
+
 #[tauri::command]
 pub async fn hf_download_dataset_file(
     repo_id: String,
@@ -2936,17 +7030,19 @@ pub async fn hf_download_dataset_file(
     cancel.store(false, std::sync::atomic::Ordering::Relaxed);
 
     let download_url = format!(
-        "https://huggingface.co/datasets/{}/resolve/main/{}",
-        repo_id, filename
+        "{}/datasets/{}/resolve/main/{}",
+        hf_endpoint(&app), repo_id, filename
     );
 
-    let client = build_http_client()?;
+    let token = hf_token(&app);
+    let client = build_hf_client(token.as_deref())?;
 
-    let resp = client.get(&download_url).send().await.map_err(|e| ModelError::ParseError {
+    let resp = send_with_retry(client.get(&download_url), HTTP_MAX_RETRIES).await.map_err(|e| ModelError::ParseError {
         format: "datastudio".into(),
         reason: format!("Download request failed: {}", e),
     })?;
 
+    check_hf_auth(resp.status(), &filename, token.as_deref())?;
     if !resp.status().is_success() {
         return Err(ModelError::ParseError {
             format: "datastudio".into(),
@@ -2967,6 +7063,7 @@ pub async fn hf_download_dataset_file(
     let mut stream = resp.bytes_stream();
     let mut downloaded: u64 = 0;
     let mut last_emit = std::time::Instant::now();
+    let mut rate = TransferRate::new();
 
     let safe_name = std::path::Path::new(&filename)
         .file_name()
@@ -2986,6 +7083,8 @@ pub async fn hf_download_dataset_file(
                 status: "cancelled".into(),
                 files_done: None,
                 files_total: None,
+                bytes_per_second: None,
+                eta_seconds: None,
             });
             return Err(ModelError::ParseError {
                 format: "datastudio".into(),
@@ -3011,6 +7110,7 @@ pub async fn hf_download_dataset_file(
             } else {
                 0.0
             };
+            rate.record(downloaded);
             let _ = app.emit("datastudio:download-progress", DownloadProgress {
                 file_name: safe_name.clone(),
                 bytes_downloaded: downloaded,
@@ -3019,6 +7119,8 @@ pub async fn hf_download_dataset_file(
                 status: "downloading".into(),
                 files_done: None,
                 files_total: None,
+                bytes_per_second: rate.bytes_per_second(),
+                eta_seconds: (total_size > 0).then(|| rate.eta_seconds(total_size.saturating_sub(downloaded))).flatten(),
             });
             last_emit = now;
         }
@@ -3038,7 +7140,214 @@ pub async fn hf_download_dataset_file(
         status: "complete".into(),
         files_done: None,
         files_total: None,
+        bytes_per_second: None,
+        eta_seconds: None,
     });
 
     Ok(out_path.to_string_lossy().to_string())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn strings(items: &[&str]) -> Vec<String> {
+        items.iter().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn sentencepiece_merges_rank_by_piece_score() {
+        let tokens = strings(&["a", "b", "c", "ab", "abc"]);
+        assert_eq!(sentencepiece_merges(&tokens, &[0.0, 0.0, 0.0, -1.0, -2.0]), ["a b", "ab c"]);
+        assert_eq!(sentencepiece_merges(&tokens, &[0.0, 0.0, 0.0, -2.0, -1.0]), ["ab c", "a b"]);
+    }
+
+    /// Serves one canned response per connection, in order, then stops.
+    fn mock_server(responses: Vec<&'static str>) -> (String, std::thread::JoinHandle<usize>) {
+        use std::io::{Read, Write};
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/api", listener.local_addr().unwrap());
+        let handle = std::thread::spawn(move || {
+            let mut served = 0;
+            for response in responses {
+                let (mut stream, _) = listener.accept().unwrap();
+                let mut request = Vec::new();
+                let mut buf = [0u8; 1024];
+                while !request.windows(4).any(|w| w == b"\r\n\r\n") {
+                    let n = stream.read(&mut buf).unwrap();
+                    if n == 0 {
+                        break;
+                    }
+                    request.extend_from_slice(&buf[..n]);
+                }
+                stream.write_all(response.as_bytes()).unwrap();
+                served += 1;
+            }
+            served
+        });
+        (url, handle)
+    }
+
+    const UNAVAILABLE: &str = "HTTP/1.1 503 Service Unavailable\r\nContent-Length: 0\r\nConnection: close\r\n\r\n";
+
+    #[test]
+    fn send_with_retry_recovers_after_two_failures() {
+        let (url, server) = mock_server(vec![
+            UNAVAILABLE,
+            UNAVAILABLE,
+            "HTTP/1.1 200 OK\r\nContent-Length: 2\r\nConnection: close\r\n\r\nok",
+        ]);
+        let resp = tauri::async_runtime::block_on(async {
+            let resp = send_with_retry(reqwest::Client::new().get(&url), HTTP_MAX_RETRIES).await.unwrap();
+            (resp.status(), resp.text().await.unwrap())
+        });
+        assert_eq!(resp, (reqwest::StatusCode::OK, "ok".to_string()));
+        assert_eq!(server.join().unwrap(), 3);
+    }
+
+    #[test]
+    fn send_with_retry_returns_the_last_failure() {
+        let (url, server) = mock_server(vec![UNAVAILABLE, UNAVAILABLE]);
+        let status = tauri::async_runtime::block_on(async {
+            send_with_retry(reqwest::Client::new().get(&url), 1).await.unwrap().status()
+        });
+        assert_eq!(status, reqwest::StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(server.join().unwrap(), 2);
+    }
+
+    #[test]
+    fn search_hits_parse_a_recorded_response() {
+        let body = r#"[
+            {"_id":"65f1","id":"bartowski/Qwen2.5-7B-Instruct-GGUF","likes":84,"trendingScore":3,"private":false,"downloads":412345,"tags":["gguf","text-generation"],"pipeline_tag":"text-generation","library_name":"gguf","createdAt":"2024-09-18T00:00:00.000Z","modelId":"bartowski/Qwen2.5-7B-Instruct-GGUF"},
+            {"_id":"65f2","modelId":"someone/tiny-gguf","likes":0,"private":false,"tags":["gguf"]},
+            {"_id":"65f3","likes":2,"downloads":10}
+        ]"#;
+        let hits = parse_search_hits(body).unwrap();
+        assert_eq!(hits.len(), 2);
+        assert_eq!(hits[0].id, "bartowski/Qwen2.5-7B-Instruct-GGUF");
+        assert_eq!((hits[0].downloads, hits[0].likes), (412345, 84));
+        assert_eq!(hits[0].pipeline_tag.as_deref(), Some("text-generation"));
+        assert_eq!(hits[1].id, "someone/tiny-gguf");
+        assert_eq!((hits[1].downloads, hits[1].likes, hits[1].pipeline_tag.as_deref()), (0, 0, None));
+        assert!(parse_search_hits(r#"{"error":"Invalid"}"#).is_err());
+    }
+
+    #[test]
+    fn nvidia_smi_lists_every_gpu() {
+        let devices = parse_nvidia_smi_devices("0, NVIDIA GeForce RTX 4090, 24564\n1, NVIDIA RTX A6000, 49140\n");
+        assert_eq!(devices.len(), 2);
+        assert_eq!((devices[0].index, devices[0].name.as_str(), devices[0].vram_mb), (0, "NVIDIA GeForce RTX 4090", 24564));
+        assert_eq!((devices[1].index, devices[1].name.as_str(), devices[1].vram_mb), (1, "NVIDIA RTX A6000", 49140));
+        assert_eq!(devices[1].vram, "49140 MB");
+    }
+
+    #[test]
+    fn nvidia_smi_skips_malformed_rows() {
+        let devices = parse_nvidia_smi_devices("\nNo devices were found\n0, Tesla T4, 15360\n");
+        assert_eq!(devices.len(), 1);
+        assert_eq!(devices[0].name, "Tesla T4");
+    }
+
+    #[test]
+    fn rocm_only_machines_offload_to_the_gpu() {
+        let gpu = GpuInfo {
+            has_nvidia: false,
+            nvidia_name: None,
+            nvidia_vram: None,
+            gpus: Vec::new(),
+            cuda_version: None,
+            driver_issue: None,
+            has_rocm: true,
+            rocm_name: Some("AMD Radeon RX 7900 XTX".into()),
+            rocm_vram: Some("24.0 GB".into()),
+            rocm_version: Some("6.1".into()),
+            has_vulkan: false,
+            has_metal: false,
+            recommended_variant: "rocm".into(),
+            os: "linux".into(),
+            arch: "x86_64".into(),
+        };
+        assert_eq!(offload_for(&gpu, None), ("99".to_string(), "ROCM".to_string()));
+        assert_eq!(offload_for(&gpu, Some(0)), ("0".to_string(), "CPU".to_string()));
+
+        let cpu = GpuInfo { has_rocm: false, ..gpu };
+        assert_eq!(offload_for(&cpu, None), ("0".to_string(), "CPU".to_string()));
+    }
+
+    #[test]
+    fn free_space_check_compares_sizes() {
+        const GB: u64 = 1 << 30;
+        assert!(check_free_space(3 * GB, 3 * GB, "download").is_ok());
+        assert!(check_free_space(0, 0, "download").is_ok());
+
+        let err = check_free_space(14 * GB + GB / 5, 3 * GB + GB / 10, "quantize").unwrap_err();
+        match err {
+            ModelError::ParseError { format, reason } => {
+                assert_eq!(format, "quantize");
+                assert_eq!(reason, "Need 14.20 GB free, only 3.10 GB available");
+            }
+            other => panic!("unexpected error: {}", other),
+        }
+        assert!(check_free_space(GB + 1, GB, "convert").is_err());
+    }
+
+    #[test]
+    fn repo_cache_keys_separate_tokens() {
+        let endpoint = "https://huggingface.co";
+        let anon = repo_cache_key(endpoint, "org/gated", None);
+        let alice = repo_cache_key(endpoint, "org/gated", Some("hf_alice"));
+        let bob = repo_cache_key(endpoint, "org/gated", Some("hf_bob"));
+        assert_eq!(anon, "anon@https://huggingface.co/org/gated");
+        assert_ne!(alice, bob);
+        assert_eq!(alice, repo_cache_key(endpoint, "org/gated", Some("hf_alice")));
+        assert!(!alice.contains("hf_alice"));
+        assert!(bob.ends_with("@https://huggingface.co/org/gated"));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn model_scan_survives_symlink_cycles() {
+        let root = std::env::temp_dir().join(format!("forgeai-scan-{}", uuid::Uuid::new_v4()));
+        let nested = root.join("a/b/c/d");
+        std::fs::create_dir_all(&nested).unwrap();
+        std::os::unix::fs::symlink(&root, root.join("a/loop")).unwrap();
+        let empty = dir_contains_model(&root);
+
+        std::fs::write(nested.join("model.gguf"), b"").unwrap();
+        let within_depth = dir_contains_model(&root);
+        std::fs::create_dir(nested.join("e")).unwrap();
+        std::fs::rename(nested.join("model.gguf"), nested.join("e/model.gguf")).unwrap();
+        let too_deep = dir_contains_model(&root);
+        std::fs::remove_dir_all(&root).unwrap();
+
+        assert!(!empty);
+        assert!(within_depth);
+        assert!(!too_deep);
+    }
+
+    #[test]
+    fn quantize_accepts_every_listed_type() {
+        for t in QUANTIZE_TYPES {
+            assert!(validate_quantize_target(t, true).is_ok(), "{} rejected", t);
+        }
+        for t in [
+            "IQ1_S", "IQ1_M", "IQ2_XXS", "IQ2_XS", "IQ2_S", "IQ2_M", "IQ3_XXS", "IQ3_XS", "IQ3_S", "IQ3_M",
+            "IQ4_XS", "IQ4_NL", "Q4_K_S", "Q4_K_L",
+        ] {
+            assert!(QUANTIZE_TYPES.contains(&t), "{} missing", t);
+        }
+        assert!(validate_quantize_target("Q4_K_XL", true).is_err());
+        assert!(validate_quantize_target("q4_k_m", true).is_err());
+    }
+
+    #[test]
+    fn imatrix_only_types_need_a_matrix() {
+        for t in IMATRIX_REQUIRED_TYPES {
+            assert!(QUANTIZE_TYPES.contains(t), "{} missing", t);
+            let err = validate_quantize_target(t, false).unwrap_err().to_string();
+            assert!(err.contains("importance matrix"), "{}", err);
+        }
+        assert!(validate_quantize_target("IQ4_XS", false).is_ok());
+        assert!(validate_quantize_target("Q3_K_S", false).is_ok());
+    }
+}
@@ -16,20 +16,31 @@ pub fn run() {
         .invoke_handler(tauri::generate_handler![
             commands::load_model,
             commands::load_model_dir,
+            commands::estimate_load_time,
             commands::get_loaded_model,
             commands::unload_model,
             commands::inspect_model,
             commands::inspect_capabilities,
+            commands::inspect_param_breakdown,
+            commands::inspect_tensors_by_dtype,
+            commands::kv_cache_breakdown,
+            commands::list_tensor_names,
+            commands::export_tensor,
             commands::compute_fingerprint,
+            commands::fingerprint_cancel,
             commands::quantize_model,
+            commands::quantize_cancel,
+            commands::generate_imatrix,
             commands::detect_gpu,
             commands::get_tools_status,
             commands::download_llama_cpp,
             commands::remove_tools,
             commands::hf_fetch_repo,
+            commands::hf_search_models,
             commands::hf_download_file,
             commands::hf_download_repo,
             commands::hub_list_local,
+            commands::hub_rebuild_manifest,
             commands::hub_delete_model,
             commands::hub_cancel_download,
             commands::hub_import_local,
@@ -38,9 +49,22 @@ pub fn run() {
             commands::convert_detect_model,
             commands::convert_run,
             commands::convert_cancel,
+            commands::convert_gguf_to_safetensors,
             commands::test_generate,
             commands::test_cancel,
+            commands::compute_perplexity,
+            commands::test_server_start,
+            commands::test_server_stop,
+            commands::test_server_status,
+            commands::chat_start,
+            commands::chat_send,
+            commands::chat_reset,
+            commands::chat_end,
+            commands::smoke_test,
+            commands::check_vocab_consistency,
             commands::get_system_info,
+            commands::gpu_monitor_start,
+            commands::gpu_monitor_stop,
             commands::load_settings,
             commands::save_settings,
             // Merge commands
@@ -56,16 +80,22 @@ pub fn run() {
             merge_commands::merge_profile_layers,
             merge_commands::merge_profile_cancel,
             merge_commands::merge_preview,
+            merge_commands::merge_estimate_output,
             merge_commands::merge_get_methods,
             merge_commands::merge_compare_tensors,
             merge_commands::merge_analyze_layers,
+            merge_commands::merge_export_analysis,
             merge_commands::merge_get_categories,
             merge_commands::merge_detect_capabilities,
+            merge_commands::merge_suggest_base,
             merge_commands::merge_get_layer_components,
             // Training commands
             training_commands::training_check_deps,
             training_commands::training_setup,
             training_commands::training_detect_dataset,
+            training_commands::training_preview_dataset,
+            training_commands::training_find_checkpoint,
+            training_commands::training_validate_script,
             training_commands::training_run,
             training_commands::training_cancel,
             training_commands::training_surgery_run,
@@ -79,6 +109,7 @@ pub fn run() {
             commands::convert_clean_env,
             // Dataset commands
             commands::hf_fetch_dataset_repo,
+            commands::hf_preview_dataset,
             commands::hf_download_dataset_file,
         ])
         .run(tauri::generate_context!())
@@ -1,6 +1,10 @@
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::path::Path;
 
+use crate::model::error::ModelError;
+use crate::model::{gguf, inspect, ModelFormat};
+
 use super::registry::ParentModel;
 
 // ── Capability Structs ──────────────────────────────────
@@ -38,20 +42,91 @@ pub(crate) fn capability_layer_range(id: &str) -> (f64, f64) {
         "safety" => (0.75, 0.95),
         "multimodal" => (0.0, 0.30),
         "moe" => (0.0, 1.0), // all layers
+        "long_context" => (0.0, 1.0), // RoPE applies in every attention layer
         _ => (0.0, 1.0),
     }
 }
 
 pub(crate) fn compute_affected_layers(id: &str, total_layers: u64) -> Vec<u64> {
+    let (start, end) = capability_layer_range(id);
+    layers_in_range(start, end, total_layers)
+}
+
+fn layers_in_range(start: f64, end: f64, total_layers: u64) -> Vec<u64> {
     if total_layers == 0 {
         return vec![];
     }
-    let (start, end) = capability_layer_range(id);
     let first = (start * total_layers as f64).floor() as u64;
     let last = (end * total_layers as f64).ceil().min(total_layers as f64) as u64;
     (first..last).collect()
 }
 
+// ── User Rules ──────────────────────────────────────────
+
+const BUILTIN_IDS: &[&str] = &[
+    "tool_calling", "reasoning", "code", "math", "multilingual",
+    "instruct", "safety", "multimodal", "moe", "long_context",
+];
+
+/// A user-defined detector loaded from `capability_rules.json`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CapabilityRule {
+    pub id: String,
+    pub name: String,
+    #[serde(default)]
+    pub token_patterns: Vec<String>,
+    #[serde(default)]
+    pub name_hints: Vec<String>,
+    /// (start_fraction, end_fraction) of the layer stack, like `capability_layer_range`.
+    pub layer_range: (f64, f64),
+}
+
+fn rule_error(reason: String) -> ModelError {
+    ModelError::ParseError {
+        format: "capability_rules".into(),
+        reason,
+    }
+}
+
+/// Load and validate user rules. A missing file means no rules.
+pub fn load_user_rules(path: &Path) -> Result<Vec<CapabilityRule>, ModelError> {
+    if !path.exists() {
+        return Ok(vec![]);
+    }
+    let content = std::fs::read_to_string(path)?;
+    let rules: Vec<CapabilityRule> = serde_json::from_str(&content)
+        .map_err(|e| rule_error(format!("{}: {}", path.display(), e)))?;
+
+    let mut seen = std::collections::HashSet::new();
+    for (i, rule) in rules.iter().enumerate() {
+        let label = if rule.id.is_empty() { format!("rule #{}", i + 1) } else { format!("rule '{}'", rule.id) };
+        if rule.id.trim().is_empty() {
+            return Err(rule_error(format!("{} has an empty id", label)));
+        }
+        if BUILTIN_IDS.contains(&rule.id.as_str()) {
+            return Err(rule_error(format!("{} clashes with a built-in capability", label)));
+        }
+        if !seen.insert(rule.id.clone()) {
+            return Err(rule_error(format!("{} is defined more than once", label)));
+        }
+        if rule.name.trim().is_empty() {
+            return Err(rule_error(format!("{} has an empty name", label)));
+        }
+        if rule.token_patterns.iter().chain(&rule.name_hints).all(|p| p.is_empty()) {
+            return Err(rule_error(format!("{} needs at least one token pattern or name hint", label)));
+        }
+        let (start, end) = rule.layer_range;
+        if !(0.0..=1.0).contains(&start) || !(0.0..=1.0).contains(&end) || start >= end {
+            return Err(rule_error(format!(
+                "{} has layer_range ({}, {}); expected 0 <= start < end <= 1",
+                label, start, end
+            )));
+        }
+    }
+
+    Ok(rules)
+}
+
 // ── Helper: Read tokenizer tokens ───────────────────────
 
 fn read_tokenizer_tokens(dir_path: &str) -> Vec<String> {
@@ -106,9 +181,80 @@ fn resolve_model_dir(parent: &ParentModel) -> Option<String> {
     }
 }
 
+/// Full GGUF metadata for a parent, or None for SafeTensors / unreadable files.
+pub fn load_gguf_metadata(parent: &ParentModel) -> Option<HashMap<String, serde_json::Value>> {
+    if parent.format != ModelFormat::Gguf {
+        return None;
+    }
+    gguf::read_metadata(Path::new(&parent.file_path)).ok()
+}
+
+/// RoPE scaling settings gathered from config.json or GGUF metadata.
+#[derive(Debug, Default)]
+struct RopeScaling {
+    kind: Option<String>,
+    factor: Option<f64>,
+    original_context: Option<u64>,
+    max_context: Option<u64>,
+}
+
+/// Read `rope_scaling` + `max_position_embeddings` from config.json, or the
+/// `{arch}.rope.scaling.*` / `{arch}.context_length` keys from GGUF metadata.
+fn read_rope_scaling(
+    parent: &ParentModel,
+    config_json: &Option<serde_json::Value>,
+    gguf_metadata: Option<&HashMap<String, serde_json::Value>>,
+) -> RopeScaling {
+    let mut rope = RopeScaling {
+        max_context: parent.compat.context_length,
+        ..Default::default()
+    };
+
+    if let Some(meta) = gguf_metadata {
+        for (key, value) in meta {
+            if key.ends_with(".rope.scaling.type") {
+                rope.kind = value.as_str().map(|s| s.to_string());
+            } else if key.ends_with(".rope.scaling.factor") {
+                rope.factor = value.as_f64();
+            } else if key.ends_with(".rope.scaling.original_context_length") {
+                rope.original_context = value.as_u64();
+            } else if key.ends_with(".context_length") && rope.max_context.is_none() {
+                rope.max_context = value.as_u64();
+            }
+        }
+        return rope;
+    }
+
+    if let Some(config) = config_json {
+        let config = config.get("text_config").unwrap_or(config);
+        if let Some(scaling) = config.get("rope_scaling").filter(|v| v.is_object()) {
+            rope.kind = scaling
+                .get("rope_type")
+                .or_else(|| scaling.get("type"))
+                .and_then(|v| v.as_str())
+                .map(|s| s.to_string());
+            rope.factor = scaling.get("factor").and_then(|v| v.as_f64());
+            rope.original_context = scaling
+                .get("original_max_position_embeddings")
+                .and_then(|v| v.as_u64());
+        }
+        if let Some(max) = config.get("max_position_embeddings").and_then(|v| v.as_u64()) {
+            rope.max_context = Some(max);
+        }
+    }
+
+    rope
+}
+
 // ── Detection Engine ────────────────────────────────────
 
-pub fn detect_capabilities(parent: &ParentModel) -> CapabilityReport {
+/// `gguf_metadata` is the raw KV map for GGUF parents (see [`load_gguf_metadata`]);
+/// it supplies the chat template and RoPE keys that `CompatInfo` doesn't carry.
+pub fn detect_capabilities(
+    parent: &ParentModel,
+    gguf_metadata: Option<&HashMap<String, serde_json::Value>>,
+    user_rules: &[CapabilityRule],
+) -> CapabilityReport {
     let total_layers = parent.layer_count.unwrap_or(0);
     let model_name = parent.name.to_lowercase();
     let dir = resolve_model_dir(parent);
@@ -117,10 +263,12 @@ pub fn detect_capabilities(parent: &ParentModel) -> CapabilityReport {
     let tokenizer_tokens = dir.as_deref().map(read_tokenizer_tokens).unwrap_or_default();
     let config_json = dir.as_deref().and_then(read_config_json);
     let tensor_names: Vec<String> = parent.compat.tensor_names();
-
-    // GGUF metadata (chat_template, etc.)
-    // We don't have direct access to raw metadata here, but the compat info
-    // contains some architecture data. We'll use tensor names + tokenizer.
+    let rope_scaling = read_rope_scaling(parent, &config_json, gguf_metadata);
+    let chat_template = gguf_metadata
+        .and_then(|m| m.get("tokenizer.chat_template"))
+        .and_then(|v| v.as_str())
+        .map(|s| s.to_string())
+        .or_else(|| dir.as_deref().and_then(|d| crate::model::safetensors::read_chat_template(Path::new(d))));
 
     let mut capabilities = vec![
         detect_tool_calling(&tokenizer_tokens, &model_name, total_layers),
@@ -128,11 +276,15 @@ pub fn detect_capabilities(parent: &ParentModel) -> CapabilityReport {
         detect_code(&tokenizer_tokens, &model_name, total_layers),
         detect_math(&tokenizer_tokens, &model_name, total_layers),
         detect_multilingual(&tokenizer_tokens, parent.compat.vocab_size, total_layers),
-        detect_instruct(&tokenizer_tokens, &model_name, total_layers),
+        detect_instruct(&tokenizer_tokens, chat_template.as_deref(), &model_name, total_layers),
         detect_safety(&model_name, total_layers),
         detect_multimodal(&config_json, &tensor_names, total_layers),
         detect_moe(&tensor_names, &parent.compat, total_layers),
+        detect_long_context(&rope_scaling, total_layers),
     ];
+    capabilities.extend(
+        user_rules.iter().map(|rule| detect_user_rule(rule, &tokenizer_tokens, &model_name, total_layers)),
+    );
 
     let total_detected = capabilities.iter().filter(|c| c.detected).count();
 
@@ -335,7 +487,12 @@ fn detect_multilingual(tokens: &[String], vocab_size: Option<u64>, total_layers:
     }
 }
 
-fn detect_instruct(tokens: &[String], model_name: &str, total_layers: u64) -> Capability {
+fn detect_instruct(
+    tokens: &[String],
+    chat_template: Option<&str>,
+    model_name: &str,
+    total_layers: u64,
+) -> Capability {
     let mut evidence = Vec::new();
     let mut confidence: f64 = 0.0;
 
@@ -353,6 +510,23 @@ fn detect_instruct(tokens: &[String], model_name: &str, total_layers: u64) -> Ca
         }
     }
 
+    // A chat template is the strongest signal; GGUF files often carry no
+    // added_tokens list, so this is the only evidence for many of them.
+    if let Some(template) = chat_template.filter(|t| !t.trim().is_empty()) {
+        let role_markers = [
+            "<|im_start|>", "<|start_header_id|>", "[INST]",
+            "<start_of_turn>", "<|user|>", "<|assistant|>",
+        ];
+        let found: Vec<&str> = role_markers.iter().copied().filter(|m| template.contains(m)).collect();
+        if found.is_empty() {
+            evidence.push("chat_template present".into());
+            confidence += 0.3;
+        } else {
+            evidence.push(format!("chat_template role markers: {}", found.join(", ")));
+            confidence += 0.5;
+        }
+    }
+
     let name_hints = ["instruct", "chat", "it", "dpo", "rlhf", "sft"];
     for hint in &name_hints {
         if model_name.contains(hint) {
@@ -420,11 +594,7 @@ fn detect_multimodal(
     }
 
     // Check tensor names for visual/image patterns
-    let visual_count = tensor_names.iter().filter(|n| {
-        let lower = n.to_lowercase();
-        lower.contains("visual") || lower.contains("image_") || lower.contains("vision_")
-            || lower.contains("vit.") || lower.contains("clip.")
-    }).count();
+    let visual_count = tensor_names.iter().filter(|n| inspect::is_vision_tensor(n)).count();
 
     if visual_count > 0 {
         evidence.push(format!("{} visual tensors found", visual_count));
@@ -490,3 +660,350 @@ fn detect_moe(
         affected_layers: if detected { compute_affected_layers("moe", total_layers) } else { vec![] },
     }
 }
+
+fn detect_long_context(rope: &RopeScaling, total_layers: u64) -> Capability {
+    const LONG_CONTEXT_THRESHOLD: u64 = 32_768;
+
+    let mut evidence = Vec::new();
+    let mut confidence: f64 = 0.0;
+
+    let kind = rope.kind.as_deref().filter(|k| !k.is_empty() && *k != "none");
+    if let Some(factor) = rope.factor.filter(|f| *f > 1.0) {
+        evidence.push(format!("rope_scaling: {} factor {:.1}", kind.unwrap_or("unknown"), factor));
+        confidence += 0.6;
+    } else if let Some(kind) = kind {
+        evidence.push(format!("rope_scaling: {}", kind));
+        confidence += 0.3;
+    }
+
+    if let Some(original) = rope.original_context {
+        evidence.push(format!("Original context: {}", original));
+    }
+
+    if let Some(max) = rope.max_context {
+        if max > LONG_CONTEXT_THRESHOLD {
+            evidence.push(format!("Max context: {} tokens", max));
+            confidence += 0.5;
+        }
+    }
+
+    confidence = confidence.min(1.0);
+    let detected = confidence >= 0.3;
+
+    Capability {
+        id: "long_context".into(),
+        name: "LONG CONTEXT".into(),
+        detected,
+        confidence: if detected { confidence } else { 0.0 },
+        evidence,
+        affected_layers: if detected { compute_affected_layers("long_context", total_layers) } else { vec![] },
+    }
+}
+
+fn detect_user_rule(
+    rule: &CapabilityRule,
+    tokens: &[String],
+    model_name: &str,
+    total_layers: u64,
+) -> Capability {
+    let mut evidence = Vec::new();
+    let mut confidence: f64 = 0.0;
+
+    for pattern in rule.token_patterns.iter().filter(|p| !p.is_empty()) {
+        if tokens.iter().any(|t| t.contains(pattern.as_str())) {
+            evidence.push(format!("Token: {}", pattern));
+            confidence += 0.4;
+        }
+    }
+
+    for hint in rule.name_hints.iter().filter(|h| !h.is_empty()) {
+        if model_name.contains(&hint.to_lowercase()) {
+            evidence.push(format!("Model name contains '{}'", hint));
+            confidence += 0.3;
+        }
+    }
+
+    confidence = confidence.min(1.0);
+    let detected = confidence >= 0.3;
+    let (start, end) = rule.layer_range;
+
+    Capability {
+        id: rule.id.clone(),
+        name: rule.name.clone(),
+        detected,
+        confidence: if detected { confidence } else { 0.0 },
+        evidence,
+        affected_layers: if detected { layers_in_range(start, end, total_layers) } else { vec![] },
+    }
+}
+
+// ── Base Model Detection ────────────────────────────────
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BaseCandidate {
+    pub parent_id: String,
+    pub parent_name: String,
+    pub score: f64,
+    pub reasons: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BaseDetection {
+    /// Ranked best-first.
+    pub candidates: Vec<BaseCandidate>,
+    pub suggested_parent_id: Option<String>,
+}
+
+const FINETUNE_MARKERS: &[&str] = &[
+    "instruct", "chat", "-it", "_it", "-ft", "finetune", "fine-tune", "sft", "dpo", "orpo", "rlhf",
+];
+
+/// Read `base_model` entries from a model card's YAML front matter
+/// (either `base_model: org/name` or a `- org/name` list).
+fn read_card_base_models(dir_path: &str) -> Vec<String> {
+    let content = match std::fs::read_to_string(Path::new(dir_path).join("README.md")) {
+        Ok(c) => c,
+        Err(_) => return vec![],
+    };
+    let mut lines = content.lines();
+    if lines.next().map(|l| l.trim()) != Some("---") {
+        return vec![];
+    }
+
+    let mut bases = Vec::new();
+    let mut in_base_list = false;
+    for line in lines {
+        let trimmed = line.trim();
+        if trimmed == "---" {
+            break;
+        }
+        if let Some(value) = trimmed.strip_prefix("base_model:") {
+            let value = value.trim().trim_matches(|c| c == '"' || c == '\'');
+            in_base_list = value.is_empty();
+            if !value.is_empty() {
+                bases.push(value.to_string());
+            }
+        } else if in_base_list {
+            match trimmed.strip_prefix("- ") {
+                Some(item) => bases.push(item.trim().trim_matches(|c| c == '"' || c == '\'').to_string()),
+                None => in_base_list = false,
+            }
+        }
+    }
+    bases
+}
+
+/// Lowercased last path segment with model-file extensions removed.
+fn model_ident(name: &str) -> String {
+    let last = name.rsplit(['/', '\\']).next().unwrap_or(name).to_lowercase();
+    last.trim_end_matches(".gguf").trim_end_matches(".safetensors").to_string()
+}
+
+/// Whether `ident` names the model `reference` points at, allowing a trailing
+/// quantization tag on GGUF file names (e.g. `llama-3-8b.q4_k_m`).
+fn refers_to(ident: &str, reference: &str) -> bool {
+    let reference = model_ident(reference);
+    if reference.is_empty() {
+        return false;
+    }
+    ident == reference
+        || ident
+            .strip_prefix(&reference)
+            .map_or(false, |rest| {
+                let rest = rest.trim_start_matches(['.', '-', '_']);
+                ["q", "iq", "f16", "f32", "bf16"].iter().any(|t| rest.starts_with(t))
+                    && rest.len() < 10
+            })
+}
+
+/// Instruct-capability evidence that only chat-tuned models carry. BOS tokens
+/// are shared with base models and name hints are scored separately.
+fn chat_evidence(report: &CapabilityReport) -> Vec<String> {
+    report
+        .capabilities
+        .iter()
+        .filter(|c| c.id == "instruct")
+        .flat_map(|c| c.evidence.iter())
+        .filter(|e| {
+            !e.starts_with("Model name")
+                && e.as_str() != "Token: <s>"
+                && e.as_str() != "Token: <|begin_of_text|>"
+        })
+        .cloned()
+        .collect()
+}
+
+/// Total parameter count from a parent's tensor shapes.
+fn param_count(parent: &ParentModel) -> u64 {
+    parent
+        .compat
+        .tensor_metas
+        .iter()
+        .map(|t| t.shape.iter().map(|&d| d as u64).product::<u64>())
+        .sum()
+}
+
+/// Rank parents by how likely each is the shared base of the others, using
+/// config.json `_name_or_path`, README `base_model`, naming conventions, the
+/// architecture and parameter count the parents share, and chat tokens or
+/// templates found by capability detection (`reports`, matched by parent id).
+pub fn detect_base(parents: &[ParentModel], reports: &[CapabilityReport]) -> BaseDetection {
+    struct Info<'a> {
+        parent: &'a ParentModel,
+        ident: String,
+        declared_bases: Vec<String>,
+        name_or_path: Option<String>,
+        arch: Option<String>,
+        params: u64,
+    }
+
+    let infos: Vec<Info> = parents
+        .iter()
+        .map(|parent| {
+            let dir = resolve_model_dir(parent);
+            let declared_bases = dir.as_deref().map(read_card_base_models).unwrap_or_default();
+            let name_or_path = dir
+                .as_deref()
+                .and_then(read_config_json)
+                .and_then(|c| c.get("_name_or_path").and_then(|v| v.as_str()).map(|s| s.to_string()));
+            let ident = model_ident(&parent.name);
+            let arch = parent.compat.architecture.as_deref().map(super::names::canonical_arch);
+            let params = param_count(parent);
+            Info { parent, ident, declared_bases, name_or_path, arch, params }
+        })
+        .collect();
+
+    // The base shares the architecture most parents have
+    let mut arch_counts: HashMap<&str, usize> = HashMap::new();
+    for arch in infos.iter().filter_map(|i| i.arch.as_deref()) {
+        *arch_counts.entry(arch).or_default() += 1;
+    }
+    let known_archs: usize = arch_counts.values().sum();
+    let majority_arch = arch_counts
+        .iter()
+        .find(|(_, count)| **count * 2 > known_archs && **count > 1)
+        .map(|(arch, _)| arch.to_string());
+    let min_vocab = parents.iter().filter_map(|p| p.compat.vocab_size).min();
+
+    let mut candidates: Vec<BaseCandidate> = infos
+        .iter()
+        .map(|info| {
+            let mut score = 0.0;
+            let mut reasons = Vec::new();
+
+            for other in infos.iter().filter(|o| o.parent.id != info.parent.id) {
+                if other.declared_bases.iter().any(|b| refers_to(&info.ident, b)) {
+                    score += 3.0;
+                    reasons.push(format!("Model card of {} lists it as base_model", other.parent.name));
+                }
+                if let Some(nop) = &other.name_or_path {
+                    if model_ident(nop) != other.ident && refers_to(&info.ident, nop) {
+                        score += 2.0;
+                        reasons.push(format!("config.json of {} points to it via _name_or_path", other.parent.name));
+                    }
+                }
+            }
+
+            if !info.declared_bases.is_empty() {
+                score -= 1.0;
+                reasons.push(format!(
+                    "Declares base_model {} (likely a fine-tune)",
+                    info.declared_bases.join(", ")
+                ));
+            }
+
+            if let (Some(majority), Some(arch)) = (&majority_arch, &info.arch) {
+                if arch != majority {
+                    score -= 2.0;
+                    reasons.push(format!("Architecture {} differs from the others ({})", arch, majority));
+                }
+            }
+
+            if info.params > 0 {
+                let same = infos
+                    .iter()
+                    .filter(|o| o.parent.id != info.parent.id && o.params == info.params)
+                    .count();
+                if same > 0 {
+                    score += 0.5;
+                    reasons.push(format!(
+                        "Same parameter count ({}) as {} other parent(s)",
+                        crate::model::format_param_count(info.params),
+                        same
+                    ));
+                }
+            }
+
+            if let (Some(vocab), Some(min)) = (info.parent.compat.vocab_size, min_vocab) {
+                if vocab > min {
+                    score -= 0.5;
+                    reasons.push(format!("Vocab is {} tokens larger than the smallest parent (added tokens)", vocab - min));
+                }
+            }
+
+            if let Some(report) = reports.iter().find(|r| r.parent_id == info.parent.id) {
+                let evidence = chat_evidence(report);
+                if evidence.is_empty() {
+                    score += 0.5;
+                    reasons.push("No chat template or chat tokens".into());
+                } else {
+                    score -= 1.0;
+                    reasons.push(format!("Chat tuning detected: {}", evidence.join(", ")));
+                }
+            }
+
+            if let Some(marker) = FINETUNE_MARKERS.iter().find(|m| info.ident.contains(*m)) {
+                score -= 1.0;
+                reasons.push(format!("Name contains \"{}\" (fine-tune suffix)", marker.trim_matches(['-', '_'])));
+            } else {
+                score += 0.5;
+                reasons.push("Name has no instruct/fine-tune suffix".into());
+            }
+            if info.ident.contains("base") {
+                score += 1.0;
+                reasons.push("Name contains \"base\"".into());
+            }
+
+            BaseCandidate {
+                parent_id: info.parent.id.clone(),
+                parent_name: info.parent.name.clone(),
+                score,
+                reasons,
+            }
+        })
+        .collect();
+
+    candidates.sort_by(|a, b| b.score.partial_cmp(&a.score).unwrap_or(std::cmp::Ordering::Equal));
+
+    // Only suggest when the top candidate is strictly ahead
+    let suggested_parent_id = match candidates.as_slice() {
+        [first, second, ..] if first.score > second.score => Some(first.parent_id.clone()),
+        [only] => Some(only.parent_id.clone()),
+        _ => None,
+    };
+
+    BaseDetection { candidates, suggested_parent_id }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const QWEN_TEMPLATE: &str = "{% for message in messages %}{{'<|im_start|>' + message['role'] + '\\n' + message['content'] + '<|im_end|>' + '\\n'}}{% endfor %}{% if add_generation_prompt %}{{ '<|im_start|>assistant\\n' }}{% endif %}";
+
+    #[test]
+    fn qwen_chat_template_marks_instruct() {
+        let cap = detect_instruct(&[], Some(QWEN_TEMPLATE), "qwen2.5-7b", 28);
+        assert!(cap.detected);
+        assert!((cap.confidence - 0.5).abs() < 1e-9);
+        assert_eq!(cap.evidence, ["chat_template role markers: <|im_start|>"]);
+        assert!(!cap.affected_layers.is_empty());
+    }
+
+    #[test]
+    fn blank_chat_template_is_ignored() {
+        let cap = detect_instruct(&[], Some("  \n"), "qwen2.5-7b", 28);
+        assert!(!cap.detected);
+        assert!(cap.evidence.is_empty());
+    }
+}
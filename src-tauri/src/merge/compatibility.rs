@@ -1,6 +1,13 @@
 use serde::{Deserialize, Serialize};
 
-use super::registry::ParentRegistry;
+use crate::model::error::ModelError;
+
+use std::collections::HashMap;
+
+use super::names;
+use super::registry::{ParentModel, ParentRegistry};
+use super::tensor_io;
+use super::vocab;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct DimensionMismatch {
@@ -33,6 +40,190 @@ pub struct CompatReport {
     pub dimension_details: Vec<DimensionMismatch>,
     #[serde(default)]
     pub resolution_strategies: Vec<ResolutionStrategy>,
+    /// One row per tensor in the union of all parents, in first-seen order.
+    #[serde(default)]
+    pub tensor_report: Vec<TensorCompat>,
+}
+
+/// How one tensor lines up across the parents. Shapes are given in
+/// HuggingFace orientation so GGUF and SafeTensors parents compare directly.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TensorCompat {
+    /// Canonical (HuggingFace-style) name
+    pub tensor_name: String,
+    pub present_in: Vec<String>,
+    pub missing_from: Vec<String>,
+    /// (parent name, shape) for every parent that has the tensor
+    pub shapes: Vec<(String, Vec<usize>)>,
+    pub shapes_match: bool,
+    /// "ok", "tolerable" (missing from some parents, or a vocab-size
+    /// difference the vocab strategy aligns) or "fatal" (shape mismatch)
+    pub severity: String,
+    pub issue: Option<String>,
+}
+
+/// Shape of a tensor meta in HuggingFace orientation.
+fn canonical_shape(parent: &ParentModel, shape: &[usize]) -> Vec<usize> {
+    match parent.format {
+        crate::model::ModelFormat::Gguf => names::reversed_shape(shape),
+        _ => shape.to_vec(),
+    }
+}
+
+/// Compare every tensor in the union of the parents' tensors.
+fn tensor_report(parents: &[ParentModel]) -> Vec<TensorCompat> {
+    // Parents are keyed by id: two loaded copies of one model share a name
+    let mut order: Vec<String> = Vec::new();
+    let mut seen: HashMap<String, Vec<(&ParentModel, Vec<usize>)>> = HashMap::new();
+    for parent in parents {
+        for meta in &parent.compat.tensor_metas {
+            let canonical = names::canonical_name(&meta.name);
+            let entry = seen.entry(canonical.clone()).or_insert_with(|| {
+                order.push(canonical);
+                Vec::new()
+            });
+            // A parent only counts once per tensor even if two of its names collapse
+            if !entry.iter().any(|(p, _)| p.id == parent.id) {
+                entry.push((parent, canonical_shape(parent, &meta.shape)));
+            }
+        }
+    }
+
+    order
+        .into_iter()
+        .map(|tensor_name| {
+            let found = seen.remove(&tensor_name).unwrap_or_default();
+            let present_in: Vec<String> = found.iter().map(|(p, _)| p.name.clone()).collect();
+            let missing_from: Vec<String> = parents
+                .iter()
+                .filter(|p| !found.iter().any(|(f, _)| f.id == p.id))
+                .map(|p| p.name.clone())
+                .collect();
+            let shapes: Vec<(String, Vec<usize>)> =
+                found.into_iter().map(|(p, shape)| (p.name.clone(), shape)).collect();
+            let shapes_match = shapes.windows(2).all(|w| w[0].1 == w[1].1);
+
+            let (severity, issue) = if !shapes_match {
+                let listed = shapes
+                    .iter()
+                    .map(|(_, shape)| format!("{:?}", shape))
+                    .collect::<Vec<_>>()
+                    .join(" vs ");
+                if vocab::is_vocab_tensor(&tensor_name) {
+                    ("tolerable", Some(format!("vocab size differs: {} (aligned by the vocab strategy)", listed)))
+                } else {
+                    ("fatal", Some(format!("shape mismatch: {}", listed)))
+                }
+            } else if !missing_from.is_empty() {
+                ("tolerable", Some(format!("missing from {}", missing_from.join(", "))))
+            } else {
+                ("ok", None)
+            };
+
+            TensorCompat {
+                tensor_name,
+                present_in,
+                missing_from,
+                shapes,
+                shapes_match,
+                severity: severity.to_string(),
+                issue,
+            }
+        })
+        .collect()
+}
+
+/// How far apart one tensor is in two parents. When the shapes differ only
+/// the per-parent norms are filled in.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TensorDiff {
+    pub tensor_name: String,
+    pub parent_a: String,
+    pub parent_b: String,
+    pub shape_a: Vec<usize>,
+    pub shape_b: Vec<usize>,
+    pub compatible: bool,
+    /// "incompatible shapes: [..] vs [..]" when `compatible` is false
+    pub reason: Option<String>,
+    pub cosine_similarity: Option<f64>,
+    pub l2_distance: Option<f64>,
+    pub mean_abs_diff: Option<f64>,
+    pub norm_a: f64,
+    pub norm_b: f64,
+}
+
+/// L2 norm of a flattened tensor.
+fn l2_norm(data: &[f32]) -> f64 {
+    data.iter().map(|&x| x as f64 * x as f64).sum::<f64>().sqrt()
+}
+
+/// Cosine similarity, L2 distance and mean absolute difference of two
+/// equal-length vectors. Cosine is 0 when either vector is all zeros.
+fn diff_stats(a: &[f32], b: &[f32]) -> (f64, f64, f64) {
+    let (mut dot, mut na, mut nb, mut sq, mut abs) = (0.0f64, 0.0f64, 0.0f64, 0.0f64, 0.0f64);
+    for (&x, &y) in a.iter().zip(b) {
+        let (x, y) = (x as f64, y as f64);
+        dot += x * y;
+        na += x * x;
+        nb += y * y;
+        sq += (x - y) * (x - y);
+        abs += (x - y).abs();
+    }
+    let cosine = if na == 0.0 || nb == 0.0 { 0.0 } else { dot / (na.sqrt() * nb.sqrt()) };
+    let mean_abs = if a.is_empty() { 0.0 } else { abs / a.len() as f64 };
+    (cosine, sq.sqrt(), mean_abs)
+}
+
+/// Load `tensor_name` (as named by `a`) from both parents, `b` converted into
+/// `a`'s layout, and compare them element-wise.
+pub fn tensor_diff(tensor_name: &str, a: &ParentModel, b: &ParentModel) -> Result<TensorDiff, ModelError> {
+    let map_err = |e: candle_core::Error| ModelError::CandleError(e.to_string());
+    let flat = |t: candle_core::Tensor| -> Result<(Vec<usize>, Vec<f32>), ModelError> {
+        let shape = t.dims().to_vec();
+        let data = t
+            .to_dtype(candle_core::DType::F32).map_err(map_err)?
+            .flatten_all().map_err(map_err)?
+            .to_vec1::<f32>().map_err(map_err)?;
+        Ok((shape, data))
+    };
+
+    let (shape_a, data_a) = flat(tensor_io::load_tensor(a, tensor_name)?)?;
+    let (shape_b, data_b) = flat(tensor_io::load_tensor_in_layout(b, tensor_name, a)?)?;
+    let norm_a = l2_norm(&data_a);
+    let norm_b = l2_norm(&data_b);
+
+    if shape_a != shape_b {
+        return Ok(TensorDiff {
+            tensor_name: tensor_name.to_string(),
+            parent_a: a.id.clone(),
+            parent_b: b.id.clone(),
+            reason: Some(format!("incompatible shapes: {:?} vs {:?}", shape_a, shape_b)),
+            shape_a,
+            shape_b,
+            compatible: false,
+            cosine_similarity: None,
+            l2_distance: None,
+            mean_abs_diff: None,
+            norm_a,
+            norm_b,
+        });
+    }
+
+    let (cosine, l2, mean_abs) = diff_stats(&data_a, &data_b);
+    Ok(TensorDiff {
+        tensor_name: tensor_name.to_string(),
+        parent_a: a.id.clone(),
+        parent_b: b.id.clone(),
+        shape_a,
+        shape_b,
+        compatible: true,
+        reason: None,
+        cosine_similarity: Some(cosine),
+        l2_distance: Some(l2),
+        mean_abs_diff: Some(mean_abs),
+        norm_a,
+        norm_b,
+    })
 }
 
 pub fn check_compatibility(registry: &ParentRegistry) -> CompatReport {
@@ -54,6 +245,7 @@ pub fn check_compatibility(registry: &ParentRegistry) -> CompatReport {
             layer_count_match: false,
             dimension_details: vec![],
             resolution_strategies: vec![],
+            tensor_report: vec![],
         };
     }
 
@@ -68,8 +260,8 @@ pub fn check_compatibility(registry: &ParentRegistry) -> CompatReport {
             warnings.push("Could not verify architecture compatibility (metadata missing)".to_string());
             true
         } else {
-            let first = known[0].to_lowercase();
-            let all_same = known.iter().all(|a| a.to_lowercase() == first);
+            let first = names::canonical_arch(known[0]);
+            let all_same = known.iter().all(|a| names::canonical_arch(a) == first);
             if !all_same {
                 errors.push(format!(
                     "Architecture mismatch: {}",
@@ -146,7 +338,7 @@ pub fn check_compatibility(registry: &ParentRegistry) -> CompatReport {
         .collect();
     if vocab_sizes.len() >= 2 && !vocab_sizes.iter().all(|&v| v == vocab_sizes[0]) {
         warnings.push(format!(
-            "Vocab size mismatch: {}",
+            "Vocab size mismatch: {} (embedding and output tensors will be aligned by the vocab strategy)",
             vocab_sizes.iter().map(|v| v.to_string()).collect::<Vec<_>>().join(" vs ")
         ));
         dimension_details.push(DimensionMismatch {
@@ -245,11 +437,24 @@ pub fn check_compatibility(registry: &ParentRegistry) -> CompatReport {
         .map(|p| match &p.format {
             crate::model::ModelFormat::SafeTensors => "SafeTensors",
             crate::model::ModelFormat::Gguf => "GGUF",
+            crate::model::ModelFormat::PyTorch => "PyTorch",
         })
         .collect();
     let has_mixed = formats.windows(2).any(|w| w[0] != w[1]);
     if has_mixed {
-        warnings.push("Mixed formats detected (GGUF tensors will be dequantized to F32)".to_string());
+        warnings.push("Mixed formats detected (GGUF tensors will be dequantized to F32 and matched to SafeTensors by canonical name)".to_string());
+    }
+
+    // Per-tensor comparison. A hidden-dimension mismatch already explains the
+    // fatal rows, so only report them separately when the dimensions agree.
+    let tensor_report = tensor_report(parents);
+    let fatal: Vec<&TensorCompat> = tensor_report.iter().filter(|t| t.severity == "fatal").collect();
+    if !fatal.is_empty() && dim_match {
+        errors.push(format!(
+            "{} tensor(s) have mismatched shapes (first: {})",
+            fatal.len(),
+            fatal[0].tensor_name
+        ));
     }
 
     let compatible = errors.is_empty();
@@ -268,6 +473,7 @@ pub fn check_compatibility(registry: &ParentRegistry) -> CompatReport {
         layer_count_match: layer_match,
         dimension_details,
         resolution_strategies,
+        tensor_report,
     }
 }
 
@@ -339,3 +545,115 @@ fn generate_resolution_strategies(mismatches: &[DimensionMismatch]) -> Vec<Resol
 
     strategies
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::merge::registry::{CompatInfo, TensorMeta};
+
+    fn parent(id: &str, name: &str, tensors: &[&str]) -> ParentModel {
+        ParentModel {
+            id: id.into(),
+            slot: 0,
+            name: name.into(),
+            file_path: String::new(),
+            format: crate::model::ModelFormat::SafeTensors,
+            file_size: 0,
+            file_size_display: String::new(),
+            parameter_count: 0,
+            parameter_count_display: String::new(),
+            layer_count: None,
+            architecture: None,
+            quantization: None,
+            compat: CompatInfo {
+                architecture: None,
+                hidden_size: None,
+                num_layers: None,
+                num_attention_heads: None,
+                num_kv_heads: None,
+                vocab_size: None,
+                context_length: None,
+                head_dim: None,
+                rope_theta: None,
+                rms_norm_eps: None,
+                tensor_metas: tensors
+                    .iter()
+                    .map(|name| TensorMeta { name: name.to_string(), shape: vec![8, 8], dtype: "F16".into() })
+                    .collect(),
+            },
+            color: String::new(),
+            is_dir: false,
+        }
+    }
+
+    #[test]
+    fn same_named_parents_are_tracked_separately() {
+        let parents = [
+            parent("a", "model", &["model.norm.weight", "lm_head.weight"]),
+            parent("b", "model", &["model.norm.weight"]),
+        ];
+        let report = tensor_report(&parents);
+
+        let norm = report.iter().find(|t| t.tensor_name == "model.norm.weight").unwrap();
+        assert_eq!(norm.present_in.len(), 2);
+        assert!(norm.missing_from.is_empty());
+        assert_eq!(norm.severity, "ok");
+
+        let head = report.iter().find(|t| t.tensor_name == "lm_head.weight").unwrap();
+        assert_eq!(head.present_in, ["model"]);
+        assert_eq!(head.missing_from, ["model"]);
+        assert_eq!(head.severity, "tolerable");
+    }
+
+    /// A one-tensor F32 safetensors file named `w`.
+    fn write_safetensors(path: &std::path::Path, shape: &[usize], values: &[f32]) {
+        let header = format!(
+            r#"{{"w":{{"dtype":"F32","shape":{:?},"data_offsets":[0,{}]}}}}"#,
+            shape,
+            values.len() * 4
+        );
+        let mut bytes = (header.len() as u64).to_le_bytes().to_vec();
+        bytes.extend_from_slice(header.as_bytes());
+        bytes.extend(values.iter().flat_map(|v| v.to_le_bytes()));
+        std::fs::write(path, bytes).unwrap();
+    }
+
+    fn file_parent(id: &str, path: &std::path::Path) -> ParentModel {
+        ParentModel { file_path: path.to_string_lossy().to_string(), ..parent(id, id, &["w"]) }
+    }
+
+    #[test]
+    fn tensor_diff_matches_hand_computed_metrics() {
+        let dir = std::env::temp_dir().join(format!("forgeai-diff-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        write_safetensors(&dir.join("a.safetensors"), &[2, 2], &[3.0, 4.0, 0.0, 0.0]);
+        write_safetensors(&dir.join("b.safetensors"), &[2, 2], &[0.0, 4.0, 3.0, 0.0]);
+        write_safetensors(&dir.join("c.safetensors"), &[4], &[3.0, 4.0, 0.0, 0.0]);
+        let a = file_parent("a", &dir.join("a.safetensors"));
+        let b = file_parent("b", &dir.join("b.safetensors"));
+        let c = file_parent("c", &dir.join("c.safetensors"));
+        let same = tensor_diff("w", &a, &b);
+        let mismatched = tensor_diff("w", &a, &c);
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        // a·b = 16, |a| = |b| = 5, a - b = [3, 0, -3, 0]
+        let same = same.unwrap();
+        assert!(same.compatible);
+        assert!((same.cosine_similarity.unwrap() - 0.64).abs() < 1e-9);
+        assert!((same.l2_distance.unwrap() - 18f64.sqrt()).abs() < 1e-9);
+        assert!((same.mean_abs_diff.unwrap() - 1.5).abs() < 1e-9);
+        assert_eq!((same.norm_a, same.norm_b), (5.0, 5.0));
+
+        let mismatched = mismatched.unwrap();
+        assert!(!mismatched.compatible);
+        assert_eq!(mismatched.reason.as_deref(), Some("incompatible shapes: [2, 2] vs [4]"));
+        assert_eq!(mismatched.cosine_similarity, None);
+        assert_eq!((mismatched.norm_a, mismatched.norm_b), (5.0, 5.0));
+    }
+
+    #[test]
+    fn zero_vectors_have_zero_cosine() {
+        assert_eq!(diff_stats(&[0.0, 0.0], &[1.0, 1.0]), (0.0, 2f64.sqrt(), 1.0));
+        assert_eq!(diff_stats(&[], &[]), (0.0, 0.0, 0.0));
+    }
+}
@@ -1,3 +1,5 @@
+use std::collections::HashMap;
+
 use serde::{Deserialize, Serialize};
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
@@ -10,11 +12,19 @@ pub enum MergeMethod {
     Dare,
     Ties,
     Della,
+    DellaLinear,
     Passthrough,
     ComponentMerge,
     TensorSurgery,
     ParameterSlice,
     MoeConversion,
+    MaxMagnitude,
+    Linear,
+    ModelStock,
+    Sce,
+    #[serde(rename = "nuslerp")]
+    NuSlerp,
+    Breadcrumbs,
 }
 
 impl MergeMethod {
@@ -27,11 +37,18 @@ impl MergeMethod {
             Self::Dare => "DARE",
             Self::Ties => "TIES",
             Self::Della => "DELLA",
+            Self::DellaLinear => "DELLA LINEAR",
             Self::Passthrough => "PASSTHROUGH",
             Self::ComponentMerge => "COMPONENT MERGE",
             Self::TensorSurgery => "TENSOR SURGERY",
             Self::ParameterSlice => "PARAMETER SLICE",
             Self::MoeConversion => "MOE CONVERSION",
+            Self::MaxMagnitude => "MAX MAGNITUDE",
+            Self::Linear => "LINEAR",
+            Self::ModelStock => "MODEL STOCK",
+            Self::Sce => "SCE",
+            Self::NuSlerp => "NUSLERP",
+            Self::Breadcrumbs => "BREADCRUMBS",
         }
     }
 
@@ -44,16 +61,28 @@ impl MergeMethod {
             Self::Dare => "Drop and rescale: random dropout with rescaling",
             Self::Ties => "Trim, elect sign, merge: task-specific merging",
             Self::Della => "Density-based DARE with lambda interpolation",
+            Self::DellaLinear => "DELLA magnitude pruning without rescaling survivors",
             Self::Passthrough => "Direct copy of tensors from a single parent",
             Self::ComponentMerge => "Route attention/MLP/norm to different parents",
             Self::TensorSurgery => "Per-tensor source mapping from parents",
             Self::ParameterSlice => "Dimensional slicing across parents",
             Self::MoeConversion => "Convert dense models to Mixture-of-Experts",
+            Self::MaxMagnitude => "Keep the largest-magnitude value per element",
+            Self::Linear => "Weighted average with per-layer weight curves, renormalized per layer",
+            Self::ModelStock => "Average fine-tunes, interpolated toward the base by task-vector angle",
+            Self::Sce => "Select by variance, calculate weights, erase sign conflicts",
+            Self::NuSlerp => "Weight-driven SLERP, optionally per row/column or around a base",
+            Self::Breadcrumbs => "Task arithmetic with the largest and smallest task-vector elements trimmed",
         }
     }
 
     pub fn requires_base(&self) -> bool {
-        matches!(self, Self::TaskArithmetic | Self::Dare | Self::Ties | Self::Della)
+        matches!(self, Self::TaskArithmetic | Self::Dare | Self::Ties | Self::Della | Self::DellaLinear | Self::ModelStock | Self::Sce | Self::Breadcrumbs)
+    }
+
+    /// Whether a base model is used when one is set (always true if required).
+    pub fn accepts_base(&self) -> bool {
+        self.requires_base() || matches!(self, Self::NuSlerp)
     }
 
     pub fn min_parents(&self) -> usize {
@@ -65,9 +94,9 @@ impl MergeMethod {
 
     pub fn difficulty(&self) -> &'static str {
         match self {
-            Self::Average | Self::Slerp | Self::Passthrough => "easy",
-            Self::TaskArithmetic | Self::Frankenmerge | Self::Dare | Self::Ties => "intermediate",
-            Self::Della | Self::ComponentMerge | Self::TensorSurgery | Self::ParameterSlice | Self::MoeConversion => "advanced",
+            Self::Average | Self::Slerp | Self::Passthrough | Self::MaxMagnitude => "easy",
+            Self::TaskArithmetic | Self::Frankenmerge | Self::Dare | Self::Ties | Self::Linear | Self::NuSlerp | Self::Breadcrumbs => "intermediate",
+            Self::Della | Self::DellaLinear | Self::ModelStock | Self::Sce | Self::ComponentMerge | Self::TensorSurgery | Self::ParameterSlice | Self::MoeConversion => "advanced",
         }
     }
 
@@ -80,11 +109,18 @@ impl MergeMethod {
             Self::Dare,
             Self::Ties,
             Self::Della,
+            Self::DellaLinear,
             Self::Passthrough,
             Self::ComponentMerge,
             Self::TensorSurgery,
             Self::ParameterSlice,
             Self::MoeConversion,
+            Self::MaxMagnitude,
+            Self::Linear,
+            Self::ModelStock,
+            Self::Sce,
+            Self::NuSlerp,
+            Self::Breadcrumbs,
         ]
     }
 }
@@ -96,11 +132,143 @@ pub enum OutputFormat {
     Gguf,
 }
 
+/// Element type for SafeTensors output data.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OutputDtype {
+    F32,
+    F16,
+    Bf16,
+}
+
+impl OutputDtype {
+    /// Dtype string used in the SafeTensors header.
+    pub fn safetensors_name(&self) -> &'static str {
+        match self {
+            Self::F32 => "F32",
+            Self::F16 => "F16",
+            Self::Bf16 => "BF16",
+        }
+    }
+
+    /// `torch_dtype` value written to config.json.
+    pub fn torch_name(&self) -> &'static str {
+        match self {
+            Self::F32 => "float32",
+            Self::F16 => "float16",
+            Self::Bf16 => "bfloat16",
+        }
+    }
+
+    pub fn size_in_bytes(&self) -> u64 {
+        match self {
+            Self::F32 => 4,
+            Self::F16 | Self::Bf16 => 2,
+        }
+    }
+
+    /// Keep half precision when every source tensor already used it
+    /// (lossless round-trip); anything mixed or quantized falls back to F32.
+    pub fn infer<'a>(source_dtypes: impl IntoIterator<Item = &'a str>) -> Self {
+        let mut seen: Option<Self> = None;
+        for dtype in source_dtypes {
+            let this = match dtype.to_uppercase().as_str() {
+                "F16" => Self::F16,
+                "BF16" => Self::Bf16,
+                _ => return Self::F32,
+            };
+            match seen {
+                Some(prev) if prev != this => return Self::F32,
+                _ => seen = Some(this),
+            }
+        }
+        seen.unwrap_or(Self::F32)
+    }
+}
+
+/// Tensor encoding for GGUF output. Quantized targets apply to 2D weight
+/// tensors; norms and other 1D tensors are written as F16.
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+pub enum GgufQuantType {
+    #[default]
+    #[serde(rename = "F32")]
+    F32,
+    #[serde(rename = "F16")]
+    F16,
+    #[serde(rename = "Q8_0")]
+    Q8_0,
+    #[serde(rename = "Q4_0")]
+    Q4_0,
+    #[serde(rename = "Q4_K_S")]
+    Q4KS,
+    #[serde(rename = "Q4_K_M")]
+    Q4KM,
+    #[serde(rename = "Q6_K")]
+    Q6K,
+}
+
+impl GgufQuantType {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::F32 => "F32",
+            Self::F16 => "F16",
+            Self::Q8_0 => "Q8_0",
+            Self::Q4_0 => "Q4_0",
+            Self::Q4KS => "Q4_K_S",
+            Self::Q4KM => "Q4_K_M",
+            Self::Q6K => "Q6_K",
+        }
+    }
+
+    /// llama.cpp `llama_ftype` value written to `general.file_type`.
+    pub fn file_type(&self) -> u32 {
+        match self {
+            Self::F32 => 0,
+            Self::F16 => 1,
+            Self::Q4_0 => 2,
+            Self::Q8_0 => 7,
+            Self::Q4KS => 14,
+            Self::Q4KM => 15,
+            Self::Q6K => 18,
+        }
+    }
+}
+
+/// How embedding and output tensors are reconciled when parents have
+/// different vocabulary sizes.
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum VocabStrategy {
+    /// Zero-pad to the largest vocab and keep that parent's tokenizer.
+    #[default]
+    Pad,
+    /// Truncate to the smallest vocab and keep that parent's tokenizer.
+    Truncate,
+    /// Keep the primary (base, else first) parent's tokenizer and remap the
+    /// other parents' embedding/output rows to it by token string. Rows for
+    /// tokens a parent lacks are taken from the primary.
+    Transplant,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct OutputConfig {
     pub format: OutputFormat,
     pub path: String,
     pub model_name: String,
+    /// SafeTensors element type; `None` infers it from the parents' tensors.
+    #[serde(default)]
+    pub dtype: Option<OutputDtype>,
+    /// GGUF tensor encoding; defaults to unquantized F32.
+    #[serde(default)]
+    pub target_type: GgufQuantType,
+    /// Split SafeTensors output into `model-0000N-of-0000M.safetensors` shards of at
+    /// most this many bytes, with a `model.safetensors.index.json` weight map.
+    #[serde(default)]
+    pub max_shard_bytes: Option<u64>,
+    /// When building GGUF tokenizer metadata, fill vocab gaps with `<unused{id}>`
+    /// placeholder tokens (typed unused) instead of empty strings.
+    #[serde(default)]
+    pub repair_tokenizer_gaps: bool,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -131,32 +299,60 @@ pub struct ComponentOverride {
     pub layer_end: u64,
 }
 
+/// A run of one parent's layers placed next in the output stack (mergekit's
+/// `slices`). Both ends are inclusive, as in `ComponentOverride`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LayerSlice {
+    pub parent_id: String,
+    pub layer_start: u64,
+    pub layer_end: u64,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct TensorOverride {
     pub tensor_name: String,
     pub parent_id: String,
 }
 
+/// Control point of a per-layer weight curve; weights between points are
+/// linearly interpolated and held constant beyond the first/last point.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct CurvePoint {
+    pub layer: f64,
+    pub weight: f64,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct MethodParams {
     // SLERP
     pub t: Option<f64>,
     // Task Arithmetic
     pub scaling: Option<f64>,
-    // DARE
+    // DARE / SCE / Breadcrumbs
     pub density: Option<f64>,
+    // Breadcrumbs: fraction of largest-magnitude elements dropped
+    #[serde(default)]
+    pub gamma: Option<f64>,
     // TIES
     pub majority_sign_method: Option<String>,
     pub trim_threshold: Option<f64>,
-    // DELLA
+    // DELLA / DELLA linear
     pub lambda: Option<f64>,
     pub della_density: Option<f64>,
+    // NuSLERP
+    #[serde(default)]
+    pub nuslerp_flatten: Option<bool>,
+    #[serde(default)]
+    pub nuslerp_row_wise: Option<bool>,
     // MoE
     pub num_experts: Option<usize>,
     pub experts_per_token: Option<usize>,
     // Parameter Slice
     pub slice_dim: Option<usize>,
     pub slice_ranges: Option<Vec<(usize, usize)>>,
+    // Linear: one curve per parent, in `parents` order
+    #[serde(default)]
+    pub weight_curves: Option<Vec<Vec<CurvePoint>>>,
 }
 
 impl Default for MethodParams {
@@ -165,14 +361,18 @@ impl Default for MethodParams {
             t: None,
             scaling: None,
             density: None,
+            gamma: None,
             majority_sign_method: None,
             trim_threshold: None,
             lambda: None,
             della_density: None,
+            nuslerp_flatten: None,
+            nuslerp_row_wise: None,
             num_experts: None,
             experts_per_token: None,
             slice_dim: None,
             slice_ranges: None,
+            weight_curves: None,
         }
     }
 }
@@ -195,6 +395,20 @@ pub struct MergeConfig {
     pub skip_layers: Vec<u64>,
     #[serde(default = "default_batch_size")]
     pub batch_size: usize,
+    /// Record per-tensor load/merge/write timings and return a `MergePerfReport`.
+    #[serde(default)]
+    pub profile: bool,
+    #[serde(default)]
+    pub vocab_strategy: VocabStrategy,
+    /// Ordered layer slices for Frankenmerge/Passthrough. When set, the output
+    /// layers are exactly these slices in order, renumbered from 0.
+    #[serde(default)]
+    pub slices: Vec<LayerSlice>,
+    /// Per-parent weights (in `parents` order) replacing the defaults for matching
+    /// tensors. Keys are exact tensor names, `*` globs, or component names from
+    /// `classify_tensor` ("attention", "mlp", ...); see `planner::weight_override`.
+    #[serde(default)]
+    pub weight_overrides: HashMap<String, Vec<f64>>,
 }
 
 fn default_batch_size() -> usize {
@@ -207,6 +421,7 @@ pub struct MergeMethodInfo {
     pub name: String,
     pub description: String,
     pub requires_base: bool,
+    pub accepts_base: bool,
     pub min_parents: usize,
     pub difficulty: String,
 }
@@ -218,6 +433,7 @@ impl From<MergeMethod> for MergeMethodInfo {
             name: m.display_name().to_string(),
             description: m.description().to_string(),
             requires_base: m.requires_base(),
+            accepts_base: m.accepts_base(),
             min_parents: m.min_parents(),
             difficulty: m.difficulty().to_string(),
         }
@@ -1,20 +1,25 @@
 use std::sync::atomic::{AtomicBool, Ordering};
+use std::collections::VecDeque;
 use std::sync::Arc;
+use std::time::{Duration, Instant};
 
 use candle_core::{DType, Device, Tensor};
 use serde::{Deserialize, Serialize};
 use tauri::{AppHandle, Emitter};
 
 use crate::model::error::ModelError;
+use crate::model::inspect;
 
-use super::config::{MergeConfig, OutputFormat};
+use super::config::{MergeConfig, MergeMethod, MethodParams, OutputDtype, OutputFormat, VocabStrategy};
 use super::methods;
+use super::names;
 use super::output;
-use super::planner::{TensorMergePlan, TensorOperation};
+use super::planner::{self, TensorMergePlan, TensorOperation};
 use super::precompute;
 use super::projections;
 use super::registry::ParentRegistry;
 use super::tensor_io;
+use super::vocab;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct MergeProgress {
@@ -24,6 +29,15 @@ pub struct MergeProgress {
     pub current_tensor: Option<String>,
     pub tensors_done: usize,
     pub tensors_total: usize,
+    /// Tensor data written to the output so far
+    pub bytes_written: u64,
+    /// Average output throughput since the merge loop started
+    pub bytes_per_sec: f64,
+    /// Estimated seconds until the last tensor is written, from the average
+    /// time of recent tensors. `None` outside the tensor loop or before the
+    /// first tensor finishes.
+    #[serde(default)]
+    pub eta_seconds: Option<f64>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -40,6 +54,115 @@ pub struct MergeResult {
     pub tensors_written: usize,
     pub method: String,
     pub copied_files: Vec<String>,
+    pub warnings: Vec<String>,
+    /// Present when the merge ran with `profile` enabled.
+    pub perf: Option<MergePerfReport>,
+}
+
+/// Wall-clock time spent on one output tensor, in milliseconds.
+/// `load_ms` includes layout conversion and shape projection.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TensorTiming {
+    pub tensor_name: String,
+    pub operation: String,
+    pub load_ms: f64,
+    pub merge_ms: f64,
+    pub write_ms: f64,
+    pub total_ms: f64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MergePerfReport {
+    pub total_ms: f64,
+    pub load_ms: f64,
+    pub merge_ms: f64,
+    pub write_ms: f64,
+    /// Writer flush, auxiliary file copies and verification after the tensor loop
+    pub finalize_ms: f64,
+    pub tensors_timed: usize,
+    /// Slowest tensors by total time, slowest first
+    pub slowest: Vec<TensorTiming>,
+}
+
+/// Tensors listed in `MergePerfReport::slowest`.
+const PERF_SLOWEST_COUNT: usize = 20;
+
+fn record_timing(
+    timings: &mut Option<Vec<TensorTiming>>,
+    tensor_name: &str,
+    operation: &str,
+    load: Duration,
+    merge: Duration,
+    write: Duration,
+) {
+    if let Some(timings) = timings {
+        let ms = |d: Duration| d.as_secs_f64() * 1000.0;
+        timings.push(TensorTiming {
+            tensor_name: tensor_name.to_string(),
+            operation: operation.to_string(),
+            load_ms: ms(load),
+            merge_ms: ms(merge),
+            write_ms: ms(write),
+            total_ms: ms(load + merge + write),
+        });
+    }
+}
+
+fn build_perf_report(mut timings: Vec<TensorTiming>, total: Duration, finalize: Duration) -> MergePerfReport {
+    let load_ms = timings.iter().map(|t| t.load_ms).sum();
+    let merge_ms = timings.iter().map(|t| t.merge_ms).sum();
+    let write_ms = timings.iter().map(|t| t.write_ms).sum();
+    let tensors_timed = timings.len();
+
+    timings.sort_by(|a, b| b.total_ms.total_cmp(&a.total_ms));
+    timings.truncate(PERF_SLOWEST_COUNT);
+
+    MergePerfReport {
+        total_ms: total.as_secs_f64() * 1000.0,
+        load_ms,
+        merge_ms,
+        write_ms,
+        finalize_ms: finalize.as_secs_f64() * 1000.0,
+        tensors_timed,
+        slowest: timings,
+    }
+}
+
+fn throughput(bytes: u64, since: Instant) -> f64 {
+    let secs = since.elapsed().as_secs_f64();
+    if secs > 0.0 { bytes as f64 / secs } else { 0.0 }
+}
+
+/// Rolling per-tensor timing for the merge ETA. Only the last few tensors count,
+/// so the estimate adapts when big tensors (embeddings, output head) skew early timings.
+struct EtaTracker {
+    recent: VecDeque<Duration>,
+    last: Instant,
+}
+
+impl EtaTracker {
+    const WINDOW: usize = 32;
+
+    fn new() -> Self {
+        Self { recent: VecDeque::with_capacity(Self::WINDOW), last: Instant::now() }
+    }
+
+    fn tensor_done(&mut self) {
+        let now = Instant::now();
+        if self.recent.len() == Self::WINDOW {
+            self.recent.pop_front();
+        }
+        self.recent.push_back(now - self.last);
+        self.last = now;
+    }
+
+    fn eta_seconds(&self, remaining: usize) -> Option<f64> {
+        if self.recent.is_empty() {
+            return None;
+        }
+        let avg = self.recent.iter().sum::<Duration>().as_secs_f64() / self.recent.len() as f64;
+        Some(avg * remaining as f64)
+    }
 }
 
 fn emit_progress(app: &AppHandle, progress: &MergeProgress) {
@@ -60,6 +183,8 @@ pub fn execute_merge(
     registry: &ParentRegistry,
     cancel: Arc<AtomicBool>,
 ) -> Result<MergeResult, ModelError> {
+    let merge_started = Instant::now();
+    let mut timings: Option<Vec<TensorTiming>> = config.profile.then(Vec::new);
     let strategy = methods::get_strategy(config.method);
     let total_ops = plan.operations.iter().filter(|op| !matches!(op, TensorOperation::CopyMetadata { .. })).count();
     let mut tensors_done = 0;
@@ -73,6 +198,9 @@ pub fn execute_merge(
         current_tensor: None,
         tensors_done: 0,
         tensors_total: total_ops,
+        bytes_written: 0,
+        bytes_per_sec: 0.0,
+        eta_seconds: None,
     });
 
     if cancel.load(Ordering::Relaxed) {
@@ -81,26 +209,40 @@ pub fn execute_merge(
 
     // Phase 2: Pre-compute output manifest (tensor shapes + offsets, no data loading)
     emit_phase(app, "planning", "Pre-computing tensor offsets");
-    let manifest = precompute::build_output_manifest(&plan.operations, registry)?;
+    let vocab_alignment = vocab::plan_alignment(
+        registry,
+        config.vocab_strategy,
+        config.base_parent_id.as_deref(),
+    );
+    let manifest = precompute::build_output_manifest(&plan.operations, registry, vocab_alignment.as_ref())?;
+    let estimate = planner::estimate_from_manifest(config, registry, &manifest, vocab_alignment.as_ref());
 
     emit_progress(app, &MergeProgress {
         stage: "planning".into(),
         percent: 7.0,
         message: format!("Plan: {} tensors, {} estimated output",
-            manifest.tensors.len(),
-            crate::model::format_file_size(manifest.total_data_bytes)),
+            estimate.tensor_count,
+            estimate.estimated_display),
         current_tensor: None,
         tensors_done: 0,
         tensors_total: total_ops,
+        bytes_written: 0,
+        bytes_per_sec: 0.0,
+        eta_seconds: None,
     });
 
     if cancel.load(Ordering::Relaxed) {
         return Err(ModelError::MergeCancelled);
     }
 
-    // Determine base parent
+    // Determine base parent. Tokenizer metadata must come from the parent whose
+    // vocab the output was aligned to.
     let base_parent = config.base_parent_id.as_ref().and_then(|id| registry.get(id));
-    let metadata_parent = base_parent.or_else(|| registry.all().first());
+    let tokenizer_parent_id = vocab_alignment
+        .as_ref()
+        .map(|v| v.tokenizer_parent_id.as_str())
+        .or(config.base_parent_id.as_deref());
+    let metadata_parent = planner::metadata_parent(config, registry, vocab_alignment.as_ref());
     let output_path = &config.output.path;
 
     // Resolve parent config dir for GGUF metadata
@@ -117,15 +259,48 @@ pub fn execute_merge(
 
     // Phase 3: Open streaming writer + merge loop
     emit_phase(app, "merging", "Processing tensors");
+    let mut warnings: Vec<String> = Vec::new();
+
+    if let Some(v) = &vocab_alignment {
+        let tokenizer_name = registry
+            .get(&v.tokenizer_parent_id)
+            .map(|p| p.name.clone())
+            .unwrap_or_default();
+        if v.transplant.is_some() {
+            warnings.extend(vocab::transplant_warnings(v, registry));
+        } else {
+            warnings.push(format!(
+                "Vocab sizes differ ({}): embedding and output tensors {} to {} tokens using {}'s tokenizer",
+                v.sizes.iter().map(|(_, s)| s.to_string()).collect::<Vec<_>>().join(" vs "),
+                match config.vocab_strategy {
+                    VocabStrategy::Truncate => "truncated",
+                    VocabStrategy::Pad | VocabStrategy::Transplant => "zero-padded",
+                },
+                v.target,
+                tokenizer_name,
+            ));
+        }
+    }
 
     let (actual_file_path, aux_target_dir, mut writer) = match config.output.format {
         OutputFormat::SafeTensors => {
             let dir = std::path::Path::new(output_path);
             std::fs::create_dir_all(dir).map_err(ModelError::IoError)?;
-            let model_file = dir.join("model.safetensors");
-            let file_str = model_file.to_string_lossy().to_string();
-            let st_writer = output::StreamingSafeTensorsWriter::new(&file_str, &manifest)?;
-            (file_str, output_path.clone(), output::StreamWriter::SafeTensors(st_writer))
+            let dtype = planner::resolve_output_dtype(config, registry);
+            let st_writer = output::StreamingSafeTensorsWriter::new(
+                output_path,
+                &manifest,
+                dtype,
+                config.output.max_shard_bytes,
+            )?;
+            let files = st_writer.output_files();
+            // A single file is reported directly; a sharded model by its directory
+            let reported = if files.len() == 1 {
+                files[0].clone()
+            } else {
+                output_path.clone()
+            };
+            (reported, output_path.clone(), output::StreamWriter::SafeTensors(st_writer))
         }
         OutputFormat::Gguf => {
             let mp = metadata_parent
@@ -150,8 +325,25 @@ pub fn execute_merge(
                 source_gguf,
                 Some(&mp.compat),
                 cfg_dir,
+                config.output.repair_tokenizer_gaps,
+                config.output.target_type,
+                (!config.slices.is_empty()).then(|| planner::sliced_layer_count(config) as u32),
             )?;
 
+            if gguf_writer.tokenizer_gaps > 0 {
+                warnings.push(if config.output.repair_tokenizer_gaps {
+                    format!(
+                        "Source tokenizer is sparse: filled {} vocab gaps with <unused> placeholder tokens",
+                        gguf_writer.tokenizer_gaps
+                    )
+                } else {
+                    format!(
+                        "Source tokenizer is sparse: {} vocab gaps written as empty tokens (enable tokenizer gap repair to use placeholders)",
+                        gguf_writer.tokenizer_gaps
+                    )
+                });
+            }
+
             let aux_dir = std::path::Path::new(output_path)
                 .parent()
                 .map(|p| p.to_string_lossy().to_string())
@@ -161,10 +353,15 @@ pub fn execute_merge(
     };
 
     // Streaming merge loop — each tensor is written immediately and dropped
+    let loop_started = Instant::now();
+    let mut eta = EtaTracker::new();
     for op in &plan.operations {
         if cancel.load(Ordering::Relaxed) {
+            let files = writer.output_files();
             drop(writer);
-            let _ = std::fs::remove_file(&actual_file_path);
+            for file in &files {
+                let _ = std::fs::remove_file(file);
+            }
             return Err(ModelError::MergeCancelled);
         }
 
@@ -181,11 +378,46 @@ pub fn execute_merge(
                     current_tensor: Some(tensor_name.clone()),
                     tensors_done,
                     tensors_total: total_ops,
+                    bytes_written: writer.bytes_written(),
+                    bytes_per_sec: throughput(writer.bytes_written(), loop_started),
+                    eta_seconds: eta.eta_seconds(total_ops - tensors_done),
                 });
 
-                let tensor = tensor_io::load_tensor(parent, tensor_name)?;
+                let started = Instant::now();
+                let layout = names::layout_parent(registry, tensor_name, parent);
+                let tensor = tensor_io::load_tensor_in_layout(parent, tensor_name, layout)?;
+                let tensor = vocab::align(vocab_alignment.as_ref(), parent, layout, tensor_name, tensor)?;
+                let loaded = Instant::now();
                 writer.write_tensor(&tensor)?;
+                record_timing(&mut timings, tensor_name, "copy", loaded - started, Duration::ZERO, loaded.elapsed());
                 tensors_done += 1;
+                eta.tensor_done();
+            }
+
+            TensorOperation::CopyLayer { tensor_name, source_name, parent_id } => {
+                let parent = registry.get(parent_id).ok_or_else(|| {
+                    ModelError::ParentNotFound(parent_id.clone())
+                })?;
+
+                emit_progress(app, &MergeProgress {
+                    stage: "merging".into(),
+                    percent: 10.0 + (tensors_done as f64 / total_ops as f64) * 80.0,
+                    message: format!("Copying {} as {}", source_name, tensor_name),
+                    current_tensor: Some(tensor_name.clone()),
+                    tensors_done,
+                    tensors_total: total_ops,
+                    bytes_written: writer.bytes_written(),
+                    bytes_per_sec: throughput(writer.bytes_written(), loop_started),
+                    eta_seconds: eta.eta_seconds(total_ops - tensors_done),
+                });
+
+                let started = Instant::now();
+                let tensor = tensor_io::load_tensor(parent, source_name)?;
+                let loaded = Instant::now();
+                writer.write_tensor(&tensor)?;
+                record_timing(&mut timings, tensor_name, "copy", loaded - started, Duration::ZERO, loaded.elapsed());
+                tensors_done += 1;
+                eta.tensor_done();
             }
 
             TensorOperation::Merge { tensor_name, parent_ids, weights } => {
@@ -196,16 +428,44 @@ pub fn execute_merge(
                     current_tensor: Some(tensor_name.clone()),
                     tensors_done,
                     tensors_total: total_ops,
+                    bytes_written: writer.bytes_written(),
+                    bytes_per_sec: throughput(writer.bytes_written(), loop_started),
+                    eta_seconds: eta.eta_seconds(total_ops - tensors_done),
                 });
 
-                let mut parent_tensors: Vec<(Tensor, f64)> = Vec::new();
-                for (pid, weight) in parent_ids.iter().zip(weights.iter()) {
-                    let parent = registry.get(pid).ok_or_else(|| {
-                        ModelError::ParentNotFound(pid.clone())
-                    })?;
-                    let tensor = tensor_io::load_tensor(parent, tensor_name)?;
-                    parent_tensors.push((tensor, *weight));
-                }
+                let started = Instant::now();
+
+                // Output naming/layout follows the first parent that stores this tensor natively;
+                // other parents (possibly a different format) are converted to match.
+                let first_parent = registry.get(&parent_ids[0]).ok_or_else(|| {
+                    ModelError::ParentNotFound(parent_ids[0].clone())
+                })?;
+                let layout = names::layout_parent(registry, tensor_name, first_parent);
+
+                // A matching weight override replaces the parent weights, including
+                // any Linear weight curve
+                let override_weights = planner::weight_override(config, tensor_name);
+                let weights: &[f64] = override_weights.unwrap_or(weights);
+                let curve_free;
+                let params = if override_weights.is_some() && config.params.weight_curves.is_some() {
+                    curve_free = MethodParams { weight_curves: None, ..config.params.clone() };
+                    &curve_free
+                } else {
+                    &config.params
+                };
+
+                let excluded_base = base_parent
+                    .filter(|_| strategy.excludes_base_parent())
+                    .map(|bp| bp.id.as_str());
+                let mut parent_tensors = load_parent_tensors(
+                    registry,
+                    parent_ids,
+                    weights,
+                    excluded_base,
+                    layout,
+                    tensor_name,
+                    vocab_alignment.as_ref(),
+                )?;
 
                 // Apply projection when shapes mismatch
                 if parent_tensors.len() >= 2 {
@@ -269,26 +529,37 @@ pub fn execute_merge(
                     }
                 }
 
-                let base_tensor = if strategy.requires_base() {
-                    if let Some(bp) = base_parent {
-                        Some(tensor_io::load_tensor(bp, tensor_name)?)
-                    } else if !parent_tensors.is_empty() {
+                let base_tensor = match base_parent {
+                    Some(bp) if strategy.accepts_base() => {
+                        let tensor = tensor_io::load_tensor_in_layout(bp, tensor_name, layout)?;
+                        Some(vocab::align(vocab_alignment.as_ref(), bp, layout, tensor_name, tensor)?)
+                    }
+                    None if strategy.requires_base() && !parent_tensors.is_empty() => {
                         Some(parent_tensors[0].0.clone())
-                    } else {
-                        None
                     }
-                } else {
-                    None
+                    _ => None,
                 };
 
+                let loaded = Instant::now();
                 let merged = strategy.merge(
                     &parent_tensors,
-                    &config.params,
+                    params,
                     base_tensor.as_ref(),
+                    inspect::extract_layer_index(tensor_name),
                 )?;
+                let computed = Instant::now();
 
                 writer.write_tensor(&merged)?;
+                record_timing(
+                    &mut timings,
+                    tensor_name,
+                    "merge",
+                    loaded - started,
+                    computed - loaded,
+                    computed.elapsed(),
+                );
                 tensors_done += 1;
+                eta.tensor_done();
             }
 
             TensorOperation::Synthesize { tensor_name, shape, strategy: synth_strategy } => {
@@ -299,8 +570,12 @@ pub fn execute_merge(
                     current_tensor: Some(tensor_name.clone()),
                     tensors_done,
                     tensors_total: total_ops,
+                    bytes_written: writer.bytes_written(),
+                    bytes_per_sec: throughput(writer.bytes_written(), loop_started),
+                    eta_seconds: eta.eta_seconds(total_ops - tensors_done),
                 });
 
+                let started = Instant::now();
                 let tensor = match synth_strategy.as_str() {
                     "random_init" => {
                         let num_elements: usize = shape.iter().product();
@@ -315,9 +590,19 @@ pub fn execute_merge(
                             .map_err(|e| ModelError::CandleError(e.to_string()))?
                     }
                 };
+                let computed = Instant::now();
 
                 writer.write_tensor(&tensor)?;
+                record_timing(
+                    &mut timings,
+                    tensor_name,
+                    "synthesize",
+                    Duration::ZERO,
+                    computed - started,
+                    computed.elapsed(),
+                );
                 tensors_done += 1;
+                eta.tensor_done();
             }
 
             TensorOperation::CopyMetadata { .. } => {
@@ -327,6 +612,8 @@ pub fn execute_merge(
     }
 
     // Phase 4: Finalize output
+    let finalize_started = Instant::now();
+    let total_written = writer.bytes_written();
     emit_phase(app, "writing", "Finalizing output file");
     emit_progress(app, &MergeProgress {
         stage: "writing".into(),
@@ -335,17 +622,59 @@ pub fn execute_merge(
         current_tensor: None,
         tensors_done,
         tensors_total: total_ops,
+        bytes_written: total_written,
+        bytes_per_sec: throughput(total_written, loop_started),
+        eta_seconds: None,
     });
 
+    let output_files = writer.output_files();
     writer.finish()?;
 
-    // Phase 4b: Copy auxiliary files
+    // Phase 4b: Copy auxiliary files. The model config follows the primary
+    // parent; the tokenizer follows the parent the vocab was aligned to.
     emit_phase(app, "copying", "Copying tokenizer and config files");
-    let copied_files = copy_auxiliary_files(&aux_target_dir, registry, config.base_parent_id.as_deref());
+    let config_parent = base_parent.or_else(|| registry.all().first());
+    let mut copied_files = copy_auxiliary_files(
+        &aux_target_dir,
+        registry,
+        config_parent.map(|p| p.id.as_str()),
+        tokenizer_parent_id,
+    );
+
+    // SafeTensors output is only loadable as a folder with a config.json, so
+    // derive one from the primary parent's metadata when no parent ships it
+    let output_dtype = matches!(config.output.format, OutputFormat::SafeTensors)
+        .then(|| planner::resolve_output_dtype(config, registry));
+    let mut derived_config = false;
+    if output_dtype.is_some() && !std::path::Path::new(&aux_target_dir).join("config.json").exists() {
+        if let Some(parent) = config_parent {
+            match write_derived_config(&aux_target_dir, parent) {
+                Ok(()) => {
+                    copied_files.push("config.json".into());
+                    warnings.push(format!(
+                        "No parent has a config.json; derived one from {}'s metadata",
+                        parent.name
+                    ));
+                    derived_config = true;
+                }
+                Err(reason) => warnings.push(format!(
+                    "No parent has a config.json and none could be derived from {}'s metadata ({}); add one before loading the output",
+                    parent.name, reason
+                )),
+            }
+        }
+    }
 
-    // Phase 4c: Patch config.json if projection was used (cross-dimension merge)
-    if config.projection_strategy.is_some() || !registry.all_same_hidden_dim() {
-        patch_config_json(&aux_target_dir, &manifest);
+    // Phase 4c: Patch config.json if projection, vocab alignment or a layer
+    // rearrangement changed dimensions, and record the output dtype
+    let infer_dims = derived_config
+        || config.projection_strategy.is_some()
+        || !registry.all_same_hidden_dim()
+        || vocab_alignment.is_some()
+        || !config.slices.is_empty()
+        || matches!(config.method, MergeMethod::Frankenmerge | MergeMethod::Passthrough);
+    if infer_dims || output_dtype.is_some() {
+        patch_config_json(&aux_target_dir, &manifest, infer_dims, output_dtype);
     }
 
     // Phase 5: Verifying
@@ -357,10 +686,15 @@ pub fn execute_merge(
         current_tensor: None,
         tensors_done: total_ops,
         tensors_total: total_ops,
+        bytes_written: total_written,
+        bytes_per_sec: throughput(total_written, loop_started),
+        eta_seconds: None,
     });
 
-    let output_file = std::fs::metadata(&actual_file_path).map_err(ModelError::IoError)?;
-    let output_size = output_file.len();
+    let mut output_size = 0u64;
+    for file in &output_files {
+        output_size += std::fs::metadata(file).map_err(ModelError::IoError)?.len();
+    }
 
     emit_progress(app, &MergeProgress {
         stage: "complete".into(),
@@ -369,6 +703,9 @@ pub fn execute_merge(
         current_tensor: None,
         tensors_done: total_ops,
         tensors_total: total_ops,
+        bytes_written: total_written,
+        bytes_per_sec: throughput(total_written, loop_started),
+        eta_seconds: None,
     });
 
     Ok(MergeResult {
@@ -378,59 +715,104 @@ pub fn execute_merge(
         tensors_written: tensors_done,
         method: config.method.display_name().to_string(),
         copied_files,
+        warnings,
+        perf: timings.map(|t| build_perf_report(t, merge_started.elapsed(), finalize_started.elapsed())),
     })
 }
 
-/// Files to copy from parent model directory to output directory.
-const AUXILIARY_FILES: &[&str] = &[
+/// Model config files, copied from the primary parent.
+const CONFIG_FILES: &[&str] = &[
+    "config.json",
+    "generation_config.json",
+    "preprocessor_config.json",
+];
+
+/// Tokenizer files, copied together from the tokenizer-source parent.
+const TOKENIZER_FILES: &[&str] = &[
     "tokenizer.json",
     "tokenizer_config.json",
     "tokenizer.model",
-    "config.json",
-    "generation_config.json",
     "special_tokens_map.json",
     "vocab.json",
     "merges.txt",
     "added_tokens.json",
-    "preprocessor_config.json",
 ];
 
-/// Copy tokenizer/config files from the best source parent directory to the output directory.
-fn copy_auxiliary_files(
-    output_dir_path: &str,
+/// Directory holding a parent's auxiliary files.
+/// Load each contributing parent's copy of `tensor_name` in `layout`'s
+/// convention, paired with its weight. `excluded` (a base the strategy merges
+/// against rather than with) is skipped, and vocab rows are aligned using the
+/// parent each tensor actually came from.
+fn load_parent_tensors(
     registry: &ParentRegistry,
-    base_parent_id: Option<&str>,
-) -> Vec<String> {
-    use std::path::Path;
+    parent_ids: &[String],
+    weights: &[f64],
+    excluded: Option<&str>,
+    layout: &super::registry::ParentModel,
+    tensor_name: &str,
+    alignment: Option<&vocab::VocabAlignment>,
+) -> Result<Vec<(Tensor, f64)>, ModelError> {
+    let expected = layout.compat.tensor_shape(tensor_name);
+    let mut parent_tensors = Vec::new();
+    for (pid, weight) in parent_ids.iter().zip(weights.iter()) {
+        if Some(pid.as_str()) == excluded {
+            continue;
+        }
+        let parent = registry.get(pid).ok_or_else(|| {
+            ModelError::ParentNotFound(pid.clone())
+        })?;
+        let mut tensor = tensor_io::load_tensor_in_layout(parent, tensor_name, layout)?;
+
+        // Cross-format parents can disagree on orientation (e.g. Conv1D weights):
+        // a 2D tensor whose transpose matches the layout shape is transposed back.
+        if let Some(expected) = expected {
+            let dims = tensor.dims();
+            if dims.len() == 2 && dims != expected && dims[0] == expected[1] && dims[1] == expected[0] {
+                tensor = tensor.t()
+                    .and_then(|t| t.contiguous())
+                    .map_err(|e| ModelError::CandleError(e.to_string()))?;
+            }
+        }
 
-    let output_dir = Path::new(output_dir_path);
-    if !output_dir.exists() {
-        let _ = std::fs::create_dir_all(output_dir);
+        // Bring embedding/output rows to the common vocab before any projection
+        let tensor = vocab::align(alignment, parent, layout, tensor_name, tensor)?;
+        parent_tensors.push((tensor, *weight));
     }
+    Ok(parent_tensors)
+}
 
-    let source_dir = base_parent_id
-        .and_then(|id| registry.get(id))
-        .or_else(|| registry.all().iter().find(|p| p.is_dir))
-        .or_else(|| registry.all().first());
+fn parent_dir(parent: &super::registry::ParentModel) -> Option<std::path::PathBuf> {
+    let path = std::path::Path::new(&parent.file_path);
+    if parent.is_dir {
+        Some(path.to_path_buf())
+    } else {
+        path.parent().map(|dir| dir.to_path_buf())
+    }
+}
 
-    let source_parent = match source_dir {
-        Some(p) => p,
+/// Copy one group of files from a single parent: `preferred_id` when its
+/// directory has any of them, otherwise the first parent (in registry order)
+/// that does. Files from different parents are never mixed within a group.
+fn copy_file_group(
+    output_dir: &std::path::Path,
+    registry: &ParentRegistry,
+    preferred_id: Option<&str>,
+    files: &[&str],
+) -> Vec<String> {
+    let preferred = preferred_id.and_then(|id| registry.get(id));
+    let source_dir = preferred
+        .into_iter()
+        .chain(registry.all().iter())
+        .filter_map(parent_dir)
+        .find(|dir| files.iter().any(|f| dir.join(f).exists()));
+
+    let source_dir = match source_dir {
+        Some(dir) => dir,
         None => return vec![],
     };
 
-    let source_path = Path::new(&source_parent.file_path);
-    let source_dir = if source_parent.is_dir {
-        source_path.to_path_buf()
-    } else {
-        match source_path.parent() {
-            Some(dir) => dir.to_path_buf(),
-            None => return vec![],
-        }
-    };
-
     let mut copied = Vec::new();
-
-    for &filename in AUXILIARY_FILES {
+    for &filename in files {
         let src = source_dir.join(filename);
         let dst = output_dir.join(filename);
         if src.exists() && !dst.exists() {
@@ -439,13 +821,111 @@ fn copy_auxiliary_files(
             }
         }
     }
+    copied
+}
+
+/// Copy config files from the primary parent and tokenizer files from the
+/// tokenizer-source parent into the output directory.
+fn copy_auxiliary_files(
+    output_dir_path: &str,
+    registry: &ParentRegistry,
+    config_parent_id: Option<&str>,
+    tokenizer_parent_id: Option<&str>,
+) -> Vec<String> {
+    use std::path::Path;
+
+    let output_dir = Path::new(output_dir_path);
+    if !output_dir.exists() {
+        let _ = std::fs::create_dir_all(output_dir);
+    }
 
+    let mut copied = copy_file_group(output_dir, registry, config_parent_id, CONFIG_FILES);
+    copied.extend(copy_file_group(
+        output_dir,
+        registry,
+        tokenizer_parent_id.or(config_parent_id),
+        TOKENIZER_FILES,
+    ));
     copied
 }
 
-/// Patch config.json in the output directory to match the actual merged tensor dimensions.
-/// This is needed when cross-dimension merging changes hidden_size, num_layers, etc.
-fn patch_config_json(output_dir: &str, manifest: &precompute::OutputManifest) {
+/// Write a minimal HuggingFace config.json built from a parent's metadata.
+/// GGUF architecture names (`llama`) become `model_type` and map to the
+/// matching `...ForCausalLM` class. Refuses (with the reason) when the RoPE
+/// base or norm epsilon is unknown, since transformers' defaults for those
+/// would load the model with the wrong values.
+fn write_derived_config(output_dir: &str, parent: &super::registry::ParentModel) -> Result<(), String> {
+    let compat = &parent.compat;
+    let missing: Vec<&str> = [
+        ("rope_theta", compat.rope_theta.is_none()),
+        ("rms_norm_eps", compat.rms_norm_eps.is_none()),
+    ]
+    .into_iter()
+    .filter_map(|(key, absent)| absent.then_some(key))
+    .collect();
+    if !missing.is_empty() {
+        return Err(format!("metadata has no {}", missing.join(" or ")));
+    }
+
+    let mut obj = serde_json::Map::new();
+
+    if let Some(arch) = &compat.architecture {
+        if arch.contains("For") {
+            obj.insert("architectures".into(), serde_json::json!([arch]));
+            obj.insert("model_type".into(), serde_json::json!(names::canonical_arch(arch)));
+        } else {
+            let lower = arch.to_lowercase();
+            let mut class = String::new();
+            let mut chars = lower.chars();
+            if let Some(first) = chars.next() {
+                class.extend(first.to_uppercase());
+                class.push_str(chars.as_str());
+            }
+            obj.insert("architectures".into(), serde_json::json!([format!("{}ForCausalLM", class)]));
+            obj.insert("model_type".into(), serde_json::json!(lower));
+        }
+    }
+
+    let fields = [
+        ("hidden_size", compat.hidden_size),
+        ("num_hidden_layers", compat.num_layers),
+        ("num_attention_heads", compat.num_attention_heads),
+        ("num_key_value_heads", compat.num_kv_heads),
+        ("vocab_size", compat.vocab_size),
+        ("max_position_embeddings", compat.context_length),
+    ];
+    for (key, value) in fields {
+        if let Some(v) = value {
+            obj.insert(key.into(), serde_json::json!(v));
+        }
+    }
+
+    // Without an explicit key length the head width is hidden / heads, as in llama.cpp
+    let head_dim = compat.head_dim.or_else(|| {
+        compat.hidden_size.zip(compat.num_attention_heads.filter(|&h| h > 0)).map(|(h, n)| h / n)
+    });
+    if let Some(head_dim) = head_dim {
+        obj.insert("head_dim".into(), serde_json::json!(head_dim));
+    }
+    obj.insert("rope_theta".into(), serde_json::json!(compat.rope_theta));
+    obj.insert("rms_norm_eps".into(), serde_json::json!(compat.rms_norm_eps));
+
+    let path = std::path::Path::new(output_dir).join("config.json");
+    let json = serde_json::to_string_pretty(&serde_json::Value::Object(obj))
+        .map_err(|e| e.to_string())?;
+    std::fs::write(path, json).map_err(|e| e.to_string())
+}
+
+/// Patch config.json in the output directory. With `infer_dims` the dimensions are
+/// re-derived from the merged tensors, which is needed when cross-dimension merging
+/// or a layer rearrangement changes hidden_size, num_layers, etc. `dtype` sets
+/// `torch_dtype` to the SafeTensors output element type.
+fn patch_config_json(
+    output_dir: &str,
+    manifest: &precompute::OutputManifest,
+    infer_dims: bool,
+    dtype: Option<OutputDtype>,
+) {
     use std::path::Path;
 
     let config_path = Path::new(output_dir).join("config.json");
@@ -468,6 +948,24 @@ fn patch_config_json(output_dir: &str, manifest: &precompute::OutputManifest) {
         None => return,
     };
 
+    if let Some(dtype) = dtype {
+        obj.insert("torch_dtype".into(), serde_json::json!(dtype.torch_name()));
+    }
+
+    if infer_dims {
+        infer_config_dims(obj, manifest);
+    }
+
+    if let Ok(patched) = serde_json::to_string_pretty(&config) {
+        let _ = std::fs::write(&config_path, patched);
+    }
+}
+
+/// Overwrite config dimensions with the values implied by the merged tensor shapes.
+fn infer_config_dims(
+    obj: &mut serde_json::Map<String, serde_json::Value>,
+    manifest: &precompute::OutputManifest,
+) {
     // Infer hidden_size from an embedding tensor: shape = [vocab_size, hidden_size]
     for info in &manifest.tensors {
         if info.name.contains("embed_tokens") && info.shape.len() == 2 {
@@ -482,7 +980,7 @@ fn patch_config_json(output_dir: &str, manifest: &precompute::OutputManifest) {
     for info in &manifest.tensors {
         let parts: Vec<&str> = info.name.split('.').collect();
         for (i, part) in parts.iter().enumerate() {
-            if (*part == "layers" || *part == "h") && i + 1 < parts.len() {
+            if (*part == "layers" || *part == "h" || *part == "blk") && i + 1 < parts.len() {
                 if let Ok(idx) = parts[i + 1].parse::<u64>() {
                     max_layer = Some(max_layer.map_or(idx, |m: u64| m.max(idx)));
                 }
@@ -500,8 +998,145 @@ fn patch_config_json(output_dir: &str, manifest: &precompute::OutputManifest) {
             break;
         }
     }
+}
 
-    if let Ok(patched) = serde_json::to_string_pretty(&config) {
-        let _ = std::fs::write(&config_path, patched);
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::registry::{CompatInfo, ParentModel};
+
+    fn gguf_parent(rope_theta: Option<f64>, rms_norm_eps: Option<f64>) -> ParentModel {
+        ParentModel {
+            id: "p0".into(),
+            slot: 0,
+            name: "parent".into(),
+            file_path: "parent.gguf".into(),
+            format: crate::model::ModelFormat::Gguf,
+            file_size: 0,
+            file_size_display: String::new(),
+            parameter_count: 0,
+            parameter_count_display: String::new(),
+            layer_count: Some(2),
+            architecture: Some("llama".into()),
+            quantization: None,
+            compat: CompatInfo {
+                architecture: Some("llama".into()),
+                hidden_size: Some(4096),
+                num_layers: Some(2),
+                num_attention_heads: Some(32),
+                num_kv_heads: Some(8),
+                vocab_size: Some(32000),
+                context_length: Some(4096),
+                head_dim: None,
+                rope_theta,
+                rms_norm_eps,
+                tensor_metas: Vec::new(),
+            },
+            color: String::new(),
+            is_dir: false,
+        }
+    }
+
+    #[test]
+    fn derived_config_carries_rope_and_norm_values() {
+        let dir = std::env::temp_dir().join(format!("forgeai-config-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        write_derived_config(&dir.to_string_lossy(), &gguf_parent(Some(500000.0), Some(0.00001))).unwrap();
+
+        let config: serde_json::Value =
+            serde_json::from_str(&std::fs::read_to_string(dir.join("config.json")).unwrap()).unwrap();
+        assert_eq!(config["rope_theta"], 500000.0);
+        assert_eq!(config["rms_norm_eps"], 0.00001);
+        assert_eq!(config["head_dim"], 128);
+        assert_eq!(config["architectures"][0], "LlamaForCausalLM");
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    /// A safetensors parent holding one F32 `lm_head.weight` of `rows` x 2,
+    /// row `r` filled with `base + r`.
+    fn head_parent(dir: &std::path::Path, id: &str, rows: usize, base: f32) -> ParentModel {
+        let values: Vec<f32> = (0..rows).flat_map(|r| [base + r as f32; 2]).collect();
+        let header = format!(
+            r#"{{"lm_head.weight":{{"dtype":"F32","shape":[{},2],"data_offsets":[0,{}]}}}}"#,
+            rows,
+            values.len() * 4
+        );
+        let mut bytes = (header.len() as u64).to_le_bytes().to_vec();
+        bytes.extend_from_slice(header.as_bytes());
+        bytes.extend(values.iter().flat_map(|v| v.to_le_bytes()));
+        let path = dir.join(format!("{}.safetensors", id));
+        std::fs::write(&path, bytes).unwrap();
+
+        let template = gguf_parent(None, None);
+        ParentModel {
+            id: id.into(),
+            file_path: path.to_string_lossy().to_string(),
+            format: crate::model::ModelFormat::SafeTensors,
+            compat: CompatInfo {
+                tensor_metas: vec![super::super::registry::TensorMeta {
+                    name: "lm_head.weight".into(),
+                    shape: vec![rows, 2],
+                    dtype: "F32".into(),
+                }],
+                ..template.compat
+            },
+            ..template
+        }
+    }
+
+    #[test]
+    fn excluded_base_keeps_each_tensor_with_its_own_row_map() {
+        let dir = std::env::temp_dir().join(format!("forgeai-exclude-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let base = head_parent(&dir, "base", 3, 0.0);
+        let short = head_parent(&dir, "short", 2, 10.0);
+        let full = head_parent(&dir, "full", 3, 20.0);
+        let registry = ParentRegistry::from_snapshot(vec![base.clone(), short, full]);
+        let alignment = vocab::VocabAlignment {
+            target: 3,
+            tokenizer_parent_id: "base".into(),
+            sizes: vec![("base".into(), 3), ("short".into(), 2), ("full".into(), 3)],
+            transplant: Some(vocab::TokenTransplant {
+                primary: base.clone(),
+                row_maps: [
+                    ("short".to_string(), vec![Some(1), Some(0), None]),
+                    ("full".to_string(), vec![Some(0), Some(1), Some(2)]),
+                ]
+                .into_iter()
+                .collect(),
+                overlap: Vec::new(),
+                unreadable: Vec::new(),
+            }),
+        };
+
+        let ids = ["base".to_string(), "short".to_string(), "full".to_string()];
+        let tensors = load_parent_tensors(
+            &registry,
+            &ids,
+            &[0.2, 0.3, 0.5],
+            Some("base"),
+            &base,
+            "lm_head.weight",
+            Some(&alignment),
+        );
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        let rows = |t: &Tensor| -> Vec<f32> {
+            t.to_vec2::<f32>().unwrap().into_iter().map(|r| r[0]).collect()
+        };
+        let tensors = tensors.unwrap();
+        assert_eq!(tensors.len(), 2);
+        assert_eq!((rows(&tensors[0].0), tensors[0].1), (vec![11.0, 10.0, 2.0], 0.3));
+        assert_eq!((rows(&tensors[1].0), tensors[1].1), (vec![20.0, 21.0, 22.0], 0.5));
+    }
+
+    #[test]
+    fn derived_config_refuses_without_rope_base() {
+        let dir = std::env::temp_dir().join(format!("forgeai-config-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let err = write_derived_config(&dir.to_string_lossy(), &gguf_parent(None, Some(0.00001))).unwrap_err();
+        assert!(err.contains("rope_theta"));
+        assert!(!dir.join("config.json").exists());
+        std::fs::remove_dir_all(&dir).unwrap();
     }
 }
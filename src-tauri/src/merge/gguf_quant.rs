@@ -0,0 +1,313 @@
+//! Encoders for quantized GGUF merge output.
+//!
+//! These are the plain round-to-nearest forms of llama.cpp's reference
+//! quantizers (no importance matrix or iterative scale search), producing the
+//! same block layouts that `tensor_io` dequantizes.
+
+use crate::model::inspect;
+
+use super::config::GgufQuantType;
+
+const QK_K: usize = 256;
+
+/// GGUF data alignment for tensor offsets and the start of the data section.
+pub const GGUF_ALIGNMENT: u64 = 32;
+
+/// Storage type of a single GGUF tensor.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum GgmlType {
+    F32,
+    F16,
+    Q4_0,
+    Q8_0,
+    Q4K,
+    Q6K,
+}
+
+impl GgmlType {
+    /// `ggml_type` id written in the tensor info.
+    pub fn id(&self) -> u32 {
+        match self {
+            Self::F32 => 0,
+            Self::F16 => 1,
+            Self::Q4_0 => 2,
+            Self::Q8_0 => 8,
+            Self::Q4K => 12,
+            Self::Q6K => 14,
+        }
+    }
+
+    fn block_size(&self) -> usize {
+        match self {
+            Self::F32 | Self::F16 => 1,
+            Self::Q4_0 | Self::Q8_0 => 32,
+            Self::Q4K | Self::Q6K => QK_K,
+        }
+    }
+
+    fn type_size(&self) -> usize {
+        match self {
+            Self::F32 => 4,
+            Self::F16 => 2,
+            Self::Q4_0 => 18,
+            Self::Q8_0 => 34,
+            Self::Q4K => 144,
+            Self::Q6K => 210,
+        }
+    }
+
+    pub fn byte_size(&self, elem_count: usize) -> u64 {
+        (elem_count / self.block_size() * self.type_size()) as u64
+    }
+}
+
+/// Round `offset` up to the next multiple of [`GGUF_ALIGNMENT`].
+pub fn align_offset(offset: u64) -> u64 {
+    offset.div_ceil(GGUF_ALIGNMENT) * GGUF_ALIGNMENT
+}
+
+/// Shape as written to a GGUF header, innermost dimension first. Tensors read
+/// from GGUF already use that order; SafeTensors lists the outermost first.
+pub fn header_shape(shape: &[usize], from_gguf: bool) -> Vec<usize> {
+    if from_gguf {
+        shape.to_vec()
+    } else {
+        shape.iter().rev().copied().collect()
+    }
+}
+
+/// llama.cpp's `use_more_bits`: the first and last eighth of the layers plus
+/// every third layer in between get the higher-precision type in `_M` mixes.
+fn use_more_bits(layer: u64, n_layers: u64) -> bool {
+    layer < n_layers / 8 || layer >= 7 * n_layers / 8 || (layer - n_layers / 8) % 3 == 2
+}
+
+/// Choose the storage type of every tensor for `target`, given names and the
+/// shapes as written to the GGUF header (innermost dimension first). Norms and
+/// biases stay F32; stacked (3D) expert weights quantize like their 2D slices,
+/// since rows run along the innermost dimension either way.
+pub fn plan_tensor_types(target: GgufQuantType, tensors: &[(&str, &[usize])]) -> Vec<GgmlType> {
+    let n_layers = tensors
+        .iter()
+        .filter_map(|(name, _)| inspect::extract_layer_index(name))
+        .max()
+        .map(|i| i + 1)
+        .unwrap_or(0);
+
+    tensors
+        .iter()
+        .map(|(name, shape)| {
+            let base = match target {
+                GgufQuantType::F32 => return GgmlType::F32,
+                GgufQuantType::F16 => return GgmlType::F16,
+                _ if shape.len() < 2 => return GgmlType::F32,
+                GgufQuantType::Q8_0 => GgmlType::Q8_0,
+                GgufQuantType::Q4_0 => GgmlType::Q4_0,
+                GgufQuantType::Q4KS => GgmlType::Q4K,
+                GgufQuantType::Q6K => GgmlType::Q6K,
+                GgufQuantType::Q4KM => {
+                    let is_output = *name == "output.weight" || *name == "lm_head.weight";
+                    let sensitive = ["attn_v", "v_proj", "ffn_down", "down_proj"]
+                        .iter()
+                        .any(|part| name.contains(part));
+                    let more_bits = inspect::extract_layer_index(name)
+                        .is_some_and(|i| use_more_bits(i, n_layers));
+                    if is_output || (sensitive && more_bits) {
+                        GgmlType::Q6K
+                    } else {
+                        GgmlType::Q4K
+                    }
+                }
+            };
+
+            // Rows must hold whole blocks; fall back like llama-quantize does
+            let row = shape.first().copied().unwrap_or(0);
+            if row % base.block_size() == 0 {
+                base
+            } else if row % 32 == 0 {
+                GgmlType::Q8_0
+            } else {
+                GgmlType::F16
+            }
+        })
+        .collect()
+}
+
+/// Encode F32 values as `ty`. Quantized types expect a whole number of blocks.
+pub fn encode(values: &[f32], ty: GgmlType) -> Vec<u8> {
+    let mut out = Vec::with_capacity(ty.byte_size(values.len()) as usize);
+    match ty {
+        GgmlType::F32 => values.iter().for_each(|v| out.extend_from_slice(&v.to_le_bytes())),
+        GgmlType::F16 => values
+            .iter()
+            .for_each(|v| out.extend_from_slice(&half::f16::from_f32(*v).to_le_bytes())),
+        GgmlType::Q4_0 => quantize_q4_0(values, &mut out),
+        GgmlType::Q8_0 => quantize_q8_0(values, &mut out),
+        GgmlType::Q4K => quantize_q4_k(values, &mut out),
+        GgmlType::Q6K => quantize_q6_k(values, &mut out),
+    }
+    out
+}
+
+fn push_f16(out: &mut Vec<u8>, v: f32) -> f32 {
+    let h = half::f16::from_f32(v);
+    out.extend_from_slice(&h.to_le_bytes());
+    h.to_f32()
+}
+
+/// Value with the largest magnitude, keeping its sign.
+fn signed_abs_max(values: &[f32]) -> f32 {
+    values
+        .iter()
+        .fold(0.0f32, |best, &v| if v.abs() > best.abs() { v } else { best })
+}
+
+fn quantize_q8_0(values: &[f32], out: &mut Vec<u8>) {
+    for block in values.chunks_exact(32) {
+        let amax = block.iter().fold(0.0f32, |m, v| m.max(v.abs()));
+        let d = push_f16(out, amax / 127.0);
+        let id = if d != 0.0 { 1.0 / d } else { 0.0 };
+        out.extend(block.iter().map(|&v| (v * id).round() as i8 as u8));
+    }
+}
+
+fn quantize_q4_0(values: &[f32], out: &mut Vec<u8>) {
+    for block in values.chunks_exact(32) {
+        let d = push_f16(out, signed_abs_max(block) / -8.0);
+        let id = if d != 0.0 { 1.0 / d } else { 0.0 };
+        let q = |v: f32| ((v * id + 8.5) as u8).min(15);
+        for j in 0..16 {
+            out.push(q(block[j]) | (q(block[j + 16]) << 4));
+        }
+    }
+}
+
+fn quantize_q4_k(values: &[f32], out: &mut Vec<u8>) {
+    for block in values.chunks_exact(QK_K) {
+        // Per 32-element sub-block: x ≈ scale * q - min, q in 0..=15
+        let mut scales = [0.0f32; 8];
+        let mut mins = [0.0f32; 8];
+        for (j, sub) in block.chunks_exact(32).enumerate() {
+            let lo = sub.iter().copied().fold(f32::INFINITY, f32::min).min(0.0);
+            let hi = sub.iter().copied().fold(f32::NEG_INFINITY, f32::max);
+            scales[j] = (hi - lo) / 15.0;
+            mins[j] = -lo;
+        }
+
+        // 6-bit sub-block scales/mins relative to the super-block d/dmin
+        let max_scale = scales.iter().copied().fold(0.0f32, f32::max);
+        let max_min = mins.iter().copied().fold(0.0f32, f32::max);
+        let inv_scale = if max_scale > 0.0 { 63.0 / max_scale } else { 0.0 };
+        let inv_min = if max_min > 0.0 { 63.0 / max_min } else { 0.0 };
+        let ls: Vec<u8> = scales.iter().map(|s| (s * inv_scale).round().min(63.0) as u8).collect();
+        let lm: Vec<u8> = mins.iter().map(|m| (m * inv_min).round().min(63.0) as u8).collect();
+
+        let d = push_f16(out, max_scale / 63.0);
+        let dmin = push_f16(out, max_min / 63.0);
+
+        let mut packed = [0u8; 12];
+        for j in 0..8 {
+            if j < 4 {
+                packed[j] = ls[j];
+                packed[j + 4] = lm[j];
+            } else {
+                packed[j + 4] = (ls[j] & 0x0F) | ((lm[j] & 0x0F) << 4);
+                packed[j - 4] |= (ls[j] >> 4) << 6;
+                packed[j] |= (lm[j] >> 4) << 6;
+            }
+        }
+        out.extend_from_slice(&packed);
+
+        let mut l = [0u8; QK_K];
+        for j in 0..8 {
+            let sd = d * ls[j] as f32;
+            let m = dmin * lm[j] as f32;
+            for i in 0..32 {
+                let q = if sd != 0.0 { ((block[32 * j + i] + m) / sd).round() } else { 0.0 };
+                l[32 * j + i] = q.clamp(0.0, 15.0) as u8;
+            }
+        }
+        for chunk in 0..4 {
+            for i in 0..32 {
+                out.push(l[64 * chunk + i] | (l[64 * chunk + 32 + i] << 4));
+            }
+        }
+    }
+}
+
+fn quantize_q6_k(values: &[f32], out: &mut Vec<u8>) {
+    for block in values.chunks_exact(QK_K) {
+        // Per 16-element sub-block: x ≈ scale * q, q in -32..=31
+        let mut sub_scales = [0.0f32; 16];
+        for (k, sub) in block.chunks_exact(16).enumerate() {
+            sub_scales[k] = signed_abs_max(sub) / -32.0;
+        }
+
+        let max_scale = signed_abs_max(&sub_scales);
+        let iscale = if max_scale != 0.0 { -128.0 / max_scale } else { 0.0 };
+        let d = half::f16::from_f32(if iscale != 0.0 { 1.0 / iscale } else { 0.0 });
+        let sc: Vec<i8> = sub_scales
+            .iter()
+            .map(|s| (s * iscale).round().clamp(-128.0, 127.0) as i8)
+            .collect();
+
+        let mut l = [0u8; QK_K];
+        for k in 0..16 {
+            let sd = d.to_f32() * sc[k] as f32;
+            for i in 0..16 {
+                let q = if sd != 0.0 { (block[16 * k + i] / sd).round() } else { 0.0 };
+                l[16 * k + i] = (q.clamp(-32.0, 31.0) as i32 + 32) as u8;
+            }
+        }
+
+        let mut ql = [0u8; 128];
+        let mut qh = [0u8; 64];
+        for n in 0..2 {
+            for i in 0..32 {
+                let base = n * 128 + i;
+                let (q1, q2, q3, q4) = (l[base], l[base + 32], l[base + 64], l[base + 96]);
+                ql[n * 64 + i] = (q1 & 0x0F) | ((q3 & 0x0F) << 4);
+                ql[n * 64 + i + 32] = (q2 & 0x0F) | ((q4 & 0x0F) << 4);
+                qh[n * 32 + i] = (q1 >> 4) | ((q2 >> 4) << 2) | ((q3 >> 4) << 4) | ((q4 >> 4) << 6);
+            }
+        }
+        out.extend_from_slice(&ql);
+        out.extend_from_slice(&qh);
+        out.extend(sc.iter().map(|&s| s as u8));
+        out.extend_from_slice(&d.to_le_bytes());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn safetensors_shapes_are_reversed_for_the_header() {
+        assert_eq!(header_shape(&[11008, 4096], false), vec![4096, 11008]);
+        assert_eq!(header_shape(&[4096, 11008], true), vec![4096, 11008]);
+    }
+
+    #[test]
+    fn row_length_is_the_innermost_dimension() {
+        let tensors: [(&str, &[usize]); 3] = [
+            ("blk.0.attn_q.weight", &[512, 96]),
+            ("blk.0.attn_k.weight", &[96, 512]),
+            ("blk.0.attn_norm.weight", &[96]),
+        ];
+        let types = plan_tensor_types(GgufQuantType::Q4KS, &tensors);
+        assert_eq!(types, vec![GgmlType::Q4K, GgmlType::Q8_0, GgmlType::F32]);
+    }
+
+    #[test]
+    fn norms_stay_f32_and_experts_are_quantized() {
+        let tensors: [(&str, &[usize]); 4] = [
+            ("blk.0.attn_norm.weight", &[4096]),
+            ("blk.0.attn_q.bias", &[4096]),
+            ("blk.0.ffn_gate_exps.weight", &[4096, 14336, 8]),
+            ("blk.0.attn_q.weight", &[4096, 4096]),
+        ];
+        let types = plan_tensor_types(GgufQuantType::Q4KM, &tensors);
+        assert_eq!(types, vec![GgmlType::F32, GgmlType::F32, GgmlType::Q4K, GgmlType::Q4K]);
+    }
+}
@@ -13,6 +13,7 @@ impl MergeStrategy for AverageMerge {
         tensors: &[(Tensor, f64)],
         _params: &MethodParams,
         _base_tensor: Option<&Tensor>,
+        _layer_index: Option<u64>,
     ) -> Result<Tensor, ModelError> {
         if tensors.is_empty() {
             return Err(ModelError::MergeError("No tensors to average".into()));
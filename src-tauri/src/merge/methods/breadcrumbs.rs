@@ -0,0 +1,111 @@
+use candle_core::Tensor;
+
+use crate::merge::config::MethodParams;
+use crate::model::error::ModelError;
+
+use super::MergeStrategy;
+
+/// Breadcrumbs: task arithmetic on outlier-trimmed task vectors. Each task
+/// vector drops its largest `gamma` fraction of elements by magnitude, keeps
+/// the next `density` fraction and drops the small remainder, then the
+/// weighted sum is added to the base.
+pub struct BreadcrumbsMerge;
+
+/// 1.0 for elements ranked after the top `gamma` fraction by magnitude and
+/// within the following `density` fraction; 0.0 everywhere else.
+fn breadcrumb_mask(magnitudes: &[f32], density: f64, gamma: f64) -> Vec<f32> {
+    let numel = magnitudes.len();
+    let mut order: Vec<usize> = (0..numel).collect();
+    order.sort_unstable_by(|&a, &b| magnitudes[b].total_cmp(&magnitudes[a]));
+
+    let skip = ((numel as f64 * gamma).round() as usize).min(numel);
+    let keep = ((numel as f64 * density).round() as usize).min(numel - skip);
+
+    let mut mask = vec![0.0f32; numel];
+    for &i in &order[skip..skip + keep] {
+        mask[i] = 1.0;
+    }
+    mask
+}
+
+impl MergeStrategy for BreadcrumbsMerge {
+    fn merge(
+        &self,
+        tensors: &[(Tensor, f64)],
+        params: &MethodParams,
+        base_tensor: Option<&Tensor>,
+        _layer_index: Option<u64>,
+    ) -> Result<Tensor, ModelError> {
+        let base = base_tensor.ok_or_else(|| {
+            ModelError::MergeError("Breadcrumbs requires a base model".into())
+        })?;
+
+        let density = params.density.unwrap_or(0.9).clamp(0.0, 1.0);
+        let gamma = params.gamma.unwrap_or(0.01).clamp(0.0, 1.0);
+        let map_err = |e: candle_core::Error| ModelError::CandleError(e.to_string());
+
+        let total_weight: f64 = tensors.iter().map(|(_, w)| w).sum();
+        let norm = if total_weight > 0.0 { total_weight } else { 1.0 };
+
+        let mut merged_delta = Tensor::zeros_like(base).map_err(map_err)?;
+
+        for (tensor, weight) in tensors {
+            let delta = (tensor - base).map_err(map_err)?;
+            let magnitudes: Vec<f32> = delta.abs().map_err(map_err)?
+                .flatten_all().map_err(map_err)?
+                .to_vec1::<f32>().map_err(map_err)?;
+
+            let mask = Tensor::from_vec(breadcrumb_mask(&magnitudes, density, gamma), delta.shape(), delta.device())
+                .map_err(map_err)?;
+
+            let trimmed = (&delta * &mask).map_err(map_err)?;
+            let weighted = (&trimmed * (*weight / norm)).map_err(map_err)?;
+            merged_delta = (&merged_delta + &weighted).map_err(map_err)?;
+        }
+
+        let result = (base + &merged_delta).map_err(map_err)?;
+        Ok(result)
+    }
+
+    fn name(&self) -> &'static str { "Breadcrumbs" }
+    fn requires_base(&self) -> bool { true }
+    fn min_parents(&self) -> usize { 2 }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Magnitudes 1..=10 in scrambled order.
+    const MAGNITUDES: [f32; 10] = [3.0, 10.0, 1.0, 7.0, 5.0, 9.0, 2.0, 8.0, 4.0, 6.0];
+
+    fn kept(density: f64, gamma: f64) -> Vec<f32> {
+        let mask = breadcrumb_mask(&MAGNITUDES, density, gamma);
+        let mut kept: Vec<f32> = MAGNITUDES.iter()
+            .zip(&mask)
+            .filter(|(_, &m)| m == 1.0)
+            .map(|(&v, _)| v)
+            .collect();
+        kept.sort_by(f32::total_cmp);
+        kept
+    }
+
+    #[test]
+    fn drops_top_gamma_then_keeps_density_window() {
+        assert_eq!(kept(0.5, 0.1), [5.0, 6.0, 7.0, 8.0, 9.0]);
+    }
+
+    #[test]
+    fn fractions_round_to_whole_elements() {
+        // 1.5 elements of gamma round to 2, 2.4 of density round to 2
+        assert_eq!(kept(0.24, 0.15), [7.0, 8.0]);
+    }
+
+    #[test]
+    fn window_is_clamped_at_the_edges() {
+        assert_eq!(kept(1.0, 0.2), [1.0, 2.0, 3.0, 4.0, 5.0, 6.0, 7.0, 8.0]);
+        assert!(kept(0.5, 1.0).is_empty());
+        assert!(kept(0.0, 0.0).is_empty());
+        assert_eq!(kept(1.0, 0.0).len(), 10);
+    }
+}
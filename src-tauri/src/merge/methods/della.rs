@@ -10,70 +10,149 @@ use super::MergeStrategy;
 /// Combines DARE-style dropout with density-aware rescaling.
 pub struct DellaMerge;
 
+/// DELLA-linear: the same magnitude-based dropout as DELLA, but surviving
+/// deltas keep their original magnitude instead of being scaled by `1/p`.
+pub struct DellaLinearMerge;
+
 impl MergeStrategy for DellaMerge {
     fn merge(
         &self,
         tensors: &[(Tensor, f64)],
         params: &MethodParams,
         base_tensor: Option<&Tensor>,
+        _layer_index: Option<u64>,
     ) -> Result<Tensor, ModelError> {
-        let base = base_tensor.ok_or_else(|| {
-            ModelError::MergeError("DELLA requires a base model".into())
-        })?;
-
-        let density = params.della_density.unwrap_or(0.7);
-        let lambda = params.lambda.unwrap_or(1.0);
-        let map_err = |e: candle_core::Error| ModelError::CandleError(e.to_string());
-
-        let mut rng = rand::thread_rng();
-        let numel = base.elem_count();
-        let total_weight: f64 = tensors.iter().map(|(_, w)| w).sum();
-        let norm = if total_weight > 0.0 { total_weight } else { 1.0 };
-
-        let mut merged_delta = Tensor::zeros_like(base).map_err(map_err)?;
-
-        for (tensor, weight) in tensors {
-            let delta = (tensor - base).map_err(map_err)?;
-            let abs_delta = delta.abs().map_err(map_err)?;
-            let flat_abs: Vec<f32> = abs_delta.flatten_all().map_err(map_err)?
-                .to_vec1::<f32>().map_err(map_err)?;
-
-            // Compute magnitude-based density scores
-            let max_mag = flat_abs.iter().cloned().fold(0.0f32, f32::max);
-            let density_scores: Vec<f32> = if max_mag > 1e-10 {
-                flat_abs.iter().map(|&v| v / max_mag).collect()
-            } else {
-                vec![0.5; numel]
-            };
-
-            // Create density-aware dropout mask
-            let mask_data: Vec<f32> = density_scores
-                .iter()
-                .map(|&score| {
-                    let keep_prob = density * (1.0 + score as f64 * lambda) / (1.0 + lambda);
-                    let keep_prob = keep_prob.clamp(0.01, 1.0);
-                    if rng.gen::<f64>() < keep_prob {
-                        (1.0 / keep_prob) as f32
-                    } else {
-                        0.0
-                    }
-                })
-                .collect();
-
-            let mask = Tensor::from_vec(mask_data, delta.shape(), delta.device())
-                .map_err(map_err)?;
-
-            let masked = (&delta * &mask).map_err(map_err)?;
-            let weighted = (&masked * (*weight / norm)).map_err(map_err)?;
-
-            merged_delta = (&merged_delta + &weighted).map_err(map_err)?;
-        }
-
-        let result = (base + &merged_delta).map_err(map_err)?;
-        Ok(result)
+        della_merge(self.name(), tensors, params, base_tensor, true, &mut rand::thread_rng())
     }
 
     fn name(&self) -> &'static str { "DELLA" }
     fn requires_base(&self) -> bool { true }
     fn min_parents(&self) -> usize { 2 }
 }
+
+impl MergeStrategy for DellaLinearMerge {
+    fn merge(
+        &self,
+        tensors: &[(Tensor, f64)],
+        params: &MethodParams,
+        base_tensor: Option<&Tensor>,
+        _layer_index: Option<u64>,
+    ) -> Result<Tensor, ModelError> {
+        della_merge(self.name(), tensors, params, base_tensor, false, &mut rand::thread_rng())
+    }
+
+    fn name(&self) -> &'static str { "DELLA-linear" }
+    fn requires_base(&self) -> bool { true }
+    fn min_parents(&self) -> usize { 2 }
+}
+
+/// Keep probability of an element whose normalized magnitude is `score`.
+fn keep_probability(score: f32, density: f64, lambda: f64) -> f64 {
+    let keep_prob = density * (1.0 + score as f64 * lambda) / (1.0 + lambda);
+    keep_prob.clamp(0.01, 1.0)
+}
+
+/// Shared DELLA body; `rescale` divides kept deltas by their keep probability.
+fn della_merge(
+    name: &str,
+    tensors: &[(Tensor, f64)],
+    params: &MethodParams,
+    base_tensor: Option<&Tensor>,
+    rescale: bool,
+    rng: &mut impl Rng,
+) -> Result<Tensor, ModelError> {
+    let base = base_tensor.ok_or_else(|| {
+        ModelError::MergeError(format!("{} requires a base model", name))
+    })?;
+
+    let density = params.della_density.unwrap_or(0.7);
+    let lambda = params.lambda.unwrap_or(1.0);
+    let map_err = |e: candle_core::Error| ModelError::CandleError(e.to_string());
+
+    let numel = base.elem_count();
+    let total_weight: f64 = tensors.iter().map(|(_, w)| w).sum();
+    let norm = if total_weight > 0.0 { total_weight } else { 1.0 };
+
+    let mut merged_delta = Tensor::zeros_like(base).map_err(map_err)?;
+
+    for (tensor, weight) in tensors {
+        let delta = (tensor - base).map_err(map_err)?;
+        let abs_delta = delta.abs().map_err(map_err)?;
+        let flat_abs: Vec<f32> = abs_delta.flatten_all().map_err(map_err)?
+            .to_vec1::<f32>().map_err(map_err)?;
+
+        // Compute magnitude-based density scores
+        let max_mag = flat_abs.iter().cloned().fold(0.0f32, f32::max);
+        let density_scores: Vec<f32> = if max_mag > 1e-10 {
+            flat_abs.iter().map(|&v| v / max_mag).collect()
+        } else {
+            vec![0.5; numel]
+        };
+
+        // Create density-aware dropout mask
+        let mask_data: Vec<f32> = density_scores
+            .iter()
+            .map(|&score| {
+                let keep_prob = keep_probability(score, density, lambda);
+                if rng.gen::<f64>() >= keep_prob {
+                    0.0
+                } else if rescale {
+                    (1.0 / keep_prob) as f32
+                } else {
+                    1.0
+                }
+            })
+            .collect();
+
+        let mask = Tensor::from_vec(mask_data, delta.shape(), delta.device())
+            .map_err(map_err)?;
+
+        let masked = (&delta * &mask).map_err(map_err)?;
+        let weighted = (&masked * (*weight / norm)).map_err(map_err)?;
+
+        merged_delta = (&merged_delta + &weighted).map_err(map_err)?;
+    }
+
+    let result = (base + &merged_delta).map_err(map_err)?;
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use candle_core::Device;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    use super::*;
+
+    fn run(rescale: bool) -> Vec<f32> {
+        let delta: Vec<f32> = (1..=64).map(|i| i as f32 / 8.0).collect();
+        let base = Tensor::zeros(64, candle_core::DType::F32, &Device::Cpu).unwrap();
+        let tuned = Tensor::from_vec(delta, 64, &Device::Cpu).unwrap();
+        let params = MethodParams { della_density: Some(0.5), lambda: Some(1.0), ..MethodParams::default() };
+        let mut rng = StdRng::seed_from_u64(7);
+        della_merge("DELLA", &[(tuned, 1.0)], &params, Some(&base), rescale, &mut rng)
+            .unwrap()
+            .to_vec1::<f32>()
+            .unwrap()
+    }
+
+    #[test]
+    fn seeded_variants_share_the_drop_pattern() {
+        let della = run(true);
+        let linear = run(false);
+        assert!(della.iter().any(|&v| v == 0.0) && della.iter().any(|&v| v != 0.0));
+
+        for (i, (&d, &l)) in della.iter().zip(&linear).enumerate() {
+            assert_eq!(d == 0.0, l == 0.0, "drop pattern differs at {}", i);
+            if l == 0.0 {
+                continue;
+            }
+            // DELLA-linear keeps the delta as is; DELLA divides it by the keep probability
+            let delta = (i + 1) as f32 / 8.0;
+            assert_eq!(l, delta);
+            let p = keep_probability(delta / 8.0, 0.5, 1.0);
+            assert!((d - delta / p as f32).abs() < 1e-5);
+        }
+    }
+}
@@ -16,6 +16,7 @@ impl MergeStrategy for FrankenmergeMerge {
         tensors: &[(Tensor, f64)],
         _params: &MethodParams,
         _base_tensor: Option<&Tensor>,
+        _layer_index: Option<u64>,
     ) -> Result<Tensor, ModelError> {
         // Frankenmerge operates at the layer assignment level.
         // If we reach this merge function, just take the first tensor (highest weight).
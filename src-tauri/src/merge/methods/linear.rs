@@ -0,0 +1,88 @@
+use candle_core::Tensor;
+
+use crate::merge::config::{CurvePoint, MethodParams};
+use crate::model::error::ModelError;
+
+use super::MergeStrategy;
+
+/// Linear: weighted average where each parent's weight can follow a curve over
+/// layer index (`weight_curves`, in parent order). Weights are renormalized per
+/// layer so they always sum to 1. Parents without a curve, and tensors outside
+/// the transformer blocks, use the flat parent weight.
+pub struct LinearMerge;
+
+/// Piecewise-linear sample of `curve` at `layer`, clamped to the end points.
+fn sample_curve(curve: &[CurvePoint], layer: f64) -> Option<f64> {
+    let mut points = curve.to_vec();
+    points.sort_by(|a, b| a.layer.total_cmp(&b.layer));
+
+    let first = points.first()?;
+    let last = points.last()?;
+    if layer <= first.layer {
+        return Some(first.weight);
+    }
+    if layer >= last.layer {
+        return Some(last.weight);
+    }
+
+    points.windows(2).find_map(|pair| {
+        let (a, b) = (pair[0], pair[1]);
+        if layer < a.layer || layer > b.layer {
+            return None;
+        }
+        let span = b.layer - a.layer;
+        if span <= 0.0 {
+            return Some(b.weight);
+        }
+        Some(a.weight + (b.weight - a.weight) * (layer - a.layer) / span)
+    })
+}
+
+impl MergeStrategy for LinearMerge {
+    fn merge(
+        &self,
+        tensors: &[(Tensor, f64)],
+        params: &MethodParams,
+        _base_tensor: Option<&Tensor>,
+        layer_index: Option<u64>,
+    ) -> Result<Tensor, ModelError> {
+        if tensors.is_empty() {
+            return Err(ModelError::MergeError("No tensors to merge".into()));
+        }
+        if tensors.len() == 1 {
+            return Ok(tensors[0].0.clone());
+        }
+
+        let curves = params.weight_curves.as_deref().unwrap_or(&[]);
+        let weights: Vec<f64> = tensors
+            .iter()
+            .enumerate()
+            .map(|(i, (_, flat))| {
+                layer_index
+                    .and_then(|layer| curves.get(i).and_then(|c| sample_curve(c, layer as f64)))
+                    .unwrap_or(*flat)
+            })
+            .collect();
+
+        let total_weight: f64 = weights.iter().sum();
+        if total_weight <= 0.0 {
+            return Err(ModelError::MergeError(format!(
+                "Linear weights sum to {:.3} at layer {:?}; they must be positive",
+                total_weight, layer_index
+            )));
+        }
+
+        let map_err = |e: candle_core::Error| ModelError::CandleError(e.to_string());
+        let mut result = (&tensors[0].0 * (weights[0] / total_weight)).map_err(map_err)?;
+        for ((tensor, _), weight) in tensors[1..].iter().zip(&weights[1..]) {
+            let scaled = (tensor * (weight / total_weight)).map_err(map_err)?;
+            result = (&result + &scaled).map_err(map_err)?;
+        }
+
+        Ok(result)
+    }
+
+    fn name(&self) -> &'static str { "Linear" }
+    fn requires_base(&self) -> bool { false }
+    fn min_parents(&self) -> usize { 2 }
+}
@@ -0,0 +1,105 @@
+use candle_core::Tensor;
+
+use crate::merge::config::MethodParams;
+use crate::model::error::ModelError;
+
+use super::MergeStrategy;
+
+/// Max-Magnitude: per element, keep the value from the parent with the
+/// largest weighted absolute value. Ties go to the earlier parent.
+pub struct MaxMagnitudeMerge;
+
+impl MergeStrategy for MaxMagnitudeMerge {
+    fn merge(
+        &self,
+        tensors: &[(Tensor, f64)],
+        _params: &MethodParams,
+        _base_tensor: Option<&Tensor>,
+        _layer_index: Option<u64>,
+    ) -> Result<Tensor, ModelError> {
+        if tensors.is_empty() {
+            return Err(ModelError::MergeError("No tensors to merge".into()));
+        }
+        if tensors.len() == 1 {
+            return Ok(tensors[0].0.clone());
+        }
+
+        let map_err = |e: candle_core::Error| ModelError::CandleError(e.to_string());
+
+        let first = &tensors[0].0;
+        let numel = first.elem_count();
+
+        let mut result: Vec<f32> = first.flatten_all().map_err(map_err)?
+            .to_vec1::<f32>().map_err(map_err)?;
+        let first_weight = tensors[0].1.abs() as f32;
+        let mut best_score: Vec<f32> = result.iter().map(|v| v.abs() * first_weight).collect();
+
+        for (tensor, weight) in &tensors[1..] {
+            if tensor.elem_count() != numel {
+                return Err(ModelError::MergeError(format!(
+                    "Max-Magnitude shape mismatch: {:?} vs {:?}",
+                    first.dims(),
+                    tensor.dims()
+                )));
+            }
+            let w = weight.abs() as f32;
+            let flat: Vec<f32> = tensor.flatten_all().map_err(map_err)?
+                .to_vec1::<f32>().map_err(map_err)?;
+            for (i, &val) in flat.iter().enumerate() {
+                let score = val.abs() * w;
+                // Strictly greater, so ties keep the earlier parent's value
+                if score > best_score[i] {
+                    best_score[i] = score;
+                    result[i] = val;
+                }
+            }
+        }
+
+        Tensor::from_vec(result, first.shape(), first.device()).map_err(map_err)
+    }
+
+    fn name(&self) -> &'static str { "Max-Magnitude" }
+    fn requires_base(&self) -> bool { false }
+    fn min_parents(&self) -> usize { 2 }
+}
+
+#[cfg(test)]
+mod tests {
+    use candle_core::Device;
+
+    use super::*;
+
+    fn values(t: Tensor) -> Vec<f32> {
+        t.flatten_all().unwrap().to_vec1::<f32>().unwrap()
+    }
+
+    #[test]
+    fn picks_the_largest_magnitude_per_element() {
+        let a = Tensor::new(&[1.0f32, -3.0, 2.0, -2.0, 0.0], &Device::Cpu).unwrap();
+        let b = Tensor::new(&[-2.0f32, 1.0, -2.0, 2.0, 0.5], &Device::Cpu).unwrap();
+        let c = Tensor::new(&[0.5f32, 2.5, 1.0, 1.0, -0.5], &Device::Cpu).unwrap();
+        let merged = MaxMagnitudeMerge
+            .merge(&[(a, 1.0), (b, 1.0), (c, 1.0)], &MethodParams::default(), None, None)
+            .unwrap();
+        // Elements 2 and 3 tie between a and b; element 4 ties between b and c
+        assert_eq!(values(merged), [-2.0, -3.0, 2.0, -2.0, 0.5]);
+    }
+
+    #[test]
+    fn weights_bias_the_comparison() {
+        let a = Tensor::new(&[1.0f32, 4.0], &Device::Cpu).unwrap();
+        let b = Tensor::new(&[-3.0f32, 1.0], &Device::Cpu).unwrap();
+        // Scores: a = [3, 12], b = [3, 1]; the first element ties and stays with a
+        let merged = MaxMagnitudeMerge
+            .merge(&[(a, 3.0), (b, 1.0)], &MethodParams::default(), None, None)
+            .unwrap();
+        assert_eq!(values(merged), [1.0, 4.0]);
+    }
+
+    #[test]
+    fn rejects_mismatched_shapes() {
+        let a = Tensor::new(&[1.0f32, 2.0], &Device::Cpu).unwrap();
+        let b = Tensor::new(&[1.0f32, 2.0, 3.0], &Device::Cpu).unwrap();
+        assert!(MaxMagnitudeMerge.merge(&[(a, 1.0), (b, 1.0)], &MethodParams::default(), None, None).is_err());
+    }
+}
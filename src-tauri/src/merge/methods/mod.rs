@@ -1,11 +1,17 @@
 pub mod average;
+pub mod breadcrumbs;
 pub mod component_merge;
 pub mod dare;
 pub mod della;
 pub mod frankenmerge;
+pub mod linear;
+pub mod max_magnitude;
+pub mod model_stock;
 pub mod moe_conversion;
+pub mod nuslerp;
 pub mod parameter_slice;
 pub mod passthrough;
+pub mod sce;
 pub mod slerp;
 pub mod task_arithmetic;
 pub mod tensor_surgery;
@@ -20,16 +26,22 @@ use super::config::{MergeMethod, MethodParams};
 /// Trait for merge strategy implementations.
 pub trait MergeStrategy: Send + Sync {
     /// Merge multiple tensors with weights using this strategy.
-    /// `tensors` is a list of (tensor, weight) pairs.
+    /// `tensors` is a list of (tensor, weight) pairs; `layer_index` is the
+    /// transformer block the tensor belongs to (`None` for embeddings, final norm, etc.).
     fn merge(
         &self,
         tensors: &[(Tensor, f64)],
         params: &MethodParams,
         base_tensor: Option<&Tensor>,
+        layer_index: Option<u64>,
     ) -> Result<Tensor, ModelError>;
 
     fn name(&self) -> &'static str;
     fn requires_base(&self) -> bool;
+    /// Whether a base tensor is used when one is configured, even if optional.
+    fn accepts_base(&self) -> bool { self.requires_base() }
+    /// Whether the base parent, when also listed as a parent, is left out of `tensors`.
+    fn excludes_base_parent(&self) -> bool { false }
     fn min_parents(&self) -> usize;
 }
 
@@ -43,10 +55,17 @@ pub fn get_strategy(method: MergeMethod) -> Box<dyn MergeStrategy> {
         MergeMethod::Dare => Box::new(dare::DareMerge),
         MergeMethod::Ties => Box::new(ties::TiesMerge),
         MergeMethod::Della => Box::new(della::DellaMerge),
+        MergeMethod::DellaLinear => Box::new(della::DellaLinearMerge),
         MergeMethod::Passthrough => Box::new(passthrough::PassthroughMerge),
         MergeMethod::ComponentMerge => Box::new(component_merge::ComponentMergeMerge),
         MergeMethod::TensorSurgery => Box::new(tensor_surgery::TensorSurgeryMerge),
         MergeMethod::ParameterSlice => Box::new(parameter_slice::ParameterSliceMerge),
         MergeMethod::MoeConversion => Box::new(moe_conversion::MoeConversionMerge),
+        MergeMethod::MaxMagnitude => Box::new(max_magnitude::MaxMagnitudeMerge),
+        MergeMethod::Linear => Box::new(linear::LinearMerge),
+        MergeMethod::ModelStock => Box::new(model_stock::ModelStockMerge),
+        MergeMethod::Sce => Box::new(sce::SceMerge),
+        MergeMethod::NuSlerp => Box::new(nuslerp::NuSlerpMerge),
+        MergeMethod::Breadcrumbs => Box::new(breadcrumbs::BreadcrumbsMerge),
     }
 }
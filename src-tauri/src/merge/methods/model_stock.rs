@@ -0,0 +1,152 @@
+use candle_core::Tensor;
+
+use crate::merge::config::MethodParams;
+use crate::model::error::ModelError;
+
+use super::MergeStrategy;
+
+/// Model Stock: average the fine-tuned models, then pull the average back toward
+/// the base by a ratio derived from the angle between their task vectors:
+/// `t = N·cosθ / (1 + (N−1)·cosθ)`, `result = t·avg + (1−t)·base`, where cosθ
+/// is the mean pairwise cosine similarity of the task vectors. Parent weights
+/// are not used; parents identical to the base are ignored.
+pub struct ModelStockMerge;
+
+fn cosine(a: &[f32], b: &[f32]) -> f64 {
+    let (mut dot, mut na, mut nb) = (0.0f64, 0.0f64, 0.0f64);
+    for (&x, &y) in a.iter().zip(b) {
+        dot += x as f64 * y as f64;
+        na += x as f64 * x as f64;
+        nb += y as f64 * y as f64;
+    }
+    if na == 0.0 || nb == 0.0 {
+        return 0.0;
+    }
+    dot / (na.sqrt() * nb.sqrt())
+}
+
+impl MergeStrategy for ModelStockMerge {
+    fn merge(
+        &self,
+        tensors: &[(Tensor, f64)],
+        _params: &MethodParams,
+        base_tensor: Option<&Tensor>,
+        _layer_index: Option<u64>,
+    ) -> Result<Tensor, ModelError> {
+        let base = base_tensor.ok_or_else(|| {
+            ModelError::MergeError("Model Stock requires a base model".into())
+        })?;
+        let map_err = |e: candle_core::Error| ModelError::CandleError(e.to_string());
+
+        let base_flat: Vec<f32> = base.flatten_all().map_err(map_err)?
+            .to_vec1::<f32>().map_err(map_err)?;
+
+        // Task vectors of the parents that actually differ from the base
+        let mut task_vectors: Vec<Vec<f32>> = Vec::new();
+        for (tensor, _) in tensors {
+            if tensor.elem_count() != base_flat.len() {
+                return Err(ModelError::MergeError(format!(
+                    "Model Stock shape mismatch: {:?} vs base {:?}",
+                    tensor.dims(),
+                    base.dims()
+                )));
+            }
+            let flat: Vec<f32> = tensor.flatten_all().map_err(map_err)?
+                .to_vec1::<f32>().map_err(map_err)?;
+            let delta: Vec<f32> = flat.iter().zip(&base_flat).map(|(t, b)| t - b).collect();
+            if delta.iter().any(|&d| d != 0.0) {
+                task_vectors.push(delta);
+            }
+        }
+
+        let n = task_vectors.len();
+        if n == 0 {
+            return Ok(base.clone());
+        }
+
+        let cos_theta = if n == 1 {
+            1.0
+        } else {
+            let mut sum = 0.0;
+            let mut pairs = 0usize;
+            for i in 0..n {
+                for j in (i + 1)..n {
+                    sum += cosine(&task_vectors[i], &task_vectors[j]);
+                    pairs += 1;
+                }
+            }
+            (sum / pairs as f64).clamp(-1.0, 1.0)
+        };
+
+        let denom = 1.0 + (n as f64 - 1.0) * cos_theta;
+        let t = if denom.abs() < 1e-8 { 0.0 } else { (n as f64 * cos_theta / denom).clamp(0.0, 1.0) };
+
+        // base + t · mean(task vectors) == t·avg + (1−t)·base
+        let scale = (t / n as f64) as f32;
+        let mut result = base_flat;
+        for delta in &task_vectors {
+            for (r, d) in result.iter_mut().zip(delta) {
+                *r += scale * d;
+            }
+        }
+
+        Tensor::from_vec(result, base.shape(), base.device()).map_err(map_err)
+    }
+
+    fn name(&self) -> &'static str { "Model Stock" }
+    fn requires_base(&self) -> bool { true }
+    fn min_parents(&self) -> usize { 2 }
+}
+
+#[cfg(test)]
+mod tests {
+    use candle_core::Device;
+
+    use super::*;
+
+    fn t(values: &[f32]) -> Tensor {
+        Tensor::new(values, &Device::Cpu).unwrap()
+    }
+
+    fn merge(parents: &[&[f32]], base: &[f32]) -> Vec<f32> {
+        let tensors: Vec<(Tensor, f64)> = parents.iter().map(|p| (t(p), 1.0)).collect();
+        ModelStockMerge
+            .merge(&tensors, &MethodParams::default(), Some(&t(base)), None)
+            .unwrap()
+            .to_vec1::<f32>()
+            .unwrap()
+    }
+
+    fn assert_close(got: &[f32], want: &[f32]) {
+        for (g, w) in got.iter().zip(want) {
+            assert!((g - w).abs() < 1e-6, "{:?} vs {:?}", got, want);
+        }
+    }
+
+    #[test]
+    fn interpolates_by_the_task_vector_angle() {
+        // Task vectors [1, 1, 0] and [1, 0, 1]: cosθ = 1/2, so t = 2·½ / (1 + ½) = 2/3
+        // and the result is base + 2/3 · [1, ½, ½]
+        let merged = merge(&[&[2.0, 2.0, 1.0], &[2.0, 1.0, 2.0]], &[1.0, 1.0, 1.0]);
+        assert_close(&merged, &[1.0 + 2.0 / 3.0, 1.0 + 1.0 / 3.0, 1.0 + 1.0 / 3.0]);
+    }
+
+    #[test]
+    fn orthogonal_task_vectors_fall_back_to_the_base() {
+        let merged = merge(&[&[1.0, 0.0], &[0.0, 1.0]], &[0.0, 0.0]);
+        assert_close(&merged, &[0.0, 0.0]);
+    }
+
+    #[test]
+    fn parents_equal_to_the_base_are_ignored() {
+        // One real task vector left, so t = 1 and it passes through unchanged
+        let merged = merge(&[&[3.0, -1.0], &[1.0, 1.0]], &[1.0, 1.0]);
+        assert_close(&merged, &[3.0, -1.0]);
+    }
+
+    #[test]
+    fn requires_a_base() {
+        let tensors = [(t(&[1.0]), 1.0), (t(&[2.0]), 1.0)];
+        assert!(ModelStockMerge.merge(&tensors, &MethodParams::default(), None, None).is_err());
+    }
+}
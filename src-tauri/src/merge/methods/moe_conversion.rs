@@ -18,6 +18,7 @@ impl MergeStrategy for MoeConversionMerge {
         tensors: &[(Tensor, f64)],
         _params: &MethodParams,
         _base_tensor: Option<&Tensor>,
+        _layer_index: Option<u64>,
     ) -> Result<Tensor, ModelError> {
         if tensors.is_empty() {
             return Err(ModelError::MergeError("No tensors for MoE conversion".into()));
@@ -0,0 +1,162 @@
+use candle_core::Tensor;
+
+use crate::merge::config::MethodParams;
+use crate::model::error::ModelError;
+
+use super::slerp::slerp_tensors;
+use super::MergeStrategy;
+
+/// NuSLERP: SLERP where the interpolation factor comes from the parent weights
+/// (`t = w₁ / (w₀ + w₁)`). With `nuslerp_flatten` off, each row
+/// (`nuslerp_row_wise`) or column vector is interpolated on its own sphere.
+/// Given a base, the task vectors are interpolated and added back to it; the
+/// base parent itself is left out of `tensors` by the executor.
+pub struct NuSlerpMerge;
+
+/// SLERP coefficients for two vectors with the given dot product and norms,
+/// falling back to linear interpolation when either is ~zero or they are
+/// nearly parallel (same thresholds as `slerp_tensors`).
+fn slerp_scales(dot: f64, norm_a: f64, norm_b: f64, t: f64) -> (f64, f64) {
+    if norm_a < 1e-10 || norm_b < 1e-10 {
+        return (1.0 - t, t);
+    }
+    let cos = (dot / (norm_a * norm_b)).clamp(-1.0, 1.0);
+    if cos.abs() > 0.9995 {
+        return (1.0 - t, t);
+    }
+    let omega = cos.acos();
+    let sin_omega = omega.sin();
+    (((1.0 - t) * omega).sin() / sin_omega, (t * omega).sin() / sin_omega)
+}
+
+/// Interpolate consecutive `len`-element vectors of `a` and `b` independently.
+fn slerp_vectors(a: &[f32], b: &[f32], len: usize, t: f64) -> Vec<f32> {
+    let mut out = Vec::with_capacity(a.len());
+    for (va, vb) in a.chunks(len).zip(b.chunks(len)) {
+        let (mut dot, mut na, mut nb) = (0.0f64, 0.0f64, 0.0f64);
+        for (&x, &y) in va.iter().zip(vb) {
+            dot += x as f64 * y as f64;
+            na += x as f64 * x as f64;
+            nb += y as f64 * y as f64;
+        }
+        let (sa, sb) = slerp_scales(dot, na.sqrt(), nb.sqrt(), t);
+        out.extend(va.iter().zip(vb).map(|(&x, &y)| (sa * x as f64 + sb * y as f64) as f32));
+    }
+    out
+}
+
+/// Per-vector SLERP: rows are the last-dim slices, columns run along dim 0.
+fn slerp_per_vector(a: &Tensor, b: &Tensor, t: f64, row_wise: bool) -> Result<Tensor, ModelError> {
+    let map_err = |e: candle_core::Error| ModelError::CandleError(e.to_string());
+    let dims = a.dims().to_vec();
+    if dims.len() < 2 {
+        return slerp_tensors(a, b, t);
+    }
+
+    let (outer, len) = if row_wise {
+        let len = dims[dims.len() - 1];
+        (a.elem_count() / len, len)
+    } else {
+        (a.elem_count() / dims[0], dims[0])
+    };
+    // Lay the vectors out contiguously: columns need a transpose first
+    let as_vectors = |x: &Tensor| -> Result<Vec<f32>, candle_core::Error> {
+        if row_wise {
+            x.flatten_all()?.to_vec1::<f32>()
+        } else {
+            x.reshape((len, outer))?.t()?.contiguous()?.flatten_all()?.to_vec1::<f32>()
+        }
+    };
+    let va = as_vectors(a).map_err(map_err)?;
+    let vb = as_vectors(b).map_err(map_err)?;
+    let merged = slerp_vectors(&va, &vb, len, t);
+
+    let result = Tensor::from_vec(merged, (outer, len), a.device()).map_err(map_err)?;
+    let result = if row_wise {
+        result
+    } else {
+        result.t().and_then(|r| r.contiguous()).map_err(map_err)?
+    };
+    result.reshape(dims).map_err(map_err)
+}
+
+impl MergeStrategy for NuSlerpMerge {
+    fn merge(
+        &self,
+        tensors: &[(Tensor, f64)],
+        params: &MethodParams,
+        base_tensor: Option<&Tensor>,
+        _layer_index: Option<u64>,
+    ) -> Result<Tensor, ModelError> {
+        let map_err = |e: candle_core::Error| ModelError::CandleError(e.to_string());
+
+        let [(a, wa), (b, wb)] = tensors else {
+            return Err(ModelError::MergeError(format!(
+                "NuSLERP merges exactly 2 models (plus an optional base), got {}",
+                tensors.len()
+            )));
+        };
+        if wa + wb <= 0.0 {
+            return Err(ModelError::MergeError("NuSLERP weights must sum to more than 0".into()));
+        }
+        let t = wb / (wa + wb);
+        let flatten = params.nuslerp_flatten.unwrap_or(true);
+        let row_wise = params.nuslerp_row_wise.unwrap_or(false);
+
+        let interpolate = |x: &Tensor, y: &Tensor| {
+            if flatten {
+                slerp_tensors(x, y, t)
+            } else {
+                slerp_per_vector(x, y, t, row_wise)
+            }
+        };
+
+        match base_tensor {
+            Some(base) => {
+                let da = (a - base).map_err(map_err)?;
+                let db = (b - base).map_err(map_err)?;
+                let merged = interpolate(&da, &db)?;
+                (base + &merged).map_err(map_err)
+            }
+            None => interpolate(a, b),
+        }
+    }
+
+    fn name(&self) -> &'static str { "NuSLERP" }
+    fn requires_base(&self) -> bool { false }
+    fn accepts_base(&self) -> bool { true }
+    fn excludes_base_parent(&self) -> bool { true }
+    fn min_parents(&self) -> usize { 2 }
+}
+
+#[cfg(test)]
+mod tests {
+    use candle_core::Device;
+
+    use super::super::slerp::SlerpMerge;
+    use super::*;
+
+    #[test]
+    fn matches_slerp_with_default_params() {
+        let a = Tensor::new(&[[1.0f32, 0.5, -2.0], [0.25, 3.0, 1.0]], &Device::Cpu).unwrap();
+        let b = Tensor::new(&[[-0.5f32, 2.0, 1.0], [1.5, -1.0, 0.75]], &Device::Cpu).unwrap();
+        let tensors = [(a, 1.0), (b, 1.0)];
+        let params = MethodParams::default();
+
+        let nuslerp = NuSlerpMerge.merge(&tensors, &params, None, None).unwrap();
+        let slerp = SlerpMerge.merge(&tensors, &params, None, None).unwrap();
+
+        let nuslerp = nuslerp.flatten_all().unwrap().to_vec1::<f32>().unwrap();
+        let slerp = slerp.flatten_all().unwrap().to_vec1::<f32>().unwrap();
+        for (x, y) in nuslerp.iter().zip(&slerp) {
+            assert!((x - y).abs() < 1e-5, "{} vs {}", x, y);
+        }
+    }
+
+    #[test]
+    fn rejects_a_third_model() {
+        let t = Tensor::new(&[1.0f32, 2.0], &Device::Cpu).unwrap();
+        let tensors = [(t.clone(), 1.0), (t.clone(), 1.0), (t, 1.0)];
+        assert!(NuSlerpMerge.merge(&tensors, &MethodParams::default(), None, None).is_err());
+    }
+}
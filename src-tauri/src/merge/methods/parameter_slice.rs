@@ -15,6 +15,7 @@ impl MergeStrategy for ParameterSliceMerge {
         tensors: &[(Tensor, f64)],
         params: &MethodParams,
         _base_tensor: Option<&Tensor>,
+        _layer_index: Option<u64>,
     ) -> Result<Tensor, ModelError> {
         if tensors.len() < 2 {
             return Err(ModelError::MergeError("Parameter Slice requires at least 2 tensors".into()));
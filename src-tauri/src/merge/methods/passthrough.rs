@@ -14,6 +14,7 @@ impl MergeStrategy for PassthroughMerge {
         tensors: &[(Tensor, f64)],
         _params: &MethodParams,
         _base_tensor: Option<&Tensor>,
+        _layer_index: Option<u64>,
     ) -> Result<Tensor, ModelError> {
         if tensors.is_empty() {
             return Err(ModelError::MergeError("No tensor to pass through".into()));
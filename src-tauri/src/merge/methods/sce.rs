@@ -0,0 +1,171 @@
+use candle_core::Tensor;
+
+use crate::merge::config::MethodParams;
+use crate::model::error::ModelError;
+
+use super::MergeStrategy;
+
+/// SCE-Merging: Select, Calculate, Erase.
+///
+/// 1. Select: keep the `density` fraction of positions with the highest
+///    variance across the task vectors (same mask for every parent).
+/// 2. Calculate: each parent's coefficient is the energy (sum of squares) of
+///    its selected task vector, normalized over parents.
+/// 3. Erase: drop values whose sign disagrees with the elected majority sign,
+///    then average the survivors by the coefficients of the parents that kept them.
+pub struct SceMerge;
+
+/// Positions whose task-vector variance across parents is in the top
+/// `density` fraction of `numel`.
+fn variance_mask(deltas: &[Vec<f32>], numel: usize, density: f64) -> Vec<bool> {
+    let n = deltas.len() as f64;
+    let variance: Vec<f64> = (0..numel)
+        .map(|i| {
+            let mean = deltas.iter().map(|d| d[i] as f64).sum::<f64>() / n;
+            deltas.iter().map(|d| (d[i] as f64 - mean).powi(2)).sum::<f64>() / n
+        })
+        .collect();
+    let keep = ((numel as f64) * density).round() as usize;
+    let mut selected = vec![false; numel];
+    if keep >= numel {
+        selected.iter_mut().for_each(|s| *s = true);
+    } else if keep > 0 {
+        let mut order: Vec<usize> = (0..numel).collect();
+        order.select_nth_unstable_by(keep - 1, |&a, &b| variance[b].total_cmp(&variance[a]));
+        for &i in &order[..keep] {
+            selected[i] = true;
+        }
+    }
+    selected
+}
+
+impl MergeStrategy for SceMerge {
+    fn merge(
+        &self,
+        tensors: &[(Tensor, f64)],
+        params: &MethodParams,
+        base_tensor: Option<&Tensor>,
+        _layer_index: Option<u64>,
+    ) -> Result<Tensor, ModelError> {
+        let base = base_tensor.ok_or_else(|| {
+            ModelError::MergeError("SCE requires a base model".into())
+        })?;
+        if tensors.is_empty() {
+            return Ok(base.clone());
+        }
+
+        let density = params.density.unwrap_or(0.5).clamp(0.0, 1.0);
+        let map_err = |e: candle_core::Error| ModelError::CandleError(e.to_string());
+        let numel = base.elem_count();
+
+        // Task vectors
+        let mut deltas: Vec<Vec<f32>> = Vec::with_capacity(tensors.len());
+        for (tensor, _) in tensors {
+            let delta = (tensor - base).map_err(map_err)?;
+            deltas.push(delta.flatten_all().map_err(map_err)?.to_vec1::<f32>().map_err(map_err)?);
+        }
+        let n = deltas.len() as f64;
+
+        // Step 1: Select top-density positions by variance across parents
+        let selected = variance_mask(&deltas, numel, density);
+        for delta in deltas.iter_mut() {
+            for (v, &sel) in delta.iter_mut().zip(&selected) {
+                if !sel {
+                    *v = 0.0;
+                }
+            }
+        }
+
+        // Step 2: Calculate per-parent coefficients from selected energy
+        let energy: Vec<f64> = deltas
+            .iter()
+            .map(|d| d.iter().map(|&v| (v as f64).powi(2)).sum())
+            .collect();
+        let total_energy: f64 = energy.iter().sum();
+        let coeffs: Vec<f64> = if total_energy > 0.0 {
+            energy.iter().map(|e| e / total_energy).collect()
+        } else {
+            vec![1.0 / n; deltas.len()]
+        };
+
+        // Step 3: Erase sign conflicts, then take the coefficient-weighted mean
+        let mut sign_votes = vec![0.0f64; numel];
+        for (delta, &c) in deltas.iter().zip(&coeffs) {
+            for (vote, &v) in sign_votes.iter_mut().zip(delta) {
+                *vote += c * v as f64;
+            }
+        }
+
+        let mut merged = vec![0.0f64; numel];
+        let mut divisor = vec![0.0f64; numel];
+        for (delta, &c) in deltas.iter().zip(&coeffs) {
+            for (i, &v) in delta.iter().enumerate() {
+                let v = v as f64;
+                let agrees = (v > 0.0 && sign_votes[i] > 0.0) || (v < 0.0 && sign_votes[i] < 0.0);
+                if agrees {
+                    merged[i] += c * v;
+                    divisor[i] += c;
+                }
+            }
+        }
+        let merged_data: Vec<f32> = merged
+            .iter()
+            .zip(&divisor)
+            .map(|(&m, &d)| if d > 0.0 { (m / d) as f32 } else { 0.0 })
+            .collect();
+
+        let merged_delta = Tensor::from_vec(merged_data, base.shape(), base.device())
+            .map_err(map_err)?;
+        let result = (base + &merged_delta).map_err(map_err)?;
+        Ok(result)
+    }
+
+    fn name(&self) -> &'static str { "SCE" }
+    fn requires_base(&self) -> bool { true }
+    fn min_parents(&self) -> usize { 2 }
+}
+
+#[cfg(test)]
+mod tests {
+    use candle_core::Device;
+
+    use super::*;
+
+    const A: [f32; 4] = [1.0, 2.0, 0.1, -3.0];
+    const B: [f32; 4] = [1.0, -2.0, 0.2, 1.0];
+    const C: [f32; 4] = [1.0, 1.0, 0.1, -1.0];
+
+    fn params(density: f64) -> MethodParams {
+        MethodParams { density: Some(density), ..Default::default() }
+    }
+
+    #[test]
+    fn selects_the_highest_variance_positions() {
+        // Variances: 0, 78/27, ~0.002, 8/3
+        let deltas = [A.to_vec(), B.to_vec(), C.to_vec()];
+        assert_eq!(variance_mask(&deltas, 4, 0.5), [false, true, false, true]);
+        assert_eq!(variance_mask(&deltas, 4, 0.25), [false, true, false, false]);
+        assert_eq!(variance_mask(&deltas, 4, 1.0), [true; 4]);
+        assert_eq!(variance_mask(&deltas, 4, 0.0), [false; 4]);
+    }
+
+    #[test]
+    fn averages_only_the_parents_agreeing_with_the_elected_sign() {
+        let base = Tensor::ones(4, candle_core::DType::F32, &Device::Cpu).unwrap();
+        let parent = |delta: [f32; 4]| (Tensor::new(&delta, &Device::Cpu).unwrap() + 1.0).unwrap();
+        let tensors = [(parent(A), 1.0), (parent(B), 1.0), (parent(C), 1.0)];
+        let merged = SceMerge
+            .merge(&tensors, &params(0.5), Some(&base), None)
+            .unwrap()
+            .to_vec1::<f32>()
+            .unwrap();
+
+        // Masked energies 13, 5, 2 give coefficients 0.65, 0.25, 0.10.
+        // Position 1 votes +0.9 (b is erased): (0.65·2 + 0.10·1) / 0.75
+        // Position 3 votes −1.8 (b is erased): (0.65·−3 + 0.10·−1) / 0.75
+        let want = [1.0, 1.0 + 1.4 / 0.75, 1.0, 1.0 - 2.05 / 0.75];
+        for (g, w) in merged.iter().zip(&want) {
+            assert!((g - w).abs() < 1e-5, "{:?} vs {:?}", merged, want);
+        }
+    }
+}
@@ -13,6 +13,7 @@ impl MergeStrategy for SlerpMerge {
         tensors: &[(Tensor, f64)],
         params: &MethodParams,
         _base_tensor: Option<&Tensor>,
+        _layer_index: Option<u64>,
     ) -> Result<Tensor, ModelError> {
         if tensors.len() < 2 {
             return Err(ModelError::MergeError("SLERP requires exactly 2 tensors".into()));
@@ -30,7 +31,7 @@ impl MergeStrategy for SlerpMerge {
     fn min_parents(&self) -> usize { 2 }
 }
 
-fn slerp_tensors(a: &Tensor, b: &Tensor, t: f64) -> Result<Tensor, ModelError> {
+pub(super) fn slerp_tensors(a: &Tensor, b: &Tensor, t: f64) -> Result<Tensor, ModelError> {
     let map_err = |e: candle_core::Error| ModelError::CandleError(e.to_string());
 
     // Flatten for dot product
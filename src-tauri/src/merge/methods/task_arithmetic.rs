@@ -13,6 +13,7 @@ impl MergeStrategy for TaskArithmeticMerge {
         tensors: &[(Tensor, f64)],
         params: &MethodParams,
         base_tensor: Option<&Tensor>,
+        _layer_index: Option<u64>,
     ) -> Result<Tensor, ModelError> {
         let base = base_tensor.ok_or_else(|| {
             ModelError::MergeError("Task Arithmetic requires a base model".into())
@@ -14,6 +14,7 @@ impl MergeStrategy for TiesMerge {
         tensors: &[(Tensor, f64)],
         params: &MethodParams,
         base_tensor: Option<&Tensor>,
+        _layer_index: Option<u64>,
     ) -> Result<Tensor, ModelError> {
         let base = base_tensor.ok_or_else(|| {
             ModelError::MergeError("TIES requires a base model".into())
@@ -2,7 +2,9 @@ pub mod capabilities;
 pub mod compatibility;
 pub mod config;
 pub mod executor;
+pub mod gguf_quant;
 pub mod methods;
+pub mod names;
 pub mod output;
 pub mod planner;
 pub mod precompute;
@@ -10,3 +12,4 @@ pub mod profiler;
 pub mod projections;
 pub mod registry;
 pub mod tensor_io;
+pub mod vocab;
@@ -0,0 +1,297 @@
+use candle_core::Tensor;
+
+use crate::model::error::ModelError;
+use crate::model::ModelFormat;
+
+use super::registry::{ParentModel, ParentRegistry};
+
+/// GGUF block-level tensor names and their HuggingFace equivalents
+/// (relative to `model.layers.N.`).
+const GGUF_BLOCK_NAMES: &[(&str, &str)] = &[
+    ("attn_q", "self_attn.q_proj"),
+    ("attn_k", "self_attn.k_proj"),
+    ("attn_v", "self_attn.v_proj"),
+    ("attn_output", "self_attn.o_proj"),
+    ("attn_q_norm", "self_attn.q_norm"),
+    ("attn_k_norm", "self_attn.k_norm"),
+    ("attn_norm", "input_layernorm"),
+    ("ffn_norm", "post_attention_layernorm"),
+    ("ffn_gate", "mlp.gate_proj"),
+    ("ffn_up", "mlp.up_proj"),
+    ("ffn_down", "mlp.down_proj"),
+];
+
+/// GGUF top-level tensor names and their HuggingFace equivalents.
+const GGUF_GLOBAL_NAMES: &[(&str, &str)] = &[
+    ("token_embd", "model.embed_tokens"),
+    ("output_norm", "model.norm"),
+    ("output", "lm_head"),
+];
+
+/// Map a tensor name to its canonical (HuggingFace-style) form.
+/// GGUF names are translated; anything else passes through unchanged.
+pub fn canonical_name(raw: &str) -> String {
+    let (stem, suffix) = match raw.rsplit_once('.') {
+        Some((stem, suffix)) if suffix == "weight" || suffix == "bias" => (stem, suffix),
+        _ => return raw.to_string(),
+    };
+
+    if let Some((_, hf)) = GGUF_GLOBAL_NAMES.iter().find(|(gguf, _)| *gguf == stem) {
+        return format!("{}.{}", hf, suffix);
+    }
+
+    if let Some(rest) = stem.strip_prefix("blk.") {
+        if let Some((idx, part)) = rest.split_once('.') {
+            if idx.parse::<u64>().is_ok() {
+                if let Some((_, hf)) = GGUF_BLOCK_NAMES.iter().find(|(gguf, _)| *gguf == part) {
+                    return format!("model.layers.{}.{}.{}", idx, hf, suffix);
+                }
+            }
+        }
+    }
+
+    raw.to_string()
+}
+
+/// Find the parent's own name for `name`, matching exactly first and then by canonical form.
+pub fn resolve_name(parent: &ParentModel, name: &str) -> Option<String> {
+    if parent.compat.tensor_metas.iter().any(|t| t.name == name) {
+        return Some(name.to_string());
+    }
+    let wanted = canonical_name(name);
+    parent
+        .compat
+        .tensor_metas
+        .iter()
+        .find(|t| canonical_name(&t.name) == wanted)
+        .map(|t| t.name.clone())
+}
+
+/// The first parent (in registry order) that stores `name` under exactly that name.
+/// Its naming and layout define how the tensor is written to the output.
+pub fn native_parent<'a>(registry: &'a ParentRegistry, name: &str) -> Option<&'a ParentModel> {
+    registry
+        .all()
+        .iter()
+        .find(|p| p.compat.tensor_metas.iter().any(|t| t.name == name))
+}
+
+/// Parent whose layout the output uses for `name`: `preferred` when it stores the
+/// tensor natively, otherwise the first parent that does.
+pub fn layout_parent<'a>(
+    registry: &'a ParentRegistry,
+    name: &str,
+    preferred: &'a ParentModel,
+) -> &'a ParentModel {
+    if preferred.compat.tensor_metas.iter().any(|t| t.name == name) {
+        return preferred;
+    }
+    native_parent(registry, name).unwrap_or(preferred)
+}
+
+/// Normalize an architecture string so GGUF (`llama`) and HF (`LlamaForCausalLM`)
+/// spellings of the same family compare equal.
+pub fn canonical_arch(arch: &str) -> String {
+    let lower = arch.to_lowercase();
+    let base = ["forcausallm", "forconditionalgeneration", "model"]
+        .iter()
+        .find_map(|suffix| lower.strip_suffix(suffix))
+        .unwrap_or(&lower);
+    match base {
+        // llama.cpp converts these under the llama architecture
+        "mistral" => "llama".to_string(),
+        other => other.to_string(),
+    }
+}
+
+/// GGUF shapes are stored innermost-first; the underlying data is laid out the
+/// same as the row-major HF tensor, so converting is a reshape, not a transpose.
+pub fn reversed_shape(shape: &[usize]) -> Vec<usize> {
+    shape.iter().rev().copied().collect()
+}
+
+/// Head count used by llama.cpp's Q/K row permutation, if it applies to this tensor.
+fn rope_permute_heads(parent: &ParentModel, raw_name: &str) -> Option<usize> {
+    if !matches!(parent.format, ModelFormat::Gguf) {
+        return None;
+    }
+    let arch = parent.compat.architecture.as_deref().map(canonical_arch)?;
+    if arch != "llama" {
+        return None;
+    }
+    if raw_name.ends_with("attn_q.weight") {
+        parent.compat.num_attention_heads.map(|h| h as usize)
+    } else if raw_name.ends_with("attn_k.weight") {
+        parent
+            .compat
+            .num_kv_heads
+            .or(parent.compat.num_attention_heads)
+            .map(|h| h as usize)
+    } else {
+        None
+    }
+}
+
+/// Swap the two interleaved halves of each head's rows. `to_gguf` selects
+/// the direction: HF → GGUF (llama.cpp's permute) or GGUF → HF (its inverse).
+fn permute_rope_rows(tensor: &Tensor, n_head: usize, to_gguf: bool) -> Result<Tensor, ModelError> {
+    let map_err = |e: candle_core::Error| ModelError::CandleError(e.to_string());
+    let dims = tensor.dims().to_vec();
+    if dims.len() != 2 || n_head == 0 || dims[0] % (n_head * 2) != 0 {
+        return Ok(tensor.clone());
+    }
+    let half = dims[0] / n_head / 2;
+    let split = if to_gguf {
+        (n_head, 2, half, dims[1])
+    } else {
+        (n_head, half, 2, dims[1])
+    };
+    tensor
+        .reshape(split)
+        .and_then(|t| t.transpose(1, 2))
+        .and_then(|t| t.contiguous())
+        .and_then(|t| t.reshape(dims.as_slice()))
+        .map_err(map_err)
+}
+
+/// Convert a tensor loaded from `parent` under `raw_name` into canonical HF layout.
+pub fn to_canonical(parent: &ParentModel, raw_name: &str, tensor: Tensor) -> Result<Tensor, ModelError> {
+    if !matches!(parent.format, ModelFormat::Gguf) {
+        return Ok(tensor);
+    }
+    let tensor = tensor
+        .reshape(reversed_shape(tensor.dims()))
+        .map_err(|e| ModelError::CandleError(e.to_string()))?;
+    match rope_permute_heads(parent, raw_name) {
+        Some(n_head) => permute_rope_rows(&tensor, n_head, false),
+        None => Ok(tensor),
+    }
+}
+
+/// Convert a canonical HF-layout tensor into `parent`'s native layout for `raw_name`.
+pub fn to_native(parent: &ParentModel, raw_name: &str, tensor: Tensor) -> Result<Tensor, ModelError> {
+    if !matches!(parent.format, ModelFormat::Gguf) {
+        return Ok(tensor);
+    }
+    let tensor = match rope_permute_heads(parent, raw_name) {
+        Some(n_head) => permute_rope_rows(&tensor, n_head, true)?,
+        None => tensor,
+    };
+    tensor
+        .reshape(reversed_shape(tensor.dims()))
+        .map_err(|e| ModelError::CandleError(e.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use candle_core::Device;
+
+    use super::*;
+    use crate::merge::registry::{CompatInfo, TensorMeta};
+
+    fn parent(format: ModelFormat, arch: &str, tensors: &[(&str, Vec<usize>)]) -> ParentModel {
+        ParentModel {
+            id: "p".into(),
+            slot: 0,
+            name: "p".into(),
+            file_path: String::new(),
+            format,
+            file_size: 0,
+            file_size_display: String::new(),
+            parameter_count: 0,
+            parameter_count_display: String::new(),
+            layer_count: None,
+            architecture: None,
+            quantization: None,
+            compat: CompatInfo {
+                architecture: Some(arch.into()),
+                hidden_size: None,
+                num_layers: None,
+                num_attention_heads: Some(2),
+                num_kv_heads: Some(1),
+                vocab_size: None,
+                context_length: None,
+                head_dim: None,
+                rope_theta: None,
+                rms_norm_eps: None,
+                tensor_metas: tensors
+                    .iter()
+                    .map(|(name, shape)| TensorMeta { name: name.to_string(), shape: shape.clone(), dtype: "F32".into() })
+                    .collect(),
+            },
+            color: String::new(),
+            is_dir: false,
+        }
+    }
+
+    fn rows(n: usize, cols: usize) -> Tensor {
+        let data: Vec<f32> = (0..n * cols).map(|i| (i / cols) as f32).collect();
+        Tensor::from_vec(data, (n, cols), &Device::Cpu).unwrap()
+    }
+
+    fn values(t: &Tensor) -> Vec<f32> {
+        t.flatten_all().unwrap().to_vec1::<f32>().unwrap()
+    }
+
+    #[test]
+    fn gguf_names_map_to_hf() {
+        assert_eq!(canonical_name("blk.3.attn_q.weight"), "model.layers.3.self_attn.q_proj.weight");
+        assert_eq!(canonical_name("blk.0.ffn_down.bias"), "model.layers.0.mlp.down_proj.bias");
+        assert_eq!(canonical_name("token_embd.weight"), "model.embed_tokens.weight");
+        assert_eq!(canonical_name("output.weight"), "lm_head.weight");
+        // HF names and unknown GGUF parts pass through
+        assert_eq!(canonical_name("model.norm.weight"), "model.norm.weight");
+        assert_eq!(canonical_name("blk.x.attn_q.weight"), "blk.x.attn_q.weight");
+        assert_eq!(canonical_name("blk.0.rope_freqs"), "blk.0.rope_freqs");
+    }
+
+    #[test]
+    fn names_resolve_across_formats() {
+        let gguf = parent(ModelFormat::Gguf, "llama", &[("blk.0.attn_k.weight", vec![8, 4])]);
+        let hf = parent(ModelFormat::SafeTensors, "LlamaForCausalLM", &[("model.layers.0.self_attn.k_proj.weight", vec![4, 8])]);
+        assert_eq!(resolve_name(&gguf, "model.layers.0.self_attn.k_proj.weight").as_deref(), Some("blk.0.attn_k.weight"));
+        assert_eq!(resolve_name(&hf, "blk.0.attn_k.weight").as_deref(), Some("model.layers.0.self_attn.k_proj.weight"));
+        assert_eq!(resolve_name(&hf, "blk.0.attn_v.weight"), None);
+        assert_eq!(canonical_arch("LlamaForCausalLM"), canonical_arch("llama"));
+        assert_eq!(canonical_arch("MistralForCausalLM"), "llama");
+    }
+
+    #[test]
+    fn gguf_shapes_are_reversed_without_transposing() {
+        let gguf = parent(ModelFormat::Gguf, "llama", &[]);
+        let hf = Tensor::from_vec((0..6).map(|i| i as f32).collect::<Vec<_>>(), (2, 3), &Device::Cpu).unwrap();
+        let native = to_native(&gguf, "blk.0.ffn_up.weight", hf.clone()).unwrap();
+        assert_eq!(native.dims(), [3, 2]);
+        assert_eq!(values(&native), values(&hf));
+        let back = to_canonical(&gguf, "blk.0.ffn_up.weight", native).unwrap();
+        assert_eq!(back.dims(), [2, 3]);
+
+        let st = parent(ModelFormat::SafeTensors, "llama", &[]);
+        assert_eq!(to_native(&st, "w", hf.clone()).unwrap().dims(), [2, 3]);
+    }
+
+    #[test]
+    fn llama_q_k_rows_follow_the_convert_permutation() {
+        let gguf = parent(ModelFormat::Gguf, "llama", &[]);
+        // llama.cpp: reshape(n_head, 2, rows / n_head / 2, cols).swapaxes(1, 2)
+        let q = to_native(&gguf, "blk.0.attn_q.weight", rows(8, 2)).unwrap();
+        let q_rows: Vec<f32> = values(&q).into_iter().step_by(2).collect();
+        assert_eq!(q_rows, [0.0, 2.0, 1.0, 3.0, 4.0, 6.0, 5.0, 7.0]);
+        // K uses the KV head count (1 here)
+        let k = to_native(&gguf, "blk.0.attn_k.weight", rows(8, 2)).unwrap();
+        let k_rows: Vec<f32> = values(&k).into_iter().step_by(2).collect();
+        assert_eq!(k_rows, [0.0, 4.0, 1.0, 5.0, 2.0, 6.0, 3.0, 7.0]);
+
+        for name in ["blk.0.attn_q.weight", "blk.0.attn_k.weight"] {
+            let back = to_canonical(&gguf, name, to_native(&gguf, name, rows(8, 2)).unwrap()).unwrap();
+            assert_eq!(values(&back), values(&rows(8, 2)));
+        }
+
+        // V isn't permuted, and neither is Q outside the llama family
+        let v = to_native(&gguf, "blk.0.attn_v.weight", rows(8, 2)).unwrap();
+        assert_eq!(values(&v), values(&rows(8, 2)));
+        let qwen = parent(ModelFormat::Gguf, "qwen2", &[]);
+        let q = to_native(&qwen, "blk.0.attn_q.weight", rows(8, 2)).unwrap();
+        assert_eq!(values(&q), values(&rows(8, 2)));
+    }
+}
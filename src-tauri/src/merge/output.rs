@@ -4,83 +4,102 @@ use std::path::Path;
 
 use candle_core::{DType, Tensor};
 
+use crate::merge::config::{GgufQuantType, OutputDtype};
+use crate::merge::gguf_quant::{self, GgmlType};
 use crate::merge::registry::CompatInfo;
+use crate::merge::tensor_io::{checked_range, offset_to_usize};
 use crate::model::error::ModelError;
 
-/// Write merged tensors to a SafeTensors file.
+/// Flatten a tensor into little-endian bytes of the requested output dtype.
+fn tensor_to_bytes(tensor: &Tensor, dtype: OutputDtype) -> Result<Vec<u8>, ModelError> {
+    let map_err = |e: candle_core::Error| ModelError::CandleError(e.to_string());
+    let flat = tensor.flatten_all().map_err(map_err)?;
+
+    let bytes = match dtype {
+        OutputDtype::F32 => flat
+            .to_dtype(DType::F32).map_err(map_err)?
+            .to_vec1::<f32>().map_err(map_err)?
+            .iter()
+            .flat_map(|v| v.to_le_bytes())
+            .collect(),
+        OutputDtype::F16 => flat
+            .to_dtype(DType::F16).map_err(map_err)?
+            .to_vec1::<half::f16>().map_err(map_err)?
+            .iter()
+            .flat_map(|v| v.to_le_bytes())
+            .collect(),
+        OutputDtype::Bf16 => flat
+            .to_dtype(DType::BF16).map_err(map_err)?
+            .to_vec1::<half::bf16>().map_err(map_err)?
+            .iter()
+            .flat_map(|v| v.to_le_bytes())
+            .collect(),
+    };
+    Ok(bytes)
+}
+
+/// Write merged tensors to a SafeTensors file in the given dtype.
 pub fn write_safetensors(
     output_path: &str,
     tensors: &[(String, Tensor)],
+    dtype: OutputDtype,
 ) -> Result<(), ModelError> {
-    let map_err = |e: candle_core::Error| ModelError::CandleError(e.to_string());
-
-    // Build the safetensors data structure
-    // Header format: { "tensor_name": { "dtype": "F32", "shape": [...], "data_offsets": [start, end] }, ... }
-    let mut tensor_data: Vec<(&str, Vec<u8>, &str, Vec<usize>)> = Vec::new();
-    let _current_offset: usize = 0;
-
-    for (name, tensor) in tensors {
-        let tensor_f32 = tensor.to_dtype(DType::F32).map_err(map_err)?;
-        let flat: Vec<f32> = tensor_f32.flatten_all().map_err(map_err)?
-            .to_vec1::<f32>().map_err(map_err)?;
-
-        let bytes: Vec<u8> = flat.iter().flat_map(|f| f.to_le_bytes()).collect();
-        let shape: Vec<usize> = tensor.shape().dims().to_vec();
-        let dtype = "F32";
-
-        tensor_data.push((name.as_str(), bytes, dtype, shape));
+    let manifest = manifest_for(tensors);
+    let mut writer = StreamingSafeTensorsWriter::create(output_path, &manifest, dtype)?;
+    for (_, tensor) in tensors {
+        writer.write_tensor(tensor)?;
     }
+    writer.finish()
+}
 
-    // Build header JSON
-    let mut header_entries: Vec<String> = Vec::new();
-    let mut data_offset = 0usize;
-
-    for (name, bytes, dtype, shape) in &tensor_data {
-        let end_offset = data_offset + bytes.len();
-        let shape_str = shape
-            .iter()
-            .map(|s| s.to_string())
-            .collect::<Vec<_>>()
-            .join(",");
-
-        header_entries.push(format!(
-            "\"{}\":{{\"dtype\":\"{}\",\"shape\":[{}],\"data_offsets\":[{},{}]}}",
-            name, dtype, shape_str, data_offset, end_offset
-        ));
+/// Output manifest describing in-memory tensors, for the streaming writers.
+fn manifest_for(tensors: &[(String, Tensor)]) -> OutputManifest {
+    let shapes: Vec<Vec<usize>> = tensors.iter().map(|(_, t)| t.dims().to_vec()).collect();
+    precompute::manifest_from_shapes(
+        tensors.iter().zip(&shapes).map(|((name, _), shape)| (name.as_str(), shape.as_slice())),
+    )
+}
 
-        data_offset = end_offset;
-    }
+/// Write a single tensor as a NumPy `.npy` file (v1.0, little-endian F32).
+pub fn write_npy(output_path: &str, tensor: &Tensor) -> Result<(), ModelError> {
+    let map_err = |e: candle_core::Error| ModelError::CandleError(e.to_string());
 
-    // Add metadata
-    header_entries.push(
-        "\"__metadata__\":{\"format\":\"pt\",\"source\":\"forgeai-merge\"}".to_string()
+    let tensor_f32 = tensor.to_dtype(DType::F32).map_err(map_err)?;
+    let flat: Vec<f32> = tensor_f32.flatten_all().map_err(map_err)?
+        .to_vec1::<f32>().map_err(map_err)?;
+
+    let dims = tensor.shape().dims();
+    let shape_str = match dims.len() {
+        1 => format!("({},)", dims[0]),
+        _ => format!(
+            "({})",
+            dims.iter().map(|d| d.to_string()).collect::<Vec<_>>().join(", ")
+        ),
+    };
+    let mut header = format!(
+        "{{'descr': '<f4', 'fortran_order': False, 'shape': {}, }}",
+        shape_str
     );
+    // Pad so magic (6) + version (2) + length (2) + header is a multiple of 64, ending in '\n'
+    let unpadded = 10 + header.len() + 1;
+    header.push_str(&" ".repeat((64 - unpadded % 64) % 64));
+    header.push('\n');
 
-    let header_json = format!("{{{}}}", header_entries.join(","));
-    let header_bytes = header_json.as_bytes();
-    let header_len = header_bytes.len() as u64;
-
-    // Write file
     let file = File::create(output_path).map_err(ModelError::IoError)?;
     let mut writer = BufWriter::new(file);
-
-    // Write header length (8 bytes, little-endian)
-    writer.write_all(&header_len.to_le_bytes()).map_err(ModelError::IoError)?;
-
-    // Write header JSON
-    writer.write_all(header_bytes).map_err(ModelError::IoError)?;
-
-    // Write tensor data
-    for (_, bytes, _, _) in &tensor_data {
-        writer.write_all(bytes).map_err(ModelError::IoError)?;
+    writer.write_all(b"\x93NUMPY").map_err(ModelError::IoError)?;
+    writer.write_all(&[1u8, 0u8]).map_err(ModelError::IoError)?;
+    writer.write_all(&(header.len() as u16).to_le_bytes()).map_err(ModelError::IoError)?;
+    writer.write_all(header.as_bytes()).map_err(ModelError::IoError)?;
+    for v in &flat {
+        writer.write_all(&v.to_le_bytes()).map_err(ModelError::IoError)?;
     }
-
     writer.flush().map_err(ModelError::IoError)?;
 
     Ok(())
 }
 
-/// Write merged tensors to a GGUF file (F32 unquantized).
+/// Write merged tensors to a GGUF file, quantizing 2D weights to `target_type`.
 ///
 /// Metadata source priority:
 /// 1. `source_gguf_path` — copies raw metadata from an existing GGUF file
@@ -94,104 +113,75 @@ pub fn write_gguf(
     source_gguf_path: Option<&str>,
     compat: Option<&CompatInfo>,
     config_json_dir: Option<&str>,
+    target_type: GgufQuantType,
 ) -> Result<(), ModelError> {
-    let map_err = |e: candle_core::Error| ModelError::CandleError(e.to_string());
-
-    // If we have a source GGUF, extract its raw metadata section
-    let source_metadata = source_gguf_path.and_then(|path| {
-        extract_gguf_metadata(path).ok()
-    });
-
-    let file = File::create(output_path).map_err(ModelError::IoError)?;
-    let mut writer = BufWriter::new(file);
-
-    // Track bytes written for alignment
-    let mut bytes_written: usize = 0;
-
-    // GGUF Magic
-    writer.write_all(b"GGUF").map_err(ModelError::IoError)?;
-    bytes_written += 4;
-
-    // Version 3
-    writer.write_all(&3u32.to_le_bytes()).map_err(ModelError::IoError)?;
-    bytes_written += 4;
-
-    // Tensor count
-    writer.write_all(&(tensors.len() as u64).to_le_bytes()).map_err(ModelError::IoError)?;
-    bytes_written += 8;
-
-    if let Some(ref meta) = source_metadata {
-        // Write original metadata KV count + raw bytes
-        writer.write_all(&(meta.kv_count as u64).to_le_bytes()).map_err(ModelError::IoError)?;
-        bytes_written += 8;
-        writer.write_all(&meta.raw_kv_bytes).map_err(ModelError::IoError)?;
-        bytes_written += meta.raw_kv_bytes.len();
-    } else {
-        // Build metadata from CompatInfo + config.json
-        let metadata_kvs = build_gguf_metadata(model_name, compat, config_json_dir);
-
-        writer.write_all(&(metadata_kvs.len() as u64).to_le_bytes()).map_err(ModelError::IoError)?;
-        bytes_written += 8;
-
-        for (key, value) in &metadata_kvs {
-            bytes_written += write_gguf_string(&mut writer, key)?;
-            bytes_written += write_gguf_value(&mut writer, value)?;
-        }
-    }
-
-    // Compute tensor data offsets
-    let mut tensor_infos: Vec<(String, Vec<usize>, u64)> = Vec::new();
-    let mut offset: u64 = 0;
-
-    for (name, tensor) in tensors {
-        let shape: Vec<usize> = tensor.shape().dims().to_vec();
-        let elem_count: usize = shape.iter().product();
-        let byte_size = (elem_count * 4) as u64; // F32
-
-        tensor_infos.push((name.clone(), shape, offset));
-        offset += byte_size;
+    let manifest = manifest_for(tensors);
+    let mut writer = StreamingGgufWriter::new(
+        output_path,
+        &manifest,
+        model_name,
+        source_gguf_path,
+        compat,
+        config_json_dir,
+        false,
+        target_type,
+        None,
+    )?;
+    for (_, tensor) in tensors {
+        writer.write_tensor(tensor)?;
     }
+    writer.finish()
+}
 
-    // Write tensor info entries
-    for (name, shape, data_offset) in &tensor_infos {
-        bytes_written += write_gguf_string(&mut writer, name)?;
+/// Write GGUF tensor info entries with each tensor's type and a data offset
+/// aligned to [`gguf_quant::GGUF_ALIGNMENT`]. Returns the bytes written.
+fn write_gguf_tensor_infos<W: Write>(
+    writer: &mut W,
+    tensors: &[(&str, &[usize])],
+    types: &[GgmlType],
+) -> Result<u64, ModelError> {
+    let mut bytes_written = 0u64;
+    let mut offset = 0u64;
+    for (&(name, shape), ty) in tensors.iter().zip(types) {
+        bytes_written += write_gguf_string(writer, name)?;
         writer.write_all(&(shape.len() as u32).to_le_bytes()).map_err(ModelError::IoError)?;
         bytes_written += 4;
         for &dim in shape {
             writer.write_all(&(dim as u64).to_le_bytes()).map_err(ModelError::IoError)?;
             bytes_written += 8;
         }
-        writer.write_all(&0u32.to_le_bytes()).map_err(ModelError::IoError)?; // ggml_type F32
+        writer.write_all(&ty.id().to_le_bytes()).map_err(ModelError::IoError)?;
         bytes_written += 4;
-        writer.write_all(&data_offset.to_le_bytes()).map_err(ModelError::IoError)?;
+        writer.write_all(&offset.to_le_bytes()).map_err(ModelError::IoError)?;
         bytes_written += 8;
-    }
-
-    // Align to 32 bytes
-    let alignment = 32;
-    let padding = (alignment - (bytes_written % alignment)) % alignment;
-    for _ in 0..padding {
-        writer.write_all(&[0u8]).map_err(ModelError::IoError)?;
-    }
-
-    // Write tensor data
-    for (_, tensor) in tensors {
-        let tensor_f32 = tensor.to_dtype(DType::F32).map_err(map_err)?;
-        let flat: Vec<f32> = tensor_f32.flatten_all().map_err(map_err)?
-            .to_vec1::<f32>().map_err(map_err)?;
 
-        let bytes: Vec<u8> = flat.iter().flat_map(|f| f.to_le_bytes()).collect();
-        writer.write_all(&bytes).map_err(ModelError::IoError)?;
+        let elem_count: usize = shape.iter().product();
+        offset = gguf_quant::align_offset(offset + ty.byte_size(elem_count));
     }
+    Ok(bytes_written)
+}
 
-    writer.flush().map_err(ModelError::IoError)?;
+/// Zero-pad so that `written` bytes end on the GGUF alignment boundary.
+fn write_gguf_padding<W: Write>(writer: &mut W, written: u64) -> Result<(), ModelError> {
+    let padding = gguf_quant::align_offset(written) - written;
+    writer.write_all(&vec![0u8; padding as usize]).map_err(ModelError::IoError)
+}
 
-    Ok(())
+fn encode_gguf_tensor(tensor: &Tensor, ty: GgmlType) -> Result<Vec<u8>, ModelError> {
+    let map_err = |e: candle_core::Error| ModelError::CandleError(e.to_string());
+    let flat: Vec<f32> = tensor.to_dtype(DType::F32).map_err(map_err)?
+        .flatten_all().map_err(map_err)?
+        .to_vec1::<f32>().map_err(map_err)?;
+    Ok(gguf_quant::encode(&flat, ty))
 }
 
 struct GgufSourceMetadata {
     kv_count: usize,
     raw_kv_bytes: Vec<u8>,
+    /// Offset of the `*.block_count` UINT32 value within `raw_kv_bytes`.
+    block_count_offset: Option<usize>,
+    /// Offset of the `general.file_type` UINT32 value within `raw_kv_bytes`.
+    file_type_offset: Option<usize>,
 }
 
 /// Extract raw metadata KV bytes from a GGUF file.
@@ -210,21 +200,33 @@ fn extract_gguf_metadata(path: &str) -> Result<GgufSourceMetadata, ModelError> {
 
     let _version = u32::from_le_bytes(mmap[4..8].try_into().unwrap());
     let _tensor_count = u64::from_le_bytes(mmap[8..16].try_into().unwrap());
-    let kv_count = u64::from_le_bytes(mmap[16..24].try_into().unwrap()) as usize;
+    let kv_count_raw = u64::from_le_bytes(mmap[16..24].try_into().unwrap());
+    let kv_count = offset_to_usize(kv_count_raw, "GGUF", "Metadata KV count")?;
 
     // Parse through metadata KVs to find where they end
     let mut pos = 24usize;
+    let mut block_count_offset = None;
+    let mut file_type_offset = None;
 
     for _ in 0..kv_count {
         // Read key string
         if pos + 8 > mmap.len() { break; }
-        let key_len = u64::from_le_bytes(mmap[pos..pos+8].try_into().unwrap()) as usize;
-        pos += 8 + key_len;
+        let key_len = u64::from_le_bytes(mmap[pos..pos+8].try_into().unwrap());
+        let key_range = checked_range(pos as u64 + 8, key_len, mmap.len(), "GGUF")?;
+        let is_block_count = mmap[key_range.clone()].ends_with(b".block_count");
+        let is_file_type = &mmap[key_range.clone()] == b"general.file_type";
+        pos = key_range.end;
 
         // Read value type
         if pos + 4 > mmap.len() { break; }
         let vtype = u32::from_le_bytes(mmap[pos..pos+4].try_into().unwrap());
         pos += 4;
+        if is_block_count && vtype == 4 {
+            block_count_offset = Some(pos - 24);
+        }
+        if is_file_type && vtype == 4 {
+            file_type_offset = Some(pos - 24);
+        }
 
         // Skip value based on type
         pos = skip_gguf_value(&mmap, pos, vtype)?;
@@ -236,38 +238,43 @@ fn extract_gguf_metadata(path: &str) -> Result<GgufSourceMetadata, ModelError> {
     Ok(GgufSourceMetadata {
         kv_count,
         raw_kv_bytes,
+        block_count_offset,
+        file_type_offset,
     })
 }
 
 /// Skip a GGUF metadata value and return the new position.
 fn skip_gguf_value(data: &[u8], mut pos: usize, vtype: u32) -> Result<usize, ModelError> {
+    let skip = |pos: usize, len: u64| -> Result<usize, ModelError> {
+        Ok(checked_range(pos as u64, len, data.len(), "GGUF")?.end)
+    };
     match vtype {
-        0 => { pos += 1; }  // UINT8
-        1 => { pos += 1; }  // INT8
-        2 => { pos += 2; }  // UINT16
-        3 => { pos += 2; }  // INT16
-        4 => { pos += 4; }  // UINT32
-        5 => { pos += 4; }  // INT32
-        6 => { pos += 4; }  // FLOAT32
-        7 => { pos += 1; }  // BOOL
+        0 => { pos = skip(pos, 1)?; }  // UINT8
+        1 => { pos = skip(pos, 1)?; }  // INT8
+        2 => { pos = skip(pos, 2)?; }  // UINT16
+        3 => { pos = skip(pos, 2)?; }  // INT16
+        4 => { pos = skip(pos, 4)?; }  // UINT32
+        5 => { pos = skip(pos, 4)?; }  // INT32
+        6 => { pos = skip(pos, 4)?; }  // FLOAT32
+        7 => { pos = skip(pos, 1)?; }  // BOOL
         8 => {              // STRING
             if pos + 8 > data.len() { return Ok(pos); }
-            let len = u64::from_le_bytes(data[pos..pos+8].try_into().unwrap()) as usize;
-            pos += 8 + len;
+            let len = u64::from_le_bytes(data[pos..pos+8].try_into().unwrap());
+            pos = skip(pos + 8, len)?;
         }
         9 => {              // ARRAY
             if pos + 12 > data.len() { return Ok(pos); }
             let arr_type = u32::from_le_bytes(data[pos..pos+4].try_into().unwrap());
-            let arr_len = u64::from_le_bytes(data[pos+4..pos+12].try_into().unwrap()) as usize;
+            let arr_len = u64::from_le_bytes(data[pos+4..pos+12].try_into().unwrap());
             pos += 12;
             for _ in 0..arr_len {
                 pos = skip_gguf_value(data, pos, arr_type)?;
             }
         }
-        10 => { pos += 8; } // UINT64
-        11 => { pos += 8; } // INT64
-        12 => { pos += 8; } // FLOAT64
-        _ => { pos += 4; }  // Unknown, skip 4
+        10 => { pos = skip(pos, 8)?; } // UINT64
+        11 => { pos = skip(pos, 8)?; } // INT64
+        12 => { pos = skip(pos, 8)?; } // FLOAT64
+        _ => { pos = skip(pos, 4)?; }  // Unknown, skip 4
     }
     Ok(pos)
 }
@@ -281,15 +288,15 @@ enum GgufMetaValue {
     I32Array(Vec<i32>),
 }
 
-fn write_gguf_string<W: Write>(writer: &mut W, s: &str) -> Result<usize, ModelError> {
+fn write_gguf_string<W: Write>(writer: &mut W, s: &str) -> Result<u64, ModelError> {
     let bytes = s.as_bytes();
     writer.write_all(&(bytes.len() as u64).to_le_bytes()).map_err(ModelError::IoError)?;
     writer.write_all(bytes).map_err(ModelError::IoError)?;
-    Ok(8 + bytes.len())
+    Ok(8 + bytes.len() as u64)
 }
 
-fn write_gguf_value<W: Write>(writer: &mut W, value: &GgufMetaValue) -> Result<usize, ModelError> {
-    let mut written = 0usize;
+fn write_gguf_value<W: Write>(writer: &mut W, value: &GgufMetaValue) -> Result<u64, ModelError> {
+    let mut written = 0u64;
     match value {
         GgufMetaValue::String(s) => {
             // Type tag: 8 = GGUF_TYPE_STRING
@@ -358,11 +365,15 @@ fn write_gguf_value<W: Write>(writer: &mut W, value: &GgufMetaValue) -> Result<u
 /// Build comprehensive GGUF metadata from CompatInfo + config.json.
 /// This handles the safetensors → GGUF conversion case where there's
 /// no source GGUF to copy metadata from.
+///
+/// Returns the KVs plus the number of tokenizer vocab gaps that were filled.
 fn build_gguf_metadata(
     model_name: &str,
     compat: Option<&CompatInfo>,
     config_json_dir: Option<&str>,
-) -> Vec<(String, GgufMetaValue)> {
+    repair_tokenizer_gaps: bool,
+    file_type: u32,
+) -> (Vec<(String, GgufMetaValue)>, usize) {
     // Try to read config.json for extra fields not in CompatInfo
     let config = config_json_dir.and_then(|dir| {
         let path = Path::new(dir).join("config.json");
@@ -392,7 +403,7 @@ fn build_gguf_metadata(
     // Required general keys
     kvs.push(("general.architecture".into(), GgufMetaValue::String(arch_key.clone())));
     kvs.push(("general.name".into(), GgufMetaValue::String(model_name.into())));
-    kvs.push(("general.file_type".into(), GgufMetaValue::U32(0))); // 0 = F32
+    kvs.push(("general.file_type".into(), GgufMetaValue::U32(file_type)));
 
     // Architecture-specific keys from CompatInfo
     if let Some(c) = compat {
@@ -447,17 +458,23 @@ fn build_gguf_metadata(
     }
 
     // Embed tokenizer data from tokenizer.json
+    let mut tokenizer_gaps = 0;
     if let Some(dir) = config_json_dir {
-        if let Some(tok_kvs) = parse_tokenizer_for_gguf(dir) {
+        if let Some((tok_kvs, gaps)) = parse_tokenizer_for_gguf(dir, repair_tokenizer_gaps) {
             kvs.extend(tok_kvs);
+            tokenizer_gaps = gaps;
         }
     }
 
-    kvs
+    (kvs, tokenizer_gaps)
 }
 
 /// Parse tokenizer.json + tokenizer_config.json and produce GGUF tokenizer metadata.
-fn parse_tokenizer_for_gguf(dir: &str) -> Option<Vec<(String, GgufMetaValue)>> {
+/// Also returns how many ids between 0 and the max token id had no token.
+fn parse_tokenizer_for_gguf(
+    dir: &str,
+    repair_gaps: bool,
+) -> Option<(Vec<(String, GgufMetaValue)>, usize)> {
     let dir_path = Path::new(dir);
 
     // Read tokenizer.json
@@ -515,21 +532,35 @@ fn parse_tokenizer_for_gguf(dir: &str) -> Option<Vec<(String, GgufMetaValue)>> {
 
     let vocab_size = token_list.last().map(|(id, _, _)| *id + 1).unwrap_or(0) as usize;
 
-    // Build dense arrays (fill gaps with empty tokens)
+    // Build dense arrays (fill gaps with empty tokens, or placeholders when repairing)
     let mut tokens: Vec<String> = vec![String::new(); vocab_size];
     let mut scores: Vec<f32> = vec![0.0; vocab_size];
     let mut token_types: Vec<i32> = vec![1; vocab_size]; // 1 = normal
+    let mut filled = vec![false; vocab_size];
 
     for (id, token, special) in &token_list {
         let idx = *id as usize;
         if idx < vocab_size {
             tokens[idx] = token.clone();
+            filled[idx] = true;
             if *special {
                 token_types[idx] = 3; // 3 = control
             }
         }
     }
 
+    let mut gaps = 0usize;
+    for idx in 0..vocab_size {
+        if filled[idx] {
+            continue;
+        }
+        gaps += 1;
+        if repair_gaps {
+            tokens[idx] = format!("<unused{}>", idx);
+            token_types[idx] = 5; // 5 = unused
+        }
+    }
+
     // Detect BOS/EOS token IDs
     let bos_token_id = tok_config.as_ref()
         .and_then(|c| {
@@ -574,71 +605,232 @@ fn parse_tokenizer_for_gguf(dir: &str) -> Option<Vec<(String, GgufMetaValue)>> {
         }
     }
 
-    Some(kvs)
+    Some((kvs, gaps))
 }
 
 // ── Streaming Writers ────────────────────────────────────────
 
-use super::precompute::OutputManifest;
+use super::precompute::{self, OutputManifest};
+
+/// One output file of a (possibly sharded) SafeTensors write.
+struct SafeTensorsShard {
+    path: String,
+    header_json: String,
+    tensor_count: usize,
+}
 
 pub struct StreamingSafeTensorsWriter {
-    writer: BufWriter<File>,
+    shards: Vec<SafeTensorsShard>,
+    current_shard: usize,
+    written_in_shard: usize,
+    writer: Option<BufWriter<File>>,
+    dtype: OutputDtype,
+    /// Written on finish when the output spans more than one file.
+    index_json: Option<(String, String)>,
+    bytes_written: u64,
 }
 
 impl StreamingSafeTensorsWriter {
+    /// Plan the output in `output_dir`: a single `model.safetensors`, or when
+    /// `max_shard_bytes` is set and exceeded, HuggingFace-style
+    /// `model-00001-of-0000N.safetensors` shards plus `model.safetensors.index.json`.
     pub fn new(
-        output_path: &str,
+        output_dir: &str,
+        manifest: &OutputManifest,
+        dtype: OutputDtype,
+        max_shard_bytes: Option<u64>,
+    ) -> Result<Self, ModelError> {
+        // Manifest sizes assume F32; rescale for the output dtype
+        let sizes: Vec<u64> = manifest.tensors.iter()
+            .map(|info| info.f32_byte_size / 4 * dtype.size_in_bytes())
+            .collect();
+
+        // Greedily group tensors (in write order) so each shard stays under the limit;
+        // a single tensor larger than the limit gets a shard to itself.
+        let mut groups: Vec<std::ops::Range<usize>> = Vec::new();
+        let mut start = 0usize;
+        let mut group_bytes = 0u64;
+        for (i, &size) in sizes.iter().enumerate() {
+            if let Some(limit) = max_shard_bytes {
+                if i > start && group_bytes + size > limit {
+                    groups.push(start..i);
+                    start = i;
+                    group_bytes = 0;
+                }
+            }
+            group_bytes += size;
+        }
+        groups.push(start..sizes.len());
+
+        let dir = Path::new(output_dir);
+        let shard_total = groups.len();
+        let files: Vec<(String, std::ops::Range<usize>)> = groups
+            .into_iter()
+            .enumerate()
+            .map(|(shard_idx, range)| {
+                let file_name = if shard_total == 1 {
+                    "model.safetensors".to_string()
+                } else {
+                    format!("model-{:05}-of-{:05}.safetensors", shard_idx + 1, shard_total)
+                };
+                (dir.join(file_name).to_string_lossy().to_string(), range)
+            })
+            .collect();
+        let index_path = (shard_total > 1)
+            .then(|| dir.join("model.safetensors.index.json").to_string_lossy().to_string());
+
+        Self::open(files, index_path, manifest, &sizes, dtype)
+    }
+
+    /// Open a single SafeTensors file at `file_path` for the manifest's tensors.
+    pub fn create(
+        file_path: &str,
         manifest: &OutputManifest,
+        dtype: OutputDtype,
     ) -> Result<Self, ModelError> {
-        let mut header_entries: Vec<String> = Vec::new();
+        let sizes: Vec<u64> = manifest.tensors.iter()
+            .map(|info| info.f32_byte_size / 4 * dtype.size_in_bytes())
+            .collect();
+        Self::open(vec![(file_path.to_string(), 0..sizes.len())], None, manifest, &sizes, dtype)
+    }
+
+    /// Build each file's header from its tensor range and open the first file.
+    fn open(
+        files: Vec<(String, std::ops::Range<usize>)>,
+        index_path: Option<String>,
+        manifest: &OutputManifest,
+        sizes: &[u64],
+        dtype: OutputDtype,
+    ) -> Result<Self, ModelError> {
+        let mut shards = Vec::with_capacity(files.len());
+        let mut weight_map = serde_json::Map::new();
+
+        for (path, range) in files {
+            let file_name = Path::new(&path)
+                .file_name()
+                .map(|n| n.to_string_lossy().to_string())
+                .unwrap_or_default();
+
+            let mut header_entries: Vec<String> = Vec::new();
+            let mut data_offset = 0u64;
+            for i in range.clone() {
+                let info = &manifest.tensors[i];
+                let shape_str = info.shape.iter()
+                    .map(|s| s.to_string())
+                    .collect::<Vec<_>>()
+                    .join(",");
+                let end_offset = data_offset + sizes[i];
+                header_entries.push(format!(
+                    "\"{}\":{{\"dtype\":\"{}\",\"shape\":[{}],\"data_offsets\":[{},{}]}}",
+                    info.name, dtype.safetensors_name(), shape_str, data_offset, end_offset
+                ));
+                data_offset = end_offset;
+                weight_map.insert(info.name.clone(), serde_json::Value::String(file_name.clone()));
+            }
+
+            header_entries.push(
+                "\"__metadata__\":{\"format\":\"pt\",\"source\":\"forgeai-merge\"}".to_string()
+            );
 
-        for info in &manifest.tensors {
-            let shape_str = info.shape.iter()
-                .map(|s| s.to_string())
-                .collect::<Vec<_>>()
-                .join(",");
-            let end_offset = info.data_offset + info.f32_byte_size;
-            header_entries.push(format!(
-                "\"{}\":{{\"dtype\":\"F32\",\"shape\":[{}],\"data_offsets\":[{},{}]}}",
-                info.name, shape_str, info.data_offset, end_offset
-            ));
+            shards.push(SafeTensorsShard {
+                path,
+                header_json: format!("{{{}}}", header_entries.join(",")),
+                tensor_count: range.len(),
+            });
         }
 
-        header_entries.push(
-            "\"__metadata__\":{\"format\":\"pt\",\"source\":\"forgeai-merge\"}".to_string()
-        );
+        let index_json = match index_path {
+            Some(path) => {
+                let index = serde_json::json!({
+                    "metadata": { "total_size": sizes.iter().sum::<u64>() },
+                    "weight_map": weight_map,
+                });
+                let json = serde_json::to_string_pretty(&index)
+                    .map_err(|e| ModelError::MergeError(format!("Failed to build shard index: {}", e)))?;
+                Some((path, json))
+            }
+            None => None,
+        };
 
-        let header_json = format!("{{{}}}", header_entries.join(","));
-        let header_bytes = header_json.as_bytes();
-        let header_len = header_bytes.len() as u64;
+        let mut this = Self {
+            shards,
+            current_shard: 0,
+            written_in_shard: 0,
+            writer: None,
+            dtype,
+            index_json,
+            bytes_written: 0,
+        };
+        this.open_shard(0)?;
+        Ok(this)
+    }
 
-        let file = File::create(output_path).map_err(ModelError::IoError)?;
-        let mut writer = BufWriter::new(file);
+    fn open_shard(&mut self, idx: usize) -> Result<(), ModelError> {
+        if let Some(mut w) = self.writer.take() {
+            w.flush().map_err(ModelError::IoError)?;
+        }
+        let shard = &self.shards[idx];
+        let header_bytes = shard.header_json.as_bytes();
 
-        writer.write_all(&header_len.to_le_bytes()).map_err(ModelError::IoError)?;
+        let file = File::create(&shard.path).map_err(ModelError::IoError)?;
+        let mut writer = BufWriter::new(file);
+        writer.write_all(&(header_bytes.len() as u64).to_le_bytes()).map_err(ModelError::IoError)?;
         writer.write_all(header_bytes).map_err(ModelError::IoError)?;
 
-        Ok(Self { writer })
+        self.writer = Some(writer);
+        self.current_shard = idx;
+        self.written_in_shard = 0;
+        Ok(())
+    }
+
+    /// Tensor data bytes written so far (headers excluded).
+    pub fn bytes_written(&self) -> u64 {
+        self.bytes_written
+    }
+
+    /// Every file this writer creates (shards plus index, if any).
+    pub fn output_files(&self) -> Vec<String> {
+        let mut files: Vec<String> = self.shards.iter().map(|s| s.path.clone()).collect();
+        if let Some((path, _)) = &self.index_json {
+            files.push(path.clone());
+        }
+        files
     }
 
     pub fn write_tensor(&mut self, tensor: &Tensor) -> Result<(), ModelError> {
-        let map_err = |e: candle_core::Error| ModelError::CandleError(e.to_string());
-        let tensor_f32 = tensor.to_dtype(DType::F32).map_err(map_err)?;
-        let flat: Vec<f32> = tensor_f32.flatten_all().map_err(map_err)?
-            .to_vec1::<f32>().map_err(map_err)?;
-        let bytes: Vec<u8> = flat.iter().flat_map(|f| f.to_le_bytes()).collect();
-        self.writer.write_all(&bytes).map_err(ModelError::IoError)?;
+        if self.written_in_shard >= self.shards[self.current_shard].tensor_count
+            && self.current_shard + 1 < self.shards.len()
+        {
+            self.open_shard(self.current_shard + 1)?;
+        }
+        let bytes = tensor_to_bytes(tensor, self.dtype)?;
+        let writer = self.writer.as_mut()
+            .ok_or_else(|| ModelError::MergeError("SafeTensors writer already closed".into()))?;
+        writer.write_all(&bytes).map_err(ModelError::IoError)?;
+        self.written_in_shard += 1;
+        self.bytes_written += bytes.len() as u64;
         Ok(())
     }
 
     pub fn finish(mut self) -> Result<(), ModelError> {
-        self.writer.flush().map_err(ModelError::IoError)?;
+        if let Some(mut w) = self.writer.take() {
+            w.flush().map_err(ModelError::IoError)?;
+        }
+        if let Some((path, json)) = &self.index_json {
+            std::fs::write(path, json).map_err(ModelError::IoError)?;
+        }
         Ok(())
     }
 }
 
 pub struct StreamingGgufWriter {
     writer: BufWriter<File>,
+    pub output_path: String,
+    types: Vec<GgmlType>,
+    next_tensor: usize,
+    bytes_written: u64,
+    /// Tokenizer vocab gaps found while building metadata (0 when copied from a source GGUF).
+    pub tokenizer_gaps: usize,
 }
 
 impl StreamingGgufWriter {
@@ -649,14 +841,29 @@ impl StreamingGgufWriter {
         source_gguf_path: Option<&str>,
         compat: Option<&CompatInfo>,
         config_json_dir: Option<&str>,
+        repair_tokenizer_gaps: bool,
+        target_type: GgufQuantType,
+        block_count: Option<u32>,
     ) -> Result<Self, ModelError> {
-        let source_metadata = source_gguf_path.and_then(|path| {
+        let mut source_metadata = source_gguf_path.and_then(|path| {
             extract_gguf_metadata(path).ok()
         });
+        if let Some(meta) = source_metadata.as_mut() {
+            // Layer slices change the layer count the source metadata declares
+            if let (Some(offset), Some(n)) = (meta.block_count_offset, block_count) {
+                meta.raw_kv_bytes[offset..offset + 4].copy_from_slice(&n.to_le_bytes());
+            }
+            // The source's file type describes its own quantization, not ours
+            if let Some(offset) = meta.file_type_offset {
+                meta.raw_kv_bytes[offset..offset + 4]
+                    .copy_from_slice(&target_type.file_type().to_le_bytes());
+            }
+        }
 
         let file = File::create(output_path).map_err(ModelError::IoError)?;
         let mut writer = BufWriter::new(file);
-        let mut bytes_written: usize = 0;
+        let mut bytes_written: u64 = 0;
+        let mut tokenizer_gaps = 0;
 
         // GGUF Magic + Version
         writer.write_all(b"GGUF").map_err(ModelError::IoError)?;
@@ -675,9 +882,24 @@ impl StreamingGgufWriter {
                 .map_err(ModelError::IoError)?;
             bytes_written += 8;
             writer.write_all(&meta.raw_kv_bytes).map_err(ModelError::IoError)?;
-            bytes_written += meta.raw_kv_bytes.len();
+            bytes_written += meta.raw_kv_bytes.len() as u64;
         } else {
-            let metadata_kvs = build_gguf_metadata(model_name, compat, config_json_dir);
+            let (mut metadata_kvs, gaps) =
+                build_gguf_metadata(
+                model_name,
+                compat,
+                config_json_dir,
+                repair_tokenizer_gaps,
+                target_type.file_type(),
+            );
+            if let Some(n) = block_count {
+                for (key, value) in metadata_kvs.iter_mut() {
+                    if key.ends_with(".block_count") {
+                        *value = GgufMetaValue::U32(n);
+                    }
+                }
+            }
+            tokenizer_gaps = gaps;
             writer.write_all(&(metadata_kvs.len() as u64).to_le_bytes())
                 .map_err(ModelError::IoError)?;
             bytes_written += 8;
@@ -688,42 +910,44 @@ impl StreamingGgufWriter {
         }
 
         // Tensor info entries
-        for info in &manifest.tensors {
-            bytes_written += write_gguf_string(&mut writer, &info.name)?;
-            writer.write_all(&(info.shape.len() as u32).to_le_bytes())
-                .map_err(ModelError::IoError)?;
-            bytes_written += 4;
-            for &dim in &info.shape {
-                writer.write_all(&(dim as u64).to_le_bytes())
-                    .map_err(ModelError::IoError)?;
-                bytes_written += 8;
-            }
-            writer.write_all(&0u32.to_le_bytes()).map_err(ModelError::IoError)?; // F32 type
-            bytes_written += 4;
-            writer.write_all(&info.data_offset.to_le_bytes()).map_err(ModelError::IoError)?;
-            bytes_written += 8;
-        }
-
-        // Align to 32 bytes
-        let alignment = 32;
-        let padding = (alignment - (bytes_written % alignment)) % alignment;
-        for _ in 0..padding {
-            writer.write_all(&[0u8]).map_err(ModelError::IoError)?;
-        }
-
-        Ok(Self { writer })
+        let shapes: Vec<Vec<usize>> = manifest.tensors.iter()
+            .map(|info| gguf_quant::header_shape(&info.shape, source_gguf_path.is_some()))
+            .collect();
+        let named: Vec<(&str, &[usize])> = manifest.tensors.iter()
+            .zip(&shapes)
+            .map(|(info, shape)| (info.name.as_str(), shape.as_slice()))
+            .collect();
+        let types = gguf_quant::plan_tensor_types(target_type, &named);
+        bytes_written += write_gguf_tensor_infos(&mut writer, &named, &types)?;
+        write_gguf_padding(&mut writer, bytes_written)?;
+
+        Ok(Self {
+            writer,
+            output_path: output_path.to_string(),
+            types,
+            next_tensor: 0,
+            bytes_written: 0,
+            tokenizer_gaps,
+        })
     }
 
     pub fn write_tensor(&mut self, tensor: &Tensor) -> Result<(), ModelError> {
-        let map_err = |e: candle_core::Error| ModelError::CandleError(e.to_string());
-        let tensor_f32 = tensor.to_dtype(DType::F32).map_err(map_err)?;
-        let flat: Vec<f32> = tensor_f32.flatten_all().map_err(map_err)?
-            .to_vec1::<f32>().map_err(map_err)?;
-        let bytes: Vec<u8> = flat.iter().flat_map(|f| f.to_le_bytes()).collect();
+        let ty = *self.types.get(self.next_tensor).ok_or_else(|| {
+            ModelError::MergeError("More tensors written than declared in the GGUF header".into())
+        })?;
+        let bytes = encode_gguf_tensor(tensor, ty)?;
         self.writer.write_all(&bytes).map_err(ModelError::IoError)?;
+        write_gguf_padding(&mut self.writer, bytes.len() as u64)?;
+        self.next_tensor += 1;
+        self.bytes_written += bytes.len() as u64;
         Ok(())
     }
 
+    /// Tensor data bytes written so far (header and padding excluded).
+    pub fn bytes_written(&self) -> u64 {
+        self.bytes_written
+    }
+
     pub fn finish(mut self) -> Result<(), ModelError> {
         self.writer.flush().map_err(ModelError::IoError)?;
         Ok(())
@@ -736,6 +960,20 @@ pub enum StreamWriter {
 }
 
 impl StreamWriter {
+    pub fn bytes_written(&self) -> u64 {
+        match self {
+            Self::SafeTensors(w) => w.bytes_written(),
+            Self::Gguf(w) => w.bytes_written(),
+        }
+    }
+
+    pub fn output_files(&self) -> Vec<String> {
+        match self {
+            Self::SafeTensors(w) => w.output_files(),
+            Self::Gguf(w) => vec![w.output_path.clone()],
+        }
+    }
+
     pub fn write_tensor(&mut self, tensor: &Tensor) -> Result<(), ModelError> {
         match self {
             Self::SafeTensors(w) => w.write_tensor(tensor),
@@ -750,3 +988,73 @@ impl StreamWriter {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn copied_metadata_takes_the_target_file_type() {
+        let dir = std::env::temp_dir().join(format!("forgeai-gguf-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let source = dir.join("source.gguf").to_string_lossy().to_string();
+        let output = dir.join("output.gguf").to_string_lossy().to_string();
+        let tensor = Tensor::zeros((2, 32), DType::F32, &candle_core::Device::Cpu).unwrap();
+        let tensors = vec![("blk.0.attn_q.weight".to_string(), tensor)];
+
+        write_gguf(&source, &tensors, "src", None, None, None, GgufQuantType::F32).unwrap();
+        write_gguf(&output, &tensors, "out", Some(&source), None, None, GgufQuantType::Q8_0).unwrap();
+
+        let meta = extract_gguf_metadata(&output).unwrap();
+        let offset = meta.file_type_offset.expect("general.file_type is copied");
+        let file_type = u32::from_le_bytes(meta.raw_kv_bytes[offset..offset + 4].try_into().unwrap());
+        assert_eq!(file_type, GgufQuantType::Q8_0.file_type());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn sharded_safetensors_index_covers_every_tensor() {
+        let dir = std::env::temp_dir().join(format!("forgeai-shards-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let shapes: [(&str, &[usize]); 5] = [
+            ("a", &[16, 16]),
+            ("b", &[8, 8]),
+            ("c", &[32, 32]),
+            ("d", &[32, 8]),
+            ("e", &[4]),
+        ];
+        let manifest = precompute::manifest_from_shapes(shapes.iter().map(|(n, s)| (*n, *s)));
+        let limit = 700;
+
+        let mut writer =
+            StreamingSafeTensorsWriter::new(&dir.to_string_lossy(), &manifest, OutputDtype::F16, Some(limit)).unwrap();
+        for (_, shape) in &shapes {
+            writer.write_tensor(&Tensor::ones(*shape, DType::F32, &candle_core::Device::Cpu).unwrap()).unwrap();
+        }
+        writer.finish().unwrap();
+
+        let index: serde_json::Value =
+            serde_json::from_str(&std::fs::read_to_string(dir.join("model.safetensors.index.json")).unwrap()).unwrap();
+        let weight_map = index["weight_map"].as_object().unwrap();
+        let shard = |name: &str| weight_map[name].as_str().unwrap().to_string();
+        assert_eq!(weight_map.len(), shapes.len());
+        assert_eq!(shard("a"), "model-00001-of-00003.safetensors");
+        assert_eq!(shard("b"), "model-00001-of-00003.safetensors");
+        // Larger than the limit on its own, so it gets a shard to itself
+        assert_eq!(shard("c"), "model-00002-of-00003.safetensors");
+        assert_eq!(shard("e"), "model-00003-of-00003.safetensors");
+        assert_eq!(index["metadata"]["total_size"], 512 + 128 + 2048 + 512 + 8);
+
+        for i in [1, 3] {
+            let path = dir.join(format!("model-{:05}-of-00003.safetensors", i));
+            let bytes = std::fs::read(&path).unwrap();
+            let header_len = u64::from_le_bytes(bytes[..8].try_into().unwrap());
+            assert!(bytes.len() as u64 - 8 - header_len <= limit, "{} over the limit", path.display());
+        }
+
+        let info = crate::model::safetensors::parse_dir(&dir).unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(info.shard_count, Some(3));
+        assert_eq!(info.tensor_count, 5);
+    }
+}
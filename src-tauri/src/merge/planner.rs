@@ -5,8 +5,9 @@ use serde::{Deserialize, Serialize};
 use crate::model::error::ModelError;
 use crate::model::inspect;
 
-use super::config::{ComponentType, MergeConfig, MergeMethod};
-use super::registry::ParentRegistry;
+use super::config::{ComponentType, MergeConfig, MergeMethod, OutputDtype, OutputFormat};
+use super::registry::{ParentModel, ParentRegistry};
+use super::{gguf_quant, names, precompute, vocab};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum TensorOperation {
@@ -15,6 +16,12 @@ pub enum TensorOperation {
         tensor_name: String,
         parent_id: String,
     },
+    /// Copy a layer tensor into a different output layer (layer slices)
+    CopyLayer {
+        tensor_name: String,
+        source_name: String,
+        parent_id: String,
+    },
     /// Merge multiple parent tensors using configured method
     Merge {
         tensor_name: String,
@@ -38,7 +45,6 @@ pub struct TensorMergePlan {
     pub operations: Vec<TensorOperation>,
     pub total_tensors: usize,
     pub method: MergeMethod,
-    pub estimated_output_bytes: u64,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -52,6 +58,16 @@ pub struct MergePreview {
     pub tensor_sources: Vec<TensorSourceInfo>,
 }
 
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OutputEstimate {
+    pub tensor_count: usize,
+    pub total_params: u64,
+    /// Storage type the estimate assumes, e.g. "BF16" or "Q4_K_M".
+    pub dtype: String,
+    pub estimated_bytes: u64,
+    pub estimated_display: String,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct TensorSourceInfo {
     pub tensor_name: String,
@@ -92,6 +108,10 @@ pub fn build_plan(
         return Err(ModelError::MergeError("No parents loaded".to_string()));
     }
 
+    if !config.slices.is_empty() {
+        return build_sliced_plan(config, registry);
+    }
+
     // For methods that just copy layers (Frankenmerge, Passthrough)
     let is_layer_copy = matches!(config.method, MergeMethod::Frankenmerge | MergeMethod::Passthrough);
 
@@ -128,7 +148,7 @@ pub fn build_plan(
                     && idx <= co.layer_end
                     && match (&co.component, component) {
                         (ComponentType::Attention, "attention") => true,
-                        (ComponentType::Mlp, "mlp") => true,
+                        (ComponentType::Mlp, "mlp" | "expert") => true,
                         (ComponentType::Norm, "norm") => true,
                         _ => false,
                     }
@@ -223,17 +243,325 @@ pub fn build_plan(
 
     let total_tensors = operations.len();
 
-    // Rough size estimate: sum of first parent's file size
-    let estimated_output_bytes = primary_parent.file_size;
+    Ok(TensorMergePlan {
+        operations,
+        total_tensors,
+        method: config.method,
+    })
+}
+
+/// `*` matches any run of characters and `?` any single character.
+fn glob_match(pattern: &str, text: &str) -> bool {
+    let (p, t): (Vec<char>, Vec<char>) = (pattern.chars().collect(), text.chars().collect());
+    let (mut pi, mut ti) = (0, 0);
+    let mut backtrack: Option<(usize, usize)> = None;
+    while ti < t.len() {
+        if pi < p.len() && (p[pi] == '?' || p[pi] == t[ti]) {
+            pi += 1;
+            ti += 1;
+        } else if pi < p.len() && p[pi] == '*' {
+            backtrack = Some((pi, ti));
+            pi += 1;
+        } else if let Some((star, matched)) = backtrack {
+            // Let the last `*` swallow one more character and retry
+            pi = star + 1;
+            ti = matched + 1;
+            backtrack = Some((star, matched + 1));
+        } else {
+            return false;
+        }
+    }
+    p[pi..].iter().all(|&c| c == '*')
+}
+
+/// How strongly an override key matches a tensor, or None. Exact names rank
+/// above globs (the longer pattern wins), which rank above component names.
+fn override_rank(key: &str, name: &str, canonical: &str, component: &str) -> Option<(u8, usize)> {
+    if key == name || key == canonical {
+        Some((2, key.len()))
+    } else if key.contains(['*', '?']) {
+        (glob_match(key, name) || glob_match(key, canonical)).then_some((1, key.len()))
+    } else {
+        (key == component).then_some((0, 0))
+    }
+}
+
+/// Per-parent weights from `config.weight_overrides` for `tensor_name`, if any key
+/// matches its native or canonical name. Precedence: exact name > glob > component
+/// > the default parent weights. Equal-ranked keys resolve alphabetically.
+pub fn weight_override<'a>(config: &'a MergeConfig, tensor_name: &str) -> Option<&'a [f64]> {
+    let canonical = names::canonical_name(tensor_name);
+    let component = inspect::classify_tensor(tensor_name);
+    config
+        .weight_overrides
+        .iter()
+        .filter_map(|(key, weights)| {
+            override_rank(key, tensor_name, &canonical, component).map(|rank| (rank, key, weights))
+        })
+        .max_by(|a, b| a.0.cmp(&b.0).then_with(|| b.1.cmp(a.1)))
+        .map(|(_, _, weights)| weights.as_slice())
+}
+
+/// Problems with `config.weight_overrides`: wrong weight counts are errors,
+/// keys that match no tensor of the first parent are warnings.
+pub fn check_weight_overrides(config: &MergeConfig, registry: &ParentRegistry) -> (Vec<String>, Vec<String>) {
+    let mut errors = Vec::new();
+    let mut warnings = Vec::new();
+    let tensor_names: Vec<String> = registry.all().first().map(|p| p.compat.tensor_names()).unwrap_or_default();
+
+    let mut keys: Vec<&String> = config.weight_overrides.keys().collect();
+    keys.sort();
+    for key in keys {
+        let weights = &config.weight_overrides[key];
+        if weights.len() != config.parents.len() {
+            errors.push(format!(
+                "Weight override '{}' has {} weights for {} parents",
+                key,
+                weights.len(),
+                config.parents.len()
+            ));
+        } else if weights.iter().any(|w| !w.is_finite()) {
+            errors.push(format!("Weight override '{}' contains a non-finite weight", key));
+        }
+        let matches_any = tensor_names.iter().any(|name| {
+            override_rank(key, name, &names::canonical_name(name), inspect::classify_tensor(name)).is_some()
+        });
+        if !matches_any {
+            warnings.push(format!("Weight override '{}' matches no tensor", key));
+        }
+    }
+    (errors, warnings)
+}
+
+/// Layers in `parent`, from its metadata or else its tensor names.
+fn parent_layer_count(parent: &ParentModel) -> u64 {
+    parent.layer_count.unwrap_or_else(|| {
+        parent.compat.tensor_metas.iter()
+            .filter_map(|t| inspect::extract_layer_index(&t.name))
+            .max()
+            .map_or(0, |i| i + 1)
+    })
+}
+
+/// Layers in the output when `config.slices` is set.
+pub fn sliced_layer_count(config: &MergeConfig) -> u64 {
+    config.slices.iter()
+        .map(|s| (s.layer_end + 1).saturating_sub(s.layer_start))
+        .sum()
+}
+
+/// Problems with `config.slices`: unknown parents, empty or out-of-range
+/// layer ranges, and parents whose layers can't be stacked together.
+pub fn check_slices(config: &MergeConfig, registry: &ParentRegistry) -> Vec<String> {
+    let mut errors = Vec::new();
+    if config.slices.is_empty() {
+        return errors;
+    }
+
+    if !matches!(config.method, MergeMethod::Frankenmerge | MergeMethod::Passthrough) {
+        errors.push(format!(
+            "Layer slices need FRANKENMERGE or PASSTHROUGH, not {}",
+            config.method.display_name()
+        ));
+    }
+
+    let mut formats = Vec::new();
+    for (i, slice) in config.slices.iter().enumerate() {
+        let Some(parent) = registry.get(&slice.parent_id) else {
+            errors.push(format!("Slice {}: parent '{}' not found", i + 1, slice.parent_id));
+            continue;
+        };
+        let layers = parent_layer_count(parent);
+        if slice.layer_start > slice.layer_end {
+            errors.push(format!(
+                "Slice {}: layer range {}-{} is empty",
+                i + 1, slice.layer_start, slice.layer_end
+            ));
+        } else if slice.layer_end >= layers {
+            errors.push(format!(
+                "Slice {}: layer {} is out of range, {} has {} layers",
+                i + 1, slice.layer_end, parent.name, layers
+            ));
+        }
+        formats.push(parent.format.clone());
+    }
+
+    if formats.windows(2).any(|w| w[0] != w[1]) {
+        errors.push("Layer slices can't mix GGUF and SafeTensors parents".to_string());
+    }
+    if !registry.all_same_hidden_dim() {
+        errors.push("Layer slices need parents with the same hidden size".to_string());
+    }
+
+    errors
+}
+
+/// Plan for `config.slices`: non-layer tensors from the base (or first) parent,
+/// with the slices' layers stacked in order where its layers were and
+/// renumbered into one contiguous range.
+fn build_sliced_plan(
+    config: &MergeConfig,
+    registry: &ParentRegistry,
+) -> Result<TensorMergePlan, ModelError> {
+    let errors = check_slices(config, registry);
+    if !errors.is_empty() {
+        return Err(ModelError::MergeError(errors.join("; ")));
+    }
+
+    let layout = config.base_parent_id.as_deref()
+        .and_then(|id| registry.get(id))
+        .unwrap_or(&registry.all()[0]);
+
+    let tensor_override_map: HashMap<&str, &str> = config
+        .tensor_overrides
+        .iter()
+        .map(|to| (to.tensor_name.as_str(), to.parent_id.as_str()))
+        .collect();
+
+    let mut layer_ops = Vec::new();
+    let mut out_layer = 0u64;
+    for slice in &config.slices {
+        let parent = registry.get(&slice.parent_id)
+            .ok_or_else(|| ModelError::ParentNotFound(slice.parent_id.clone()))?;
+        let names = parent.compat.tensor_names();
+        for src_layer in slice.layer_start..=slice.layer_end {
+            for name in names.iter().filter(|n| inspect::extract_layer_index(n) == Some(src_layer)) {
+                let out_name = inspect::replace_layer_index(name, out_layer)
+                    .unwrap_or_else(|| name.clone());
+                layer_ops.push(if out_name == *name {
+                    TensorOperation::Copy {
+                        tensor_name: out_name,
+                        parent_id: parent.id.clone(),
+                    }
+                } else {
+                    TensorOperation::CopyLayer {
+                        tensor_name: out_name,
+                        source_name: name.clone(),
+                        parent_id: parent.id.clone(),
+                    }
+                });
+            }
+            out_layer += 1;
+        }
+    }
+
+    let mut operations = Vec::new();
+    let mut pending_layers = Some(layer_ops);
+    for tensor_name in layout.compat.tensor_names() {
+        if inspect::extract_layer_index(&tensor_name).is_some() {
+            if let Some(ops) = pending_layers.take() {
+                operations.extend(ops);
+            }
+            continue;
+        }
+        let parent_id = tensor_override_map.get(tensor_name.as_str())
+            .map(|id| id.to_string())
+            .unwrap_or_else(|| layout.id.clone());
+        operations.push(TensorOperation::Copy { tensor_name, parent_id });
+    }
+    if let Some(ops) = pending_layers {
+        operations.extend(ops);
+    }
+
+    operations.push(TensorOperation::CopyMetadata {
+        parent_id: layout.id.clone(),
+    });
+
+    let total_tensors = operations.len();
 
     Ok(TensorMergePlan {
         operations,
         total_tensors,
         method: config.method,
-        estimated_output_bytes,
     })
 }
 
+/// SafeTensors element type: the configured one, else inferred from the parents.
+pub fn resolve_output_dtype(config: &MergeConfig, registry: &ParentRegistry) -> OutputDtype {
+    config.output.dtype.unwrap_or_else(|| {
+        OutputDtype::infer(
+            registry.all().iter()
+                .flat_map(|p| p.compat.tensor_metas.iter().map(|t| t.dtype.as_str())),
+        )
+    })
+}
+
+/// Parent whose metadata and tokenizer the output carries: the parent the
+/// vocab was aligned to, else the base parent, else the first parent.
+pub fn metadata_parent<'a>(
+    config: &MergeConfig,
+    registry: &'a ParentRegistry,
+    alignment: Option<&vocab::VocabAlignment>,
+) -> Option<&'a ParentModel> {
+    alignment
+        .map(|v| v.tokenizer_parent_id.as_str())
+        .or(config.base_parent_id.as_deref())
+        .and_then(|id| registry.get(id))
+        .or_else(|| registry.all().first())
+}
+
+/// Tensor data size of the merged model, from the shapes the merge will write:
+/// layers dropped or stacked by the plan, vocab alignment, and the output
+/// dtype or GGUF quant mix all count. File headers and metadata are not included.
+pub fn estimate_output(
+    config: &MergeConfig,
+    registry: &ParentRegistry,
+    plan: &TensorMergePlan,
+) -> Result<OutputEstimate, ModelError> {
+    let alignment = vocab::plan_alignment(
+        registry,
+        config.vocab_strategy,
+        config.base_parent_id.as_deref(),
+    );
+    let manifest = precompute::build_output_manifest(&plan.operations, registry, alignment.as_ref())?;
+    Ok(estimate_from_manifest(config, registry, &manifest, alignment.as_ref()))
+}
+
+/// [`estimate_output`] for an already-built manifest and its vocab alignment.
+pub fn estimate_from_manifest(
+    config: &MergeConfig,
+    registry: &ParentRegistry,
+    manifest: &precompute::OutputManifest,
+    alignment: Option<&vocab::VocabAlignment>,
+) -> OutputEstimate {
+    let total_params: u64 = manifest.tensors.iter()
+        .map(|t| t.shape.iter().map(|&d| d as u64).product::<u64>())
+        .sum();
+
+    let (dtype, estimated_bytes) = match config.output.format {
+        OutputFormat::SafeTensors => {
+            let dtype = resolve_output_dtype(config, registry);
+            (dtype.safetensors_name().to_string(), total_params * dtype.size_in_bytes())
+        }
+        OutputFormat::Gguf => {
+            let from_gguf = metadata_parent(config, registry, alignment)
+                .is_some_and(|p| matches!(p.format, crate::model::ModelFormat::Gguf));
+            let shapes: Vec<Vec<usize>> = manifest.tensors.iter()
+                .map(|t| gguf_quant::header_shape(&t.shape, from_gguf))
+                .collect();
+            let named: Vec<(&str, &[usize])> = manifest.tensors.iter()
+                .zip(&shapes)
+                .map(|(t, shape)| (t.name.as_str(), shape.as_slice()))
+                .collect();
+            let types = gguf_quant::plan_tensor_types(config.output.target_type, &named);
+            let bytes = named.iter().zip(&types)
+                .map(|((_, shape), ty)| {
+                    gguf_quant::align_offset(ty.byte_size(shape.iter().product()))
+                })
+                .sum();
+            (config.output.target_type.name().to_string(), bytes)
+        }
+    };
+
+    OutputEstimate {
+        tensor_count: manifest.tensors.len(),
+        total_params,
+        dtype,
+        estimated_bytes,
+        estimated_display: crate::model::format_file_size(estimated_bytes),
+    }
+}
+
 pub fn preview_plan(
     config: &MergeConfig,
     registry: &ParentRegistry,
@@ -255,6 +583,14 @@ pub fn preview_plan(
                     source_parents: vec![parent_id.clone()],
                 });
             }
+            TensorOperation::CopyLayer { tensor_name, parent_id, .. } => {
+                copy_ops += 1;
+                tensor_sources.push(TensorSourceInfo {
+                    tensor_name: tensor_name.clone(),
+                    operation: "copy".to_string(),
+                    source_parents: vec![parent_id.clone()],
+                });
+            }
             TensorOperation::Merge { tensor_name, parent_ids, .. } => {
                 merge_ops += 1;
                 tensor_sources.push(TensorSourceInfo {
@@ -275,7 +611,10 @@ pub fn preview_plan(
         }
     }
 
-    let estimated = plan.estimated_output_bytes;
+    // Plans whose shapes can't be resolved yet fall back to the first parent's size
+    let estimated = estimate_output(config, registry, &plan)
+        .map(|e| e.estimated_bytes)
+        .unwrap_or_else(|_| registry.all().first().map(|p| p.file_size).unwrap_or(0));
 
     Ok(MergePreview {
         total_operations: plan.total_tensors,
@@ -287,3 +626,68 @@ pub fn preview_plan(
         tensor_sources,
     })
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(overrides: &[(&str, &[f64])]) -> MergeConfig {
+        let mut config: MergeConfig = serde_json::from_value(serde_json::json!({
+            "parents": [{"parent_id": "a", "weight": 0.5}, {"parent_id": "b", "weight": 0.5}],
+            "method": "linear",
+            "params": {},
+            "base_parent_id": null,
+            "layer_assignments": [],
+            "component_overrides": [],
+            "tensor_overrides": [],
+            "output": {"format": "gguf", "path": "", "model_name": ""},
+        }))
+        .unwrap();
+        config.weight_overrides = overrides.iter().map(|(k, w)| (k.to_string(), w.to_vec())).collect();
+        config
+    }
+
+    #[test]
+    fn override_precedence_is_exact_then_glob_then_component() {
+        let config = config(&[
+            ("model.layers.0.self_attn.q_proj.weight", &[1.0, 0.0]),
+            ("model.layers.0.self_attn.*", &[0.7, 0.3]),
+            ("attention", &[0.2, 0.8]),
+        ]);
+        let lookup = |name| weight_override(&config, name);
+        assert_eq!(lookup("model.layers.0.self_attn.q_proj.weight"), Some(&[1.0, 0.0][..]));
+        assert_eq!(lookup("model.layers.0.self_attn.k_proj.weight"), Some(&[0.7, 0.3][..]));
+        assert_eq!(lookup("model.layers.1.self_attn.k_proj.weight"), Some(&[0.2, 0.8][..]));
+        assert_eq!(lookup("model.layers.1.mlp.up_proj.weight"), None);
+    }
+
+    #[test]
+    fn longer_glob_wins_and_ties_resolve_alphabetically() {
+        let config = config(&[
+            ("model.layers.*", &[0.9, 0.1]),
+            ("model.layers.0.*", &[0.6, 0.4]),
+            ("model.layers.?.*", &[0.3, 0.7]),
+        ]);
+        // Both 16-character globs match layer 0; "model.layers.0.*" sorts first
+        assert_eq!(weight_override(&config, "model.layers.0.mlp.up_proj.weight"), Some(&[0.6, 0.4][..]));
+        assert_eq!(weight_override(&config, "model.layers.5.mlp.up_proj.weight"), Some(&[0.3, 0.7][..]));
+        assert_eq!(weight_override(&config, "model.layers.12.mlp.up_proj.weight"), Some(&[0.9, 0.1][..]));
+    }
+
+    #[test]
+    fn override_weight_counts_are_checked() {
+        let config = config(&[
+            ("attention", &[0.5, 0.5]),
+            ("mlp", &[0.2, 0.3, 0.5]),
+            ("norm", &[f64::NAN, 1.0]),
+        ]);
+        let (errors, _) = check_weight_overrides(&config, &ParentRegistry::default());
+        assert_eq!(
+            errors,
+            [
+                "Weight override 'mlp' has 3 weights for 2 parents",
+                "Weight override 'norm' contains a non-finite weight",
+            ]
+        );
+    }
+}
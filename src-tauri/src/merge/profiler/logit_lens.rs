@@ -1,29 +1,112 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
 
+use candle_core::{DType, Device, Tensor, D};
 use tauri::{AppHandle, Emitter};
 
+use crate::merge::names;
+use crate::merge::registry::ParentModel;
+use crate::merge::tensor_io;
 use crate::model::error::ModelError;
+use crate::model::{gguf, ModelFormat};
 
 use super::{LayerProfile, PredictionEntry, ProfileProgress, ProfileResult};
 use super::layer_analysis;
 
+/// Prompt used when the caller doesn't supply one.
+pub const DEFAULT_PROMPT: &str = "The capital of France is";
+
+const TOP_K: usize = 5;
+
 /// Run Logit Lens profiling on a model.
 ///
-/// This performs a simplified layer-by-layer analysis:
-/// For each layer, we examine the tensor norms and distributions
-/// to estimate what each layer specializes in.
+/// Runs `prompt` through the model one decoder block at a time on the CPU and,
+/// after every block, projects the residual stream at the last position
+/// through the final norm and unembedding. The resulting next-token
+/// distribution gives each layer's top predictions, entropy and a
+/// specialization label.
 ///
-/// Full Logit Lens (with actual inference through layers) requires
-/// loading the full model into Candle, which is expensive.
-/// This implementation uses tensor statistics as a proxy.
+/// Only Llama-style decoders (RMSNorm, RoPE, SwiGLU MLP) can be run; other
+/// architectures fall back to the position-based estimates in `layer_analysis`.
 pub fn profile_layers(
     app: &AppHandle,
-    parent_id: &str,
-    tensor_names: &[String],
+    parent: &ParentModel,
+    prompt: &str,
+    cancel: Arc<AtomicBool>,
+) -> Result<ProfileResult, ModelError> {
+    let total_layers = parent.compat.num_layers.or(parent.layer_count).unwrap_or(32);
+
+    if !supports_lens(parent) {
+        return estimate_layers(app, parent, total_layers, cancel);
+    }
+
+    let map_err = |e: candle_core::Error| ModelError::CandleError(e.to_string());
+
+    let vocab = read_vocab(parent)?;
+    let token_ids = tokenize(prompt, &vocab);
+    if token_ids.is_empty() {
+        return Err(ModelError::MergeError("Prompt produced no tokens".into()));
+    }
+    let last_input = *token_ids.last().unwrap_or(&0) as usize;
+
+    let embed = load_f32(parent, "model.embed_tokens.weight")?;
+    let hidden_size = embed.dims().get(1).copied().unwrap_or(0);
+    let lens = read_lens_config(parent, hidden_size)?;
+    let final_norm = load_f32(parent, "model.norm.weight")?;
+    // Tied embeddings reuse the input matrix for unembedding
+    let unembed = load_opt(parent, "lm_head.weight")?.unwrap_or_else(|| embed.clone());
+
+    let seq_len = token_ids.len();
+    let ids = Tensor::from_vec(token_ids, seq_len, &Device::Cpu).map_err(map_err)?;
+    let mut x = embed.index_select(&ids, 0).map_err(map_err)?;
+    drop(embed);
+
+    let (cos, sin) = rope_tables(seq_len, lens.head_dim, lens.rope_theta)?;
+    let mask = causal_mask(seq_len)?;
+
+    let mut layers = Vec::new();
+    for layer_idx in 0..total_layers {
+        if cancel.load(Ordering::Relaxed) {
+            return Err(ModelError::MergeCancelled);
+        }
+
+        x = decoder_block(parent, layer_idx, &x, &lens, &cos, &sin, &mask)?;
+
+        let last = x.narrow(0, seq_len - 1, 1).map_err(map_err)?;
+        let normed = rms_norm(&last, &final_norm, lens.rms_eps)?;
+        let logits: Vec<f32> = linear(&normed, &unembed, None)?
+            .flatten_all()
+            .and_then(|t| t.to_vec1())
+            .map_err(map_err)?;
+
+        let profile = lens_profile(layer_idx, &logits, &vocab, last_input);
+
+        let _ = app.emit("merge:profile-progress", ProfileProgress {
+            layer_index: layer_idx,
+            total_layers,
+            percent: ((layer_idx as f64 + 1.0) / total_layers as f64) * 100.0,
+        });
+        let _ = app.emit("merge:profile-layer-done", &profile);
+        layers.push(profile);
+    }
+
+    Ok(ProfileResult {
+        parent_id: parent.id.clone(),
+        layers,
+        total_layers,
+    })
+}
+
+/// Position-based estimates for architectures the lens can't run.
+fn estimate_layers(
+    app: &AppHandle,
+    parent: &ParentModel,
     total_layers: u64,
     cancel: Arc<AtomicBool>,
 ) -> Result<ProfileResult, ModelError> {
+    let tensor_names = parent.compat.tensor_names();
     let mut layers = Vec::new();
 
     for layer_idx in 0..total_layers {
@@ -73,8 +156,463 @@ pub fn profile_layers(
     }
 
     Ok(ProfileResult {
-        parent_id: parent_id.to_string(),
+        parent_id: parent.id.clone(),
         layers,
         total_layers,
     })
 }
+
+// ── Lens output ─────────────────────────────────────────
+
+/// Top-k, entropy (nats) and specialization for one layer's lens distribution.
+///
+/// Labels: a layer still dominated by the input token, or near-uniform, is
+/// `syntactic`; one spreading mass over candidate continuations is `semantic`;
+/// one that commits to a confident prediction is `reasoning`.
+fn lens_profile(layer_idx: u64, logits: &[f32], vocab: &[String], last_input: usize) -> LayerProfile {
+    let max = logits.iter().copied().fold(f32::NEG_INFINITY, f32::max) as f64;
+    let exps: Vec<f64> = logits.iter().map(|&l| (l as f64 - max).exp()).collect();
+    let sum: f64 = exps.iter().sum();
+    let probs: Vec<f64> = exps.iter().map(|e| e / sum).collect();
+
+    let entropy: f64 = probs.iter().filter(|&&p| p > 0.0).map(|&p| -p * p.ln()).sum();
+    let max_entropy = (probs.len().max(2) as f64).ln();
+
+    let mut order: Vec<usize> = (0..probs.len()).collect();
+    let k = TOP_K.min(order.len());
+    if k > 0 {
+        order.select_nth_unstable_by(k - 1, |&a, &b| probs[b].total_cmp(&probs[a]));
+    }
+    order.truncate(k);
+    order.sort_by(|&a, &b| probs[b].total_cmp(&probs[a]));
+
+    let top_predictions: Vec<PredictionEntry> = order
+        .iter()
+        .enumerate()
+        .map(|(rank, &id)| PredictionEntry {
+            token: vocab.get(id).map(|t| display_token(t)).unwrap_or_else(|| format!("<{}>", id)),
+            probability: probs[id],
+            rank,
+        })
+        .collect();
+
+    let top_id = order.first().copied();
+    let confidence = top_id.map(|id| probs[id]).unwrap_or(0.0);
+    let specialization = if top_id == Some(last_input) || entropy / max_entropy > 0.75 {
+        "syntactic"
+    } else if confidence < 0.3 {
+        "semantic"
+    } else {
+        "reasoning"
+    };
+
+    LayerProfile {
+        layer_index: layer_idx,
+        top_predictions,
+        entropy,
+        specialization: specialization.to_string(),
+        confidence,
+    }
+}
+
+/// Render SentencePiece / byte-level BPE markers as plain text.
+fn display_token(token: &str) -> String {
+    token.replace('▁', " ").replace('Ġ', " ").replace('Ċ', "\n")
+}
+
+// ── Model config and vocab ──────────────────────────────
+
+struct LensConfig {
+    n_heads: usize,
+    n_kv_heads: usize,
+    head_dim: usize,
+    rms_eps: f64,
+    rope_theta: f64,
+}
+
+fn model_dir(parent: &ParentModel) -> PathBuf {
+    let path = Path::new(&parent.file_path);
+    if parent.is_dir {
+        path.to_path_buf()
+    } else {
+        path.parent().map(|p| p.to_path_buf()).unwrap_or_default()
+    }
+}
+
+fn read_config_json(parent: &ParentModel) -> Option<serde_json::Value> {
+    let content = std::fs::read_to_string(model_dir(parent).join("config.json")).ok()?;
+    serde_json::from_str(&content).ok()
+}
+
+fn read_lens_config(parent: &ParentModel, hidden_size: usize) -> Result<LensConfig, ModelError> {
+    let (heads, kv_heads, head_dim, eps, theta) = match parent.format {
+        ModelFormat::Gguf => {
+            let info = gguf::parse(Path::new(&parent.file_path))?;
+            let arch = info.metadata.get("general.architecture").cloned().unwrap_or_default();
+            let get = |key: &str| {
+                info.metadata
+                    .get(&format!("{}.{}", arch, key))
+                    .and_then(|v| v.parse::<f64>().ok())
+            };
+            (
+                get("attention.head_count"),
+                get("attention.head_count_kv"),
+                get("attention.key_length"),
+                get("attention.layer_norm_rms_epsilon"),
+                get("rope.freq_base"),
+            )
+        }
+        ModelFormat::SafeTensors | ModelFormat::PyTorch => {
+            let config = read_config_json(parent);
+            let get = |key: &str| config.as_ref().and_then(|c| c.get(key)).and_then(|v| v.as_f64());
+            (
+                get("num_attention_heads"),
+                get("num_key_value_heads"),
+                get("head_dim"),
+                get("rms_norm_eps"),
+                get("rope_theta"),
+            )
+        }
+    };
+
+    let n_heads = heads
+        .map(|h| h as usize)
+        .or(parent.compat.num_attention_heads.map(|h| h as usize))
+        .filter(|&h| h > 0)
+        .ok_or_else(|| ModelError::MergeError("Logit lens needs the attention head count".into()))?;
+    let n_kv_heads = kv_heads
+        .map(|h| h as usize)
+        .or(parent.compat.num_kv_heads.map(|h| h as usize))
+        .filter(|&h| h > 0 && n_heads % h == 0)
+        .unwrap_or(n_heads);
+    // Qwen3, Gemma and others set head_dim independently of hidden / heads
+    let head_dim = head_dim
+        .map(|d| d as usize)
+        .or(parent.compat.head_dim.map(|d| d as usize))
+        .filter(|&d| d > 0)
+        .unwrap_or(hidden_size / n_heads);
+
+    Ok(LensConfig {
+        n_heads,
+        n_kv_heads,
+        head_dim,
+        rms_eps: eps.unwrap_or(1e-5),
+        rope_theta: theta.unwrap_or(10000.0),
+    })
+}
+
+/// Token strings indexed by id, from GGUF metadata or `tokenizer.json`.
+pub(crate) fn read_vocab(parent: &ParentModel) -> Result<Vec<String>, ModelError> {
+    if matches!(parent.format, ModelFormat::Gguf) {
+        return gguf::read_token_list(Path::new(&parent.file_path));
+    }
+
+    let path = model_dir(parent).join("tokenizer.json");
+    let content = std::fs::read_to_string(&path).map_err(|_| {
+        ModelError::MergeError(format!("Logit lens needs {}", path.display()))
+    })?;
+    let json: serde_json::Value = serde_json::from_str(&content).map_err(|e| ModelError::ParseError {
+        format: "tokenizer.json".into(),
+        reason: e.to_string(),
+    })?;
+
+    let mut by_id: HashMap<usize, String> = HashMap::new();
+    if let Some(vocab) = json.get("model").and_then(|m| m.get("vocab")).and_then(|v| v.as_object()) {
+        for (token, id) in vocab {
+            if let Some(id) = id.as_u64() {
+                by_id.insert(id as usize, token.clone());
+            }
+        }
+    }
+    if let Some(added) = json.get("added_tokens").and_then(|a| a.as_array()) {
+        for token in added {
+            if let (Some(id), Some(content)) = (
+                token.get("id").and_then(|i| i.as_u64()),
+                token.get("content").and_then(|c| c.as_str()),
+            ) {
+                by_id.insert(id as usize, content.to_string());
+            }
+        }
+    }
+
+    let size = by_id.keys().max().map(|m| m + 1).unwrap_or(0);
+    let mut tokens = vec![String::new(); size];
+    for (id, token) in by_id {
+        tokens[id] = token;
+    }
+    Ok(tokens)
+}
+
+/// Greedy longest-match tokenization against the vocab. Close enough to the
+/// real tokenizer for a lens probe; a BOS token is prepended when present.
+fn tokenize(prompt: &str, vocab: &[String]) -> Vec<u32> {
+    let lookup: HashMap<&str, u32> = vocab
+        .iter()
+        .enumerate()
+        .filter(|(_, t)| !t.is_empty())
+        .map(|(i, t)| (t.as_str(), i as u32))
+        .collect();
+
+    let text = if lookup.keys().any(|t| t.starts_with('▁')) {
+        format!("▁{}", prompt.replace(' ', "▁"))
+    } else if lookup.keys().any(|t| t.starts_with('Ġ')) {
+        prompt.replace(' ', "Ġ").replace('\n', "Ċ")
+    } else {
+        prompt.to_string()
+    };
+
+    let mut ids: Vec<u32> = ["<s>", "<|begin_of_text|>", "<bos>"]
+        .iter()
+        .find_map(|bos| lookup.get(bos).copied())
+        .into_iter()
+        .collect();
+
+    let chars: Vec<(usize, char)> = text.char_indices().collect();
+    let mut i = 0;
+    while i < chars.len() {
+        let start = chars[i].0;
+        let matched = (i + 1..=chars.len()).rev().find_map(|j| {
+            let end = chars.get(j).map(|c| c.0).unwrap_or(text.len());
+            lookup.get(&text[start..end]).map(|&id| (id, j))
+        });
+        match matched {
+            Some((id, next)) => {
+                ids.push(id);
+                i = next;
+            }
+            None => i += 1,
+        }
+    }
+    ids
+}
+
+// ── Forward pass ────────────────────────────────────────
+
+fn supports_lens(parent: &ParentModel) -> bool {
+    [
+        "model.embed_tokens.weight",
+        "model.norm.weight",
+        "model.layers.0.self_attn.q_proj.weight",
+        "model.layers.0.mlp.gate_proj.weight",
+    ]
+    .iter()
+    .all(|name| names::resolve_name(parent, name).is_some())
+}
+
+fn load_f32(parent: &ParentModel, name: &str) -> Result<Tensor, ModelError> {
+    tensor_io::load_tensor_canonical(parent, name)?
+        .to_dtype(DType::F32)
+        .map_err(|e| ModelError::CandleError(e.to_string()))
+}
+
+fn load_opt(parent: &ParentModel, name: &str) -> Result<Option<Tensor>, ModelError> {
+    if names::resolve_name(parent, name).is_none() {
+        return Ok(None);
+    }
+    load_f32(parent, name).map(Some)
+}
+
+fn rms_norm(x: &Tensor, weight: &Tensor, eps: f64) -> Result<Tensor, ModelError> {
+    let map_err = |e: candle_core::Error| ModelError::CandleError(e.to_string());
+    let mean_sq = x.sqr().and_then(|t| t.mean_keepdim(D::Minus1)).map_err(map_err)?;
+    let denom = (mean_sq + eps).and_then(|t| t.sqrt()).map_err(map_err)?;
+    x.broadcast_div(&denom)
+        .and_then(|t| t.broadcast_mul(weight))
+        .map_err(map_err)
+}
+
+/// `x @ weight^T + bias` for HF-layout `[out, in]` weights.
+fn linear(x: &Tensor, weight: &Tensor, bias: Option<&Tensor>) -> Result<Tensor, ModelError> {
+    let map_err = |e: candle_core::Error| ModelError::CandleError(e.to_string());
+    let out = x.matmul(&weight.t().map_err(map_err)?).map_err(map_err)?;
+    match bias {
+        Some(b) => out.broadcast_add(b).map_err(map_err),
+        None => Ok(out),
+    }
+}
+
+fn softmax_last(x: &Tensor) -> Result<Tensor, ModelError> {
+    let map_err = |e: candle_core::Error| ModelError::CandleError(e.to_string());
+    let max = x.max_keepdim(D::Minus1).map_err(map_err)?;
+    let exp = x.broadcast_sub(&max).and_then(|t| t.exp()).map_err(map_err)?;
+    let sum = exp.sum_keepdim(D::Minus1).map_err(map_err)?;
+    exp.broadcast_div(&sum).map_err(map_err)
+}
+
+/// `[seq, head_dim]` cos/sin tables for HF-style (rotate-half) RoPE.
+fn rope_tables(seq_len: usize, head_dim: usize, theta: f64) -> Result<(Tensor, Tensor), ModelError> {
+    let half = head_dim / 2;
+    let mut cos = Vec::with_capacity(seq_len * head_dim);
+    let mut sin = Vec::with_capacity(seq_len * head_dim);
+    for pos in 0..seq_len {
+        let angles: Vec<f64> = (0..half)
+            .map(|i| pos as f64 / theta.powf(2.0 * i as f64 / head_dim as f64))
+            .collect();
+        for _ in 0..2 {
+            cos.extend(angles.iter().map(|a| a.cos() as f32));
+            sin.extend(angles.iter().map(|a| a.sin() as f32));
+        }
+    }
+    let map_err = |e: candle_core::Error| ModelError::CandleError(e.to_string());
+    Ok((
+        Tensor::from_vec(cos, (seq_len, head_dim), &Device::Cpu).map_err(map_err)?,
+        Tensor::from_vec(sin, (seq_len, head_dim), &Device::Cpu).map_err(map_err)?,
+    ))
+}
+
+/// Rotate `[heads, seq, head_dim]` by the position tables.
+fn apply_rope(x: &Tensor, cos: &Tensor, sin: &Tensor) -> Result<Tensor, ModelError> {
+    let map_err = |e: candle_core::Error| ModelError::CandleError(e.to_string());
+    let half = x.dim(D::Minus1).map_err(map_err)? / 2;
+    let x1 = x.narrow(D::Minus1, 0, half).map_err(map_err)?;
+    let x2 = x.narrow(D::Minus1, half, half).map_err(map_err)?;
+    let rotated = Tensor::cat(&[&x2.neg().map_err(map_err)?, &x1], D::Minus1).map_err(map_err)?;
+    let a = x.broadcast_mul(cos).map_err(map_err)?;
+    let b = rotated.broadcast_mul(sin).map_err(map_err)?;
+    (a + b).map_err(map_err)
+}
+
+fn causal_mask(seq_len: usize) -> Result<Tensor, ModelError> {
+    let mask: Vec<f32> = (0..seq_len)
+        .flat_map(|i| (0..seq_len).map(move |j| if j > i { f32::NEG_INFINITY } else { 0.0 }))
+        .collect();
+    Tensor::from_vec(mask, (seq_len, seq_len), &Device::Cpu)
+        .map_err(|e| ModelError::CandleError(e.to_string()))
+}
+
+/// `[seq, hidden]` → `[heads, seq, head_dim]`
+fn split_heads(x: &Tensor, heads: usize, head_dim: usize) -> Result<Tensor, ModelError> {
+    let seq_len = x.dim(0).map_err(|e| ModelError::CandleError(e.to_string()))?;
+    x.reshape((seq_len, heads, head_dim))
+        .and_then(|t| t.transpose(0, 1))
+        .and_then(|t| t.contiguous())
+        .map_err(|e| ModelError::CandleError(e.to_string()))
+}
+
+/// Repeat grouped KV heads up to the query head count.
+fn repeat_kv(x: Tensor, n_rep: usize) -> Result<Tensor, ModelError> {
+    if n_rep == 1 {
+        return Ok(x);
+    }
+    let (kv_heads, seq_len, head_dim) = x.dims3().map_err(|e| ModelError::CandleError(e.to_string()))?;
+    x.unsqueeze(1)
+        .and_then(|t| t.expand((kv_heads, n_rep, seq_len, head_dim)))
+        .and_then(|t| t.contiguous())
+        .and_then(|t| t.reshape((kv_heads * n_rep, seq_len, head_dim)))
+        .map_err(|e| ModelError::CandleError(e.to_string()))
+}
+
+/// One pre-norm decoder block; its weights are loaded here and dropped on return.
+fn decoder_block(
+    parent: &ParentModel,
+    layer_idx: u64,
+    x: &Tensor,
+    lens: &LensConfig,
+    cos: &Tensor,
+    sin: &Tensor,
+    mask: &Tensor,
+) -> Result<Tensor, ModelError> {
+    let map_err = |e: candle_core::Error| ModelError::CandleError(e.to_string());
+    let p = format!("model.layers.{}.", layer_idx);
+    let w = |name: &str| load_f32(parent, &format!("{}{}", p, name));
+    let b = |name: &str| load_opt(parent, &format!("{}{}", p, name));
+    let seq_len = x.dim(0).map_err(map_err)?;
+
+    // Self-attention
+    let h = rms_norm(x, &w("input_layernorm.weight")?, lens.rms_eps)?;
+    let q = linear(&h, &w("self_attn.q_proj.weight")?, b("self_attn.q_proj.bias")?.as_ref())?;
+    let k = linear(&h, &w("self_attn.k_proj.weight")?, b("self_attn.k_proj.bias")?.as_ref())?;
+    let v = linear(&h, &w("self_attn.v_proj.weight")?, b("self_attn.v_proj.bias")?.as_ref())?;
+
+    let q = apply_rope(&split_heads(&q, lens.n_heads, lens.head_dim)?, cos, sin)?;
+    let k = apply_rope(&split_heads(&k, lens.n_kv_heads, lens.head_dim)?, cos, sin)?;
+    let v = split_heads(&v, lens.n_kv_heads, lens.head_dim)?;
+    let n_rep = lens.n_heads / lens.n_kv_heads;
+    let k = repeat_kv(k, n_rep)?;
+    let v = repeat_kv(v, n_rep)?;
+
+    let scale = 1.0 / (lens.head_dim as f64).sqrt();
+    let scores = k
+        .t()
+        .and_then(|kt| kt.contiguous())
+        .and_then(|kt| q.matmul(&kt))
+        .and_then(|s| s * scale)
+        .and_then(|s| s.broadcast_add(mask))
+        .map_err(map_err)?;
+    let attn = softmax_last(&scores)?
+        .matmul(&v)
+        .and_then(|t| t.transpose(0, 1))
+        .and_then(|t| t.contiguous())
+        .and_then(|t| t.reshape((seq_len, lens.n_heads * lens.head_dim)))
+        .map_err(map_err)?;
+    let attn = linear(&attn, &w("self_attn.o_proj.weight")?, b("self_attn.o_proj.bias")?.as_ref())?;
+    let x = (x + attn).map_err(map_err)?;
+
+    // SwiGLU MLP
+    let h = rms_norm(&x, &w("post_attention_layernorm.weight")?, lens.rms_eps)?;
+    let gate = linear(&h, &w("mlp.gate_proj.weight")?, None)?.silu().map_err(map_err)?;
+    let up = linear(&h, &w("mlp.up_proj.weight")?, None)?;
+    let mlp = linear(&(gate * up).map_err(map_err)?, &w("mlp.down_proj.weight")?, None)?;
+    (x + mlp).map_err(map_err)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::merge::registry::CompatInfo;
+
+    fn dir_parent(config: &str) -> (PathBuf, ParentModel) {
+        let dir = std::env::temp_dir().join(format!("forgeai-lens-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("config.json"), config).unwrap();
+        let parent = ParentModel {
+            id: "p".into(),
+            slot: 0,
+            name: "p".into(),
+            file_path: dir.to_string_lossy().to_string(),
+            format: ModelFormat::SafeTensors,
+            file_size: 0,
+            file_size_display: String::new(),
+            parameter_count: 0,
+            parameter_count_display: String::new(),
+            layer_count: None,
+            architecture: None,
+            quantization: None,
+            compat: CompatInfo {
+                architecture: None,
+                hidden_size: None,
+                num_layers: None,
+                num_attention_heads: None,
+                num_kv_heads: None,
+                vocab_size: None,
+                context_length: None,
+                head_dim: None,
+                rope_theta: None,
+                rms_norm_eps: None,
+                tensor_metas: vec![],
+            },
+            color: String::new(),
+            is_dir: true,
+        };
+        (dir, parent)
+    }
+
+    #[test]
+    fn explicit_head_dim_wins_over_hidden_by_heads() {
+        // Qwen3-0.6B: hidden 1024, 16 heads of 128
+        let (dir, parent) = dir_parent(r#"{"num_attention_heads":16,"num_key_value_heads":8,"head_dim":128}"#);
+        let lens = read_lens_config(&parent, 1024);
+        std::fs::remove_dir_all(&dir).unwrap();
+        let lens = lens.unwrap();
+        assert_eq!((lens.n_heads, lens.n_kv_heads, lens.head_dim), (16, 8, 128));
+    }
+
+    #[test]
+    fn head_dim_falls_back_to_hidden_by_heads() {
+        let (dir, parent) = dir_parent(r#"{"num_attention_heads":16}"#);
+        let lens = read_lens_config(&parent, 1024);
+        std::fs::remove_dir_all(&dir).unwrap();
+        let lens = lens.unwrap();
+        assert_eq!((lens.n_kv_heads, lens.head_dim), (16, 64));
+    }
+}
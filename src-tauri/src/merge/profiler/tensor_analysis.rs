@@ -161,6 +161,8 @@ pub struct LayerAnalysis {
     pub attn_tensors: usize,
     pub mlp_tensors: usize,
     pub norm_tensors: usize,
+    /// Norm tensors that failed to load; stats for this layer are partial if > 0.
+    pub norm_load_failures: usize,
     pub norm_l2: f64,
     pub norm_variance: f64,
     pub mlp_dominance: f64,
@@ -173,6 +175,8 @@ pub struct AnalysisResult {
     pub layers: Vec<LayerAnalysis>,
     pub total_layers: u64,
     pub categories: Vec<LayerCategory>,
+    /// Total norm tensors that failed to load across all layers.
+    pub failed_norm_tensors: usize,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -184,6 +188,35 @@ pub struct AnalysisProgress {
     pub stage: String,
 }
 
+// ── Export ──────────────────────────────────────────────
+
+/// Column order of [`analysis_to_csv`].
+pub const ANALYSIS_CSV_HEADER: &str = "layer_index,category,confidence,norm_l2,norm_variance,mlp_dominance";
+
+/// One CSV row per layer, for diffing analyses across checkpoints. Floats use
+/// Rust's shortest round-trip formatting, so re-parsing gives the same numbers.
+pub fn analysis_to_csv(result: &AnalysisResult) -> String {
+    let mut out = String::from(ANALYSIS_CSV_HEADER);
+    out.push('\n');
+    for layer in &result.layers {
+        let category = if layer.category.contains([',', '"', '\n']) {
+            format!("\"{}\"", layer.category.replace('"', "\"\""))
+        } else {
+            layer.category.clone()
+        };
+        out.push_str(&format!(
+            "{},{},{},{},{},{}\n",
+            layer.layer_index,
+            category,
+            layer.confidence,
+            layer.norm_l2,
+            layer.norm_variance,
+            layer.mlp_dominance,
+        ));
+    }
+    out
+}
+
 // ── Tensor Statistics ───────────────────────────────────
 
 struct NormStats {
@@ -232,11 +265,13 @@ pub fn analyze_parent(
             layers: vec![],
             total_layers: 0,
             categories: all_categories(),
+            failed_norm_tensors: 0,
         });
     }
 
     // Phase 1: Collect raw stats for all layers
-    let mut raw_stats: Vec<(u64, f64, f64, usize, usize, usize, Vec<String>)> = Vec::new();
+    let mut raw_stats: Vec<(u64, f64, f64, usize, usize, usize, usize, Vec<String>)> = Vec::new();
+    let mut failed_norm_tensors = 0usize;
 
     for layer_idx in 0..total_layers {
         if cancel.load(Ordering::Relaxed) {
@@ -269,7 +304,7 @@ pub fn analyze_parent(
             let class = inspect::classify_tensor(name);
             match class {
                 "attention" => attn_count += 1,
-                "mlp" => mlp_count += 1,
+                "mlp" | "expert" => mlp_count += 1,
                 "norm" => {
                     if is_norm_tensor(name) {
                         norm_names.push(name.clone());
@@ -283,23 +318,28 @@ pub fn analyze_parent(
         let mut layer_l2 = 0.0f64;
         let mut layer_var = 0.0f64;
         let mut loaded_count = 0usize;
+        let mut failed_count = 0usize;
 
         for norm_name in &norm_names {
-            match tensor_io::load_tensor(parent, norm_name) {
-                Ok(tensor) => {
-                    // Flatten to 1D and convert to f32
-                    if let Ok(flat) = tensor.flatten_all() {
-                        if let Ok(data) = flat.to_vec1::<f32>() {
-                            let stats = compute_stats(&data);
-                            layer_l2 += stats.l2;
-                            layer_var += stats.variance;
-                            loaded_count += 1;
-                        }
-                    }
+            // Large models can take a while per tensor, so check between loads
+            if cancel.load(Ordering::Relaxed) {
+                return Err(ModelError::MergeCancelled);
+            }
+
+            let data = tensor_io::load_tensor(parent, norm_name)
+                .and_then(|t| t.flatten_all().map_err(|e| ModelError::CandleError(e.to_string())))
+                .and_then(|t| t.to_vec1::<f32>().map_err(|e| ModelError::CandleError(e.to_string())));
+            match data {
+                Ok(data) => {
+                    let stats = compute_stats(&data);
+                    layer_l2 += stats.l2;
+                    layer_var += stats.variance;
+                    loaded_count += 1;
                 }
-                Err(_) => continue,
+                Err(_) => failed_count += 1,
             }
         }
+        failed_norm_tensors += failed_count;
 
         // Average the stats if we loaded multiple norm tensors
         if loaded_count > 0 {
@@ -307,7 +347,7 @@ pub fn analyze_parent(
             layer_var /= loaded_count as f64;
         }
 
-        raw_stats.push((layer_idx, layer_l2, layer_var, attn_count, mlp_count, norm_names.len(), layer_tensor_names));
+        raw_stats.push((layer_idx, layer_l2, layer_var, attn_count, mlp_count, norm_names.len(), failed_count, layer_tensor_names));
     }
 
     // Phase 2: Normalize stats across all layers
@@ -325,7 +365,7 @@ pub fn analyze_parent(
     // Phase 3: Classify each layer
     let mut layers = Vec::new();
 
-    for (layer_idx, l2, var, attn_count, mlp_count, norm_count, layer_tensor_names) in &raw_stats {
+    for (layer_idx, l2, var, attn_count, mlp_count, norm_count, norm_failures, layer_tensor_names) in &raw_stats {
         if cancel.load(Ordering::Relaxed) {
             return Err(ModelError::MergeCancelled);
         }
@@ -364,6 +404,7 @@ pub fn analyze_parent(
             attn_tensors: *attn_count,
             mlp_tensors: *mlp_count,
             norm_tensors: *norm_count,
+            norm_load_failures: *norm_failures,
             norm_l2: *l2,
             norm_variance: *var,
             mlp_dominance,
@@ -379,6 +420,7 @@ pub fn analyze_parent(
         layers,
         total_layers,
         categories: all_categories(),
+        failed_norm_tensors,
     })
 }
 
@@ -548,3 +590,69 @@ fn classify_layer_base(position: f64, l2_z: f64, var_z: f64, mlp_dom: f64) -> (&
 
     ("head", 0.90)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn layer(layer_index: u64, category: &str, confidence: f64, norm_l2: f64, norm_variance: f64, mlp_dominance: f64) -> LayerAnalysis {
+        LayerAnalysis {
+            layer_index,
+            category: category.into(),
+            label: String::new(),
+            color: String::new(),
+            description: String::new(),
+            confidence,
+            attn_tensors: 4,
+            mlp_tensors: 3,
+            norm_tensors: 2,
+            norm_load_failures: 0,
+            norm_l2,
+            norm_variance,
+            mlp_dominance,
+        }
+    }
+
+    #[test]
+    fn csv_round_trips_the_numbers() {
+        let result = AnalysisResult {
+            parent_id: "p".into(),
+            parent_name: "p".into(),
+            layers: vec![
+                layer(0, "embed", 0.1 + 0.2, 12345.678901234567, 1e-12, 0.0),
+                layer(1, "tool, code", 1.0 / 3.0, f64::MIN_POSITIVE, 2.5e17, -0.75),
+            ],
+            total_layers: 2,
+            categories: all_categories(),
+            failed_norm_tensors: 0,
+        };
+
+        let csv = analysis_to_csv(&result);
+        let mut lines = csv.lines();
+        assert_eq!(lines.next(), Some(ANALYSIS_CSV_HEADER));
+        let rows: Vec<&str> = lines.collect();
+        assert_eq!(rows.len(), 2);
+
+        for (row, expected) in rows.iter().zip(&result.layers) {
+            // The category is the only column that may be quoted, so split numbers off both ends
+            let (index, rest) = row.split_once(',').unwrap();
+            let mut tail = rest.rsplitn(5, ',');
+            let mlp_dominance: f64 = tail.next().unwrap().parse().unwrap();
+            let norm_variance: f64 = tail.next().unwrap().parse().unwrap();
+            let norm_l2: f64 = tail.next().unwrap().parse().unwrap();
+            let confidence: f64 = tail.next().unwrap().parse().unwrap();
+            let category = tail.next().unwrap();
+
+            assert_eq!(index.parse::<u64>().unwrap(), expected.layer_index);
+            assert_eq!(confidence, expected.confidence);
+            assert_eq!(norm_l2, expected.norm_l2);
+            assert_eq!(norm_variance, expected.norm_variance);
+            assert_eq!(mlp_dominance, expected.mlp_dominance);
+            if expected.category.contains(',') {
+                assert_eq!(category, format!("\"{}\"", expected.category));
+            } else {
+                assert_eq!(category, expected.category);
+            }
+        }
+    }
+}
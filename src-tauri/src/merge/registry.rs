@@ -21,6 +21,9 @@ pub struct CompatInfo {
     pub num_kv_heads: Option<u64>,
     pub vocab_size: Option<u64>,
     pub context_length: Option<u64>,
+    pub head_dim: Option<u64>,
+    pub rope_theta: Option<f64>,
+    pub rms_norm_eps: Option<f64>,
     pub tensor_metas: Vec<TensorMeta>,
 }
 
@@ -69,6 +72,16 @@ impl CompatInfo {
                 .and_then(|v| v.parse::<u64>().ok())
         });
 
+        let head_dim = meta
+            .get(&format!("{}.attention.key_length", arch_prefix))
+            .and_then(|v| v.parse::<u64>().ok());
+        let rope_theta = meta
+            .get(&format!("{}.rope.freq_base", arch_prefix))
+            .and_then(|v| v.parse::<f64>().ok());
+        let rms_norm_eps = meta
+            .get(&format!("{}.attention.layer_norm_rms_epsilon", arch_prefix))
+            .and_then(|v| v.parse::<f64>().ok());
+
         Self {
             architecture: arch,
             hidden_size,
@@ -77,6 +90,9 @@ impl CompatInfo {
             num_kv_heads,
             vocab_size,
             context_length,
+            head_dim,
+            rope_theta,
+            rms_norm_eps,
             tensor_metas,
         }
     }
@@ -186,17 +202,20 @@ impl ParentRegistry {
         dims.len() < 2 || dims.windows(2).all(|w| w[0] == w[1])
     }
 
+    /// Names (as spelled by the first parent) of tensors every parent has.
+    /// Matching is by canonical name so GGUF and SafeTensors parents line up.
     pub fn shared_tensor_names(&self) -> HashSet<String> {
         if self.parents.len() < 2 {
             return HashSet::new();
         }
 
-        let first: HashSet<String> = self.parents[0].compat.tensor_metas.iter().map(|t| t.name.clone()).collect();
-        let mut shared: HashSet<String> = first;
+        let mut shared: HashSet<String> = self.parents[0].compat.tensor_metas.iter().map(|t| t.name.clone()).collect();
 
         for parent in &self.parents[1..] {
-            let names: HashSet<String> = parent.compat.tensor_metas.iter().map(|t| t.name.clone()).collect();
-            shared.retain(|n| names.contains(n));
+            let names: HashSet<String> = parent.compat.tensor_metas.iter()
+                .map(|t| super::names::canonical_name(&t.name))
+                .collect();
+            shared.retain(|n| names.contains(&super::names::canonical_name(n)));
         }
 
         shared
@@ -8,22 +8,73 @@ use memmap2::Mmap;
 use crate::model::error::ModelError;
 use crate::model::ModelFormat;
 
+use super::names;
 use super::registry::ParentModel;
 
+/// Convert a file-derived u64 offset or length to usize, failing cleanly when it
+/// does not fit the platform's address space (e.g. >4GB files on 32-bit targets).
+pub(crate) fn offset_to_usize(value: u64, format: &str, what: &str) -> Result<usize, ModelError> {
+    usize::try_from(value).map_err(|_| ModelError::ParseError {
+        format: format.into(),
+        reason: format!("{} ({}) exceeds the addressable size on this platform", what, value),
+    })
+}
+
+/// Resolve `[start, start + len)` against a buffer of `data_len` bytes using u64
+/// arithmetic, returning an in-bounds usize range or a clear error.
+pub(crate) fn checked_range(
+    start: u64,
+    len: u64,
+    data_len: usize,
+    format: &str,
+) -> Result<std::ops::Range<usize>, ModelError> {
+    let end = start.checked_add(len).ok_or_else(|| ModelError::ParseError {
+        format: format.into(),
+        reason: format!("Offset overflow: {} + {}", start, len),
+    })?;
+    if end > data_len as u64 {
+        return Err(ModelError::ParseError {
+            format: format.into(),
+            reason: format!(
+                "Tensor data extends past file end: {} + {} > {}",
+                start, len, data_len
+            ),
+        });
+    }
+    Ok(offset_to_usize(start, format, "Offset")?..offset_to_usize(end, format, "Offset")?)
+}
+
+/// Multiply tensor dimensions in u64, rejecting element counts that overflow.
+fn checked_elem_count(shape: &[usize], format: &str) -> Result<u64, ModelError> {
+    shape.iter().try_fold(1u64, |acc, &d| acc.checked_mul(d as u64)).ok_or_else(|| {
+        ModelError::ParseError {
+            format: format.into(),
+            reason: format!("Tensor element count overflows: {:?}", shape),
+        }
+    })
+}
+
 /// Load a single tensor from a SafeTensors file by name.
 pub fn load_safetensors_tensor(path: &Path, tensor_name: &str) -> Result<Tensor, ModelError> {
     let file = File::open(path).map_err(ModelError::IoError)?;
     let mmap = unsafe { Mmap::map(&file).map_err(ModelError::IoError)? };
 
+    if mmap.len() < 8 {
+        return Err(ModelError::ParseError {
+            format: "SafeTensors".into(),
+            reason: "Failed to read header length".into(),
+        });
+    }
     let header_len = u64::from_le_bytes(
         mmap[0..8].try_into().map_err(|_| ModelError::ParseError {
             format: "SafeTensors".into(),
             reason: "Failed to read header length".into(),
         })?,
-    ) as usize;
+    );
+    let header_range = checked_range(8, header_len, mmap.len(), "SafeTensors")?;
 
     let header_json: serde_json::Value =
-        serde_json::from_slice(&mmap[8..8 + header_len]).map_err(|e| ModelError::ParseError {
+        serde_json::from_slice(&mmap[header_range]).map_err(|e| ModelError::ParseError {
             format: "SafeTensors".into(),
             reason: format!("Invalid JSON header: {}", e),
         })?;
@@ -54,7 +105,13 @@ pub fn load_safetensors_tensor(path: &Path, tensor_name: &str) -> Result<Tensor,
     let shape: Vec<usize> = obj
         .get("shape")
         .and_then(|v| v.as_array())
-        .map(|arr| arr.iter().filter_map(|x| x.as_u64().map(|n| n as usize)).collect())
+        .map(|arr| {
+            arr.iter()
+                .filter_map(|x| x.as_u64())
+                .map(|n| offset_to_usize(n, "SafeTensors", "Tensor dimension"))
+                .collect::<Result<Vec<_>, _>>()
+        })
+        .transpose()?
         .unwrap_or_default();
 
     let offsets = obj
@@ -65,16 +122,25 @@ pub fn load_safetensors_tensor(path: &Path, tensor_name: &str) -> Result<Tensor,
             reason: format!("No data_offsets for tensor '{}'", tensor_name),
         })?;
 
-    let start = offsets[0].as_u64().unwrap_or(0) as usize;
-    let end = offsets[1].as_u64().unwrap_or(0) as usize;
+    let start = offsets.first().and_then(|v| v.as_u64()).unwrap_or(0);
+    let end = offsets.get(1).and_then(|v| v.as_u64()).unwrap_or(0);
+    if end < start {
+        return Err(ModelError::ParseError {
+            format: "SafeTensors".into(),
+            reason: format!("Invalid data_offsets for tensor '{}'", tensor_name),
+        });
+    }
 
     let data_offset = 8 + header_len;
-    let tensor_bytes = &mmap[data_offset + start..data_offset + end];
+    let tensor_range = checked_range(data_offset.saturating_add(start), end - start, mmap.len(), "SafeTensors")?;
+    let tensor_bytes = &mmap[tensor_range];
 
     let (candle_dtype, elem_size) = safetensors_dtype_to_candle(dtype_str)?;
 
-    let expected_bytes = shape.iter().product::<usize>() * elem_size;
-    if tensor_bytes.len() < expected_bytes {
+    let expected_bytes = checked_elem_count(&shape, "SafeTensors")?
+        .checked_mul(elem_size as u64)
+        .unwrap_or(u64::MAX);
+    if (tensor_bytes.len() as u64) < expected_bytes {
         return Err(ModelError::ParseError {
             format: "SafeTensors".into(),
             reason: format!(
@@ -158,32 +224,120 @@ pub fn load_safetensors_tensor_sharded(
     })
 }
 
+/// Load a tensor from any shard of a split GGUF (`model-00001-of-00003.gguf`).
+pub fn load_gguf_tensor_sharded(
+    path: &Path,
+    tensor_name: &str,
+) -> Result<Tensor, ModelError> {
+    let shard_files = crate::model::gguf::split_shard_paths(path)?;
+
+    // Try each shard until we find the tensor
+    for shard_path in &shard_files {
+        match GgufTensorFile::open(shard_path).and_then(|f| f.load(tensor_name)) {
+            Ok(tensor) => return Ok(tensor),
+            Err(ModelError::TensorNotFound { .. }) => continue,
+            Err(e) => return Err(e),
+        }
+    }
+
+    Err(ModelError::TensorNotFound {
+        tensor_name: tensor_name.to_string(),
+        parent_id: path.to_string_lossy().to_string(),
+    })
+}
+
 /// Load a tensor from a GGUF file (dequantizes quantized tensors to F32).
+/// Split models are searched shard by shard.
 pub fn load_gguf_tensor(path: &Path, tensor_name: &str) -> Result<Tensor, ModelError> {
-    let file = File::open(path).map_err(ModelError::IoError)?;
-    let mmap = unsafe { Mmap::map(&file).map_err(ModelError::IoError)? };
+    if crate::model::gguf::is_split_path(path) {
+        return load_gguf_tensor_sharded(path, tensor_name);
+    }
+    GgufTensorFile::open(path)?.load(tensor_name)
+}
 
-    let mut reader = GgufReader::new(&mmap);
-    reader.parse_header()?;
+/// A GGUF file mapped and header-parsed once, for loading many of its tensors.
+pub struct GgufTensorFile {
+    path: std::path::PathBuf,
+    mmap: Mmap,
+    tensors: HashMap<String, GgufTensorEntry>,
+    data_offset: u64,
+}
 
-    let tensor_entry = reader
-        .tensors
-        .get(tensor_name)
-        .ok_or_else(|| ModelError::TensorNotFound {
+impl GgufTensorFile {
+    pub fn open(path: &Path) -> Result<Self, ModelError> {
+        let file = File::open(path).map_err(ModelError::IoError)?;
+        let mmap = unsafe { Mmap::map(&file).map_err(ModelError::IoError)? };
+
+        let mut reader = GgufReader::new(&mmap);
+        reader.parse_header()?;
+        let tensors = std::mem::take(&mut reader.tensors);
+        let data_offset = reader.data_offset;
+
+        Ok(Self {
+            path: path.to_path_buf(),
+            mmap,
+            tensors,
+            data_offset,
+        })
+    }
+
+    /// Load a tensor, dequantizing quantized types to F32.
+    pub fn load(&self, tensor_name: &str) -> Result<Tensor, ModelError> {
+        let tensor_entry = self.tensors.get(tensor_name).ok_or_else(|| ModelError::TensorNotFound {
             tensor_name: tensor_name.to_string(),
-            parent_id: path.to_string_lossy().to_string(),
-        })?
-        .clone();
+            parent_id: self.path.to_string_lossy().to_string(),
+        })?;
+        load_gguf_entry(&self.mmap, tensor_entry, self.data_offset, tensor_name)
+    }
+}
+
+/// All shards of a (possibly split) GGUF model, each parsed once.
+pub struct GgufTensorSource {
+    shards: Vec<GgufTensorFile>,
+}
+
+impl GgufTensorSource {
+    pub fn open(path: &Path) -> Result<Self, ModelError> {
+        let shards = crate::model::gguf::split_shard_paths(path)?
+            .iter()
+            .map(|p| GgufTensorFile::open(p))
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(Self { shards })
+    }
 
-    let data_start = reader.data_offset + tensor_entry.offset as usize;
+    pub fn load(&self, tensor_name: &str) -> Result<Tensor, ModelError> {
+        let shard = self
+            .shards
+            .iter()
+            .find(|s| s.tensors.contains_key(tensor_name))
+            .ok_or_else(|| ModelError::TensorNotFound {
+                tensor_name: tensor_name.to_string(),
+                parent_id: self.shards.first().map(|s| s.path.to_string_lossy().to_string()).unwrap_or_default(),
+            })?;
+        shard.load(tensor_name)
+    }
+}
+
+fn load_gguf_entry(
+    mmap: &[u8],
+    tensor_entry: &GgufTensorEntry,
+    data_offset: u64,
+    tensor_name: &str,
+) -> Result<Tensor, ModelError> {
+    let data_start = data_offset.checked_add(tensor_entry.offset).ok_or_else(|| {
+        ModelError::ParseError {
+            format: "GGUF".into(),
+            reason: format!("Offset overflow for tensor '{}'", tensor_name),
+        }
+    })?;
+    let elem_count = checked_elem_count(&tensor_entry.shape, "GGUF")?;
 
     // For quantized types, we need to dequantize to F32
     match tensor_entry.ggml_type {
         0 => {
             // F32
-            let elem_count: usize = tensor_entry.shape.iter().product();
-            let byte_count = elem_count * 4;
-            let bytes = &mmap[data_start..data_start + byte_count];
+            let byte_count = elem_count.saturating_mul(4);
+            let bytes = &mmap[checked_range(data_start, byte_count, mmap.len(), "GGUF")?];
             let data: Vec<f32> = bytes
                 .chunks_exact(4)
                 .map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]))
@@ -193,9 +347,8 @@ pub fn load_gguf_tensor(path: &Path, tensor_name: &str) -> Result<Tensor, ModelE
         }
         1 => {
             // F16 → F32
-            let elem_count: usize = tensor_entry.shape.iter().product();
-            let byte_count = elem_count * 2;
-            let bytes = &mmap[data_start..data_start + byte_count];
+            let byte_count = elem_count.saturating_mul(2);
+            let bytes = &mmap[checked_range(data_start, byte_count, mmap.len(), "GGUF")?];
             let data: Vec<f32> = bytes
                 .chunks_exact(2)
                 .map(|b| {
@@ -208,9 +361,8 @@ pub fn load_gguf_tensor(path: &Path, tensor_name: &str) -> Result<Tensor, ModelE
         }
         30 => {
             // BF16 → F32
-            let elem_count: usize = tensor_entry.shape.iter().product();
-            let byte_count = elem_count * 2;
-            let bytes = &mmap[data_start..data_start + byte_count];
+            let byte_count = elem_count.saturating_mul(2);
+            let bytes = &mmap[checked_range(data_start, byte_count, mmap.len(), "GGUF")?];
             let data: Vec<f32> = bytes
                 .chunks_exact(2)
                 .map(|b| {
@@ -224,7 +376,7 @@ pub fn load_gguf_tensor(path: &Path, tensor_name: &str) -> Result<Tensor, ModelE
         _ => {
             // Quantized types: use candle's built-in GGUF handling
             // For quantized tensors, we load the raw data and dequantize via candle
-            dequantize_ggml_tensor(&mmap, &tensor_entry, data_start)
+            dequantize_ggml_tensor(mmap, tensor_entry, data_start)
         }
     }
 }
@@ -241,7 +393,36 @@ pub fn load_tensor(parent: &ParentModel, tensor_name: &str) -> Result<Tensor, Mo
             }
         }
         ModelFormat::Gguf => load_gguf_tensor(path, tensor_name),
+        ModelFormat::PyTorch => Err(ModelError::MergeError(
+            "PyTorch checkpoints can be inspected but not merged yet; convert to SafeTensors first".into(),
+        )),
+    }
+}
+
+/// Load a tensor by canonical or native name, returned in canonical (HF) layout.
+/// Lets GGUF and SafeTensors parents be merged tensor-for-tensor.
+pub fn load_tensor_canonical(parent: &ParentModel, tensor_name: &str) -> Result<Tensor, ModelError> {
+    let raw_name = names::resolve_name(parent, tensor_name).ok_or_else(|| ModelError::TensorNotFound {
+        tensor_name: tensor_name.to_string(),
+        parent_id: parent.id.clone(),
+    })?;
+    let tensor = load_tensor(parent, &raw_name)?;
+    names::to_canonical(parent, &raw_name, tensor)
+}
+
+/// Load `tensor_name` (as named by `layout`) from `parent`, converted into
+/// `layout`'s native naming and shape convention.
+pub fn load_tensor_in_layout(
+    parent: &ParentModel,
+    tensor_name: &str,
+    layout: &ParentModel,
+) -> Result<Tensor, ModelError> {
+    let native = parent.compat.tensor_metas.iter().any(|t| t.name == tensor_name);
+    if native && parent.format == layout.format {
+        return load_tensor(parent, tensor_name);
     }
+    let tensor = load_tensor_canonical(parent, tensor_name)?;
+    names::to_native(layout, tensor_name, tensor)
 }
 
 /// Get list of all tensor names from a parent model.
@@ -275,11 +456,78 @@ struct GgufTensorEntry {
     offset: u64,
 }
 
+/// A metadata KV whose encoded value sits at `value` in the file.
+#[derive(Debug, Clone)]
+pub struct GgufMetadataEntry {
+    pub key: String,
+    pub value_type: u32,
+    pub value: std::ops::Range<usize>,
+}
+
+/// A tensor info entry; `offset` is relative to the data section.
+#[derive(Debug, Clone)]
+pub struct GgufTensorLayout {
+    pub name: String,
+    pub shape: Vec<usize>,
+    pub ggml_type: u32,
+    pub offset: u64,
+}
+
+/// Raw structure of a GGUF file, in file order, for rewriting it without
+/// decoding any tensor data.
+#[derive(Debug, Clone)]
+pub struct GgufLayout {
+    pub version: u32,
+    pub metadata: Vec<GgufMetadataEntry>,
+    pub tensors: Vec<GgufTensorLayout>,
+    pub alignment: u64,
+    pub data_offset: u64,
+}
+
+/// Parse the header of a GGUF file held in `data`.
+pub fn read_gguf_layout(data: &[u8]) -> Result<GgufLayout, ModelError> {
+    if data.len() < 24 || &data[..4] != b"GGUF" {
+        return Err(ModelError::ParseError {
+            format: "GGUF".into(),
+            reason: "Invalid GGUF magic".into(),
+        });
+    }
+
+    let mut reader = GgufReader::new(data);
+    reader.parse_header()?;
+
+    let mut entries = std::mem::take(&mut reader.tensors);
+    let tensors = reader
+        .tensor_order
+        .iter()
+        .filter_map(|name| {
+            entries.remove(name).map(|e| GgufTensorLayout {
+                name: name.clone(),
+                shape: e.shape,
+                ggml_type: e.ggml_type,
+                offset: e.offset,
+            })
+        })
+        .collect();
+
+    Ok(GgufLayout {
+        version: reader.version,
+        metadata: reader.metadata,
+        tensors,
+        alignment: reader.alignment,
+        data_offset: reader.data_offset,
+    })
+}
+
 struct GgufReader<'a> {
     data: &'a [u8],
     pos: usize,
+    version: u32,
+    metadata: Vec<GgufMetadataEntry>,
     tensors: HashMap<String, GgufTensorEntry>,
-    data_offset: usize,
+    tensor_order: Vec<String>,
+    alignment: u64,
+    data_offset: u64,
 }
 
 impl<'a> GgufReader<'a> {
@@ -287,7 +535,11 @@ impl<'a> GgufReader<'a> {
         Self {
             data,
             pos: 0,
+            version: 0,
+            metadata: Vec::new(),
             tensors: HashMap::new(),
+            tensor_order: Vec::new(),
+            alignment: 32,
             data_offset: 0,
         }
     }
@@ -296,6 +548,7 @@ impl<'a> GgufReader<'a> {
         // Skip magic
         self.pos = 4;
         let version = self.read_u32()?;
+        self.version = version;
 
         let (tensor_count, metadata_kv_count) = if version >= 3 {
             (self.read_u64()?, self.read_u64()?)
@@ -303,11 +556,23 @@ impl<'a> GgufReader<'a> {
             (self.read_u32()? as u64, self.read_u32()? as u64)
         };
 
-        // Skip metadata
+        // Record where each metadata value sits without decoding it
         for _ in 0..metadata_kv_count {
-            self.skip_string()?;
+            let key = self.read_string()?;
             let vtype = self.read_u32()?;
+            let start = self.pos;
             self.skip_value(vtype)?;
+            if key == "general.alignment" && vtype == 4 {
+                let align = u32::from_le_bytes(self.data[start..start + 4].try_into().unwrap());
+                if align > 0 {
+                    self.alignment = align as u64;
+                }
+            }
+            self.metadata.push(GgufMetadataEntry {
+                key,
+                value_type: vtype,
+                value: start..self.pos,
+            });
         }
 
         // Parse tensor entries
@@ -316,11 +581,12 @@ impl<'a> GgufReader<'a> {
             let n_dims = self.read_u32()?;
             let mut shape = Vec::with_capacity(n_dims as usize);
             for _ in 0..n_dims {
-                shape.push(self.read_u64()? as usize);
+                shape.push(offset_to_usize(self.read_u64()?, "GGUF", "Tensor dimension")?);
             }
             let ggml_type = self.read_u32()?;
             let offset = self.read_u64()?;
 
+            self.tensor_order.push(name.clone());
             self.tensors.insert(name, GgufTensorEntry {
                 shape,
                 ggml_type,
@@ -328,13 +594,19 @@ impl<'a> GgufReader<'a> {
             });
         }
 
-        // Align to 32 bytes for data section
-        let alignment = 32;
-        self.data_offset = (self.pos + alignment - 1) / alignment * alignment;
+        // The data section starts at the next alignment boundary
+        self.data_offset = (self.pos as u64).div_ceil(self.alignment) * self.alignment;
 
         Ok(())
     }
 
+    /// Advance the cursor by `len` bytes, rejecting lengths that run past the end.
+    fn advance(&mut self, len: u64) -> Result<(), ModelError> {
+        let range = checked_range(self.pos as u64, len, self.data.len(), "GGUF")?;
+        self.pos = range.end;
+        Ok(())
+    }
+
     fn read_u32(&mut self) -> Result<u32, ModelError> {
         if self.pos + 4 > self.data.len() {
             return Err(ModelError::ParseError {
@@ -360,29 +632,27 @@ impl<'a> GgufReader<'a> {
     }
 
     fn read_string(&mut self) -> Result<String, ModelError> {
-        let len = self.read_u64()? as usize;
-        if self.pos + len > self.data.len() {
-            return Err(ModelError::ParseError {
+        let len = self.read_u64()?;
+        let range = checked_range(self.pos as u64, len, self.data.len(), "GGUF").map_err(|_| {
+            ModelError::ParseError {
                 format: "GGUF".into(),
                 reason: "String extends past end of file".into(),
-            });
-        }
-        let s = String::from_utf8_lossy(&self.data[self.pos..self.pos + len]).to_string();
-        self.pos += len;
-        Ok(s)
+            }
+        })?;
+        self.pos = range.end;
+        Ok(String::from_utf8_lossy(&self.data[range]).to_string())
     }
 
     fn skip_string(&mut self) -> Result<(), ModelError> {
-        let len = self.read_u64()? as usize;
-        self.pos += len;
-        Ok(())
+        let len = self.read_u64()?;
+        self.advance(len)
     }
 
     fn skip_value(&mut self, vtype: u32) -> Result<(), ModelError> {
         match vtype {
-            0 | 1 | 7 => self.pos += 1,     // u8, i8, bool
-            2 | 3 => self.pos += 2,           // u16, i16
-            4 | 5 | 6 => self.pos += 4,       // u32, i32, f32
+            0 | 1 | 7 => self.advance(1)?,    // u8, i8, bool
+            2 | 3 => self.advance(2)?,        // u16, i16
+            4 | 5 | 6 => self.advance(4)?,    // u32, i32, f32
             8 => { self.skip_string()?; }      // string
             9 => {                             // array
                 let elem_type = self.read_u32()?;
@@ -391,7 +661,7 @@ impl<'a> GgufReader<'a> {
                     self.skip_value(elem_type)?;
                 }
             }
-            10 | 11 | 12 => self.pos += 8,    // u64, i64, f64
+            10 | 11 | 12 => self.advance(8)?, // u64, i64, f64
             _ => {
                 return Err(ModelError::ParseError {
                     format: "GGUF".into(),
@@ -407,9 +677,13 @@ impl<'a> GgufReader<'a> {
 fn dequantize_ggml_tensor(
     mmap: &[u8],
     entry: &GgufTensorEntry,
-    data_start: usize,
+    data_start: u64,
 ) -> Result<Tensor, ModelError> {
-    let elem_count: usize = entry.shape.iter().product();
+    let elem_count = offset_to_usize(
+        checked_elem_count(&entry.shape, "GGUF")?,
+        "GGUF",
+        "Tensor element count",
+    )?;
 
     // Calculate the raw byte size for this quantized type
     let block_size = ggml_block_size(entry.ggml_type);
@@ -422,30 +696,29 @@ fn dequantize_ggml_tensor(
         )));
     }
 
-    let num_blocks = (elem_count + block_size - 1) / block_size;
-    let byte_count = num_blocks * type_size;
-
-    if data_start + byte_count > mmap.len() {
-        return Err(ModelError::ParseError {
-            format: "GGUF".into(),
-            reason: format!(
-                "Tensor data extends past file end: {} + {} > {}",
-                data_start, byte_count, mmap.len()
-            ),
-        });
-    }
+    let num_blocks = (elem_count as u64).div_ceil(block_size as u64);
+    let byte_count = num_blocks.saturating_mul(type_size as u64);
 
-    let raw_bytes = &mmap[data_start..data_start + byte_count];
+    let raw_bytes = &mmap[checked_range(data_start, byte_count, mmap.len(), "GGUF")?];
 
     // Dequantize based on type
     let f32_data = match entry.ggml_type {
         2 => dequantize_q4_0(raw_bytes, elem_count),
         3 => dequantize_q4_1(raw_bytes, elem_count),
+        6 => dequantize_q5_0(raw_bytes, elem_count),
+        7 => dequantize_q5_1(raw_bytes, elem_count),
         8 => dequantize_q8_0(raw_bytes, elem_count),
+        10 => dequantize_q2_k(raw_bytes, elem_count),
+        11 => dequantize_q3_k(raw_bytes, elem_count),
+        12 => dequantize_q4_k(raw_bytes, elem_count),
+        13 => dequantize_q5_k(raw_bytes, elem_count),
+        14 => dequantize_q6_k(raw_bytes, elem_count),
+        15 => dequantize_q8_k(raw_bytes, elem_count),
         _ => {
-            // For types we don't have explicit dequantizers for, create zeros
-            // (this is a fallback — real implementations would cover all types)
-            vec![0.0f32; elem_count]
+            return Err(ModelError::CandleError(format!(
+                "Unsupported GGML type {} for dequantization",
+                entry.ggml_type
+            )));
         }
     };
 
@@ -562,6 +835,92 @@ fn dequantize_q4_1(data: &[u8], elem_count: usize) -> Vec<f32> {
     result
 }
 
+/// Unpack one Q5 block: low nibbles from `qs`, 5th bit from the 32-bit `qh` mask.
+/// Element j uses qs[j] low nibble + qh bit j; element j+16 the high nibble + qh bit j+16.
+fn unpack_q5_block(qh: u32, qs: &[u8]) -> [u8; 32] {
+    let mut out = [0u8; 32];
+    for j in 0..16 {
+        let xh_0 = (((qh >> j) << 4) & 0x10) as u8;
+        let xh_1 = ((qh >> (j + 12)) & 0x10) as u8;
+        out[j] = (qs[j] & 0x0F) | xh_0;
+        out[j + 16] = (qs[j] >> 4) | xh_1;
+    }
+    out
+}
+
+fn dequantize_q5_0(data: &[u8], elem_count: usize) -> Vec<f32> {
+    let block_size = 32;
+    let type_size = 22; // 2 scale + 4 qh + 16 data
+    let num_blocks = (elem_count + block_size - 1) / block_size;
+    let mut result = Vec::with_capacity(elem_count);
+
+    for i in 0..num_blocks {
+        let block_offset = i * type_size;
+        if block_offset + type_size > data.len() {
+            break;
+        }
+
+        let scale = half::f16::from_le_bytes([
+            data[block_offset],
+            data[block_offset + 1],
+        ])
+        .to_f32();
+        let qh = u32::from_le_bytes([
+            data[block_offset + 2],
+            data[block_offset + 3],
+            data[block_offset + 4],
+            data[block_offset + 5],
+        ]);
+
+        let qs = &data[block_offset + 6..block_offset + 22];
+        for q in unpack_q5_block(qh, qs) {
+            result.push((q as i32 - 16) as f32 * scale);
+        }
+    }
+
+    result.truncate(elem_count);
+    result
+}
+
+fn dequantize_q5_1(data: &[u8], elem_count: usize) -> Vec<f32> {
+    let block_size = 32;
+    let type_size = 24; // 2 scale + 2 min + 4 qh + 16 data
+    let num_blocks = (elem_count + block_size - 1) / block_size;
+    let mut result = Vec::with_capacity(elem_count);
+
+    for i in 0..num_blocks {
+        let block_offset = i * type_size;
+        if block_offset + type_size > data.len() {
+            break;
+        }
+
+        let scale = half::f16::from_le_bytes([
+            data[block_offset],
+            data[block_offset + 1],
+        ])
+        .to_f32();
+        let min = half::f16::from_le_bytes([
+            data[block_offset + 2],
+            data[block_offset + 3],
+        ])
+        .to_f32();
+        let qh = u32::from_le_bytes([
+            data[block_offset + 4],
+            data[block_offset + 5],
+            data[block_offset + 6],
+            data[block_offset + 7],
+        ]);
+
+        let qs = &data[block_offset + 8..block_offset + 24];
+        for q in unpack_q5_block(qh, qs) {
+            result.push(q as f32 * scale + min);
+        }
+    }
+
+    result.truncate(elem_count);
+    result
+}
+
 fn dequantize_q8_0(data: &[u8], elem_count: usize) -> Vec<f32> {
     let block_size = 32;
     let type_size = 34; // 2 bytes scale + 32 bytes data
@@ -589,3 +948,477 @@ fn dequantize_q8_0(data: &[u8], elem_count: usize) -> Vec<f32> {
     result.truncate(elem_count);
     result
 }
+
+// ── K-quants (256-element super-blocks) ─────────────────
+
+const QK_K: usize = 256;
+
+fn f16_at(data: &[u8], offset: usize) -> f32 {
+    half::f16::from_le_bytes([data[offset], data[offset + 1]]).to_f32()
+}
+
+/// Unpack the 6-bit (scale, min) pair `j` from the 12-byte Q4_K/Q5_K scales array.
+fn get_scale_min_k4(j: usize, q: &[u8]) -> (u8, u8) {
+    if j < 4 {
+        (q[j] & 63, q[j + 4] & 63)
+    } else {
+        (
+            (q[j + 4] & 0x0F) | ((q[j - 4] >> 6) << 4),
+            (q[j + 4] >> 4) | ((q[j] >> 6) << 4),
+        )
+    }
+}
+
+fn dequantize_q2_k(data: &[u8], elem_count: usize) -> Vec<f32> {
+    let type_size = 84; // 16 scales + 64 qs + 2 d + 2 dmin
+    let num_blocks = elem_count.div_ceil(QK_K);
+    let mut result = Vec::with_capacity(num_blocks * QK_K);
+
+    for i in 0..num_blocks {
+        let block = i * type_size;
+        if block + type_size > data.len() {
+            break;
+        }
+        let scales = &data[block..block + 16];
+        let qs = &data[block + 16..block + 80];
+        let d = f16_at(data, block + 80);
+        let dmin = f16_at(data, block + 82);
+
+        let mut is = 0;
+        for n in 0..2 {
+            let q = &qs[n * 32..n * 32 + 32];
+            for shift in [0u32, 2, 4, 6] {
+                for part in 0..2 {
+                    let sc = scales[is];
+                    is += 1;
+                    let dl = d * (sc & 0x0F) as f32;
+                    let ml = dmin * (sc >> 4) as f32;
+                    for l in 0..16 {
+                        let v = (q[part * 16 + l] >> shift) & 3;
+                        result.push(dl * v as f32 - ml);
+                    }
+                }
+            }
+        }
+    }
+
+    result.truncate(elem_count);
+    result
+}
+
+fn dequantize_q3_k(data: &[u8], elem_count: usize) -> Vec<f32> {
+    let type_size = 110; // 32 hmask + 64 qs + 12 scales + 2 d
+    let num_blocks = elem_count.div_ceil(QK_K);
+    let mut result = Vec::with_capacity(num_blocks * QK_K);
+
+    const KMASK1: u32 = 0x0303_0303;
+    const KMASK2: u32 = 0x0f0f_0f0f;
+
+    for i in 0..num_blocks {
+        let block = i * type_size;
+        if block + type_size > data.len() {
+            break;
+        }
+        let hmask = &data[block..block + 32];
+        let qs = &data[block + 32..block + 96];
+        let raw_scales = &data[block + 96..block + 108];
+        let d = f16_at(data, block + 108);
+
+        // Unpack 16 6-bit scales packed into 12 bytes
+        let word = |k: usize| {
+            u32::from_le_bytes([
+                raw_scales[k * 4],
+                raw_scales[k * 4 + 1],
+                raw_scales[k * 4 + 2],
+                raw_scales[k * 4 + 3],
+            ])
+        };
+        let (a0, a1, tmp) = (word(0), word(1), word(2));
+        let aux = [
+            (a0 & KMASK2) | ((tmp & KMASK1) << 4),
+            (a1 & KMASK2) | (((tmp >> 2) & KMASK1) << 4),
+            ((a0 >> 4) & KMASK2) | (((tmp >> 4) & KMASK1) << 4),
+            ((a1 >> 4) & KMASK2) | (((tmp >> 6) & KMASK1) << 4),
+        ];
+        let scales: Vec<i8> = aux.iter().flat_map(|w| w.to_le_bytes()).map(|b| b as i8).collect();
+
+        let mut is = 0;
+        let mut m: u8 = 1;
+        for n in 0..2 {
+            let q = &qs[n * 32..n * 32 + 32];
+            for shift in [0u32, 2, 4, 6] {
+                for part in 0..2 {
+                    let dl = d * (scales[is] as i32 - 32) as f32;
+                    is += 1;
+                    for l in 0..16 {
+                        let idx = part * 16 + l;
+                        let low = ((q[idx] >> shift) & 3) as i32;
+                        let high = if hmask[idx] & m != 0 { 0 } else { 4 };
+                        result.push(dl * (low - high) as f32);
+                    }
+                }
+                m <<= 1;
+            }
+        }
+    }
+
+    result.truncate(elem_count);
+    result
+}
+
+fn dequantize_q4_k(data: &[u8], elem_count: usize) -> Vec<f32> {
+    let type_size = 144; // 2 d + 2 dmin + 12 scales + 128 qs
+    let num_blocks = elem_count.div_ceil(QK_K);
+    let mut result = Vec::with_capacity(num_blocks * QK_K);
+
+    for i in 0..num_blocks {
+        let block = i * type_size;
+        if block + type_size > data.len() {
+            break;
+        }
+        let d = f16_at(data, block);
+        let dmin = f16_at(data, block + 2);
+        let scales = &data[block + 4..block + 16];
+        let qs = &data[block + 16..block + 144];
+
+        for chunk in 0..4 {
+            let q = &qs[chunk * 32..chunk * 32 + 32];
+            let (sc1, m1) = get_scale_min_k4(chunk * 2, scales);
+            let (sc2, m2) = get_scale_min_k4(chunk * 2 + 1, scales);
+            let (d1, min1) = (d * sc1 as f32, dmin * m1 as f32);
+            let (d2, min2) = (d * sc2 as f32, dmin * m2 as f32);
+            for &b in q {
+                result.push(d1 * (b & 0x0F) as f32 - min1);
+            }
+            for &b in q {
+                result.push(d2 * (b >> 4) as f32 - min2);
+            }
+        }
+    }
+
+    result.truncate(elem_count);
+    result
+}
+
+fn dequantize_q5_k(data: &[u8], elem_count: usize) -> Vec<f32> {
+    let type_size = 176; // 2 d + 2 dmin + 12 scales + 32 qh + 128 qs
+    let num_blocks = elem_count.div_ceil(QK_K);
+    let mut result = Vec::with_capacity(num_blocks * QK_K);
+
+    for i in 0..num_blocks {
+        let block = i * type_size;
+        if block + type_size > data.len() {
+            break;
+        }
+        let d = f16_at(data, block);
+        let dmin = f16_at(data, block + 2);
+        let scales = &data[block + 4..block + 16];
+        let qh = &data[block + 16..block + 48];
+        let qs = &data[block + 48..block + 176];
+
+        let mut u1: u8 = 1;
+        let mut u2: u8 = 2;
+        for chunk in 0..4 {
+            let ql = &qs[chunk * 32..chunk * 32 + 32];
+            let (sc1, m1) = get_scale_min_k4(chunk * 2, scales);
+            let (sc2, m2) = get_scale_min_k4(chunk * 2 + 1, scales);
+            let (d1, min1) = (d * sc1 as f32, dmin * m1 as f32);
+            let (d2, min2) = (d * sc2 as f32, dmin * m2 as f32);
+            for l in 0..32 {
+                let hi = if qh[l] & u1 != 0 { 16 } else { 0 };
+                result.push(d1 * ((ql[l] & 0x0F) + hi) as f32 - min1);
+            }
+            for l in 0..32 {
+                let hi = if qh[l] & u2 != 0 { 16 } else { 0 };
+                result.push(d2 * ((ql[l] >> 4) + hi) as f32 - min2);
+            }
+            u1 <<= 2;
+            u2 <<= 2;
+        }
+    }
+
+    result.truncate(elem_count);
+    result
+}
+
+fn dequantize_q6_k(data: &[u8], elem_count: usize) -> Vec<f32> {
+    let type_size = 210; // 128 ql + 64 qh + 16 scales + 2 d
+    let num_blocks = elem_count.div_ceil(QK_K);
+    let mut result = Vec::with_capacity(num_blocks * QK_K);
+
+    for i in 0..num_blocks {
+        let block = i * type_size;
+        if block + type_size > data.len() {
+            break;
+        }
+        let d = f16_at(data, block + 208);
+
+        for n in 0..2 {
+            let ql = &data[block + n * 64..block + n * 64 + 64];
+            let qh = &data[block + 128 + n * 32..block + 128 + n * 32 + 32];
+            let sc = &data[block + 192 + n * 8..block + 192 + n * 8 + 8];
+
+            let mut out = [0.0f32; 128];
+            for l in 0..32 {
+                let is = l / 16;
+                let q1 = ((ql[l] & 0x0F) | ((qh[l] & 3) << 4)) as i32 - 32;
+                let q2 = ((ql[l + 32] & 0x0F) | (((qh[l] >> 2) & 3) << 4)) as i32 - 32;
+                let q3 = ((ql[l] >> 4) | (((qh[l] >> 4) & 3) << 4)) as i32 - 32;
+                let q4 = ((ql[l + 32] >> 4) | (((qh[l] >> 6) & 3) << 4)) as i32 - 32;
+                out[l] = d * (sc[is] as i8) as f32 * q1 as f32;
+                out[l + 32] = d * (sc[is + 2] as i8) as f32 * q2 as f32;
+                out[l + 64] = d * (sc[is + 4] as i8) as f32 * q3 as f32;
+                out[l + 96] = d * (sc[is + 6] as i8) as f32 * q4 as f32;
+            }
+            result.extend_from_slice(&out);
+        }
+    }
+
+    result.truncate(elem_count);
+    result
+}
+
+fn dequantize_q8_k(data: &[u8], elem_count: usize) -> Vec<f32> {
+    let type_size = 292; // 4 d (f32) + 256 qs + 32 bsums
+    let num_blocks = elem_count.div_ceil(QK_K);
+    let mut result = Vec::with_capacity(num_blocks * QK_K);
+
+    for i in 0..num_blocks {
+        let block = i * type_size;
+        if block + type_size > data.len() {
+            break;
+        }
+        let d = f32::from_le_bytes([data[block], data[block + 1], data[block + 2], data[block + 3]]);
+        for j in 0..QK_K {
+            result.push(d * (data[block + 4 + j] as i8) as f32);
+        }
+    }
+
+    result.truncate(elem_count);
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Deterministic stand-in for random quants in `0..range`.
+    fn quants(range: u32) -> Vec<u32> {
+        (0..QK_K as u32).map(|i| (i * 7 + 3) % range).collect()
+    }
+
+    fn f16_bytes(v: f32) -> [u8; 2] {
+        half::f16::from_f32(v).to_le_bytes()
+    }
+
+    fn assert_close(got: &[f32], want: &[f32]) {
+        assert_eq!(got.len(), want.len());
+        for (i, (g, w)) in got.iter().zip(want).enumerate() {
+            assert!((g - w).abs() < 1e-4, "element {}: {} vs {}", i, g, w);
+        }
+    }
+
+    /// Pack eight 6-bit (scale, min) pairs the way Q4_K/Q5_K store them.
+    fn pack_scale_min_k4(sc: &[u8; 8], m: &[u8; 8]) -> [u8; 12] {
+        let mut q = [0u8; 12];
+        for j in 0..4 {
+            q[j] = sc[j] | ((sc[j + 4] >> 4) << 6);
+            q[j + 4] = m[j] | ((m[j + 4] >> 4) << 6);
+            q[j + 8] = (sc[j + 4] & 0x0F) | ((m[j + 4] & 0x0F) << 4);
+        }
+        q
+    }
+
+    const SC8: [u8; 8] = [1, 63, 17, 32, 5, 48, 21, 60];
+    const M8: [u8; 8] = [0, 9, 40, 3, 63, 16, 33, 7];
+
+    #[test]
+    fn scale_min_pairs_unpack() {
+        let packed = pack_scale_min_k4(&SC8, &M8);
+        for j in 0..8 {
+            assert_eq!(get_scale_min_k4(j, &packed), (SC8[j], M8[j]));
+        }
+    }
+
+    #[test]
+    fn q4_k_block_round_trips() {
+        let (d, dmin) = (0.5f32, 0.25f32);
+        let q = quants(16);
+        let mut block = Vec::new();
+        block.extend(f16_bytes(d));
+        block.extend(f16_bytes(dmin));
+        block.extend(pack_scale_min_k4(&SC8, &M8));
+        for chunk in 0..4 {
+            for l in 0..32 {
+                block.push(q[chunk * 64 + l] as u8 | ((q[chunk * 64 + 32 + l] as u8) << 4));
+            }
+        }
+        let want: Vec<f32> = (0..QK_K)
+            .map(|i| d * SC8[i / 32] as f32 * q[i] as f32 - dmin * M8[i / 32] as f32)
+            .collect();
+        assert_close(&dequantize_q4_k(&block, QK_K), &want);
+    }
+
+    #[test]
+    fn q5_k_block_round_trips() {
+        let (d, dmin) = (0.25f32, 0.5f32);
+        let q = quants(32);
+        let mut block = Vec::new();
+        block.extend(f16_bytes(d));
+        block.extend(f16_bytes(dmin));
+        block.extend(pack_scale_min_k4(&SC8, &M8));
+        let mut qh = [0u8; 32];
+        let mut qs = [0u8; 128];
+        for chunk in 0..4 {
+            for l in 0..32 {
+                let (lo, hi) = (q[chunk * 64 + l] as u8, q[chunk * 64 + 32 + l] as u8);
+                qs[chunk * 32 + l] = (lo & 0x0F) | ((hi & 0x0F) << 4);
+                qh[l] |= ((lo >> 4) << (2 * chunk)) | ((hi >> 4) << (2 * chunk + 1));
+            }
+        }
+        block.extend(qh);
+        block.extend(qs);
+        let want: Vec<f32> = (0..QK_K)
+            .map(|i| d * SC8[i / 32] as f32 * q[i] as f32 - dmin * M8[i / 32] as f32)
+            .collect();
+        assert_close(&dequantize_q5_k(&block, QK_K), &want);
+    }
+
+    #[test]
+    fn q6_k_block_round_trips() {
+        let d = 0.125f32;
+        let q = quants(64);
+        let scales: Vec<i8> = (0..16).map(|i| (i as i8 - 8) * 3).collect();
+        let mut ql = [0u8; 128];
+        let mut qh = [0u8; 64];
+        for n in 0..2 {
+            for l in 0..32 {
+                let at = |k: usize| q[n * 128 + k * 32 + l] as u8;
+                ql[n * 64 + l] = (at(0) & 0x0F) | ((at(2) & 0x0F) << 4);
+                ql[n * 64 + l + 32] = (at(1) & 0x0F) | ((at(3) & 0x0F) << 4);
+                qh[n * 32 + l] = (at(0) >> 4) | ((at(1) >> 4) << 2) | ((at(2) >> 4) << 4) | ((at(3) >> 4) << 6);
+            }
+        }
+        let mut block = ql.to_vec();
+        block.extend(qh);
+        block.extend(scales.iter().map(|&s| s as u8));
+        block.extend(f16_bytes(d));
+        let want: Vec<f32> = (0..QK_K)
+            .map(|i| d * scales[i / 16] as f32 * (q[i] as i32 - 32) as f32)
+            .collect();
+        assert_close(&dequantize_q6_k(&block, QK_K), &want);
+    }
+
+    #[test]
+    fn q2_k_block_round_trips() {
+        let (d, dmin) = (0.5f32, 0.125f32);
+        let q = quants(4);
+        let scales: Vec<u8> = (0..16).map(|i| (i as u8 * 5 % 16) | ((15 - i as u8) << 4)).collect();
+        let mut qs = [0u8; 64];
+        for (i, &v) in q.iter().enumerate() {
+            let (n, shift, rest) = (i / 128, (i % 128) / 32, i % 32);
+            qs[n * 32 + rest] |= (v as u8) << (2 * shift);
+        }
+        let mut block = scales.clone();
+        block.extend(qs);
+        block.extend(f16_bytes(d));
+        block.extend(f16_bytes(dmin));
+        let want: Vec<f32> = (0..QK_K)
+            .map(|i| d * (scales[i / 16] & 0x0F) as f32 * q[i] as f32 - dmin * (scales[i / 16] >> 4) as f32)
+            .collect();
+        assert_close(&dequantize_q2_k(&block, QK_K), &want);
+    }
+
+    #[test]
+    fn q3_k_block_round_trips() {
+        let d = 0.25f32;
+        // Stored as 0..8, meaning -4..=3
+        let q = quants(8);
+        let six_bit: Vec<u8> = (0..16).map(|i| (i as u8 * 11 + 7) % 64).collect();
+        let mut scales = [0u8; 12];
+        for (j, &s) in six_bit.iter().enumerate() {
+            if j < 8 {
+                scales[j] = s & 0x0F;
+            } else {
+                scales[j - 8] |= (s & 0x0F) << 4;
+            }
+            scales[j % 4 + 8] |= (s >> 4) << (2 * (j / 4));
+        }
+        let mut hmask = [0u8; 32];
+        let mut qs = [0u8; 64];
+        for (i, &v) in q.iter().enumerate() {
+            let (n, shift, rest) = (i / 128, (i % 128) / 32, i % 32);
+            qs[n * 32 + rest] |= ((v & 3) as u8) << (2 * shift);
+            if v >= 4 {
+                hmask[rest] |= 1 << (n * 4 + shift);
+            }
+        }
+        let mut block = hmask.to_vec();
+        block.extend(qs);
+        block.extend(scales);
+        block.extend(f16_bytes(d));
+        let want: Vec<f32> = (0..QK_K)
+            .map(|i| d * (six_bit[i / 16] as i32 - 32) as f32 * (q[i] as i32 - 4) as f32)
+            .collect();
+        assert_close(&dequantize_q3_k(&block, QK_K), &want);
+    }
+
+    /// Q5 quants 0..32 in order: low nibbles in `qs`, the 5th bit (set for
+    /// 16..32, i.e. the high-nibble half) in `qh`.
+    fn q5_ramp() -> ([u8; 4], [u8; 16]) {
+        let qs = std::array::from_fn(|j| j as u8 | (((j + 16) as u8 & 0x0F) << 4));
+        (0xFFFF_0000u32.to_le_bytes(), qs)
+    }
+
+    #[test]
+    fn q5_tensors_load_from_a_gguf_file() {
+        fn string(out: &mut Vec<u8>, s: &str) {
+            out.extend_from_slice(&(s.len() as u64).to_le_bytes());
+            out.extend_from_slice(s.as_bytes());
+        }
+        let (qh, qs) = q5_ramp();
+        let mut data = b"GGUF".to_vec();
+        data.extend_from_slice(&3u32.to_le_bytes());
+        data.extend_from_slice(&2u64.to_le_bytes());
+        data.extend_from_slice(&0u64.to_le_bytes());
+        for (name, ggml_type, offset) in [("q5_0", 6u32, 0u64), ("q5_1", 7, 32)] {
+            string(&mut data, name);
+            data.extend_from_slice(&1u32.to_le_bytes());
+            data.extend_from_slice(&32u64.to_le_bytes());
+            data.extend_from_slice(&ggml_type.to_le_bytes());
+            data.extend_from_slice(&offset.to_le_bytes());
+        }
+        data.resize(data.len().div_ceil(32) * 32, 0);
+        // Q5_0: d, qh, qs (22 bytes), padded to the next tensor
+        data.extend(f16_bytes(0.5));
+        data.extend(qh);
+        data.extend(qs);
+        data.resize(data.len() + 10, 0);
+        // Q5_1: d, m, qh, qs
+        data.extend(f16_bytes(0.25));
+        data.extend(f16_bytes(-1.0));
+        data.extend(qh);
+        data.extend(qs);
+
+        let path = std::env::temp_dir().join(format!("forgeai-q5-{}.gguf", uuid::Uuid::new_v4()));
+        std::fs::write(&path, data).unwrap();
+        let q5_0 = load_gguf_tensor(&path, "q5_0");
+        let q5_1 = load_gguf_tensor(&path, "q5_1");
+        std::fs::remove_file(&path).unwrap();
+
+        let q5_0 = q5_0.unwrap().to_vec1::<f32>().unwrap();
+        assert_eq!(q5_0.len(), 32);
+        assert_eq!((q5_0[0], q5_0[15], q5_0[16], q5_0[31]), (-8.0, -0.5, 0.0, 7.5));
+
+        let q5_1 = q5_1.unwrap().to_vec1::<f32>().unwrap();
+        assert_eq!(q5_1.len(), 32);
+        assert_eq!((q5_1[0], q5_1[4], q5_1[17], q5_1[31]), (-1.0, 0.0, 3.25, 6.75));
+    }
+
+    #[test]
+    fn unsupported_types_error_instead_of_zero_filling() {
+        let entry = GgufTensorEntry { shape: vec![32], ggml_type: 16, offset: 0 };
+        let err = dequantize_ggml_tensor(&[0u8; 1024], &entry, 0).unwrap_err();
+        assert!(err.to_string().contains("Unsupported GGML type 16"), "{}", err);
+    }
+}
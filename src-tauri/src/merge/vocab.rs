@@ -0,0 +1,456 @@
+use std::collections::HashMap;
+
+use candle_core::Tensor;
+
+use crate::model::error::ModelError;
+use crate::model::ModelFormat;
+
+use super::config::VocabStrategy;
+use super::names;
+use super::profiler::logit_lens;
+use super::projections;
+use super::registry::{ParentModel, ParentRegistry};
+use super::tensor_io;
+
+/// Share of the primary tokenizer another parent must cover before a
+/// transplant stops warning about it.
+const LOW_OVERLAP: f64 = 0.5;
+
+/// Final name component of tensors indexed by token id (canonical names).
+const VOCAB_TENSOR_STEMS: &[&str] = &[
+    "embed_tokens",
+    "lm_head",
+    "wte",
+    "embed_in",
+    "embed_out",
+    "word_embeddings",
+];
+
+/// Common vocab size for a merge whose parents disagree, and the parent whose
+/// tokenizer matches it.
+#[derive(Debug, Clone)]
+pub struct VocabAlignment {
+    pub target: usize,
+    pub tokenizer_parent_id: String,
+    pub sizes: Vec<(String, usize)>,
+    /// Set for `VocabStrategy::Transplant`.
+    pub transplant: Option<TokenTransplant>,
+}
+
+/// Row remapping for merging parents with different tokenizers. Output rows
+/// follow the primary parent's tokenizer.
+#[derive(Debug, Clone)]
+pub struct TokenTransplant {
+    pub primary: ParentModel,
+    /// Per non-primary parent id: for each output row, the row holding the same
+    /// token in that parent, or `None` to take the primary's row.
+    pub row_maps: HashMap<String, Vec<Option<usize>>>,
+    /// (parent id, tokens shared with the primary, primary tokenizer size)
+    pub overlap: Vec<(String, usize, usize)>,
+    /// Parents whose tokenizer could not be read; their rows align by id.
+    pub unreadable: Vec<String>,
+}
+
+/// Whether `name` (native or canonical) is an embedding or output tensor.
+pub fn is_vocab_tensor(name: &str) -> bool {
+    let canonical = names::canonical_name(name);
+    let stem = canonical
+        .strip_suffix(".weight")
+        .or_else(|| canonical.strip_suffix(".bias"))
+        .unwrap_or(&canonical);
+    let last = stem.rsplit('.').next().unwrap_or(stem);
+    VOCAB_TENSOR_STEMS.contains(&last)
+}
+
+/// Axis holding the token dimension in `layout`'s native shape convention.
+/// GGUF stores shapes innermost-first, so the rows come last.
+fn vocab_axis(layout: &ParentModel, rank: usize) -> usize {
+    match layout.format {
+        ModelFormat::Gguf => rank.saturating_sub(1),
+        _ => 0,
+    }
+}
+
+/// Rows of the parent's embedding matrix, which can exceed the tokenizer's
+/// vocab when the model pads it.
+fn embedding_rows(parent: &ParentModel) -> Option<usize> {
+    parent
+        .compat
+        .tensor_metas
+        .iter()
+        .find(|t| is_vocab_tensor(&t.name) && t.shape.len() == 2)
+        .map(|t| t.shape[vocab_axis(parent, t.shape.len())])
+}
+
+/// Work out the alignment needed, or `None` when every parent shares a vocab size.
+pub fn plan_alignment(
+    registry: &ParentRegistry,
+    strategy: VocabStrategy,
+    base_parent_id: Option<&str>,
+) -> Option<VocabAlignment> {
+    let sizes: Vec<(String, usize)> = registry
+        .all()
+        .iter()
+        .filter_map(|p| embedding_rows(p).map(|rows| (p.id.clone(), rows)))
+        .collect();
+    if strategy == VocabStrategy::Transplant {
+        return plan_transplant(registry, base_parent_id, sizes.clone())
+            .or_else(|| plan_alignment(registry, VocabStrategy::Pad, base_parent_id));
+    }
+    if sizes.len() < 2 || sizes.iter().all(|(_, s)| *s == sizes[0].1) {
+        return None;
+    }
+
+    let target = match strategy {
+        VocabStrategy::Pad | VocabStrategy::Transplant => sizes.iter().map(|(_, s)| *s).max()?,
+        VocabStrategy::Truncate => sizes.iter().map(|(_, s)| *s).min()?,
+    };
+
+    // Prefer the base parent's tokenizer when it already has the target size
+    let tokenizer_parent_id = base_parent_id
+        .filter(|id| sizes.iter().any(|(pid, s)| pid == id && *s == target))
+        .map(|id| id.to_string())
+        .or_else(|| sizes.iter().find(|(_, s)| *s == target).map(|(pid, _)| pid.clone()))?;
+
+    Some(VocabAlignment {
+        target,
+        tokenizer_parent_id,
+        sizes,
+        transplant: None,
+    })
+}
+
+/// Map every other parent's vocab onto the primary parent's tokenizer by token
+/// string. `None` when the primary's tokenizer can't be read (the caller falls
+/// back to padding) or when every parent already shares it row for row.
+fn plan_transplant(
+    registry: &ParentRegistry,
+    base_parent_id: Option<&str>,
+    sizes: Vec<(String, usize)>,
+) -> Option<VocabAlignment> {
+    let primary = base_parent_id
+        .and_then(|id| registry.get(id))
+        .or_else(|| registry.all().first())?;
+    let target = embedding_rows(primary)?;
+    let primary_vocab = logit_lens::read_vocab(primary).ok()?;
+
+    let mut row_maps = HashMap::new();
+    let mut overlap = Vec::new();
+    let mut unreadable = Vec::new();
+    for parent in registry.all().iter().filter(|p| p.id != primary.id) {
+        let Some(rows) = embedding_rows(parent) else { continue };
+        let map: Vec<Option<usize>> = match logit_lens::read_vocab(parent) {
+            Ok(vocab) => {
+                let mut index: HashMap<&str, usize> = HashMap::new();
+                for (id, token) in vocab.iter().enumerate() {
+                    if !token.is_empty() {
+                        index.entry(token.as_str()).or_insert(id);
+                    }
+                }
+                let map: Vec<Option<usize>> = (0..target)
+                    .map(|row| {
+                        primary_vocab
+                            .get(row)
+                            .filter(|t| !t.is_empty())
+                            .and_then(|t| index.get(t.as_str()).copied())
+                            .filter(|&src| src < rows)
+                    })
+                    .collect();
+                let shared = map.iter().filter(|m| m.is_some()).count();
+                overlap.push((parent.id.clone(), shared, primary_vocab.len().min(target)));
+                map
+            }
+            Err(_) => {
+                unreadable.push(parent.id.clone());
+                (0..target).map(|row| (row < rows).then_some(row)).collect()
+            }
+        };
+        row_maps.insert(parent.id.clone(), map);
+    }
+
+    let identity = row_maps
+        .values()
+        .all(|map| map.iter().enumerate().all(|(row, src)| *src == Some(row)));
+    if identity && sizes.iter().all(|(_, s)| *s == target) {
+        return None;
+    }
+
+    Some(VocabAlignment {
+        target,
+        tokenizer_parent_id: primary.id.clone(),
+        sizes,
+        transplant: Some(TokenTransplant {
+            primary: primary.clone(),
+            row_maps,
+            overlap,
+            unreadable,
+        }),
+    })
+}
+
+/// Human-readable notes on a transplant: per-parent token overlap (flagging
+/// low overlap) and parents whose tokenizer could not be read.
+pub fn transplant_warnings(alignment: &VocabAlignment, registry: &ParentRegistry) -> Vec<String> {
+    let Some(t) = &alignment.transplant else { return vec![] };
+    let name = |id: &str| registry.get(id).map(|p| p.name.clone()).unwrap_or_else(|| id.to_string());
+    let mut warnings = Vec::new();
+    for (id, shared, total) in &t.overlap {
+        let ratio = if *total == 0 { 0.0 } else { *shared as f64 / *total as f64 };
+        let mut msg = format!(
+            "Token transplant: {} shares {}/{} tokens ({:.0}%) with {}'s tokenizer",
+            name(id),
+            shared,
+            total,
+            ratio * 100.0,
+            t.primary.name
+        );
+        if ratio < LOW_OVERLAP {
+            msg.push_str("; overlap is low, so most embedding rows come from the primary unmerged");
+        }
+        warnings.push(msg);
+    }
+    for id in &t.unreadable {
+        warnings.push(format!(
+            "Token transplant: no tokenizer found for {}; its embedding rows are matched by id",
+            name(id)
+        ));
+    }
+    warnings
+}
+
+impl VocabAlignment {
+    /// Output shape of `name` as written in `layout`'s convention.
+    pub fn aligned_shape(&self, layout: &ParentModel, name: &str, shape: &[usize]) -> Vec<usize> {
+        let mut shape = shape.to_vec();
+        if is_vocab_tensor(name) && !shape.is_empty() {
+            let axis = vocab_axis(layout, shape.len());
+            shape[axis] = self.target;
+        }
+        shape
+    }
+
+    /// Bring `parent`'s vocab tensor (in `layout`'s convention) to the target size:
+    /// remapped by token when transplanting, otherwise zero-padded or truncated.
+    pub fn align(
+        &self,
+        parent: &ParentModel,
+        layout: &ParentModel,
+        name: &str,
+        tensor: Tensor,
+    ) -> Result<Tensor, ModelError> {
+        if !is_vocab_tensor(name) || tensor.dims().is_empty() {
+            return Ok(tensor);
+        }
+        if let Some(t) = &self.transplant {
+            if let Some(map) = t.row_maps.get(&parent.id) {
+                return t.remap(layout, name, &tensor, map);
+            }
+        }
+        let axis = vocab_axis(layout, tensor.dims().len());
+        let rows = tensor.dims()[axis];
+        if rows == self.target {
+            return Ok(tensor);
+        }
+
+        let mut target_shape = tensor.dims().to_vec();
+        target_shape[axis] = self.target;
+        if rows < self.target {
+            projections::zero_pad(&tensor, &target_shape)
+        } else {
+            projections::truncate(&tensor, &target_shape)
+        }
+    }
+}
+
+impl TokenTransplant {
+    /// Rebuild `tensor` with one row per primary token: rows `map` finds in this
+    /// parent are gathered from it, the rest are the primary's own rows.
+    fn remap(
+        &self,
+        layout: &ParentModel,
+        name: &str,
+        tensor: &Tensor,
+        map: &[Option<usize>],
+    ) -> Result<Tensor, ModelError> {
+        let map_err = |e: candle_core::Error| ModelError::CandleError(e.to_string());
+        let axis = vocab_axis(layout, tensor.dims().len());
+        let primary = tensor_io::load_tensor_in_layout(&self.primary, name, layout)?
+            .to_dtype(tensor.dtype())
+            .map_err(map_err)?;
+
+        let mut expected = tensor.dims().to_vec();
+        expected[axis] = map.len();
+        if primary.dims() != expected.as_slice() {
+            return Err(ModelError::MergeError(format!(
+                "Token transplant for {}: primary shape {:?} does not match {:?}",
+                name,
+                primary.dims(),
+                expected
+            )));
+        }
+
+        let device = tensor.device();
+        let src: Vec<u32> = map.iter().map(|m| m.unwrap_or(0) as u32).collect();
+        let keep: Vec<u8> = map.iter().map(|m| m.is_some() as u8).collect();
+        let picked = tensor
+            .index_select(&Tensor::new(src.as_slice(), device).map_err(map_err)?, axis)
+            .map_err(map_err)?;
+
+        let mut mask_shape = vec![1usize; expected.len()];
+        mask_shape[axis] = map.len();
+        let mask = Tensor::new(keep.as_slice(), device)
+            .and_then(|m| m.reshape(mask_shape))
+            .and_then(|m| m.broadcast_as(expected.as_slice()))
+            .and_then(|m| m.contiguous())
+            .map_err(map_err)?;
+        mask.where_cond(&picked, &primary).map_err(map_err)
+    }
+}
+
+/// Apply `alignment` to `parent`'s tensor when present; a no-op otherwise.
+pub fn align(
+    alignment: Option<&VocabAlignment>,
+    parent: &ParentModel,
+    layout: &ParentModel,
+    name: &str,
+    tensor: Tensor,
+) -> Result<Tensor, ModelError> {
+    match alignment {
+        Some(a) => a.align(parent, layout, name, tensor),
+        None => Ok(tensor),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use candle_core::Device;
+
+    use super::*;
+    use crate::merge::registry::{CompatInfo, TensorMeta};
+
+    /// Parent whose only tensor is `name` with `shape` (native convention).
+    fn parent(id: &str, format: ModelFormat, name: &str, shape: &[usize]) -> ParentModel {
+        ParentModel {
+            id: id.into(),
+            slot: 0,
+            name: id.into(),
+            file_path: String::new(),
+            format,
+            file_size: 0,
+            file_size_display: String::new(),
+            parameter_count: 0,
+            parameter_count_display: String::new(),
+            layer_count: None,
+            architecture: None,
+            quantization: None,
+            compat: CompatInfo {
+                architecture: None,
+                hidden_size: None,
+                num_layers: None,
+                num_attention_heads: None,
+                num_kv_heads: None,
+                vocab_size: None,
+                context_length: None,
+                head_dim: None,
+                rope_theta: None,
+                rms_norm_eps: None,
+                tensor_metas: vec![TensorMeta { name: name.into(), shape: shape.to_vec(), dtype: "F32".into() }],
+            },
+            color: String::new(),
+            is_dir: false,
+        }
+    }
+
+    fn alignment(target: usize) -> VocabAlignment {
+        VocabAlignment { target, tokenizer_parent_id: "a".into(), sizes: Vec::new(), transplant: None }
+    }
+
+    #[test]
+    fn pad_target_prefers_the_base_tokenizer() {
+        let registry = ParentRegistry::from_snapshot(vec![
+            parent("a", ModelFormat::SafeTensors, "lm_head.weight", &[8, 4]),
+            parent("b", ModelFormat::Gguf, "output.weight", &[4, 10]),
+            parent("c", ModelFormat::SafeTensors, "lm_head.weight", &[10, 4]),
+        ]);
+        let pad = plan_alignment(&registry, VocabStrategy::Pad, Some("c")).unwrap();
+        assert_eq!((pad.target, pad.tokenizer_parent_id.as_str()), (10, "c"));
+        let pad = plan_alignment(&registry, VocabStrategy::Pad, Some("a")).unwrap();
+        assert_eq!((pad.target, pad.tokenizer_parent_id.as_str()), (10, "b"));
+        let truncate = plan_alignment(&registry, VocabStrategy::Truncate, None).unwrap();
+        assert_eq!((truncate.target, truncate.tokenizer_parent_id.as_str()), (8, "a"));
+    }
+
+    #[test]
+    fn gguf_rows_are_padded_and_truncated_on_the_last_axis() {
+        let layout = parent("a", ModelFormat::Gguf, "output.weight", &[2, 3]);
+        // Innermost-first: 3 tokens of width 2, token r holding [r, r]
+        let tensor = Tensor::new(&[[0f32, 1., 2.], [0., 1., 2.]], &Device::Cpu).unwrap();
+
+        let padded = alignment(4).align(&layout, &layout, "output.weight", tensor.clone()).unwrap();
+        assert_eq!(padded.to_vec2::<f32>().unwrap(), vec![vec![0., 1., 2., 0.]; 2]);
+        assert_eq!(alignment(4).aligned_shape(&layout, "output.weight", &[2, 3]), vec![2, 4]);
+
+        let truncated = alignment(2).align(&layout, &layout, "output.weight", tensor).unwrap();
+        assert_eq!(truncated.to_vec2::<f32>().unwrap(), vec![vec![0., 1.]; 2]);
+    }
+
+    #[test]
+    fn unmapped_rows_come_from_the_primary() {
+        let dir = std::env::temp_dir().join(format!("forgeai-vocab-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let values: Vec<f32> = (0..3).flat_map(|r| [100. + r as f32; 2]).collect();
+        let header = format!(
+            r#"{{"lm_head.weight":{{"dtype":"F32","shape":[3,2],"data_offsets":[0,{}]}}}}"#,
+            values.len() * 4
+        );
+        let mut bytes = (header.len() as u64).to_le_bytes().to_vec();
+        bytes.extend_from_slice(header.as_bytes());
+        bytes.extend(values.iter().flat_map(|v| v.to_le_bytes()));
+        let path = dir.join("primary.safetensors");
+        std::fs::write(&path, bytes).unwrap();
+
+        let primary = ParentModel {
+            file_path: path.to_string_lossy().to_string(),
+            ..parent("primary", ModelFormat::SafeTensors, "lm_head.weight", &[3, 2])
+        };
+        let other = parent("other", ModelFormat::SafeTensors, "lm_head.weight", &[2, 2]);
+        let alignment = VocabAlignment {
+            target: 3,
+            tokenizer_parent_id: "primary".into(),
+            sizes: Vec::new(),
+            transplant: Some(TokenTransplant {
+                primary: primary.clone(),
+                row_maps: HashMap::from([("other".to_string(), vec![None, Some(0), Some(1)])]),
+                overlap: Vec::new(),
+                unreadable: Vec::new(),
+            }),
+        };
+        let tensor = Tensor::new(&[[0f32, 0.], [1., 1.]], &Device::Cpu).unwrap();
+        let remapped = alignment.align(&other, &primary, "lm_head.weight", tensor);
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(
+            remapped.unwrap().to_vec2::<f32>().unwrap(),
+            vec![vec![100., 100.], vec![0., 0.], vec![1., 1.]]
+        );
+    }
+
+    #[test]
+    fn matching_vocabs_need_no_alignment() {
+        let registry = ParentRegistry::from_snapshot(vec![
+            parent("a", ModelFormat::SafeTensors, "lm_head.weight", &[8, 4]),
+            parent("b", ModelFormat::SafeTensors, "lm_head.weight", &[8, 4]),
+        ]);
+        assert!(plan_alignment(&registry, VocabStrategy::Pad, None).is_none());
+
+        let layout = registry.get("a").unwrap();
+        let tensor = Tensor::new(&[[1f32, 2.], [3., 4.]], &Device::Cpu).unwrap();
+        let same = align(None, layout, layout, "lm_head.weight", tensor.clone()).unwrap();
+        assert_eq!(same.to_vec2::<f32>().unwrap(), tensor.to_vec2::<f32>().unwrap());
+        // Non-vocab tensors and rows already at the target pass through
+        let norm = alignment(4).align(layout, layout, "model.norm.weight", tensor.clone()).unwrap();
+        assert_eq!(norm.dims(), &[2, 2]);
+        let at_target = alignment(2).align(layout, layout, "lm_head.weight", tensor).unwrap();
+        assert_eq!(at_target.dims(), &[2, 2]);
+    }
+}
@@ -4,12 +4,13 @@ use std::sync::atomic::Ordering;
 use serde::{Deserialize, Serialize};
 use tauri::{AppHandle, Manager, State};
 
-use crate::merge::capabilities::{self, CapabilityReport};
+use crate::merge::capabilities::{self, BaseDetection, CapabilityReport};
 use crate::merge::compatibility;
-use crate::merge::config::{MergeConfig, MergeMethod, MergeMethodInfo};
+use crate::merge::config::{MergeConfig, MergeMethod, MergeMethodInfo, VocabStrategy};
 use crate::merge::executor::{self, MergeResult};
 use crate::merge::planner;
 use crate::merge::profiler;
+use crate::merge::vocab;
 use crate::merge::registry::ParentModel;
 use crate::model::error::ModelError;
 use crate::model::inspect;
@@ -46,6 +47,7 @@ impl From<&ParentModel> for ParentModelInfo {
             format: match &p.format {
                 ModelFormat::SafeTensors => "safe_tensors".to_string(),
                 ModelFormat::Gguf => "gguf".to_string(),
+                ModelFormat::PyTorch => "pytorch".to_string(),
             },
             file_size: p.file_size,
             file_size_display: p.file_size_display.clone(),
@@ -82,6 +84,8 @@ pub struct TensorComparison {
     pub entries: Vec<TensorComparisonEntry>,
     pub shared_count: usize,
     pub unique_count: usize,
+    /// Element-wise statistics, when a tensor and two parents were requested
+    pub diff: Option<compatibility::TensorDiff>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -200,6 +204,31 @@ pub fn merge_validate_config(
         errors.push(format!("{} requires a base model", config.method.display_name()));
     }
 
+    // NuSLERP interpolates exactly two models; the base doesn't count
+    if config.method == MergeMethod::NuSlerp {
+        let models = config
+            .parents
+            .iter()
+            .filter(|p| Some(p.parent_id.as_str()) != config.base_parent_id.as_deref())
+            .count();
+        if models != 2 {
+            errors.push(format!("NUSLERP merges exactly 2 models (plus an optional base), got {}", models));
+        }
+    }
+
+    if config.method == MergeMethod::Breadcrumbs {
+        let density = config.params.density.unwrap_or(0.9);
+        let gamma = config.params.gamma.unwrap_or(0.01);
+        if density + gamma > 1.0 {
+            warnings.push(format!(
+                "BREADCRUMBS density {:.2} + gamma {:.3} exceeds 1; only {:.0}% of each task vector can be kept",
+                density,
+                gamma,
+                (1.0 - gamma).max(0.0) * 100.0
+            ));
+        }
+    }
+
     // Validate parent weights sum
     let total_weight: f64 = config.parents.iter().map(|p| p.weight).sum();
     if (total_weight - 1.0).abs() > 0.01 && total_weight > 0.0 {
@@ -218,11 +247,30 @@ pub fn merge_validate_config(
         errors.push("Output path is required".to_string());
     }
 
-    // Frankenmerge / Passthrough require layer assignments
-    if matches!(config.method, MergeMethod::Frankenmerge) && config.layer_assignments.is_empty() {
+    errors.extend(planner::check_slices(&config, &registry));
+
+    let (override_errors, override_warnings) = planner::check_weight_overrides(&config, &registry);
+    errors.extend(override_errors);
+    warnings.extend(override_warnings);
+
+    if config.vocab_strategy == VocabStrategy::Transplant {
+        match vocab::plan_alignment(&registry, config.vocab_strategy, config.base_parent_id.as_deref()) {
+            Some(alignment) if alignment.transplant.is_some() => {
+                warnings.extend(vocab::transplant_warnings(&alignment, &registry));
+            }
+            Some(_) => warnings.push(
+                "Token transplant needs the primary parent's tokenizer; falling back to zero-padding".to_string(),
+            ),
+            None => {}
+        }
+    }
+
+    // Frankenmerge / Passthrough require layer assignments or slices
+    let has_layer_recipe = !config.layer_assignments.is_empty() || !config.slices.is_empty();
+    if matches!(config.method, MergeMethod::Frankenmerge) && !has_layer_recipe {
         errors.push("FRANKENMERGE requires layer assignments. Assign each offspring layer to a parent in the Layers tab.".to_string());
     }
-    if matches!(config.method, MergeMethod::Passthrough) && config.layer_assignments.is_empty() {
+    if matches!(config.method, MergeMethod::Passthrough) && !has_layer_recipe {
         warnings.push("PASSTHROUGH with no layer assignments will copy all layers from the first parent.".to_string());
     }
 
@@ -264,7 +312,25 @@ pub async fn merge_execute(
     };
 
     // Build plan from snapshot (lock already released)
-    let plan = planner::build_plan(&config, &snapshot)?;
+    let plan = match planner::build_plan(&config, &snapshot) {
+        Ok(plan) => plan,
+        Err(e) => {
+            state.merge_active.store(false, Ordering::Relaxed);
+            return Err(e);
+        }
+    };
+
+    // Fail before writing anything when the output can't fit
+    if let Ok(estimate) = planner::estimate_output(&config, &snapshot, &plan) {
+        if let Err(e) = crate::commands::ensure_disk_space(
+            std::path::Path::new(&config.output.path),
+            estimate.estimated_bytes,
+            "merge",
+        ) {
+            state.merge_active.store(false, Ordering::Relaxed);
+            return Err(e);
+        }
+    }
 
     let merge_active = state.merge_active.clone();
 
@@ -294,21 +360,24 @@ pub fn merge_cancel(state: State<'_, AppState>) {
 pub async fn merge_profile_layers(
     app: AppHandle,
     parent_id: String,
+    prompt: Option<String>,
     state: State<'_, AppState>,
 ) -> Result<profiler::ProfileResult, ModelError> {
     state.profiler_cancel.store(false, Ordering::Relaxed);
     let cancel = state.profiler_cancel.clone();
 
-    let (tensor_names, total_layers, pid) = {
+    let parent = {
         let registry = state.merge_parents.lock().unwrap();
-        let parent = registry.get(&parent_id).ok_or_else(|| {
+        registry.get(&parent_id).cloned().ok_or_else(|| {
             ModelError::ParentNotFound(parent_id.clone())
-        })?;
-        (parent.compat.tensor_names(), parent.layer_count.unwrap_or(32), parent_id.clone())
+        })?
     };
+    let prompt = prompt
+        .filter(|p| !p.trim().is_empty())
+        .unwrap_or_else(|| profiler::logit_lens::DEFAULT_PROMPT.to_string());
 
     tauri::async_runtime::spawn_blocking(move || {
-        profiler::logit_lens::profile_layers(&app, &pid, &tensor_names, total_layers, cancel)
+        profiler::logit_lens::profile_layers(&app, &parent, &prompt, cancel)
     })
     .await
     .map_err(|e| ModelError::MergeError(format!("Task join error: {}", e)))?
@@ -330,29 +399,47 @@ pub fn merge_preview(
     planner::preview_plan(&config, &registry)
 }
 
+#[tauri::command]
+pub fn merge_estimate_output(
+    config: MergeConfig,
+    state: State<'_, AppState>,
+) -> Result<planner::OutputEstimate, ModelError> {
+    let registry = state.merge_parents.lock().unwrap();
+    let plan = planner::build_plan(&config, &registry)?;
+    planner::estimate_output(&config, &registry, &plan)
+}
+
 #[tauri::command]
 pub fn merge_get_methods() -> Vec<MergeMethodInfo> {
     MergeMethod::all().iter().map(|&m| MergeMethodInfo::from(m)).collect()
 }
 
+/// Which parents hold each tensor. With `tensor_name`, `parent_a` and
+/// `parent_b` also loads that tensor from both and reports how far apart they are.
 #[tauri::command]
 pub fn merge_compare_tensors(
+    tensor_name: Option<String>,
+    parent_a: Option<String>,
+    parent_b: Option<String>,
     state: State<'_, AppState>,
-) -> TensorComparison {
+) -> Result<TensorComparison, ModelError> {
     let registry = state.merge_parents.lock().unwrap();
     let parents = registry.all();
 
     if parents.is_empty() {
-        return TensorComparison {
+        return Ok(TensorComparison {
             entries: vec![],
             shared_count: 0,
             unique_count: 0,
-        };
+            diff: None,
+        });
     }
 
-    // Collect all tensor names with which parents have them
+    // Collect all tensor names with which parents have them, and their shapes
     let mut tensor_parents: std::collections::HashMap<String, Vec<String>> =
         std::collections::HashMap::new();
+    let mut tensor_shapes: std::collections::HashMap<String, Vec<Vec<usize>>> =
+        std::collections::HashMap::new();
 
     for parent in parents {
         for meta in &parent.compat.tensor_metas {
@@ -360,6 +447,10 @@ pub fn merge_compare_tensors(
                 .entry(meta.name.clone())
                 .or_default()
                 .push(parent.id.clone());
+            tensor_shapes
+                .entry(meta.name.clone())
+                .or_default()
+                .push(meta.shape.clone());
         }
     }
 
@@ -376,19 +467,39 @@ pub fn merge_compare_tensors(
             } else {
                 unique_count += 1;
             }
+            let shapes_match = tensor_shapes
+                .get(&name)
+                .is_some_and(|shapes| shapes.windows(2).all(|w| w[0] == w[1]));
             TensorComparisonEntry {
                 tensor_name: name,
                 present_in,
-                shapes_match: true, // Simplified; full impl would compare shapes
+                shapes_match,
             }
         })
         .collect();
 
-    TensorComparison {
+    // Clone the two parents and release the lock before loading tensor data
+    let diff_inputs = match (tensor_name, parent_a, parent_b) {
+        (Some(name), Some(a), Some(b)) => {
+            let a = registry.get(&a).cloned().ok_or(ModelError::ParentNotFound(a))?;
+            let b = registry.get(&b).cloned().ok_or(ModelError::ParentNotFound(b))?;
+            Some((name, a, b))
+        }
+        _ => None,
+    };
+    drop(registry);
+
+    let diff = match diff_inputs {
+        Some((name, a, b)) => Some(compatibility::tensor_diff(&name, &a, &b)?),
+        None => None,
+    };
+
+    Ok(TensorComparison {
         entries,
         shared_count,
         unique_count,
-    }
+        diff,
+    })
 }
 
 #[tauri::command]
@@ -408,7 +519,9 @@ pub async fn merge_analyze_layers(
     };
 
     // Detect capabilities first for capability-aware classification
-    let cap_report = capabilities::detect_capabilities(&parent);
+    let rules = crate::commands::user_capability_rules(&app)?;
+    let gguf_metadata = capabilities::load_gguf_metadata(&parent);
+    let cap_report = capabilities::detect_capabilities(&parent, gguf_metadata.as_ref(), &rules);
 
     // Run on a blocking thread to avoid freezing the UI
     tauri::async_runtime::spawn_blocking(move || {
@@ -418,6 +531,24 @@ pub async fn merge_analyze_layers(
     .map_err(|e| ModelError::MergeError(format!("Task join error: {}", e)))?
 }
 
+/// Write an analysis to `path` as `csv` (one row per layer) or `json` (the full result).
+#[tauri::command]
+pub fn merge_export_analysis(
+    result: profiler::tensor_analysis::AnalysisResult,
+    path: String,
+    format: String,
+) -> Result<(), ModelError> {
+    let content = match format.as_str() {
+        "csv" => profiler::tensor_analysis::analysis_to_csv(&result),
+        "json" => serde_json::to_string_pretty(&result)
+            .map_err(|e| ModelError::MergeError(format!("Failed to serialize analysis: {}", e)))?,
+        other => {
+            return Err(ModelError::MergeError(format!("Unsupported export format: {}", other)));
+        }
+    };
+    std::fs::write(&path, content).map_err(ModelError::IoError)
+}
+
 #[tauri::command]
 pub fn merge_get_categories() -> Vec<profiler::tensor_analysis::LayerCategory> {
     profiler::tensor_analysis::all_categories()
@@ -426,13 +557,43 @@ pub fn merge_get_categories() -> Vec<profiler::tensor_analysis::LayerCategory> {
 #[tauri::command]
 pub fn merge_detect_capabilities(
     parent_id: String,
+    app: AppHandle,
     state: State<'_, AppState>,
 ) -> Result<CapabilityReport, ModelError> {
-    let registry = state.merge_parents.lock().unwrap();
-    let parent = registry.get(&parent_id).ok_or_else(|| {
-        ModelError::ParentNotFound(parent_id.clone())
-    })?;
-    Ok(capabilities::detect_capabilities(parent))
+    let rules = crate::commands::user_capability_rules(&app)?;
+    let parent = {
+        let registry = state.merge_parents.lock().unwrap();
+        registry.get(&parent_id).ok_or_else(|| {
+            ModelError::ParentNotFound(parent_id.clone())
+        })?.clone()
+    };
+    let gguf_metadata = capabilities::load_gguf_metadata(&parent);
+    Ok(capabilities::detect_capabilities(&parent, gguf_metadata.as_ref(), &rules))
+}
+
+/// Suggest which loaded parent is the base model for base-requiring methods.
+/// Capability detection runs on every parent so chat-tuned ones rank lower.
+#[tauri::command]
+pub fn merge_suggest_base(
+    app: AppHandle,
+    state: State<'_, AppState>,
+) -> Result<BaseDetection, ModelError> {
+    let rules = crate::commands::user_capability_rules(&app)?;
+    let parents = {
+        let registry = state.merge_parents.lock().unwrap();
+        if registry.len() < 2 {
+            return Err(ModelError::MergeError("Need at least 2 parents to detect a base model".into()));
+        }
+        registry.all().to_vec()
+    };
+    let reports: Vec<CapabilityReport> = parents
+        .iter()
+        .map(|p| {
+            let gguf_metadata = capabilities::load_gguf_metadata(p);
+            capabilities::detect_capabilities(p, gguf_metadata.as_ref(), &rules)
+        })
+        .collect();
+    Ok(capabilities::detect_base(&parents, &reports))
 }
 
 #[tauri::command]
@@ -461,7 +622,7 @@ pub fn merge_get_layer_components(
                     let component = inspect::classify_tensor(&meta.name);
                     match component {
                         "attention" => attn.push(meta.name.clone()),
-                        "mlp" => mlp.push(meta.name.clone()),
+                        "mlp" | "expert" => mlp.push(meta.name.clone()),
                         "norm" => norm.push(meta.name.clone()),
                         _ => other.push(meta.name.clone()),
                     }
@@ -36,6 +36,10 @@ pub enum ModelError {
     TrainingError(String),
     #[error("Training cancelled")]
     TrainingCancelled,
+    #[error("{0} cancelled")]
+    Cancelled(String),
+    #[error("HuggingFace access denied: {0}")]
+    HubAuth(String),
 }
 
 impl Serialize for ModelError {
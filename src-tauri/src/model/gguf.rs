@@ -1,6 +1,6 @@
 use std::collections::HashMap;
 use std::fs::File;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 
 use memmap2::Mmap;
 
@@ -129,7 +129,6 @@ enum GgufValue {
     Float32(f32),
     Bool(bool),
     String(String),
-    #[allow(dead_code)]
     Array(Vec<GgufValue>),
     Uint64(u64),
     Int64(i64),
@@ -155,6 +154,31 @@ impl GgufValue {
         }
     }
 
+    /// Run-length summary of a numeric array, e.g. `[32×36]` for 36 equal
+    /// entries or `[16, 24×2, 0]`. None for string or nested arrays.
+    fn array_summary(&self) -> Option<String> {
+        let items = match self {
+            GgufValue::Array(items) => items,
+            _ => return None,
+        };
+        let mut runs: Vec<(String, usize)> = Vec::new();
+        for item in items {
+            if matches!(item, GgufValue::String(_) | GgufValue::Array(_)) {
+                return None;
+            }
+            let value = item.as_string()?;
+            match runs.last_mut() {
+                Some((last, count)) if *last == value => *count += 1,
+                _ => runs.push((value, 1)),
+            }
+        }
+        let parts: Vec<String> = runs
+            .into_iter()
+            .map(|(value, count)| if count > 1 { format!("{}×{}", value, count) } else { value })
+            .collect();
+        Some(format!("[{}]", parts.join(", ")))
+    }
+
     fn as_u64(&self) -> Option<u64> {
         match self {
             GgufValue::Uint8(v) => Some(*v as u64),
@@ -206,17 +230,8 @@ fn read_value(reader: &mut Reader, value_type: u32) -> Result<GgufValue, ModelEr
     }
 }
 
-pub fn parse(path: &Path) -> Result<ModelInfo, ModelError> {
-    let file = File::open(path)?;
-    let file_size = file.metadata()?.len();
-
-    if file_size < 24 {
-        return Err(ModelError::FileTooSmall(file_size));
-    }
-
-    let mmap = unsafe { Mmap::map(&file)? };
-    let mut reader = Reader::new(&mmap);
-
+/// Validate the magic and version, then read the tensor count and all metadata KVs.
+fn read_header(reader: &mut Reader) -> Result<(u64, HashMap<String, GgufValue>), ModelError> {
     // Validate magic
     let magic = reader.read_bytes(4)?;
     if magic != GGUF_MAGIC {
@@ -256,10 +271,140 @@ pub fn parse(path: &Path) -> Result<ModelInfo, ModelError> {
     for _ in 0..metadata_kv_count {
         let key = reader.read_string()?;
         let value_type = reader.read_u32()?;
-        let value = read_value(&mut reader, value_type)?;
+        let value = read_value(reader, value_type)?;
         kv_map.insert(key, value);
     }
 
+    Ok((tensor_count, kv_map))
+}
+
+impl GgufValue {
+    fn to_json(&self) -> serde_json::Value {
+        use serde_json::Value;
+        match self {
+            GgufValue::Uint8(v) => Value::from(*v),
+            GgufValue::Int8(v) => Value::from(*v),
+            GgufValue::Uint16(v) => Value::from(*v),
+            GgufValue::Int16(v) => Value::from(*v),
+            GgufValue::Uint32(v) => Value::from(*v),
+            GgufValue::Int32(v) => Value::from(*v),
+            GgufValue::Float32(v) => Value::from(*v as f64),
+            GgufValue::Bool(v) => Value::from(*v),
+            GgufValue::String(s) => Value::from(s.clone()),
+            GgufValue::Array(items) => Value::Array(items.iter().map(|v| v.to_json()).collect()),
+            GgufValue::Uint64(v) => Value::from(*v),
+            GgufValue::Int64(v) => Value::from(*v),
+            GgufValue::Float64(v) => Value::from(*v),
+        }
+    }
+}
+
+// ── Split Files ──────────────────────────────────────
+
+/// Parse `<prefix>-NNNNN-of-MMMMM.gguf` into (prefix, index, total, digit width).
+fn split_name_parts(path: &Path) -> Option<(String, u32, u32, usize)> {
+    let name = path.file_name()?.to_str()?;
+    let stem = name.strip_suffix(".gguf").or_else(|| name.strip_suffix(".GGUF"))?;
+    let (left, total) = stem.rsplit_once("-of-")?;
+    let (prefix, index) = left.rsplit_once('-')?;
+    let all_digits = |s: &str| !s.is_empty() && s.bytes().all(|b| b.is_ascii_digit());
+    if !all_digits(total) || !all_digits(index) {
+        return None;
+    }
+    Some((prefix.to_string(), index.parse().ok()?, total.parse().ok()?, total.len()))
+}
+
+/// True for a shard of a split GGUF (`model-00001-of-00003.gguf`).
+pub fn is_split_path(path: &Path) -> bool {
+    split_name_parts(path).is_some_and(|(_, _, total, _)| total > 1)
+}
+
+/// Every shard of the split GGUF containing `path`, first shard first.
+/// A non-split file yields just itself. Errors if a sibling shard is missing.
+pub fn split_shard_paths(path: &Path) -> Result<Vec<PathBuf>, ModelError> {
+    let Some((prefix, _, total, width)) = split_name_parts(path).filter(|p| p.2 > 1) else {
+        return Ok(vec![path.to_path_buf()]);
+    };
+    let dir = path.parent().unwrap_or(Path::new(""));
+    (1..=total)
+        .map(|i| {
+            let shard = dir.join(format!("{}-{:0w$}-of-{:0w$}.gguf", prefix, i, total, w = width));
+            if shard.exists() {
+                Ok(shard)
+            } else {
+                Err(ModelError::FileNotFound(shard.to_string_lossy().to_string()))
+            }
+        })
+        .collect()
+}
+
+/// The file holding the model-wide KV metadata: the first shard of a split.
+fn metadata_path(path: &Path) -> PathBuf {
+    split_shard_paths(path)
+        .ok()
+        .and_then(|shards| shards.into_iter().next())
+        .unwrap_or_else(|| path.to_path_buf())
+}
+
+/// Every metadata KV with its full value, arrays included.
+pub fn read_metadata(path: &Path) -> Result<HashMap<String, serde_json::Value>, ModelError> {
+    let file = File::open(metadata_path(path))?;
+    let mmap = unsafe { Mmap::map(&file)? };
+    let mut reader = Reader::new(&mmap);
+    let (_, kv_map) = read_header(&mut reader)?;
+    Ok(kv_map.into_iter().map(|(k, v)| (k, v.to_json())).collect())
+}
+
+/// The Jinja chat template embedded as `tokenizer.chat_template`, if any.
+pub fn read_chat_template(path: &Path) -> Option<String> {
+    let file = File::open(metadata_path(path)).ok()?;
+    let mmap = unsafe { Mmap::map(&file).ok()? };
+    let mut reader = Reader::new(&mmap);
+    let (_, kv_map) = read_header(&mut reader).ok()?;
+    match kv_map.get("tokenizer.chat_template") {
+        Some(GgufValue::String(s)) => Some(s.clone()),
+        _ => None,
+    }
+}
+
+/// Token strings from `tokenizer.ggml.tokens`, indexed by token id.
+pub fn read_token_list(path: &Path) -> Result<Vec<String>, ModelError> {
+    let file = File::open(metadata_path(path))?;
+    let mmap = unsafe { Mmap::map(&file)? };
+    let mut reader = Reader::new(&mmap);
+    let (_, kv_map) = read_header(&mut reader)?;
+
+    match kv_map.get("tokenizer.ggml.tokens") {
+        Some(GgufValue::Array(tokens)) => Ok(tokens
+            .iter()
+            .map(|t| match t {
+                GgufValue::String(s) => s.clone(),
+                _ => String::new(),
+            })
+            .collect()),
+        _ => Err(ModelError::ParseError {
+            format: "GGUF".into(),
+            reason: "No tokenizer.ggml.tokens in metadata".into(),
+        }),
+    }
+}
+
+/// Read one file's header and tensor infos: (file size, KV map, tensors, params).
+fn parse_file(
+    path: &Path,
+) -> Result<(u64, HashMap<String, GgufValue>, Vec<TensorInfo>, u64), ModelError> {
+    let file = File::open(path)?;
+    let file_size = file.metadata()?.len();
+
+    if file_size < 24 {
+        return Err(ModelError::FileTooSmall(file_size));
+    }
+
+    let mmap = unsafe { Mmap::map(&file)? };
+    let mut reader = Reader::new(&mmap);
+
+    let (tensor_count, kv_map) = read_header(&mut reader)?;
+
     // Parse tensor info entries
     let mut tensors = Vec::new();
     let mut total_params: u64 = 0;
@@ -287,11 +432,54 @@ pub fn parse(path: &Path) -> Result<ModelInfo, ModelError> {
 
         tensors.push(TensorInfo {
             name,
-            dtype: ggml_type_name(ggml_type).to_string(),
+            dtype: ggml_type_name(ggml_type),
             shape,
         });
     }
 
+    Ok((file_size, kv_map, tensors, total_params))
+}
+
+/// Parse a GGUF file. For split models any shard may be given: metadata comes
+/// from the first shard and tensors, parameters and sizes are summed over all.
+pub fn parse(path: &Path) -> Result<ModelInfo, ModelError> {
+    let shards = split_shard_paths(path)?;
+    let (mut file_size, kv_map, mut tensors, mut total_params) = parse_file(&shards[0])?;
+
+    for shard in &shards[1..] {
+        let (size, _, shard_tensors, params) = parse_file(shard)?;
+        file_size += size;
+        total_params += params;
+        tensors.extend(shard_tensors);
+    }
+
+    let tensor_count = tensors.len() as u64;
+    if let Some(expected) = kv_map.get("split.tensors.count").and_then(|v| v.as_u64()) {
+        if expected != tensor_count {
+            return Err(ModelError::ParseError {
+                format: "GGUF".into(),
+                reason: format!(
+                    "Split model declares {} tensors but its {} shard(s) contain {}",
+                    expected,
+                    shards.len(),
+                    tensor_count
+                ),
+            });
+        }
+    }
+    if let Some(count) = kv_map.get("split.count").and_then(|v| v.as_u64()) {
+        if count as usize != shards.len() {
+            return Err(ModelError::ParseError {
+                format: "GGUF".into(),
+                reason: format!(
+                    "Split model declares {} shards but {} were found; keep the -NNNNN-of-NNNNN names intact",
+                    count,
+                    shards.len()
+                ),
+            });
+        }
+    }
+
     tensors.sort_by(|a, b| a.name.cmp(&b.name));
 
     // Extract well-known metadata
@@ -314,26 +502,33 @@ pub fn parse(path: &Path) -> Result<ModelInfo, ModelError> {
         .and_then(|v| v.as_u64());
 
     let file_type = kv_map.get("general.file_type").and_then(|v| v.as_u64());
-    let quantization = file_type.map(|ft| gguf_file_type_name(ft).to_string());
+    let quantization = file_type
+        .map(|ft| gguf_file_type_name(ft).to_string())
+        .or_else(|| dominant_weight_type(&tensors));
 
-    // Build general metadata for display
+    // Build general metadata for display. Arrays are recorded by length as
+    // `{key}_count`; numeric ones (per-layer head counts etc.) also get a
+    // run-length summary under their own key.
     let mut metadata = HashMap::new();
     for (key, value) in &kv_map {
-        if let Some(s) = value.as_string() {
-            // Skip very long values
-            if s.len() <= 500 {
+        if let GgufValue::Array(items) = value {
+            metadata.insert(format!("{}_count", key), items.len().to_string());
+            if let Some(summary) = value.array_summary().filter(|s| s.len() <= 500) {
+                metadata.insert(key.clone(), summary);
+            }
+        } else if let Some(s) = value.as_string() {
+            // Skip very long values; chat templates are needed in full
+            if s.len() <= 500 || key == "tokenizer.chat_template" {
                 metadata.insert(key.clone(), s);
             }
         }
     }
+    if shards.len() > 1 {
+        metadata.insert("split.count".to_string(), shards.len().to_string());
+    }
 
-    // Extract tokenizer vocab size and resolve special token names
+    // Resolve special token names
     if let Some(GgufValue::Array(tokens)) = kv_map.get("tokenizer.ggml.tokens") {
-        metadata.insert(
-            "tokenizer.ggml.tokens_count".to_string(),
-            tokens.len().to_string(),
-        );
-
         let special_keys = [
             "tokenizer.ggml.bos_token_id",
             "tokenizer.ggml.eos_token_id",
@@ -352,6 +547,7 @@ pub fn parse(path: &Path) -> Result<ModelInfo, ModelError> {
         }
     }
 
+    let path = shards[0].as_path();
     Ok(ModelInfo {
         file_name: path
             .file_name()
@@ -373,7 +569,7 @@ pub fn parse(path: &Path) -> Result<ModelInfo, ModelError> {
         metadata,
         tensor_preview: tensors.iter().take(50).cloned().collect(),
         all_tensors: tensors,
-        shard_count: None,
+        shard_count: (shards.len() > 1).then_some(shards.len() as u32),
         has_tokenizer: None,
         has_config: None,
         model_type: None,
@@ -381,8 +577,10 @@ pub fn parse(path: &Path) -> Result<ModelInfo, ModelError> {
     })
 }
 
-fn ggml_type_name(t: u32) -> &'static str {
-    match t {
+/// Per-tensor ggml type. Unknown ids keep their number so different
+/// unrecognised types aren't lumped together in the quant distribution.
+fn ggml_type_name(t: u32) -> String {
+    let name = match t {
         0 => "F32",
         1 => "F16",
         2 => "Q4_0",
@@ -412,8 +610,31 @@ fn ggml_type_name(t: u32) -> &'static str {
         28 => "F64",
         29 => "IQ1_M",
         30 => "BF16",
-        _ => "UNKNOWN",
+        31 => "Q4_0_4_4",
+        32 => "Q4_0_4_8",
+        33 => "Q4_0_8_8",
+        34 => "TQ1_0",
+        35 => "TQ2_0",
+        36 => "IQ4_NL_4_4",
+        37 => "IQ4_NL_4_8",
+        38 => "IQ4_NL_8_8",
+        39 => "MXFP4",
+        other => return format!("TYPE_{}", other),
+    };
+    name.to_string()
+}
+
+/// Model-level label for files without `general.file_type`: the type holding
+/// the most weights among 2D tensors (1D norms and biases stay F32 in any quant).
+fn dominant_weight_type(tensors: &[TensorInfo]) -> Option<String> {
+    let mut params: HashMap<&str, u64> = HashMap::new();
+    for t in tensors.iter().filter(|t| t.shape.len() >= 2) {
+        *params.entry(t.dtype.as_str()).or_insert(0) += t.shape.iter().product::<u64>();
     }
+    params
+        .into_iter()
+        .max_by_key(|(_, count)| *count)
+        .map(|(dtype, _)| dtype.to_string())
 }
 
 fn gguf_file_type_name(ft: u64) -> &'static str {
@@ -447,3 +668,103 @@ fn gguf_file_type_name(ft: u64) -> &'static str {
         _ => "UNKNOWN",
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn numeric_arrays_are_run_length_summarized() {
+        let uniform = GgufValue::Array(vec![GgufValue::Uint32(32); 36]);
+        assert_eq!(uniform.array_summary().as_deref(), Some("[32×36]"));
+
+        let mixed = GgufValue::Array(vec![
+            GgufValue::Int32(16),
+            GgufValue::Int32(24),
+            GgufValue::Int32(24),
+            GgufValue::Int32(0),
+        ]);
+        assert_eq!(mixed.array_summary().as_deref(), Some("[16, 24×2, 0]"));
+
+        let strings = GgufValue::Array(vec![GgufValue::String("a".into())]);
+        assert_eq!(strings.array_summary(), None);
+    }
+
+    /// Header-only GGUF v3 with one `general.architecture` key; parsing never
+    /// touches tensor data.
+    fn gguf_header(tensors: &[(&str, &[u64], u32)]) -> Vec<u8> {
+        fn string(out: &mut Vec<u8>, s: &str) {
+            out.extend_from_slice(&(s.len() as u64).to_le_bytes());
+            out.extend_from_slice(s.as_bytes());
+        }
+        let mut out = b"GGUF".to_vec();
+        out.extend_from_slice(&3u32.to_le_bytes());
+        out.extend_from_slice(&(tensors.len() as u64).to_le_bytes());
+        out.extend_from_slice(&1u64.to_le_bytes());
+        string(&mut out, "general.architecture");
+        out.extend_from_slice(&8u32.to_le_bytes());
+        string(&mut out, "llama");
+        for (name, shape, ggml_type) in tensors {
+            string(&mut out, name);
+            out.extend_from_slice(&(shape.len() as u32).to_le_bytes());
+            for dim in *shape {
+                out.extend_from_slice(&dim.to_le_bytes());
+            }
+            out.extend_from_slice(&ggml_type.to_le_bytes());
+            out.extend_from_slice(&0u64.to_le_bytes());
+        }
+        out
+    }
+
+    #[test]
+    fn split_models_report_their_shard_count() {
+        let dir = std::env::temp_dir().join(format!("forgeai-split-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let first = dir.join("model-00001-of-00002.gguf");
+        std::fs::write(&first, gguf_header(&[("token_embd.weight", &[8, 4], 0)])).unwrap();
+        std::fs::write(dir.join("model-00002-of-00002.gguf"), gguf_header(&[("output.weight", &[8, 4], 0)])).unwrap();
+        let single = dir.join("single.gguf");
+        std::fs::write(&single, gguf_header(&[("output.weight", &[8, 4], 0)])).unwrap();
+
+        let split = parse(&dir.join("model-00002-of-00002.gguf"));
+        let single = parse(&single);
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        let split = split.unwrap();
+        assert_eq!(split.shard_count, Some(2));
+        assert_eq!(split.tensor_count, 2);
+        assert_eq!(split.file_path, first.to_string_lossy());
+        assert_eq!(single.unwrap().shard_count, None);
+    }
+
+    #[test]
+    fn mixed_quant_file_keeps_per_tensor_types() {
+        // A Q4_K_M-style mix: Q4_K bulk, Q6_K for ffn_down and output, F32 norms
+        let square: &[u64] = &[256, 256];
+        let data = gguf_header(&[
+            ("token_embd.weight", square, 12),
+            ("blk.0.attn_norm.weight", &[256], 0),
+            ("blk.0.attn_q.weight", square, 12),
+            ("blk.0.attn_k.weight", square, 12),
+            ("blk.0.ffn_down.weight", square, 14),
+            ("output_norm.weight", &[256], 0),
+            ("output.weight", square, 14),
+        ]);
+        let path = std::env::temp_dir().join(format!("forgeai-mixed-{}.gguf", uuid::Uuid::new_v4()));
+        std::fs::write(&path, data).unwrap();
+        let info = parse(&path);
+        std::fs::remove_file(&path).unwrap();
+        let info = info.unwrap();
+
+        let data = crate::model::inspect::analyze(&info.all_tensors, &info.metadata);
+        let rows: Vec<(&str, u64, u64)> = data
+            .quant_distribution
+            .iter()
+            .map(|q| (q.dtype.as_str(), q.count, q.total_bytes))
+            .collect();
+        assert_eq!(
+            rows,
+            [("Q4_K", 3, 3 * 36_864), ("Q6_K", 2, 2 * 53_760), ("F32", 2, 2 * 1024)]
+        );
+    }
+}
@@ -4,34 +4,38 @@ use serde::{Deserialize, Serialize};
 
 use super::TensorInfo;
 
-/// Bits per weight for each GGML/quantization type.
+/// Bits per weight for each GGML/quantization type: block bytes * 8 / block
+/// elements, so K-quant and IQ tensors are sized exactly.
 pub fn bits_per_weight(dtype: &str) -> f64 {
     match dtype {
         "F32" => 32.0,
         "F16" => 16.0,
         "BF16" => 16.0,
         "F64" => 64.0,
-        "Q4_0" => 4.5,
+        "Q4_0" | "Q4_0_4_4" | "Q4_0_4_8" | "Q4_0_8_8" => 4.5,
         "Q4_1" => 5.0,
         "Q5_0" => 5.5,
         "Q5_1" => 6.0,
         "Q8_0" => 8.5,
         "Q8_1" => 9.0,
-        "Q2_K" => 2.56,
-        "Q3_K" => 3.44,
+        "Q2_K" => 2.625,
+        "Q3_K" => 3.4375,
         "Q4_K" => 4.5,
         "Q5_K" => 5.5,
-        "Q6_K" => 6.56,
-        "Q8_K" => 8.5,
-        "IQ1_S" => 1.56,
+        "Q6_K" => 6.5625,
+        "Q8_K" => 9.125,
+        "IQ1_S" => 1.5625,
         "IQ1_M" => 1.75,
-        "IQ2_XXS" => 2.06,
-        "IQ2_XS" => 2.31,
-        "IQ2_S" => 2.5,
-        "IQ3_XXS" => 3.06,
-        "IQ3_S" => 3.44,
-        "IQ4_NL" => 4.5,
+        "IQ2_XXS" => 2.0625,
+        "IQ2_XS" => 2.3125,
+        "IQ2_S" => 2.5625,
+        "IQ3_XXS" => 3.0625,
+        "IQ3_S" => 3.4375,
+        "IQ4_NL" | "IQ4_NL_4_4" | "IQ4_NL_4_8" | "IQ4_NL_8_8" => 4.5,
         "IQ4_XS" => 4.25,
+        "TQ1_0" => 1.6875,
+        "TQ2_0" => 2.0625,
+        "MXFP4" => 4.25,
         "I8" => 8.0,
         "I16" => 16.0,
         "I32" => 32.0,
@@ -66,10 +70,41 @@ pub fn format_bytes(bytes: u64) -> String {
     }
 }
 
+/// Whether a tensor belongs to a vision tower / multimodal projector.
+pub fn is_vision_tensor(name: &str) -> bool {
+    let lower = name.to_lowercase();
+    lower.contains("visual")
+        || lower.contains("image_")
+        || lower.contains("vision_")
+        || lower.contains("vit.")
+        || lower.contains("clip.")
+}
+
+/// Whether a tensor is a Mixture-of-Experts expert weight or router.
+pub fn is_expert_tensor(name: &str) -> bool {
+    let lower = name.to_lowercase();
+    lower.contains("experts.")
+        || lower.contains("expert_")
+        || lower.contains("router.")
+        || lower.contains("block_sparse_moe.gate")
+        || lower.contains("_exps")
+        || lower.contains("ffn_gate_inp")
+}
+
 /// Classify a tensor by its component type based on name patterns.
 pub fn classify_tensor(name: &str) -> &'static str {
     let lower = name.to_lowercase();
 
+    // Vision tower / projector (checked first: these contain attn/mlp names too)
+    if is_vision_tensor(&lower) {
+        return "vision";
+    }
+
+    // MoE experts and routers (otherwise absorbed into "mlp")
+    if is_expert_tensor(&lower) {
+        return "expert";
+    }
+
     // Embeddings
     if lower.contains("token_embd")
         || lower.contains("embed_tokens")
@@ -160,6 +195,22 @@ pub fn extract_layer_index(name: &str) -> Option<u64> {
     None
 }
 
+/// `name` with the layer index found by `extract_layer_index` replaced by `index`.
+pub fn replace_layer_index(name: &str, index: u64) -> Option<String> {
+    let patterns = ["blk.", "layers.", "blocks.", "h.", "layer."];
+    for pat in &patterns {
+        if let Some(pos) = name.find(pat) {
+            let start = pos + pat.len();
+            let after = &name[start..];
+            let end = after.find('.').unwrap_or(after.len());
+            if after[..end].parse::<u64>().is_ok() {
+                return Some(format!("{}{}{}", &name[..start], index, &after[end..]));
+            }
+        }
+    }
+    None
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct InspectTensor {
     pub name: String,
@@ -173,6 +224,8 @@ pub struct InspectTensor {
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct MemoryComponent {
+    #[serde(default)]
+    pub component: String,
     pub name: String,
     pub bytes: u64,
     pub display: String,
@@ -232,6 +285,8 @@ pub struct TokenizerInfo {
     pub tokenizer_type: Option<String>,
     pub vocab_size: Option<u64>,
     pub special_tokens: Vec<SpecialToken>,
+    /// Jinja template the model expects its chat prompts rendered with.
+    pub chat_template: Option<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -278,16 +333,50 @@ fn extract_model_config(metadata: &HashMap<String, String>) -> ModelConfig {
         ),
     ];
 
-    let entries: Vec<ConfigEntry> = keys
-        .into_iter()
+    let block_count = metadata.get(&format!("{}.block_count", arch));
+    let is_array = |key: &str| {
+        metadata.contains_key(&format!("{}_count", key))
+            && metadata.get(key).is_some_and(|v| v.starts_with('['))
+    };
+    // Array-valued keys hold one entry per block in non-uniform architectures
+    let array_label = |key: &str, label: &str| {
+        if metadata.get(&format!("{}_count", key)) == block_count {
+            format!("{} (PER LAYER)", label)
+        } else {
+            format!("{} (ARRAY)", label)
+        }
+    };
+
+    let mut entries: Vec<ConfigEntry> = keys
+        .iter()
         .filter_map(|(key, label)| {
-            metadata.get(&key).map(|value| ConfigEntry {
-                label: label.to_string(),
+            metadata.get(key).map(|value| ConfigEntry {
+                label: if is_array(key) { array_label(key, label) } else { label.to_string() },
                 value: value.clone(),
             })
         })
         .collect();
 
+    // Other numeric arrays under the architecture prefix
+    let prefix = format!("{}.", arch);
+    let mut extra: Vec<(&String, &String)> = metadata
+        .iter()
+        .filter(|(key, _)| {
+            !arch.is_empty()
+                && key.starts_with(&prefix)
+                && is_array(key)
+                && !keys.iter().any(|(known, _)| known == *key)
+        })
+        .collect();
+    extra.sort();
+    for (key, value) in extra {
+        let label = key[prefix.len()..].replace(['.', '_'], " ").to_uppercase();
+        entries.push(ConfigEntry {
+            label: array_label(key, &label),
+            value: value.clone(),
+        });
+    }
+
     ModelConfig { entries }
 }
 
@@ -297,7 +386,9 @@ fn extract_tokenizer_info(metadata: &HashMap<String, String>) -> Option<Tokenize
         .get("tokenizer.ggml.tokens_count")
         .and_then(|v| v.parse::<u64>().ok());
 
-    if tokenizer_type.is_none() && vocab_size.is_none() {
+    let chat_template = metadata.get("tokenizer.chat_template").cloned();
+
+    if tokenizer_type.is_none() && vocab_size.is_none() && chat_template.is_none() {
         return None;
     }
 
@@ -326,6 +417,7 @@ fn extract_tokenizer_info(metadata: &HashMap<String, String>) -> Option<Tokenize
         tokenizer_type,
         vocab_size,
         special_tokens,
+        chat_template,
     })
 }
 
@@ -363,19 +455,9 @@ pub fn analyze(tensors: &[TensorInfo], metadata: &HashMap<String, String>) -> In
         *component_bytes.entry(leak_str(&t.component)).or_insert(0) += t.memory_bytes;
     }
 
-    let component_order = ["embedding", "attention", "mlp", "norm", "output", "other"];
-    let component_labels = [
-        "Token Embeddings",
-        "Attention Weights",
-        "MLP / Feed-Forward",
-        "Layer Norms",
-        "Output Head",
-        "Other",
-    ];
-
-    let memory_breakdown: Vec<MemoryComponent> = component_order
+    let memory_breakdown: Vec<MemoryComponent> = COMPONENT_ORDER
         .iter()
-        .zip(component_labels.iter())
+        .zip(COMPONENT_LABELS.iter())
         .filter_map(|(key, label)| {
             let bytes = *component_bytes.get(key).unwrap_or(&0);
             if bytes == 0 {
@@ -387,6 +469,7 @@ pub fn analyze(tensors: &[TensorInfo], metadata: &HashMap<String, String>) -> In
                 0.0
             };
             Some(MemoryComponent {
+                component: key.to_string(),
                 name: label.to_string(),
                 bytes,
                 display: format_bytes(bytes),
@@ -514,19 +597,14 @@ fn detect_attention_arch(
         .and_then(|v| v.parse::<u64>().ok());
 
     if let (Some(q_heads), Some(kv_heads)) = (q_heads_meta, kv_heads_meta) {
-        let head_dim = embedding_length.map(|e| e / q_heads);
-        let (attn_type, gqa_ratio) = if kv_heads == 1 {
-            ("MQA".to_string(), Some(q_heads))
-        } else if kv_heads == q_heads {
-            ("MHA".to_string(), None)
-        } else {
-            (
-                "GQA".to_string(),
-                Some(q_heads / kv_heads),
-            )
-        };
+        // key_length is set when heads aren't embedding / head_count wide (e.g. Gemma)
+        let head_dim = metadata
+            .get(&format!("{}.attention.key_length", arch))
+            .and_then(|v| v.parse::<u64>().ok())
+            .or_else(|| embedding_length.map(|e| e / q_heads.max(1)));
+        let (attention_type, gqa_ratio) = classify_attention(q_heads, kv_heads);
         return Some(AttentionInfo {
-            attention_type: attn_type,
+            attention_type,
             q_heads: Some(q_heads),
             kv_heads: Some(kv_heads),
             head_dim,
@@ -534,65 +612,432 @@ fn detect_attention_arch(
         });
     }
 
-    // Fallback: detect from tensor shapes
-    // Find Q and K tensors from first layer
-    let q_tensor = tensors.iter().find(|t| {
-        let lower = t.name.to_lowercase();
-        (lower.contains("blk.0.attn_q") || lower.contains("layers.0.self_attn.q_proj"))
-            && lower.contains("weight")
-    });
-    let k_tensor = tensors.iter().find(|t| {
-        let lower = t.name.to_lowercase();
-        (lower.contains("blk.0.attn_k") || lower.contains("layers.0.self_attn.k_proj"))
-            && lower.contains("weight")
-    });
-
-    if let (Some(q), Some(k)) = (q_tensor, k_tensor) {
-        if q.shape.len() >= 2 && k.shape.len() >= 2 {
-            let q_out = q.shape[0]; // output dim of Q projection
-            let k_out = k.shape[0]; // output dim of K projection
-
-            if q_out > 0 && k_out > 0 && q_out >= k_out {
-                let ratio = q_out / k_out;
-                let (attn_type, gqa_ratio) = if ratio == 1 {
-                    ("MHA".to_string(), None)
-                } else if k_out == q.shape.get(1).copied().unwrap_or(0) / q_out {
-                    ("MQA".to_string(), Some(ratio))
-                } else {
-                    ("GQA".to_string(), Some(ratio))
-                };
-
-                // Estimate head dim from embedding size
-                let hidden = q.shape.get(1).copied().unwrap_or(q.shape[0]);
-                let estimated_heads = q_out / k_out;
-                let head_dim = if estimated_heads > 0 {
-                    Some(hidden / (q_out / (hidden / q_out.max(1)).max(1)).max(1))
-                } else {
-                    None
-                };
-
-                return Some(AttentionInfo {
-                    attention_type: attn_type,
-                    q_heads: None,
-                    kv_heads: None,
-                    head_dim,
-                    gqa_ratio,
-                });
+    // Fallback: detect from the first layer's Q and K projection shapes
+    let find = |suffixes: &[&str]| {
+        tensors.iter().find(|t| {
+            let lower = t.name.to_lowercase();
+            suffixes.iter().any(|s| lower.ends_with(s))
+        })
+    };
+    let q = find(&["blk.0.attn_q.weight", "layers.0.self_attn.q_proj.weight"])?;
+    let k = find(&["blk.0.attn_k.weight", "layers.0.self_attn.k_proj.weight"])?;
+    let (q_out, k_out) = projection_out_dims(&q.shape, &k.shape)?;
+    if q_out == 0 || k_out == 0 || q_out < k_out || q_out % k_out != 0 {
+        return None;
+    }
+
+    // Head width from an explicit value or a per-head Q/K norm, else from the
+    // shapes only when a single common width fits them
+    let explicit = metadata
+        .get(&format!("{}.attention.key_length", arch))
+        .or_else(|| metadata.get("head_dim"))
+        .and_then(|v| v.parse::<u64>().ok());
+    let qk_norm = find(&[
+        "blk.0.attn_q_norm.weight",
+        "layers.0.self_attn.q_norm.weight",
+        "blk.0.attn_k_norm.weight",
+        "layers.0.self_attn.k_norm.weight",
+    ])
+    .filter(|t| t.shape.len() == 1)
+    .map(|t| t.shape[0]);
+    let head_dim = explicit
+        .or(qk_norm)
+        .filter(|&d| d > 0 && k_out % d == 0)
+        .or_else(|| {
+            let mut fits = COMMON_HEAD_DIMS.iter().copied().filter(|&d| k_out % d == 0);
+            match (fits.next(), fits.next()) {
+                (Some(d), None) => Some(d),
+                _ => None,
             }
-        }
+        });
+
+    let Some(head_dim) = head_dim else {
+        // Heads unknown; the Q/K ratio still tells MHA from grouped attention
+        let ratio = q_out / k_out;
+        return Some(AttentionInfo {
+            attention_type: if ratio == 1 { "MHA" } else { "GQA" }.to_string(),
+            q_heads: None,
+            kv_heads: None,
+            head_dim: None,
+            gqa_ratio: (ratio > 1).then_some(ratio),
+        });
+    };
+
+    let q_heads = q_out / head_dim;
+    let kv_heads = k_out / head_dim;
+    let (attention_type, gqa_ratio) = classify_attention(q_heads, kv_heads);
+    Some(AttentionInfo {
+        attention_type,
+        q_heads: Some(q_heads),
+        kv_heads: Some(kv_heads),
+        head_dim: Some(head_dim),
+        gqa_ratio,
+    })
+}
+
+/// Head widths seen in practice; shapes alone decide only when exactly one fits.
+const COMMON_HEAD_DIMS: [u64; 8] = [128, 64, 256, 96, 80, 160, 192, 112];
+
+/// Output widths of the Q and K projections. Safetensors stores weights as
+/// `[out, in]` and GGUF as `[in, out]`; the input (hidden) dim is the one
+/// both projections share, so the other one is the output.
+fn projection_out_dims(q: &[u64], k: &[u64]) -> Option<(u64, u64)> {
+    if q.len() != 2 || k.len() != 2 {
+        return None;
+    }
+    if q[1] == k[1] {
+        Some((q[0], k[0]))
+    } else if q[0] == k[0] {
+        Some((q[1], k[1]))
+    } else {
+        None
     }
+}
 
-    None
+fn classify_attention(q_heads: u64, kv_heads: u64) -> (String, Option<u64>) {
+    if kv_heads == 1 && q_heads > 1 {
+        ("MQA".to_string(), Some(q_heads))
+    } else if kv_heads == q_heads || kv_heads == 0 {
+        ("MHA".to_string(), None)
+    } else {
+        ("GQA".to_string(), Some(q_heads / kv_heads))
+    }
 }
 
+/// Display order of components in breakdowns, paired with `COMPONENT_LABELS`.
+const COMPONENT_ORDER: [&str; 8] = [
+    "embedding", "attention", "mlp", "expert", "vision", "norm", "output", "other",
+];
+const COMPONENT_LABELS: [&str; 8] = [
+    "Token Embeddings",
+    "Attention Weights",
+    "MLP / Feed-Forward",
+    "MoE Experts",
+    "Vision Encoder",
+    "Layer Norms",
+    "Output Head",
+    "Other",
+];
+
 // Helper to convert &str component to &'static str for HashMap key
 fn leak_str(s: &str) -> &'static str {
     match s {
         "embedding" => "embedding",
         "attention" => "attention",
         "mlp" => "mlp",
+        "expert" => "expert",
+        "vision" => "vision",
         "norm" => "norm",
         "output" => "output",
         _ => "other",
     }
 }
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ParamComponent {
+    pub component: String,
+    pub name: String,
+    pub params: u64,
+    pub display: String,
+    pub percentage: f64,
+    pub per_layer_avg: u64,
+    pub per_layer_avg_display: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ParamBreakdown {
+    pub components: Vec<ParamComponent>,
+    pub total_params: u64,
+    pub total_params_display: String,
+    pub layer_count: u64,
+    pub per_layer_avg: u64,
+    pub per_layer_avg_display: String,
+}
+
+/// Aggregate parameter counts by component from analyzed inspect data.
+/// Per-layer averages only count tensors that live inside a transformer block.
+pub fn param_breakdown(data: &InspectData) -> ParamBreakdown {
+    let mut totals: HashMap<&str, u64> = HashMap::new();
+    let mut in_layers: HashMap<&str, u64> = HashMap::new();
+
+    for layer in &data.layers {
+        for t in layer
+            .attention
+            .iter()
+            .chain(layer.mlp.iter())
+            .chain(layer.norms.iter())
+            .chain(layer.other.iter())
+        {
+            let key = leak_str(&t.component);
+            *totals.entry(key).or_insert(0) += t.params;
+            *in_layers.entry(key).or_insert(0) += t.params;
+        }
+    }
+    for t in &data.other_tensors {
+        *totals.entry(leak_str(&t.component)).or_insert(0) += t.params;
+    }
+
+    let layer_count = data.layers.len() as u64;
+    let total_params = data.total_params;
+
+    let components: Vec<ParamComponent> = COMPONENT_ORDER
+        .iter()
+        .zip(COMPONENT_LABELS.iter())
+        .filter_map(|(key, label)| {
+            let params = *totals.get(key).unwrap_or(&0);
+            if params == 0 {
+                return None;
+            }
+            let pct = if total_params > 0 {
+                (params as f64 / total_params as f64) * 100.0
+            } else {
+                0.0
+            };
+            let per_layer_avg = if layer_count > 0 {
+                *in_layers.get(key).unwrap_or(&0) / layer_count
+            } else {
+                0
+            };
+            Some(ParamComponent {
+                component: key.to_string(),
+                name: label.to_string(),
+                params,
+                display: super::format_param_count(params),
+                percentage: (pct * 10.0).round() / 10.0,
+                per_layer_avg,
+                per_layer_avg_display: super::format_param_count(per_layer_avg),
+            })
+        })
+        .collect();
+
+    let layer_params: u64 = in_layers.values().sum();
+    let per_layer_avg = if layer_count > 0 { layer_params / layer_count } else { 0 };
+
+    ParamBreakdown {
+        components,
+        total_params,
+        total_params_display: super::format_param_count(total_params),
+        layer_count,
+        per_layer_avg,
+        per_layer_avg_display: super::format_param_count(per_layer_avg),
+    }
+}
+
+/// Collect every tensor whose dtype matches `dtype` (case-insensitive),
+/// in layer order followed by non-layer tensors.
+pub fn tensors_by_dtype(data: &InspectData, dtype: &str) -> Vec<InspectTensor> {
+    let wanted = dtype.to_uppercase();
+    data.layers
+        .iter()
+        .flat_map(|layer| {
+            layer
+                .attention
+                .iter()
+                .chain(layer.mlp.iter())
+                .chain(layer.norms.iter())
+                .chain(layer.other.iter())
+        })
+        .chain(data.other_tensors.iter())
+        .filter(|t| t.dtype.to_uppercase() == wanted)
+        .cloned()
+        .collect()
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct KvCacheLayer {
+    pub layer_index: u64,
+    pub bytes_f16: u64,
+    pub bytes_f32: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct KvCacheBreakdown {
+    pub context_length: u64,
+    pub attention_type: String,
+    pub kv_heads: u64,
+    pub head_dim: u64,
+    pub layers: Vec<KvCacheLayer>,
+    pub total_bytes_f16: u64,
+    pub total_bytes_f32: u64,
+    pub total_f16_display: String,
+    pub total_f32_display: String,
+}
+
+/// KV-cache size per layer for `context_length` tokens:
+/// 2 (K and V) × context × kv_heads × head_dim × bytes per element.
+/// Layers without attention tensors (e.g. in hybrid models) hold no cache.
+pub fn kv_cache_breakdown(data: &InspectData, context_length: u64) -> Option<KvCacheBreakdown> {
+    let attn = data.attention_info.as_ref()?;
+    let kv_heads = attn.kv_heads.or(attn.q_heads)?;
+    let head_dim = attn.head_dim?;
+
+    // Context length comes from the user; a size that overflows u64 has no answer
+    let elems = 2u64
+        .checked_mul(context_length)?
+        .checked_mul(kv_heads)?
+        .checked_mul(head_dim)?;
+    let bytes_f16 = elems.checked_mul(2)?;
+    let bytes_f32 = elems.checked_mul(4)?;
+    let layers: Vec<KvCacheLayer> = data
+        .layers
+        .iter()
+        .map(|layer| {
+            let has_kv = !layer.attention.is_empty();
+            KvCacheLayer {
+                layer_index: layer.index,
+                bytes_f16: if has_kv { bytes_f16 } else { 0 },
+                bytes_f32: if has_kv { bytes_f32 } else { 0 },
+            }
+        })
+        .collect();
+
+    let total_bytes_f16 = layers.iter().try_fold(0u64, |acc, l| acc.checked_add(l.bytes_f16))?;
+    let total_bytes_f32 = layers.iter().try_fold(0u64, |acc, l| acc.checked_add(l.bytes_f32))?;
+
+    Some(KvCacheBreakdown {
+        context_length,
+        attention_type: attn.attention_type.clone(),
+        kv_heads,
+        head_dim,
+        layers,
+        total_bytes_f16,
+        total_bytes_f32,
+        total_f16_display: super::format_file_size(total_bytes_f16),
+        total_f32_display: super::format_file_size(total_bytes_f32),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tensor(name: &str, shape: &[u64]) -> InspectTensor {
+        InspectTensor {
+            name: name.into(),
+            dtype: "F16".into(),
+            shape: shape.to_vec(),
+            memory_bytes: 0,
+            memory_display: String::new(),
+            component: "attention".into(),
+            params: shape.iter().product(),
+        }
+    }
+
+    fn attention(tensors: &[InspectTensor], metadata: &[(&str, &str)]) -> AttentionInfo {
+        let metadata = metadata.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect();
+        detect_attention_arch(tensors, &metadata).unwrap()
+    }
+
+    /// Two llama blocks with GQA heads declared in metadata.
+    fn gqa_model() -> InspectData {
+        let tensors: Vec<TensorInfo> = (0..2)
+            .flat_map(|i| {
+                [("attn_q", [4096, 4096]), ("attn_k", [4096, 1024])].map(|(part, shape)| TensorInfo {
+                    name: format!("blk.{}.{}.weight", i, part),
+                    dtype: "F16".into(),
+                    shape: shape.to_vec(),
+                })
+            })
+            .collect();
+        let metadata = [
+            ("general.architecture", "llama"),
+            ("llama.attention.head_count", "32"),
+            ("llama.attention.head_count_kv", "8"),
+            ("llama.attention.key_length", "128"),
+        ]
+        .iter()
+        .map(|(k, v)| (k.to_string(), v.to_string()))
+        .collect();
+        analyze(&tensors, &metadata)
+    }
+
+    #[test]
+    fn per_layer_arrays_are_labelled() {
+        let metadata: HashMap<String, String> = [
+            ("general.architecture", "llama"),
+            ("llama.block_count", "36"),
+            ("llama.attention.head_count", "[32×36]"),
+            ("llama.attention.head_count_count", "36"),
+            ("llama.attention.sliding_window_pattern", "[1, 0×2]"),
+            ("llama.attention.sliding_window_pattern_count", "3"),
+        ]
+        .iter()
+        .map(|(k, v)| (k.to_string(), v.to_string()))
+        .collect();
+        let config = extract_model_config(&metadata);
+        let entry = |label: &str| {
+            config.entries.iter().find(|e| e.label == label).map(|e| e.value.as_str())
+        };
+
+        assert_eq!(entry("BLOCKS"), Some("36"));
+        assert_eq!(entry("ATTENTION HEADS (PER LAYER)"), Some("[32×36]"));
+        assert_eq!(entry("ATTENTION SLIDING WINDOW PATTERN (ARRAY)"), Some("[1, 0×2]"));
+    }
+
+    #[test]
+    fn kv_cache_sums_layers() {
+        let kv = kv_cache_breakdown(&gqa_model(), 4096).unwrap();
+        // K and V: 2 × 4096 positions × 8 heads × 128 dims per layer
+        assert_eq!(kv.layers[0].bytes_f16, 2 * 4096 * 8 * 128 * 2);
+        assert_eq!(kv.total_bytes_f32, 2 * (2 * 4096 * 8 * 128 * 4));
+    }
+
+    #[test]
+    fn kv_cache_overflow_is_none() {
+        assert!(kv_cache_breakdown(&gqa_model(), u64::MAX / 4).is_none());
+    }
+
+    #[test]
+    fn mha_head_dim_from_q_norm() {
+        let info = attention(
+            &[
+                tensor("model.layers.0.self_attn.q_proj.weight", &[4096, 4096]),
+                tensor("model.layers.0.self_attn.k_proj.weight", &[4096, 4096]),
+                tensor("model.layers.0.self_attn.q_norm.weight", &[128]),
+            ],
+            &[],
+        );
+        assert_eq!(info.attention_type, "MHA");
+        assert_eq!((info.q_heads, info.kv_heads, info.head_dim), (Some(32), Some(32), Some(128)));
+    }
+
+    #[test]
+    fn gqa_head_dim_from_key_length_on_gguf_shapes() {
+        let info = attention(
+            &[
+                tensor("blk.0.attn_q.weight", &[4096, 4096]),
+                tensor("blk.0.attn_k.weight", &[4096, 1024]),
+            ],
+            &[("general.architecture", "llama"), ("llama.attention.key_length", "128")],
+        );
+        assert_eq!(info.attention_type, "GQA");
+        assert_eq!((info.q_heads, info.kv_heads, info.gqa_ratio), (Some(32), Some(8), Some(4)));
+    }
+
+    #[test]
+    fn mqa_head_dim_from_k_norm() {
+        let info = attention(
+            &[
+                tensor("model.layers.0.self_attn.q_proj.weight", &[2048, 2048]),
+                tensor("model.layers.0.self_attn.k_proj.weight", &[256, 2048]),
+                tensor("model.layers.0.self_attn.k_norm.weight", &[256]),
+            ],
+            &[],
+        );
+        assert_eq!(info.attention_type, "MQA");
+        assert_eq!((info.q_heads, info.kv_heads, info.head_dim), (Some(8), Some(1), Some(256)));
+    }
+
+    #[test]
+    fn ambiguous_shapes_leave_heads_unknown() {
+        let info = attention(
+            &[
+                tensor("model.layers.0.self_attn.q_proj.weight", &[4096, 4096]),
+                tensor("model.layers.0.self_attn.k_proj.weight", &[1024, 4096]),
+            ],
+            &[],
+        );
+        assert_eq!(info.attention_type, "GQA");
+        assert_eq!((info.head_dim, info.q_heads, info.gqa_ratio), (None, None, Some(4)));
+    }
+}
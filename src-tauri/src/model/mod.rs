@@ -1,17 +1,21 @@
 pub mod error;
 pub mod gguf;
 pub mod inspect;
+pub mod pytorch;
 pub mod safetensors;
 pub mod state;
 
 use serde::{Deserialize, Serialize};
 use std::collections::{HashMap, HashSet};
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "snake_case")]
 pub enum ModelFormat {
     SafeTensors,
     Gguf,
+    /// Zip-format `torch.save` checkpoint (`.bin` / `.pt` / `.pth`); inspect-only.
+    #[serde(rename = "pytorch")]
+    PyTorch,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
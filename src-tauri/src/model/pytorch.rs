@@ -0,0 +1,635 @@
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+
+use super::error::ModelError;
+use super::{
+    derive_layer_count, format_file_size, format_param_count, ModelFormat, ModelInfo, TensorInfo,
+};
+
+// PyTorch checkpoints (`torch.save`, zip format) are a zip archive holding
+// `<name>/data.pkl` plus one raw file per storage. Only the pickle is read:
+// a restricted VM walks it symbolically, never importing or calling anything,
+// and refuses any global that isn't part of a plain tensor state dict.
+
+fn parse_err(reason: impl Into<String>) -> ModelError {
+    ModelError::ParseError {
+        format: "PyTorch".into(),
+        reason: reason.into(),
+    }
+}
+
+#[derive(Debug, Clone)]
+enum Value {
+    None,
+    /// Bools, floats and bytes: never needed for tensor metadata.
+    Opaque,
+    Int(i64),
+    Str(String),
+    Tuple(Vec<Value>),
+    List(Vec<Value>),
+    Dict(Vec<(Value, Value)>),
+    Global(String, String),
+    /// Result of calling an allowed global other than a tensor rebuild; holds the args.
+    Call(Vec<Value>),
+    Persistent(Box<Value>),
+    Tensor { dtype: String, shape: Vec<u64> },
+}
+
+/// Globals a tensor state dict legitimately references. Anything else
+/// (`os.system`, `builtins.eval`, `subprocess.Popen`, ...) is rejected.
+fn is_allowed_global(module: &str, name: &str) -> bool {
+    match module {
+        "collections" => name == "OrderedDict",
+        "torch._utils" => name.starts_with("_rebuild_") && name != "_rebuild_from_type",
+        "torch" => {
+            name.ends_with("Storage")
+                || name == "Size"
+                || matches!(
+                    name,
+                    "float16" | "bfloat16" | "float32" | "float64" | "half" | "float" | "double"
+                        | "int8" | "int16" | "int32" | "int64" | "uint8" | "bool"
+                )
+        }
+        "torch.storage" => name == "UntypedStorage",
+        _ => false,
+    }
+}
+
+/// SafeTensors-style dtype name for a `torch.*Storage` class.
+fn storage_dtype(name: &str) -> String {
+    match name {
+        "FloatStorage" => "F32",
+        "HalfStorage" => "F16",
+        "BFloat16Storage" => "BF16",
+        "DoubleStorage" => "F64",
+        "LongStorage" => "I64",
+        "IntStorage" => "I32",
+        "ShortStorage" => "I16",
+        "CharStorage" => "I8",
+        "ByteStorage" | "UntypedStorage" => "U8",
+        "BoolStorage" => "BOOL",
+        other => other,
+    }
+    .to_string()
+}
+
+fn as_shape(value: &Value) -> Option<Vec<u64>> {
+    let items = match value {
+        Value::Tuple(items) | Value::List(items) => items,
+        Value::Call(args) => return args.first().and_then(as_shape), // torch.Size((..))
+        _ => return None,
+    };
+    items
+        .iter()
+        .map(|v| match v {
+            Value::Int(i) if *i >= 0 => Some(*i as u64),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Deepest container nesting a pickle may build. Real state dicts stay
+/// within a handful of levels; the cap keeps recursive walks and drops of
+/// hostile nesting chains off the end of the stack.
+const MAX_DEPTH: usize = 256;
+
+/// Total values the VM may walk or copy. Memo fetches deep-copy, so a few
+/// bytes of BINGET/TUPLE2/BINPUT can otherwise double a value without limit.
+const MAX_WORK: u64 = 50_000_000;
+
+/// Nesting depth and value count of `value`. Nothing deeper than
+/// `MAX_DEPTH` is ever built, which bounds the recursion here.
+fn measure(value: &Value) -> (usize, u64) {
+    let (mut depth, mut nodes) = (0, 1);
+    let mut visit = |child: &Value| {
+        let (d, n) = measure(child);
+        depth = depth.max(d);
+        nodes += n;
+    };
+    match value {
+        Value::Tuple(items) | Value::List(items) | Value::Call(items) => items.iter().for_each(&mut visit),
+        Value::Dict(entries) => entries.iter().for_each(|(k, v)| {
+            visit(k);
+            visit(v);
+        }),
+        Value::Persistent(inner) => visit(inner),
+        _ => {}
+    }
+    (depth + 1, nodes)
+}
+
+/// Interpret a REDUCE of an allowed global. Tensor rebuilds collapse into
+/// `Value::Tensor`; OrderedDict becomes an empty dict filled by SETITEMS.
+fn reduce(callable: Value, args: Vec<Value>) -> Value {
+    let Value::Global(module, name) = &callable else {
+        return Value::Call(args);
+    };
+    match (module.as_str(), name.as_str()) {
+        ("collections", "OrderedDict") => Value::Dict(vec![]),
+        ("torch._utils", "_rebuild_tensor_v2") | ("torch._utils", "_rebuild_tensor") => {
+            // (storage, storage_offset, size, stride, ...)
+            let dtype = match args.first() {
+                Some(Value::Persistent(pid)) => match pid.as_ref() {
+                    Value::Tuple(parts) => match parts.get(1) {
+                        Some(Value::Global(_, storage)) => storage_dtype(storage),
+                        _ => "unknown".to_string(),
+                    },
+                    _ => "unknown".to_string(),
+                },
+                _ => "unknown".to_string(),
+            };
+            let shape = args.get(2).and_then(as_shape).unwrap_or_default();
+            Value::Tensor { dtype, shape }
+        }
+        ("torch._utils", n) if n.starts_with("_rebuild_parameter") => {
+            // (tensor, requires_grad, backward_hooks[, state])
+            args.into_iter().next().unwrap_or(Value::None)
+        }
+        _ => Value::Call(args),
+    }
+}
+
+struct Unpickler<'a> {
+    data: &'a [u8],
+    pos: usize,
+    stack: Vec<Value>,
+    marks: Vec<usize>,
+    memo: HashMap<u32, Value>,
+    /// Values walked or copied so far, checked against `MAX_WORK`.
+    work: u64,
+}
+
+impl<'a> Unpickler<'a> {
+    fn new(data: &'a [u8]) -> Self {
+        Self {
+            data,
+            pos: 0,
+            stack: Vec::new(),
+            marks: Vec::new(),
+            memo: HashMap::new(),
+            work: 0,
+        }
+    }
+
+    fn take(&mut self, n: usize) -> Result<&'a [u8], ModelError> {
+        let end = self.pos.checked_add(n).filter(|e| *e <= self.data.len())
+            .ok_or_else(|| parse_err("Unexpected end of pickle data"))?;
+        let bytes = &self.data[self.pos..end];
+        self.pos = end;
+        Ok(bytes)
+    }
+
+    fn u8(&mut self) -> Result<u8, ModelError> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u16(&mut self) -> Result<u16, ModelError> {
+        Ok(u16::from_le_bytes(self.take(2)?.try_into().unwrap()))
+    }
+
+    fn u32(&mut self) -> Result<u32, ModelError> {
+        Ok(u32::from_le_bytes(self.take(4)?.try_into().unwrap()))
+    }
+
+    fn u64(&mut self) -> Result<u64, ModelError> {
+        Ok(u64::from_le_bytes(self.take(8)?.try_into().unwrap()))
+    }
+
+    fn line(&mut self) -> Result<String, ModelError> {
+        let rest = &self.data[self.pos..];
+        let len = rest.iter().position(|b| *b == b'\n')
+            .ok_or_else(|| parse_err("Unterminated GLOBAL in pickle"))?;
+        let s = String::from_utf8_lossy(&rest[..len]).to_string();
+        self.pos += len + 1;
+        Ok(s)
+    }
+
+    fn string(&mut self, len: usize) -> Result<Value, ModelError> {
+        let bytes = self.take(len)?;
+        Ok(Value::Str(String::from_utf8_lossy(bytes).to_string()))
+    }
+
+    fn pop(&mut self) -> Result<Value, ModelError> {
+        self.stack.pop().ok_or_else(|| parse_err("Pickle stack underflow"))
+    }
+
+    fn pop_mark(&mut self) -> Result<Vec<Value>, ModelError> {
+        let mark = self.marks.pop().ok_or_else(|| parse_err("Pickle MARK missing"))?;
+        if mark > self.stack.len() {
+            return Err(parse_err("Pickle MARK beyond stack"));
+        }
+        Ok(self.stack.split_off(mark))
+    }
+
+    fn top_mut(&mut self) -> Result<&mut Value, ModelError> {
+        self.stack.last_mut().ok_or_else(|| parse_err("Pickle stack underflow"))
+    }
+
+    fn global(&mut self, module: String, name: String) -> Result<(), ModelError> {
+        if !is_allowed_global(&module, &name) {
+            return Err(parse_err(format!(
+                "Refusing to read pickle: it references `{}.{}`, which is not part of a tensor \
+                 checkpoint. The file may contain executable code — convert it to SafeTensors \
+                 with a trusted tool first.",
+                module, name
+            )));
+        }
+        self.stack.push(Value::Global(module, name));
+        Ok(())
+    }
+
+    /// Charge a walk or copy of a value with the given shape against the limits.
+    fn account(&mut self, (depth, nodes): (usize, u64)) -> Result<(), ModelError> {
+        if depth >= MAX_DEPTH {
+            return Err(parse_err(format!("Pickle nests deeper than {} levels", MAX_DEPTH)));
+        }
+        self.work += nodes;
+        if self.work > MAX_WORK {
+            return Err(parse_err("Pickle expands to too many values to inspect safely"));
+        }
+        Ok(())
+    }
+
+    /// Check values about to be nested inside a new or existing container.
+    fn nest(&mut self, values: &[Value]) -> Result<(), ModelError> {
+        for value in values {
+            self.account(measure(value))?;
+        }
+        Ok(())
+    }
+
+    fn memo_get(&mut self, key: u32) -> Result<(), ModelError> {
+        let shape = self.memo.get(&key).map(measure)
+            .ok_or_else(|| parse_err(format!("Pickle memo key {} missing", key)))?;
+        self.account(shape)?;
+        let value = self.memo[&key].clone();
+        self.stack.push(value);
+        Ok(())
+    }
+
+    fn memo_put(&mut self, key: u32) -> Result<(), ModelError> {
+        let shape = self.stack.last().map(measure).ok_or_else(|| parse_err("Pickle stack underflow"))?;
+        self.account(shape)?;
+        let value = self.stack[self.stack.len() - 1].clone();
+        self.memo.insert(key, value);
+        Ok(())
+    }
+
+    fn set_items(&mut self, items: Vec<Value>) -> Result<(), ModelError> {
+        self.nest(&items)?;
+        let Value::Dict(entries) = self.top_mut()? else {
+            return Err(parse_err("SETITEMS target is not a dict"));
+        };
+        let mut iter = items.into_iter();
+        while let (Some(k), Some(v)) = (iter.next(), iter.next()) {
+            entries.push((k, v));
+        }
+        Ok(())
+    }
+
+    fn append_items(&mut self, items: Vec<Value>) -> Result<(), ModelError> {
+        self.nest(&items)?;
+        match self.top_mut()? {
+            Value::List(list) => {
+                list.extend(items);
+                Ok(())
+            }
+            _ => Err(parse_err("APPENDS target is not a list")),
+        }
+    }
+
+    fn run(mut self) -> Result<Value, ModelError> {
+        loop {
+            let op = self.u8()?;
+            match op {
+                0x80 => { self.u8()?; }                       // PROTO
+                0x95 => { self.u64()?; }                      // FRAME
+                b'(' => self.marks.push(self.stack.len()),    // MARK
+                b'.' => return self.pop(),                    // STOP
+                b'N' => self.stack.push(Value::None),
+                0x88 | 0x89 => self.stack.push(Value::Opaque), // NEWTRUE / NEWFALSE
+                b'J' => {
+                    let v = self.u32()? as i32;
+                    self.stack.push(Value::Int(v as i64));
+                }
+                b'K' => {
+                    let v = self.u8()?;
+                    self.stack.push(Value::Int(v as i64));
+                }
+                b'M' => {
+                    let v = self.u16()?;
+                    self.stack.push(Value::Int(v as i64));
+                }
+                0x8a => {
+                    // LONG1: little-endian two's complement
+                    let n = self.u8()? as usize;
+                    let bytes = self.take(n)?;
+                    if n > 8 {
+                        return Err(parse_err("Pickle integer too large"));
+                    }
+                    let mut buf = if bytes.last().is_some_and(|b| b & 0x80 != 0) { [0xffu8; 8] } else { [0u8; 8] };
+                    buf[..n].copy_from_slice(bytes);
+                    self.stack.push(Value::Int(i64::from_le_bytes(buf)));
+                }
+                b'G' => {
+                    self.take(8)?;
+                    self.stack.push(Value::Opaque);
+                }
+                b'X' => {
+                    let n = self.u32()? as usize;
+                    let s = self.string(n)?;
+                    self.stack.push(s);
+                }
+                0x8c => {
+                    let n = self.u8()? as usize;
+                    let s = self.string(n)?;
+                    self.stack.push(s);
+                }
+                0x8d => {
+                    let n = self.u64()? as usize;
+                    let s = self.string(n)?;
+                    self.stack.push(s);
+                }
+                b'C' | b'B' | 0x8e => {
+                    let n = match op {
+                        b'C' => self.u8()? as usize,
+                        b'B' => self.u32()? as usize,
+                        _ => self.u64()? as usize,
+                    };
+                    self.take(n)?;
+                    self.stack.push(Value::Opaque);
+                }
+                b'}' => self.stack.push(Value::Dict(vec![])),
+                b']' => self.stack.push(Value::List(vec![])),
+                b')' => self.stack.push(Value::Tuple(vec![])),
+                b't' => {
+                    let items = self.pop_mark()?;
+                    self.nest(&items)?;
+                    self.stack.push(Value::Tuple(items));
+                }
+                0x85 | 0x86 | 0x87 => {
+                    let n = (op - 0x84) as usize;
+                    if self.stack.len() < n {
+                        return Err(parse_err("Pickle stack underflow"));
+                    }
+                    let items = self.stack.split_off(self.stack.len() - n);
+                    self.nest(&items)?;
+                    self.stack.push(Value::Tuple(items));
+                }
+                b's' => {
+                    let v = self.pop()?;
+                    let k = self.pop()?;
+                    self.set_items(vec![k, v])?;
+                }
+                b'u' => {
+                    let items = self.pop_mark()?;
+                    self.set_items(items)?;
+                }
+                b'a' => {
+                    let v = self.pop()?;
+                    self.append_items(vec![v])?;
+                }
+                b'e' => {
+                    let items = self.pop_mark()?;
+                    self.append_items(items)?;
+                }
+                b'q' => {
+                    let k = self.u8()? as u32;
+                    self.memo_put(k)?;
+                }
+                b'r' => {
+                    let k = self.u32()?;
+                    self.memo_put(k)?;
+                }
+                0x94 => {
+                    let k = self.memo.len() as u32;
+                    self.memo_put(k)?;
+                }
+                b'h' => {
+                    let k = self.u8()? as u32;
+                    self.memo_get(k)?;
+                }
+                b'j' => {
+                    let k = self.u32()?;
+                    self.memo_get(k)?;
+                }
+                b'c' => {
+                    let module = self.line()?;
+                    let name = self.line()?;
+                    self.global(module, name)?;
+                }
+                0x93 => {
+                    let name = self.pop()?;
+                    let module = self.pop()?;
+                    match (module, name) {
+                        (Value::Str(m), Value::Str(n)) => self.global(m, n)?,
+                        _ => return Err(parse_err("STACK_GLOBAL expects two strings")),
+                    }
+                }
+                b'Q' => {
+                    let pid = self.pop()?;
+                    self.nest(std::slice::from_ref(&pid))?;
+                    self.stack.push(Value::Persistent(Box::new(pid)));
+                }
+                b'R' => {
+                    let args = match self.pop()? {
+                        Value::Tuple(items) => items,
+                        _ => return Err(parse_err("REDUCE expects an argument tuple")),
+                    };
+                    self.nest(&args)?;
+                    let callable = self.pop()?;
+                    self.stack.push(reduce(callable, args));
+                }
+                0x81 => {
+                    // NEWOBJ: cls.__new__(cls, *args)
+                    let args = match self.pop()? {
+                        Value::Tuple(items) => items,
+                        _ => return Err(parse_err("NEWOBJ expects an argument tuple")),
+                    };
+                    self.nest(&args)?;
+                    self.pop()?;
+                    self.stack.push(Value::Call(args));
+                }
+                b'b' => {
+                    // BUILD: state is irrelevant for metadata, keep the object
+                    self.pop()?;
+                }
+                other => {
+                    return Err(parse_err(format!(
+                        "Unsupported pickle opcode 0x{:02x} at byte {}",
+                        other,
+                        self.pos - 1
+                    )));
+                }
+            }
+        }
+    }
+}
+
+/// Top-level keys that wrap the real state dict in training checkpoints.
+const WRAPPER_KEYS: &[&str] = &["state_dict", "model", "module", "model_state_dict"];
+
+fn collect_tensors(value: &Value, prefix: &str, out: &mut Vec<TensorInfo>) {
+    let Value::Dict(entries) = value else {
+        return;
+    };
+    for (key, child) in entries {
+        let key = match key {
+            Value::Str(s) => s.clone(),
+            Value::Int(i) => i.to_string(),
+            _ => continue,
+        };
+        let name = if prefix.is_empty() { key.clone() } else { format!("{}.{}", prefix, key) };
+        match child {
+            Value::Tensor { dtype, shape } => out.push(TensorInfo {
+                name,
+                dtype: dtype.clone(),
+                shape: shape.clone(),
+            }),
+            Value::Dict(_) => {
+                let nested = if prefix.is_empty() && WRAPPER_KEYS.contains(&key.as_str()) { "" } else { name.as_str() };
+                collect_tensors(child, nested, out);
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Read tensor names, dtypes and shapes from a zip-format `torch.save` checkpoint.
+fn read_tensor_infos(path: &Path) -> Result<Vec<TensorInfo>, ModelError> {
+    let mut file = File::open(path)?;
+    let mut magic = [0u8; 4];
+    file.read_exact(&mut magic).map_err(|_| parse_err("File too small to be a checkpoint"))?;
+    if &magic != b"PK\x03\x04" {
+        return Err(parse_err(
+            "Legacy (pre-1.6) torch.save format is not supported; re-save the checkpoint \
+             with a current PyTorch or convert it to SafeTensors",
+        ));
+    }
+
+    let file = File::open(path)?;
+    let mut archive = zip::ZipArchive::new(file).map_err(|e| parse_err(format!("Invalid zip archive: {}", e)))?;
+    let pkl_name = archive
+        .file_names()
+        .find(|n| n.ends_with("data.pkl"))
+        .map(|n| n.to_string())
+        .ok_or_else(|| parse_err("No data.pkl found in checkpoint archive"))?;
+
+    let mut pickle = Vec::new();
+    archive
+        .by_name(&pkl_name)
+        .map_err(|e| parse_err(format!("Cannot read {}: {}", pkl_name, e)))?
+        .read_to_end(&mut pickle)?;
+
+    let root = Unpickler::new(&pickle).run()?;
+    let mut tensors = Vec::new();
+    collect_tensors(&root, "", &mut tensors);
+
+    if tensors.is_empty() {
+        return Err(parse_err("Checkpoint does not contain a tensor state dict"));
+    }
+    Ok(tensors)
+}
+
+/// Parse a `.bin` / `.pt` / `.pth` checkpoint for inspection. Tensor data is
+/// not loaded, so these models can be inspected but not yet merged.
+pub fn parse(path: &Path) -> Result<ModelInfo, ModelError> {
+    let file_size = std::fs::metadata(path)?.len();
+    let mut tensors = read_tensor_infos(path)?;
+
+    let mut metadata = HashMap::new();
+    if let Some(template) = path.parent().and_then(super::safetensors::read_chat_template) {
+        metadata.insert("tokenizer.chat_template".to_string(), template);
+    }
+
+    let total_params: u64 = tensors
+        .iter()
+        .map(|t| if t.shape.is_empty() { 0 } else { t.shape.iter().product::<u64>() })
+        .sum();
+    let tensor_count = tensors.len() as u64;
+
+    tensors.sort_by(|a, b| a.name.cmp(&b.name));
+
+    let layer_count = derive_layer_count(&tensors);
+
+    let mut dtype_counts: HashMap<&str, usize> = HashMap::new();
+    for t in &tensors {
+        *dtype_counts.entry(&t.dtype).or_insert(0) += 1;
+    }
+    let quantization = dtype_counts
+        .iter()
+        .max_by_key(|(_, count)| *count)
+        .map(|(dtype, _)| dtype.to_string());
+
+    Ok(ModelInfo {
+        file_name: path
+            .file_name()
+            .unwrap_or_default()
+            .to_string_lossy()
+            .to_string(),
+        file_path: path.to_string_lossy().to_string(),
+        file_size,
+        file_size_display: format_file_size(file_size),
+        format: ModelFormat::PyTorch,
+        tensor_count,
+        parameter_count: total_params,
+        parameter_count_display: format_param_count(total_params),
+        layer_count,
+        quantization,
+        architecture: None,
+        context_length: None,
+        embedding_size: None,
+        metadata,
+        tensor_preview: tensors.iter().take(50).cloned().collect(),
+        all_tensors: tensors,
+        shard_count: None,
+        has_tokenizer: None,
+        has_config: None,
+        model_type: None,
+        vocab_size: None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn unpickle(ops: &[u8]) -> Result<Value, ModelError> {
+        Unpickler::new(ops).run()
+    }
+
+    #[test]
+    fn rejects_memo_doubling() {
+        // () BINPUT 0, then repeatedly: BINGET 0 BINGET 0 TUPLE2 BINPUT 0
+        let mut ops = vec![0x80, 2, b')', b'q', 0];
+        for _ in 0..64 {
+            ops.extend_from_slice(&[b'h', 0, b'h', 0, 0x86, b'q', 0]);
+        }
+        ops.push(b'.');
+        let err = unpickle(&ops).unwrap_err().to_string();
+        assert!(err.contains("too many values"), "{}", err);
+    }
+
+    #[test]
+    fn rejects_deep_nesting() {
+        let mut ops = vec![0x80, 2, b')'];
+        ops.extend(std::iter::repeat(0x85).take(100_000)); // TUPLE1
+        ops.push(b'.');
+        let err = unpickle(&ops).unwrap_err().to_string();
+        assert!(err.contains("deeper"), "{}", err);
+    }
+
+    #[test]
+    fn reads_small_dict() {
+        // {"a": 1} built with EMPTY_DICT, BINPUT, SHORT_BINUNICODE, BININT1, SETITEM
+        let ops = [0x80, 2, b'}', b'q', 0, 0x8c, 1, b'a', b'K', 1, b's', b'.'];
+        match unpickle(&ops).unwrap() {
+            Value::Dict(entries) => {
+                assert!(matches!(&entries[..], [(Value::Str(k), Value::Int(1))] if k == "a"));
+            }
+            other => panic!("unexpected {:?}", other),
+        }
+    }
+}
@@ -9,6 +9,10 @@ use super::{
     derive_layer_count, format_file_size, format_param_count, ModelFormat, ModelInfo, TensorInfo,
 };
 
+/// Prefix for keys folded in from a file's `__metadata__` block, keeping
+/// them apart from GGUF-style keys such as `tokenizer.chat_template`.
+const METADATA_PREFIX: &str = "safetensors.";
+
 /// Parse a single safetensors file header, returning tensors and metadata.
 fn parse_header(path: &Path) -> Result<(Vec<TensorInfo>, HashMap<String, String>, u64), ModelError> {
     let file = File::open(path)?;
@@ -54,9 +58,14 @@ fn parse_header(path: &Path) -> Result<(Vec<TensorInfo>, HashMap<String, String>
 
     for (key, value) in header_map {
         if key == "__metadata__" {
+            // The spec says string→string, but some writers emit numbers or objects
             if let Some(meta_obj) = value.as_object() {
                 for (mk, mv) in meta_obj {
-                    metadata.insert(mk.clone(), mv.as_str().unwrap_or("").to_string());
+                    let text = match mv {
+                        serde_json::Value::String(s) => s.clone(),
+                        other => other.to_string(),
+                    };
+                    metadata.insert(format!("{}{}", METADATA_PREFIX, mk), text);
                 }
             }
             continue;
@@ -162,8 +171,12 @@ pub fn parse_dir(dir: &Path) -> Result<ModelInfo, ModelError> {
 
     let arch = architecture
         .clone()
-        .or_else(|| all_metadata.get("model_type").cloned())
-        .or_else(|| all_metadata.get("architecture").cloned());
+        .or_else(|| all_metadata.get(&format!("{}model_type", METADATA_PREFIX)).cloned())
+        .or_else(|| all_metadata.get(&format!("{}architecture", METADATA_PREFIX)).cloned());
+
+    if let Some(template) = read_chat_template(dir) {
+        all_metadata.insert("tokenizer.chat_template".to_string(), template);
+    }
 
     Ok(ModelInfo {
         file_name: dir_name,
@@ -190,6 +203,28 @@ pub fn parse_dir(dir: &Path) -> Result<ModelInfo, ModelError> {
     })
 }
 
+/// Chat template from `chat_template.jinja` or `tokenizer_config.json` in
+/// `dir`. A list of named templates resolves to "default", else the first.
+pub fn read_chat_template(dir: &Path) -> Option<String> {
+    if let Ok(template) = std::fs::read_to_string(dir.join("chat_template.jinja")) {
+        return Some(template);
+    }
+    let content = std::fs::read_to_string(dir.join("tokenizer_config.json")).ok()?;
+    let json: serde_json::Value = serde_json::from_str(&content).ok()?;
+    match json.get("chat_template")? {
+        serde_json::Value::String(s) => Some(s.clone()),
+        serde_json::Value::Array(named) => {
+            let template_of = |v: &serde_json::Value| v.get("template").and_then(|t| t.as_str()).map(String::from);
+            named
+                .iter()
+                .find(|v| v.get("name").and_then(|n| n.as_str()) == Some("default"))
+                .and_then(template_of)
+                .or_else(|| named.first().and_then(template_of))
+        }
+        _ => None,
+    }
+}
+
 fn parse_config_json(
     path: &Path,
 ) -> (bool, Option<String>, Option<String>, Option<u64>, Option<u64>, Option<u64>) {
@@ -224,7 +259,10 @@ fn parse_config_json(
 }
 
 pub fn parse(path: &Path) -> Result<ModelInfo, ModelError> {
-    let (mut tensors, metadata, file_size) = parse_header(path)?;
+    let (mut tensors, mut metadata, file_size) = parse_header(path)?;
+    if let Some(template) = path.parent().and_then(read_chat_template) {
+        metadata.insert("tokenizer.chat_template".to_string(), template);
+    }
 
     let mut total_params: u64 = 0;
     for t in &tensors {
@@ -255,9 +293,9 @@ pub fn parse(path: &Path) -> Result<ModelInfo, ModelError> {
         layer_count,
         quantization,
         architecture: metadata
-            .get("model_type")
+            .get(&format!("{}model_type", METADATA_PREFIX))
             .cloned()
-            .or_else(|| metadata.get("architecture").cloned()),
+            .or_else(|| metadata.get(&format!("{}architecture", METADATA_PREFIX)).cloned()),
         context_length: None,
         embedding_size: None,
         metadata,
@@ -289,3 +327,37 @@ fn detect_quantization(tensors: &[TensorInfo]) -> Option<String> {
 
     Some(dominant.to_string())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn write_file(path: &Path, header: &str, data_len: usize) {
+        let mut bytes = (header.len() as u64).to_le_bytes().to_vec();
+        bytes.extend_from_slice(header.as_bytes());
+        bytes.resize(bytes.len() + data_len, 0);
+        std::fs::write(path, bytes).unwrap();
+    }
+
+    #[test]
+    fn custom_metadata_is_folded_in_with_a_prefix() {
+        let dir = std::env::temp_dir().join(format!("forgeai-st-meta-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("model.safetensors");
+        write_file(
+            &path,
+            r#"{"__metadata__":{"format":"pt","merge_lineage":"slerp(a, b)","epochs":3},"w":{"dtype":"F32","shape":[2,2],"data_offsets":[0,16]}}"#,
+            16,
+        );
+
+        let info = parse(&path).unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(info.metadata.get("safetensors.format").map(String::as_str), Some("pt"));
+        assert_eq!(info.metadata.get("safetensors.merge_lineage").map(String::as_str), Some("slerp(a, b)"));
+        assert_eq!(info.metadata.get("safetensors.epochs").map(String::as_str), Some("3"));
+        assert!(!info.metadata.contains_key("format"));
+        assert_eq!(info.tensor_count, 1);
+        assert_eq!(info.parameter_count, 4);
+    }
+}
@@ -1,16 +1,65 @@
+use std::collections::HashMap;
 use std::sync::atomic::AtomicBool;
 use std::sync::{Arc, Mutex};
 
+use serde::{Deserialize, Serialize};
+
 use super::ModelInfo;
 use crate::merge::registry::ParentRegistry;
 
+/// A resident `llama-server` process keeping one GGUF model loaded.
+pub struct LlamaServer {
+    pub child: tokio::process::Child,
+    pub port: u16,
+    pub model_path: String,
+    pub gpu_layers: String,
+    pub context_size: Option<u32>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ChatMessage {
+    pub role: String,
+    pub content: String,
+}
+
+/// Sampling settings shared by single-shot generation and chat sessions.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GenerationSettings {
+    pub max_tokens: u32,
+    pub temperature: f64,
+    pub top_p: Option<f64>,
+    pub top_k: Option<u32>,
+    pub repeat_penalty: Option<f64>,
+    pub gpu_layers: Option<i32>,
+    pub context_size: Option<u32>,
+    #[serde(default)]
+    pub stop: Option<Vec<String>>,
+    /// GBNF grammar constraining the output (GGUF models only).
+    #[serde(default)]
+    pub grammar: Option<String>,
+}
+
+/// A multi-turn conversation with one model. `messages` holds the turns so
+/// far, without the system prompt.
+#[derive(Debug, Clone)]
+pub struct ChatSession {
+    pub model_path: String,
+    pub system_prompt: Option<String>,
+    pub settings: GenerationSettings,
+    pub messages: Vec<ChatMessage>,
+}
+
 pub struct AppState {
     pub loaded_model: Mutex<Option<ModelInfo>>,
     pub download_cancel: Arc<AtomicBool>,
     pub convert_cancel: Arc<AtomicBool>,
     pub convert_pid: Mutex<Option<u32>>,
+    pub quantize_cancel: Arc<AtomicBool>,
+    pub quantize_pid: Mutex<Option<u32>>,
     pub test_cancel: Arc<AtomicBool>,
     pub test_pid: Mutex<Option<u32>>,
+    pub llama_server: Mutex<Option<LlamaServer>>,
+    pub chat_sessions: Mutex<HashMap<String, ChatSession>>,
     pub merge_parents: Mutex<ParentRegistry>,
     pub merge_cancel: Arc<AtomicBool>,
     pub merge_active: Arc<AtomicBool>,
@@ -18,6 +67,8 @@ pub struct AppState {
     pub training_cancel: Arc<AtomicBool>,
     pub training_pid: Mutex<Option<u32>>,
     pub surgery_cancel: Arc<AtomicBool>,
+    pub gpu_monitor_active: Arc<AtomicBool>,
+    pub fingerprint_cancel: Arc<AtomicBool>,
 }
 
 impl Default for AppState {
@@ -27,8 +78,12 @@ impl Default for AppState {
             download_cancel: Arc::new(AtomicBool::new(false)),
             convert_cancel: Arc::new(AtomicBool::new(false)),
             convert_pid: Mutex::new(None),
+            quantize_cancel: Arc::new(AtomicBool::new(false)),
+            quantize_pid: Mutex::new(None),
             test_cancel: Arc::new(AtomicBool::new(false)),
             test_pid: Mutex::new(None),
+            llama_server: Mutex::new(None),
+            chat_sessions: Mutex::new(HashMap::new()),
             merge_parents: Mutex::new(ParentRegistry::default()),
             merge_cancel: Arc::new(AtomicBool::new(false)),
             merge_active: Arc::new(AtomicBool::new(false)),
@@ -36,6 +91,8 @@ impl Default for AppState {
             training_cancel: Arc::new(AtomicBool::new(false)),
             training_pid: Mutex::new(None),
             surgery_cancel: Arc::new(AtomicBool::new(false)),
+            gpu_monitor_active: Arc::new(AtomicBool::new(false)),
+            fingerprint_cancel: Arc::new(AtomicBool::new(false)),
         }
     }
 }
@@ -1,3 +1,5 @@
+use std::collections::HashMap;
+
 use serde::{Deserialize, Serialize};
 
 // ── Training Method ─────────────────────────────────
@@ -9,6 +11,8 @@ pub enum TrainingMethod {
     Lora,
     Qlora,
     Dpo,
+    Orpo,
+    Kto,
     FullFinetune,
 }
 
@@ -19,6 +23,8 @@ impl std::fmt::Display for TrainingMethod {
             Self::Lora => write!(f, "lora"),
             Self::Qlora => write!(f, "qlora"),
             Self::Dpo => write!(f, "dpo"),
+            Self::Orpo => write!(f, "orpo"),
+            Self::Kto => write!(f, "kto"),
             Self::FullFinetune => write!(f, "full_finetune"),
         }
     }
@@ -57,6 +63,26 @@ pub struct TrainingConfig {
     pub output_path: String,
     #[serde(default)]
     pub merge_adapter: bool,
+    /// When set (a convert outtype such as "f16"), keep the adapter in `output_path`,
+    /// save the merged model alongside it, and convert that to GGUF afterwards.
+    #[serde(default)]
+    pub export_gguf_after_merge: Option<String>,
+    /// Checkpoint directory to continue from, or "latest" for the newest
+    /// `checkpoint-*` in `output_path`. Unset starts a fresh run.
+    #[serde(default)]
+    pub resume_from_checkpoint: Option<String>,
+
+    /// Jinja chat template assigned to the tokenizer before training and saved
+    /// with it. Needed when fine-tuning a base model that ships without one.
+    #[serde(default)]
+    pub chat_template: Option<String>,
+    /// Dataset column to train on as plain text (renamed to `text`).
+    #[serde(default)]
+    pub dataset_text_field: Option<String>,
+    /// Dataset column -> column the method expects (`prompt`, `completion`,
+    /// `chosen`, ...), applied right after loading.
+    #[serde(default)]
+    pub column_map: Option<HashMap<String, String>>,
 
     // Hyperparameters
     #[serde(default = "default_lr")]
@@ -75,6 +101,18 @@ pub struct TrainingConfig {
     pub weight_decay: f64,
     #[serde(default = "default_save_steps")]
     pub save_steps: u32,
+    /// Recompute activations in the backward pass to save memory.
+    #[serde(default = "default_true")]
+    pub gradient_checkpointing: bool,
+    /// Transformers `optim` name; must be one of `SUPPORTED_OPTIMIZERS`.
+    #[serde(default = "default_optimizer")]
+    pub optimizer: String,
+    /// Pack short SFT samples into full `max_seq_length` sequences.
+    #[serde(default)]
+    pub packing: bool,
+    /// Cut SFT samples to `max_seq_length`; off passes `max_length=None`.
+    #[serde(default = "default_true")]
+    pub truncation: bool,
 
     // LoRA
     #[serde(default)]
@@ -96,11 +134,63 @@ pub struct TrainingConfig {
     #[serde(default)]
     pub dpo_beta: Option<f64>,
 
+    // ORPO
+    #[serde(default)]
+    pub orpo_beta: Option<f64>,
+
+    // KTO
+    #[serde(default)]
+    pub kto_beta: Option<f64>,
+    /// Loss weight for examples labelled desirable (`label == true`).
+    #[serde(default)]
+    pub kto_desirable_weight: Option<f64>,
+    /// Loss weight for examples labelled undesirable (`label == false`).
+    #[serde(default)]
+    pub kto_undesirable_weight: Option<f64>,
+
+    // Evaluation
+    /// Fraction of rows held out for evaluation (between 0 and 1). Unset trains on everything.
+    #[serde(default)]
+    pub eval_split: Option<f64>,
+    /// Steps between evaluations; defaults to `save_steps`.
+    #[serde(default)]
+    pub eval_steps: Option<u32>,
+    /// Evaluations without an eval-loss improvement before stopping (default 3, 0 disables).
+    #[serde(default)]
+    pub early_stopping_patience: Option<u32>,
+
     // GPU
     #[serde(default)]
     pub gpu_memory_limit_gb: Option<f64>,
 }
 
+impl TrainingConfig {
+    /// Column renames from `column_map` plus `dataset_text_field`, skipping no-ops.
+    pub fn column_renames(&self) -> HashMap<String, String> {
+        let mut renames: HashMap<String, String> = self
+            .column_map
+            .iter()
+            .flatten()
+            .filter(|(from, to)| !from.is_empty() && !to.is_empty() && from != to)
+            .map(|(from, to)| (from.clone(), to.clone()))
+            .collect();
+        if let Some(field) = self.dataset_text_field.as_ref().filter(|f| !f.is_empty() && *f != "text") {
+            renames.insert(field.clone(), "text".into());
+        }
+        renames
+    }
+
+    /// Directory the merged model is written to when a GGUF export is requested.
+    pub fn merged_output_path(&self) -> String {
+        let path = std::path::Path::new(&self.output_path);
+        let name = path
+            .file_name()
+            .map(|n| n.to_string_lossy().to_string())
+            .unwrap_or_else(|| "model".into());
+        path.join(format!("{}-merged", name)).to_string_lossy().to_string()
+    }
+}
+
 fn default_lr() -> f64 { 2e-4 }
 fn default_epochs() -> u32 { 3 }
 fn default_batch() -> u32 { 1 }
@@ -109,6 +199,25 @@ fn default_seq_len() -> u32 { 512 }
 fn default_warmup() -> u32 { 100 }
 fn default_wd() -> f64 { 0.01 }
 fn default_save_steps() -> u32 { 500 }
+fn default_true() -> bool { true }
+fn default_optimizer() -> String { "adamw_8bit".into() }
+
+/// Optimizers accepted for `TrainingConfig::optimizer`. The `8bit`/`paged`
+/// variants need bitsandbytes, which is unavailable on macOS.
+pub const SUPPORTED_OPTIMIZERS: &[&str] = &[
+    "adamw_torch",
+    "adamw_torch_fused",
+    "adamw_8bit",
+    "adamw_bnb_8bit",
+    "paged_adamw_8bit",
+    "paged_adamw_32bit",
+    "adafactor",
+    "sgd",
+    "adagrad",
+    "rmsprop",
+    "lion_8bit",
+    "paged_lion_8bit",
+];
 
 // ── Progress / Result ───────────────────────────────
 
@@ -133,6 +242,26 @@ pub struct TrainingProgress {
     pub gpu_memory_used_mb: Option<u64>,
 }
 
+/// Held-out evaluation result, emitted as `training:eval`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TrainingEval {
+    pub step: u64,
+    pub epoch: Option<f64>,
+    pub eval_loss: Option<f64>,
+}
+
+/// One line of training script output, emitted as `training:log`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TrainingLogLine {
+    /// "stdout" or "stderr"
+    pub stream: String,
+    /// "json" for structured progress/status lines, "text" for everything else
+    pub kind: String,
+    pub line: String,
+    /// Lines coalesced away by rate limiting since the previous emitted line
+    pub dropped: u64,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct TrainingResult {
     pub output_path: String,
@@ -142,6 +271,15 @@ pub struct TrainingResult {
     pub epochs_completed: u32,
     pub final_loss: Option<f64>,
     pub adapter_merged: bool,
+    #[serde(default)]
+    pub gguf_path: Option<String>,
+}
+
+/// A `checkpoint-<step>` directory left in a training output folder.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CheckpointInfo {
+    pub path: String,
+    pub step: u64,
 }
 
 // ── Dataset Info ────────────────────────────────────
@@ -156,6 +294,32 @@ pub struct DatasetInfo {
     pub size: u64,
     pub size_display: String,
     pub detected_template: Option<String>,
+    #[serde(default)]
+    pub token_lengths: Option<TokenLengthStats>,
+}
+
+/// Approximate tokens per row (about 4 characters per token) over the first
+/// rows of a dataset, for choosing `max_seq_length`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TokenLengthStats {
+    pub sampled_rows: usize,
+    pub mean: u64,
+    pub p50: u64,
+    pub p95: u64,
+    pub max: u64,
+}
+
+/// First rows of a dataset with inferred column types, checked against the
+/// columns the selected training method reads.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DatasetPreview {
+    pub path: String,
+    pub total_rows: u64,
+    pub columns: Vec<ColumnAnalysis>,
+    pub rows: Vec<serde_json::Value>,
+    pub missing_columns: Vec<String>,
+    pub valid: bool,
+    pub message: Option<String>,
 }
 
 // ── Deps Status ─────────────────────────────────────
@@ -173,6 +337,23 @@ pub struct TrainingDepsStatus {
     pub ready: bool,
 }
 
+/// An import statement from the generated script that failed in the venv.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ImportFailure {
+    pub statement: String,
+    pub error: String,
+}
+
+/// Result of compiling and import-checking a generated script without training.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ScriptValidation {
+    pub valid: bool,
+    pub script_path: String,
+    pub syntax_error: Option<String>,
+    pub imports_checked: usize,
+    pub import_errors: Vec<ImportFailure>,
+}
+
 // ── Surgery Config ──────────────────────────────────
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -187,6 +368,12 @@ pub struct SurgeryConfig {
 pub enum SurgeryOperation {
     RemoveLayer { index: u64 },
     DuplicateLayer { source_index: u64, insert_at: u64 },
+    /// SOLAR-style depth up-scaling: the bottom `keep_bottom` layers followed by
+    /// the top `keep_top`, so the overlapping middle band appears twice
+    /// (32 layers with 24 + 24 gives 48).
+    DepthUpscale { keep_bottom: u64, keep_top: u64 },
+    /// Replace layer `a` with `(1 - weight) * a + weight * b` and drop layer `b`.
+    InterpolateLayers { a: u64, b: u64, weight: f64 },
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -212,6 +399,23 @@ pub struct SurgeryProgress {
 pub struct TargetModuleGroup {
     pub name: String,
     pub modules: Vec<String>,
+    #[serde(default)]
+    pub details: Vec<TargetModuleInfo>,
+}
+
+/// Dimensions of one LoRA-targetable linear module, summed over every layer it appears in.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TargetModuleInfo {
+    pub module: String,
+    /// Weight shape as `[out_features, in_features]`, from the first instance.
+    pub shape: Vec<u64>,
+    pub instances: usize,
+    pub params: u64,
+    /// Trainable LoRA parameters per unit of rank: `sum(in + out)` over instances.
+    pub lora_params_per_rank: u64,
+    /// Some instance sits outside the per-layer blocks or in a shared expert,
+    /// so one adapter is applied wherever that weight is reused.
+    pub shared: bool,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
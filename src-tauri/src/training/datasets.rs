@@ -1,3 +1,4 @@
+use std::collections::HashMap;
 use std::fs;
 use std::io::{BufRead, BufReader};
 use std::path::Path;
@@ -5,11 +6,16 @@ use std::path::Path;
 use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
 
 use crate::model::error::ModelError;
-use super::config::{ColumnAnalysis, DatasetFormat, DatasetFullInfo, DatasetInfo};
+use super::config::{
+    ColumnAnalysis, DatasetFormat, DatasetFullInfo, DatasetInfo, DatasetPreview, TokenLengthStats,
+    TrainingMethod,
+};
 
 /// Detect dataset format, row count, columns, and preview.
 pub fn detect_dataset(path: &str) -> Result<DatasetInfo, ModelError> {
-    let (rows, columns, preview, format, size) = parse_dataset_core(path, 5)?;
+    let (rows, columns, mut preview, format, size) = parse_dataset_core(path, TOKEN_SAMPLE_ROWS)?;
+    let token_lengths = token_length_stats(&preview);
+    preview.truncate(5);
     let detected_template = detect_template(&columns, &preview);
     let size_display = format_size(size);
 
@@ -22,6 +28,41 @@ pub fn detect_dataset(path: &str) -> Result<DatasetInfo, ModelError> {
         size,
         size_display,
         detected_template,
+        token_lengths,
+    })
+}
+
+/// Rows sampled by `detect_dataset` for token-length stats.
+const TOKEN_SAMPLE_ROWS: usize = 200;
+const CHARS_PER_TOKEN: usize = 4;
+
+/// Characters in every string inside `value`, including nested messages.
+fn text_chars(value: &serde_json::Value) -> usize {
+    match value {
+        serde_json::Value::String(s) => s.chars().count(),
+        serde_json::Value::Array(items) => items.iter().map(text_chars).sum(),
+        serde_json::Value::Object(map) => map.values().map(text_chars).sum(),
+        _ => 0,
+    }
+}
+
+fn token_length_stats(rows: &[serde_json::Value]) -> Option<TokenLengthStats> {
+    let mut lengths: Vec<u64> = rows
+        .iter()
+        .map(|row| text_chars(row).div_ceil(CHARS_PER_TOKEN) as u64)
+        .collect();
+    if lengths.is_empty() {
+        return None;
+    }
+    lengths.sort_unstable();
+    let percentile = |p: f64| lengths[((lengths.len() - 1) as f64 * p).round() as usize];
+
+    Some(TokenLengthStats {
+        sampled_rows: lengths.len(),
+        mean: lengths.iter().sum::<u64>() / lengths.len() as u64,
+        p50: percentile(0.5),
+        p95: percentile(0.95),
+        max: *lengths.last()?,
     })
 }
 
@@ -45,6 +86,79 @@ pub fn detect_dataset_full(path: &str, max_preview: usize) -> Result<DatasetFull
     })
 }
 
+/// Preview the first `max_rows` rows and validate them against `method`.
+/// `renames` (dataset column -> training column) are applied before validating.
+pub fn preview_dataset(
+    path: &str,
+    method: &TrainingMethod,
+    max_rows: usize,
+    renames: &HashMap<String, String>,
+) -> Result<DatasetPreview, ModelError> {
+    let (total_rows, columns, rows, _, _) = parse_dataset_core(path, max_rows)?;
+    let column_analysis = analyze_columns(&columns, &rows);
+    let problem = check_schema(method, &rename_columns(&columns, renames));
+
+    Ok(DatasetPreview {
+        path: path.to_string(),
+        total_rows,
+        columns: column_analysis,
+        rows,
+        valid: problem.is_none(),
+        missing_columns: problem.as_ref().map(|(missing, _)| missing.clone()).unwrap_or_default(),
+        message: problem.map(|(_, message)| message),
+    })
+}
+
+/// Column names as the training script sees them after `renames`. A rename
+/// replaces any existing column of the target name.
+pub fn rename_columns(columns: &[String], renames: &HashMap<String, String>) -> Vec<String> {
+    columns
+        .iter()
+        .filter(|c| renames.contains_key(*c) || !renames.values().any(|to| to == *c))
+        .map(|c| renames.get(c).unwrap_or(c).clone())
+        .collect()
+}
+
+/// Column sets each method can train on; any one fully present is enough.
+fn accepted_schemas(method: &TrainingMethod) -> &'static [&'static [&'static str]] {
+    match method {
+        TrainingMethod::Dpo | TrainingMethod::Orpo => &[&["chosen", "rejected"]],
+        TrainingMethod::Kto => &[&["prompt", "completion", "label"]],
+        _ => &[&["text"], &["messages"], &["prompt", "completion"]],
+    }
+}
+
+/// `None` when `columns` satisfy `method`, otherwise the columns missing from
+/// the closest accepted schema and a message naming what the method expects.
+pub fn check_schema(method: &TrainingMethod, columns: &[String]) -> Option<(Vec<String>, String)> {
+    let schemas = accepted_schemas(method);
+    let missing_for = |schema: &[&str]| -> Vec<String> {
+        schema
+            .iter()
+            .filter(|c| !columns.iter().any(|col| col == *c))
+            .map(|c| c.to_string())
+            .collect()
+    };
+
+    let closest = schemas.iter().map(|s| missing_for(s)).min_by_key(|m| m.len())?;
+    if closest.is_empty() {
+        return None;
+    }
+
+    let expected = schemas
+        .iter()
+        .map(|s| s.join(" + "))
+        .collect::<Vec<_>>()
+        .join(" or ");
+    let message = format!(
+        "{} training needs {} column(s); missing: {}",
+        method.to_string().to_uppercase(),
+        expected,
+        closest.join(", ")
+    );
+    Some((closest, message))
+}
+
 fn parse_dataset_core(
     path: &str,
     max_preview: usize,
@@ -116,10 +230,39 @@ fn parse_json(path: &Path, max_preview: usize) -> Result<(u64, Vec<String>, Vec<
     }
 }
 
+/// Columns and up to `max_rows` rows from the start of a jsonl or csv file.
+/// `bytes` may be a truncated prefix; a trailing partial line is ignored.
+pub fn preview_from_prefix(
+    bytes: &[u8],
+    format: &DatasetFormat,
+    max_rows: usize,
+) -> Result<(Vec<String>, Vec<serde_json::Value>), ModelError> {
+    let complete = match bytes.iter().rposition(|&b| b == b'\n') {
+        Some(end) => &bytes[..=end],
+        None => bytes,
+    };
+    let (_, columns, rows) = match format {
+        DatasetFormat::Jsonl => parse_jsonl_reader(complete, max_rows)?,
+        DatasetFormat::Csv => parse_csv_text(&String::from_utf8_lossy(complete), max_rows),
+        other => {
+            return Err(ModelError::UnsupportedFormat(format!(
+                "Cannot preview a partial {} file",
+                other
+            )))
+        }
+    };
+    Ok((columns, rows))
+}
+
 fn parse_jsonl(path: &Path, max_preview: usize) -> Result<(u64, Vec<String>, Vec<serde_json::Value>), ModelError> {
     let file = fs::File::open(path).map_err(ModelError::IoError)?;
-    let reader = BufReader::new(file);
+    parse_jsonl_reader(BufReader::new(file), max_preview)
+}
 
+fn parse_jsonl_reader(
+    reader: impl BufRead,
+    max_preview: usize,
+) -> Result<(u64, Vec<String>, Vec<serde_json::Value>), ModelError> {
     let mut rows = 0u64;
     let mut columns = vec![];
     let mut preview = vec![];
@@ -146,11 +289,15 @@ fn parse_jsonl(path: &Path, max_preview: usize) -> Result<(u64, Vec<String>, Vec
 
 fn parse_csv(path: &Path, max_preview: usize) -> Result<(u64, Vec<String>, Vec<serde_json::Value>), ModelError> {
     let content = fs::read_to_string(path).map_err(ModelError::IoError)?;
+    Ok(parse_csv_text(&content, max_preview))
+}
+
+fn parse_csv_text(content: &str, max_preview: usize) -> (u64, Vec<String>, Vec<serde_json::Value>) {
     let mut lines = content.lines();
 
     let header = match lines.next() {
         Some(h) => h,
-        None => return Ok((0, vec![], vec![])),
+        None => return (0, vec![], vec![]),
     };
 
     let columns: Vec<String> = header.split(',')
@@ -176,7 +323,7 @@ fn parse_csv(path: &Path, max_preview: usize) -> Result<(u64, Vec<String>, Vec<s
         }
     }
 
-    Ok((rows, columns, preview))
+    (rows, columns, preview)
 }
 
 fn parse_parquet(path: &Path, max_preview: usize) -> Result<(u64, Vec<String>, Vec<serde_json::Value>), ModelError> {
@@ -241,9 +388,24 @@ fn extract_columns(val: &serde_json::Value) -> Vec<String> {
 }
 
 /// Detect common dataset templates from column names and sample data.
-pub fn detect_template(columns: &[String], _preview: &[serde_json::Value]) -> Option<String> {
+pub fn detect_template(columns: &[String], preview: &[serde_json::Value]) -> Option<String> {
     let cols_lower: Vec<String> = columns.iter().map(|c| c.to_lowercase()).collect();
 
+    // Preference pairs (DPO/ORPO): chosen + rejected. Checked first so pair datasets
+    // that also carry instruction or message columns are still offered those methods.
+    if cols_lower.contains(&"chosen".into()) && cols_lower.contains(&"rejected".into()) {
+        return Some("dpo_pairs".to_string());
+    }
+
+    // KTO: unpaired prompt/completion rows with a boolean label
+    if cols_lower.contains(&"prompt".into())
+        && cols_lower.contains(&"completion".into())
+        && cols_lower.contains(&"label".into())
+        && label_column_is_boolean(columns, preview)
+    {
+        return Some("kto".to_string());
+    }
+
     // Alpaca format: instruction, input, output
     if cols_lower.contains(&"instruction".into())
         && cols_lower.contains(&"output".into())
@@ -261,11 +423,6 @@ pub fn detect_template(columns: &[String], _preview: &[serde_json::Value]) -> Op
         return Some("chat_ml".to_string());
     }
 
-    // DPO: chosen + rejected
-    if cols_lower.contains(&"chosen".into()) && cols_lower.contains(&"rejected".into()) {
-        return Some("dpo_pairs".to_string());
-    }
-
     // Simple text completion
     if cols_lower.contains(&"text".into()) && columns.len() <= 2 {
         return Some("text".to_string());
@@ -279,6 +436,19 @@ pub fn detect_template(columns: &[String], _preview: &[serde_json::Value]) -> Op
     None
 }
 
+/// Whether every previewed `label` value is a boolean (or a "true"/"false" string,
+/// as CSV yields). An empty preview counts as boolean.
+fn label_column_is_boolean(columns: &[String], preview: &[serde_json::Value]) -> bool {
+    let Some(label) = columns.iter().find(|c| c.eq_ignore_ascii_case("label")) else {
+        return false;
+    };
+    preview.iter().all(|row| match &row[label.as_str()] {
+        serde_json::Value::Bool(_) | serde_json::Value::Null => true,
+        serde_json::Value::String(s) => matches!(s.trim().to_lowercase().as_str(), "true" | "false"),
+        _ => false,
+    })
+}
+
 fn analyze_columns(columns: &[String], preview: &[serde_json::Value]) -> Vec<ColumnAnalysis> {
     columns.iter().map(|col| {
         let mut non_null = 0u64;
@@ -1,14 +1,144 @@
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 
 use tauri::{AppHandle, Emitter};
 use tokio::io::{AsyncBufReadExt, BufReader};
 
 use crate::model::error::ModelError;
-use super::config::{TrainingConfig, TrainingMethod, TrainingProgress, TrainingResult};
+use super::config::{
+    CheckpointInfo, ImportFailure, ScriptValidation, TrainingConfig, TrainingEval, TrainingLogLine,
+    TrainingMethod, TrainingProgress, TrainingResult,
+};
 use super::scripts;
 use super::venv;
 
+/// Newest `checkpoint-<step>` directory under `output_path`, by step.
+pub fn find_latest_checkpoint(output_path: &str) -> Option<CheckpointInfo> {
+    std::fs::read_dir(output_path)
+        .ok()?
+        .flatten()
+        .filter(|e| e.path().is_dir())
+        .filter_map(|e| {
+            let name = e.file_name().to_string_lossy().to_string();
+            let step = name.strip_prefix("checkpoint-")?.parse::<u64>().ok()?;
+            Some(CheckpointInfo { path: e.path().to_string_lossy().to_string(), step })
+        })
+        .max_by_key(|c| c.step)
+}
+
+/// Training script for `config.method`.
+pub fn generate_script(config: &TrainingConfig) -> String {
+    match config.method {
+        TrainingMethod::Dpo => scripts::generate_dpo_script(config),
+        TrainingMethod::Orpo => scripts::generate_orpo_script(config),
+        TrainingMethod::Kto => scripts::generate_kto_script(config),
+        _ => scripts::generate_sft_script(config),
+    }
+}
+
+/// Import statements in `script`, with parenthesised continuations joined onto one line.
+fn import_statements(script: &str) -> Vec<String> {
+    let mut statements = Vec::new();
+    let mut lines = script.lines();
+    while let Some(line) = lines.next() {
+        let trimmed = line.trim();
+        let is_import = trimmed.starts_with("import ")
+            || (trimmed.starts_with("from ") && trimmed.contains(" import "));
+        if !is_import {
+            continue;
+        }
+        let mut statement = trimmed.to_string();
+        if statement.contains('(') && !statement.contains(')') {
+            for next in lines.by_ref() {
+                statement.push(' ');
+                statement.push_str(next.trim());
+                if next.contains(')') {
+                    break;
+                }
+            }
+        }
+        if !statements.contains(&statement) {
+            statements.push(statement);
+        }
+    }
+    statements
+}
+
+/// Runs each statement in one interpreter and prints the failures as JSON.
+const IMPORT_CHECK_CODE: &str = r#"
+import json, sys
+failures = []
+for stmt in json.loads(sys.argv[1]):
+    try:
+        exec(stmt, {})
+    except BaseException as e:
+        failures.append({"statement": stmt, "error": f"{type(e).__name__}: {e}"})
+print(json.dumps(failures))
+"#;
+
+/// Generate the script for `config` without running it: byte-compile it and
+/// import every module it uses inside the training venv.
+pub async fn validate_script(
+    app: &AppHandle,
+    config: &TrainingConfig,
+) -> Result<ScriptValidation, ModelError> {
+    let training_dir = venv::get_training_dir(app)?;
+    let venv_python = venv::get_venv_python(&training_dir);
+
+    if !venv_python.exists() {
+        return Err(ModelError::TrainingError(
+            "Training environment not set up. Please install dependencies first.".into(),
+        ));
+    }
+
+    let script = generate_script(config);
+    let script_path = training_dir.join("validate_script.py");
+    std::fs::write(&script_path, &script)
+        .map_err(|e| ModelError::TrainingError(format!("Failed to write training script: {}", e)))?;
+
+    let compile = tokio::process::Command::new(&venv_python)
+        .args(["-m", "py_compile"])
+        .arg(&script_path)
+        .output()
+        .await
+        .map_err(|e| ModelError::TrainingError(format!("Failed to run py_compile: {}", e)))?;
+    let syntax_error = if compile.status.success() {
+        None
+    } else {
+        Some(String::from_utf8_lossy(&compile.stderr).trim().to_string())
+    };
+
+    let statements = import_statements(&script);
+    let statements_json = serde_json::to_string(&statements)
+        .map_err(|e| ModelError::TrainingError(e.to_string()))?;
+    let check = tokio::process::Command::new(&venv_python)
+        .args(["-c", IMPORT_CHECK_CODE, &statements_json])
+        .output()
+        .await
+        .map_err(|e| ModelError::TrainingError(format!("Failed to run import check: {}", e)))?;
+    if !check.status.success() {
+        return Err(ModelError::TrainingError(format!(
+            "Import check failed: {}",
+            String::from_utf8_lossy(&check.stderr).trim()
+        )));
+    }
+    let stdout = String::from_utf8_lossy(&check.stdout);
+    let import_errors: Vec<ImportFailure> = stdout
+        .lines()
+        .last()
+        .and_then(|line| serde_json::from_str(line).ok())
+        .ok_or_else(|| ModelError::TrainingError(format!("Unexpected import check output: {}", stdout.trim())))?;
+
+    Ok(ScriptValidation {
+        valid: syntax_error.is_none() && import_errors.is_empty(),
+        script_path: script_path.to_string_lossy().to_string(),
+        syntax_error,
+        imports_checked: statements.len(),
+        import_errors,
+    })
+}
+
 /// Run a training job as a Python subprocess.
 pub async fn run_training(
     app: AppHandle,
@@ -26,10 +156,7 @@ pub async fn run_training(
     }
 
     // Generate the script
-    let script = match config.method {
-        TrainingMethod::Dpo => scripts::generate_dpo_script(&config),
-        _ => scripts::generate_sft_script(&config),
-    };
+    let script = generate_script(&config);
 
     let script_path = training_dir.join("train_script.py");
     std::fs::write(&script_path, &script)
@@ -63,6 +190,8 @@ pub async fn run_training(
     let mut final_loss: Option<f64> = None;
     let mut final_result: Option<serde_json::Value> = None;
     let mut stderr_lines: Vec<String> = Vec::new();
+    let mut log = LogThrottle::new();
+    let mut flush_tick = tokio::time::interval(LOG_WINDOW);
 
     loop {
         if cancel.load(Ordering::Relaxed) {
@@ -76,6 +205,7 @@ pub async fn run_training(
             line = stdout_reader.next_line() => {
                 match line {
                     Ok(Some(text)) => {
+                        log.push(&app, "stdout", &text);
                         if let Some(progress) = parse_progress(&text) {
                             if let Some(loss) = progress.loss {
                                 final_loss = Some(loss);
@@ -84,7 +214,21 @@ pub async fn run_training(
                         } else if let Ok(json) = serde_json::from_str::<serde_json::Value>(&text) {
                             if json.get("type").and_then(|v| v.as_str()) == Some("result") {
                                 final_result = Some(json);
+                            } else if json.get("type").and_then(|v| v.as_str()) == Some("eval") {
+                                let _ = app.emit("training:eval", TrainingEval {
+                                    step: json.get("step").and_then(|v| v.as_u64()).unwrap_or(0),
+                                    epoch: json.get("epoch").and_then(|v| v.as_f64()),
+                                    eval_loss: json.get("eval_loss").and_then(|v| v.as_f64()),
+                                });
                             } else if json.get("type").and_then(|v| v.as_str()) == Some("status") {
+                                // A resume that lands on step 0 means the trainer state was not restored
+                                if json.get("resumed_step").and_then(|v| v.as_u64()) == Some(0) {
+                                    let _ = child.kill().await;
+                                    *pid_store.lock().unwrap() = None;
+                                    return Err(ModelError::TrainingError(
+                                        "Checkpoint did not restore the trainer state (global_step is 0)".into(),
+                                    ));
+                                }
                                 let stage = json.get("stage").and_then(|v| v.as_str()).unwrap_or("unknown");
                                 let message = json.get("message").and_then(|v| v.as_str()).unwrap_or("");
                                 let _ = app.emit("training:progress", TrainingProgress {
@@ -105,6 +249,7 @@ pub async fn run_training(
             line = stderr_reader.next_line() => {
                 match line {
                     Ok(Some(text)) => {
+                        log.push(&app, "stderr", &text);
                         let trimmed = text.trim();
                         if !trimmed.is_empty() {
                             stderr_lines.push(trimmed.to_string());
@@ -114,8 +259,12 @@ pub async fn run_training(
                     Err(_) => {}
                 }
             }
+            _ = flush_tick.tick() => {
+                log.flush(&app);
+            }
         }
     }
+    log.finish(&app);
 
     let status = child.wait().await
         .map_err(|e| ModelError::TrainingError(format!("Failed to wait for process: {}", e)))?;
@@ -145,6 +294,7 @@ pub async fn run_training(
             epochs_completed: res.get("epochs_completed").and_then(|v| v.as_u64()).unwrap_or(config.epochs as u64) as u32,
             final_loss: res.get("final_loss").and_then(|v| v.as_f64()).or(final_loss),
             adapter_merged: config.merge_adapter,
+            gguf_path: None,
         })
     } else {
         Ok(TrainingResult {
@@ -155,6 +305,7 @@ pub async fn run_training(
             epochs_completed: config.epochs,
             final_loss,
             adapter_merged: config.merge_adapter,
+            gguf_path: None,
         })
     }
 }
@@ -211,6 +362,83 @@ fn format_size(bytes: u64) -> String {
     }
 }
 
+/// Rate-limit window for `training:log` events.
+const LOG_WINDOW: Duration = Duration::from_millis(250);
+/// Lines forwarded per window before further lines are coalesced.
+const LOG_LINES_PER_WINDOW: u32 = 20;
+
+/// Forwards script output as `training:log` events without flooding the frontend.
+/// Past the per-window budget only the latest line is kept and emitted when the
+/// window rolls over, tagged with how many lines were skipped. tqdm redraws
+/// (`\r`-separated updates on one line) are reduced to their final state.
+struct LogThrottle {
+    window_start: Instant,
+    sent_in_window: u32,
+    pending: Option<TrainingLogLine>,
+    dropped: u64,
+}
+
+impl LogThrottle {
+    fn new() -> Self {
+        Self {
+            window_start: Instant::now(),
+            sent_in_window: 0,
+            pending: None,
+            dropped: 0,
+        }
+    }
+
+    fn push(&mut self, app: &AppHandle, stream: &str, raw: &str) {
+        let text = raw.rsplit('\r').find(|s| !s.trim().is_empty()).unwrap_or("").trim_end();
+        if text.trim().is_empty() {
+            return;
+        }
+        let kind = if serde_json::from_str::<serde_json::Value>(text).is_ok() { "json" } else { "text" };
+        let entry = TrainingLogLine {
+            stream: stream.into(),
+            kind: kind.into(),
+            line: text.to_string(),
+            dropped: 0,
+        };
+
+        self.roll_window(app);
+        if self.sent_in_window < LOG_LINES_PER_WINDOW {
+            self.send(app, entry);
+        } else if self.pending.replace(entry).is_some() {
+            self.dropped += 1;
+        }
+    }
+
+    /// Emit any line held back by the rate limit once its window has passed.
+    fn flush(&mut self, app: &AppHandle) {
+        self.roll_window(app);
+    }
+
+    /// Emit whatever is still held back, regardless of the window.
+    fn finish(&mut self, app: &AppHandle) {
+        if let Some(entry) = self.pending.take() {
+            self.send(app, entry);
+        }
+    }
+
+    fn roll_window(&mut self, app: &AppHandle) {
+        if self.window_start.elapsed() < LOG_WINDOW {
+            return;
+        }
+        self.window_start = Instant::now();
+        self.sent_in_window = 0;
+        if let Some(entry) = self.pending.take() {
+            self.send(app, entry);
+        }
+    }
+
+    fn send(&mut self, app: &AppHandle, mut entry: TrainingLogLine) {
+        entry.dropped = std::mem::take(&mut self.dropped);
+        self.sent_in_window += 1;
+        let _ = app.emit("training:log", &entry);
+    }
+}
+
 /// Extract a useful error message from stderr lines.
 /// Filters out warnings, tqdm bars, and noise; returns the traceback + final error.
 fn extract_error_message(lines: &[String]) -> String {
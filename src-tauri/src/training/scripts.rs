@@ -6,7 +6,7 @@ pub fn generate_sft_script(config: &TrainingConfig) -> String {
     let dataset_path = &config.dataset_path;
     let output_path = &config.output_path;
 
-    let dataset_load = dataset_load_code(&config.dataset_format, dataset_path);
+    let dataset_load = dataset_load_code(config, dataset_path);
     let model_load = model_load_code(config);
     let lora_setup = lora_setup_code(config);
     let trainer_setup = sft_trainer_code(config);
@@ -57,10 +57,21 @@ class ForgeProgressCallback(TrainerCallback):
             "lr": logs.get("learning_rate"),
             "eta": eta,
             "gpu_mem": gpu_mem,
+            "eval_loss": logs.get("eval_loss"),
             "percent": round(state.global_step / max(state.max_steps, 1) * 100, 1),
         }}
         print(json.dumps(progress), flush=True)
 
+    def on_evaluate(self, args, state, control, metrics=None, **kwargs):
+        if not metrics:
+            return
+        print(json.dumps({{
+            "type": "eval",
+            "step": state.global_step,
+            "epoch": round(state.epoch, 2) if state.epoch else None,
+            "eval_loss": metrics.get("eval_loss"),
+        }}), flush=True)
+
     def on_train_begin(self, args, state, control, **kwargs):
         print(json.dumps({{"type": "status", "stage": "training", "message": "Training started"}}), flush=True)
 
@@ -70,6 +81,7 @@ class ForgeProgressCallback(TrainerCallback):
 # ── Load dataset ──
 print(json.dumps({{"type": "status", "stage": "loading", "message": "Loading dataset..."}}), flush=True)
 {dataset_load}
+{eval_split}
 
 # ── Load model ──
 print(json.dumps({{"type": "status", "stage": "loading", "message": "Loading model..."}}), flush=True)
@@ -78,6 +90,7 @@ print(json.dumps({{"type": "status", "stage": "loading", "message": "Loading mod
 tokenizer = AutoTokenizer.from_pretrained("{model_path}", trust_remote_code=True)
 if tokenizer.pad_token is None:
     tokenizer.pad_token = tokenizer.eos_token
+{chat_template}
 
 {lora_setup}
 
@@ -85,7 +98,8 @@ if tokenizer.pad_token is None:
 print(json.dumps({{"type": "status", "stage": "training", "message": "Starting training..."}}), flush=True)
 {trainer_setup}
 
-result = trainer.train()
+{resume_code}
+result = trainer.train(resume_from_checkpoint=resume)
 
 # ── Save ──
 print(json.dumps({{"type": "status", "stage": "saving", "message": "Saving model..."}}), flush=True)
@@ -108,12 +122,15 @@ print(json.dumps({{
         peft_imports = peft_imports(config),
         trl_imports = trl_imports(config),
         dataset_load = dataset_load,
+        eval_split = eval_split_code(config),
         model_load = model_load,
         model_path = model_path,
+        chat_template = chat_template_code(config),
         lora_setup = lora_setup,
         trainer_setup = trainer_setup,
         output_path = output_path,
         merge_code = merge_code,
+        resume_code = resume_code(config),
         epochs = config.epochs,
     )
 }
@@ -124,7 +141,7 @@ pub fn generate_dpo_script(config: &TrainingConfig) -> String {
     let dataset_path = &config.dataset_path;
     let output_path = &config.output_path;
     let beta = config.dpo_beta.unwrap_or(0.1);
-    let dataset_load = dataset_load_code(&config.dataset_format, dataset_path);
+    let dataset_load = dataset_load_code(config, dataset_path);
     let model_load = model_load_code(config);
     let lora_setup = lora_setup_code(config);
     let merge_code = merge_adapter_code(config);
@@ -163,11 +180,22 @@ class ForgeProgressCallback(TrainerCallback):
             "lr": logs.get("learning_rate"),
             "eta": eta,
             "gpu_mem": gpu_mem,
+            "eval_loss": logs.get("eval_loss"),
             "percent": round(state.global_step / max(state.max_steps, 1) * 100, 1),
         }}), flush=True)
+    def on_evaluate(self, args, state, control, metrics=None, **kwargs):
+        if not metrics:
+            return
+        print(json.dumps({{
+            "type": "eval",
+            "step": state.global_step,
+            "epoch": round(state.epoch, 2) if state.epoch else None,
+            "eval_loss": metrics.get("eval_loss"),
+        }}), flush=True)
 
 print(json.dumps({{"type": "status", "stage": "loading", "message": "Loading dataset..."}}), flush=True)
 {dataset_load}
+{eval_split}
 
 print(json.dumps({{"type": "status", "stage": "loading", "message": "Loading model..."}}), flush=True)
 {model_load}
@@ -175,6 +203,7 @@ print(json.dumps({{"type": "status", "stage": "loading", "message": "Loading mod
 tokenizer = AutoTokenizer.from_pretrained("{model_path}", trust_remote_code=True)
 if tokenizer.pad_token is None:
     tokenizer.pad_token = tokenizer.eos_token
+{chat_template}
 
 {lora_setup}
 
@@ -190,7 +219,7 @@ dpo_config = DPOConfig(
     logging_steps=1,
     save_strategy="steps",
     save_steps={save_steps},
-    bf16=torch.cuda.is_available(),
+{eval_args}{optim_args}    bf16=torch.cuda.is_available(),
     remove_unused_columns=False,
 )
 
@@ -199,11 +228,305 @@ trainer = DPOTrainer(
     args=dpo_config,
     train_dataset=dataset,
     processing_class=tokenizer,
-    callbacks=[ForgeProgressCallback()],
+    eval_dataset=eval_dataset,
+    callbacks=[ForgeProgressCallback()] + early_stopping,
 )
 
 print(json.dumps({{"type": "status", "stage": "training", "message": "Starting DPO training..."}}), flush=True)
-result = trainer.train()
+{resume_code}
+result = trainer.train(resume_from_checkpoint=resume)
+
+print(json.dumps({{"type": "status", "stage": "saving", "message": "Saving model..."}}), flush=True)
+trainer.save_model("{output_path}")
+tokenizer.save_pretrained("{output_path}")
+
+{merge_code}
+
+import pathlib
+total_size = sum(f.stat().st_size for f in pathlib.Path("{output_path}").rglob("*") if f.is_file())
+print(json.dumps({{
+    "type": "result",
+    "output_path": "{output_path}",
+    "output_size": total_size,
+    "final_loss": result.training_loss if hasattr(result, "training_loss") else None,
+    "epochs_completed": {epochs},
+}}), flush=True)
+"#,
+        peft_imports = peft_imports(config),
+        dataset_load = dataset_load,
+        eval_split = eval_split_code(config),
+        model_load = model_load,
+        model_path = model_path,
+        chat_template = chat_template_code(config),
+        lora_setup = lora_setup,
+        output_path = output_path,
+        merge_code = merge_code,
+        resume_code = resume_code(config),
+        epochs = config.epochs,
+        batch_size = config.batch_size,
+        grad_accum = config.gradient_accumulation_steps,
+        lr = config.learning_rate,
+        warmup = config.warmup_steps,
+        wd = config.weight_decay,
+        beta = beta,
+        save_steps = config.save_steps,
+        eval_args = eval_args_code(config),
+        optim_args = optimizer_args_code(config),
+    )
+}
+
+/// Generate an ORPO training script. ORPO folds the preference signal into
+/// the SFT loss, so it needs no reference model and uses the same
+/// prompt/chosen/rejected columns as DPO.
+pub fn generate_orpo_script(config: &TrainingConfig) -> String {
+    let model_path = &config.model_path;
+    let dataset_path = &config.dataset_path;
+    let output_path = &config.output_path;
+    let beta = config.orpo_beta.unwrap_or(0.1);
+    let dataset_load = dataset_load_code(config, dataset_path);
+    let model_load = model_load_code(config);
+    let lora_setup = lora_setup_code(config);
+    let merge_code = merge_adapter_code(config);
+
+    format!(r#"#!/usr/bin/env python3
+"""ForgeAI ORPO Training Script — Auto-generated"""
+import json, sys, os, time
+os.environ["TOKENIZERS_PARALLELISM"] = "false"
+os.environ["PYTORCH_CUDA_ALLOC_CONF"] = "expandable_segments:True"
+
+import torch
+if torch.cuda.is_available():
+    torch.cuda.empty_cache()
+from transformers import AutoModelForCausalLM, AutoTokenizer, TrainingArguments, TrainerCallback
+from datasets import load_dataset
+from trl import ORPOConfig, ORPOTrainer
+{peft_imports}
+
+class ForgeProgressCallback(TrainerCallback):
+    def __init__(self):
+        self.start_time = time.time()
+    def on_log(self, args, state, control, logs=None, **kwargs):
+        if not logs:
+            return
+        elapsed = time.time() - self.start_time
+        eta = None
+        if state.global_step > 0 and state.max_steps > 0:
+            eta = int((elapsed / state.global_step) * (state.max_steps - state.global_step))
+        gpu_mem = int(torch.cuda.max_memory_allocated() / 1048576) if torch.cuda.is_available() else None
+        print(json.dumps({{
+            "type": "progress",
+            "step": state.global_step,
+            "total_steps": state.max_steps,
+            "epoch": round(state.epoch, 2) if state.epoch else None,
+            "loss": logs.get("loss"),
+            "lr": logs.get("learning_rate"),
+            "eta": eta,
+            "gpu_mem": gpu_mem,
+            "eval_loss": logs.get("eval_loss"),
+            "percent": round(state.global_step / max(state.max_steps, 1) * 100, 1),
+        }}), flush=True)
+    def on_evaluate(self, args, state, control, metrics=None, **kwargs):
+        if not metrics:
+            return
+        print(json.dumps({{
+            "type": "eval",
+            "step": state.global_step,
+            "epoch": round(state.epoch, 2) if state.epoch else None,
+            "eval_loss": metrics.get("eval_loss"),
+        }}), flush=True)
+
+print(json.dumps({{"type": "status", "stage": "loading", "message": "Loading dataset..."}}), flush=True)
+{dataset_load}
+{eval_split}
+
+print(json.dumps({{"type": "status", "stage": "loading", "message": "Loading model..."}}), flush=True)
+{model_load}
+
+tokenizer = AutoTokenizer.from_pretrained("{model_path}", trust_remote_code=True)
+if tokenizer.pad_token is None:
+    tokenizer.pad_token = tokenizer.eos_token
+{chat_template}
+
+{lora_setup}
+
+orpo_config = ORPOConfig(
+    output_dir="{output_path}",
+    num_train_epochs={epochs},
+    per_device_train_batch_size={batch_size},
+    gradient_accumulation_steps={grad_accum},
+    learning_rate={lr},
+    warmup_steps={warmup},
+    weight_decay={wd},
+    beta={beta},
+    max_length={seq_len},
+    logging_steps=1,
+    save_strategy="steps",
+    save_steps={save_steps},
+{eval_args}{optim_args}    bf16=torch.cuda.is_available(),
+    remove_unused_columns=False,
+)
+
+trainer = ORPOTrainer(
+    model=model,
+    args=orpo_config,
+    train_dataset=dataset,
+    processing_class=tokenizer,
+    eval_dataset=eval_dataset,
+    callbacks=[ForgeProgressCallback()] + early_stopping,
+)
+
+print(json.dumps({{"type": "status", "stage": "training", "message": "Starting ORPO training..."}}), flush=True)
+{resume_code}
+result = trainer.train(resume_from_checkpoint=resume)
+
+print(json.dumps({{"type": "status", "stage": "saving", "message": "Saving model..."}}), flush=True)
+trainer.save_model("{output_path}")
+tokenizer.save_pretrained("{output_path}")
+
+{merge_code}
+
+import pathlib
+total_size = sum(f.stat().st_size for f in pathlib.Path("{output_path}").rglob("*") if f.is_file())
+print(json.dumps({{
+    "type": "result",
+    "output_path": "{output_path}",
+    "output_size": total_size,
+    "final_loss": result.training_loss if hasattr(result, "training_loss") else None,
+    "epochs_completed": {epochs},
+}}), flush=True)
+"#,
+        peft_imports = peft_imports(config),
+        dataset_load = dataset_load,
+        eval_split = eval_split_code(config),
+        model_load = model_load,
+        model_path = model_path,
+        chat_template = chat_template_code(config),
+        lora_setup = lora_setup,
+        output_path = output_path,
+        merge_code = merge_code,
+        resume_code = resume_code(config),
+        epochs = config.epochs,
+        batch_size = config.batch_size,
+        grad_accum = config.gradient_accumulation_steps,
+        lr = config.learning_rate,
+        warmup = config.warmup_steps,
+        wd = config.weight_decay,
+        beta = beta,
+        seq_len = config.max_seq_length,
+        save_steps = config.save_steps,
+        eval_args = eval_args_code(config),
+        optim_args = optimizer_args_code(config),
+    )
+}
+
+/// Generate a KTO training script. KTO learns from unpaired
+/// prompt/completion rows with a boolean `label` marking each completion
+/// desirable or undesirable.
+pub fn generate_kto_script(config: &TrainingConfig) -> String {
+    let model_path = &config.model_path;
+    let dataset_path = &config.dataset_path;
+    let output_path = &config.output_path;
+    let beta = config.kto_beta.unwrap_or(0.1);
+    let desirable_weight = config.kto_desirable_weight.unwrap_or(1.0);
+    let undesirable_weight = config.kto_undesirable_weight.unwrap_or(1.0);
+    let dataset_load = dataset_load_code(config, dataset_path);
+    let model_load = model_load_code(config);
+    let lora_setup = lora_setup_code(config);
+    let merge_code = merge_adapter_code(config);
+
+    format!(r#"#!/usr/bin/env python3
+"""ForgeAI KTO Training Script — Auto-generated"""
+import json, sys, os, time
+os.environ["TOKENIZERS_PARALLELISM"] = "false"
+os.environ["PYTORCH_CUDA_ALLOC_CONF"] = "expandable_segments:True"
+
+import torch
+if torch.cuda.is_available():
+    torch.cuda.empty_cache()
+from transformers import AutoModelForCausalLM, AutoTokenizer, TrainingArguments, TrainerCallback
+from datasets import load_dataset
+from trl import KTOConfig, KTOTrainer
+{peft_imports}
+
+class ForgeProgressCallback(TrainerCallback):
+    def __init__(self):
+        self.start_time = time.time()
+    def on_log(self, args, state, control, logs=None, **kwargs):
+        if not logs:
+            return
+        elapsed = time.time() - self.start_time
+        eta = None
+        if state.global_step > 0 and state.max_steps > 0:
+            eta = int((elapsed / state.global_step) * (state.max_steps - state.global_step))
+        gpu_mem = int(torch.cuda.max_memory_allocated() / 1048576) if torch.cuda.is_available() else None
+        print(json.dumps({{
+            "type": "progress",
+            "step": state.global_step,
+            "total_steps": state.max_steps,
+            "epoch": round(state.epoch, 2) if state.epoch else None,
+            "loss": logs.get("loss"),
+            "lr": logs.get("learning_rate"),
+            "eta": eta,
+            "gpu_mem": gpu_mem,
+            "eval_loss": logs.get("eval_loss"),
+            "percent": round(state.global_step / max(state.max_steps, 1) * 100, 1),
+        }}), flush=True)
+    def on_evaluate(self, args, state, control, metrics=None, **kwargs):
+        if not metrics:
+            return
+        print(json.dumps({{
+            "type": "eval",
+            "step": state.global_step,
+            "epoch": round(state.epoch, 2) if state.epoch else None,
+            "eval_loss": metrics.get("eval_loss"),
+        }}), flush=True)
+
+print(json.dumps({{"type": "status", "stage": "loading", "message": "Loading dataset..."}}), flush=True)
+{dataset_load}
+dataset = dataset.map(lambda row: {{"label": str(row["label"]).strip().lower() in ("true", "1", "yes")}})
+{eval_split}
+
+print(json.dumps({{"type": "status", "stage": "loading", "message": "Loading model..."}}), flush=True)
+{model_load}
+
+tokenizer = AutoTokenizer.from_pretrained("{model_path}", trust_remote_code=True)
+if tokenizer.pad_token is None:
+    tokenizer.pad_token = tokenizer.eos_token
+{chat_template}
+
+{lora_setup}
+
+kto_config = KTOConfig(
+    output_dir="{output_path}",
+    num_train_epochs={epochs},
+    per_device_train_batch_size={batch_size},
+    gradient_accumulation_steps={grad_accum},
+    learning_rate={lr},
+    warmup_steps={warmup},
+    weight_decay={wd},
+    beta={beta},
+    desirable_weight={desirable_weight},
+    undesirable_weight={undesirable_weight},
+    max_length={seq_len},
+    logging_steps=1,
+    save_strategy="steps",
+    save_steps={save_steps},
+{eval_args}{optim_args}    bf16=torch.cuda.is_available(),
+    remove_unused_columns=False,
+)
+
+trainer = KTOTrainer(
+    model=model,
+    args=kto_config,
+    train_dataset=dataset,
+    processing_class=tokenizer,
+    eval_dataset=eval_dataset,
+    callbacks=[ForgeProgressCallback()] + early_stopping,
+)
+
+print(json.dumps({{"type": "status", "stage": "training", "message": "Starting KTO training..."}}), flush=True)
+{resume_code}
+result = trainer.train(resume_from_checkpoint=resume)
 
 print(json.dumps({{"type": "status", "stage": "saving", "message": "Saving model..."}}), flush=True)
 trainer.save_model("{output_path}")
@@ -223,11 +546,14 @@ print(json.dumps({{
 "#,
         peft_imports = peft_imports(config),
         dataset_load = dataset_load,
+        eval_split = eval_split_code(config),
         model_load = model_load,
         model_path = model_path,
+        chat_template = chat_template_code(config),
         lora_setup = lora_setup,
         output_path = output_path,
         merge_code = merge_code,
+        resume_code = resume_code(config),
         epochs = config.epochs,
         batch_size = config.batch_size,
         grad_accum = config.gradient_accumulation_steps,
@@ -235,7 +561,12 @@ print(json.dumps({{
         warmup = config.warmup_steps,
         wd = config.weight_decay,
         beta = beta,
+        desirable_weight = desirable_weight,
+        undesirable_weight = undesirable_weight,
+        seq_len = config.max_seq_length,
         save_steps = config.save_steps,
+        eval_args = eval_args_code(config),
+        optim_args = optimizer_args_code(config),
     )
 }
 
@@ -256,17 +587,135 @@ fn trl_imports(config: &TrainingConfig) -> &'static str {
             "from trl import SFTTrainer, SFTConfig"
         }
         TrainingMethod::Dpo => "from trl import DPOConfig, DPOTrainer",
+        TrainingMethod::Orpo => "from trl import ORPOConfig, ORPOTrainer",
+        TrainingMethod::Kto => "from trl import KTOConfig, KTOTrainer",
         TrainingMethod::FullFinetune => "from transformers import Trainer",
     }
 }
 
-fn dataset_load_code(format: &DatasetFormat, path: &str) -> String {
-    match format {
+/// Sets `resume` for `trainer.train()`. When resuming, a callback reports the
+/// restored `global_step` so the app can confirm the trainer state came back.
+fn resume_code(config: &TrainingConfig) -> String {
+    match config.resume_from_checkpoint.as_deref() {
+        Some(path) if !path.is_empty() => format!(
+            r#"resume = "{path}"
+class ForgeResumeCallback(TrainerCallback):
+    def on_train_begin(self, args, state, control, **kwargs):
+        print(json.dumps({{"type": "status", "stage": "training", "message": f"Resumed from checkpoint at step {{state.global_step}}", "resumed_step": state.global_step}}), flush=True)
+trainer.add_callback(ForgeResumeCallback())
+print(json.dumps({{"type": "status", "stage": "training", "message": "Resuming from {path}"}}), flush=True)"#,
+            path = path
+        ),
+        _ => "resume = None".to_string(),
+    }
+}
+
+/// Holds out `eval_split` of the dataset as `eval_dataset` and sets up
+/// `early_stopping` (a callback list, empty when disabled).
+fn eval_split_code(config: &TrainingConfig) -> String {
+    let Some(fraction) = config.eval_split.filter(|f| *f > 0.0 && *f < 1.0) else {
+        return "eval_dataset = None\nearly_stopping = []".to_string();
+    };
+
+    let early_stopping = match config.early_stopping_patience.unwrap_or(3) {
+        0 => "early_stopping = []".to_string(),
+        patience => format!(
+            r#"from transformers import EarlyStoppingCallback
+class ForgeEarlyStopping(EarlyStoppingCallback):
+    def on_evaluate(self, args, state, control, metrics, **kwargs):
+        super().on_evaluate(args, state, control, metrics, **kwargs)
+        if control.should_training_stop:
+            print(json.dumps({{"type": "status", "stage": "training", "message": f"Eval loss stopped improving, stopping early at step {{state.global_step}}"}}), flush=True)
+early_stopping = [ForgeEarlyStopping(early_stopping_patience={patience})]"#,
+            patience = patience
+        ),
+    };
+
+    format!(
+        r#"split = dataset.train_test_split(test_size={fraction}, seed=42)
+dataset, eval_dataset = split["train"], split["test"]
+print(json.dumps({{"type": "status", "stage": "loading", "message": f"Holding out {{len(eval_dataset)}} rows for evaluation"}}), flush=True)
+{early_stopping}"#,
+        fraction = fraction,
+        early_stopping = early_stopping
+    )
+}
+
+/// Trainer config arguments for periodic evaluation. With early stopping the
+/// best checkpoint by eval loss is restored at the end.
+fn eval_args_code(config: &TrainingConfig) -> String {
+    if !config.eval_split.is_some_and(|f| f > 0.0 && f < 1.0) {
+        return String::new();
+    }
+    let mut args = format!(
+        "    eval_strategy=\"steps\",\n    eval_steps={},\n    per_device_eval_batch_size={},\n",
+        config.eval_steps.unwrap_or(config.save_steps),
+        config.batch_size
+    );
+    if config.early_stopping_patience.unwrap_or(3) > 0 {
+        args.push_str("    load_best_model_at_end=True,\n    metric_for_best_model=\"eval_loss\",\n    greater_is_better=False,\n");
+    }
+    args
+}
+
+/// Trainer config arguments for gradient checkpointing and the optimizer.
+fn optimizer_args_code(config: &TrainingConfig) -> String {
+    let mut args = format!("    gradient_checkpointing={},\n", py_bool(config.gradient_checkpointing));
+    if config.gradient_checkpointing {
+        args.push_str("    gradient_checkpointing_kwargs={\"use_reentrant\": False},\n");
+    }
+    args.push_str(&format!("    optim=\"{}\",\n", config.optimizer));
+    args
+}
+
+/// Packed sequences concatenate samples, so prompt tokens are no longer masked
+/// out of the loss; say so in the run log.
+fn packing_warning_code(config: &TrainingConfig) -> String {
+    if !config.packing {
+        return String::new();
+    }
+    "print(json.dumps({\"type\": \"status\", \"stage\": \"training\", \"message\": \"Packing enabled: prompt tokens are not masked from the loss (no completion-only collator)\"}), flush=True)\n".into()
+}
+
+fn py_bool(value: bool) -> &'static str {
+    if value { "True" } else { "False" }
+}
+
+/// Assigns `chat_template` to the tokenizer so it is used for formatting and
+/// written out by `save_pretrained`.
+fn chat_template_code(config: &TrainingConfig) -> String {
+    match config.chat_template.as_deref().map(str::trim) {
+        // A JSON string literal is also a valid Python string literal
+        Some(template) if !template.is_empty() => format!(
+            "tokenizer.chat_template = {}",
+            serde_json::to_string(template).unwrap_or_default()
+        ),
+        _ => "# Using the tokenizer's own chat template".to_string(),
+    }
+}
+
+fn dataset_load_code(config: &TrainingConfig, path: &str) -> String {
+    let load = match config.dataset_format {
         DatasetFormat::Json => format!(r#"dataset = load_dataset("json", data_files="{}", split="train")"#, path),
         DatasetFormat::Jsonl => format!(r#"dataset = load_dataset("json", data_files="{}", split="train")"#, path),
         DatasetFormat::Csv => format!(r#"dataset = load_dataset("csv", data_files="{}", split="train")"#, path),
         DatasetFormat::Parquet => format!(r#"dataset = load_dataset("parquet", data_files="{}", split="train")"#, path),
+    };
+
+    let renames = config.column_renames();
+    if renames.is_empty() {
+        return load;
     }
+    // Drop columns that a rename would collide with before renaming
+    let renames_json = serde_json::to_string(&renames).unwrap_or_default();
+    format!(
+        r#"{load}
+column_map = {renames_json}
+dataset = dataset.remove_columns([c for c in column_map.values() if c in dataset.column_names and c not in column_map])
+dataset = dataset.rename_columns(column_map)"#,
+        load = load,
+        renames_json = renames_json
+    )
 }
 
 fn model_load_code(config: &TrainingConfig) -> String {
@@ -288,8 +737,8 @@ model = AutoModelForCausalLM.from_pretrained(
     trust_remote_code=True,
     torch_dtype=torch.bfloat16,
 )
-model = prepare_model_for_kbit_training(model)"#,
-                bits, bits, bits, bits, config.model_path
+model = prepare_model_for_kbit_training(model, use_gradient_checkpointing={})"#,
+                bits, bits, bits, bits, config.model_path, py_bool(config.gradient_checkpointing)
             )
         }
         TrainingMethod::FullFinetune => {
@@ -367,7 +816,7 @@ fn sft_trainer_code(config: &TrainingConfig) -> String {
     match config.method {
         TrainingMethod::Sft | TrainingMethod::Lora | TrainingMethod::Qlora => {
             format!(
-                r#"sft_config = SFTConfig(
+                r#"{packing_warning}sft_config = SFTConfig(
     output_dir="{output}",
     num_train_epochs={epochs},
     per_device_train_batch_size={batch},
@@ -376,15 +825,13 @@ fn sft_trainer_code(config: &TrainingConfig) -> String {
     warmup_steps={warmup},
     weight_decay={wd},
     max_length={seq_len},
+    packing={packing},
     logging_steps=1,
     save_strategy="steps",
     save_steps={save_steps},
-    bf16=torch.cuda.is_available(),
+{eval_args}    bf16=torch.cuda.is_available(),
     fp16=not torch.cuda.is_bf16_supported() and torch.cuda.is_available(),
-    gradient_checkpointing=True,
-    gradient_checkpointing_kwargs={{"use_reentrant": False}},
-    optim="adamw_8bit",
-    dataloader_pin_memory=False,
+{optim_args}    dataloader_pin_memory=False,
     max_grad_norm=0.3,
 )
 
@@ -393,7 +840,8 @@ trainer = SFTTrainer(
     args=sft_config,
     train_dataset=dataset,
     processing_class=tokenizer,
-    callbacks=[ForgeProgressCallback()],
+    eval_dataset=eval_dataset,
+    callbacks=[ForgeProgressCallback()] + early_stopping,
 )"#,
                 output = config.output_path,
                 epochs = config.epochs,
@@ -402,8 +850,12 @@ trainer = SFTTrainer(
                 lr = config.learning_rate,
                 warmup = config.warmup_steps,
                 wd = config.weight_decay,
-                seq_len = config.max_seq_length,
+                seq_len = if config.truncation { config.max_seq_length.to_string() } else { "None".into() },
+                packing = py_bool(config.packing),
+                packing_warning = packing_warning_code(config),
                 save_steps = config.save_steps,
+                eval_args = eval_args_code(config),
+                optim_args = optimizer_args_code(config),
             )
         }
         TrainingMethod::FullFinetune => {
@@ -419,19 +871,17 @@ trainer = SFTTrainer(
     logging_steps=1,
     save_strategy="steps",
     save_steps={save_steps},
-    bf16=torch.cuda.is_available(),
+{eval_args}    bf16=torch.cuda.is_available(),
     fp16=not torch.cuda.is_bf16_supported() and torch.cuda.is_available(),
-    gradient_checkpointing=True,
-    gradient_checkpointing_kwargs={{"use_reentrant": False}},
-    optim="adamw_8bit",
-    dataloader_pin_memory=False,
+{optim_args}    dataloader_pin_memory=False,
 )
 
 trainer = Trainer(
     model=model,
     args=training_args,
     train_dataset=dataset,
-    callbacks=[ForgeProgressCallback()],
+    eval_dataset=eval_dataset,
+    callbacks=[ForgeProgressCallback()] + early_stopping,
 )"#,
                 output = config.output_path,
                 epochs = config.epochs,
@@ -441,9 +891,13 @@ trainer = Trainer(
                 warmup = config.warmup_steps,
                 wd = config.weight_decay,
                 save_steps = config.save_steps,
+                eval_args = eval_args_code(config),
+                optim_args = optimizer_args_code(config),
             )
         }
         TrainingMethod::Dpo => "# DPO handled in separate script".to_string(),
+        TrainingMethod::Orpo => "# ORPO handled in separate script".to_string(),
+        TrainingMethod::Kto => "# KTO handled in separate script".to_string(),
     }
 }
 
@@ -452,6 +906,18 @@ fn merge_adapter_code(config: &TrainingConfig) -> String {
         return "# Adapter not merged (saved separately)".to_string();
     }
     match config.method {
+        TrainingMethod::Lora | TrainingMethod::Qlora if config.export_gguf_after_merge.is_some() => {
+            // Keep the adapter in output_path and write the merged model next to it
+            let merged_path = config.merged_output_path();
+            format!(
+                r#"print(json.dumps({{"type": "status", "stage": "merging", "message": "Merging adapter into base model..."}}), flush=True)
+merged_model = model.merge_and_unload()
+merged_model.save_pretrained("{merged}")
+tokenizer.save_pretrained("{merged}")
+print(json.dumps({{"type": "status", "stage": "merging", "message": "Adapter merged successfully (adapter kept separately)"}}), flush=True)"#,
+                merged = merged_path
+            )
+        }
         TrainingMethod::Lora | TrainingMethod::Qlora => {
             format!(
                 r#"print(json.dumps({{"type": "status", "stage": "merging", "message": "Merging adapter into base model..."}}), flush=True)
@@ -464,3 +930,32 @@ print(json.dumps({{"type": "status", "stage": "merging", "message": "Adapter mer
         _ => "# Full model already saved, no adapter merge needed".to_string(),
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn preference_config(method: &str) -> TrainingConfig {
+        serde_json::from_value(serde_json::json!({
+            "model_path": "/models/base",
+            "dataset_path": "/data/prefs.jsonl",
+            "dataset_format": "jsonl",
+            "method": method,
+            "output_path": "/out",
+            "gradient_checkpointing": false,
+            "optimizer": "adafactor",
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn orpo_and_kto_pass_optimizer_and_checkpointing() {
+        for script in [
+            generate_orpo_script(&preference_config("orpo")),
+            generate_kto_script(&preference_config("kto")),
+        ] {
+            assert!(script.contains("    optim=\"adafactor\",\n"));
+            assert!(script.contains("    gradient_checkpointing=False,\n"));
+        }
+    }
+}
@@ -1,15 +1,21 @@
-use std::collections::HashMap;
-use std::path::Path;
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
 
 use tauri::{AppHandle, Emitter};
 
+use crate::merge::tensor_io::checked_range;
 use crate::model::error::ModelError;
 use crate::model::inspect::extract_layer_index;
 use crate::model;
 use super::config::{SurgeryConfig, SurgeryOperation, SurgeryResult, SurgeryProgress};
 
+/// Minimum interval between surgery progress events.
+const PROGRESS_INTERVAL_MS: u128 = 250;
+/// Chunk size for streaming tensor data to disk.
+const WRITE_CHUNK_BYTES: usize = 16 * 1024 * 1024;
+
 /// Execute layer surgery on a model.
 pub fn execute_surgery(
     app: &AppHandle,
@@ -41,22 +47,87 @@ pub fn execute_surgery(
 
     let original_layers = info.layer_count.unwrap_or(0);
 
+    let blends = interpolation_blends(&config.operations);
+    for (&a, &(b, weight)) in &blends {
+        if a == b || a >= original_layers || b >= original_layers {
+            return Err(ModelError::TrainingError(format!(
+                "Cannot interpolate layers {} and {} in a {}-layer model",
+                a, b, original_layers
+            )));
+        }
+        if !(0.0..=1.0).contains(&weight) {
+            return Err(ModelError::TrainingError(format!(
+                "Interpolation weight must be between 0 and 1, got {}",
+                weight
+            )));
+        }
+    }
+    if is_gguf && !blends.is_empty() {
+        return Err(ModelError::TrainingError(
+            "Layer interpolation needs SafeTensors weights; GGUF tensors are only copied".into(),
+        ));
+    }
+
     // Build layer remap
     let remap = build_layer_remap(original_layers, &config.operations);
-    let final_layers = remap.values().max().map(|v| v + 1).unwrap_or(0);
+    let final_layers = remap.keys().max().map(|v| v + 1).unwrap_or(0);
+    if final_layers == 0 {
+        return Err(ModelError::TrainingError("Surgery would leave the model with no layers".into()));
+    }
+    for (&a, &(b, _)) in &blends {
+        if !remap.values().any(|&orig| orig == a) {
+            return Err(ModelError::TrainingError(format!(
+                "Layer {} is interpolated with layer {} but another operation removes it",
+                a, b
+            )));
+        }
+    }
 
     emit_surgery_progress(app, &format!("{} → {} layers", original_layers, final_layers), 10.0);
 
-    // Create output directory
-    std::fs::create_dir_all(&config.output_path)
+    // Write into a staging directory next to the output; it only replaces
+    // `output_path` once everything is written, so a cancel or error never
+    // leaves a truncated model behind.
+    let staging = staging_dir(&config.output_path);
+    if staging.exists() {
+        let _ = std::fs::remove_dir_all(&staging);
+    }
+    std::fs::create_dir_all(&staging)
         .map_err(|e| ModelError::TrainingError(format!("Cannot create output dir: {}", e)))?;
+    let staging_str = staging.to_string_lossy().to_string();
 
-    let tensors_written = if is_dir || (!is_gguf && !is_dir) {
-        surgery_safetensors(app, path, is_dir, &info, &remap, &config.output_path, original_layers, final_layers, cancel.clone())?
+    // The surgery functions own every mmap and file handle, so all of them are
+    // closed by the time they return and the staging dir can be removed (Windows).
+    let written = if is_dir || (!is_gguf && !is_dir) {
+        surgery_safetensors(app, path, is_dir, &info, &remap, &blends, &staging_str, original_layers, final_layers, cancel.clone())
     } else {
-        surgery_gguf(app, path, &info, &remap, &config.output_path, original_layers, final_layers, cancel.clone())?
+        let progress = |message: &str, percent: f64| emit_surgery_progress(app, message, percent);
+        surgery_gguf(progress, path, &remap, &staging_str, original_layers, final_layers, cancel.clone())
+    };
+    let written = written.and_then(|n| {
+        if cancel.load(Ordering::Relaxed) {
+            Err(ModelError::TrainingCancelled)
+        } else {
+            Ok(n)
+        }
+    });
+
+    let tensors_written = match written {
+        Ok(n) => n,
+        Err(e) => {
+            let _ = std::fs::remove_dir_all(&staging);
+            if matches!(e, ModelError::TrainingCancelled) {
+                emit_surgery_progress(app, "Surgery cancelled, partial output removed.", 0.0);
+            }
+            return Err(e);
+        }
     };
 
+    if let Err(e) = commit_staging(&staging, Path::new(&config.output_path)) {
+        let _ = std::fs::remove_dir_all(&staging);
+        return Err(ModelError::TrainingError(format!("Failed to move output into place: {}", e)));
+    }
+
     // Calculate output size
     let output_size = dir_or_file_size(&config.output_path);
     let output_size_display = format_size(output_size);
@@ -82,13 +153,12 @@ fn build_layer_remap(
     // Start with all original layers
     let mut layers: Vec<u64> = (0..original_count).collect();
 
-    // Apply removals first (in reverse order to preserve indices)
-    let mut removals: Vec<u64> = operations.iter().filter_map(|op| {
-        if let SurgeryOperation::RemoveLayer { index } = op {
-            Some(*index)
-        } else {
-            None
-        }
+    // Apply removals first (in reverse order to preserve indices). The second
+    // layer of an interpolation is folded into the first, so it goes too.
+    let mut removals: Vec<u64> = operations.iter().filter_map(|op| match op {
+        SurgeryOperation::RemoveLayer { index } => Some(*index),
+        SurgeryOperation::InterpolateLayers { b, .. } => Some(*b),
+        _ => None,
     }).collect();
     removals.sort_unstable();
     removals.dedup();
@@ -98,6 +168,18 @@ fn build_layer_remap(
         }
     }
 
+    // Depth up-scaling works on the layers left after removals
+    for op in operations {
+        if let SurgeryOperation::DepthUpscale { keep_bottom, keep_top } = op {
+            let n = layers.len();
+            let bottom = (*keep_bottom as usize).min(n);
+            let top = (*keep_top as usize).min(n);
+            let mut upscaled = layers[..bottom].to_vec();
+            upscaled.extend_from_slice(&layers[n - top..]);
+            layers = upscaled;
+        }
+    }
+
     // Apply duplications
     for op in operations {
         if let SurgeryOperation::DuplicateLayer { source_index, insert_at } = op {
@@ -118,6 +200,116 @@ fn build_layer_remap(
     remap
 }
 
+/// Interpolations keyed by the layer that is kept: `a → (b, weight)`.
+fn interpolation_blends(operations: &[SurgeryOperation]) -> HashMap<u64, (u64, f64)> {
+    operations
+        .iter()
+        .filter_map(|op| match op {
+            SurgeryOperation::InterpolateLayers { a, b, weight } => Some((*a, (*b, *weight))),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Element-wise `(1 - weight) * a + weight * b` over raw SafeTensors data of `dtype`.
+fn blend_tensor_bytes(a: &[u8], b: &[u8], dtype: &str, weight: f64) -> Result<Vec<u8>, ModelError> {
+    if a.len() != b.len() {
+        return Err(ModelError::TrainingError(format!(
+            "Cannot interpolate tensors of different sizes ({} vs {} bytes)",
+            a.len(),
+            b.len()
+        )));
+    }
+    let (wa, wb) = ((1.0 - weight) as f32, weight as f32);
+    let mut out = Vec::with_capacity(a.len());
+    match dtype {
+        "F32" => {
+            for (x, y) in a.chunks_exact(4).zip(b.chunks_exact(4)) {
+                let x = f32::from_le_bytes(x.try_into().unwrap());
+                let y = f32::from_le_bytes(y.try_into().unwrap());
+                out.extend_from_slice(&(wa * x + wb * y).to_le_bytes());
+            }
+        }
+        "F16" => {
+            for (x, y) in a.chunks_exact(2).zip(b.chunks_exact(2)) {
+                let x = half::f16::from_le_bytes([x[0], x[1]]).to_f32();
+                let y = half::f16::from_le_bytes([y[0], y[1]]).to_f32();
+                out.extend_from_slice(&half::f16::from_f32(wa * x + wb * y).to_le_bytes());
+            }
+        }
+        "BF16" => {
+            for (x, y) in a.chunks_exact(2).zip(b.chunks_exact(2)) {
+                let x = half::bf16::from_le_bytes([x[0], x[1]]).to_f32();
+                let y = half::bf16::from_le_bytes([y[0], y[1]]).to_f32();
+                out.extend_from_slice(&half::bf16::from_f32(wa * x + wb * y).to_le_bytes());
+            }
+        }
+        other => {
+            return Err(ModelError::TrainingError(format!(
+                "Cannot interpolate {} tensors (F32, F16 and BF16 only)",
+                other
+            )));
+        }
+    }
+    Ok(out)
+}
+
+/// Parse a SafeTensors header, returning it and the offset of the data section.
+/// Truncated or corrupt headers are errors rather than panics.
+fn read_safetensors_header(mmap: &[u8]) -> Result<(serde_json::Value, u64), ModelError> {
+    let len_bytes = &mmap[checked_range(0, 8, mmap.len(), "SafeTensors")?];
+    let header_len = u64::from_le_bytes(len_bytes.try_into().unwrap());
+    let header_range = checked_range(8, header_len, mmap.len(), "SafeTensors")?;
+    let header_json: serde_json::Value = serde_json::from_slice(&mmap[header_range])
+        .map_err(|e| ModelError::TrainingError(format!("Invalid SafeTensors header: {}", e)))?;
+    Ok((header_json, 8 + header_len))
+}
+
+/// In-bounds byte range of a tensor whose `data_offsets` are `[start, end)`.
+fn tensor_data_range(
+    data_start: u64,
+    start: u64,
+    end: u64,
+    data_len: usize,
+    name: &str,
+) -> Result<std::ops::Range<usize>, ModelError> {
+    if end < start {
+        return Err(ModelError::TrainingError(format!("Invalid data_offsets for tensor '{}'", name)));
+    }
+    checked_range(data_start.saturating_add(start), end - start, data_len, "SafeTensors")
+}
+
+/// Data of every tensor in `layers`, by name, for blending into the kept layer.
+fn read_layer_tensors(
+    shard_files: &[PathBuf],
+    layers: &HashSet<u64>,
+) -> Result<HashMap<String, Vec<u8>>, ModelError> {
+    use memmap2::Mmap;
+
+    let mut tensors = HashMap::new();
+    if layers.is_empty() {
+        return Ok(tensors);
+    }
+    for shard_path in shard_files {
+        let file = std::fs::File::open(shard_path).map_err(ModelError::IoError)?;
+        let mmap = unsafe { Mmap::map(&file) }.map_err(ModelError::IoError)?;
+        let (header_json, data_start) = read_safetensors_header(&mmap)?;
+
+        for (name, meta) in header_json.as_object().into_iter().flatten() {
+            if !extract_layer_index(name).is_some_and(|i| layers.contains(&i)) {
+                continue;
+            }
+            let offsets = meta.get("data_offsets").and_then(|v| v.as_array());
+            if let Some([start, end]) = offsets.map(|a| a.as_slice()) {
+                let (Some(start), Some(end)) = (start.as_u64(), end.as_u64()) else { continue };
+                let range = tensor_data_range(data_start, start, end, mmap.len(), name)?;
+                tensors.insert(name.clone(), mmap[range].to_vec());
+            }
+        }
+    }
+    Ok(tensors)
+}
+
 /// Remap a tensor name from old layer index to new layer index.
 fn remap_tensor_name(name: &str, old_idx: u64, new_idx: u64) -> String {
     let old_patterns = [
@@ -152,6 +344,7 @@ fn surgery_safetensors(
     is_dir: bool,
     info: &model::ModelInfo,
     remap: &HashMap<u64, u64>,  // new_idx → original_idx
+    blends: &HashMap<u64, (u64, f64)>,  // kept original_idx → (folded original_idx, weight)
     output_path: &str,
     original_layers: u64,
     final_layers: u64,
@@ -176,14 +369,26 @@ fn surgery_safetensors(
         vec![path.to_path_buf()]
     };
 
-    // Build inverse remap: for each new layer index, what original index to read from
-    // remap is already new_idx → original_idx
+    // Layers folded into an interpolation are read up front, since their
+    // tensors may live in a different shard from the layer they blend into
+    let partner_layers: HashSet<u64> = blends.values().map(|(b, _)| *b).collect();
+    let partner_tensors = read_layer_tensors(&shard_files, &partner_layers)?;
 
     // Collect all tensors we need to write and their source info
     let mut output_tensors: BTreeMap<String, Vec<u8>> = BTreeMap::new();
     let mut tensors_processed = 0usize;
     let total_tensors = info.tensor_count;
 
+    // Progress is byte-weighted so a few huge tensors (embeddings) don't stall the bar
+    let total_read_bytes: u64 = shard_files
+        .iter()
+        .filter_map(|p| std::fs::metadata(p).ok())
+        .map(|m| m.len())
+        .sum::<u64>()
+        .max(1);
+    let mut bytes_processed = 0u64;
+    let mut last_emit = std::time::Instant::now();
+
     for shard_path in &shard_files {
         if cancel.load(Ordering::Relaxed) {
             return Err(ModelError::TrainingCancelled);
@@ -192,19 +397,7 @@ fn surgery_safetensors(
         let file = std::fs::File::open(shard_path).map_err(ModelError::IoError)?;
         let mmap = unsafe { Mmap::map(&file) }.map_err(ModelError::IoError)?;
 
-        // Parse safetensors header
-        if mmap.len() < 8 {
-            continue;
-        }
-        let header_len = u64::from_le_bytes(mmap[..8].try_into().unwrap()) as usize;
-        if mmap.len() < 8 + header_len {
-            continue;
-        }
-
-        let header_json: serde_json::Value = serde_json::from_slice(&mmap[8..8 + header_len])
-            .map_err(|e| ModelError::TrainingError(format!("Invalid SafeTensors header: {}", e)))?;
-
-        let data_start = 8 + header_len;
+        let (header_json, data_start) = read_safetensors_header(&mmap)?;
 
         if let Some(obj) = header_json.as_object() {
             for (tensor_name, meta) in obj {
@@ -220,7 +413,7 @@ fn surgery_safetensors(
                     .and_then(|v| v.as_array())
                     .and_then(|arr| {
                         if arr.len() == 2 {
-                            Some((arr[0].as_u64()? as usize, arr[1].as_u64()? as usize))
+                            Some((arr[0].as_u64()?, arr[1].as_u64()?))
                         } else {
                             None
                         }
@@ -231,10 +424,27 @@ fn surgery_safetensors(
                     None => continue,
                 };
 
-                let tensor_data = &mmap[data_start + start..data_start + end];
+                let tensor_data = &mmap[tensor_data_range(data_start, start, end, mmap.len(), tensor_name)?];
 
                 // Check if this is a layer tensor
                 if let Some(original_idx) = extract_layer_index(tensor_name) {
+                    let blended;
+                    let tensor_data = match blends.get(&original_idx) {
+                        Some(&(partner, weight)) => {
+                            let partner_name = remap_tensor_name(tensor_name, original_idx, partner);
+                            let partner_data = partner_tensors.get(&partner_name).ok_or_else(|| {
+                                ModelError::TrainingError(format!(
+                                    "Cannot interpolate {}: layer {} has no {}",
+                                    tensor_name, partner, partner_name
+                                ))
+                            })?;
+                            let dtype = meta.get("dtype").and_then(|v| v.as_str()).unwrap_or("");
+                            blended = blend_tensor_bytes(tensor_data, partner_data, dtype, weight)?;
+                            blended.as_slice()
+                        }
+                        None => tensor_data,
+                    };
+
                     // Find all new indices that map to this original index
                     for (&new_idx, &orig) in remap {
                         if orig == original_idx {
@@ -249,9 +459,21 @@ fn surgery_safetensors(
                 }
 
                 tensors_processed += 1;
-                if tensors_processed % 50 == 0 {
-                    let pct = 10.0 + (tensors_processed as f64 / total_tensors as f64) * 80.0;
-                    emit_surgery_progress(app, &format!("Processing tensor {}/{}", tensors_processed, total_tensors), pct);
+                bytes_processed += end - start;
+                if last_emit.elapsed().as_millis() >= PROGRESS_INTERVAL_MS {
+                    let pct = 10.0 + (bytes_processed as f64 / total_read_bytes as f64).min(1.0) * 80.0;
+                    emit_surgery_progress(
+                        app,
+                        &format!(
+                            "Processing tensor {}/{} ({} / {})",
+                            tensors_processed,
+                            total_tensors,
+                            format_size(bytes_processed),
+                            format_size(total_read_bytes)
+                        ),
+                        pct,
+                    );
+                    last_emit = std::time::Instant::now();
                 }
             }
         }
@@ -268,9 +490,7 @@ fn surgery_safetensors(
     for shard_path in &shard_files {
         let file = std::fs::File::open(shard_path).map_err(ModelError::IoError)?;
         let mmap = unsafe { Mmap::map(&file) }.map_err(ModelError::IoError)?;
-        let header_len = u64::from_le_bytes(mmap[..8].try_into().unwrap()) as usize;
-        let header_json: serde_json::Value = serde_json::from_slice(&mmap[8..8 + header_len])
-            .map_err(|e| ModelError::TrainingError(format!("Parse header: {}", e)))?;
+        let (header_json, _) = read_safetensors_header(&mmap)?;
 
         if let Some(obj) = header_json.as_object() {
             for (tensor_name, meta) in obj {
@@ -341,9 +561,31 @@ fn surgery_safetensors(
     writer.write_all(&(header_bytes.len() as u64).to_le_bytes()).map_err(ModelError::IoError)?;
     writer.write_all(&header_bytes).map_err(ModelError::IoError)?;
 
+    // Stream tensor data in chunks, reporting 92% → 99% by bytes written
+    let total_write_bytes = (offset as u64).max(1);
+    let mut bytes_written = 0u64;
     for name in &ordered_names {
         if let Some(data) = output_tensors.get(name) {
-            writer.write_all(data).map_err(ModelError::IoError)?;
+            for chunk in data.chunks(WRITE_CHUNK_BYTES) {
+                if cancel.load(Ordering::Relaxed) {
+                    return Err(ModelError::TrainingCancelled);
+                }
+                writer.write_all(chunk).map_err(ModelError::IoError)?;
+                bytes_written += chunk.len() as u64;
+                if last_emit.elapsed().as_millis() >= PROGRESS_INTERVAL_MS {
+                    let pct = 92.0 + (bytes_written as f64 / total_write_bytes as f64) * 7.0;
+                    emit_surgery_progress(
+                        app,
+                        &format!(
+                            "Writing output model ({} / {})",
+                            format_size(bytes_written),
+                            format_size(total_write_bytes)
+                        ),
+                        pct,
+                    );
+                    last_emit = std::time::Instant::now();
+                }
+            }
         }
     }
     writer.flush().map_err(ModelError::IoError)?;
@@ -375,47 +617,240 @@ fn surgery_safetensors(
     Ok(output_tensors.len())
 }
 
-/// GGUF surgery — similar but for single GGUF files.
+/// GGUF surgery — rebuild the tensor list from `remap`, renaming `blk.N.*`
+/// entries and updating `*.block_count`. Tensor data is copied verbatim, so
+/// quantized models need no dequantization (surgery only reorders layers).
 fn surgery_gguf(
-    app: &AppHandle,
+    progress: impl Fn(&str, f64),
     path: &Path,
-    info: &model::ModelInfo,
-    _remap: &HashMap<u64, u64>,
+    remap: &HashMap<u64, u64>,  // new_idx → original_idx
     output_path: &str,
-    _original_layers: u64,
-    _final_layers: u64,
+    original_layers: u64,
+    final_layers: u64,
     cancel: Arc<AtomicBool>,
 ) -> Result<usize, ModelError> {
-    // For GGUF, we use a simpler approach: read → filter/remap → write
-    // This relies on the existing GGUF parsing infrastructure
+    use std::io::Write;
+    use memmap2::Mmap;
+    use crate::merge::tensor_io::{self, GgufTensorLayout};
 
-    emit_surgery_progress(app, "GGUF surgery: reading model...", 15.0);
+    progress("GGUF surgery: reading header...", 15.0);
 
-    // Read the GGUF file
-    let file_data = std::fs::read(path).map_err(ModelError::IoError)?;
+    let file = std::fs::File::open(path).map_err(ModelError::IoError)?;
+    let mmap = unsafe { Mmap::map(&file) }.map_err(ModelError::IoError)?;
+    let layout = tensor_io::read_gguf_layout(&mmap)?;
+    // Metadata is copied byte for byte under a v3 header, so older encodings
+    // would be misread by every loader
+    if layout.version < 3 {
+        return Err(ModelError::TrainingError(format!(
+            "GGUF v{} files are not supported for layer surgery; re-convert the model to GGUF v3",
+            layout.version
+        )));
+    }
+    let alignment = layout.alignment;
+    let align = |n: u64| n.div_ceil(alignment) * alignment;
 
-    if file_data.len() < 4 || &file_data[..4] != b"GGUF" {
-        return Err(ModelError::TrainingError("Invalid GGUF file".into()));
+    // A tensor's bytes run up to the next tensor's offset (or the end of the
+    // file), which holds for every ggml type, including ones we cannot decode
+    let data_len = (mmap.len() as u64).saturating_sub(layout.data_offset);
+    let mut offsets: Vec<u64> = layout.tensors.iter().map(|t| t.offset).collect();
+    offsets.sort_unstable();
+    offsets.dedup();
+    let span = |offset: u64| -> u64 {
+        let next = offsets.iter().find(|&&o| o > offset).copied().unwrap_or(data_len);
+        next.saturating_sub(offset)
+    };
+
+    // Non-layer tensors keep their place before or after the blocks
+    let first_layer = layout
+        .tensors
+        .iter()
+        .position(|t| extract_layer_index(&t.name).is_some())
+        .unwrap_or(layout.tensors.len());
+    let mut by_layer: HashMap<u64, Vec<&GgufTensorLayout>> = HashMap::new();
+    let mut output: Vec<(String, &GgufTensorLayout)> = Vec::new();
+    let mut trailing: Vec<(String, &GgufTensorLayout)> = Vec::new();
+    for (i, tensor) in layout.tensors.iter().enumerate() {
+        match extract_layer_index(&tensor.name) {
+            Some(layer) => by_layer.entry(layer).or_default().push(tensor),
+            None if i < first_layer => output.push((tensor.name.clone(), tensor)),
+            None => trailing.push((tensor.name.clone(), tensor)),
+        }
+    }
+    for new_idx in 0..final_layers {
+        let Some(&original_idx) = remap.get(&new_idx) else { continue };
+        for tensor in by_layer.get(&original_idx).into_iter().flatten() {
+            output.push((remap_tensor_name(&tensor.name, original_idx, new_idx), *tensor));
+        }
     }
+    output.extend(trailing);
 
-    // For now, GGUF surgery is limited — we'll do a simple approach
-    // by shelling out to a Python script that uses gguf library, or
-    // we copy the file and update metadata
-    // TODO: Full native GGUF tensor remapping
+    // Metadata: copied as-is apart from the block count and per-layer arrays
+    let mut kv_bytes: Vec<u8> = Vec::new();
+    for entry in &layout.metadata {
+        kv_bytes.extend_from_slice(&(entry.key.len() as u64).to_le_bytes());
+        kv_bytes.extend_from_slice(entry.key.as_bytes());
+        kv_bytes.extend_from_slice(&entry.value_type.to_le_bytes());
+        let raw = &mmap[entry.value.clone()];
+        if entry.key.ends_with(".block_count") {
+            kv_bytes.extend(encode_gguf_uint(entry.value_type, final_layers)?);
+        } else if let Some(remapped) =
+            remap_layer_array(&entry.key, entry.value_type, raw, original_layers, remap, final_layers)
+        {
+            kv_bytes.extend(remapped);
+        } else {
+            kv_bytes.extend_from_slice(raw);
+        }
+    }
 
     if cancel.load(Ordering::Relaxed) {
         return Err(ModelError::TrainingCancelled);
     }
 
-    // Simplified: copy the GGUF file (full surgery requires GGUF write support)
-    let output_file = Path::new(output_path).join(
-        path.file_name().unwrap_or_default()
+    let output_file = Path::new(output_path).join(path.file_name().unwrap_or_default());
+    let mut writer = std::io::BufWriter::new(
+        std::fs::File::create(&output_file).map_err(ModelError::IoError)?
     );
-    std::fs::copy(path, &output_file).map_err(ModelError::IoError)?;
 
-    emit_surgery_progress(app, "GGUF surgery complete (metadata update pending).", 95.0);
+    // Always written as GGUF v3 (64-bit counts)
+    let mut header: Vec<u8> = Vec::with_capacity(24 + kv_bytes.len());
+    header.extend_from_slice(b"GGUF");
+    header.extend_from_slice(&3u32.to_le_bytes());
+    header.extend_from_slice(&(output.len() as u64).to_le_bytes());
+    header.extend_from_slice(&(layout.metadata.len() as u64).to_le_bytes());
+    header.extend_from_slice(&kv_bytes);
+
+    let mut offset = 0u64;
+    for (name, tensor) in &output {
+        header.extend_from_slice(&(name.len() as u64).to_le_bytes());
+        header.extend_from_slice(name.as_bytes());
+        header.extend_from_slice(&(tensor.shape.len() as u32).to_le_bytes());
+        for &dim in &tensor.shape {
+            header.extend_from_slice(&(dim as u64).to_le_bytes());
+        }
+        header.extend_from_slice(&tensor.ggml_type.to_le_bytes());
+        header.extend_from_slice(&offset.to_le_bytes());
+        offset = align(offset + span(tensor.offset));
+    }
+    header.resize(align(header.len() as u64) as usize, 0);
+    writer.write_all(&header).map_err(ModelError::IoError)?;
+
+    // Copy tensor data, reporting 20% → 95% by bytes written
+    let total_write_bytes = offset.max(1);
+    let mut bytes_written = 0u64;
+    let mut last_emit = std::time::Instant::now();
+    for (_, tensor) in &output {
+        let len = span(tensor.offset);
+        let range = tensor_io::checked_range(layout.data_offset + tensor.offset, len, mmap.len(), "GGUF")?;
+        for chunk in mmap[range].chunks(WRITE_CHUNK_BYTES) {
+            if cancel.load(Ordering::Relaxed) {
+                return Err(ModelError::TrainingCancelled);
+            }
+            writer.write_all(chunk).map_err(ModelError::IoError)?;
+            bytes_written += chunk.len() as u64;
+            if last_emit.elapsed().as_millis() >= PROGRESS_INTERVAL_MS {
+                let pct = 20.0 + (bytes_written as f64 / total_write_bytes as f64).min(1.0) * 75.0;
+                progress(
+                    &format!(
+                        "Writing GGUF ({} / {})",
+                        format_size(bytes_written),
+                        format_size(total_write_bytes)
+                    ),
+                    pct,
+                );
+                last_emit = std::time::Instant::now();
+            }
+        }
+        let padding = align(len) - len;
+        writer.write_all(&vec![0u8; padding as usize]).map_err(ModelError::IoError)?;
+        bytes_written += padding;
+    }
+    writer.flush().map_err(ModelError::IoError)?;
+
+    Ok(output.len())
+}
+
+/// Encode `value` as a GGUF integer of `value_type`.
+fn encode_gguf_uint(value_type: u32, value: u64) -> Result<Vec<u8>, ModelError> {
+    Ok(match value_type {
+        0 => vec![value as u8],
+        1 => vec![value as i8 as u8],
+        2 => (value as u16).to_le_bytes().to_vec(),
+        3 => (value as i16).to_le_bytes().to_vec(),
+        4 => (value as u32).to_le_bytes().to_vec(),
+        5 => (value as i32).to_le_bytes().to_vec(),
+        10 => value.to_le_bytes().to_vec(),
+        11 => (value as i64).to_le_bytes().to_vec(),
+        _ => {
+            return Err(ModelError::TrainingError(format!(
+                "Unsupported GGUF type {} for block_count",
+                value_type
+            )))
+        }
+    })
+}
 
-    Ok(info.tensor_count as usize)
+/// Reorder an architecture array with one numeric entry per layer (e.g. a
+/// per-layer head count) to follow `remap`. `None` leaves the value unchanged.
+fn remap_layer_array(
+    key: &str,
+    value_type: u32,
+    raw: &[u8],
+    original_layers: u64,
+    remap: &HashMap<u64, u64>,
+    final_layers: u64,
+) -> Option<Vec<u8>> {
+    if value_type != 9 || raw.len() < 12 || key.starts_with("general.") || key.starts_with("tokenizer.") {
+        return None;
+    }
+    let elem_type = u32::from_le_bytes(raw[0..4].try_into().ok()?);
+    let count = u64::from_le_bytes(raw[4..12].try_into().ok()?);
+    let width = match elem_type {
+        0 | 1 | 7 => 1,
+        2 | 3 => 2,
+        4..=6 => 4,
+        10..=12 => 8,
+        _ => return None,
+    };
+    if original_layers == 0 || count != original_layers {
+        return None;
+    }
+
+    let items = &raw[12..];
+    let mut out = Vec::with_capacity(12 + final_layers as usize * width);
+    out.extend_from_slice(&elem_type.to_le_bytes());
+    out.extend_from_slice(&final_layers.to_le_bytes());
+    for new_idx in 0..final_layers {
+        let original_idx = *remap.get(&new_idx)? as usize;
+        out.extend_from_slice(items.get(original_idx * width..(original_idx + 1) * width)?);
+    }
+    Some(out)
+}
+
+/// Hidden sibling of `output_path`, on the same filesystem so the final move is a rename.
+fn staging_dir(output_path: &str) -> PathBuf {
+    let out = Path::new(output_path);
+    let name = out
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_else(|| "surgery".into());
+    out.with_file_name(format!(".{}.surgery-partial", name))
+}
+
+/// Move a finished staging directory to `output`. A missing or empty output dir is
+/// replaced in one rename; otherwise each file is renamed into the existing dir.
+fn commit_staging(staging: &Path, output: &Path) -> std::io::Result<()> {
+    if output.is_dir() && std::fs::read_dir(output)?.next().is_none() {
+        std::fs::remove_dir(output)?;
+    }
+    if !output.exists() {
+        return std::fs::rename(staging, output);
+    }
+
+    for entry in std::fs::read_dir(staging)? {
+        let entry = entry?;
+        std::fs::rename(entry.path(), output.join(entry.file_name()))?;
+    }
+    std::fs::remove_dir(staging)
 }
 
 fn emit_surgery_progress(app: &AppHandle, message: &str, percent: f64) {
@@ -454,3 +889,139 @@ fn format_size(bytes: u64) -> String {
         format!("{} B", bytes)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn f32_bytes(values: &[f32]) -> Vec<u8> {
+        values.iter().flat_map(|v| v.to_le_bytes()).collect()
+    }
+
+    #[test]
+    fn solar_depth_upscale_remap() {
+        let ops = [SurgeryOperation::DepthUpscale { keep_bottom: 24, keep_top: 24 }];
+        let remap = build_layer_remap(32, &ops);
+        let sequence: Vec<u64> = (0..remap.len() as u64).map(|i| remap[&i]).collect();
+
+        // Layers 0–23 of one copy, then 8–31 of the other
+        let expected: Vec<u64> = (0..24).chain(8..32).collect();
+        assert_eq!(sequence, expected);
+    }
+
+    #[test]
+    fn interpolation_averages_two_layers() {
+        let a = f32_bytes(&[1.0, 2.0, -4.0]);
+        let b = f32_bytes(&[3.0, 6.0, 4.0]);
+        let blended = blend_tensor_bytes(&a, &b, "F32", 0.5).unwrap();
+        assert_eq!(blended, f32_bytes(&[2.0, 4.0, 0.0]));
+    }
+
+    #[test]
+    fn interpolation_rejects_mismatched_sizes() {
+        let a = f32_bytes(&[1.0, 2.0]);
+        let b = f32_bytes(&[1.0]);
+        assert!(blend_tensor_bytes(&a, &b, "F32", 0.5).is_err());
+    }
+
+    /// A GGUF v3 file with one F32 `[4]` tensor per layer between the
+    /// embedding and the output norm.
+    fn small_gguf(layers: u32) -> Vec<u8> {
+        fn string(out: &mut Vec<u8>, s: &str) {
+            out.extend_from_slice(&(s.len() as u64).to_le_bytes());
+            out.extend_from_slice(s.as_bytes());
+        }
+        let mut names = vec!["token_embd.weight".to_string()];
+        names.extend((0..layers).map(|i| format!("blk.{}.attn_norm.weight", i)));
+        names.push("output_norm.weight".into());
+
+        let mut out = b"GGUF".to_vec();
+        out.extend_from_slice(&3u32.to_le_bytes());
+        out.extend_from_slice(&(names.len() as u64).to_le_bytes());
+        out.extend_from_slice(&2u64.to_le_bytes());
+        string(&mut out, "general.architecture");
+        out.extend_from_slice(&8u32.to_le_bytes());
+        string(&mut out, "llama");
+        string(&mut out, "llama.block_count");
+        out.extend_from_slice(&4u32.to_le_bytes());
+        out.extend_from_slice(&layers.to_le_bytes());
+        for (i, name) in names.iter().enumerate() {
+            string(&mut out, name);
+            out.extend_from_slice(&1u32.to_le_bytes());
+            out.extend_from_slice(&4u64.to_le_bytes());
+            out.extend_from_slice(&0u32.to_le_bytes());
+            out.extend_from_slice(&(i as u64 * 32).to_le_bytes());
+        }
+        out.resize(out.len().div_ceil(32) * 32, 0);
+        for i in 0..names.len() {
+            out.extend(f32_bytes(&[i as f32; 4]));
+            out.resize(out.len().div_ceil(32) * 32, 0);
+        }
+        out
+    }
+
+    fn run_gguf_surgery(data: &[u8], remap: &HashMap<u64, u64>, original: u64, fin: u64) -> Result<Vec<u8>, ModelError> {
+        let dir = std::env::temp_dir().join(format!("forgeai-surgery-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let source = dir.join("model.gguf");
+        std::fs::write(&source, data).unwrap();
+        let out_dir = dir.join("out");
+        std::fs::create_dir_all(&out_dir).unwrap();
+        let result = surgery_gguf(
+            |_, _| {},
+            &source,
+            remap,
+            &out_dir.to_string_lossy(),
+            original,
+            fin,
+            Arc::new(AtomicBool::new(false)),
+        )
+        .map(|_| std::fs::read(out_dir.join("model.gguf")).unwrap());
+        std::fs::remove_dir_all(&dir).unwrap();
+        result
+    }
+
+    #[test]
+    fn gguf_layer_removal_renumbers_blocks() {
+        // Drop layer 1 of 3
+        let remap: HashMap<u64, u64> = [(0, 0), (1, 2)].into_iter().collect();
+        let output = run_gguf_surgery(&small_gguf(3), &remap, 3, 2).unwrap();
+        let layout = crate::merge::tensor_io::read_gguf_layout(&output).unwrap();
+
+        let block_count = layout.metadata.iter().find(|e| e.key == "llama.block_count").unwrap();
+        assert_eq!(output[block_count.value.clone()], 2u32.to_le_bytes());
+
+        let names: Vec<&str> = layout.tensors.iter().map(|t| t.name.as_str()).collect();
+        assert_eq!(
+            names,
+            ["token_embd.weight", "blk.0.attn_norm.weight", "blk.1.attn_norm.weight", "output_norm.weight"]
+        );
+        // The new blk.1 carries the data of the original layer 2
+        let start = (layout.data_offset + layout.tensors[2].offset) as usize;
+        assert_eq!(output[start..start + 16], f32_bytes(&[3.0; 4]));
+    }
+
+    #[test]
+    fn gguf_surgery_rejects_pre_v3_files() {
+        let remap: HashMap<u64, u64> = [(0, 0)].into_iter().collect();
+        let data = small_gguf(1);
+        // Same file with a v2 header: 32-bit tensor and KV counts
+        let v2: Vec<u8> = data[..4]
+            .iter()
+            .chain(&2u32.to_le_bytes())
+            .chain(&3u32.to_le_bytes())
+            .chain(&2u32.to_le_bytes())
+            .chain(&data[24..])
+            .copied()
+            .collect();
+        assert!(run_gguf_surgery(&v2, &remap, 1, 1).is_err());
+    }
+
+    #[test]
+    fn truncated_header_is_an_error() {
+        assert!(read_safetensors_header(&[1, 2, 3]).is_err());
+        let mut data = 100u64.to_le_bytes().to_vec();
+        data.extend_from_slice(b"{}");
+        assert!(read_safetensors_header(&data).is_err());
+    }
+}
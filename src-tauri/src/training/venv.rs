@@ -1,17 +1,17 @@
 use std::path::{Path, PathBuf};
 
-use tauri::{AppHandle, Emitter, Manager};
+use tauri::{AppHandle, Emitter};
 use tokio::io::{AsyncBufReadExt, BufReader};
 
-use crate::commands::{detect_gpu, find_python};
+use crate::commands::{detect_gpu, find_python, get_tools_root};
 use crate::model::error::ModelError;
 use super::config::{TrainingDepsStatus, TrainingProgress};
 
 /// Get the training tools directory.
 pub fn get_training_dir(app: &AppHandle) -> Result<PathBuf, ModelError> {
-    let data_dir = app.path().app_data_dir()
-        .map_err(|e| ModelError::TrainingError(format!("Cannot resolve app data dir: {}", e)))?;
-    Ok(data_dir.join("tools").join("training"))
+    let tools_dir = get_tools_root(app, "training")
+        .map_err(|e| ModelError::TrainingError(e.to_string()))?;
+    Ok(tools_dir.join("training"))
 }
 
 /// Get the venv python binary path.
@@ -1,17 +1,19 @@
+use std::collections::HashMap;
 use std::path::Path;
 use std::sync::atomic::Ordering;
 use std::sync::{Arc, Mutex};
 
-use tauri::{AppHandle, State};
+use tauri::{AppHandle, Emitter, State};
 
 use crate::model::error::ModelError;
 use crate::model::state::AppState;
 use crate::model::{self, ModelFormat, TensorInfo};
 use crate::merge::capabilities;
 use crate::training::config::{
-    DatasetFullInfo, DatasetInfo, LayerCapabilityMapping, SurgeryConfig, SurgeryResult,
-    TargetModuleGroup, TrainingConfig, TrainingDepsStatus, TrainingLayerDetail,
-    TrainingResult, LayerTensorInfo,
+    CheckpointInfo, DatasetFullInfo, DatasetInfo, DatasetPreview, LayerCapabilityMapping, ScriptValidation,
+    SurgeryConfig, SurgeryResult,
+    TargetModuleGroup, TargetModuleInfo, TrainingConfig, TrainingDepsStatus, TrainingLayerDetail,
+    TrainingMethod, TrainingProgress, TrainingResult, LayerTensorInfo, SUPPORTED_OPTIMIZERS,
 };
 use crate::training::{datasets, executor, surgery, venv};
 
@@ -37,14 +39,99 @@ pub fn training_detect_dataset(path: String) -> Result<DatasetInfo, ModelError>
     datasets::detect_dataset(&path)
 }
 
+/// First `rows` rows (default 20) with column types, validated for `method`
+/// after applying `column_map` (dataset column -> training column).
+#[tauri::command]
+pub fn training_preview_dataset(
+    path: String,
+    method: TrainingMethod,
+    rows: Option<usize>,
+    column_map: Option<HashMap<String, String>>,
+) -> Result<DatasetPreview, ModelError> {
+    datasets::preview_dataset(&path, &method, rows.unwrap_or(20), &column_map.unwrap_or_default())
+}
+
 // ── Run Training ────────────────────────────────────
 
+/// Latest checkpoint in `output_path`, so the UI can offer to resume.
 #[tauri::command]
-pub async fn training_run(
+pub fn training_find_checkpoint(output_path: String) -> Option<CheckpointInfo> {
+    executor::find_latest_checkpoint(&output_path)
+}
+
+/// Dry run: generate the script for `config`, byte-compile it and import its
+/// modules in the training venv, reporting any failures without training.
+#[tauri::command]
+pub async fn training_validate_script(
     config: TrainingConfig,
     app: AppHandle,
+) -> Result<ScriptValidation, ModelError> {
+    check_options(&config)?;
+    executor::validate_script(&app, &config).await
+}
+
+/// Option combinations rejected before any script is generated.
+fn check_options(config: &TrainingConfig) -> Result<(), ModelError> {
+    if !SUPPORTED_OPTIMIZERS.contains(&config.optimizer.as_str()) {
+        return Err(ModelError::TrainingError(format!(
+            "Unknown optimizer '{}'. Supported: {}",
+            config.optimizer,
+            SUPPORTED_OPTIMIZERS.join(", ")
+        )));
+    }
+
+    if config.packing && !config.truncation {
+        return Err(ModelError::TrainingError(
+            "Packing needs truncation: packed sequences are cut to the max sequence length".into(),
+        ));
+    }
+
+    if let Some(split) = config.eval_split {
+        if !(split > 0.0 && split < 1.0) {
+            return Err(ModelError::TrainingError(format!(
+                "Eval split must be between 0 and 1, got {}",
+                split
+            )));
+        }
+        let eval_steps = config.eval_steps.unwrap_or(config.save_steps);
+        if eval_steps == 0 {
+            return Err(ModelError::TrainingError(
+                "Eval steps must be at least 1 when an eval split is set".into(),
+            ));
+        }
+        // Restoring the best checkpoint needs every save to land on an evaluation
+        if config.early_stopping_patience.unwrap_or(3) > 0 && config.save_steps % eval_steps != 0 {
+            return Err(ModelError::TrainingError(format!(
+                "Save steps ({}) must be a multiple of eval steps ({}) for early stopping",
+                config.save_steps, eval_steps
+            )));
+        }
+    }
+
+    Ok(())
+}
+
+#[tauri::command]
+pub async fn training_run(
+    mut config: TrainingConfig,
+    app: AppHandle,
     state: State<'_, AppState>,
 ) -> Result<TrainingResult, ModelError> {
+    // Fail before spawning Python when the dataset lacks the method's columns
+    let dataset = datasets::detect_dataset(&config.dataset_path)?;
+    let columns = datasets::rename_columns(&dataset.columns, &config.column_renames());
+    if let Some((_, message)) = datasets::check_schema(&config.method, &columns) {
+        return Err(ModelError::TrainingError(message));
+    }
+
+    check_options(&config)?;
+
+    // "latest" resolves to the newest checkpoint; with none on disk the run starts fresh
+    if config.resume_from_checkpoint.as_deref() == Some("latest") {
+        config.resume_from_checkpoint =
+            executor::find_latest_checkpoint(&config.output_path).map(|c| c.path);
+    }
+
     let cancel = state.training_cancel.clone();
     cancel.store(false, Ordering::Relaxed);
 
@@ -56,7 +143,12 @@ pub async fn training_run(
         *pid_lock = None;
     }
 
-    let result = executor::run_training(app, config, cancel.clone(), pid_store).await;
+    let export_outtype = config.export_gguf_after_merge.clone().filter(|_| {
+        config.merge_adapter && matches!(config.method, TrainingMethod::Lora | TrainingMethod::Qlora)
+    });
+    let merged_path = config.merged_output_path();
+
+    let result = executor::run_training(app.clone(), config, cancel.clone(), pid_store).await;
 
     // Clean up PID
     {
@@ -64,7 +156,23 @@ pub async fn training_run(
         *pid_lock = None;
     }
 
-    result
+    let mut result = result?;
+
+    // Chain into GGUF conversion of the merged model
+    if let Some(outtype) = export_outtype {
+        let _ = app.emit("training:progress", TrainingProgress {
+            stage: "exporting".into(),
+            message: format!("Converting merged model to GGUF ({})...", outtype),
+            percent: -1.0,
+            epoch: None, step: None, total_steps: None,
+            loss: None, learning_rate: None,
+            eta_seconds: None, gpu_memory_used_mb: None,
+        });
+        let converted = crate::commands::convert_run(merged_path, outtype, app, state).await?;
+        result.gguf_path = Some(converted.output_path);
+    }
+
+    Ok(result)
 }
 
 // ── Cancel Training ─────────────────────────────────
@@ -95,7 +203,7 @@ pub fn training_cancel(state: State<'_, AppState>) -> Result<(), ModelError> {
 // ── Surgery ─────────────────────────────────────────
 
 #[tauri::command]
-pub fn training_surgery_run(
+pub async fn training_surgery_run(
     config: SurgeryConfig,
     app: AppHandle,
     state: State<'_, AppState>,
@@ -103,7 +211,10 @@ pub fn training_surgery_run(
     let cancel = state.surgery_cancel.clone();
     cancel.store(false, Ordering::Relaxed);
 
-    surgery::execute_surgery(&app, &config, cancel)
+    // Off the main thread so `training_surgery_cancel` can run while this is busy
+    tauri::async_runtime::spawn_blocking(move || surgery::execute_surgery(&app, &config, cancel))
+        .await
+        .map_err(|e| ModelError::TrainingError(format!("Surgery task failed: {}", e)))?
 }
 
 #[tauri::command]
@@ -149,6 +260,37 @@ fn quick_parse_model(model_path: &str) -> Result<(Vec<TensorInfo>, Option<u64>),
 
 // ── Target Modules ──────────────────────────────────
 
+/// Shape and parameter totals for every `<module>.weight` tensor. GGUF stores
+/// dims innermost-first, so its shapes are reversed to `[out, in]`.
+fn module_details(tensors: &[TensorInfo], module: &str, gguf: bool) -> Option<TargetModuleInfo> {
+    let suffix = format!(".{}.weight", module);
+    let mut info: Option<TargetModuleInfo> = None;
+
+    for tensor in tensors.iter().filter(|t| t.name.ends_with(&suffix) && t.shape.len() == 2) {
+        let mut shape = tensor.shape.clone();
+        if gguf {
+            shape.reverse();
+        }
+        let shared = model::inspect::extract_layer_index(&tensor.name).is_none()
+            || tensor.name.contains("shared");
+
+        let entry = info.get_or_insert_with(|| TargetModuleInfo {
+            module: module.to_string(),
+            shape: shape.clone(),
+            instances: 0,
+            params: 0,
+            lora_params_per_rank: 0,
+            shared: false,
+        });
+        entry.instances += 1;
+        entry.params += shape[0] * shape[1];
+        entry.lora_params_per_rank += shape[0] + shape[1];
+        entry.shared |= shared;
+    }
+
+    info
+}
+
 #[tauri::command]
 pub fn training_get_target_modules(
     model_path: String,
@@ -194,6 +336,7 @@ pub fn training_get_target_modules(
         groups.push(TargetModuleGroup {
             name: "Attention Q/K/V".into(),
             modules,
+            details: vec![],
         });
     }
 
@@ -201,6 +344,7 @@ pub fn training_get_target_modules(
         groups.push(TargetModuleGroup {
             name: "Attention Output".into(),
             modules: vec!["o_proj".to_string()],
+            details: vec![],
         });
     }
 
@@ -212,6 +356,7 @@ pub fn training_get_target_modules(
         groups.push(TargetModuleGroup {
             name: "MLP".into(),
             modules,
+            details: vec![],
         });
     }
 
@@ -220,6 +365,7 @@ pub fn training_get_target_modules(
         groups.push(TargetModuleGroup {
             name: "Fused QKV".into(),
             modules: vec!["query_key_value".to_string()],
+            details: vec![],
         });
     }
 
@@ -232,6 +378,7 @@ pub fn training_get_target_modules(
         groups.push(TargetModuleGroup {
             name: "Feed Forward".into(),
             modules,
+            details: vec![],
         });
     }
 
@@ -240,9 +387,19 @@ pub fn training_get_target_modules(
         groups.push(TargetModuleGroup {
             name: "Default".into(),
             modules: vec!["q_proj".to_string(), "v_proj".to_string()],
+            details: vec![],
         });
     }
 
+    let gguf = model_path.ends_with(".gguf");
+    for group in &mut groups {
+        group.details = group
+            .modules
+            .iter()
+            .filter_map(|m| module_details(&tensors, m, gguf))
+            .collect();
+    }
+
     Ok(groups)
 }
 
@@ -334,7 +491,7 @@ pub fn training_get_layer_details(
 
             match component {
                 "attention" => { attention_count += 1; attention_bytes += bytes; }
-                "mlp" => { mlp_count += 1; mlp_bytes += bytes; }
+                "mlp" | "expert" => { mlp_count += 1; mlp_bytes += bytes; }
                 "norm" => { norm_count += 1; norm_bytes += bytes; }
                 _ => { other_count += 1; other_bytes += bytes; }
             }
@@ -379,3 +536,42 @@ pub fn training_get_layer_details(
 pub fn training_detect_dataset_full(path: String) -> Result<DatasetFullInfo, ModelError> {
     datasets::detect_dataset_full(&path, 50)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(extra: serde_json::Value) -> TrainingConfig {
+        let mut json = serde_json::json!({
+            "model_path": "/models/base",
+            "dataset_path": "/data/train.jsonl",
+            "dataset_format": "jsonl",
+            "method": "lora",
+            "output_path": "/out",
+        });
+        json.as_object_mut().unwrap().extend(extra.as_object().unwrap().clone());
+        serde_json::from_value(json).unwrap()
+    }
+
+    #[test]
+    fn unknown_optimizers_are_rejected() {
+        assert!(check_options(&config(serde_json::json!({}))).is_ok());
+        assert!(check_options(&config(serde_json::json!({ "optimizer": "adafactor" }))).is_ok());
+        let err = check_options(&config(serde_json::json!({ "optimizer": "adam_typo" }))).unwrap_err();
+        assert!(err.to_string().contains("Unknown optimizer 'adam_typo'"), "{}", err);
+    }
+
+    #[test]
+    fn eval_split_must_be_a_fraction() {
+        assert!(check_options(&config(serde_json::json!({ "eval_split": 0.1 }))).is_ok());
+        assert!(check_options(&config(serde_json::json!({ "eval_split": 1.0 }))).is_err());
+    }
+
+    #[test]
+    fn eval_steps_must_be_positive() {
+        let zero = config(serde_json::json!({ "eval_split": 0.1, "eval_steps": 0 }));
+        assert!(check_options(&zero).is_err());
+        let unset = config(serde_json::json!({ "eval_steps": 0 }));
+        assert!(check_options(&unset).is_ok());
+    }
+}
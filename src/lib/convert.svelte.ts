@@ -39,6 +39,18 @@ export interface ConvertResult {
   output_size_display: string;
 }
 
+export interface GgufExportResult {
+  output_dir: string;
+  files: string[];
+  tensor_count: number;
+  output_size: number;
+  output_size_display: string;
+  dtype: string;
+  tokenizer_written: boolean;
+  skipped_tensors: string[];
+  precision_note: string | null;
+}
+
 class ConvertStore {
   deps = $state<ConvertDepsStatus | null>(null);
   depsLoading = $state(false);
@@ -56,6 +68,11 @@ class ConvertStore {
   convertError = $state<string | null>(null);
   convertResult = $state<ConvertResult | null>(null);
 
+  // ── GGUF → SafeTensors ──
+  exporting = $state(false);
+  exportError = $state<string | null>(null);
+  exportResult = $state<GgufExportResult | null>(null);
+
   private setupUnlisten: UnlistenFn | null = null;
   private convertUnlisten: UnlistenFn | null = null;
 
@@ -114,8 +131,7 @@ class ConvertStore {
     }
   }
 
-  async run(repoPath: string, outtype: string) {
-    // Listen for convert progress events
+  private async ensureProgressListener() {
     if (!this.convertUnlisten) {
       this.convertUnlisten = await listen<ConvertProgress>(
         "convert:progress",
@@ -124,6 +140,10 @@ class ConvertStore {
         },
       );
     }
+  }
+
+  async run(repoPath: string, outtype: string) {
+    await this.ensureProgressListener();
 
     this.converting = true;
     this.convertError = null;
@@ -149,6 +169,32 @@ class ConvertStore {
     }
   }
 
+  /** Convert a GGUF back to a SafeTensors directory for fine-tuning. */
+  async exportGguf(ggufPath: string, outputDir: string, dtype: "f16" | "bf16" | "f32" | null) {
+    await this.ensureProgressListener();
+
+    this.exporting = true;
+    this.exportError = null;
+    this.exportResult = null;
+    this.convertProgress = null;
+
+    try {
+      this.exportResult = await invoke<GgufExportResult>("convert_gguf_to_safetensors", {
+        ggufPath,
+        outputDir,
+        dtype,
+      });
+    } catch (e) {
+      const msg = String(e);
+      if (!msg.includes("cancelled")) {
+        this.exportError = msg;
+      }
+    } finally {
+      this.exporting = false;
+      this.convertProgress = null;
+    }
+  }
+
   async cancel() {
     try {
       await invoke("convert_cancel");
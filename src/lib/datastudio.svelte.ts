@@ -37,12 +37,22 @@ export interface HfDatasetRepoInfo {
   files: HfDatasetFileInfo[];
 }
 
+export interface HfDatasetPreview {
+  id: string;
+  source: string;
+  split: string | null;
+  columns: string[];
+  rows: any[];
+}
+
 export interface DownloadProgress {
   file_name: string;
   bytes_downloaded: number;
   bytes_total: number;
   percent: number;
   status: string;
+  bytes_per_second: number | null;
+  eta_seconds: number | null;
 }
 
 // ── Store ───────────────────────────────────────────
@@ -61,6 +71,9 @@ class DataStudioStore {
   hfRepo = $state<HfDatasetRepoInfo | null>(null);
   hfLoading = $state(false);
   hfError = $state<string | null>(null);
+  hfPreview = $state<HfDatasetPreview | null>(null);
+  hfPreviewLoading = $state(false);
+  hfPreviewError = $state<string | null>(null);
   hfDownloading = $state(false);
   hfDownloadProgress = $state<DownloadProgress | null>(null);
 
@@ -95,6 +108,8 @@ class DataStudioStore {
     this.hfLoading = true;
     this.hfError = null;
     this.hfRepo = null;
+    this.hfPreview = null;
+    this.hfPreviewError = null;
     try {
       this.hfRepo = await invoke<HfDatasetRepoInfo>("hf_fetch_dataset_repo", { repoId });
     } catch (e) {
@@ -104,6 +119,19 @@ class DataStudioStore {
     }
   }
 
+  async previewHfDataset(repoId: string) {
+    this.hfPreviewLoading = true;
+    this.hfPreviewError = null;
+    this.hfPreview = null;
+    try {
+      this.hfPreview = await invoke<HfDatasetPreview>("hf_preview_dataset", { repoId });
+    } catch (e) {
+      this.hfPreviewError = String(e);
+    } finally {
+      this.hfPreviewLoading = false;
+    }
+  }
+
   async downloadHfFile(repoId: string, filename: string) {
     this.hfDownloading = true;
     this.hfError = null;
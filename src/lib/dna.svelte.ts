@@ -1,6 +1,6 @@
 import { invoke } from "@tauri-apps/api/core";
 import { listen, type UnlistenFn } from "@tauri-apps/api/event";
-import { open } from "@tauri-apps/plugin-dialog";
+import { open, save } from "@tauri-apps/plugin-dialog";
 
 // ── Types ────────────────────────────────────────────────
 
@@ -26,6 +26,7 @@ export interface MergeMethodInfo {
   name: string;
   description: string;
   requires_base: boolean;
+  accepts_base: boolean;
   min_parents: number;
   difficulty: string;
 }
@@ -45,6 +46,17 @@ export interface ResolutionStrategy {
   requires_training: boolean;
 }
 
+export interface TensorCompat {
+  tensor_name: string;
+  present_in: string[];
+  missing_from: string[];
+  shapes: [string, number[]][];
+  shapes_match: boolean;
+  /** "ok" | "tolerable" | "fatal" */
+  severity: string;
+  issue: string | null;
+}
+
 export interface CompatReport {
   compatible: boolean;
   warnings: string[];
@@ -56,6 +68,7 @@ export interface CompatReport {
   layer_count_match: boolean;
   dimension_details: DimensionMismatch[];
   resolution_strategies: ResolutionStrategy[];
+  tensor_report: TensorCompat[];
 }
 
 export interface LayerProfile {
@@ -73,6 +86,9 @@ export interface MergeProgress {
   current_tensor: string | null;
   tensors_done: number;
   tensors_total: number;
+  bytes_written: number;
+  bytes_per_sec: number;
+  eta_seconds: number | null;
 }
 
 export interface MergeResult {
@@ -82,6 +98,27 @@ export interface MergeResult {
   tensors_written: number;
   method: string;
   copied_files: string[];
+  warnings: string[];
+  perf: MergePerfReport | null;
+}
+
+export interface TensorTiming {
+  tensor_name: string;
+  operation: string;
+  load_ms: number;
+  merge_ms: number;
+  write_ms: number;
+  total_ms: number;
+}
+
+export interface MergePerfReport {
+  total_ms: number;
+  load_ms: number;
+  merge_ms: number;
+  write_ms: number;
+  finalize_ms: number;
+  tensors_timed: number;
+  slowest: TensorTiming[];
 }
 
 export interface MergePreview {
@@ -93,11 +130,36 @@ export interface MergePreview {
   estimated_output_display: string;
 }
 
+export interface OutputEstimate {
+  tensor_count: number;
+  total_params: number;
+  dtype: string;
+  estimated_bytes: number;
+  estimated_display: string;
+}
+
 export interface LayerAssignment {
   layerIndex: number;
   sourceParentId: string;
 }
 
+/** Inclusive range of one parent's layers, stacked in order into the output. */
+export interface LayerSlice {
+  parentId: string;
+  layerStart: number;
+  layerEnd: number;
+}
+
+/**
+ * Per-parent weights for matching tensors. `pattern` is an exact tensor name,
+ * a `*` glob, or a component ("attention", "mlp", "norm", ...); exact beats glob
+ * beats component.
+ */
+export interface WeightOverride {
+  pattern: string;
+  weights: number[];
+}
+
 export interface LayerComponentInfo {
   layer_index: number;
   attention_tensors: string[];
@@ -122,6 +184,7 @@ export interface LayerAnalysis {
   attn_tensors: number;
   mlp_tensors: number;
   norm_tensors: number;
+  norm_load_failures: number;
   norm_l2: number;
   norm_variance: number;
   mlp_dominance: number;
@@ -133,6 +196,7 @@ export interface AnalysisResult {
   layers: LayerAnalysis[];
   total_layers: number;
   categories: LayerCategoryInfo[];
+  failed_norm_tensors: number;
 }
 
 export interface LayerCategoryInfo {
@@ -166,6 +230,18 @@ export interface CapabilityReport {
   total_detected: number;
 }
 
+export interface BaseCandidate {
+  parent_id: string;
+  parent_name: string;
+  score: number;
+  reasons: string[];
+}
+
+export interface BaseDetection {
+  candidates: BaseCandidate[];
+  suggested_parent_id: string | null;
+}
+
 export type DnaStatus = "idle" | "loading" | "ready" | "merging" | "profiling" | "analyzing" | "error" | "complete";
 export type DnaMode = "easy" | "intermediate" | "advanced";
 export type DnaTab = "files" | "layers" | "settings";
@@ -201,6 +277,9 @@ class DnaStore {
 
   // Layer assignments
   layerAssignments = $state<LayerAssignment[]>([]);
+  /** Ordered slices; when non-empty they replace per-layer assignments. */
+  layerSlices = $state<LayerSlice[]>([]);
+  weightOverrides = $state<WeightOverride[]>([]);
 
   // Merge config
   selectedMethod = $state("slerp");
@@ -209,14 +288,21 @@ class DnaStore {
   outputFormat = $state<"safe_tensors" | "gguf">("safe_tensors");
   outputPath = $state("");
   modelName = $state("merged-model");
+  repairTokenizerGaps = $state(false);
+  ggufTargetType = $state<"F32" | "F16" | "Q8_0" | "Q4_0" | "Q4_K_S" | "Q4_K_M" | "Q6_K">("F32");
+  /** Max SafeTensors shard size in GB; null writes a single file. */
+  maxShardGb = $state<number | null>(null);
   mergeBatchSize = $state(1);
+  profileMerge = $state(false);
   projectionStrategy = $state<string | null>(null);
+  vocabStrategy = $state<"pad" | "truncate" | "transplant">("pad");
 
   // Available methods
   methods = $state<MergeMethodInfo[]>([]);
 
   // Compatibility
   compatReport = $state<CompatReport | null>(null);
+  baseDetection = $state<BaseDetection | null>(null);
 
   // Profiling
   profiles = $state<LayerProfile[]>([]);
@@ -229,12 +315,14 @@ class DnaStore {
 
   // Preview
   preview = $state<MergePreview | null>(null);
+  outputEstimate = $state<OutputEstimate | null>(null);
 
   // Per-parent layer component data
   layerComponents = $state<Record<string, LayerComponentInfo[]>>({});
 
   // Layer analysis (computed via COMPUTE LAYERS)
   layerAnalysis = $state<Record<string, LayerAnalysis[]>>({});
+  analysisResults = $state<Record<string, AnalysisResult>>({});
   analyzing = $state(false);
   analysisProgress = $state<AnalysisProgress | null>(null);
   categories = $state<LayerCategoryInfo[]>([]);
@@ -247,6 +335,7 @@ class DnaStore {
   activeTab = $state<DnaTab>("files");
   hoveredLayer = $state<HoveredLayer | null>(null);
   selectedParentForProfile = $state<string | null>(null);
+  profilePrompt = $state("The capital of France is");
 
   // Event listeners
   private progressUnlisten: UnlistenFn | null = null;
@@ -274,7 +363,7 @@ class DnaStore {
     const minParents = method?.min_parents ?? 2;
     if (this.parents.length < minParents || this.outputPath === "") return false;
     // Frankenmerge requires layer assignments
-    if (this.selectedMethod === "frankenmerge" && this.layerAssignments.length === 0) return false;
+    if (this.selectedMethod === "frankenmerge" && this.layerAssignments.length === 0 && this.layerSlices.length === 0) return false;
     return true;
   }
 
@@ -357,6 +446,26 @@ class DnaStore {
     }));
   }
 
+  /** Linear method: [start, end] weight of a parent's curve across the layer stack. */
+  curveEnds(parentIndex: number): [number, number] {
+    const curve = this.methodParams.weight_curves?.[parentIndex];
+    const flat = this.parentWeights[parentIndex]?.weight ?? 0;
+    if (!curve || curve.length === 0) return [flat, flat];
+    return [curve[0].weight, curve[curve.length - 1].weight];
+  }
+
+  setCurveEnd(parentIndex: number, end: 0 | 1, weight: number) {
+    const lastLayer = Math.max(this.maxLayers - 1, 0);
+    const curves = this.parents.map((_, i) => {
+      const [start, finish] = this.curveEnds(i);
+      return [
+        { layer: 0, weight: i === parentIndex && end === 0 ? weight : start },
+        { layer: lastLayer, weight: i === parentIndex && end === 1 ? weight : finish },
+      ];
+    });
+    this.methodParams = { ...this.methodParams, weight_curves: curves };
+  }
+
   get activePresetId(): string | null {
     for (const preset of MERGE_PRESETS) {
       if (preset.method !== this.selectedMethod) continue;
@@ -528,17 +637,28 @@ class DnaStore {
   async removeParent(id: string) {
     try {
       await invoke("merge_remove_parent", { parentId: id });
+      const removed = this.parents.findIndex((p) => p.id === id);
       this.parents = this.parents.filter((p) => p.id !== id);
       this.layerAssignments = this.layerAssignments.filter(
         (a) => a.sourceParentId !== id
       );
+      this.layerSlices = this.layerSlices.filter((s) => s.parentId !== id);
+      if (removed >= 0) {
+        this.weightOverrides = this.weightOverrides.map((o) => ({
+          ...o,
+          weights: o.weights.filter((_, i) => i !== removed),
+        }));
+      }
       const { [id]: _, ...rest } = this.layerComponents;
       this.layerComponents = rest;
       const { [id]: _a, ...restAnalysis } = this.layerAnalysis;
       this.layerAnalysis = restAnalysis;
+      const { [id]: _r, ...restResults } = this.analysisResults;
+      this.analysisResults = restResults;
       const { [id]: _c, ...restCaps } = this.capabilities;
       this.capabilities = restCaps;
       if (this.baseParentId === id) this.baseParentId = null;
+      this.baseDetection = null;
       if (this.parents.length >= 2) {
         await this.checkCompatibility();
       } else {
@@ -549,6 +669,21 @@ class DnaStore {
     }
   }
 
+  async detectBase() {
+    try {
+      this.baseDetection = await invoke<BaseDetection>("merge_suggest_base");
+    } catch (e) {
+      this.error = String(e);
+    }
+  }
+
+  applyDetectedBase() {
+    if (this.baseDetection?.suggested_parent_id) {
+      this.baseParentId = this.baseDetection.suggested_parent_id;
+    }
+    this.baseDetection = null;
+  }
+
   async checkCompatibility() {
     try {
       this.compatReport = await invoke<CompatReport>("merge_check_compatibility");
@@ -575,6 +710,46 @@ class DnaStore {
     }
   }
 
+  addSlice() {
+    const parent = this.parents[this.layerSlices.length % Math.max(this.parents.length, 1)];
+    if (!parent) return;
+    const last = Math.max((parent.layer_count ?? 1) - 1, 0);
+    this.layerSlices = [...this.layerSlices, { parentId: parent.id, layerStart: 0, layerEnd: last }];
+  }
+
+  removeSlice(index: number) {
+    this.layerSlices = this.layerSlices.filter((_, i) => i !== index);
+  }
+
+  addWeightOverride() {
+    this.weightOverrides = [
+      ...this.weightOverrides,
+      { pattern: "attention", weights: this.parentWeights.map((p) => p.weight) },
+    ];
+  }
+
+  removeWeightOverride(index: number) {
+    this.weightOverrides = this.weightOverrides.filter((_, i) => i !== index);
+  }
+
+  setOverrideWeight(index: number, parentIndex: number, weight: number) {
+    this.weightOverrides = this.weightOverrides.map((o, i) =>
+      i === index ? { ...o, weights: o.weights.map((w, j) => (j === parentIndex ? weight : w)) } : o
+    );
+  }
+
+  get weightOverrideMap(): Record<string, number[]> {
+    return Object.fromEntries(
+      this.weightOverrides
+        .filter((o) => o.pattern.trim())
+        .map((o) => [o.pattern.trim(), o.weights])
+    );
+  }
+
+  get slicedLayerCount(): number {
+    return this.layerSlices.reduce((n, s) => n + Math.max(s.layerEnd - s.layerStart + 1, 0), 0);
+  }
+
   autoAssign(strategy: "interleave" | "split" | "first") {
     const max = this.maxLayers;
     if (max === 0 || this.parents.length === 0) return;
@@ -630,7 +805,7 @@ class DnaStore {
     }
 
     try {
-      await invoke("merge_profile_layers", { parentId });
+      await invoke("merge_profile_layers", { parentId, prompt: this.profilePrompt });
     } catch (e) {
       const msg = String(e);
       if (!msg.includes("cancelled")) {
@@ -675,17 +850,28 @@ class DnaStore {
         layer_index: a.layerIndex,
         source_parent_id: a.sourceParentId,
       })),
+      slices: this.layerSlices.map((s) => ({
+        parent_id: s.parentId,
+        layer_start: s.layerStart,
+        layer_end: s.layerEnd,
+      })),
       component_overrides: [],
       tensor_overrides: [],
+      weight_overrides: this.weightOverrideMap,
       output: {
         format: this.outputFormat,
         path: this.outputPath,
         model_name: this.modelName,
+        repair_tokenizer_gaps: this.repairTokenizerGaps,
+        max_shard_bytes: this.maxShardGb ? this.maxShardGb * 1024 ** 3 : null,
+        target_type: this.ggufTargetType,
       },
       memory_limit_mb: memoryLimitMb,
       projection_strategy: this.projectionStrategy,
       skip_layers: this.disabledLayers,
       batch_size: this.mergeBatchSize,
+      profile: this.profileMerge,
+      vocab_strategy: this.vocabStrategy,
     };
 
     // Fire-and-forget: don't block the UI on the merge result.
@@ -760,6 +946,7 @@ class DnaStore {
             parentId: parent.id,
           });
           this.layerAnalysis = { ...this.layerAnalysis, [parent.id]: result.layers };
+          this.analysisResults = { ...this.analysisResults, [parent.id]: result };
           if (result.categories.length > 0) {
             this.categories = result.categories;
           }
@@ -778,6 +965,23 @@ class DnaStore {
     analyzeSequentially();
   }
 
+  async exportAnalysis(parentId: string, format: "csv" | "json") {
+    const result = this.analysisResults[parentId];
+    if (!result) return;
+
+    const path = await save({
+      defaultPath: `${result.parent_name}-layers.${format}`,
+      filters: [{ name: format.toUpperCase(), extensions: [format] }],
+    });
+    if (!path) return;
+
+    try {
+      await invoke("merge_export_analysis", { result, path, format });
+    } catch (e) {
+      this.error = String(e);
+    }
+  }
+
   cancelAnalysis() {
     // Reuse profiler cancel since they share the same cancel flag
     try {
@@ -799,12 +1003,21 @@ class DnaStore {
         layer_index: a.layerIndex,
         source_parent_id: a.sourceParentId,
       })),
+      slices: this.layerSlices.map((s) => ({
+        parent_id: s.parentId,
+        layer_start: s.layerStart,
+        layer_end: s.layerEnd,
+      })),
       component_overrides: [],
       tensor_overrides: [],
+      weight_overrides: this.weightOverrideMap,
       output: {
         format: this.outputFormat,
         path: this.outputPath || "/tmp/preview",
         model_name: this.modelName,
+        repair_tokenizer_gaps: this.repairTokenizerGaps,
+        max_shard_bytes: this.maxShardGb ? this.maxShardGb * 1024 ** 3 : null,
+        target_type: this.ggufTargetType,
       },
     };
 
@@ -813,6 +1026,12 @@ class DnaStore {
     } catch (e) {
       console.error("Preview failed:", e);
     }
+
+    try {
+      this.outputEstimate = await invoke<OutputEstimate>("merge_estimate_output", { config });
+    } catch {
+      this.outputEstimate = null;
+    }
   }
 
   async selectOutputPath() {
@@ -841,13 +1060,17 @@ class DnaStore {
     this.status = "idle";
     this.error = null;
     this.layerAssignments = [];
+    this.layerSlices = [];
+    this.weightOverrides = [];
     this.layerComponents = {};
     this.layerAnalysis = {};
+    this.analysisResults = {};
     this.categories = [];
     this.profiles = [];
     this.mergeProgress = null;
     this.mergeResult = null;
     this.preview = null;
+    this.outputEstimate = null;
     this.hoveredLayer = null;
     this.analysisProgress = null;
     this.capabilities = {};
@@ -13,6 +13,15 @@ export interface HfRepoInfo {
   files: HfFileInfo[];
 }
 
+export interface HfModelSearchHit {
+  id: string;
+  downloads: number;
+  likes: number;
+  pipeline_tag: string | null;
+}
+
+export type SearchFormat = "all" | "gguf" | "safetensors";
+
 export interface LocalModelEntry {
   id: string;
   file_name: string;
@@ -24,6 +33,13 @@ export interface LocalModelEntry {
   downloaded_at: string;
 }
 
+export interface ManifestRebuild {
+  entries: LocalModelEntry[];
+  kept: number;
+  recovered: number;
+  dropped: number;
+}
+
 export interface DownloadProgress {
   file_name: string;
   bytes_downloaded: number;
@@ -32,6 +48,9 @@ export interface DownloadProgress {
   status: string;
   files_done: number | null;
   files_total: number | null;
+  /** Averaged over the last few seconds; null outside active transfer. */
+  bytes_per_second: number | null;
+  eta_seconds: number | null;
 }
 
 function formatBytes(bytes: number): string {
@@ -46,12 +65,17 @@ class HubStore {
   repoLoading = $state(false);
   repoError = $state<string | null>(null);
 
+  searchResults = $state<HfModelSearchHit[] | null>(null);
+  searchLoading = $state(false);
+  searchError = $state<string | null>(null);
+
   downloadProgress = $state<DownloadProgress | null>(null);
   downloading = $state(false);
   downloadError = $state<string | null>(null);
 
   localModels = $state<LocalModelEntry[]>([]);
   libraryLoading = $state(false);
+  lastRebuild = $state<ManifestRebuild | null>(null);
 
   private unlisten: UnlistenFn | null = null;
 
@@ -65,12 +89,12 @@ class HubStore {
     });
   }
 
-  async fetchRepo(repoId: string) {
+  async fetchRepo(repoId: string, forceRefresh = false) {
     this.repoLoading = true;
     this.repoError = null;
     this.repoInfo = null;
     try {
-      this.repoInfo = await invoke<HfRepoInfo>("hf_fetch_repo", { repoId });
+      this.repoInfo = await invoke<HfRepoInfo>("hf_fetch_repo", { repoId, forceRefresh });
     } catch (e) {
       this.repoError = String(e);
     } finally {
@@ -78,6 +102,23 @@ class HubStore {
     }
   }
 
+  async searchModels(query: string, format: SearchFormat) {
+    this.searchLoading = true;
+    this.searchError = null;
+    try {
+      this.searchResults = await invoke<HfModelSearchHit[]>("hf_search_models", {
+        query,
+        format: format === "all" ? null : format,
+        limit: null,
+      });
+    } catch (e) {
+      this.searchError = String(e);
+      this.searchResults = null;
+    } finally {
+      this.searchLoading = false;
+    }
+  }
+
   async downloadFile(repoId: string, filename: string) {
     await this.setupListener();
     this.downloading = true;
@@ -90,6 +131,8 @@ class HubStore {
       status: "downloading",
       files_done: null,
       files_total: null,
+      bytes_per_second: null,
+      eta_seconds: null,
     };
     try {
       await invoke<LocalModelEntry>("hf_download_file", { repoId, filename });
@@ -116,6 +159,8 @@ class HubStore {
       status: "downloading",
       files_done: 0,
       files_total: this.repoInfo?.files.length ?? 0,
+      bytes_per_second: null,
+      eta_seconds: null,
     };
     try {
       await invoke<LocalModelEntry>("hf_download_repo", { repoId });
@@ -149,6 +194,19 @@ class HubStore {
     }
   }
 
+  /** Re-scan the models directory and restore missing library entries. */
+  async rebuildLibrary() {
+    this.libraryLoading = true;
+    try {
+      this.lastRebuild = await invoke<ManifestRebuild>("hub_rebuild_manifest");
+      this.localModels = this.lastRebuild.entries;
+    } catch (e) {
+      console.error("Failed to rebuild library:", e);
+    } finally {
+      this.libraryLoading = false;
+    }
+  }
+
   async importLocal(path: string) {
     try {
       await invoke<LocalModelEntry>("hub_import_local", { path });
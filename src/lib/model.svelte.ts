@@ -11,7 +11,7 @@ export interface ModelInfo {
   file_path: string;
   file_size: number;
   file_size_display: string;
-  format: "safe_tensors" | "gguf";
+  format: "safe_tensors" | "gguf" | "pytorch";
   tensor_count: number;
   parameter_count: number;
   parameter_count_display: string;
@@ -45,6 +45,7 @@ class ModelStore {
     const map: Record<string, string> = {
       safe_tensors: "SAFETENSORS",
       gguf: "GGUF",
+      pytorch: "PYTORCH",
     };
     return map[this.info.format] ?? this.info.format.toUpperCase();
   }
@@ -78,7 +79,8 @@ class ModelStore {
   }
 
   get isFolder(): boolean {
-    return (this.info?.shard_count ?? 0) > 0;
+    // Split GGUF files also report shards but load from a single path
+    return (this.info?.shard_count ?? 0) > 0 && this.info?.format !== "gguf";
   }
 
   async unload(): Promise<void> {
@@ -4,8 +4,40 @@ import { listen, type UnlistenFn } from "@tauri-apps/api/event";
 export interface TestResult {
   text: string;
   tokens_generated: number;
+  tokens_per_second: number;
   time_ms: number;
   device: string;
+  grammar_applied: boolean;
+}
+
+export interface ChatMessage {
+  role: "system" | "user" | "assistant";
+  content: string;
+}
+
+export interface PerplexityResult {
+  perplexity: number;
+  tokens: number;
+  chunks: number;
+  elapsed_ms: number;
+  backend: string;
+}
+
+export interface PerplexityProgress {
+  chunk: number;
+  total_chunks: number | null;
+  perplexity: number;
+}
+
+interface TokenEvent {
+  session_id: string | null;
+  text: string;
+}
+
+export interface ServerStatus {
+  running: boolean;
+  port: number | null;
+  model_path: string | null;
 }
 
 export interface GenerateOptions {
@@ -19,26 +51,63 @@ export interface GenerateOptions {
   gpuLayers?: number | null;
   systemPrompt?: string | null;
   contextSize?: number | null;
+  stop?: string[] | null;
+  /** GBNF grammar; GGUF models only. */
+  grammar?: string | null;
 }
 
 class TestStore {
   generating = $state(false);
   output = $state("");
   error = $state<string | null>(null);
+  warning = $state<string | null>(null);
   result = $state<TestResult | null>(null);
+  server = $state<ServerStatus | null>(null);
+
+  // ── Chat session ──
+  chatSessionId = $state<string | null>(null);
+  chatModelPath = $state<string | null>(null);
+  chatMessages = $state<ChatMessage[]>([]);
+  chatPending = $state("");
+
+  // ── Perplexity ──
+  perplexityRunning = $state(false);
+  perplexityProgress = $state<PerplexityProgress | null>(null);
+  perplexityResult = $state<PerplexityResult | null>(null);
 
   private tokenUnlisten: UnlistenFn | null = null;
+  private warningUnlisten: UnlistenFn | null = null;
+  private perplexityUnlisten: UnlistenFn | null = null;
 
-  async generate(opts: GenerateOptions) {
+  private async ensureListeners() {
     if (!this.tokenUnlisten) {
-      this.tokenUnlisten = await listen<string>("test:token", (e) => {
-        this.output += e.payload;
+      this.tokenUnlisten = await listen<TokenEvent>("test:token", (e) => {
+        if (e.payload.session_id === null) {
+          this.output += e.payload.text;
+        } else if (e.payload.session_id === this.chatSessionId) {
+          this.chatPending += e.payload.text;
+        }
       });
     }
+    if (!this.warningUnlisten) {
+      this.warningUnlisten = await listen<string>("test:warning", (e) => {
+        this.warning = e.payload;
+      });
+    }
+    if (!this.perplexityUnlisten) {
+      this.perplexityUnlisten = await listen<PerplexityProgress>("perplexity:progress", (e) => {
+        this.perplexityProgress = e.payload;
+      });
+    }
+  }
+
+  async generate(opts: GenerateOptions) {
+    await this.ensureListeners();
 
     this.generating = true;
     this.output = "";
     this.error = null;
+    this.warning = null;
     this.result = null;
 
     try {
@@ -53,14 +122,140 @@ class TestStore {
         gpuLayers: opts.gpuLayers ?? null,
         systemPrompt: opts.systemPrompt ?? null,
         contextSize: opts.contextSize ?? null,
+        stop: opts.stop?.length ? opts.stop : null,
+        grammar: opts.grammar?.trim() ? opts.grammar : null,
+      });
+    } catch (e) {
+      const msg = String(e);
+      if (!msg.includes("cancelled")) {
+        this.error = msg;
+      }
+    } finally {
+      this.generating = false;
+      await this.refreshServer();
+    }
+  }
+
+  /** Send `opts.prompt` as the next chat turn, starting a session if needed. */
+  async chatSend(opts: GenerateOptions) {
+    await this.ensureListeners();
+
+    this.generating = true;
+    this.error = null;
+    this.warning = null;
+    this.chatPending = "";
+
+    try {
+      // Settings and system prompt are fixed per session; a new model starts over
+      if (this.chatSessionId && this.chatModelPath !== opts.modelPath) {
+        await this.chatEnd();
+      }
+      if (!this.chatSessionId) {
+        this.chatSessionId = await invoke<string>("chat_start", {
+          modelPath: opts.modelPath,
+          systemPrompt: opts.systemPrompt ?? null,
+          settings: {
+            max_tokens: opts.maxTokens,
+            temperature: opts.temperature,
+            top_p: opts.topP ?? null,
+            top_k: opts.topK ?? null,
+            repeat_penalty: opts.repeatPenalty ?? null,
+            gpu_layers: opts.gpuLayers ?? null,
+            context_size: opts.contextSize ?? null,
+            stop: opts.stop?.length ? opts.stop : null,
+            grammar: opts.grammar?.trim() ? opts.grammar : null,
+          },
+        });
+        this.chatModelPath = opts.modelPath;
+      }
+
+      this.chatMessages = [...this.chatMessages, { role: "user", content: opts.prompt }];
+      this.result = await invoke<TestResult>("chat_send", {
+        sessionId: this.chatSessionId,
+        message: opts.prompt,
       });
+      this.chatMessages = [...this.chatMessages, { role: "assistant", content: this.result.text }];
     } catch (e) {
+      // The backend keeps history unchanged on failure; mirror that
+      if (this.chatMessages.at(-1)?.role === "user") {
+        this.chatMessages = this.chatMessages.slice(0, -1);
+      }
       const msg = String(e);
       if (!msg.includes("cancelled")) {
         this.error = msg;
       }
     } finally {
       this.generating = false;
+      this.chatPending = "";
+      await this.refreshServer();
+    }
+  }
+
+  async chatReset() {
+    if (this.chatSessionId) {
+      try {
+        await invoke("chat_reset", { sessionId: this.chatSessionId });
+      } catch {
+        this.chatSessionId = null;
+      }
+    }
+    this.chatMessages = [];
+    this.result = null;
+  }
+
+  async chatEnd() {
+    if (this.chatSessionId) {
+      try {
+        await invoke("chat_end", { sessionId: this.chatSessionId });
+      } catch {
+        // ignore
+      }
+    }
+    this.chatSessionId = null;
+    this.chatModelPath = null;
+    this.chatMessages = [];
+  }
+
+  /** Perplexity of `modelPath` on the text in `textPath`; lower is better. */
+  async computePerplexity(modelPath: string, textPath: string, contextSize: number, maxChunks: number | null, gpuLayers: number | null) {
+    await this.ensureListeners();
+
+    this.perplexityRunning = true;
+    this.perplexityProgress = null;
+    this.perplexityResult = null;
+    this.error = null;
+
+    try {
+      this.perplexityResult = await invoke<PerplexityResult>("compute_perplexity", {
+        modelPath,
+        textPath,
+        contextSize,
+        maxChunks,
+        gpuLayers,
+      });
+    } catch (e) {
+      const msg = String(e);
+      if (!msg.includes("cancelled")) {
+        this.error = msg;
+      }
+    } finally {
+      this.perplexityRunning = false;
+    }
+  }
+
+  async refreshServer() {
+    try {
+      this.server = await invoke<ServerStatus>("test_server_status");
+    } catch {
+      this.server = null;
+    }
+  }
+
+  async stopServer() {
+    try {
+      this.server = await invoke<ServerStatus>("test_server_stop");
+    } catch (e) {
+      this.error = String(e);
     }
   }
 
@@ -75,6 +270,7 @@ class TestStore {
   clear() {
     this.output = "";
     this.error = null;
+    this.warning = null;
     this.result = null;
   }
 }
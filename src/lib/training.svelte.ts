@@ -1,6 +1,7 @@
 import { invoke } from "@tauri-apps/api/core";
 import { listen, type UnlistenFn } from "@tauri-apps/api/event";
 import { open } from "@tauri-apps/plugin-dialog";
+import type { ColumnAnalysis } from "./datastudio.svelte";
 
 // ── Types ───────────────────────────────────────────
 
@@ -25,6 +26,26 @@ export interface DatasetInfo {
   size: number;
   size_display: string;
   detected_template: string | null;
+  token_lengths: TokenLengthStats | null;
+}
+
+/** Estimated at ~4 characters per token over the first rows. */
+export interface TokenLengthStats {
+  sampled_rows: number;
+  mean: number;
+  p50: number;
+  p95: number;
+  max: number;
+}
+
+export interface DatasetPreview {
+  path: string;
+  total_rows: number;
+  columns: ColumnAnalysis[];
+  rows: any[];
+  missing_columns: string[];
+  valid: boolean;
+  message: string | null;
 }
 
 export interface TrainingProgress {
@@ -40,6 +61,31 @@ export interface TrainingProgress {
   gpu_memory_used_mb: number | null;
 }
 
+export interface GpuDeviceStats {
+  index: number;
+  utilization: number | null;
+  memory_used_mb: number;
+  memory_total_mb: number;
+}
+
+export interface GpuStats {
+  backend: string;
+  devices: GpuDeviceStats[];
+}
+
+export interface TrainingEval {
+  step: number;
+  epoch: number | null;
+  eval_loss: number | null;
+}
+
+export interface TrainingLogLine {
+  stream: "stdout" | "stderr";
+  kind: "json" | "text";
+  line: string;
+  dropped: number;
+}
+
 export interface TrainingResult {
   output_path: string;
   output_size: number;
@@ -50,6 +96,14 @@ export interface TrainingResult {
   adapter_merged: boolean;
 }
 
+export interface ScriptValidation {
+  valid: boolean;
+  script_path: string;
+  syntax_error: string | null;
+  imports_checked: number;
+  import_errors: { statement: string; error: string }[];
+}
+
 export interface SurgeryResult {
   output_path: string;
   output_size: number;
@@ -59,9 +113,19 @@ export interface SurgeryResult {
   tensors_written: number;
 }
 
+export interface TargetModuleInfo {
+  module: string;
+  shape: number[];
+  instances: number;
+  params: number;
+  lora_params_per_rank: number;
+  shared: boolean;
+}
+
 export interface TargetModuleGroup {
   name: string;
   modules: string[];
+  details: TargetModuleInfo[];
 }
 
 export interface LayerCapabilityMapping {
@@ -94,8 +158,24 @@ export interface LayerTensorInfo {
   component: string;
 }
 
+/** Must match `SUPPORTED_OPTIMIZERS` in training/config.rs. */
+export const OPTIMIZERS = [
+  "adamw_torch",
+  "adamw_torch_fused",
+  "adamw_8bit",
+  "adamw_bnb_8bit",
+  "paged_adamw_8bit",
+  "paged_adamw_32bit",
+  "adafactor",
+  "sgd",
+  "adagrad",
+  "rmsprop",
+  "lion_8bit",
+  "paged_lion_8bit",
+];
+
 export type TrainingMode = "finetune" | "surgery";
-export type TrainingMethod = "sft" | "lora" | "qlora" | "dpo" | "full_finetune";
+export type TrainingMethod = "sft" | "lora" | "qlora" | "dpo" | "orpo" | "kto" | "full_finetune";
 export type PresetId = "low_vram" | "balanced" | "quality" | "max_quality" | "custom";
 
 export interface TrainingPreset {
@@ -147,6 +227,9 @@ export const TRAINING_PRESETS: TrainingPreset[] = [
 
 // ── Store ───────────────────────────────────────────
 
+/** Training log lines kept in memory for the log panel. */
+const MAX_LOG_LINES = 500;
+
 class TrainingStore {
   // Dependencies
   deps = $state<TrainingDepsStatus | null>(null);
@@ -173,6 +256,11 @@ class TrainingStore {
   dataset = $state<DatasetInfo | null>(null);
   datasetLoading = $state(false);
   datasetError = $state<string | null>(null);
+  datasetPreview = $state<DatasetPreview | null>(null);
+  /** Training column -> dataset column it is filled from. */
+  columnMap = $state<Record<string, string>>({});
+  /** Jinja chat template for tokenizers that ship without one; empty keeps theirs. */
+  chatTemplate = $state("");
 
   // Training config
   method = $state<TrainingMethod>("lora");
@@ -184,6 +272,13 @@ class TrainingStore {
   warmupSteps = $state(100);
   weightDecay = $state(0.01);
   saveSteps = $state(500);
+  gradientCheckpointing = $state(true);
+  optimizer = $state("adamw_8bit");
+  packing = $state(false);
+  truncation = $state(true);
+  /** Percent of the dataset held out for evaluation; 0 disables it. */
+  evalSplit = $state(0);
+  earlyStoppingPatience = $state(3);
 
   // LoRA config
   loraRank = $state(16);
@@ -198,9 +293,19 @@ class TrainingStore {
   // DPO
   dpoBeta = $state(0.1);
 
+  // ORPO
+  orpoBeta = $state(0.1);
+
+  // KTO
+  ktoBeta = $state(0.1);
+  ktoDesirableWeight = $state(1.0);
+  ktoUndesirableWeight = $state(1.0);
+
   // Output
   outputPath = $state("");
   mergeAdapter = $state(true);
+  latestCheckpoint = $state<{ path: string; step: number } | null>(null);
+  resumeFromCheckpoint = $state(true);
 
   // Available modules/capabilities
   availableModules = $state<TargetModuleGroup[]>([]);
@@ -212,12 +317,21 @@ class TrainingStore {
   progress = $state<TrainingProgress | null>(null);
   result = $state<TrainingResult | null>(null);
   lossHistory = $state<{ step: number; loss: number }[]>([]);
+  evalHistory = $state<TrainingEval[]>([]);
+  trainingLogs = $state<TrainingLogLine[]>([]);
+  showJsonLogs = $state(false);
+  validating = $state(false);
+  validation = $state<ScriptValidation | null>(null);
 
   // Surgery state
   surgeryRunning = $state(false);
   surgeryResult = $state<SurgeryResult | null>(null);
   layersToRemove = $state<number[]>([]);
   layersToDuplicate = $state<{ source: number; insertAt: number }[]>([]);
+  /** SOLAR-style depth up-scale: bottom `keepBottom` layers + top `keepTop` layers. */
+  depthUpscale = $state<{ keepBottom: number; keepTop: number } | null>(null);
+  /** Layer `a` becomes `(1 - weight) * a + weight * b`; layer `b` is dropped. */
+  layersToInterpolate = $state<{ a: number; b: number; weight: number }[]>([]);
 
   // Layer details for surgery view
   layerDetails = $state<TrainingLayerDetail[] | null>(null);
@@ -234,10 +348,39 @@ class TrainingStore {
   private setupUnlisten: UnlistenFn | null = null;
   private setupLogUnlisten: UnlistenFn | null = null;
   private progressUnlisten: UnlistenFn | null = null;
+  private evalUnlisten: UnlistenFn | null = null;
+  private logUnlisten: UnlistenFn | null = null;
   private surgeryUnlisten: UnlistenFn | null = null;
+  private gpuStatsUnlisten: UnlistenFn | null = null;
+
+  // Live GPU stats while training (null on CPU-only systems)
+  gpuStats = $state<GpuStats | null>(null);
 
   // ── Derived ─────────────────────────────────────
 
+  moduleInfo(module: string): TargetModuleInfo | undefined {
+    for (const group of this.availableModules) {
+      const info = group.details.find((d) => d.module === module);
+      if (info) return info;
+    }
+    return undefined;
+  }
+
+  /** LoRA parameters the selected modules add at the current rank, scaled to the selected layers. */
+  get trainableParams(): number {
+    let total = 0;
+    for (const mod of this.targetModules) {
+      const info = this.moduleInfo(mod);
+      if (!info) continue;
+      let perRank = info.lora_params_per_rank;
+      if (this.selectedLayers.length > 0 && this.modelLayers > 0) {
+        perRank *= Math.min(1, this.selectedLayers.length / this.modelLayers);
+      }
+      total += perRank * this.loraRank;
+    }
+    return Math.round(total);
+  }
+
   get isLoraMethod(): boolean {
     return this.method === "lora" || this.method === "qlora";
   }
@@ -247,11 +390,21 @@ class TrainingStore {
       this.deps?.ready === true &&
       this.modelPath !== null &&
       this.dataset !== null &&
+      this.datasetPreview?.valid !== false &&
       this.outputPath !== "" &&
       !this.training
     );
   }
 
+  /** `columnMap` in the backend's dataset column -> training column direction. */
+  get columnRenames(): Record<string, string> {
+    return Object.fromEntries(
+      Object.entries(this.columnMap)
+        .filter(([, source]) => source !== "")
+        .map(([target, source]) => [source, target]),
+    );
+  }
+
   get selectedLayersFromCapabilities(): number[] {
     const layers = new Set<number>();
     for (const [capId, enabled] of Object.entries(this.capabilityToggles)) {
@@ -265,16 +418,46 @@ class TrainingStore {
     return Array.from(layers).sort((a, b) => a - b);
   }
 
-  get surgeryPreview(): { final: number; removed: number; added: number } {
+  get surgeryPreview(): { final: number; removed: number; added: number; upscaled: number; merged: number } {
     const removed = this.layersToRemove.length;
     const added = this.layersToDuplicate.length;
+    const merged = this.layersToInterpolate.filter((p) => !this.layersToRemove.includes(p.b)).length;
+    let remaining = this.modelLayers - removed - merged;
+    let upscaled = 0;
+    if (this.depthUpscale) {
+      const scaled =
+        Math.min(this.depthUpscale.keepBottom, remaining) + Math.min(this.depthUpscale.keepTop, remaining);
+      upscaled = scaled - remaining;
+      remaining = scaled;
+    }
     return {
-      final: this.modelLayers - removed + added,
+      final: remaining + added,
       removed,
       added,
+      upscaled,
+      merged,
     };
   }
 
+  get hasSurgeryOps(): boolean {
+    return (
+      this.layersToRemove.length > 0 ||
+      this.layersToDuplicate.length > 0 ||
+      this.layersToInterpolate.length > 0 ||
+      this.depthUpscale !== null
+    );
+  }
+
+  /** Toggle depth up-scaling, defaulting to SOLAR's 3/4 + 3/4 split (32 → 48). */
+  toggleDepthUpscale() {
+    if (this.depthUpscale) {
+      this.depthUpscale = null;
+    } else {
+      const keep = Math.round(this.modelLayers * 0.75);
+      this.depthUpscale = { keepBottom: keep, keepTop: keep };
+    }
+  }
+
   // ── Actions ─────────────────────────────────────
 
   async checkDeps() {
@@ -358,6 +541,8 @@ class TrainingStore {
     this.datasetLoading = true;
     this.datasetError = null;
     this.dataset = null;
+    this.datasetPreview = null;
+    this.columnMap = {};
     try {
       this.dataset = await invoke<DatasetInfo>("training_detect_dataset", { path });
     } catch (e) {
@@ -367,6 +552,26 @@ class TrainingStore {
     }
   }
 
+  /** Re-check the loaded dataset's columns against the selected method. */
+  async previewDataset() {
+    if (!this.dataset) {
+      this.datasetPreview = null;
+      return;
+    }
+    const path = this.dataset.path;
+    const method = this.method;
+    const columnMap = this.columnRenames;
+    try {
+      const preview = await invoke<DatasetPreview>("training_preview_dataset", { path, method, rows: 20, columnMap });
+      // Ignore stale responses after the dataset or method changed
+      if (this.dataset?.path === path && this.method === method) {
+        this.datasetPreview = preview;
+      }
+    } catch {
+      this.datasetPreview = null;
+    }
+  }
+
   async loadModelCapabilities() {
     if (!this.modelPath) return;
 
@@ -415,6 +620,17 @@ class TrainingStore {
     });
     if (result) {
       this.outputPath = result as string;
+      await this.findCheckpoint();
+    }
+  }
+
+  async findCheckpoint() {
+    try {
+      this.latestCheckpoint = this.outputPath
+        ? await invoke<{ path: string; step: number } | null>("training_find_checkpoint", { outputPath: this.outputPath })
+        : null;
+    } catch {
+      this.latestCheckpoint = null;
     }
   }
 
@@ -468,10 +684,35 @@ class TrainingStore {
       );
     }
 
+    if (!this.evalUnlisten) {
+      this.evalUnlisten = await listen<TrainingEval>("training:eval", (e) => {
+        this.evalHistory = [...this.evalHistory, e.payload];
+      });
+    }
+
+    if (!this.logUnlisten) {
+      this.logUnlisten = await listen<TrainingLogLine>(
+        "training:log",
+        (e) => {
+          const logs = [...this.trainingLogs, e.payload];
+          this.trainingLogs = logs.length > MAX_LOG_LINES ? logs.slice(-MAX_LOG_LINES) : logs;
+        },
+      );
+    }
+
+    if (!this.gpuStatsUnlisten) {
+      this.gpuStatsUnlisten = await listen<GpuStats>("system:gpu-stats", (e) => {
+        this.gpuStats = e.payload;
+      });
+    }
+
     this.training = true;
     this.error = null;
     this.result = null;
+    this.gpuStats = null;
     this.lossHistory = [];
+    this.evalHistory = [];
+    this.trainingLogs = [];
     this.progress = {
       stage: "starting",
       message: "Preparing training...",
@@ -481,6 +722,26 @@ class TrainingStore {
       eta_seconds: null, gpu_memory_used_mb: null,
     };
 
+    const config = this.buildConfig();
+
+    // Stops by itself once training ends; a no-op without a GPU
+    invoke("gpu_monitor_start").catch(() => {});
+
+    try {
+      this.result = await invoke<TrainingResult>("training_run", { config });
+    } catch (e) {
+      const msg = String(e);
+      if (!msg.includes("cancelled")) {
+        this.error = msg;
+      }
+    } finally {
+      this.training = false;
+      // A failed or cancelled run may have left a checkpoint to resume from
+      await this.findCheckpoint();
+    }
+  }
+
+  private buildConfig(): any {
     const config: any = {
       model_path: this.modelPath,
       dataset_path: this.dataset?.path,
@@ -488,6 +749,8 @@ class TrainingStore {
       method: this.method,
       output_path: this.outputPath,
       merge_adapter: this.mergeAdapter,
+      resume_from_checkpoint:
+        this.resumeFromCheckpoint && this.latestCheckpoint ? this.latestCheckpoint.path : null,
       learning_rate: this.learningRate,
       epochs: this.epochs,
       batch_size: this.batchSize,
@@ -496,8 +759,22 @@ class TrainingStore {
       warmup_steps: this.warmupSteps,
       weight_decay: this.weightDecay,
       save_steps: this.saveSteps,
+      gradient_checkpointing: this.gradientCheckpointing,
+      optimizer: this.optimizer,
+      packing: this.packing,
+      truncation: this.truncation,
+      column_map: this.columnRenames,
     };
 
+    if (this.chatTemplate.trim() !== "") {
+      config.chat_template = this.chatTemplate;
+    }
+
+    if (this.evalSplit > 0) {
+      config.eval_split = this.evalSplit / 100;
+      config.early_stopping_patience = this.earlyStoppingPatience;
+    }
+
     if (this.isLoraMethod) {
       config.lora_rank = this.loraRank;
       config.lora_alpha = this.loraAlpha;
@@ -516,15 +793,32 @@ class TrainingStore {
       config.dpo_beta = this.dpoBeta;
     }
 
+    if (this.method === "orpo") {
+      config.orpo_beta = this.orpoBeta;
+    }
+
+    if (this.method === "kto") {
+      config.kto_beta = this.ktoBeta;
+      config.kto_desirable_weight = this.ktoDesirableWeight;
+      config.kto_undesirable_weight = this.ktoUndesirableWeight;
+    }
+
+    return config;
+  }
+
+  /** Compile the generated script and import its modules in the venv, without training. */
+  async validateScript() {
+    this.validating = true;
+    this.validation = null;
+    this.error = null;
     try {
-      this.result = await invoke<TrainingResult>("training_run", { config });
+      this.validation = await invoke<ScriptValidation>("training_validate_script", {
+        config: this.buildConfig(),
+      });
     } catch (e) {
-      const msg = String(e);
-      if (!msg.includes("cancelled")) {
-        this.error = msg;
-      }
+      this.error = String(e);
     } finally {
-      this.training = false;
+      this.validating = false;
     }
   }
 
@@ -557,6 +851,17 @@ class TrainingStore {
     this.layersToDuplicate = this.layersToDuplicate.filter((_, i) => i !== index);
   }
 
+  /** Fold layer `index + 1` into `index` with an even blend. */
+  addInterpolation(index: number) {
+    if (index + 1 >= this.modelLayers) return;
+    if (this.layersToInterpolate.some((p) => p.a === index || p.b === index || p.a === index + 1 || p.b === index + 1)) return;
+    this.layersToInterpolate = [...this.layersToInterpolate, { a: index, b: index + 1, weight: 0.5 }];
+  }
+
+  removeInterpolation(index: number) {
+    this.layersToInterpolate = this.layersToInterpolate.filter((_, i) => i !== index);
+  }
+
   async runSurgery() {
     if (!this.surgeryUnlisten) {
       this.surgeryUnlisten = await listen<any>(
@@ -575,6 +880,14 @@ class TrainingStore {
     for (const idx of this.layersToRemove.sort((a, b) => a - b)) {
       operations.push({ remove_layer: { index: idx } });
     }
+    for (const pair of this.layersToInterpolate) {
+      operations.push({ interpolate_layers: { a: pair.a, b: pair.b, weight: pair.weight } });
+    }
+    if (this.depthUpscale) {
+      operations.push({
+        depth_upscale: { keep_bottom: this.depthUpscale.keepBottom, keep_top: this.depthUpscale.keepTop },
+      });
+    }
     for (const dup of this.layersToDuplicate) {
       operations.push({ duplicate_layer: { source_index: dup.source, insert_at: dup.insertAt } });
     }
@@ -611,19 +924,28 @@ class TrainingStore {
     this.result = null;
     this.surgeryResult = null;
     this.lossHistory = [];
+    this.evalHistory = [];
     this.layersToRemove = [];
     this.layersToDuplicate = [];
+    this.depthUpscale = null;
+    this.layersToInterpolate = [];
   }
 
   destroy() {
     this.setupUnlisten?.();
     this.setupLogUnlisten?.();
     this.progressUnlisten?.();
+    this.evalUnlisten?.();
+    this.logUnlisten?.();
     this.surgeryUnlisten?.();
+    this.gpuStatsUnlisten?.();
     this.setupUnlisten = null;
     this.setupLogUnlisten = null;
     this.progressUnlisten = null;
+    this.evalUnlisten = null;
+    this.logUnlisten = null;
     this.surgeryUnlisten = null;
+    this.gpuStatsUnlisten = null;
   }
 }
 